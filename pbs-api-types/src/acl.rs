@@ -70,6 +70,13 @@ constnamedbitmap! {
 
         /// Realm.Allocate allows viewing, creating, modifying and deleting realms
         PRIV_REALM_ALLOCATE("Realm.Allocate");
+
+        /// Remote.DatastoreBackup allows creating new snapshots on a configured `Remote`
+        /// by pushing them from a local datastore
+        PRIV_REMOTE_DATASTORE_BACKUP("Remote.DatastoreBackup");
+        /// Remote.DatastorePrune allows deleting snapshots, groups and namespaces on a
+        /// configured `Remote` that vanished from the local side of a push
+        PRIV_REMOTE_DATASTORE_PRUNE("Remote.DatastorePrune");
     }
 }
 
@@ -148,7 +155,9 @@ pub const ROLE_REMOTE_AUDIT: u64 = 0
 pub const ROLE_REMOTE_ADMIN: u64 = 0
     | PRIV_REMOTE_AUDIT
     | PRIV_REMOTE_MODIFY
-    | PRIV_REMOTE_READ;
+    | PRIV_REMOTE_READ
+    | PRIV_REMOTE_DATASTORE_BACKUP
+    | PRIV_REMOTE_DATASTORE_PRUNE;
 
 #[rustfmt::skip]
 #[allow(clippy::identity_op)]
@@ -157,6 +166,14 @@ pub const ROLE_REMOTE_SYNC_OPERATOR: u64 = 0
     | PRIV_REMOTE_AUDIT
     | PRIV_REMOTE_READ;
 
+#[rustfmt::skip]
+#[allow(clippy::identity_op)]
+/// Remote.SyncPushOperator can push snapshots to the remote and prune vanished ones.
+pub const ROLE_REMOTE_SYNC_PUSH_OPERATOR: u64 = 0
+    | PRIV_REMOTE_AUDIT
+    | PRIV_REMOTE_DATASTORE_BACKUP
+    | PRIV_REMOTE_DATASTORE_PRUNE;
+
 #[rustfmt::skip]
 #[allow(clippy::identity_op)]
 /// Tape.Audit can audit the tape backup configuration and media content
@@ -222,6 +239,8 @@ pub enum Role {
     RemoteAdmin = ROLE_REMOTE_ADMIN,
     /// Syncronisation Opertator
     RemoteSyncOperator = ROLE_REMOTE_SYNC_OPERATOR,
+    /// Push Syncronisation Opertator
+    RemoteSyncPushOperator = ROLE_REMOTE_SYNC_PUSH_OPERATOR,
     /// Tape Auditor
     TapeAudit = ROLE_TAPE_AUDIT,
     /// Tape Administrator
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use proxmox_schema::api;
+
+use crate::Authid;
+
+#[api]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Result of an audited API call.
+pub enum AuditResult {
+    /// The call succeeded.
+    Ok,
+    /// The call failed.
+    Error,
+}
+
+#[api(
+    properties: {
+        "auth-id": {
+            type: Authid,
+            optional: true,
+        },
+        result: {
+            type: AuditResult,
+        },
+    },
+)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Audit log entry of a privileged API call.
+pub struct AuditLogEntry {
+    /// Time of the call (UNIX epoch).
+    pub time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_id: Option<Authid>,
+    /// IP address of the client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    /// HTTP method of the call.
+    pub method: String,
+    /// API path of the call.
+    pub path: String,
+    /// Parameters as JSON object, with secrets redacted and long values shortened.
+    pub parameters: String,
+    /// SHA-256 digest of the parameters, with secrets redacted.
+    pub digest: String,
+    pub result: AuditResult,
+    /// Error message of a failed call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
@@ -4,6 +4,7 @@ use std::path::PathBuf;
 use anyhow::{bail, format_err, Error};
 use serde::{Deserialize, Serialize};
 
+use proxmox_human_byte::HumanByte;
 use proxmox_schema::{
     api, const_regex, ApiStringFormat, ApiType, ArraySchema, EnumEntry, IntegerSchema, ReturnType,
     Schema, StringSchema, Updater, UpdaterType,
@@ -11,8 +12,8 @@ use proxmox_schema::{
 
 use crate::{
     Authid, CryptMode, Fingerprint, MaintenanceMode, Userid, DATASTORE_NOTIFY_STRING_SCHEMA,
-    GC_SCHEDULE_SCHEMA, PROXMOX_SAFE_ID_FORMAT, PRUNE_SCHEDULE_SCHEMA, SHA256_HEX_REGEX,
-    SINGLE_LINE_COMMENT_SCHEMA, UPID,
+    GC_SCHEDULE_SCHEMA, PROXMOX_SAFE_ID_FORMAT, PRUNE_SCHEDULE_SCHEMA, S3_ENDPOINT_ID_SCHEMA,
+    SHA256_HEX_REGEX, SINGLE_LINE_COMMENT_SCHEMA, UPID, UUID_FORMAT,
 };
 
 const_regex! {
@@ -35,6 +36,8 @@ const_regex! {
     pub GROUP_OR_SNAPSHOT_PATH_REGEX = concat!(r"^", GROUP_OR_SNAPSHOT_PATH_REGEX_STR!(), r"$");
 
     pub DATASTORE_MAP_REGEX = concat!(r"^(?:", PROXMOX_SAFE_ID_REGEX_STR!(), r"=)?", PROXMOX_SAFE_ID_REGEX_STR!(), r"$");
+
+    pub S3_BUCKET_NAME_REGEX = r"^[a-z0-9](?:[a-z0-9.\-]*[a-z0-9])?$";
 }
 
 pub const CHUNK_DIGEST_FORMAT: ApiStringFormat = ApiStringFormat::Pattern(&SHA256_HEX_REGEX);
@@ -223,6 +226,97 @@ pub const DATASTORE_TUNING_STRING_SCHEMA: Schema = StringSchema::new("Datastore
     ))
     .schema();
 
+#[api]
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// Where the datastore keeps its chunks, indexes and manifests.
+pub enum DatastoreBackendType {
+    /// Local filesystem, the datastore path holds all data
+    #[default]
+    Filesystem,
+    /// S3 compatible object store, the datastore path is used as local cache
+    S3,
+}
+serde_plain::derive_display_from_serialize!(DatastoreBackendType);
+serde_plain::derive_fromstr_from_deserialize!(DatastoreBackendType);
+
+pub const S3_BUCKET_NAME_SCHEMA: Schema = StringSchema::new("Name of the S3 bucket.")
+    .format(&ApiStringFormat::Pattern(&S3_BUCKET_NAME_REGEX))
+    .min_length(3)
+    .max_length(63)
+    .schema();
+
+#[api(
+    properties: {
+        "type": {
+            type: DatastoreBackendType,
+            optional: true,
+        },
+        endpoint: {
+            schema: S3_ENDPOINT_ID_SCHEMA,
+            optional: true,
+        },
+        bucket: {
+            schema: S3_BUCKET_NAME_SCHEMA,
+            optional: true,
+        },
+        "cache-size": {
+            type: HumanByte,
+            optional: true,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+/// Datastore backend options
+pub struct DatastoreBackendConfig {
+    /// Type of the backend, defaults to the local filesystem
+    #[serde(rename = "type")]
+    pub ty: Option<DatastoreBackendType>,
+    /// S3 endpoint to use, required for the s3 backend
+    pub endpoint: Option<String>,
+    /// Bucket to store the datastore contents in, required for the s3 backend
+    pub bucket: Option<String>,
+    /// Maximal size of the chunk contents kept in the local cache of the s3 backend
+    pub cache_size: Option<HumanByte>,
+}
+
+impl DatastoreBackendConfig {
+    /// Check that all options required by the backend type are set.
+    pub fn check(&self) -> Result<(), Error> {
+        match self.ty.unwrap_or_default() {
+            DatastoreBackendType::Filesystem => {
+                if self.endpoint.is_some() || self.bucket.is_some() || self.cache_size.is_some() {
+                    bail!(
+                        "'endpoint', 'bucket' and 'cache-size' are only valid for the s3 backend"
+                    );
+                }
+            }
+            DatastoreBackendType::S3 => {
+                if self.endpoint.is_none() {
+                    bail!("s3 backend requires an 'endpoint'");
+                }
+                if self.bucket.is_none() {
+                    bail!("s3 backend requires a 'bucket'");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub const DATASTORE_BACKEND_STRING_SCHEMA: Schema = StringSchema::new("Datastore backend options")
+    .format(&ApiStringFormat::PropertyString(
+        &DatastoreBackendConfig::API_SCHEMA,
+    ))
+    .schema();
+
+pub const BACKING_DEVICE_SCHEMA: Schema = StringSchema::new(
+    "Filesystem UUID of the removable device backing the datastore, mounted on 'path'.",
+)
+.format(&UUID_FORMAT)
+.schema();
+
 #[api(
     properties: {
         name: {
@@ -268,6 +362,14 @@ pub const DATASTORE_TUNING_STRING_SCHEMA: Schema = StringSchema::new("Datastore
             format: &ApiStringFormat::PropertyString(&MaintenanceMode::API_SCHEMA),
             type: String,
         },
+        backend: {
+            optional: true,
+            schema: DATASTORE_BACKEND_STRING_SCHEMA,
+        },
+        "backing-device": {
+            optional: true,
+            schema: BACKING_DEVICE_SCHEMA,
+        },
     }
 )]
 #[derive(Serialize, Deserialize, Updater, Clone, PartialEq)]
@@ -308,9 +410,19 @@ pub struct DataStoreConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tuning: Option<String>,
 
-    /// Maintenance mode, type is either 'offline' or 'read-only', message should be enclosed in "
+    /// Maintenance mode, type is either 'offline', 'read-only' or 'delete-only', message should be enclosed in "
     #[serde(skip_serializing_if = "Option::is_none")]
     pub maintenance_mode: Option<String>,
+
+    /// Datastore backend, the local filesystem if not set
+    #[updater(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+
+    /// Filesystem UUID of the device backing a removable datastore
+    #[updater(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backing_device: Option<String>,
 }
 
 impl DataStoreConfig {
@@ -327,9 +439,25 @@ impl DataStoreConfig {
             notify: None,
             tuning: None,
             maintenance_mode: None,
+            backend: None,
+            backing_device: None,
         }
     }
 
+    /// Returns true if the datastore lives on a removable device which gets mounted on demand.
+    pub fn is_removable(&self) -> bool {
+        self.backing_device.is_some()
+    }
+
+    /// Parse the backend property string, defaults to the local filesystem backend.
+    pub fn backend_config(&self) -> Result<DatastoreBackendConfig, Error> {
+        let value = DatastoreBackendConfig::API_SCHEMA
+            .parse_property_string(self.backend.as_deref().unwrap_or(""))?;
+        let config = DatastoreBackendConfig::deserialize(value)?;
+        config.check()?;
+        Ok(config)
+    }
+
     pub fn get_maintenance_mode(&self) -> Option<MaintenanceMode> {
         self.maintenance_mode
             .as_ref()
@@ -400,6 +528,19 @@ pub enum VerifyState {
     Failed,
 }
 
+#[api()]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Chunk coverage of a rolling verification.
+pub struct VerifyCoverage {
+    /// Number of chunks (of all archives) checked in this run.
+    pub verified_chunks: u64,
+    /// Number of chunks referenced by the snapshot.
+    pub total_chunks: u64,
+    /// Period (in days) in which all chunks get verified.
+    pub period: u64,
+}
+
 #[api(
     properties: {
         upid: {
@@ -408,6 +549,10 @@ pub enum VerifyState {
         state: {
             type: VerifyState,
         },
+        coverage: {
+            type: VerifyCoverage,
+            optional: true,
+        },
     },
 )]
 #[derive(Serialize, Deserialize)]
@@ -417,6 +562,9 @@ pub struct SnapshotVerifyState {
     pub upid: UPID,
     /// State of the verification. Enum.
     pub state: VerifyState,
+    /// Set if only part of the chunks were verified (rolling verification).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<VerifyCoverage>,
 }
 
 /// A namespace provides a logical separation between backup groups from different domains
@@ -1100,6 +1248,12 @@ pub struct SnapshotListItem {
     /// Protection from prunes
     #[serde(default)]
     pub protected: bool,
+    /// Bytes only referenced by this snapshot (from the last space usage scan)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclusive_size: Option<u64>,
+    /// Bytes also referenced by other snapshots (from the last space usage scan)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared_size: Option<u64>,
 }
 
 #[api(
@@ -1138,6 +1292,12 @@ pub struct GroupListItem {
     /// The first line from group "notes"
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
+    /// Bytes only referenced by this group (from the last space usage scan)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclusive_size: Option<u64>,
+    /// Bytes also referenced by other groups (from the last space usage scan)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared_size: Option<u64>,
 }
 
 #[api()]
@@ -9,8 +9,8 @@ use proxmox_schema::*;
 use crate::{
     Authid, BackupNamespace, BackupType, RateLimitConfig, Userid, BACKUP_GROUP_SCHEMA,
     BACKUP_NAMESPACE_SCHEMA, DATASTORE_SCHEMA, DRIVE_NAME_SCHEMA, MEDIA_POOL_NAME_SCHEMA,
-    NS_MAX_DEPTH_REDUCED_SCHEMA, PROXMOX_SAFE_ID_FORMAT, REMOTE_ID_SCHEMA,
-    SINGLE_LINE_COMMENT_SCHEMA,
+    MEDIA_RETENTION_POLICY_SCHEMA, MEDIA_SET_ALLOCATION_POLICY_SCHEMA, NS_MAX_DEPTH_REDUCED_SCHEMA,
+    PROXMOX_SAFE_ID_FORMAT, REMOTE_ID_SCHEMA, SINGLE_LINE_COMMENT_SCHEMA,
 };
 
 const_regex! {
@@ -18,7 +18,9 @@ const_regex! {
     /// Regex for verification jobs 'DATASTORE:ACTUAL_JOB_ID'
     pub VERIFICATION_JOB_WORKER_ID_REGEX = concat!(r"^(", PROXMOX_SAFE_ID_REGEX_STR!(), r"):");
     /// Regex for sync jobs 'REMOTE:REMOTE_DATASTORE:LOCAL_DATASTORE:(?:LOCAL_NS_ANCHOR:)ACTUAL_JOB_ID'
-    pub SYNC_JOB_WORKER_ID_REGEX = concat!(r"^(", PROXMOX_SAFE_ID_REGEX_STR!(), r"):(", PROXMOX_SAFE_ID_REGEX_STR!(), r"):(", PROXMOX_SAFE_ID_REGEX_STR!(), r")(?::(", BACKUP_NS_RE!(), r"))?:");
+    ///
+    /// REMOTE is '-' for jobs pulling from a local datastore.
+    pub SYNC_JOB_WORKER_ID_REGEX = concat!(r"^(", PROXMOX_SAFE_ID_REGEX_STR!(), r"|-):(", PROXMOX_SAFE_ID_REGEX_STR!(), r"):(", PROXMOX_SAFE_ID_REGEX_STR!(), r")(?::(", BACKUP_NS_RE!(), r"))?:");
 }
 
 pub const JOB_ID_SCHEMA: Schema = StringSchema::new("Job ID.")
@@ -165,6 +167,14 @@ pub const VERIFICATION_OUTDATED_AFTER_SCHEMA: Schema =
         .minimum(0)
         .schema();
 
+pub const VERIFICATION_SAMPLE_PERIOD_SCHEMA: Schema = IntegerSchema::new(
+    "Only verify a rolling share of the chunks on each run, so that every chunk gets verified \
+    within this many days.",
+)
+.minimum(1)
+.maximum(3650)
+.schema();
+
 #[api(
     properties: {
         id: {
@@ -197,6 +207,30 @@ pub const VERIFICATION_OUTDATED_AFTER_SCHEMA: Schema =
             optional: true,
             schema: crate::NS_MAX_DEPTH_SCHEMA,
         },
+        "sample-period": {
+            optional: true,
+            schema: VERIFICATION_SAMPLE_PERIOD_SCHEMA,
+        },
+        "repair-remote": {
+            optional: true,
+            schema: REMOTE_ID_SCHEMA,
+            description: "Remote to fetch healthy copies of corrupt chunks from.",
+        },
+        "repair-remote-store": {
+            optional: true,
+            schema: DATASTORE_SCHEMA,
+            description: "Datastore on the repair remote.",
+        },
+        "repair-remote-ns": {
+            optional: true,
+            schema: BACKUP_NAMESPACE_SCHEMA,
+            description: "Namespace on the repair remote corresponding to the job's namespace.",
+        },
+        "repair-drive": {
+            optional: true,
+            schema: DRIVE_NAME_SCHEMA,
+            description: "Tape drive used to restore corrupt chunks from tape.",
+        },
     }
 )]
 #[derive(Serialize, Deserialize, Updater)]
@@ -227,6 +261,20 @@ pub struct VerificationJobConfig {
     /// how deep the verify should go from the `ns` level downwards. Passing 0 verifies only the
     /// snapshots on the same level as the passed `ns`, or the datastore root if none.
     pub max_depth: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// verify a rolling share of the chunks per run, covering all chunks within X days. All
+    /// snapshots are visited in this mode, 'ignore_verified' only applies to full runs.
+    pub sample_period: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// remote to fetch corrupt chunks from, by downloading them from the same snapshot
+    pub repair_remote: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repair_remote_store: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub repair_remote_ns: Option<BackupNamespace>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// drive to restore corrupt chunks from tape with, using the media catalogs to find them
+    pub repair_drive: Option<String>,
 }
 
 impl VerificationJobConfig {
@@ -300,6 +348,21 @@ pub struct VerificationJobStatus {
             schema: crate::NS_MAX_DEPTH_SCHEMA,
             optional: true,
         },
+        allocation: {
+            schema: MEDIA_SET_ALLOCATION_POLICY_SCHEMA,
+            optional: true,
+        },
+        retention: {
+            schema: MEDIA_RETENTION_POLICY_SCHEMA,
+            optional: true,
+        },
+        incremental: {
+            description: "Only backup snapshots which are not on any media set of the pool \
+                          yet (which is not expired), instead of only checking the current \
+                          media set.",
+            type: bool,
+            optional: true,
+        },
     }
 )]
 #[derive(Serialize, Deserialize, Clone, Updater)]
@@ -324,6 +387,14 @@ pub struct TapeBackupJobSetup {
     pub ns: Option<BackupNamespace>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub max_depth: Option<usize>,
+    /// Media set allocation policy, overrides the one of the media pool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocation: Option<String>,
+    /// Retention policy for media written by this job, overrides the one of the media pool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incremental: Option<bool>,
 }
 
 #[api(
@@ -449,6 +520,28 @@ pub const TRANSFER_LAST_SCHEMA: Schema =
         .minimum(1)
         .schema();
 
+pub const SYNC_ENCRYPTION_KEY_SCHEMA: Schema = StringSchema::new(
+    "Name of a server-side encryption key used to encrypt plaintext snapshots when pulling them.",
+)
+.format(&PROXMOX_SAFE_ID_FORMAT)
+.min_length(2)
+.max_length(32)
+.schema();
+
+#[api()]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Direction of a sync job
+pub enum SyncDirection {
+    /// Pull snapshots from the remote into the local datastore
+    #[default]
+    Pull,
+    /// Push snapshots from the local datastore to the remote
+    Push,
+}
+
+serde_plain::derive_display_from_serialize!(SyncDirection);
+
 #[api(
     properties: {
         id: {
@@ -467,6 +560,7 @@ pub const TRANSFER_LAST_SCHEMA: Schema =
         },
         remote: {
             schema: REMOTE_ID_SCHEMA,
+            optional: true,
         },
         "remote-store": {
             schema: DATASTORE_SCHEMA,
@@ -502,11 +596,24 @@ pub const TRANSFER_LAST_SCHEMA: Schema =
             schema: TRANSFER_LAST_SCHEMA,
             optional: true,
         },
+        "sync-direction": {
+            type: SyncDirection,
+            optional: true,
+        },
+        "encryption-key": {
+            schema: SYNC_ENCRYPTION_KEY_SCHEMA,
+            optional: true,
+        },
     }
 )]
 #[derive(Serialize, Deserialize, Clone, Updater, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 /// Sync Job
+///
+/// For pull jobs `store`/`ns` is the local target and `remote-store`/`remote-ns` the source, for
+/// push jobs the local datastore is the source and the remote one the target.
+///
+/// Pull jobs without `remote` sync from the datastore `remote-store` on this host.
 pub struct SyncJobConfig {
     #[updater(skip)]
     pub id: String,
@@ -515,7 +622,8 @@ pub struct SyncJobConfig {
     pub ns: Option<BackupNamespace>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub owner: Option<Authid>,
-    pub remote: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
     pub remote_store: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub remote_ns: Option<BackupNamespace>,
@@ -533,6 +641,10 @@ pub struct SyncJobConfig {
     pub limit: RateLimitConfig,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transfer_last: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync_direction: Option<SyncDirection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption_key: Option<String>,
 }
 
 impl SyncJobConfig {
@@ -542,6 +654,18 @@ impl SyncJobConfig {
             None => vec!["datastore", &self.store],
         }
     }
+
+    pub fn sync_direction(&self) -> SyncDirection {
+        self.sync_direction.unwrap_or_default()
+    }
+
+    /// ACL path of the source datastore/namespace of a local pull job.
+    pub fn local_source_acl_path(&self) -> Vec<&str> {
+        match self.remote_ns.as_ref() {
+            Some(ns) => ns.acl_path(&self.remote_store),
+            None => vec!["datastore", &self.remote_store],
+        }
+    }
 }
 
 #[api(
@@ -69,6 +69,9 @@ macro_rules! GROUP_OR_SNAPSHOT_PATH_REGEX_STR {
 mod acl;
 pub use acl::*;
 
+mod audit;
+pub use audit::*;
+
 mod datastore;
 pub use datastore::*;
 
@@ -87,6 +90,9 @@ pub use network::*;
 mod node;
 pub use node::*;
 
+mod notifications;
+pub use notifications::*;
+
 pub use proxmox_auth_api::types as userid;
 pub use proxmox_auth_api::types::{Authid, Userid};
 pub use proxmox_auth_api::types::{Realm, RealmRef};
@@ -113,9 +119,15 @@ pub use openid::*;
 mod ldap;
 pub use ldap::*;
 
+mod quota;
+pub use quota::*;
+
 mod remote;
 pub use remote::*;
 
+mod s3;
+pub use s3::*;
+
 mod tape;
 pub use tape::*;
 
@@ -29,7 +29,8 @@ pub enum Operation {
     ///
     /// NOTE: one must *not* do any IO operations when only helding this Op state
     Lookup,
-    // GarbageCollect or Delete?
+    /// for any operation which only removes data, like prune, forget or GC
+    Delete,
 }
 
 #[api]
@@ -37,17 +38,17 @@ pub enum Operation {
 #[serde(rename_all = "kebab-case")]
 /// Maintenance type.
 pub enum MaintenanceType {
-    // TODO:
-    //  - Add "unmounting" once we got pluggable datastores
-    //  - Add "GarbageCollection" or "DeleteOnly" as type and track GC (or all deletes) as separate
-    //    operation, so that one can enable a mode where nothing new can be added but stuff can be
-    //    cleaned
     /// Only read operations are allowed on the datastore.
     ReadOnly,
     /// Neither read nor write operations are allowed on the datastore.
     Offline,
     /// The datastore is being deleted.
     Delete,
+    /// The removable datastore is being unmounted.
+    Unmounting,
+    /// Only read and delete operations (prune, forget, GC) are allowed on the datastore, so that
+    /// space can be freed up without anything new getting added.
+    DeleteOnly,
 }
 serde_plain::derive_display_from_serialize!(MaintenanceType);
 serde_plain::derive_fromstr_from_deserialize!(MaintenanceType);
@@ -67,7 +68,7 @@ serde_plain::derive_fromstr_from_deserialize!(MaintenanceType);
 #[derive(Deserialize, Serialize)]
 /// Maintenance mode
 pub struct MaintenanceMode {
-    /// Type of maintenance ("read-only" or "offline").
+    /// Type of maintenance ("read-only", "delete-only" or "offline").
     #[serde(rename = "type")]
     ty: MaintenanceType,
 
@@ -77,6 +78,11 @@ pub struct MaintenanceMode {
 }
 
 impl MaintenanceMode {
+    /// The datastore is about to be unmounted and must not be kept open.
+    pub fn is_unmounting(&self) -> bool {
+        self.ty == MaintenanceType::Unmounting
+    }
+
     pub fn check(&self, operation: Option<Operation>) -> Result<(), Error> {
         if self.ty == MaintenanceType::Delete {
             bail!("datastore is being deleted");
@@ -88,12 +94,18 @@ impl MaintenanceMode {
 
         if let Some(Operation::Lookup) = operation {
             return Ok(());
+        } else if self.ty == MaintenanceType::Unmounting {
+            bail!("datastore is being unmounted");
         } else if self.ty == MaintenanceType::Offline {
             bail!("offline maintenance mode: {}", message);
         } else if self.ty == MaintenanceType::ReadOnly {
-            if let Some(Operation::Write) = operation {
+            if let Some(Operation::Write | Operation::Delete) = operation {
                 bail!("read-only maintenance mode: {}", message);
             }
+        } else if self.ty == MaintenanceType::DeleteOnly {
+            if let Some(Operation::Write) = operation {
+                bail!("delete-only maintenance mode: {}", message);
+            }
         }
         Ok(())
     }
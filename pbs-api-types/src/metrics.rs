@@ -3,7 +3,16 @@ use serde::{Deserialize, Serialize};
 use crate::{
     HOST_PORT_SCHEMA, HTTP_URL_SCHEMA, PROXMOX_SAFE_ID_FORMAT, SINGLE_LINE_COMMENT_SCHEMA,
 };
-use proxmox_schema::{api, Schema, StringSchema, Updater};
+use proxmox_schema::{api, const_regex, ApiStringFormat, Schema, StringSchema, Updater};
+
+const_regex! {
+    pub OTLP_HEADER_REGEX = r"^[A-Za-z0-9-]+: .+$";
+    pub OTLP_RESOURCE_ATTRIBUTE_REGEX = r"^[A-Za-z0-9_.-]+=.+$";
+}
+
+pub const OTLP_HEADER_FORMAT: ApiStringFormat = ApiStringFormat::Pattern(&OTLP_HEADER_REGEX);
+pub const OTLP_RESOURCE_ATTRIBUTE_FORMAT: ApiStringFormat =
+    ApiStringFormat::Pattern(&OTLP_RESOURCE_ATTRIBUTE_REGEX);
 
 pub const METRIC_SERVER_ID_SCHEMA: Schema = StringSchema::new("Metrics Server ID.")
     .format(&PROXMOX_SAFE_ID_FORMAT)
@@ -25,6 +34,18 @@ pub const INFLUXDB_ORGANIZATION_SCHEMA: Schema = StringSchema::new("InfluxDB Org
     .default("proxmox")
     .schema();
 
+pub const OTLP_HEADER_SCHEMA: Schema =
+    StringSchema::new("HTTP header sent with each request ('<name>: <value>').")
+        .format(&OTLP_HEADER_FORMAT)
+        .max_length(1024)
+        .schema();
+
+pub const OTLP_RESOURCE_ATTRIBUTE_SCHEMA: Schema =
+    StringSchema::new("OpenTelemetry resource attribute ('<key>=<value>').")
+        .format(&OTLP_RESOURCE_ATTRIBUTE_FORMAT)
+        .max_length(256)
+        .schema();
+
 fn return_true() -> bool {
     true
 }
@@ -147,6 +168,66 @@ pub struct InfluxDbHttp {
     pub comment: Option<String>,
 }
 
+#[api(
+    properties: {
+        name: {
+            schema: METRIC_SERVER_ID_SCHEMA,
+        },
+        enable: {
+            type: bool,
+            optional: true,
+            default: true,
+        },
+        url: {
+            schema: HTTP_URL_SCHEMA,
+        },
+        header: {
+            type: Array,
+            optional: true,
+            items: {
+                schema: OTLP_HEADER_SCHEMA,
+            },
+        },
+        "resource-attribute": {
+            type: Array,
+            optional: true,
+            items: {
+                schema: OTLP_RESOURCE_ATTRIBUTE_SCHEMA,
+            },
+        },
+        "verify-tls": {
+            type: bool,
+            optional: true,
+            default: true,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize, Updater)]
+#[serde(rename_all = "kebab-case")]
+/// OpenTelemetry collector (OTLP/HTTP with JSON encoding)
+pub struct OtlpHttp {
+    #[updater(skip)]
+    pub name: String,
+    #[serde(default = "return_true", skip_serializing_if = "is_true")]
+    #[updater(serde(skip_serializing_if = "Option::is_none"))]
+    /// Enables or disables the metrics server
+    pub enable: bool,
+    /// The URL of the collector, '/v1/metrics' is used if it contains no path
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_attribute: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// If true, the certificate will be validated.
+    pub verify_tls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
 
 #[api]
 #[derive(Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -158,6 +239,9 @@ pub enum MetricServerType {
     /// InfluxDB UDP
     #[serde(rename = "influxdb-udp")]
     InfluxDbUdp,
+    /// OpenTelemetry OTLP/HTTP
+    #[serde(rename = "otlp-http")]
+    OtlpHttp,
 }
 
 #[api(
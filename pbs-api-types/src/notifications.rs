@@ -0,0 +1,492 @@
+use serde::{Deserialize, Serialize};
+
+use proxmox_schema::{api, const_regex, ApiStringFormat, Schema, StringSchema, Updater};
+
+use crate::{
+    Userid, DNS_NAME_OR_IP_SCHEMA, EMAIL_SCHEMA, HTTP_URL_SCHEMA, PROXMOX_SAFE_ID_FORMAT,
+    SINGLE_LINE_COMMENT_SCHEMA,
+};
+
+const_regex! {
+    pub NOTIFICATION_MATCH_FIELD_REGEX = r"^(?:exact|regex):[a-z0-9_-]+=.+$";
+    pub NOTIFICATION_HTTP_HEADER_REGEX = r"^[A-Za-z0-9-]+: .+$";
+    pub NOTIFICATION_SECRET_REGEX = r"^[a-z0-9_-]+=.+$";
+}
+
+pub const NOTIFICATION_MATCH_FIELD_FORMAT: ApiStringFormat =
+    ApiStringFormat::Pattern(&NOTIFICATION_MATCH_FIELD_REGEX);
+pub const NOTIFICATION_HTTP_HEADER_FORMAT: ApiStringFormat =
+    ApiStringFormat::Pattern(&NOTIFICATION_HTTP_HEADER_REGEX);
+pub const NOTIFICATION_SECRET_FORMAT: ApiStringFormat =
+    ApiStringFormat::Pattern(&NOTIFICATION_SECRET_REGEX);
+
+pub const NOTIFICATION_ID_SCHEMA: Schema =
+    StringSchema::new("Name of a notification target or matcher.")
+        .format(&PROXMOX_SAFE_ID_FORMAT)
+        .min_length(2)
+        .max_length(32)
+        .schema();
+
+pub const NOTIFICATION_MATCH_FIELD_SCHEMA: Schema = StringSchema::new(
+    "Match a notification field, either 'exact:<field>=<value>' or 'regex:<field>=<regex>'.",
+)
+.format(&NOTIFICATION_MATCH_FIELD_FORMAT)
+.max_length(256)
+.schema();
+
+pub const NOTIFICATION_HTTP_HEADER_SCHEMA: Schema =
+    StringSchema::new("HTTP header ('<name>: <value>'), the value is a template.")
+        .format(&NOTIFICATION_HTTP_HEADER_FORMAT)
+        .max_length(1024)
+        .schema();
+
+pub const NOTIFICATION_SECRET_SCHEMA: Schema =
+    StringSchema::new("Secret ('<name>=<value>'), usable as '{{ secrets.<name> }}' in templates.")
+        .format(&NOTIFICATION_SECRET_FORMAT)
+        .max_length(1024)
+        .schema();
+
+#[api]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+/// Severity of a notification.
+pub enum NotificationSeverity {
+    /// General information
+    Info,
+    /// A noteworthy event
+    Notice,
+    /// A warning
+    Warning,
+    /// An error
+    Error,
+}
+
+#[api]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+/// Type of a notification target
+pub enum NotificationTargetType {
+    /// Local sendmail
+    #[serde(rename = "sendmail")]
+    Sendmail,
+    /// SMTP server
+    #[serde(rename = "smtp")]
+    Smtp,
+    /// Gotify server
+    #[serde(rename = "gotify")]
+    Gotify,
+    /// Generic webhook
+    #[serde(rename = "webhook")]
+    Webhook,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_ID_SCHEMA,
+        },
+        mailto: {
+            type: Array,
+            optional: true,
+            items: {
+                schema: EMAIL_SCHEMA,
+            },
+        },
+        "mailto-user": {
+            type: Array,
+            optional: true,
+            items: {
+                type: Userid,
+            },
+        },
+        "from-address": {
+            optional: true,
+            schema: EMAIL_SCHEMA,
+        },
+        author: {
+            type: String,
+            optional: true,
+            max_length: 128,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        disable: {
+            type: bool,
+            optional: true,
+            default: false,
+        },
+    },
+)]
+#[derive(Clone, Serialize, Deserialize, Updater)]
+#[serde(rename_all = "kebab-case")]
+/// Notification target sending mails with the local sendmail binary
+pub struct SendmailConfig {
+    #[updater(skip)]
+    pub name: String,
+    /// Mail recipients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mailto: Option<Vec<String>>,
+    /// Users whose email addresses are used as recipients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mailto_user: Option<Vec<Userid>>,
+    /// Sender address, defaults to the one in the node configuration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_address: Option<String>,
+    /// Author of the mail, defaults to 'Proxmox Backup Server - <nodename>'
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Disable this target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable: Option<bool>,
+}
+
+#[api]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// Connection security of an SMTP target
+pub enum SmtpMode {
+    /// Plain connection, without any encryption
+    Insecure,
+    /// Upgrade a plain connection with STARTTLS
+    Starttls,
+    /// Connect with TLS
+    #[default]
+    Tls,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_ID_SCHEMA,
+        },
+        server: {
+            schema: DNS_NAME_OR_IP_SCHEMA,
+        },
+        port: {
+            type: u16,
+            optional: true,
+            description: "Server port, defaults to 465 for 'tls', 587 for 'starttls' and 25 otherwise.",
+        },
+        mode: {
+            type: SmtpMode,
+            optional: true,
+        },
+        username: {
+            type: String,
+            optional: true,
+            max_length: 128,
+        },
+        password: {
+            type: String,
+            optional: true,
+            max_length: 256,
+        },
+        mailto: {
+            type: Array,
+            optional: true,
+            items: {
+                schema: EMAIL_SCHEMA,
+            },
+        },
+        "mailto-user": {
+            type: Array,
+            optional: true,
+            items: {
+                type: Userid,
+            },
+        },
+        "from-address": {
+            schema: EMAIL_SCHEMA,
+        },
+        author: {
+            type: String,
+            optional: true,
+            max_length: 128,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        disable: {
+            type: bool,
+            optional: true,
+            default: false,
+        },
+    },
+)]
+#[derive(Clone, Serialize, Deserialize, Updater)]
+#[serde(rename_all = "kebab-case")]
+/// Notification target sending mails via an SMTP server
+pub struct SmtpConfig {
+    #[updater(skip)]
+    pub name: String,
+    /// Host name or IP address of the SMTP server
+    pub server: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<SmtpMode>,
+    /// User name for authentication
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Password for authentication
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Mail recipients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mailto: Option<Vec<String>>,
+    /// Users whose email addresses are used as recipients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mailto_user: Option<Vec<Userid>>,
+    /// Sender address
+    pub from_address: String,
+    /// Author of the mail, defaults to 'Proxmox Backup Server - <nodename>'
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Disable this target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable: Option<bool>,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_ID_SCHEMA,
+        },
+        server: {
+            schema: HTTP_URL_SCHEMA,
+        },
+        token: {
+            type: String,
+            optional: true,
+            max_length: 256,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        disable: {
+            type: bool,
+            optional: true,
+            default: false,
+        },
+    },
+)]
+#[derive(Clone, Serialize, Deserialize, Updater)]
+#[serde(rename_all = "kebab-case")]
+/// Notification target sending messages to a Gotify server
+pub struct GotifyConfig {
+    #[updater(skip)]
+    pub name: String,
+    /// Base URL of the Gotify server
+    pub server: String,
+    /// Application token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Disable this target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable: Option<bool>,
+}
+
+#[api]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// HTTP method of a webhook
+pub enum WebhookMethod {
+    /// HTTP POST
+    #[default]
+    Post,
+    /// HTTP PUT
+    Put,
+    /// HTTP GET
+    Get,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_ID_SCHEMA,
+        },
+        method: {
+            type: WebhookMethod,
+            optional: true,
+        },
+        url: {
+            type: String,
+            max_length: 1024,
+        },
+        header: {
+            type: Array,
+            optional: true,
+            items: {
+                schema: NOTIFICATION_HTTP_HEADER_SCHEMA,
+            },
+        },
+        body: {
+            type: String,
+            optional: true,
+            max_length: 65536,
+        },
+        secret: {
+            type: Array,
+            optional: true,
+            items: {
+                schema: NOTIFICATION_SECRET_SCHEMA,
+            },
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        disable: {
+            type: bool,
+            optional: true,
+            default: false,
+        },
+    },
+)]
+#[derive(Clone, Serialize, Deserialize, Updater)]
+#[serde(rename_all = "kebab-case")]
+/// Notification target sending HTTP requests to a generic webhook
+pub struct WebhookConfig {
+    #[updater(skip)]
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<WebhookMethod>,
+    /// URL template
+    pub url: String,
+    /// Additional HTTP headers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<Vec<String>>,
+    /// Base64 encoded body template
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Secrets, only available to the templates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Disable this target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable: Option<bool>,
+}
+
+#[api]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+/// How the match rules of a matcher are combined
+pub enum MatchModeOperator {
+    /// All rules must match
+    #[default]
+    All,
+    /// At least one rule must match
+    Any,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_ID_SCHEMA,
+        },
+        "match-field": {
+            type: Array,
+            optional: true,
+            items: {
+                schema: NOTIFICATION_MATCH_FIELD_SCHEMA,
+            },
+        },
+        "match-severity": {
+            type: Array,
+            optional: true,
+            items: {
+                type: NotificationSeverity,
+            },
+        },
+        mode: {
+            type: MatchModeOperator,
+            optional: true,
+        },
+        "invert-match": {
+            type: bool,
+            optional: true,
+            default: false,
+        },
+        target: {
+            type: Array,
+            optional: true,
+            items: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        disable: {
+            type: bool,
+            optional: true,
+            default: false,
+        },
+    },
+)]
+#[derive(Clone, Serialize, Deserialize, Updater)]
+#[serde(rename_all = "kebab-case")]
+/// Routes matching notifications to targets
+pub struct MatcherConfig {
+    #[updater(skip)]
+    pub name: String,
+    /// Match rules for notification fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_field: Option<Vec<String>>,
+    /// Match rule for the severity, matches if the severity is one of the listed ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_severity: Option<Vec<NotificationSeverity>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<MatchModeOperator>,
+    /// Invert the result of the match rules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invert_match: Option<bool>,
+    /// Targets notified when the matcher matches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Disable this matcher
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable: Option<bool>,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_ID_SCHEMA,
+        },
+        "type": {
+            type: NotificationTargetType,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    },
+)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Basic information about a notification target
+pub struct NotificationTargetInfo {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: NotificationTargetType,
+    /// Disable this target
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
@@ -0,0 +1,109 @@
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+
+use proxmox_human_byte::HumanByte;
+use proxmox_schema::{api, Schema, StringSchema, Updater};
+
+use crate::{
+    Authid, BackupNamespace, DATASTORE_SCHEMA, PROXMOX_SAFE_ID_FORMAT, SINGLE_LINE_COMMENT_SCHEMA,
+};
+
+pub const QUOTA_ID_SCHEMA: Schema = StringSchema::new("Quota ID.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(3)
+    .max_length(32)
+    .schema();
+
+#[api(
+    properties: {
+        id: {
+            schema: QUOTA_ID_SCHEMA,
+        },
+        store: {
+            schema: DATASTORE_SCHEMA,
+        },
+        ns: {
+            type: BackupNamespace,
+            optional: true,
+        },
+        owner: {
+            type: Authid,
+            optional: true,
+        },
+        limit: {
+            type: HumanByte,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    },
+)]
+#[derive(Clone, Serialize, Deserialize, PartialEq, Updater)]
+#[serde(rename_all = "kebab-case")]
+/// Storage quota, limiting the deduplicated size of all backups in a namespace (including its
+/// sub-namespaces) or of all backup groups owned by an user or API token.
+pub struct QuotaConfig {
+    #[updater(skip)]
+    pub id: String,
+    /// The datastore the quota applies to.
+    #[updater(skip)]
+    pub store: String,
+    /// Limit the namespace and all of its sub-namespaces.
+    #[updater(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ns: Option<BackupNamespace>,
+    /// Limit all backup groups owned by this user (including its API tokens) or API token.
+    #[updater(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Authid>,
+    /// Maximal deduplicated size of the referenced chunks.
+    pub limit: HumanByte,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl QuotaConfig {
+    pub fn acl_path(&self) -> Vec<&str> {
+        match &self.ns {
+            Some(ns) => ns.acl_path(&self.store),
+            None => vec!["datastore", &self.store],
+        }
+    }
+
+    /// Check that the quota either limits a namespace or an owner.
+    pub fn check(&self) -> Result<(), Error> {
+        match (&self.ns, &self.owner) {
+            (Some(_), Some(_)) => bail!("a quota cannot limit both a namespace and an owner"),
+            (None, None) => bail!("a quota requires either a namespace or an owner"),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[api(
+    properties: {
+        ns: {
+            type: BackupNamespace,
+        },
+        quota: {
+            type: HumanByte,
+            optional: true,
+        },
+    },
+)]
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+/// Space usage of a namespace, including all of its sub-namespaces.
+pub struct NamespaceUsage {
+    pub ns: BackupNamespace,
+    /// Number of snapshots.
+    pub snapshot_count: u64,
+    /// Sum of the sizes of all archives (not deduplicated).
+    pub logical_size: u64,
+    /// Sum of the sizes of all unique chunks referenced by the archives.
+    pub unique_size: u64,
+    /// Limit of the quota configured for the namespace.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<HumanByte>,
+}
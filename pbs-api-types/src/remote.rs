@@ -4,19 +4,32 @@ use super::*;
 use proxmox_schema::*;
 
 pub const REMOTE_PASSWORD_SCHEMA: Schema =
-    StringSchema::new("Password or auth token for remote host.")
+    StringSchema::new("Password or API token secret for remote host.")
         .format(&PASSWORD_FORMAT)
         .min_length(1)
         .max_length(1024)
         .schema();
 
 pub const REMOTE_PASSWORD_BASE64_SCHEMA: Schema =
-    StringSchema::new("Password or auth token for remote host (stored as base64 string).")
+    StringSchema::new("Password or API token secret for remote host (stored as base64 string).")
         .format(&PASSWORD_FORMAT)
         .min_length(1)
         .max_length(1024)
         .schema();
 
+pub const REMOTE_CLIENT_CERT_SCHEMA: Schema = StringSchema::new(
+    "PEM encoded TLS client certificate used to log in to the remote host instead of a password.",
+)
+.min_length(1)
+.max_length(64 * 1024)
+.schema();
+
+pub const REMOTE_CLIENT_KEY_SCHEMA: Schema =
+    StringSchema::new("PEM encoded private key of the TLS client certificate.")
+        .min_length(1)
+        .max_length(64 * 1024)
+        .schema();
+
 pub const REMOTE_ID_SCHEMA: Schema = StringSchema::new("Remote ID.")
     .format(&PROXMOX_SAFE_ID_FORMAT)
     .min_length(3)
@@ -44,11 +57,19 @@ pub const REMOTE_ID_SCHEMA: Schema = StringSchema::new("Remote ID.")
             optional: true,
             schema: CERT_FINGERPRINT_SHA256_SCHEMA,
         },
+        "client-cert-fingerprint": {
+            optional: true,
+            schema: CERT_FINGERPRINT_SHA256_SCHEMA,
+            description: "Fingerprint of the TLS client certificate used to log in to the remote.",
+        },
     },
 )]
 #[derive(Serialize, Deserialize, Updater, Clone, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 /// Remote configuration properties.
+///
+/// Remotes authenticate with the password or API token secret stored alongside, or with a TLS
+/// client certificate, which is stored in a separate file and identified by its fingerprint.
 pub struct RemoteConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
@@ -58,6 +79,9 @@ pub struct RemoteConfig {
     pub auth_id: Authid,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fingerprint: Option<String>,
+    #[updater(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_cert_fingerprint: Option<String>,
 }
 
 #[api(
@@ -70,6 +94,7 @@ pub struct RemoteConfig {
         },
         password: {
             schema: REMOTE_PASSWORD_BASE64_SCHEMA,
+            optional: true,
         },
     },
 )]
@@ -78,8 +103,8 @@ pub struct RemoteConfig {
 /// Remote properties.
 pub struct Remote {
     pub name: String,
-    // Note: The stored password is base64 encoded
-    #[serde(skip_serializing_if = "String::is_empty")]
+    // Note: The stored password is base64 encoded, and empty for client certificate logins
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     #[serde(with = "proxmox_serde::string_as_base64")]
     pub password: String,
     #[serde(flatten)]
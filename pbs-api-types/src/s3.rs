@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+use super::*;
+use proxmox_schema::*;
+
+pub const S3_ENDPOINT_ID_SCHEMA: Schema = StringSchema::new("S3 endpoint ID.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(3)
+    .max_length(32)
+    .schema();
+
+pub const S3_ACCESS_KEY_SCHEMA: Schema = StringSchema::new("S3 access key ID.")
+    .format(&PASSWORD_FORMAT)
+    .min_length(1)
+    .max_length(128)
+    .schema();
+
+pub const S3_SECRET_KEY_SCHEMA: Schema = StringSchema::new("S3 secret access key.")
+    .format(&PASSWORD_FORMAT)
+    .min_length(1)
+    .max_length(256)
+    .schema();
+
+pub const S3_SECRET_KEY_BASE64_SCHEMA: Schema =
+    StringSchema::new("S3 secret access key (stored as base64 string).")
+        .format(&PASSWORD_FORMAT)
+        .min_length(1)
+        .max_length(512)
+        .schema();
+
+pub const S3_REGION_SCHEMA: Schema = StringSchema::new("S3 region used for request signing.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(1)
+    .max_length(64)
+    .schema();
+
+#[api(
+    properties: {
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        host: {
+            schema: DNS_NAME_OR_IP_SCHEMA,
+        },
+        port: {
+            optional: true,
+            description: "The (optional) port, defaults to 443 (or 80 with 'insecure-http')",
+            type: u16,
+        },
+        region: {
+            optional: true,
+            schema: S3_REGION_SCHEMA,
+        },
+        "access-key": {
+            schema: S3_ACCESS_KEY_SCHEMA,
+        },
+        "path-style": {
+            optional: true,
+            default: false,
+            description: "Address buckets as path component instead of as subdomain of the host.",
+            type: bool,
+        },
+        "insecure-http": {
+            optional: true,
+            default: false,
+            description: "Use plain HTTP instead of HTTPS, only intended for local test setups.",
+            type: bool,
+        },
+        fingerprint: {
+            optional: true,
+            schema: CERT_FINGERPRINT_SHA256_SCHEMA,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize, Updater, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+/// S3 endpoint configuration properties.
+pub struct S3EndpointConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    pub host: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    pub access_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_style: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insecure_http: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+#[api(
+    properties: {
+        id: {
+            schema: S3_ENDPOINT_ID_SCHEMA,
+        },
+        config: {
+            type: S3EndpointConfig,
+        },
+        "secret-key": {
+            schema: S3_SECRET_KEY_BASE64_SCHEMA,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+/// S3 endpoint properties.
+pub struct S3Endpoint {
+    pub id: String,
+    // Note: The stored secret key is base64 encoded
+    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(with = "proxmox_serde::string_as_base64")]
+    pub secret_key: String,
+    #[serde(flatten)]
+    pub config: S3EndpointConfig,
+}
+
+#[api(
+    properties: {
+        id: {
+            schema: S3_ENDPOINT_ID_SCHEMA,
+        },
+        config: {
+            type: S3EndpointConfig,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+/// S3 endpoint properties.
+pub struct S3EndpointWithoutSecret {
+    pub id: String,
+    #[serde(flatten)]
+    pub config: S3EndpointConfig,
+}
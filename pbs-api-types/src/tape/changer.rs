@@ -6,7 +6,7 @@ use proxmox_schema::{
     api, ApiStringFormat, ArraySchema, IntegerSchema, Schema, StringSchema, Updater,
 };
 
-use crate::{OptionalDeviceIdentification, PROXMOX_SAFE_ID_FORMAT};
+use crate::{OptionalDeviceIdentification, DRIVE_NAME_SCHEMA, PROXMOX_SAFE_ID_FORMAT};
 
 pub const CHANGER_NAME_SCHEMA: Schema = StringSchema::new("Tape Changer Identifier.")
     .format(&PROXMOX_SAFE_ID_FORMAT)
@@ -39,6 +39,14 @@ Import/Export, i.e. any media in those slots are considered to be
 .format(&ApiStringFormat::PropertyString(&SLOT_ARRAY_SCHEMA))
 .schema();
 
+pub const CHANGER_RECONCILE_SCHEDULE_SCHEMA: Schema =
+    StringSchema::new("Run inventory reconciliation at specified schedule.")
+        .format(&ApiStringFormat::VerifyFn(
+            proxmox_time::verify_calendar_event,
+        ))
+        .type_text("<calendar-event>")
+        .schema();
+
 #[api(
     properties: {
         name: {
@@ -51,6 +59,14 @@ Import/Export, i.e. any media in those slots are considered to be
             schema: EXPORT_SLOT_LIST_SCHEMA,
             optional: true,
         },
+        "reconcile-schedule": {
+            schema: CHANGER_RECONCILE_SCHEDULE_SCHEMA,
+            optional: true,
+        },
+        "reconcile-drive": {
+            schema: DRIVE_NAME_SCHEMA,
+            optional: true,
+        },
     },
 )]
 #[derive(Serialize, Deserialize, Updater)]
@@ -62,6 +78,11 @@ pub struct ScsiTapeChanger {
     pub path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub export_slots: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconcile_schedule: Option<String>,
+    /// Drive used to read the labels of unknown media during reconciliation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconcile_drive: Option<String>,
 }
 
 #[api(
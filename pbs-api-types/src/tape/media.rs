@@ -20,6 +20,10 @@ pub const MEDIA_UUID_SCHEMA: Schema = StringSchema::new("Media Uuid.")
         "media-set-uuid": {
             schema: MEDIA_SET_UUID_SCHEMA,
         },
+        "copy-of": {
+            schema: MEDIA_SET_UUID_SCHEMA,
+            optional: true,
+        },
     },
 )]
 #[derive(Serialize, Deserialize)]
@@ -33,6 +37,9 @@ pub struct MediaSetListEntry {
     pub media_set_ctime: i64,
     /// Media Pool
     pub pool: String,
+    /// Media set this media set is a copy of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copy_of: Option<Uuid>,
 }
 
 #[api(
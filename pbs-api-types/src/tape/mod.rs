@@ -27,7 +27,7 @@ use serde::{Deserialize, Serialize};
 use proxmox_schema::{api, const_regex, ApiStringFormat, Schema, StringSchema};
 use proxmox_uuid::Uuid;
 
-use crate::{BackupType, BACKUP_ID_SCHEMA, FINGERPRINT_SHA256_FORMAT};
+use crate::{BackupType, BACKUP_ID_SCHEMA, FINGERPRINT_SHA256_FORMAT, PROXMOX_SAFE_ID_FORMAT};
 
 const_regex! {
     pub TAPE_RESTORE_SNAPSHOT_REGEX = concat!(r"^", PROXMOX_SAFE_ID_REGEX_STR!(), r":(?:", BACKUP_NS_PATH_RE!(),")?", SNAPSHOT_PATH_REGEX_STR!(), r"$");
@@ -47,6 +47,44 @@ pub const TAPE_RESTORE_SNAPSHOT_SCHEMA: Schema =
         .type_text("store:[ns/namespace/...]type/id/time")
         .schema();
 
+#[api]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Output format for restoring single files or directories from tape
+pub enum TapeFileRestoreFormat {
+    /// A pxar archive (directories only)
+    Pxar,
+    /// A zip archive
+    #[default]
+    Zip,
+    /// A tar archive
+    Tar,
+}
+
+pub const TAPE_FILE_RESTORE_TARGET_SCHEMA: Schema =
+    StringSchema::new("Name of a single file restore result on the server.")
+        .format(&PROXMOX_SAFE_ID_FORMAT)
+        .max_length(255)
+        .schema();
+
+#[api(
+    properties: {
+        target: {
+            schema: TAPE_FILE_RESTORE_TARGET_SCHEMA,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// A single file restore result stored on the server
+pub struct TapeFileRestoreResult {
+    pub target: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Modification time (epoch)
+    pub mtime: i64,
+}
+
 #[api(
     properties: {
         pool: {
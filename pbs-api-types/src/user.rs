@@ -3,7 +3,10 @@ use serde::{Deserialize, Serialize};
 use proxmox_schema::{api, BooleanSchema, IntegerSchema, Schema, StringSchema, Updater};
 
 use super::userid::{Authid, Userid, PROXMOX_TOKEN_ID_SCHEMA};
-use super::{SINGLE_LINE_COMMENT_FORMAT, SINGLE_LINE_COMMENT_SCHEMA};
+use super::{
+    CERT_FINGERPRINT_SHA256_SCHEMA, PROXMOX_SAFE_ID_FORMAT, SINGLE_LINE_COMMENT_FORMAT,
+    SINGLE_LINE_COMMENT_SCHEMA,
+};
 
 pub const ENABLE_USER_SCHEMA: Schema = BooleanSchema::new(
     "Enable the account (default). You can set this to '0' to disable the account.",
@@ -224,3 +227,49 @@ impl User {
         true
     }
 }
+
+pub const CLIENT_CERT_ID_SCHEMA: Schema = StringSchema::new("Client certificate ID.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(3)
+    .max_length(32)
+    .schema();
+
+#[api(
+    properties: {
+        id: {
+            schema: CLIENT_CERT_ID_SCHEMA,
+        },
+        userid: {
+            type: Userid,
+        },
+        fingerprint: {
+            schema: CERT_FINGERPRINT_SHA256_SCHEMA,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+/// TLS client certificate which allows a user to log in without password.
+pub struct ClientCertificate {
+    pub id: String,
+    pub userid: Userid,
+    pub fingerprint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// Data signed with the private key of a client certificate to log in as `userid`.
+///
+/// `challenge` is the one-time challenge issued by the server, and `server_fingerprint` the
+/// fingerprint of the TLS certificate the server presented, so that a signature can neither be
+/// replayed nor relayed to another server.
+pub fn client_cert_login_data(
+    userid: &Userid,
+    challenge: &str,
+    server_fingerprint: &str,
+) -> String {
+    format!("PBS-CERT-LOGIN:{userid}:{challenge}:{server_fingerprint}")
+}
@@ -69,6 +69,9 @@ pub const API_ACCESS_LOG_FN: &str = concat!(PROXMOX_BACKUP_LOG_DIR_M!(), "/api/a
 /// creations. This file can be useful for fail2ban.
 pub const API_AUTH_LOG_FN: &str = concat!(PROXMOX_BACKUP_LOG_DIR_M!(), "/api/auth.log");
 
+/// append-only logfile recording privileged API actions, like ACL, user and datastore changes.
+pub const API_AUDIT_LOG_FN: &str = concat!(PROXMOX_BACKUP_LOG_DIR_M!(), "/api/audit.log");
+
 /// the PID filename for the unprivileged proxy daemon
 pub const PROXMOX_BACKUP_PROXY_PID_FN: &str = concat!(PROXMOX_BACKUP_RUN_DIR_M!(), "/proxy.pid");
 
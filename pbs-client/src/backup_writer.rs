@@ -24,6 +24,7 @@ use pbs_tools::crypt_config::CryptConfig;
 use proxmox_human_byte::HumanByte;
 
 use super::merge_known_chunks::{MergeKnownChunks, MergedChunkInfo};
+use super::ChunkOrReused;
 
 use super::{H2Client, HttpClient};
 
@@ -265,6 +266,20 @@ impl BackupWriter {
         archive_name: &str,
         stream: impl Stream<Item = Result<bytes::BytesMut, Error>>,
         options: UploadOptions,
+    ) -> Result<BackupStats, Error> {
+        self.upload_reused_chunk_stream(archive_name, stream.map_ok(ChunkOrReused::Chunk), options)
+            .await
+    }
+
+    /// Upload a stream of new chunks, interleaved with chunks reused from the previous snapshot.
+    ///
+    /// Reused chunks must be referenced by the previous index of `archive_name`, which gets
+    /// downloaded to make its chunks known to the server.
+    pub async fn upload_reused_chunk_stream(
+        &self,
+        archive_name: &str,
+        stream: impl Stream<Item = Result<ChunkOrReused, Error>>,
+        options: UploadOptions,
     ) -> Result<BackupStats, Error> {
         let known_chunks = Arc::new(Mutex::new(HashSet::new()));
 
@@ -412,6 +427,121 @@ impl BackupWriter {
         })
     }
 
+    /// Upload an index whose chunks are already encoded.
+    ///
+    /// In contrast to [Self::upload_stream], no chunking or encoding takes place, the chunks of
+    /// `stream` are sent as they are, e.g. when pushing an existing snapshot to another server.
+    /// Chunks yielded as [MergedChunkInfo::Known] must be known to the server, for example by
+    /// downloading the previous index first. `index_csum` and `index_size` must match those of
+    /// the index the chunks belong to.
+    pub async fn upload_index_chunk_info(
+        &self,
+        archive_name: &str,
+        stream: impl Stream<Item = Result<MergedChunkInfo, Error>>,
+        index_csum: [u8; 32],
+        index_size: u64,
+        options: UploadOptions,
+    ) -> Result<BackupStats, Error> {
+        let mut param = json!({ "archive-name": archive_name });
+        let prefix = if let Some(size) = options.fixed_size {
+            param["size"] = size.into();
+            "fixed"
+        } else {
+            "dynamic"
+        };
+
+        let index_path = format!("{}_index", prefix);
+        let upload_chunk_path = format!("{}_chunk", prefix);
+        let close_path = format!("{}_close", prefix);
+
+        let wid = self
+            .h2
+            .post(&index_path, Some(param))
+            .await?
+            .as_u64()
+            .unwrap();
+
+        let (upload_queue, upload_result) =
+            Self::append_chunk_queue(self.h2.clone(), wid, index_path);
+
+        let mut chunk_count = 0;
+        let mut chunk_reused = 0;
+
+        let stream = stream.merge_known_chunks();
+        futures::pin_mut!(stream);
+
+        let result: Result<(), Error> = async {
+            while let Some(merged_chunk_info) = stream.try_next().await? {
+                match merged_chunk_info {
+                    MergedChunkInfo::New(chunk_info) => {
+                        chunk_count += 1;
+                        let digest = chunk_info.digest;
+                        let chunk_data = chunk_info.chunk.into_inner();
+                        let param = json!({
+                            "wid": wid,
+                            "digest": hex::encode(digest),
+                            "size": chunk_info.chunk_len,
+                            "encoded-size": chunk_data.len(),
+                        });
+                        let request = H2Client::request_builder(
+                            "localhost",
+                            "POST",
+                            &upload_chunk_path,
+                            Some(param),
+                            Some("application/octet-stream"),
+                        )
+                        .unwrap();
+                        let response = self
+                            .h2
+                            .send_request(request, Some(bytes::Bytes::from(chunk_data)))
+                            .await?;
+                        let new_info = MergedChunkInfo::Known(vec![(chunk_info.offset, digest)]);
+                        upload_queue
+                            .send((new_info, Some(response)))
+                            .await
+                            .map_err(|err| {
+                                format_err!("failed to send to upload queue: {}", err)
+                            })?;
+                    }
+                    MergedChunkInfo::Known(list) => {
+                        chunk_count += list.len();
+                        chunk_reused += list.len();
+                        upload_queue
+                            .send((MergedChunkInfo::Known(list), None))
+                            .await
+                            .map_err(|err| {
+                                format_err!("failed to send to upload queue: {}", err)
+                            })?;
+                    }
+                }
+            }
+            Ok(())
+        }
+        .await;
+
+        drop(upload_queue);
+        upload_result.await?.and(result)?;
+
+        log::debug!(
+            "{}: reused {} from {} chunks.",
+            archive_name,
+            chunk_reused,
+            chunk_count
+        );
+
+        let param = json!({
+            "wid": wid ,
+            "chunk-count": chunk_count,
+            "size": index_size,
+            "csum": hex::encode(index_csum),
+        });
+        let _value = self.h2.post(&close_path, Some(param)).await?;
+        Ok(BackupStats {
+            size: index_size,
+            csum: index_csum,
+        })
+    }
+
     fn response_queue() -> (
         mpsc::Sender<h2::client::ResponseFuture>,
         oneshot::Receiver<Result<(), Error>>,
@@ -632,7 +762,7 @@ impl BackupWriter {
     fn upload_chunk_info_stream(
         h2: H2Client,
         wid: u64,
-        stream: impl Stream<Item = Result<bytes::BytesMut, Error>>,
+        stream: impl Stream<Item = Result<ChunkOrReused, Error>>,
         prefix: &str,
         known_chunks: Arc<Mutex<HashSet<[u8; 32]>>>,
         crypt_config: Option<Arc<CryptConfig>>,
@@ -664,6 +794,33 @@ impl BackupWriter {
 
         stream
             .and_then(move |data| {
+                let data = match data {
+                    ChunkOrReused::Chunk(data) => data,
+                    ChunkOrReused::Reused(list) => {
+                        let mut guard = index_csum.lock().unwrap();
+                        let csum = guard.as_mut().unwrap();
+
+                        let mut chunk_list = Vec::with_capacity(list.len());
+                        for chunk in list {
+                            let chunk_len = chunk.size as usize;
+
+                            total_chunks.fetch_add(1, Ordering::SeqCst);
+                            known_chunk_count.fetch_add(1, Ordering::SeqCst);
+                            reused_len.fetch_add(chunk_len, Ordering::SeqCst);
+                            let offset = stream_len.fetch_add(chunk_len, Ordering::SeqCst) as u64;
+
+                            let chunk_end = offset + chunk.size;
+                            if !is_fixed_chunk_size {
+                                csum.update(&chunk_end.to_le_bytes());
+                            }
+                            csum.update(&chunk.digest);
+
+                            chunk_list.push((offset, chunk.digest));
+                        }
+                        return future::ok(MergedChunkInfo::Known(chunk_list));
+                    }
+                };
+
                 let chunk_len = data.len();
 
                 total_chunks.fetch_add(1, Ordering::SeqCst);
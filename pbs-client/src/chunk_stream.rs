@@ -132,3 +132,114 @@ where
         }
     }
 }
+
+/// Chunk of a previous index, re-referenced without reading its data.
+#[derive(Clone, Debug)]
+pub struct ReusedChunk {
+    pub digest: [u8; 32],
+    pub size: u64,
+}
+
+/// Input of an [`InjectionChunkStream`]
+pub enum StreamData {
+    /// Data to be split into dynamic sized chunks.
+    Data(Vec<u8>),
+    /// Chunks to be injected at the current position.
+    Reused(Vec<ReusedChunk>),
+}
+
+/// Output of an [`InjectionChunkStream`]
+pub enum ChunkOrReused {
+    Chunk(BytesMut),
+    Reused(Vec<ReusedChunk>),
+}
+
+/// Split input stream into dynamic sized chunks, while passing through injected chunks
+///
+/// Injected chunks force a chunk boundary, so the data buffered before them is emitted as a
+/// (possibly small) chunk of its own.
+pub struct InjectionChunkStream<S: Unpin> {
+    input: S,
+    chunk_size: usize,
+    chunker: Chunker,
+    buffer: BytesMut,
+    scan_pos: usize,
+    reused: Option<Vec<ReusedChunk>>,
+}
+
+impl<S: Unpin> InjectionChunkStream<S> {
+    pub fn new(input: S, chunk_size: Option<usize>) -> Self {
+        let chunk_size = chunk_size.unwrap_or(4 * 1024 * 1024);
+        Self {
+            input,
+            chunk_size,
+            chunker: Chunker::new(chunk_size),
+            buffer: BytesMut::new(),
+            scan_pos: 0,
+            reused: None,
+        }
+    }
+}
+
+impl<S: Unpin> Unpin for InjectionChunkStream<S> {}
+
+impl<S: Unpin> Stream for InjectionChunkStream<S>
+where
+    S: TryStream<Ok = StreamData>,
+    S::Error: Into<Error>,
+{
+    type Item = Result<ChunkOrReused, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(reused) = this.reused.take() {
+                return Poll::Ready(Some(Ok(ChunkOrReused::Reused(reused))));
+            }
+
+            if this.scan_pos < this.buffer.len() {
+                let boundary = this.chunker.scan(&this.buffer[this.scan_pos..]);
+
+                let chunk_size = this.scan_pos + boundary;
+
+                if boundary == 0 {
+                    this.scan_pos = this.buffer.len();
+                    // continue poll
+                } else if chunk_size <= this.buffer.len() {
+                    let result = this.buffer.split_to(chunk_size);
+                    this.scan_pos = 0;
+                    return Poll::Ready(Some(Ok(ChunkOrReused::Chunk(result))));
+                } else {
+                    panic!("got unexpected chunk boundary from chunker");
+                }
+            }
+
+            match ready!(Pin::new(&mut this.input).try_poll_next(cx)) {
+                Some(Err(err)) => {
+                    return Poll::Ready(Some(Err(err.into())));
+                }
+                None => {
+                    this.scan_pos = 0;
+                    if !this.buffer.is_empty() {
+                        return Poll::Ready(Some(Ok(ChunkOrReused::Chunk(this.buffer.split()))));
+                    } else {
+                        return Poll::Ready(None);
+                    }
+                }
+                Some(Ok(StreamData::Data(data))) => {
+                    this.buffer.extend_from_slice(&data);
+                }
+                Some(Ok(StreamData::Reused(reused))) => {
+                    // force a chunk boundary, the chunker restarts after the injected chunks
+                    this.chunker = Chunker::new(this.chunk_size);
+                    this.scan_pos = 0;
+                    if this.buffer.is_empty() {
+                        return Poll::Ready(Some(Ok(ChunkOrReused::Reused(reused))));
+                    }
+                    this.reused = Some(reused);
+                    return Poll::Ready(Some(Ok(ChunkOrReused::Chunk(this.buffer.split()))));
+                }
+            }
+        }
+    }
+}
@@ -10,8 +10,11 @@ use http::{Request, Response};
 use hyper::client::{Client, HttpConnector};
 use hyper::Body;
 use openssl::{
-    ssl::{SslConnector, SslMethod},
-    x509::X509StoreContextRef,
+    hash::MessageDigest,
+    pkey::{Id, PKey, Private},
+    sign::Signer,
+    ssl::{SslConnector, SslFiletype, SslMethod},
+    x509::{X509Ref, X509StoreContextRef},
 };
 use percent_encoding::percent_encode;
 use serde_json::{json, Value};
@@ -27,7 +30,7 @@ use proxmox_http::uri::{build_authority, json_object_to_query};
 use proxmox_http::{ProxyConfig, RateLimiter};
 
 use pbs_api_types::percent_encoding::DEFAULT_ENCODE_SET;
-use pbs_api_types::{Authid, RateLimitConfig, Userid};
+use pbs_api_types::{client_cert_login_data, Authid, RateLimitConfig, Userid};
 
 use super::pipe_to_stream::PipeToSendStream;
 use super::PROXMOX_BACKUP_TCP_KEEPALIVE_TIME;
@@ -52,6 +55,13 @@ pub struct HttpClientOptions {
     fingerprint_cache: bool,
     verify_cert: bool,
     limit: RateLimitConfig,
+    client_cert: Option<(String, String)>,
+}
+
+/// TLS client certificate and key used to log in without password
+struct ClientCertLogin {
+    certificate: String,
+    key: PKey<Private>,
 }
 
 impl HttpClientOptions {
@@ -114,6 +124,15 @@ impl HttpClientOptions {
         self.limit = rate_limit;
         self
     }
+
+    /// Use the TLS client certificate and private key stored at the given paths.
+    ///
+    /// The certificate is presented during the TLS handshake, and used to log in if no password
+    /// is set. This is not possible for API tokens.
+    pub fn client_cert(mut self, cert_path: String, key_path: String) -> Self {
+        self.client_cert = Some((cert_path, key_path));
+        self
+    }
 }
 
 impl Default for HttpClientOptions {
@@ -127,6 +146,7 @@ impl Default for HttpClientOptions {
             fingerprint_cache: false,
             verify_cert: true,
             limit: RateLimitConfig::default(), // unlimited
+            client_cert: None,
         }
     }
 }
@@ -168,6 +188,20 @@ pub fn delete_ticket_info(prefix: &str, server: &str, username: &Userid) -> Resu
     Ok(())
 }
 
+/// SHA256 fingerprint of `cert`, as colon separated hex string.
+fn cert_fingerprint(cert: &X509Ref) -> Result<String, Error> {
+    let fp = match cert.digest(openssl::hash::MessageDigest::sha256()) {
+        Ok(fp) => fp,
+        Err(err) => bail!("failed to calculate certificate FP - {}", err), // should not happen
+    };
+    Ok(hex::encode(fp)
+        .as_bytes()
+        .chunks(2)
+        .map(|v| std::str::from_utf8(v).unwrap())
+        .collect::<Vec<&str>>()
+        .join(":"))
+}
+
 fn store_fingerprint(prefix: &str, server: &str, fingerprint: &str) -> Result<(), Error> {
     let base = BaseDirectories::with_prefix(prefix)?;
 
@@ -314,6 +348,8 @@ impl HttpClient {
         mut options: HttpClientOptions,
     ) -> Result<Self, Error> {
         let verified_fingerprint = Arc::new(Mutex::new(None));
+        // fingerprint of the server certificate, signed for client certificate logins
+        let peer_fingerprint = Arc::new(Mutex::new(None));
 
         let mut expected_fingerprint = options.fingerprint.take();
 
@@ -332,29 +368,39 @@ impl HttpClient {
             let interactive = options.interactive;
             let fingerprint_cache = options.fingerprint_cache;
             let prefix = options.prefix.clone();
+            let peer_fingerprint = peer_fingerprint.clone();
             ssl_connector_builder.set_verify_callback(
                 openssl::ssl::SslVerifyMode::PEER,
-                move |valid, ctx| match Self::verify_callback(
-                    valid,
-                    ctx,
-                    expected_fingerprint.as_ref(),
-                    interactive,
-                ) {
-                    Ok(None) => true,
-                    Ok(Some(fingerprint)) => {
-                        if fingerprint_cache && prefix.is_some() {
-                            if let Err(err) =
-                                store_fingerprint(prefix.as_ref().unwrap(), &server, &fingerprint)
-                            {
-                                log::error!("{}", err);
-                            }
+                move |valid, ctx| {
+                    if ctx.error_depth() == 0 {
+                        if let Some(cert) = ctx.current_cert() {
+                            *peer_fingerprint.lock().unwrap() = cert_fingerprint(cert).ok();
                         }
-                        *verified_fingerprint.lock().unwrap() = Some(fingerprint);
-                        true
                     }
-                    Err(err) => {
-                        log::error!("certificate validation failed - {}", err);
-                        false
+                    match Self::verify_callback(
+                        valid,
+                        ctx,
+                        expected_fingerprint.as_ref(),
+                        interactive,
+                    ) {
+                        Ok(None) => true,
+                        Ok(Some(fingerprint)) => {
+                            if fingerprint_cache && prefix.is_some() {
+                                if let Err(err) = store_fingerprint(
+                                    prefix.as_ref().unwrap(),
+                                    &server,
+                                    &fingerprint,
+                                ) {
+                                    log::error!("{}", err);
+                                }
+                            }
+                            *verified_fingerprint.lock().unwrap() = Some(fingerprint);
+                            true
+                        }
+                        Err(err) => {
+                            log::error!("certificate validation failed - {}", err);
+                            false
+                        }
                     }
                 },
             );
@@ -362,6 +408,32 @@ impl HttpClient {
             ssl_connector_builder.set_verify(openssl::ssl::SslVerifyMode::NONE);
         }
 
+        let cert_login = match options.client_cert.take() {
+            Some((cert_path, key_path)) => {
+                if auth_id.is_token() {
+                    bail!("client certificate login is not possible for API tokens");
+                }
+                if !options.verify_cert {
+                    bail!("client certificate login requires verifying the server certificate");
+                }
+                ssl_connector_builder
+                    .set_certificate_chain_file(&cert_path)
+                    .map_err(|err| format_err!("unable to load '{cert_path}' - {err}"))?;
+                ssl_connector_builder
+                    .set_private_key_file(&key_path, SslFiletype::PEM)
+                    .map_err(|err| format_err!("unable to load '{key_path}' - {err}"))?;
+                ssl_connector_builder.check_private_key().map_err(|err| {
+                    format_err!("client certificate does not match private key - {err}")
+                })?;
+
+                let certificate = proxmox_sys::fs::file_read_string(&cert_path)?;
+                let key =
+                    PKey::private_key_from_pem(&proxmox_sys::fs::file_get_contents(&key_path)?)?;
+                Some(Arc::new(ClientCertLogin { certificate, key }))
+            }
+            None => None,
+        };
+
         let mut httpc = HttpConnector::new();
         httpc.set_nodelay(true); // important for h2 download performance!
         httpc.enforce_http(false); // we want https...
@@ -405,6 +477,9 @@ impl HttpClient {
 
         let password = if let Some(password) = password {
             password
+        } else if cert_login.is_some() {
+            // the ticket is acquired with the client certificate on first use
+            String::new()
         } else {
             let userid = if auth_id.is_token() {
                 bail!("API token secret must be provided!");
@@ -475,13 +550,25 @@ impl HttpClient {
 
         let (renewal_future, ticket_abort) = futures::future::abortable(renewal_future);
 
-        let login_future = Self::credentials(
-            client.clone(),
-            server.to_owned(),
-            port,
-            auth_id.user().clone(),
-            password,
-        )
+        let login_future = match cert_login {
+            Some(cert_login) => Self::cert_credentials(
+                client.clone(),
+                server.to_owned(),
+                port,
+                auth_id.user().clone(),
+                cert_login,
+                peer_fingerprint,
+            )
+            .boxed(),
+            None => Self::credentials(
+                client.clone(),
+                server.to_owned(),
+                port,
+                auth_id.user().clone(),
+                password,
+            )
+            .boxed(),
+        }
         .map_ok({
             let server = server.to_string();
             let prefix = options.prefix.clone();
@@ -576,17 +663,7 @@ impl HttpClient {
             bail!("context depth != 0")
         }
 
-        let fp = match cert.digest(openssl::hash::MessageDigest::sha256()) {
-            Ok(fp) => fp,
-            Err(err) => bail!("failed to calculate certificate FP - {}", err), // should not happen
-        };
-        let fp_string = hex::encode(fp);
-        let fp_string = fp_string
-            .as_bytes()
-            .chunks(2)
-            .map(|v| std::str::from_utf8(v).unwrap())
-            .collect::<Vec<&str>>()
-            .join(":");
+        let fp_string = cert_fingerprint(cert)?;
 
         if let Some(expected_fingerprint) = expected_fingerprint {
             let expected_fingerprint = expected_fingerprint.to_lowercase();
@@ -822,6 +899,67 @@ impl HttpClient {
             Some(data),
         )?;
         let cred = Self::api_request(client, req).await?;
+        Self::parse_auth_info(&cred)
+    }
+
+    /// Acquire a ticket by signing a login challenge with the private key of the client
+    /// certificate.
+    ///
+    /// The signed data includes the fingerprint of the server certificate seen by this client.
+    async fn cert_credentials(
+        client: Client<HttpsConnector>,
+        server: String,
+        port: u16,
+        username: Userid,
+        cert_login: Arc<ClientCertLogin>,
+        peer_fingerprint: Arc<Mutex<Option<String>>>,
+    ) -> Result<AuthInfo, Error> {
+        let req = Self::request_builder(
+            &server,
+            port,
+            "POST",
+            "/api2/json/access/cert-challenge",
+            Some(json!({ "username": username })),
+        )?;
+        let res = Self::api_request(client.clone(), req).await?;
+        let challenge = res["data"]["challenge"]
+            .as_str()
+            .ok_or_else(|| format_err!("missing login challenge"))?;
+
+        let server_fingerprint = peer_fingerprint
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| format_err!("unable to get server certificate fingerprint"))?;
+        let login_data = client_cert_login_data(&username, challenge, &server_fingerprint);
+
+        let signature = {
+            // EdDSA signs the message itself, without a separate digest
+            let mut signer = match cert_login.key.id() {
+                Id::ED25519 | Id::ED448 => Signer::new_without_digest(&cert_login.key)?,
+                _ => Signer::new(MessageDigest::sha256(), &cert_login.key)?,
+            };
+            hex::encode(signer.sign_oneshot_to_vec(login_data.as_bytes())?)
+        };
+
+        let data = json!({
+            "username": username,
+            "certificate": cert_login.certificate,
+            "challenge": challenge,
+            "signature": signature,
+        });
+        let req = Self::request_builder(
+            &server,
+            port,
+            "POST",
+            "/api2/json/access/cert-ticket",
+            Some(data),
+        )?;
+        let cred = Self::api_request(client, req).await?;
+        Self::parse_auth_info(&cred)
+    }
+
+    fn parse_auth_info(cred: &Value) -> Result<AuthInfo, Error> {
         let auth = AuthInfo {
             auth_id: cred["data"]["username"].as_str().unwrap().parse()?,
             ticket: cred["data"]["ticket"].as_str().unwrap().to_owned(),
@@ -27,6 +27,10 @@ use proxmox_sys::fs::{self, acl, xattr};
 use pbs_datastore::catalog::BackupCatalogWriter;
 
 use crate::pxar::metadata::errno_is_unsupported;
+use crate::pxar::payload::{
+    lookup_previous_dir, lookup_previous_payload, payload_header, PayloadRef, PreviousReader,
+    SplitArchive, PAYLOAD_REF_SIZE,
+};
 use crate::pxar::tools::assert_single_path_component;
 use crate::pxar::Flags;
 
@@ -128,9 +132,12 @@ struct Archiver {
     device_set: Option<HashSet<u64>>,
     hardlinks: HashMap<HardLinkInfo, (PathBuf, LinkOffset)>,
     file_copy_buffer: Vec<u8>,
+    split: Option<SplitArchive>,
+    previous_dir: Option<Directory<PreviousReader>>,
 }
 
 type Encoder<'a, T> = pxar::encoder::aio::Encoder<'a, T>;
+type Directory<T> = pxar::accessor::aio::Directory<T>;
 
 pub async fn create_archive<T, F>(
     source_dir: Dir,
@@ -139,6 +146,7 @@ pub async fn create_archive<T, F>(
     callback: F,
     catalog: Option<Arc<Mutex<dyn BackupCatalogWriter + Send>>>,
     options: PxarCreateOptions,
+    split: Option<SplitArchive>,
 ) -> Result<(), Error>
 where
     T: SeqWrite + Send,
@@ -192,12 +200,37 @@ where
         device_set,
         hardlinks: HashMap::new(),
         file_copy_buffer: vec::undefined(4 * 1024 * 1024),
+        split,
+        previous_dir: None,
     };
 
+    if let Some(previous) = archiver
+        .split
+        .as_ref()
+        .and_then(|split| split.previous.as_ref())
+    {
+        match previous.accessor.open_root().await {
+            Ok(dir) => archiver.previous_dir = Some(dir),
+            Err(err) => log::warn!("unable to open previous metadata archive - {err}"),
+        }
+    }
+
     archiver
         .archive_dir_contents(&mut encoder, source_dir, true)
         .await?;
     encoder.finish().await?;
+
+    if let Some(split) = archiver.split {
+        if split.previous.is_some() {
+            log::info!(
+                "reused {} unchanged files ({} bytes) of previous snapshot",
+                split.reused_files,
+                split.reused_bytes,
+            );
+        }
+        split.writer.finish()?;
+    }
+
     Ok(())
 }
 
@@ -400,6 +433,23 @@ impl Archiver {
         let mut metadata = Metadata::default();
         metadata.stat.mode = pxar::format::mode::IFREG | 0o600;
 
+        if let Some(ref mut split) = self.split {
+            let payload_ref = PayloadRef {
+                offset: split.writer.position(),
+                size: content.len() as u64,
+                inode: 0,
+            };
+            split.writer.write_all(&payload_header(payload_ref.size))?;
+            split.writer.write_all(&content)?;
+
+            let mut file = encoder
+                .create_file(&metadata, ".pxarexclude-cli", PAYLOAD_REF_SIZE)
+                .await?;
+            file.write_all(&payload_ref.to_bytes()).await?;
+
+            return Ok(());
+        }
+
         let mut file = encoder
             .create_file(&metadata, ".pxarexclude-cli", content.len() as u64)
             .await?;
@@ -579,9 +629,13 @@ impl Archiver {
                         .add_file(c_file_name, file_size, stat.st_mtime)?;
                 }
 
-                let offset: LinkOffset = self
-                    .add_regular_file(encoder, fd, file_name, &metadata, file_size)
-                    .await?;
+                let offset: LinkOffset = if self.split.is_some() {
+                    self.add_split_file(encoder, fd, file_name, &metadata, file_size, stat.st_ino)
+                        .await?
+                } else {
+                    self.add_regular_file(encoder, fd, file_name, &metadata, file_size)
+                        .await?
+                };
 
                 if stat.st_nlink > 1 {
                     self.hardlinks
@@ -659,6 +713,17 @@ impl Archiver {
 
         let mut encoder = encoder.create_directory(dir_name, metadata).await?;
 
+        let old_previous_dir = self.previous_dir.take();
+        if let Some(ref parent) = old_previous_dir {
+            match lookup_previous_dir(parent, dir_name.as_ref()).await {
+                Ok(dir) => self.previous_dir = dir,
+                Err(err) => log::warn!(
+                    "unable to look up {:?} in previous metadata archive - {err}",
+                    self.path
+                ),
+            }
+        }
+
         let old_fs_magic = self.fs_magic;
         let old_fs_feature_flags = self.fs_feature_flags;
         let old_st_dev = self.current_st_dev;
@@ -686,6 +751,7 @@ impl Archiver {
         self.fs_magic = old_fs_magic;
         self.fs_feature_flags = old_fs_feature_flags;
         self.current_st_dev = old_st_dev;
+        self.previous_dir = old_previous_dir;
 
         encoder.finish().await?;
         result
@@ -730,6 +796,132 @@ impl Archiver {
         Ok(out.file_offset())
     }
 
+    /// Add a regular file to a split archive.
+    ///
+    /// The payload of the file is taken over from the previous snapshot if its mtime, size and
+    /// inode did not change, otherwise it is read and appended to the payload stream.
+    async fn add_split_file<T: SeqWrite + Send>(
+        &mut self,
+        encoder: &mut Encoder<'_, T>,
+        fd: OwnedFd,
+        file_name: &Path,
+        metadata: &Metadata,
+        file_size: u64,
+        inode: u64,
+    ) -> Result<LinkOffset, Error> {
+        let payload_ref = match self
+            .reuse_previous_payload(file_name, metadata, file_size, inode)
+            .await?
+        {
+            Some(payload_ref) => payload_ref,
+            None => self.write_payload(fd, file_size, inode)?,
+        };
+
+        let mut out = encoder
+            .create_file(metadata, file_name, PAYLOAD_REF_SIZE)
+            .await?;
+        out.write_all(&payload_ref.to_bytes()).await?;
+
+        Ok(out.file_offset())
+    }
+
+    async fn reuse_previous_payload(
+        &mut self,
+        file_name: &Path,
+        metadata: &Metadata,
+        file_size: u64,
+        inode: u64,
+    ) -> Result<Option<PayloadRef>, Error> {
+        let (split, dir) = match (&mut self.split, &self.previous_dir) {
+            (Some(split), Some(dir)) => (split, dir),
+            _ => return Ok(None),
+        };
+        let previous = match split.previous {
+            Some(ref previous) => previous,
+            None => return Ok(None),
+        };
+
+        let (previous_metadata, previous_ref) = match lookup_previous_payload(dir, file_name).await
+        {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return Ok(None),
+            Err(err) => {
+                log::warn!(
+                    "unable to look up {:?} in previous metadata archive - {err}",
+                    self.path
+                );
+                return Ok(None);
+            }
+        };
+
+        if previous_metadata.stat.mtime != metadata.stat.mtime
+            || previous_ref.size != file_size
+            || previous_ref.inode != inode
+        {
+            return Ok(None);
+        }
+
+        match split.writer.reuse(&previous.payload_index, &previous_ref)? {
+            Some(offset) => {
+                split.reused_files += 1;
+                split.reused_bytes += file_size;
+                Ok(Some(PayloadRef {
+                    offset,
+                    size: file_size,
+                    inode,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn write_payload(
+        &mut self,
+        fd: OwnedFd,
+        file_size: u64,
+        inode: u64,
+    ) -> Result<PayloadRef, Error> {
+        let mut file = unsafe { std::fs::File::from_raw_fd(fd.into_raw_fd()) };
+
+        let split = self.split.as_mut().unwrap();
+        let payload_ref = PayloadRef {
+            offset: split.writer.position(),
+            size: file_size,
+            inode,
+        };
+        split.writer.write_all(&payload_header(file_size))?;
+
+        let mut remaining = file_size;
+        while remaining != 0 {
+            let mut got = match file.read(&mut self.file_copy_buffer[..]) {
+                Ok(0) => break,
+                Ok(got) => got,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => bail!(err),
+            };
+            if got as u64 > remaining {
+                self.report_file_grew_while_reading()?;
+                got = remaining as usize;
+            }
+            let split = self.split.as_mut().unwrap();
+            split.writer.write_all(&self.file_copy_buffer[..got])?;
+            remaining -= got as u64;
+        }
+        if remaining > 0 {
+            self.report_file_shrunk_while_reading()?;
+            let to_zero = remaining.min(self.file_copy_buffer.len() as u64) as usize;
+            vec::clear(&mut self.file_copy_buffer[..to_zero]);
+            let split = self.split.as_mut().unwrap();
+            while remaining != 0 {
+                let fill = remaining.min(self.file_copy_buffer.len() as u64) as usize;
+                split.writer.write_all(&self.file_copy_buffer[..fill])?;
+                remaining -= fill as u64;
+            }
+        }
+
+        Ok(payload_ref)
+    }
+
     async fn add_symlink<T: SeqWrite + Send>(
         &mut self,
         encoder: &mut Encoder<'_, T>,
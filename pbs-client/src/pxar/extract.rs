@@ -16,18 +16,19 @@ use nix::sys::stat::Mode;
 
 use pathpatterns::{MatchEntry, MatchList, MatchType};
 use pxar::accessor::aio::{Accessor, FileContents, FileEntry};
-use pxar::decoder::{aio::Decoder, Contents};
+use pxar::decoder::aio::Decoder;
 use pxar::format::Device;
 use pxar::{Entry, EntryKind, Metadata};
 
 use proxmox_io::{sparse_copy, sparse_copy_async};
 use proxmox_sys::c_result;
-use proxmox_sys::fs::{create_path, CreateOptions};
+use proxmox_sys::fs::{acl, create_path, xattr, CreateOptions};
 
 use proxmox_compression::zip::{ZipEncoder, ZipEntry};
 
 use crate::pxar::dir_stack::PxarDirStack;
 use crate::pxar::metadata;
+use crate::pxar::payload::{open_payload, PayloadReader, PayloadRef};
 use crate::pxar::Flags;
 
 pub struct PxarExtractOptions<'a> {
@@ -36,6 +37,8 @@ pub struct PxarExtractOptions<'a> {
     pub allow_existing_dirs: bool,
     pub overwrite_flags: OverwriteFlags,
     pub on_error: Option<ErrorHandler>,
+    /// Payload archive, required to extract the metadata archive of a split archive.
+    pub payload: Option<Box<dyn PayloadReader + Send>>,
 }
 
 bitflags! {
@@ -88,6 +91,7 @@ where
     callback: F,
     extractor: Extractor,
     match_list: &'a [MatchEntry],
+    payload: Option<Box<dyn PayloadReader + Send>>,
     state: ExtractorIterState,
 }
 
@@ -167,6 +171,7 @@ where
             callback,
             extractor,
             match_list: options.match_list,
+            payload: options.payload,
             state,
         })
     }
@@ -356,8 +361,23 @@ where
             (true, EntryKind::File { size, .. }) => {
                 let contents = self.decoder.contents();
 
-                if let Some(mut contents) = contents {
-                    self.extractor.extract_file(
+                match (contents, self.payload.as_mut()) {
+                    (Some(mut contents), Some(payload)) => {
+                        PayloadRef::read_from(&mut contents, *size).and_then(|payload_ref| {
+                            let mut payload_contents =
+                                open_payload(payload.as_mut(), &payload_ref)?;
+                            self.extractor.extract_file(
+                                &file_name,
+                                metadata,
+                                payload_ref.size,
+                                &mut payload_contents,
+                                self.extractor
+                                    .overwrite_flags
+                                    .contains(OverwriteFlags::FILE),
+                            )
+                        })
+                    }
+                    (Some(mut contents), None) => self.extractor.extract_file(
                         &file_name,
                         metadata,
                         *size,
@@ -365,11 +385,10 @@ where
                         self.extractor
                             .overwrite_flags
                             .contains(OverwriteFlags::FILE),
-                    )
-                } else {
-                    Err(format_err!(
+                    ),
+                    (None, _) => Err(format_err!(
                         "found regular file entry without contents in archive"
-                    ))
+                    )),
                 }
                 .context(PxarExtractContext::ExtractFile)
             }
@@ -780,17 +799,199 @@ fn add_metadata_to_header(header: &mut tar::Header, metadata: &Metadata) {
     header.set_gid(metadata.stat.gid as u64);
 }
 
-async fn tar_add_file<'a, W, T>(
+fn add_pax_record(records: &mut Vec<u8>, key: &[u8], value: &[u8]) {
+    // a record is "<length> <key>=<value>\n", where the length includes its own digits
+    let rest = key.len() + value.len() + 3;
+    let mut len = rest + rest.to_string().len();
+    if len.to_string().len() > rest.to_string().len() {
+        len += 1;
+    }
+
+    records.extend_from_slice(format!("{len} ").as_bytes());
+    records.extend_from_slice(key);
+    records.push(b'=');
+    records.extend_from_slice(value);
+    records.push(b'\n');
+}
+
+fn acl_permissions_text(permissions: pxar::format::acl::Permissions) -> String {
+    let mut text = String::with_capacity(3);
+    text.push(if permissions.0 & acl::ACL_READ != 0 {
+        'r'
+    } else {
+        '-'
+    });
+    text.push(if permissions.0 & acl::ACL_WRITE != 0 {
+        'w'
+    } else {
+        '-'
+    });
+    text.push(if permissions.0 & acl::ACL_EXECUTE != 0 {
+        'x'
+    } else {
+        '-'
+    });
+    text
+}
+
+// ACLs in the short text form used by the SCHILY.acl.* pax records of GNU tar and star
+fn acl_access_text(metadata: &Metadata) -> Option<String> {
+    let pxar_acl = &metadata.acl;
+    if pxar_acl.users.is_empty() && pxar_acl.groups.is_empty() && pxar_acl.group_obj.is_none() {
+        return None;
+    }
+
+    let perms = |mode| acl_permissions_text(pxar::format::acl::Permissions(mode));
+    let mode = metadata.stat.mode;
+
+    let mut entries = vec![format!(
+        "user::{}",
+        perms(acl::mode_user_to_acl_permissions(mode))
+    )];
+    for user in &pxar_acl.users {
+        entries.push(format!(
+            "user:{}:{}",
+            user.uid,
+            acl_permissions_text(user.permissions)
+        ));
+    }
+    let group_mode = perms(acl::mode_group_to_acl_permissions(mode));
+    match pxar_acl.group_obj.as_ref() {
+        Some(group_obj) => entries.push(format!(
+            "group::{}",
+            acl_permissions_text(group_obj.permissions)
+        )),
+        None => entries.push(format!("group::{group_mode}")),
+    }
+    for group in &pxar_acl.groups {
+        entries.push(format!(
+            "group:{}:{}",
+            group.gid,
+            acl_permissions_text(group.permissions)
+        ));
+    }
+    entries.push(format!("mask::{group_mode}"));
+    entries.push(format!(
+        "other::{}",
+        perms(acl::mode_other_to_acl_permissions(mode))
+    ));
+
+    Some(entries.join(","))
+}
+
+fn acl_default_text(metadata: &Metadata) -> Option<String> {
+    let pxar_acl = &metadata.acl;
+    let default = pxar_acl.default.as_ref()?;
+
+    let mut entries = vec![format!(
+        "user::{}",
+        acl_permissions_text(default.user_obj_permissions)
+    )];
+    for user in &pxar_acl.default_users {
+        entries.push(format!(
+            "user:{}:{}",
+            user.uid,
+            acl_permissions_text(user.permissions)
+        ));
+    }
+    entries.push(format!(
+        "group::{}",
+        acl_permissions_text(default.group_obj_permissions)
+    ));
+    for group in &pxar_acl.default_groups {
+        entries.push(format!(
+            "group:{}:{}",
+            group.gid,
+            acl_permissions_text(group.permissions)
+        ));
+    }
+    if default.mask_permissions != pxar::format::acl::Permissions::NO_MASK {
+        entries.push(format!(
+            "mask::{}",
+            acl_permissions_text(default.mask_permissions)
+        ));
+    }
+    entries.push(format!(
+        "other::{}",
+        acl_permissions_text(default.other_permissions)
+    ));
+
+    Some(entries.join(","))
+}
+
+/// Encode the metadata which does not fit into a plain tar header as pax records.
+///
+/// Uses the `SCHILY.xattr.*` and `SCHILY.acl.*` keywords understood by GNU tar (with `--xattrs`
+/// and `--acls`), bsdtar and star.
+fn pax_records(metadata: &Metadata) -> Vec<u8> {
+    let mut records = Vec::new();
+
+    let mtime = &metadata.stat.mtime;
+    if mtime.nanos != 0 {
+        let value = format!("{}.{:09}", mtime.secs, mtime.nanos);
+        add_pax_record(&mut records, b"mtime", value.as_bytes());
+    }
+
+    for xattr in &metadata.xattrs {
+        let mut key = b"SCHILY.xattr.".to_vec();
+        key.extend_from_slice(xattr.name().to_bytes());
+        add_pax_record(&mut records, &key, xattr.value());
+    }
+
+    if let Some(fcaps) = metadata.fcaps.as_ref() {
+        let mut key = b"SCHILY.xattr.".to_vec();
+        key.extend_from_slice(xattr::xattr_name_fcaps().to_bytes());
+        add_pax_record(&mut records, &key, &fcaps.data);
+    }
+
+    if let Some(text) = acl_access_text(metadata) {
+        add_pax_record(&mut records, b"SCHILY.acl.access", text.as_bytes());
+    }
+    if let Some(text) = acl_default_text(metadata) {
+        add_pax_record(&mut records, b"SCHILY.acl.default", text.as_bytes());
+    }
+
+    records
+}
+
+/// Add a pax extended header with the metadata of the following entry, if required.
+async fn tar_add_pax_header<W>(
     tar: &mut proxmox_compression::tar::Builder<W>,
-    contents: Option<Contents<'a, T>>,
+    metadata: &Metadata,
+) -> Result<(), Error>
+where
+    W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    let records = pax_records(metadata);
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(tar::EntryType::XHeader);
+    header.set_mode(0o644);
+    header.set_mtime(metadata.stat.mtime.secs as u64);
+    header.set_size(records.len() as u64);
+    header.set_cksum();
+
+    tar.add_entry(&mut header, "././@PaxHeader", &records[..])
+        .await
+        .context("could not send pax header")
+}
+
+async fn tar_add_file<W, R>(
+    tar: &mut proxmox_compression::tar::Builder<W>,
+    contents: Option<R>,
     size: u64,
     metadata: &Metadata,
     path: &Path,
 ) -> Result<(), Error>
 where
-    T: pxar::decoder::SeqRead + Unpin + Send + Sync + 'static,
+    R: tokio::io::AsyncRead + Unpin,
     W: tokio::io::AsyncWrite + Unpin + Send + 'static,
 {
+    tar_add_pax_header(tar, metadata).await?;
+
     let mut header = tar::Header::new_gnu();
     header.set_entry_type(tar::EntryType::Regular);
     header.set_size(size);
@@ -805,6 +1006,9 @@ where
 }
 
 /// Creates a tar file from `path` and writes it into `output`
+///
+/// Split metadata archives are not supported, callers need to reject them, see
+/// [`check_no_split_archive`](crate::pxar::payload::check_no_split_archive).
 pub async fn create_tar<T, W, P>(output: W, accessor: Accessor<T>, path: P) -> Result<(), Error>
 where
     T: Clone + pxar::accessor::ReadAt + Unpin + Send + Sync + 'static,
@@ -830,6 +1034,7 @@ where
 
         if path != Path::new("/") {
             let metadata = entry.metadata();
+            tar_add_pax_header(&mut tarencoder, metadata).await?;
             let mut header = tar::Header::new_gnu();
             header.set_entry_type(tar::EntryType::Directory);
             add_metadata_to_header(&mut header, metadata);
@@ -900,6 +1105,7 @@ where
                 EntryKind::Symlink(link) if !link.data.is_empty() => {
                     log::debug!("adding '{}' to tar", path.display());
                     let realpath = Path::new(link);
+                    tar_add_pax_header(&mut tarencoder, metadata).await?;
                     let mut header = tar::Header::new_gnu();
                     header.set_entry_type(tar::EntryType::Symlink);
                     add_metadata_to_header(&mut header, metadata);
@@ -911,6 +1117,7 @@ where
                 }
                 EntryKind::Fifo => {
                     log::debug!("adding '{}' to tar", path.display());
+                    tar_add_pax_header(&mut tarencoder, metadata).await?;
                     let mut header = tar::Header::new_gnu();
                     header.set_entry_type(tar::EntryType::Fifo);
                     add_metadata_to_header(&mut header, metadata);
@@ -927,6 +1134,7 @@ where
                     log::debug!("adding '{}' to tar", path.display());
                     // we cannot add the root path itself
                     if path != Path::new("/") {
+                        tar_add_pax_header(&mut tarencoder, metadata).await?;
                         let mut header = tar::Header::new_gnu();
                         header.set_entry_type(tar::EntryType::Directory);
                         add_metadata_to_header(&mut header, metadata);
@@ -945,6 +1153,7 @@ where
                     } else {
                         tar::EntryType::Block
                     };
+                    tar_add_pax_header(&mut tarencoder, metadata).await?;
                     let mut header = tar::Header::new_gnu();
                     header.set_entry_type(entry_type);
                     header.set_device_major(device.major as u32)?;
@@ -959,6 +1168,27 @@ where
                 _ => {} // ignore all else
             }
         }
+    } else {
+        // a single file, the tar only contains this entry
+        let path = file.entry().path().strip_prefix(prefix)?.to_owned();
+        let file = match file.kind() {
+            EntryKind::Hardlink(_) => accessor.follow_hardlink(&file).await?,
+            _ => file,
+        };
+        match file.kind() {
+            EntryKind::File { size, .. } => {
+                log::debug!("adding '{}' to tar", path.display());
+                tar_add_file(
+                    &mut tarencoder,
+                    Some(file.contents().await?),
+                    *size,
+                    file.entry().metadata(),
+                    &path,
+                )
+                .await?
+            }
+            other => bail!("cannot create tar of {:?}", other),
+        }
     }
 
     tarencoder.finish().await.map_err(|err| {
@@ -968,6 +1198,10 @@ where
     Ok(())
 }
 
+/// Creates a zip file from `path` and writes it into `output`
+///
+/// Split metadata archives are not supported, callers need to reject them, see
+/// [`check_no_split_archive`](crate::pxar::payload::check_no_split_archive).
 pub async fn create_zip<T, W, P>(output: W, accessor: Accessor<T>, path: P) -> Result<(), Error>
 where
     T: Clone + pxar::accessor::ReadAt + Unpin + Send + Sync + 'static,
@@ -1300,3 +1534,71 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use pxar::format::acl::{Default as DefaultAcl, GroupObject, Permissions, User};
+    use pxar::format::XAttr;
+
+    use super::*;
+
+    // the length prefix of a record must match the length of the whole record
+    fn check_record(record: &[u8]) {
+        let space = record.iter().position(|b| *b == b' ').unwrap();
+        let len: usize = std::str::from_utf8(&record[..space])
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(len, record.len());
+    }
+
+    #[test]
+    fn test_add_pax_record_length() {
+        // "<len> k=<value>\n" has 4 bytes besides the value and the length digits
+        for (value_len, expected) in [(4, 9), (5, 11), (93, 99), (94, 101), (95, 102)] {
+            let mut record = Vec::new();
+            add_pax_record(&mut record, b"k", &vec![b'a'; value_len]);
+            assert_eq!(record.len(), expected, "value length {value_len}");
+            check_record(&record);
+        }
+
+        for value_len in 0..1100 {
+            let mut record = Vec::new();
+            add_pax_record(&mut record, b"key", &vec![b'a'; value_len]);
+            check_record(&record);
+        }
+    }
+
+    #[test]
+    fn test_pax_records_xattr_acl() {
+        let mut metadata = Metadata::default();
+        metadata.stat.mode = 0o100640;
+        metadata.xattrs.push(XAttr::new(b"user.foo", b"bar"));
+        metadata.acl.users.push(User {
+            uid: 1000,
+            permissions: Permissions(4),
+        });
+        metadata.acl.group_obj = Some(GroupObject {
+            permissions: Permissions(6),
+        });
+        metadata.acl.default = Some(DefaultAcl {
+            user_obj_permissions: Permissions(7),
+            group_obj_permissions: Permissions(5),
+            other_permissions: Permissions(0),
+            mask_permissions: Permissions::NO_MASK,
+        });
+
+        let records = pax_records(&metadata);
+        let expected = b"29 SCHILY.xattr.user.foo=bar\n\
+            77 SCHILY.acl.access=user::rw-,user:1000:r--,group::rw-,mask::r--,other::---\n\
+            54 SCHILY.acl.default=user::rwx,group::r-x,other::---\n";
+        assert_eq!(
+            String::from_utf8_lossy(&records),
+            String::from_utf8_lossy(expected)
+        );
+
+        for record in records.split_inclusive(|b| *b == b'\n') {
+            check_record(record);
+        }
+    }
+}
@@ -51,6 +51,8 @@ pub(crate) mod create;
 pub(crate) mod dir_stack;
 pub(crate) mod extract;
 pub(crate) mod metadata;
+pub mod payload;
+pub(crate) mod tar_import;
 pub(crate) mod tools;
 
 mod flags;
@@ -61,6 +63,7 @@ pub use extract::{
     create_tar, create_zip, extract_archive, extract_sub_dir, extract_sub_dir_seq, ErrorHandler,
     OverwriteFlags, PxarExtractContext, PxarExtractOptions,
 };
+pub use tar_import::import_tar;
 
 /// The format requires to build sorted directory lookup tables in
 /// memory, so we restrict the number of allowed entries to limit
@@ -0,0 +1,438 @@
+//! Split pxar archives with a separate payload stream.
+//!
+//! In split mode, a directory is stored as two archives: the metadata archive (`.mpxar`) is a
+//! regular pxar archive, except that the contents of each regular file are replaced by an
+//! encoded [`PayloadRef`]. The payload archive (`.ppxar`) is the concatenation of all file
+//! payloads, each prefixed by a header containing [`PAYLOAD_HEADER_MAGIC`] and the payload size.
+//!
+//! Since the payloads are independent of the metadata, the payload of an unchanged file can be
+//! taken over from the previous snapshot by re-referencing the chunks it is stored in, without
+//! reading the file again.
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::mpsc::SyncSender;
+use std::sync::Arc;
+
+use anyhow::{bail, format_err, Error};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+
+use proxmox_schema::api;
+
+use pxar::accessor::aio::{Accessor, Directory};
+use pxar::accessor::ReadAt;
+use pxar::{EntryKind, Metadata};
+
+use pbs_datastore::dynamic_index::DynamicIndexReader;
+use pbs_datastore::index::IndexFile;
+
+use crate::{ReusedChunk, StreamData};
+
+/// Magic of an encoded [`PayloadRef`].
+pub const PAYLOAD_REF_MAGIC: u64 = 0x5d4f_a6b2_e1c8_7039;
+/// Size of an encoded [`PayloadRef`], which is the file size stored in the metadata archive.
+pub const PAYLOAD_REF_SIZE: u64 = 32;
+
+/// Magic of the header in front of each payload in the payload archive.
+pub const PAYLOAD_HEADER_MAGIC: u64 = 0x0a3e_97c4_52f1_b86d;
+/// Size of the header in front of each payload in the payload archive.
+pub const PAYLOAD_HEADER_SIZE: u64 = 16;
+
+// send buffered payload data in blocks of this size
+const PAYLOAD_BUFFER_SIZE: usize = 256 * 1024;
+
+// limit the number of reused chunks sent at once
+const MAX_REUSED_CHUNKS: usize = 1024;
+
+#[api]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+/// How to detect changed files when creating a pxar archive.
+pub enum PxarChangeDetectionMode {
+    /// Create a regular pxar archive, reading all files.
+    #[default]
+    Legacy,
+    /// Create a split archive, reusing the payload of files whose metadata did not change since
+    /// the previous snapshot.
+    Metadata,
+}
+
+/// Returns the names of the metadata and payload archive of a split pxar archive.
+///
+/// `archive_name` is the server archive name of the corresponding regular pxar archive, i.e.
+/// `<name>.pxar.didx`.
+pub fn split_archive_names(archive_name: &str) -> Option<(String, String)> {
+    let name = archive_name.strip_suffix(".pxar.didx")?;
+    Some((format!("{name}.mpxar.didx"), format!("{name}.ppxar.didx")))
+}
+
+/// Returns the name of the payload archive belonging to the metadata archive `archive_name`.
+pub fn payload_archive_name(archive_name: &str) -> Option<String> {
+    let name = archive_name.strip_suffix(".mpxar.didx")?;
+    Some(format!("{name}.ppxar.didx"))
+}
+
+/// Returns true if `archive_name` is the metadata or payload archive of a split pxar archive.
+///
+/// Works for both archive names (`<name>.mpxar`) and server archive names (`<name>.mpxar.didx`).
+pub fn is_split_archive(archive_name: &str) -> bool {
+    let name = archive_name.strip_suffix(".didx").unwrap_or(archive_name);
+    name.ends_with(".mpxar") || name.ends_with(".ppxar")
+}
+
+/// Fails for split pxar archives, which can only be accessed together with their payload archive.
+///
+/// Used by tools which only support regular pxar archives, `action` describes what is refused.
+pub fn check_no_split_archive(archive_name: &str, action: &str) -> Result<(), Error> {
+    if is_split_archive(archive_name) {
+        bail!(
+            "{action} is not supported for split pxar archive '{archive_name}' - use \
+            'proxmox-backup-client restore' instead"
+        );
+    }
+    Ok(())
+}
+
+/// Reference to the payload of a regular file, stored as file contents in the metadata archive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PayloadRef {
+    /// Offset of the payload header in the payload archive.
+    pub offset: u64,
+    /// Size of the payload, i.e. the file size.
+    pub size: u64,
+    /// Inode number of the file at backup time, used to detect changed files.
+    pub inode: u64,
+}
+
+impl PayloadRef {
+    pub fn to_bytes(&self) -> [u8; PAYLOAD_REF_SIZE as usize] {
+        let mut data = [0u8; PAYLOAD_REF_SIZE as usize];
+        data[0..8].copy_from_slice(&PAYLOAD_REF_MAGIC.to_le_bytes());
+        data[8..16].copy_from_slice(&self.offset.to_le_bytes());
+        data[16..24].copy_from_slice(&self.size.to_le_bytes());
+        data[24..32].copy_from_slice(&self.inode.to_le_bytes());
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        if data.len() != PAYLOAD_REF_SIZE as usize {
+            bail!("invalid payload reference size {}", data.len());
+        }
+        let read_u64 = |pos: usize| u64::from_le_bytes(data[pos..(pos + 8)].try_into().unwrap());
+        if read_u64(0) != PAYLOAD_REF_MAGIC {
+            bail!("invalid payload reference magic");
+        }
+        Ok(Self {
+            offset: read_u64(8),
+            size: read_u64(16),
+            inode: read_u64(24),
+        })
+    }
+
+    /// Read a payload reference from the contents of a regular file in a metadata archive.
+    pub fn read_from(contents: &mut dyn Read, size: u64) -> Result<Self, Error> {
+        if size != PAYLOAD_REF_SIZE {
+            bail!("regular file in metadata archive without payload reference");
+        }
+        let mut data = [0u8; PAYLOAD_REF_SIZE as usize];
+        contents.read_exact(&mut data)?;
+        Self::from_bytes(&data)
+    }
+}
+
+/// Encode the header written in front of a payload of `size` bytes.
+pub fn payload_header(size: u64) -> [u8; PAYLOAD_HEADER_SIZE as usize] {
+    let mut data = [0u8; PAYLOAD_HEADER_SIZE as usize];
+    data[0..8].copy_from_slice(&PAYLOAD_HEADER_MAGIC.to_le_bytes());
+    data[8..16].copy_from_slice(&size.to_le_bytes());
+    data
+}
+
+/// Random access reader for payload archives.
+pub trait PayloadReader: Read + Seek {}
+
+impl<T: Read + Seek> PayloadReader for T {}
+
+/// Seek to the payload referenced by `payload_ref` and check its header.
+///
+/// Returns a reader limited to the payload.
+pub fn open_payload<'a>(
+    reader: &'a mut dyn PayloadReader,
+    payload_ref: &PayloadRef,
+) -> Result<io::Take<&'a mut dyn PayloadReader>, Error> {
+    reader.seek(SeekFrom::Start(payload_ref.offset))?;
+
+    let mut header = [0u8; PAYLOAD_HEADER_SIZE as usize];
+    reader.read_exact(&mut header)?;
+    if header != payload_header(payload_ref.size) {
+        bail!(
+            "payload header mismatch at offset {} (expected size {})",
+            payload_ref.offset,
+            payload_ref.size
+        );
+    }
+
+    Ok(reader.take(payload_ref.size))
+}
+
+/// Reader type used to access the metadata archive of the previous snapshot.
+pub type PreviousReader = Arc<dyn ReadAt + Send + Sync + 'static>;
+
+/// Metadata and payload index of the previous snapshot, used to detect unchanged files.
+pub struct PreviousArchive {
+    /// Accessor for the previous metadata archive.
+    pub accessor: Accessor<PreviousReader>,
+    /// Index of the previous payload archive.
+    pub payload_index: DynamicIndexReader,
+}
+
+/// Payload writer and optional previous archive used when creating a split archive.
+pub struct SplitArchive {
+    pub(crate) writer: PayloadWriter,
+    pub(crate) previous: Option<PreviousArchive>,
+    pub(crate) reused_files: u64,
+    pub(crate) reused_bytes: u64,
+}
+
+impl SplitArchive {
+    pub fn new(writer: PayloadWriter, previous: Option<PreviousArchive>) -> Self {
+        Self {
+            writer,
+            previous,
+            reused_files: 0,
+            reused_bytes: 0,
+        }
+    }
+}
+
+/// Look up a subdirectory of a directory in a previous metadata archive.
+pub(crate) async fn lookup_previous_dir(
+    dir: &Directory<PreviousReader>,
+    dir_name: &Path,
+) -> Result<Option<Directory<PreviousReader>>, Error> {
+    match dir.lookup(dir_name).await? {
+        Some(entry) if entry.is_dir() => Ok(Some(entry.enter_directory().await?)),
+        _ => Ok(None),
+    }
+}
+
+/// Look up the payload reference of a regular file in a directory of a previous metadata archive.
+pub(crate) async fn lookup_previous_payload(
+    dir: &Directory<PreviousReader>,
+    file_name: &Path,
+) -> Result<Option<(Metadata, PayloadRef)>, Error> {
+    let entry = match dir.lookup(file_name).await? {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    match entry.kind() {
+        EntryKind::File { size, .. } if *size == PAYLOAD_REF_SIZE => (),
+        _ => return Ok(None),
+    }
+
+    let mut data = [0u8; PAYLOAD_REF_SIZE as usize];
+    entry.contents().await?.read_exact(&mut data).await?;
+
+    Ok(Some((
+        entry.entry().metadata().clone(),
+        PayloadRef::from_bytes(&data)?,
+    )))
+}
+
+// chunks of the previous payload index re-referenced at `start` of the new payload stream
+struct ReuseRange {
+    first: usize,
+    end: usize,
+    start: u64,
+}
+
+/// Writer for the payload stream of a split archive.
+///
+/// New payload data is sent as [`StreamData::Data`] in blocks, reused chunks of the previous
+/// payload archive as [`StreamData::Reused`].
+pub struct PayloadWriter {
+    tx: SyncSender<Result<StreamData, Error>>,
+    buffer: Vec<u8>,
+    position: u64,
+    reuse_range: Option<ReuseRange>,
+    reused_chunks: Vec<ReusedChunk>,
+}
+
+impl PayloadWriter {
+    pub fn new(tx: SyncSender<Result<StreamData, Error>>) -> Self {
+        Self {
+            tx,
+            buffer: Vec::with_capacity(PAYLOAD_BUFFER_SIZE),
+            position: 0,
+            reuse_range: None,
+            reused_chunks: Vec::new(),
+        }
+    }
+
+    /// Current offset in the payload stream.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn send(&self, data: StreamData) -> Result<(), Error> {
+        proxmox_async::runtime::block_in_place(|| self.tx.send(Ok(data)))
+            .map_err(|_| format_err!("payload stream closed"))
+    }
+
+    fn flush_data(&mut self) -> Result<(), Error> {
+        if !self.buffer.is_empty() {
+            let data = std::mem::replace(&mut self.buffer, Vec::with_capacity(PAYLOAD_BUFFER_SIZE));
+            self.send(StreamData::Data(data))?;
+        }
+        Ok(())
+    }
+
+    fn flush_reused(&mut self) -> Result<(), Error> {
+        if !self.reused_chunks.is_empty() {
+            let chunks = std::mem::take(&mut self.reused_chunks);
+            self.send(StreamData::Reused(chunks))?;
+        }
+        Ok(())
+    }
+
+    /// Append new payload data.
+    pub fn write_all(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.flush_reused()?;
+        self.reuse_range = None;
+
+        self.buffer.extend_from_slice(data);
+        self.position += data.len() as u64;
+
+        if self.buffer.len() >= PAYLOAD_BUFFER_SIZE {
+            self.flush_data()?;
+        }
+        Ok(())
+    }
+
+    /// Reuse the payload referenced by `payload_ref` in the previous payload archive.
+    ///
+    /// All chunks containing the payload are injected into the stream, unless they directly
+    /// follow the previously reused chunks. Returns the offset of the payload header in the new
+    /// payload stream, or `None` if the reference is out of range of the previous index.
+    pub fn reuse(
+        &mut self,
+        index: &DynamicIndexReader,
+        payload_ref: &PayloadRef,
+    ) -> Result<Option<u64>, Error> {
+        let end = payload_ref.offset + PAYLOAD_HEADER_SIZE + payload_ref.size;
+        if end > index.index_bytes() {
+            return Ok(None);
+        }
+        let (first, last) = match (
+            index.chunk_from_offset(payload_ref.offset),
+            index.chunk_from_offset(end - 1),
+        ) {
+            (Some((first, _)), Some((last, _))) => (first, last),
+            _ => return Ok(None),
+        };
+
+        let mut range = match self.reuse_range.take() {
+            Some(range) if first >= range.first && first <= range.end => range,
+            _ => {
+                self.flush_reused()?;
+                self.flush_data()?;
+                ReuseRange {
+                    first,
+                    end: first,
+                    start: self.position,
+                }
+            }
+        };
+
+        for pos in range.end..=last {
+            let info = index.chunk_info(pos).unwrap();
+            let size = info.range.end - info.range.start;
+            self.reused_chunks.push(ReusedChunk {
+                digest: info.digest,
+                size,
+            });
+            self.position += size;
+        }
+        range.end = range.end.max(last + 1);
+
+        let range_start = index.chunk_info(range.first).unwrap().range.start;
+        let offset = range.start + (payload_ref.offset - range_start);
+
+        self.reuse_range = Some(range);
+
+        if self.reused_chunks.len() >= MAX_REUSED_CHUNKS {
+            self.flush_reused()?;
+        }
+
+        Ok(Some(offset))
+    }
+
+    /// Send all remaining data, dropping the writer closes the payload stream.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.flush_reused()?;
+        self.flush_data()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_payload_ref_encoding() {
+        let payload_ref = PayloadRef {
+            offset: 4096,
+            size: 1234567,
+            inode: 42,
+        };
+        let data = payload_ref.to_bytes();
+        assert_eq!(PayloadRef::from_bytes(&data).unwrap(), payload_ref);
+        assert!(PayloadRef::from_bytes(&data[1..]).is_err());
+        assert!(PayloadRef::from_bytes(&payload_header(0)).is_err());
+    }
+
+    #[test]
+    fn test_open_payload() {
+        let mut stream = Vec::new();
+        stream.extend_from_slice(&payload_header(3));
+        stream.extend_from_slice(b"foo");
+        stream.extend_from_slice(&payload_header(6));
+        stream.extend_from_slice(b"foobar");
+
+        let mut reader = std::io::Cursor::new(stream);
+
+        let payload_ref = PayloadRef {
+            offset: PAYLOAD_HEADER_SIZE + 3,
+            size: 6,
+            inode: 0,
+        };
+        let mut data = Vec::new();
+        open_payload(&mut reader, &payload_ref)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, b"foobar");
+
+        let payload_ref = PayloadRef {
+            offset: 0,
+            size: 6,
+            inode: 0,
+        };
+        assert!(open_payload(&mut reader, &payload_ref).is_err());
+    }
+
+    #[test]
+    fn test_split_archive_names() {
+        assert_eq!(
+            split_archive_names("root.pxar.didx"),
+            Some(("root.mpxar.didx".to_string(), "root.ppxar.didx".to_string()))
+        );
+        assert_eq!(split_archive_names("disk.img.fidx"), None);
+        assert_eq!(
+            payload_archive_name("root.mpxar.didx"),
+            Some("root.ppxar.didx".to_string())
+        );
+    }
+}
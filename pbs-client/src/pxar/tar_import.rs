@@ -0,0 +1,489 @@
+//! Conversion of tar archives into pxar archives.
+//!
+//! The tar archive is read as a stream, so entries have to be in depth-first order, i.e. all
+//! entries below a directory have to follow each other. This is the case for archives created by
+//! GNU tar, bsdtar and most other tools. Directories without an entry of their own are created
+//! with default metadata.
+//!
+//! Extended attributes, file capabilities and ACLs are read from the `SCHILY.xattr.*` and
+//! `SCHILY.acl.*` pax records written by GNU tar (with `--xattrs` and `--acls`), bsdtar and star.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CString, OsStr};
+use std::io::{Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, format_err, Context, Error};
+
+use pxar::encoder::LinkOffset;
+use pxar::format::acl::{Default as DefaultAcl, Group, GroupObject, Permissions, User};
+use pxar::format::{Device, FCaps, StatxTimestamp, XAttr};
+use pxar::Metadata;
+
+use pbs_datastore::catalog::BackupCatalogWriter;
+
+type Encoder<'a, W> = pxar::encoder::sync::Encoder<'a, pxar::encoder::sync::StandardWriter<W>>;
+type TarEntry<'a, R> = (PathBuf, tar::Entry<'a, R>);
+
+/// Convert the tar archive read from `input` into a pxar archive written to `output`.
+///
+/// If a catalog is passed, all entries are also added to it.
+pub fn import_tar<R, W>(
+    input: R,
+    output: W,
+    catalog: Option<Arc<Mutex<dyn BackupCatalogWriter + Send>>>,
+) -> Result<(), Error>
+where
+    R: Read,
+    W: Write,
+{
+    let mut archive = tar::Archive::new(input);
+    let mut entries = archive.entries().context("failed to read tar archive")?;
+
+    let mut pending = next_entry(&mut entries)?;
+
+    // use the metadata of a leading "./" entry for the root directory
+    let root_metadata = match pending {
+        Some((ref path, ref mut entry))
+            if path.as_os_str().is_empty()
+                && entry.header().entry_type() == tar::EntryType::Directory =>
+        {
+            let metadata = entry_metadata(entry, pxar::format::mode::IFDIR)?;
+            pending = next_entry(&mut entries)?;
+            metadata
+        }
+        _ => default_directory_metadata(),
+    };
+
+    let mut encoder = Encoder::from_std(output, &root_metadata)?;
+
+    let mut importer = TarImporter {
+        catalog,
+        hardlinks: HashMap::new(),
+        directories: HashSet::new(),
+    };
+
+    let rest = importer.import_directory(&mut encoder, &mut entries, Path::new(""), pending)?;
+    if let Some((path, _)) = rest {
+        bail!("unexpected tar entry {path:?}");
+    }
+
+    encoder.finish()?;
+
+    Ok(())
+}
+
+struct TarImporter {
+    catalog: Option<Arc<Mutex<dyn BackupCatalogWriter + Send>>>,
+    hardlinks: HashMap<PathBuf, LinkOffset>,
+    directories: HashSet<PathBuf>,
+}
+
+impl TarImporter {
+    /// Add all entries below `dir` to `encoder`.
+    ///
+    /// Returns the first entry which is not located below `dir`.
+    fn import_directory<'a, R: Read, W: Write>(
+        &mut self,
+        encoder: &mut Encoder<'_, W>,
+        entries: &mut tar::Entries<'a, R>,
+        dir: &Path,
+        mut pending: Option<TarEntry<'a, R>>,
+    ) -> Result<Option<TarEntry<'a, R>>, Error> {
+        loop {
+            let (path, mut entry) = match pending.take() {
+                Some(pending) => pending,
+                None => return Ok(None),
+            };
+
+            let parent = match path.parent() {
+                Some(parent) => parent,
+                None => {
+                    // another entry for the root directory
+                    pending = next_entry(entries)?;
+                    continue;
+                }
+            };
+
+            if !parent.starts_with(dir) {
+                return Ok(Some((path, entry)));
+            }
+
+            if parent != dir {
+                // the parent directory has no entry of its own
+                let name = parent.strip_prefix(dir)?.components().next().unwrap();
+                let subdir = dir.join(name);
+                pending = self.add_directory(
+                    encoder,
+                    entries,
+                    &subdir,
+                    &default_directory_metadata(),
+                    Some((path, entry)),
+                )?;
+                continue;
+            }
+
+            let file_name = path.file_name().unwrap();
+            let c_file_name = CString::new(file_name.as_bytes())?;
+
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => {
+                    let metadata = entry_metadata(&mut entry, pxar::format::mode::IFDIR)?;
+                    let next = next_entry(entries)?;
+                    pending = self.add_directory(encoder, entries, &path, &metadata, next)?;
+                    continue;
+                }
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    let metadata = entry_metadata(&mut entry, pxar::format::mode::IFREG)?;
+                    let size = entry.size();
+                    if let Some(ref catalog) = self.catalog {
+                        catalog.lock().unwrap().add_file(
+                            &c_file_name,
+                            size,
+                            metadata.stat.mtime.secs,
+                        )?;
+                    }
+                    let offset = encoder
+                        .add_file(&metadata, file_name, size, &mut entry)
+                        .with_context(|| format!("failed to add file {path:?}"))?;
+                    self.hardlinks.insert(path.clone(), offset);
+                }
+                tar::EntryType::Symlink => {
+                    let metadata = entry_metadata(&mut entry, pxar::format::mode::IFLNK)?;
+                    let target = entry
+                        .link_name()?
+                        .ok_or_else(|| format_err!("symlink {path:?} without target"))?;
+                    if let Some(ref catalog) = self.catalog {
+                        catalog.lock().unwrap().add_symlink(&c_file_name)?;
+                    }
+                    encoder.add_symlink(&metadata, file_name, target)?;
+                }
+                tar::EntryType::Link => {
+                    let target = entry
+                        .link_name()?
+                        .ok_or_else(|| format_err!("hardlink {path:?} without target"))?;
+                    let target = normalize_path(&target)?;
+                    match self.hardlinks.get(&target) {
+                        Some(offset) => {
+                            if let Some(ref catalog) = self.catalog {
+                                catalog.lock().unwrap().add_hardlink(&c_file_name)?;
+                            }
+                            encoder.add_hardlink(file_name, &target, *offset)?;
+                        }
+                        None => log::warn!("skipping hardlink {path:?} to unknown file {target:?}"),
+                    }
+                }
+                tar::EntryType::Char | tar::EntryType::Block => {
+                    let is_char = entry.header().entry_type() == tar::EntryType::Char;
+                    let device = Device {
+                        major: entry.header().device_major()?.unwrap_or(0) as u64,
+                        minor: entry.header().device_minor()?.unwrap_or(0) as u64,
+                    };
+                    let file_type = if is_char {
+                        pxar::format::mode::IFCHR
+                    } else {
+                        pxar::format::mode::IFBLK
+                    };
+                    let metadata = entry_metadata(&mut entry, file_type)?;
+                    if let Some(ref catalog) = self.catalog {
+                        let mut catalog = catalog.lock().unwrap();
+                        if is_char {
+                            catalog.add_char_device(&c_file_name)?;
+                        } else {
+                            catalog.add_block_device(&c_file_name)?;
+                        }
+                    }
+                    encoder.add_device(&metadata, file_name, device)?;
+                }
+                tar::EntryType::Fifo => {
+                    let metadata = entry_metadata(&mut entry, pxar::format::mode::IFIFO)?;
+                    if let Some(ref catalog) = self.catalog {
+                        catalog.lock().unwrap().add_fifo(&c_file_name)?;
+                    }
+                    encoder.add_fifo(&metadata, file_name)?;
+                }
+                other => log::warn!("skipping tar entry {path:?} of type {other:?}"),
+            }
+
+            pending = next_entry(entries)?;
+        }
+    }
+
+    fn add_directory<'a, R: Read, W: Write>(
+        &mut self,
+        encoder: &mut Encoder<'_, W>,
+        entries: &mut tar::Entries<'a, R>,
+        path: &Path,
+        metadata: &Metadata,
+        pending: Option<TarEntry<'a, R>>,
+    ) -> Result<Option<TarEntry<'a, R>>, Error> {
+        if !self.directories.insert(path.to_owned()) {
+            bail!("tar archive is not in depth-first order, found {path:?} twice");
+        }
+
+        let file_name = path.file_name().unwrap();
+
+        if let Some(ref catalog) = self.catalog {
+            let c_file_name = CString::new(file_name.as_bytes())?;
+            catalog.lock().unwrap().start_directory(&c_file_name)?;
+        }
+
+        let mut dir_encoder = encoder.create_directory(file_name, metadata)?;
+        let rest = self.import_directory(&mut dir_encoder, entries, path, pending)?;
+        dir_encoder.finish()?;
+
+        if let Some(ref catalog) = self.catalog {
+            catalog.lock().unwrap().end_directory()?;
+        }
+
+        Ok(rest)
+    }
+}
+
+fn next_entry<'a, R: Read>(
+    entries: &mut tar::Entries<'a, R>,
+) -> Result<Option<TarEntry<'a, R>>, Error> {
+    match entries.next() {
+        Some(entry) => {
+            let entry = entry.context("failed to read tar entry")?;
+            let path = normalize_path(&entry.path()?)?;
+            Ok(Some((path, entry)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Returns the path relative to the archive root, without `.` components.
+fn normalize_path(path: &Path) -> Result<PathBuf, Error> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::RootDir | Component::CurDir => (),
+            Component::ParentDir | Component::Prefix(_) => {
+                bail!("refusing to import tar entry {path:?} outside of the archive root");
+            }
+        }
+    }
+    Ok(normalized)
+}
+
+fn default_directory_metadata() -> Metadata {
+    let mut metadata = Metadata::default();
+    metadata.stat.mode = pxar::format::mode::IFDIR | 0o755;
+    metadata.stat.mtime = StatxTimestamp::new(proxmox_time::epoch_i64(), 0);
+    metadata
+}
+
+fn entry_metadata<R: Read>(entry: &mut tar::Entry<R>, file_type: u64) -> Result<Metadata, Error> {
+    let header = entry.header();
+
+    let mut metadata = Metadata::default();
+    metadata.stat.mode = file_type | (header.mode()? as u64 & 0o7777);
+    metadata.stat.uid = header.uid()? as u32;
+    metadata.stat.gid = header.gid()? as u32;
+    metadata.stat.mtime = StatxTimestamp::new(header.mtime()? as i64, 0);
+
+    let extensions = match entry.pax_extensions()? {
+        Some(extensions) => extensions,
+        None => return Ok(metadata),
+    };
+
+    for extension in extensions {
+        let extension = extension?;
+        let key = extension.key_bytes();
+        let value = extension.value_bytes();
+
+        if let Some(name) = key.strip_prefix(b"SCHILY.xattr.") {
+            if name == b"security.capability" {
+                metadata.fcaps = Some(FCaps {
+                    data: value.to_vec(),
+                });
+            } else {
+                metadata.xattrs.push(XAttr::new(name, value));
+            }
+            continue;
+        }
+
+        match key {
+            b"mtime" => metadata.stat.mtime = parse_pax_time(value)?,
+            b"uid" => metadata.stat.uid = parse_pax_value(value)?,
+            b"gid" => metadata.stat.gid = parse_pax_value(value)?,
+            b"SCHILY.acl.access" => parse_acl(&mut metadata, value, false)?,
+            b"SCHILY.acl.default" => parse_acl(&mut metadata, value, true)?,
+            _ => (),
+        }
+    }
+
+    Ok(metadata)
+}
+
+fn parse_pax_value<T: std::str::FromStr>(value: &[u8]) -> Result<T, Error> {
+    std::str::from_utf8(value)?
+        .parse()
+        .map_err(|_| format_err!("invalid pax value {:?}", OsStr::from_bytes(value)))
+}
+
+/// Parse a pax time value of the form `<seconds>[.<fraction>]`.
+fn parse_pax_time(value: &[u8]) -> Result<StatxTimestamp, Error> {
+    let value = std::str::from_utf8(value)?;
+    let (secs, fraction) = value.split_once('.').unwrap_or((value, ""));
+
+    let secs: i64 = secs
+        .parse()
+        .map_err(|_| format_err!("invalid pax time {value:?}"))?;
+
+    let mut nanos = 0u32;
+    for (i, digit) in fraction.bytes().take(9).enumerate() {
+        if !digit.is_ascii_digit() {
+            bail!("invalid pax time {value:?}");
+        }
+        nanos += (digit - b'0') as u32 * 10u32.pow(8 - i as u32);
+    }
+
+    Ok(StatxTimestamp::new(secs, nanos))
+}
+
+fn parse_acl_permissions(text: &str) -> Result<Permissions, Error> {
+    let mut permissions = 0;
+    for c in text.chars() {
+        match c {
+            'r' => permissions |= 4,
+            'w' => permissions |= 2,
+            'x' => permissions |= 1,
+            '-' => (),
+            _ => bail!("invalid ACL permissions {text:?}"),
+        }
+    }
+    Ok(Permissions(permissions))
+}
+
+/// Parse an ACL in short text form, like `user::rwx,user:1000:r-x,group::r-x,mask::r-x,other::-`.
+///
+/// Named entries need a numeric id, either as qualifier or as additional field as written by star.
+fn parse_acl(metadata: &mut Metadata, text: &[u8], default: bool) -> Result<(), Error> {
+    let text = std::str::from_utf8(text)?;
+
+    let mut users = Vec::new();
+    let mut groups = Vec::new();
+    let mut user_obj = None;
+    let mut group_obj = None;
+    let mut other = None;
+    let mut mask = None;
+
+    for entry in text.split([',', '\n']).map(str::trim) {
+        if entry.is_empty() || entry.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = entry.split(':').collect();
+        if fields.len() < 3 {
+            bail!("invalid ACL entry {entry:?}");
+        }
+        let permissions = parse_acl_permissions(fields[2])?;
+
+        let id = if fields[1].is_empty() {
+            None
+        } else if let Ok(id) = fields[1].parse::<u64>() {
+            Some(id)
+        } else if let Some(Ok(id)) = fields.get(3).map(|id| id.parse::<u64>()) {
+            Some(id)
+        } else {
+            log::warn!("skipping ACL entry {entry:?} without numeric id");
+            continue;
+        };
+
+        match (fields[0], id) {
+            ("user" | "u", None) => user_obj = Some(permissions),
+            ("user" | "u", Some(uid)) => users.push(User { uid, permissions }),
+            ("group" | "g", None) => group_obj = Some(permissions),
+            ("group" | "g", Some(gid)) => groups.push(Group { gid, permissions }),
+            ("mask" | "m", _) => mask = Some(permissions),
+            ("other" | "o", _) => other = Some(permissions),
+            _ => bail!("invalid ACL entry {entry:?}"),
+        }
+    }
+
+    users.sort();
+    groups.sort();
+
+    if default {
+        if user_obj.is_some() || group_obj.is_some() || other.is_some() || mask.is_some() {
+            metadata.acl.default = Some(DefaultAcl {
+                user_obj_permissions: user_obj.unwrap_or(Permissions::NO_MASK),
+                group_obj_permissions: group_obj.unwrap_or(Permissions::NO_MASK),
+                other_permissions: other.unwrap_or(Permissions::NO_MASK),
+                mask_permissions: mask.unwrap_or(Permissions::NO_MASK),
+            });
+        }
+        metadata.acl.default_users = users;
+        metadata.acl.default_groups = groups;
+    } else {
+        // like for archives created from a file system, the group object permissions are only
+        // stored if there is a mask, which is mapped to the group permissions of the mode
+        if let (Some(permissions), Some(_)) = (group_obj, mask) {
+            metadata.acl.group_obj = Some(GroupObject { permissions });
+        }
+        metadata.acl.users = users;
+        metadata.acl.groups = groups;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_pax_time() {
+        let time = parse_pax_time(b"1700000000.5").unwrap();
+        assert_eq!((time.secs, time.nanos), (1700000000, 500_000_000));
+        let time = parse_pax_time(b"42").unwrap();
+        assert_eq!((time.secs, time.nanos), (42, 0));
+        assert!(parse_pax_time(b"42.x").is_err());
+    }
+
+    #[test]
+    fn test_parse_acl() {
+        let mut metadata = Metadata::default();
+        parse_acl(
+            &mut metadata,
+            b"user::rwx,user:1000:r-x,user:alice:rw-:1001,user:bob:rw-,group::r-x,mask::rwx,other::---",
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            metadata.acl.users,
+            vec![
+                User {
+                    uid: 1000,
+                    permissions: Permissions(5),
+                },
+                User {
+                    uid: 1001,
+                    permissions: Permissions(6),
+                },
+            ]
+        );
+        assert_eq!(
+            metadata.acl.group_obj,
+            Some(GroupObject {
+                permissions: Permissions(5),
+            })
+        );
+        assert!(metadata.acl.default.is_none());
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new("./a/./b/")).unwrap(),
+            PathBuf::from("a/b")
+        );
+        assert_eq!(normalize_path(Path::new("/")).unwrap(), PathBuf::new());
+        assert!(normalize_path(Path::new("a/../../b")).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::io::{Read, Write};
 //use std::os::unix::io::FromRawFd;
 use std::path::Path;
 use std::pin::Pin;
@@ -17,6 +17,9 @@ use proxmox_io::StdChannelWriter;
 
 use pbs_datastore::catalog::CatalogWriter;
 
+use crate::pxar::payload::{PayloadWriter, PreviousArchive, SplitArchive};
+use crate::StreamData;
+
 /// Stream implementation to encode and upload .pxar archives.
 ///
 /// The hyper client needs an async Stream for file upload, so we
@@ -40,12 +43,45 @@ impl PxarBackupStream {
         dir: Dir,
         catalog: Arc<Mutex<CatalogWriter<W>>>,
         options: crate::pxar::PxarCreateOptions,
+    ) -> Result<Self, Error> {
+        Self::spawn(dir, catalog, options, None, Arc::new(Mutex::new(None)))
+    }
+
+    /// Create a split archive, returning the metadata and the payload stream.
+    ///
+    /// If `previous` is set, payloads of unchanged files are taken over from the previous
+    /// snapshot.
+    pub fn new_split<W: Write + Send + 'static>(
+        dir: Dir,
+        catalog: Arc<Mutex<CatalogWriter<W>>>,
+        options: crate::pxar::PxarCreateOptions,
+        previous: Option<PreviousArchive>,
+    ) -> Result<(Self, PxarPayloadStream), Error> {
+        let (payload_tx, payload_rx) = std::sync::mpsc::sync_channel(10);
+        let split = SplitArchive::new(PayloadWriter::new(payload_tx), previous);
+
+        let error = Arc::new(Mutex::new(None));
+        let payload_stream = PxarPayloadStream {
+            rx: Some(payload_rx),
+            error: Arc::clone(&error),
+        };
+
+        let stream = Self::spawn(dir, catalog, options, Some(split), error)?;
+
+        Ok((stream, payload_stream))
+    }
+
+    fn spawn<W: Write + Send + 'static>(
+        dir: Dir,
+        catalog: Arc<Mutex<CatalogWriter<W>>>,
+        options: crate::pxar::PxarCreateOptions,
+        split: Option<SplitArchive>,
+        error: Arc<Mutex<Option<String>>>,
     ) -> Result<Self, Error> {
         let (tx, rx) = std::sync::mpsc::sync_channel(10);
 
         let buffer_size = 256 * 1024;
 
-        let error = Arc::new(Mutex::new(None));
         let error2 = Arc::clone(&error);
         let handler = async move {
             let writer = TokioWriterAdapter::new(std::io::BufWriter::with_capacity(
@@ -64,6 +100,7 @@ impl PxarBackupStream {
                 },
                 Some(catalog),
                 options,
+                split,
             )
             .await
             {
@@ -83,6 +120,51 @@ impl PxarBackupStream {
         })
     }
 
+    /// Create an archive from the tar archive read from `input`.
+    ///
+    /// See [`import_tar`](crate::pxar::import_tar) for details.
+    pub fn from_tar<R: Read + Send + 'static, W: Write + Send + 'static>(
+        input: R,
+        catalog: Arc<Mutex<CatalogWriter<W>>>,
+    ) -> Result<Self, Error> {
+        let (tx, rx) = std::sync::mpsc::sync_channel(10);
+
+        let buffer_size = 256 * 1024;
+
+        let error = Arc::new(Mutex::new(None));
+        let error2 = Arc::clone(&error);
+        let handler = async move {
+            let result = tokio::task::spawn_blocking(move || {
+                let mut writer =
+                    std::io::BufWriter::with_capacity(buffer_size, StdChannelWriter::new(tx));
+                crate::pxar::import_tar(input, &mut writer, Some(catalog))?;
+                writer.flush()?;
+                Ok::<_, Error>(())
+            })
+            .await;
+
+            let result = match result {
+                Ok(result) => result,
+                Err(err) => Err(format_err!("tar import failed - {err}")),
+            };
+
+            if let Err(err) = result {
+                let mut error = error2.lock().unwrap();
+                *error = Some(err.to_string());
+            }
+        };
+
+        let (handle, registration) = AbortHandle::new_pair();
+        let future = Abortable::new(handler, registration);
+        tokio::spawn(future);
+
+        Ok(Self {
+            rx: Some(rx),
+            handle: Some(handle),
+            error,
+        })
+    }
+
     pub fn open<W: Write + Send + 'static>(
         dirname: &Path,
         catalog: Arc<Mutex<CatalogWriter<W>>>,
@@ -92,6 +174,17 @@ impl PxarBackupStream {
 
         Self::new(dir, catalog, options)
     }
+
+    pub fn open_split<W: Write + Send + 'static>(
+        dirname: &Path,
+        catalog: Arc<Mutex<CatalogWriter<W>>>,
+        options: crate::pxar::PxarCreateOptions,
+        previous: Option<PreviousArchive>,
+    ) -> Result<(Self, PxarPayloadStream), Error> {
+        let dir = nix::dir::Dir::open(dirname, OFlag::O_DIRECTORY, Mode::empty())?;
+
+        Self::new_split(dir, catalog, options, previous)
+    }
 }
 
 impl Stream for PxarBackupStream {
@@ -118,3 +211,34 @@ impl Stream for PxarBackupStream {
         }
     }
 }
+
+/// Payload stream of a split archive created by [`PxarBackupStream::new_split`].
+pub struct PxarPayloadStream {
+    rx: Option<std::sync::mpsc::Receiver<Result<StreamData, Error>>>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl Stream for PxarPayloadStream {
+    type Item = Result<StreamData, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Option<Self::Item>> {
+        {
+            // limit lock scope
+            let error = self.error.lock().unwrap();
+            if let Some(ref msg) = *error {
+                return Poll::Ready(Some(Err(format_err!("{}", msg))));
+            }
+        }
+
+        match proxmox_async::runtime::block_in_place(|| self.rx.as_ref().unwrap().recv()) {
+            Ok(data) => Poll::Ready(Some(data)),
+            Err(_) => {
+                let error = self.error.lock().unwrap();
+                if let Some(ref msg) = *error {
+                    return Poll::Ready(Some(Err(format_err!("{}", msg))));
+                }
+                Poll::Ready(None) // channel closed, no error
+            }
+        }
+    }
+}
@@ -100,13 +100,14 @@ pub fn check_acl_path(path: &str) -> Result<(), Error> {
                 return Ok(());
             }
             match components[1] {
-                "certificates" | "disks" | "log" | "status" | "tasks" | "time" => {
+                "certificates" | "disks" | "log" | "notifications" | "status" | "tasks"
+                | "time" => {
                     if components_len == 2 {
                         return Ok(());
                     }
                 }
-                "services" => {
-                    // /system/services/{service}
+                "services" | "sync-encryption-keys" => {
+                    // /system/services/{service}, /system/sync-encryption-keys/{key}
                     if components_len <= 3 {
                         return Ok(());
                     }
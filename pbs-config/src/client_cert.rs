@@ -0,0 +1,78 @@
+//! TLS client certificates registered for certificate based logins
+use std::collections::HashMap;
+
+use anyhow::Error;
+use lazy_static::lazy_static;
+
+use proxmox_schema::{ApiType, Schema};
+use proxmox_section_config::{SectionConfig, SectionConfigData, SectionConfigPlugin};
+
+use pbs_api_types::{ClientCertificate, Userid, CLIENT_CERT_ID_SCHEMA};
+
+use crate::{open_backup_lockfile, replace_backup_config, BackupLockGuard};
+
+lazy_static! {
+    /// Static [`SectionConfig`] to access parser/writer functions.
+    pub static ref CONFIG: SectionConfig = init();
+}
+
+fn init() -> SectionConfig {
+    let obj_schema = match ClientCertificate::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+
+    let plugin = SectionConfigPlugin::new(
+        "client-cert".to_string(),
+        Some("id".to_string()),
+        obj_schema,
+    );
+    let mut config = SectionConfig::new(&CLIENT_CERT_ID_SCHEMA);
+    config.register_plugin(plugin);
+
+    config
+}
+
+/// Configuration file name
+pub const CLIENT_CERT_CFG_FILENAME: &str = "/etc/proxmox-backup/client-certs.cfg";
+/// Lock file name (used to prevent concurrent access)
+pub const CLIENT_CERT_CFG_LOCKFILE: &str = "/etc/proxmox-backup/.client-certs.lck";
+
+/// Get exclusive lock
+pub fn lock_config() -> Result<BackupLockGuard, Error> {
+    open_backup_lockfile(CLIENT_CERT_CFG_LOCKFILE, None, true)
+}
+
+/// Read and parse the configuration file
+pub fn config() -> Result<(SectionConfigData, [u8; 32]), Error> {
+    let content =
+        proxmox_sys::fs::file_read_optional_string(CLIENT_CERT_CFG_FILENAME)?.unwrap_or_default();
+
+    let digest = openssl::sha::sha256(content.as_bytes());
+    let data = CONFIG.parse(CLIENT_CERT_CFG_FILENAME, &content)?;
+    Ok((data, digest))
+}
+
+/// Save the configuration file
+pub fn save_config(config: &SectionConfigData) -> Result<(), Error> {
+    let raw = CONFIG.write(CLIENT_CERT_CFG_FILENAME, config)?;
+    replace_backup_config(CLIENT_CERT_CFG_FILENAME, raw.as_bytes())
+}
+
+/// Look up the certificate registered for `userid` with the given fingerprint.
+pub fn lookup(userid: &Userid, fingerprint: &str) -> Result<Option<ClientCertificate>, Error> {
+    let (data, _digest) = config()?;
+    let list: Vec<ClientCertificate> = data.convert_to_typed_array("client-cert")?;
+
+    Ok(list
+        .into_iter()
+        .find(|cert| cert.userid == *userid && cert.fingerprint.eq_ignore_ascii_case(fingerprint)))
+}
+
+// shell completion helper
+pub fn complete_client_cert_id(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match config() {
+        Ok((data, _digest)) => data.sections.keys().map(|id| id.to_string()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
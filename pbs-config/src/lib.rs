@@ -1,14 +1,18 @@
 pub mod acl;
 mod cached_user_info;
 pub use cached_user_info::CachedUserInfo;
+pub mod client_cert;
 pub mod datastore;
 pub mod domains;
 pub mod drive;
 pub mod media_pool;
 pub mod metrics;
 pub mod network;
+pub mod notifications;
 pub mod prune;
+pub mod quota;
 pub mod remote;
+pub mod s3;
 pub mod sync;
 pub mod tape_job;
 pub mod token_shadow;
@@ -6,7 +6,7 @@ use lazy_static::lazy_static;
 use proxmox_schema::*;
 use proxmox_section_config::{SectionConfig, SectionConfigData, SectionConfigPlugin};
 
-use pbs_api_types::{InfluxDbHttp, InfluxDbUdp, METRIC_SERVER_ID_SCHEMA};
+use pbs_api_types::{InfluxDbHttp, InfluxDbUdp, OtlpHttp, METRIC_SERVER_ID_SCHEMA};
 
 use crate::{open_backup_lockfile, BackupLockGuard};
 
@@ -35,6 +35,16 @@ fn init() -> SectionConfig {
 
     config.register_plugin(http_plugin);
 
+    const OTLP_HTTP_SCHEMA: &ObjectSchema = OtlpHttp::API_SCHEMA.unwrap_object_schema();
+
+    let otlp_http_plugin = SectionConfigPlugin::new(
+        "otlp-http".to_string(),
+        Some("name".to_string()),
+        OTLP_HTTP_SCHEMA,
+    );
+
+    config.register_plugin(otlp_http_plugin);
+
     config
 }
 
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use anyhow::Error;
+use anyhow::{bail, Error};
 use lazy_static::lazy_static;
 
 use proxmox_schema::*;
@@ -30,6 +30,8 @@ fn init() -> SectionConfig {
 
 pub const REMOTE_CFG_FILENAME: &str = "/etc/proxmox-backup/remote.cfg";
 pub const REMOTE_CFG_LOCKFILE: &str = "/etc/proxmox-backup/.remote.lck";
+/// Directory holding TLS client certificates and keys used to log in to remotes
+pub const REMOTE_CERT_DIR: &str = "/etc/proxmox-backup/remote-certs";
 
 /// Get exclusive lock
 pub fn lock_config() -> Result<BackupLockGuard, Error> {
@@ -50,6 +52,43 @@ pub fn save_config(config: &SectionConfigData) -> Result<(), Error> {
     crate::replace_backup_config(REMOTE_CFG_FILENAME, raw.as_bytes())
 }
 
+/// Path of the TLS client certificate used to log in to remote `name`
+pub fn client_cert_path(name: &str) -> String {
+    format!("{REMOTE_CERT_DIR}/{name}.pem")
+}
+
+/// Path of the private key belonging to the TLS client certificate of remote `name`
+pub fn client_key_path(name: &str) -> String {
+    format!("{REMOTE_CERT_DIR}/{name}.key")
+}
+
+/// Store the TLS client certificate and private key used to log in to remote `name`
+///
+/// Both files are readable by group 'backup', as sync jobs run as user 'backup'.
+pub fn store_client_cert(name: &str, cert_pem: &[u8], key_pem: &[u8]) -> Result<(), Error> {
+    let backup_user = crate::backup_user()?;
+    let options = proxmox_sys::fs::CreateOptions::new()
+        .perm(nix::sys::stat::Mode::from_bits_truncate(0o0750))
+        .owner(nix::unistd::ROOT)
+        .group(backup_user.gid);
+    proxmox_sys::fs::create_path(REMOTE_CERT_DIR, None, Some(options))?;
+
+    crate::replace_backup_config(client_key_path(name), key_pem)?;
+    crate::replace_backup_config(client_cert_path(name), cert_pem)
+}
+
+/// Remove the TLS client certificate and private key of remote `name`, if any
+pub fn remove_client_cert(name: &str) -> Result<(), Error> {
+    for path in [client_cert_path(name), client_key_path(name)] {
+        if let Err(err) = std::fs::remove_file(&path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                bail!("unable to remove '{path}' - {err}");
+            }
+        }
+    }
+    Ok(())
+}
+
 // shell completion helper
 pub fn complete_remote_name(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
     match config() {
@@ -215,6 +215,7 @@ impl BackupGroup {
         }
 
         if removed_all_snaps {
+            self.store.backend_remove_dir(&path)?;
             std::fs::remove_dir_all(&path).map_err(|err| {
                 format_err!("removing group directory {:?} failed - {}", path, err)
             })?;
@@ -455,6 +456,7 @@ impl BackupDir {
         }
 
         log::info!("removing backup snapshot {:?}", full_path);
+        self.store.backend_remove_dir(&full_path)?;
         std::fs::remove_dir_all(&full_path).map_err(|err| {
             format_err!("removing backup snapshot {:?} failed - {}", full_path, err,)
         })?;
@@ -513,7 +515,7 @@ impl BackupDir {
 
         // atomic replace invalidates flock - no other writes past this point!
         replace_file(&path, raw_data, CreateOptions::new(), false)?;
-        Ok(())
+        self.store.backend_upload_file(&path)
     }
 
     /// Cleans up the backup directory by removing any file not mentioned in the manifest.
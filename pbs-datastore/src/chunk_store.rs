@@ -1,6 +1,8 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 
 use anyhow::{bail, format_err, Error};
 
@@ -10,15 +12,29 @@ use proxmox_sys::fs::{create_dir, create_path, file_type_from_file_stat, CreateO
 use proxmox_sys::process_locker::{
     ProcessLockExclusiveGuard, ProcessLockSharedGuard, ProcessLocker,
 };
-use proxmox_sys::task_log;
 use proxmox_sys::WorkerTaskContext;
+use proxmox_sys::{task_log, task_warn};
 
 use crate::file_formats::{
     COMPRESSED_BLOB_MAGIC_1_0, ENCRYPTED_BLOB_MAGIC_1_0, UNCOMPRESSED_BLOB_MAGIC_1_0,
 };
+use crate::s3_client::{S3Client, S3Object, DELETE_OBJECTS_MAX_KEYS};
 use crate::DataBlob;
 
+/// Default limit for the chunk contents kept in the local cache of an S3 backend.
+pub const DEFAULT_S3_CACHE_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
 /// File system based chunk store
+///
+/// With an S3 client set, the bucket holds the actual chunks and the chunk directory acts as
+/// local cache. A cache file may be empty if the chunk content is not cached, it is still used as
+/// marker for garbage collection, as objects carry no usable access time. A cache file is only
+/// created after its object was uploaded, and garbage collection only removes objects which have
+/// a cache file. While garbage collection removes an object, the chunk is registered as pending
+/// removal, writers of the same chunk wait for the removal and upload it again.
+///
+/// The cached chunk contents are limited to `cache_size` bytes. Once the limit is reached, only
+/// markers are written, garbage collection evicts the least recently used contents.
 pub struct ChunkStore {
     name: String, // used for error reporting
     pub(crate) base: PathBuf,
@@ -26,10 +42,38 @@ pub struct ChunkStore {
     mutex: Mutex<()>,
     locker: Option<Arc<Mutex<ProcessLocker>>>,
     sync_level: DatastoreFSyncLevel,
+    s3_client: Option<Arc<S3Client>>,
+    cache_size: u64,
+    // cached chunk contents, as measured on open or by the last garbage collection, plus newly
+    // cached chunks
+    cache_usage: AtomicU64,
+    // chunks whose objects are being removed by garbage collection
+    pending_removal: Mutex<HashSet<[u8; 32]>>,
+    pending_removal_done: Condvar,
 }
 
 // TODO: what about sysctl setting vm.vfs_cache_pressure (0 - 100) ?
 
+/// Create `target` as copy-on-write clone of `source` via the FICLONE ioctl.
+fn reflink_file(source: &Path, target: &Path) -> Result<(), Error> {
+    nix::ioctl_write_int!(ficlone, 0x94, 9);
+
+    let source = std::fs::File::open(source)?;
+    let (fd, tmp_path) = proxmox_sys::fs::make_tmp_file(target, CreateOptions::new())?;
+    let file = std::fs::File::from(fd);
+
+    let result = unsafe { ficlone(file.as_raw_fd(), source.as_raw_fd() as _) }
+        .map_err(Error::from)
+        .and_then(|_| file.sync_all().map_err(Error::from))
+        .and_then(|_| std::fs::rename(&tmp_path, target).map_err(Error::from));
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+
+    result
+}
+
 pub fn verify_chunk_size(size: usize) -> Result<(), Error> {
     static SIZES: [usize; 7] = [
         64 * 1024,
@@ -63,6 +107,16 @@ fn digest_to_prefix(digest: &[u8]) -> PathBuf {
     path.into()
 }
 
+/// Object key of a chunk in the S3 backend, mirrors the local chunk store layout.
+pub fn chunk_object_key(digest: &[u8; 32]) -> String {
+    let prefix = digest_to_prefix(digest);
+    format!(
+        ".chunks/{}{}",
+        prefix.to_string_lossy(),
+        hex::encode(digest)
+    )
+}
+
 impl ChunkStore {
     #[doc(hidden)]
     pub unsafe fn panic_store() -> Self {
@@ -73,6 +127,11 @@ impl ChunkStore {
             mutex: Mutex::new(()),
             locker: None,
             sync_level: Default::default(),
+            s3_client: None,
+            cache_size: DEFAULT_S3_CACHE_SIZE,
+            cache_usage: AtomicU64::new(0),
+            pending_removal: Mutex::new(HashSet::new()),
+            pending_removal_done: Condvar::new(),
         }
     }
 
@@ -192,9 +251,67 @@ impl ChunkStore {
             locker: Some(locker),
             mutex: Mutex::new(()),
             sync_level,
+            s3_client: None,
+            cache_size: DEFAULT_S3_CACHE_SIZE,
+            cache_usage: AtomicU64::new(0),
+            pending_removal: Mutex::new(HashSet::new()),
+            pending_removal_done: Condvar::new(),
         })
     }
 
+    /// Use an S3 bucket as backing storage, the chunk directory is used as local cache.
+    ///
+    /// This measures the size of the local cache, so that its limit is also enforced before the
+    /// next garbage collection.
+    pub fn with_s3_client(mut self, s3_client: Option<Arc<S3Client>>) -> Result<Self, Error> {
+        if s3_client.is_some() {
+            let cache_usage = self.measure_cache_usage()?;
+            self.cache_usage.store(cache_usage, Ordering::SeqCst);
+        }
+        self.s3_client = s3_client;
+        Ok(self)
+    }
+
+    // Sum up the size of all cached chunk contents.
+    fn measure_cache_usage(&self) -> Result<u64, Error> {
+        use nix::sys::stat::fstatat;
+
+        let mut cache_usage = 0;
+        for (entry, _percentage, bad) in self.get_chunk_iterator()? {
+            let entry = entry.map_err(|err| {
+                format_err!(
+                    "chunk iterator on chunk store '{}' failed - {err}",
+                    self.name
+                )
+            })?;
+            if bad {
+                continue;
+            }
+            if let Ok(stat) = fstatat(
+                entry.parent_fd(),
+                entry.file_name(),
+                nix::fcntl::AtFlags::AT_SYMLINK_NOFOLLOW,
+            ) {
+                if file_type_from_file_stat(&stat) == Some(nix::dir::Type::File) {
+                    cache_usage += stat.st_size as u64;
+                }
+            }
+        }
+        Ok(cache_usage)
+    }
+
+    /// Limit the chunk contents kept in the local cache of the S3 backend, defaults to
+    /// [`DEFAULT_S3_CACHE_SIZE`].
+    pub fn with_cache_size(mut self, cache_size: Option<u64>) -> Self {
+        self.cache_size = cache_size.unwrap_or(DEFAULT_S3_CACHE_SIZE);
+        self
+    }
+
+    /// The S3 client of the backing bucket, if any.
+    pub fn s3_client(&self) -> Option<&Arc<S3Client>> {
+        self.s3_client.as_ref()
+    }
+
     pub fn touch_chunk(&self, digest: &[u8; 32]) -> Result<(), Error> {
         // unwrap: only `None` in unit tests
         assert!(self.locker.is_some());
@@ -368,6 +485,17 @@ impl ChunkStore {
 
         min_atime -= 300; // add 5 mins gap for safety
 
+        // the local cache files are mostly empty markers, account the size of the objects
+        let mut object_sizes = self
+            .s3_client
+            .as_deref()
+            .map(|s3_client| ChunkObjectSizes::new(s3_client, &self.name));
+        let mut cached_chunks = Vec::new();
+        let mut removal = PendingRemoval {
+            store: self,
+            chunks: Vec::new(),
+        };
+
         let mut last_percentage = 0;
         let mut chunk_count = 0;
 
@@ -390,6 +518,17 @@ impl ChunkStore {
 
             let filename = entry.file_name();
 
+            // the iterator only yields hex digest file names (with suffix for bad chunks)
+            let mut digest = [0u8; 32];
+            let is_object = object_sizes.is_some()
+                && !bad
+                && hex::decode_to_slice(filename.to_bytes(), &mut digest).is_ok();
+
+            let object_size = match object_sizes {
+                Some(ref mut object_sizes) if is_object => Some(object_sizes.size(&digest)?),
+                _ => None,
+            };
+
             let lock = self.mutex.lock();
 
             if let Ok(stat) = fstatat(dirfd, filename, nix::fcntl::AtFlags::AT_SYMLINK_NOFOLLOW) {
@@ -401,9 +540,21 @@ impl ChunkStore {
 
                 chunk_count += 1;
 
+                let size = object_size.unwrap_or(stat.st_size as u64);
+
                 if stat.st_atime < min_atime {
                     //let age = now - stat.st_atime;
                     //println!("UNLINK {}  {:?}", age/(3600*24), filename);
+                    if is_object {
+                        // objects are removed in batches, writers of these chunks wait for it
+                        self.pending_removal.lock().unwrap().insert(digest);
+                        removal.chunks.push((digest, size));
+                        drop(lock);
+                        if removal.chunks.len() >= DELETE_OBJECTS_MAX_KEYS {
+                            self.remove_chunk_objects(&mut removal, status)?;
+                        }
+                        continue;
+                    }
                     if let Err(err) = unlinkat(Some(dirfd), filename, UnlinkatFlags::NoRemoveDir) {
                         if bad {
                             status.still_bad += 1;
@@ -418,24 +569,159 @@ impl ChunkStore {
                     } else {
                         status.removed_chunks += 1;
                     }
-                    status.removed_bytes += stat.st_size as u64;
+                    status.removed_bytes += size;
+                    drop(lock);
+                    continue;
                 } else if stat.st_atime < oldest_writer {
                     if bad {
                         status.still_bad += 1;
                     } else {
                         status.pending_chunks += 1;
                     }
-                    status.pending_bytes += stat.st_size as u64;
+                    status.pending_bytes += size;
                 } else {
                     if !bad {
                         status.disk_chunks += 1;
                     }
-                    status.disk_bytes += stat.st_size as u64;
+                    status.disk_bytes += size;
+                }
+
+                if is_object && stat.st_size > 0 {
+                    cached_chunks.push((stat.st_atime, digest, stat.st_size as u64));
                 }
             }
             drop(lock);
         }
 
+        if !removal.chunks.is_empty() {
+            self.remove_chunk_objects(&mut removal, status)?;
+        }
+
+        if self.s3_client.is_some() {
+            self.evict_cached_chunks(cached_chunks, worker)?;
+        }
+
+        Ok(())
+    }
+
+    // Remove the objects of unused chunks with a single request, then their cache files.
+    fn remove_chunk_objects(
+        &self,
+        removal: &mut PendingRemoval,
+        status: &mut GarbageCollectionStatus,
+    ) -> Result<(), Error> {
+        // unwrap: only called with an S3 client
+        let s3_client = self.s3_client.as_ref().unwrap();
+
+        let keys: Vec<String> = removal
+            .chunks
+            .iter()
+            .map(|(digest, _size)| chunk_object_key(digest))
+            .collect();
+
+        let failed: HashSet<String> =
+            match proxmox_async::runtime::block_on(s3_client.delete_objects(&keys)) {
+                Ok(failed) => failed.into_iter().collect(),
+                Err(err) => bail!(
+                    "removing chunk objects failed on store '{}' - {err}",
+                    self.name
+                ),
+            };
+
+        let _lock = self.mutex.lock();
+        for ((digest, size), key) in removal.chunks.iter().zip(keys.iter()) {
+            if failed.contains(key) {
+                continue; // keep the cache file, so the next run tries again
+            }
+            let (chunk_path, digest_str) = self.chunk_path(digest);
+            if let Err(err) = std::fs::remove_file(&chunk_path) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    bail!(
+                        "unlinking chunk {digest_str} failed on store '{}' - {err}",
+                        self.name,
+                    );
+                }
+            }
+            status.removed_chunks += 1;
+            status.removed_bytes += size;
+        }
+        removal.clear();
+
+        if let Some(key) = failed.iter().next() {
+            bail!(
+                "removing {} chunk objects failed on store '{}', e.g. {key}",
+                failed.len(),
+                self.name,
+            );
+        }
+
+        Ok(())
+    }
+
+    // Reduce the cached chunk contents to the cache size by replacing the least recently used
+    // ones with empty markers. `cached_chunks` contains the access time, digest and size.
+    fn evict_cached_chunks(
+        &self,
+        mut cached_chunks: Vec<(i64, [u8; 32], u64)>,
+        worker: &dyn WorkerTaskContext,
+    ) -> Result<(), Error> {
+        let mut cache_usage: u64 = cached_chunks.iter().map(|(_, _, size)| size).sum();
+
+        cached_chunks.sort_unstable_by_key(|(atime, _, _)| *atime);
+
+        let mut evicted = 0;
+        for (_atime, digest, size) in cached_chunks {
+            if cache_usage <= self.cache_size {
+                break;
+            }
+            worker.check_abort()?;
+
+            let (chunk_path, digest_str) = self.chunk_path(&digest);
+
+            let _lock = self.mutex.lock();
+            // might have been removed or replaced in the meantime
+            match std::fs::symlink_metadata(&chunk_path) {
+                Ok(metadata) if metadata.len() == size => (),
+                _ => continue,
+            }
+            // replace instead of truncate, readers keep the old content
+            proxmox_sys::fs::replace_file(&chunk_path, b"", CreateOptions::new(), false).map_err(
+                |err| format_err!("evicting chunk {digest_str} from cache failed - {err}"),
+            )?;
+            cache_usage -= size;
+            evicted += 1;
+        }
+
+        if evicted > 0 {
+            task_log!(worker, "evicted {evicted} chunks from the local cache");
+        }
+        self.cache_usage.store(cache_usage, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    // Write the local cache file of a chunk, only writes an empty marker if the cache is full.
+    // Must be called with the mutex held.
+    fn cache_chunk(&self, chunk_path: &Path, raw_data: &[u8]) -> Result<(), Error> {
+        let size = raw_data.len() as u64;
+        let cache_usage = self.cache_usage.load(Ordering::SeqCst);
+        let data: &[u8] = if cache_usage + size <= self.cache_size {
+            raw_data
+        } else {
+            b""
+        };
+
+        proxmox_sys::fs::replace_file(
+            chunk_path,
+            data,
+            CreateOptions::new(),
+            self.sync_level == DatastoreFSyncLevel::File,
+        )?;
+
+        if !data.is_empty() {
+            self.cache_usage.fetch_add(size, Ordering::SeqCst);
+        }
+
         Ok(())
     }
 
@@ -445,6 +731,10 @@ impl ChunkStore {
 
         //println!("DIGEST {}", hex::encode(digest));
 
+        if let Some(ref s3_client) = self.s3_client {
+            return self.insert_chunk_s3(s3_client, chunk, digest);
+        }
+
         let (chunk_path, digest_str) = self.chunk_path(digest);
 
         let lock = self.mutex.lock();
@@ -522,6 +812,266 @@ impl ChunkStore {
         Ok((false, encoded_size))
     }
 
+    // Upload a chunk to the bucket and add it to the local cache.
+    //
+    // The upload runs without holding the mutex, the cache file is only created afterwards. If
+    // garbage collection removed the object in the meantime, the chunk is uploaded again.
+    fn insert_chunk_s3(
+        &self,
+        s3_client: &S3Client,
+        chunk: &DataBlob,
+        digest: &[u8; 32],
+    ) -> Result<(bool, u64), Error> {
+        let (chunk_path, digest_str) = self.chunk_path(digest);
+        let raw_data = chunk.raw_data();
+        let encoded_size = raw_data.len() as u64;
+        let name = &self.name;
+
+        loop {
+            // a chunk known to the local cache (possibly only as empty marker) is present in
+            // the bucket
+            if self.touch_cached_chunk(&chunk_path, digest)? {
+                return Ok((true, encoded_size));
+            }
+
+            let key = chunk_object_key(digest);
+            proxmox_async::runtime::block_on(s3_client.put_object(&key, raw_data.to_vec()))
+                .map_err(|err| {
+                    format_err!("uploading chunk {digest_str} to store '{name}' failed - {err}")
+                })?;
+
+            let _lock = self.mutex.lock();
+            if self.pending_removal.lock().unwrap().contains(digest) {
+                continue; // the object might have been removed after the upload
+            }
+            if std::fs::symlink_metadata(&chunk_path).is_ok() {
+                // inserted by another writer in the meantime
+                self.touch_chunk(digest)?;
+                return Ok((true, encoded_size));
+            }
+            self.cache_chunk(&chunk_path, raw_data).map_err(|err| {
+                format_err!("inserting chunk on store '{name}' failed for {digest_str} - {err}")
+            })?;
+
+            return Ok((false, encoded_size));
+        }
+    }
+
+    // Touch the cache file of a chunk, after waiting for a running removal of its object.
+    //
+    // Returns `false` if the chunk is not in the local cache.
+    fn touch_cached_chunk(&self, chunk_path: &Path, digest: &[u8; 32]) -> Result<bool, Error> {
+        loop {
+            let lock = self.mutex.lock();
+            if self.pending_removal.lock().unwrap().contains(digest) {
+                drop(lock);
+                let mut pending = self.pending_removal.lock().unwrap();
+                while pending.contains(digest) {
+                    pending = self.pending_removal_done.wait(pending).unwrap();
+                }
+                continue;
+            }
+
+            return match std::fs::symlink_metadata(chunk_path) {
+                Ok(metadata) if !metadata.is_file() => bail!(
+                    "got unexpected file type on store '{}' for chunk {}",
+                    self.name,
+                    hex::encode(digest),
+                ),
+                Ok(_) => {
+                    self.touch_chunk(digest)?;
+                    Ok(true)
+                }
+                Err(_) => Ok(false),
+            };
+        }
+    }
+
+    /// Insert a chunk by hard linking or reflinking the chunk file at `source`.
+    ///
+    /// This avoids copying the chunk data when syncing between datastores on the same host.
+    /// Returns `false` if the chunk could not be linked, e.g. because the stores are located on
+    /// different file systems or this store has an S3 backend. In that case the chunk needs to
+    /// be inserted via [`insert_chunk`](Self::insert_chunk).
+    ///
+    /// The CRC of the source chunk is checked first, so a corrupt chunk is never shared with
+    /// this store.
+    pub fn insert_chunk_link(&self, source: &Path, digest: &[u8; 32]) -> Result<bool, Error> {
+        // unwrap: only `None` in unit tests
+        assert!(self.locker.is_some());
+
+        if self.s3_client.is_some() {
+            return Ok(false);
+        }
+
+        let (chunk_path, digest_str) = self.chunk_path(digest);
+
+        let lock = self.mutex.lock();
+
+        if std::fs::symlink_metadata(&chunk_path).is_ok() {
+            self.touch_chunk(digest)?;
+            return Ok(true);
+        }
+
+        let mut file = std::fs::File::open(source)
+            .map_err(|err| format_err!("unable to open source chunk {digest_str} - {err}"))?;
+        DataBlob::load_from_reader(&mut file)
+            .map_err(|err| format_err!("source chunk {digest_str} is corrupt - {err}"))?;
+
+        // hard links fail across file systems, reflinks might still work there (e.g. btrfs
+        // subvolumes), both need a file system supporting them
+        if std::fs::hard_link(source, &chunk_path).is_err()
+            && reflink_file(source, &chunk_path).is_err()
+        {
+            return Ok(false);
+        }
+
+        if self.sync_level == DatastoreFSyncLevel::File {
+            let chunk_dir_path = chunk_path
+                .parent()
+                .ok_or_else(|| format_err!("unable to get chunk dir"))?;
+            let dir = std::fs::File::open(chunk_dir_path)?;
+            nix::unistd::fsync(dir.as_raw_fd()).map_err(|err| {
+                format_err!(
+                    "fsync failed for chunk {digest_str} on store '{}': {err}",
+                    self.name
+                )
+            })?;
+        }
+
+        drop(lock);
+
+        // a hard link shares the access time with the source, update it for this store's GC
+        self.touch_chunk(digest)?;
+
+        Ok(true)
+    }
+
+    /// Load a chunk from the S3 bucket and add its content to the local cache.
+    pub fn fetch_chunk(&self, digest: &[u8; 32]) -> Result<DataBlob, Error> {
+        let s3_client = match self.s3_client {
+            Some(ref s3_client) => s3_client,
+            None => bail!("chunk store '{}' has no s3 backend", self.name),
+        };
+
+        let (chunk_path, digest_str) = self.chunk_path(digest);
+        let raw_data =
+            proxmox_async::runtime::block_on(s3_client.get_object(&chunk_object_key(digest)))?
+                .ok_or_else(|| {
+                    format_err!(
+                        "chunk {digest_str} missing in bucket '{}'",
+                        s3_client.bucket()
+                    )
+                })?;
+        let chunk = DataBlob::from_raw(raw_data)?;
+
+        let _lock = self.mutex.lock();
+        // only fill existing markers, GC might have removed the chunk in the meantime
+        if matches!(std::fs::symlink_metadata(&chunk_path), Ok(metadata) if metadata.len() == 0) {
+            self.cache_chunk(&chunk_path, chunk.raw_data())
+                .map_err(|err| format_err!("caching chunk {digest_str} failed - {err}"))?;
+        }
+
+        Ok(chunk)
+    }
+
+    /// Populate the local cache from the S3 bucket.
+    ///
+    /// Creates empty marker files for all chunks and downloads all other objects (indexes,
+    /// blobs, owner files) which are missing locally.
+    pub fn refresh_cache(&self, worker: &dyn WorkerTaskContext) -> Result<(), Error> {
+        let s3_client = match self.s3_client {
+            Some(ref s3_client) => s3_client,
+            None => bail!("chunk store '{}' has no s3 backend", self.name),
+        };
+
+        let backup_user = pbs_config::backup_user()?;
+        let options = CreateOptions::new()
+            .owner(backup_user.uid)
+            .group(backup_user.gid);
+
+        task_log!(worker, "listing bucket '{}'", s3_client.bucket());
+
+        let mut chunk_count = 0;
+        let mut file_count = 0;
+        let mut token = None;
+        loop {
+            let response =
+                proxmox_async::runtime::block_on(s3_client.list_objects("", token.as_deref()))?;
+            for object in response.objects {
+                worker.check_abort()?;
+                self.refresh_cache_object(
+                    s3_client,
+                    &object,
+                    &options,
+                    worker,
+                    &mut chunk_count,
+                    &mut file_count,
+                )?;
+            }
+            match response.next_continuation_token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+
+        task_log!(
+            worker,
+            "added {file_count} files and {chunk_count} chunk markers to the local cache"
+        );
+
+        Ok(())
+    }
+
+    // Add a single object of the bucket to the local cache, see `refresh_cache`.
+    fn refresh_cache_object(
+        &self,
+        s3_client: &S3Client,
+        object: &S3Object,
+        options: &CreateOptions,
+        worker: &dyn WorkerTaskContext,
+        chunk_count: &mut usize,
+        file_count: &mut usize,
+    ) -> Result<(), Error> {
+        let path = self.base.join(&object.key);
+        if path.exists() {
+            return Ok(());
+        }
+
+        let components: Vec<&str> = object.key.split('/').collect();
+        if components[0] == ".chunks" {
+            let valid = components.len() == 3
+                && components[2].len() == 64
+                && components[2].bytes().all(|b| b.is_ascii_hexdigit())
+                && components[2].starts_with(components[1]);
+            if !valid {
+                task_warn!(worker, "ignoring unexpected object '{}'", object.key);
+                return Ok(());
+            }
+            proxmox_sys::fs::replace_file(&path, b"", options.clone(), false)?;
+            *chunk_count += 1;
+            return Ok(());
+        }
+
+        if components
+            .iter()
+            .any(|c| c.is_empty() || c.starts_with('.'))
+        {
+            task_warn!(worker, "ignoring unexpected object '{}'", object.key);
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            create_path(parent, Some(options.clone()), Some(options.clone()))?;
+        }
+        let data = proxmox_async::runtime::block_on(s3_client.get_object(&object.key))?
+            .unwrap_or_default();
+        proxmox_sys::fs::replace_file(&path, &data, options.clone(), false)?;
+        *file_count += 1;
+
+        Ok(())
+    }
+
     pub fn chunk_path(&self, digest: &[u8; 32]) -> (PathBuf, String) {
         // unwrap: only `None` in unit tests
         assert!(self.locker.is_some());
@@ -565,6 +1115,122 @@ impl ChunkStore {
     }
 }
 
+// Chunks registered as pending removal by garbage collection, see `ChunkStore::remove_chunk_objects`.
+//
+// Waiting writers are woken up once the removal is done, also if it failed.
+struct PendingRemoval<'a> {
+    store: &'a ChunkStore,
+    chunks: Vec<([u8; 32], u64)>,
+}
+
+impl PendingRemoval<'_> {
+    fn clear(&mut self) {
+        if self.chunks.is_empty() {
+            return;
+        }
+        let mut pending = self.store.pending_removal.lock().unwrap();
+        for (digest, _size) in self.chunks.drain(..) {
+            pending.remove(&digest);
+        }
+        drop(pending);
+        self.store.pending_removal_done.notify_all();
+    }
+}
+
+impl Drop for PendingRemoval<'_> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+// Sizes of the chunk objects in the bucket, listed page by page.
+//
+// The bucket listing is ordered by key, just like the chunk iterator walks the chunk directories,
+// so only the objects of the current directory need to be kept around.
+struct ChunkObjectSizes<'a> {
+    s3_client: &'a S3Client,
+    store: &'a str,
+    objects: VecDeque<S3Object>,
+    token: Option<String>,
+    listed: bool,
+    prefix: String,
+    sizes: HashMap<[u8; 32], u64>,
+}
+
+impl<'a> ChunkObjectSizes<'a> {
+    fn new(s3_client: &'a S3Client, store: &'a str) -> Self {
+        Self {
+            s3_client,
+            store,
+            objects: VecDeque::new(),
+            token: None,
+            listed: false,
+            prefix: String::new(),
+            sizes: HashMap::new(),
+        }
+    }
+
+    // Returns the object size of a chunk, 0 if there is no such object.
+    fn size(&mut self, digest: &[u8; 32]) -> Result<u64, Error> {
+        let prefix = format!(".chunks/{}", digest_to_prefix(digest).to_string_lossy());
+        if prefix != self.prefix {
+            self.load_prefix(prefix)?;
+        }
+        Ok(self.sizes.get(digest).copied().unwrap_or(0))
+    }
+
+    fn load_prefix(&mut self, prefix: String) -> Result<(), Error> {
+        self.sizes.clear();
+
+        loop {
+            let object = match self.objects.front() {
+                Some(object) => object,
+                None if self.listed => break,
+                None => {
+                    self.list_next_page()?;
+                    continue;
+                }
+            };
+
+            if object.key.as_str() < prefix.as_str() {
+                self.objects.pop_front();
+                continue;
+            }
+            if !object.key.starts_with(&prefix) {
+                break;
+            }
+
+            // unwrap: checked above
+            let object = self.objects.pop_front().unwrap();
+            let mut digest = [0u8; 32];
+            if hex::decode_to_slice(&object.key[prefix.len()..], &mut digest).is_ok() {
+                self.sizes.insert(digest, object.size);
+            }
+        }
+
+        self.prefix = prefix;
+        Ok(())
+    }
+
+    fn list_next_page(&mut self) -> Result<(), Error> {
+        let response = proxmox_async::runtime::block_on(
+            self.s3_client
+                .list_objects(".chunks/", self.token.as_deref()),
+        )
+        .map_err(|err| {
+            format_err!(
+                "listing chunk objects of store '{}' failed - {err}",
+                self.store
+            )
+        })?;
+
+        self.objects.extend(response.objects);
+        self.token = response.next_continuation_token;
+        self.listed = self.token.is_none();
+        Ok(())
+    }
+}
+
 #[test]
 fn test_chunk_store1() {
     let mut path = std::fs::canonicalize(".").unwrap(); // we need absolute path
@@ -610,3 +1276,310 @@ fn test_chunk_store1() {
 
     if let Err(_e) = std::fs::remove_dir_all(".testdir") { /* ignore */ }
 }
+
+#[test]
+fn test_chunk_store_link() {
+    let mut path = std::fs::canonicalize(".").unwrap(); // we need absolute path
+    path.push(".testdir-link");
+
+    if let Err(_e) = std::fs::remove_dir_all(&path) { /* ignore */ }
+
+    let user = nix::unistd::User::from_uid(nix::unistd::Uid::current())
+        .unwrap()
+        .unwrap();
+    let create = |name: &str| {
+        ChunkStore::create(
+            name,
+            path.join(name),
+            user.uid,
+            user.gid,
+            None,
+            DatastoreFSyncLevel::None,
+        )
+        .unwrap()
+    };
+    let source = create("source");
+    let target = create("target");
+
+    let (chunk, digest) = crate::data_blob::DataChunkBuilder::new(&[0u8, 1u8])
+        .build()
+        .unwrap();
+    source.insert_chunk(&chunk, &digest).unwrap();
+    let (source_path, _) = source.chunk_path(&digest);
+
+    assert!(target.insert_chunk_link(&source_path, &digest).unwrap());
+    assert!(target.cond_touch_chunk(&digest, false).unwrap());
+
+    // a corrupt source chunk must not be linked
+    let (chunk, digest) = crate::data_blob::DataChunkBuilder::new(&[2u8, 3u8])
+        .build()
+        .unwrap();
+    source.insert_chunk(&chunk, &digest).unwrap();
+    let (source_path, _) = source.chunk_path(&digest);
+    let mut data = std::fs::read(&source_path).unwrap();
+    let last = data.len() - 1;
+    data[last] ^= 0xff;
+    std::fs::remove_file(&source_path).unwrap();
+    std::fs::write(&source_path, data).unwrap();
+
+    assert!(target.insert_chunk_link(&source_path, &digest).is_err());
+    assert!(!target.cond_touch_chunk(&digest, false).unwrap());
+
+    if let Err(_e) = std::fs::remove_dir_all(&path) { /* ignore */ }
+}
+
+#[cfg(test)]
+mod s3_test {
+    use std::collections::BTreeMap;
+    use std::fmt::Write;
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::Error;
+    use hyper::{Body, Method, Request, Response, StatusCode};
+
+    use pbs_api_types::{
+        DatastoreFSyncLevel, GarbageCollectionStatus, S3Endpoint, S3EndpointConfig,
+    };
+    use proxmox_sys::WorkerTaskContext;
+
+    use super::{chunk_object_key, ChunkStore};
+    use crate::data_blob::DataChunkBuilder;
+    use crate::s3_client::S3Client;
+
+    type Objects = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+    // Stand-in for an S3 endpoint, ignores authentication and only supports path-style requests
+    // for the bucket 'test'.
+    async fn handle_request(
+        objects: Objects,
+        request: Request<Body>,
+    ) -> Result<Response<Body>, Error> {
+        let method = request.method().clone();
+        let query = request.uri().query().unwrap_or("").to_string();
+        let key = request
+            .uri()
+            .path()
+            .trim_start_matches("/test")
+            .trim_start_matches('/')
+            .to_string();
+        let data = hyper::body::to_bytes(request.into_body()).await?.to_vec();
+
+        let mut objects = objects.lock().unwrap();
+        let response = match method {
+            Method::PUT => {
+                objects.insert(key, data);
+                Response::new(Body::empty())
+            }
+            Method::DELETE => {
+                objects.remove(&key);
+                Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Body::empty())?
+            }
+            Method::POST if key.is_empty() && query == "delete=" => {
+                let data = String::from_utf8(data)?;
+                for key in data.split("<Key>").skip(1) {
+                    let key = key.split("</Key>").next().unwrap_or("");
+                    objects.remove(key);
+                }
+                Response::new(Body::from("<DeleteResult></DeleteResult>"))
+            }
+            Method::GET if key.is_empty() => {
+                let prefix = query
+                    .split('&')
+                    .find_map(|param| param.strip_prefix("prefix="))
+                    .unwrap_or("")
+                    .replace("%2F", "/");
+                let mut xml = String::from("<ListBucketResult>");
+                for (key, data) in objects.iter().filter(|(key, _)| key.starts_with(&prefix)) {
+                    write!(
+                        xml,
+                        "<Contents><Key>{key}</Key><Size>{}</Size></Contents>",
+                        data.len()
+                    )?;
+                }
+                xml.push_str("<IsTruncated>false</IsTruncated></ListBucketResult>");
+                Response::new(Body::from(xml))
+            }
+            Method::GET => match objects.get(&key) {
+                Some(data) => Response::new(Body::from(data.clone())),
+                None => Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Body::empty())?,
+            },
+            _ => Response::builder()
+                .status(StatusCode::NOT_IMPLEMENTED)
+                .body(Body::empty())?,
+        };
+
+        Ok(response)
+    }
+
+    fn start_s3_server(objects: Objects) -> Result<u16, Error> {
+        use hyper::server::conn::AddrIncoming;
+        use hyper::service::{make_service_fn, service_fn};
+
+        proxmox_async::runtime::block_on(async move {
+            let service = make_service_fn(move |_| {
+                let objects = Arc::clone(&objects);
+                async move {
+                    Ok::<_, Error>(service_fn(move |request| {
+                        handle_request(Arc::clone(&objects), request)
+                    }))
+                }
+            });
+
+            let incoming = AddrIncoming::bind(&([127, 0, 0, 1], 0).into())?;
+            let port = incoming.local_addr().port();
+            tokio::spawn(hyper::Server::builder(incoming).serve(service));
+
+            Ok(port)
+        })
+    }
+
+    struct TestWorker;
+
+    impl WorkerTaskContext for TestWorker {
+        fn abort_requested(&self) -> bool {
+            false
+        }
+
+        fn shutdown_requested(&self) -> bool {
+            false
+        }
+
+        fn log(&self, _level: log::Level, _message: &std::fmt::Arguments) {}
+    }
+
+    fn set_atime(store: &ChunkStore, digest: &[u8; 32], atime: i64) -> Result<(), Error> {
+        use nix::NixPath;
+
+        let (chunk_path, _) = store.chunk_path(digest);
+        let times = [
+            libc::timespec {
+                tv_sec: atime,
+                tv_nsec: 0,
+            },
+            libc::timespec {
+                tv_sec: atime,
+                tv_nsec: 0,
+            },
+        ];
+        chunk_path.with_nix_path(|cstr| unsafe {
+            nix::errno::Errno::result(libc::utimensat(-1, cstr.as_ptr(), &times[0], 0))
+        })??;
+        Ok(())
+    }
+
+    fn cache_file_len(store: &ChunkStore, digest: &[u8; 32]) -> Option<u64> {
+        let (chunk_path, _) = store.chunk_path(digest);
+        std::fs::metadata(chunk_path)
+            .ok()
+            .map(|metadata| metadata.len())
+    }
+
+    #[test]
+    fn test_chunk_store_s3() -> Result<(), Error> {
+        let objects = Objects::default();
+        let port = start_s3_server(Arc::clone(&objects))?;
+
+        let endpoint = S3Endpoint {
+            id: "test".to_string(),
+            secret_key: "secret".to_string(),
+            config: S3EndpointConfig {
+                comment: None,
+                host: "127.0.0.1".to_string(),
+                port: Some(port),
+                region: None,
+                access_key: "access".to_string(),
+                path_style: Some(true),
+                insecure_http: Some(true),
+                fingerprint: None,
+            },
+        };
+
+        let mut path = std::fs::canonicalize(".")?; // we need absolute path
+        path.push(".testdir-s3");
+        let _ = std::fs::remove_dir_all(&path);
+
+        let user = nix::unistd::User::from_uid(nix::unistd::Uid::current())?.unwrap();
+
+        let (chunk1, digest1) = DataChunkBuilder::new(&[1u8; 1024])
+            .compress(false)
+            .build()?;
+        let (chunk2, digest2) = DataChunkBuilder::new(&[2u8; 1024])
+            .compress(false)
+            .build()?;
+        let (chunk3, digest3) = DataChunkBuilder::new(&[3u8; 1024])
+            .compress(false)
+            .build()?;
+        let chunk_size = chunk1.raw_size();
+
+        let mut store = ChunkStore::create(
+            "test",
+            &path,
+            user.uid,
+            user.gid,
+            None,
+            DatastoreFSyncLevel::None,
+        )?
+        .with_s3_client(Some(Arc::new(S3Client::new(&endpoint, "test")?)))?
+        .with_cache_size(Some(chunk_size));
+
+        // upload, only the first chunk fits into the cache
+        assert!(!store.insert_chunk(&chunk1, &digest1)?.0);
+        assert!(!store.insert_chunk(&chunk2, &digest2)?.0);
+        assert_eq!(objects.lock().unwrap().len(), 2);
+        assert_eq!(
+            objects.lock().unwrap().get(&chunk_object_key(&digest2)),
+            Some(&chunk2.raw_data().to_vec())
+        );
+        assert_eq!(cache_file_len(&store, &digest1), Some(chunk_size));
+        assert_eq!(cache_file_len(&store, &digest2), Some(0));
+
+        // markers count as existing chunks
+        assert!(store.insert_chunk(&chunk2, &digest2)?.0);
+        assert_eq!(cache_file_len(&store, &digest2), Some(0));
+
+        // fetch works with full cache
+        assert_eq!(store.fetch_chunk(&digest2)?.raw_data(), chunk2.raw_data());
+        assert_eq!(cache_file_len(&store, &digest2), Some(0));
+
+        assert!(!store.insert_chunk(&chunk3, &digest3)?.0);
+
+        // sweep removes the second chunk and evicts the content of the first one
+        let now = proxmox_time::epoch_i64();
+        set_atime(&store, &digest1, now - 60)?;
+        set_atime(&store, &digest2, now - 3 * 24 * 3600)?;
+        store.cache_size = 0;
+
+        let mut status = GarbageCollectionStatus::default();
+        store.sweep_unused_chunks(now - 3600, now, &mut status, &TestWorker)?;
+
+        assert_eq!(status.removed_chunks, 1);
+        assert_eq!(status.removed_bytes, chunk_size);
+        assert_eq!(status.disk_chunks, 2);
+        assert_eq!(status.disk_bytes, 2 * chunk_size);
+        assert!(!objects
+            .lock()
+            .unwrap()
+            .contains_key(&chunk_object_key(&digest2)));
+        assert_eq!(cache_file_len(&store, &digest2), None);
+        assert_eq!(cache_file_len(&store, &digest1), Some(0));
+
+        // the cache has room again
+        store.cache_size = chunk_size;
+        assert_eq!(store.fetch_chunk(&digest3)?.raw_data(), chunk3.raw_data());
+        assert_eq!(cache_file_len(&store, &digest3), Some(chunk_size));
+
+        // the cache usage is measured when opening the store again
+        let store = ChunkStore::open("test", &path, DatastoreFSyncLevel::None)?
+            .with_s3_client(Some(Arc::new(S3Client::new(&endpoint, "test")?)))?;
+        assert_eq!(store.cache_usage.load(Ordering::SeqCst), chunk_size);
+
+        std::fs::remove_dir_all(&path)?;
+
+        Ok(())
+    }
+}
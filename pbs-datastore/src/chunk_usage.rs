@@ -0,0 +1,82 @@
+//! Deduplication aware space accounting of index files
+
+use std::collections::HashSet;
+
+use crate::index::IndexFile;
+
+/// Accumulates the logical size and the size of the unique chunks of a set of index files.
+///
+/// The logical size is the uncompressed size of the indices, while the unique size is the sum of
+/// the chunk sizes passed in by the caller, usually their (compressed) size on disk.
+#[derive(Default)]
+pub struct ChunkUsage {
+    digests: HashSet<[u8; 32]>,
+    /// Number of accounted snapshots.
+    pub snapshot_count: u64,
+    /// Sum of the sizes of all added index files.
+    pub logical_size: u64,
+    /// Sum of the sizes of all distinct chunks.
+    pub unique_size: u64,
+}
+
+impl ChunkUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add all chunks referenced by `index`.
+    ///
+    /// `chunk_size` maps the digest and uncompressed size of a chunk not accounted yet to the
+    /// size added to the unique size.
+    pub fn add_index<F>(&mut self, index: &dyn IndexFile, chunk_size: F)
+    where
+        F: Fn(&[u8; 32], u64) -> u64,
+    {
+        self.logical_size += index.index_bytes();
+        for pos in 0..index.index_count() {
+            if let Some(info) = index.chunk_info(pos) {
+                if !self.contains(&info.digest) {
+                    self.add_chunk(info.digest, chunk_size(&info.digest, info.size()));
+                }
+            }
+        }
+    }
+
+    /// Add a single chunk, returns `true` if the chunk was not referenced yet.
+    pub fn add_chunk(&mut self, digest: [u8; 32], size: u64) -> bool {
+        let is_new = self.digests.insert(digest);
+        if is_new {
+            self.unique_size += size;
+        }
+        is_new
+    }
+
+    /// Check whether a chunk is already accounted for.
+    pub fn contains(&self, digest: &[u8; 32]) -> bool {
+        self.digests.contains(digest)
+    }
+
+    /// Number of distinct chunks.
+    pub fn chunk_count(&self) -> usize {
+        self.digests.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ChunkUsage;
+
+    #[test]
+    fn test_chunk_usage_dedup() {
+        let mut usage = ChunkUsage::new();
+
+        assert!(usage.add_chunk([1u8; 32], 100));
+        assert!(usage.add_chunk([2u8; 32], 50));
+        assert!(!usage.add_chunk([1u8; 32], 100));
+
+        assert_eq!(usage.unique_size, 150);
+        assert_eq!(usage.chunk_count(), 2);
+        assert!(usage.contains(&[2u8; 32]));
+        assert!(!usage.contains(&[3u8; 32]));
+    }
+}
@@ -25,11 +25,14 @@ use pbs_api_types::{
 
 use crate::backup_info::{BackupDir, BackupGroup};
 use crate::chunk_store::ChunkStore;
+use crate::chunk_usage::ChunkUsage;
 use crate::dynamic_index::{DynamicIndexReader, DynamicIndexWriter};
 use crate::fixed_index::{FixedIndexReader, FixedIndexWriter};
 use crate::hierarchy::{ListGroups, ListGroupsType, ListNamespaces, ListNamespacesRecursive};
 use crate::index::IndexFile;
 use crate::manifest::{archive_type, ArchiveType};
+use crate::s3_client::{datastore_s3_client, S3Client};
+use crate::space_usage::{SpaceUsageScan, SpaceUsageStatus, SPACE_USAGE_FILE_NAME};
 use crate::task_tracking::{self, update_active_operations};
 use crate::DataBlob;
 
@@ -49,6 +52,26 @@ pub fn check_backup_owner(owner: &Authid, auth_id: &Authid) -> Result<(), Error>
     Ok(())
 }
 
+/// Checks whether the device backing a removable datastore is mounted on the datastore path.
+///
+/// Datastores without a backing device are always considered mounted.
+pub fn is_datastore_mounted(config: &DataStoreConfig) -> bool {
+    let uuid = match config.backing_device.as_deref() {
+        Some(uuid) => uuid,
+        None => return true,
+    };
+
+    let device = match nix::sys::stat::stat(format!("/dev/disk/by-uuid/{uuid}").as_str()) {
+        Ok(stat) => stat,
+        Err(_) => return false, // device not present
+    };
+
+    match nix::sys::stat::stat(config.path.as_str()) {
+        Ok(stat) => stat.st_dev == device.st_rdev,
+        Err(_) => false,
+    }
+}
+
 /// Datastore Management
 ///
 /// A Datastore can store severals backups, and provides the
@@ -134,10 +157,16 @@ impl DataStore {
         let (config, digest) = pbs_config::datastore::config()?;
         let config: DataStoreConfig = config.lookup("datastore", name)?;
 
+        let mut unmounting = false;
         if let Some(maintenance_mode) = config.get_maintenance_mode() {
             if let Err(error) = maintenance_mode.check(operation) {
                 bail!("datastore '{name}' is in {error}");
             }
+            unmounting = maintenance_mode.is_unmounting();
+        }
+
+        if !is_datastore_mounted(&config) {
+            bail!("removable datastore '{name}' is not mounted");
         }
 
         if let Some(operation) = operation {
@@ -148,6 +177,10 @@ impl DataStore {
         drop(config_lock);
 
         let mut datastore_cache = DATASTORE_MAP.lock().unwrap();
+        if unmounting {
+            // lookups are still allowed, but must not keep files on the datastore open
+            datastore_cache.remove(name);
+        }
         let entry = datastore_cache.get(name);
 
         // reuse chunk store so that we keep using the same process locker instance!
@@ -165,17 +198,19 @@ impl DataStore {
                 DatastoreTuning::API_SCHEMA
                     .parse_property_string(config.tuning.as_deref().unwrap_or(""))?,
             )?;
-            Arc::new(ChunkStore::open(
-                name,
-                &config.path,
-                tuning.sync_level.unwrap_or_default(),
-            )?)
+            Arc::new(
+                ChunkStore::open(name, &config.path, tuning.sync_level.unwrap_or_default())?
+                    .with_s3_client(datastore_s3_client(&config)?)?
+                    .with_cache_size(config.backend_config()?.cache_size.map(|s| s.as_u64())),
+            )
         };
 
         let datastore = DataStore::with_store_and_config(chunk_store, config, Some(digest))?;
 
         let datastore = Arc::new(datastore);
-        datastore_cache.insert(name.to_string(), datastore.clone());
+        if !unmounting {
+            datastore_cache.insert(name.to_string(), datastore.clone());
+        }
 
         Ok(Arc::new(Self {
             inner: datastore,
@@ -183,13 +218,23 @@ impl DataStore {
         }))
     }
 
-    /// removes all datastores that are not configured anymore
+    /// removes all datastores that are not configured anymore or that are being unmounted
     pub fn remove_unused_datastores() -> Result<(), Error> {
         let (config, _digest) = pbs_config::datastore::config()?;
 
         let mut map = DATASTORE_MAP.lock().unwrap();
-        // removes all elements that are not in the config
-        map.retain(|key, _| config.sections.contains_key(key));
+        // removes all elements that are not in the config, and drops removable datastores which
+        // are getting unmounted, so that we do not keep files on them open
+        map.retain(|key, _| {
+            config
+                .lookup::<DataStoreConfig>("datastore", key)
+                .map(|store| {
+                    !store
+                        .get_maintenance_mode()
+                        .map_or(false, |mode| mode.is_unmounting())
+                })
+                .unwrap_or(false)
+        });
         Ok(())
     }
 
@@ -230,7 +275,9 @@ impl DataStore {
                 .parse_property_string(config.tuning.as_deref().unwrap_or(""))?,
         )?;
         let chunk_store =
-            ChunkStore::open(&name, &config.path, tuning.sync_level.unwrap_or_default())?;
+            ChunkStore::open(&name, &config.path, tuning.sync_level.unwrap_or_default())?
+                .with_s3_client(datastore_s3_client(&config)?)?
+                .with_cache_size(config.backend_config()?.cache_size.map(|s| s.as_u64()));
         let inner = Arc::new(Self::with_store_and_config(
             Arc::new(chunk_store),
             config,
@@ -643,7 +690,7 @@ impl DataStore {
         writeln!(file, "{}", auth_id)
             .map_err(|err| format_err!("unable to write owner file  {:?} - {}", path, err))?;
 
-        Ok(())
+        self.backend_upload_file(&path)
     }
 
     /// Create (if it does not already exists) and lock a backup group
@@ -849,6 +896,159 @@ impl DataStore {
         ListGroups::new(Arc::clone(self), ns)?.collect()
     }
 
+    /// Compute the logical and deduplicated size of all finished snapshots in `ns` and all of
+    /// its sub-namespaces.
+    ///
+    /// Like for [`scan_space_usage`](Self::scan_space_usage), the deduplicated size is the
+    /// on-disk size of the chunks.
+    ///
+    /// If `owner` is set, only groups owned by it (or by one of its API tokens) are accounted.
+    pub fn chunk_usage(
+        self: &Arc<DataStore>,
+        ns: BackupNamespace,
+        owner: Option<&Authid>,
+    ) -> Result<ChunkUsage, Error> {
+        let mut usage = ChunkUsage::new();
+
+        for ns in self.recursive_iter_backup_ns_ok(ns, None)? {
+            for group in self.iter_backup_groups_ok(ns)? {
+                if let Some(owner) = owner {
+                    match group.get_owner() {
+                        Ok(group_owner) if check_backup_owner(&group_owner, owner).is_ok() => {}
+                        _ => continue,
+                    }
+                }
+
+                for info in group.list_backups()? {
+                    if !info.is_finished() {
+                        continue;
+                    }
+                    usage.snapshot_count += 1;
+
+                    for file in info.files.iter() {
+                        match archive_type(file)? {
+                            ArchiveType::FixedIndex | ArchiveType::DynamicIndex => (),
+                            ArchiveType::Blob => continue,
+                        }
+                        let mut path = info.backup_dir.full_path();
+                        path.push(file);
+                        // snapshots might vanish in the meantime, e.g. by a concurrent prune
+                        match self.open_index(&path) {
+                            Ok(index) => usage.add_index(&*index, |digest, size| {
+                                self.chunk_disk_size(digest, size)
+                            }),
+                            Err(err) => log::warn!("skipping index {path:?} - {err}"),
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(usage)
+    }
+
+    /// Computes the exclusive and shared bytes of all snapshots, groups and namespaces.
+    ///
+    /// Chunk sizes are the on-disk sizes of the (compressed) chunk files. If a chunk file is
+    /// missing or empty, e.g. for chunks stored on an S3 backend, the uncompressed size from
+    /// the index is used instead. The result is saved in the datastore base directory, see
+    /// [`space_usage_status`](Self::space_usage_status).
+    pub fn scan_space_usage(
+        self: &Arc<DataStore>,
+        worker: &dyn WorkerTaskContext,
+        upid: Option<&UPID>,
+    ) -> Result<SpaceUsageStatus, Error> {
+        let mut scan = SpaceUsageScan::new();
+        let mut snapshot_count = 0;
+
+        for ns in self.recursive_iter_backup_ns_ok(BackupNamespace::root(), None)? {
+            worker.check_abort()?;
+            task_log!(worker, "scanning namespace '{}'", ns.display_as_path());
+            scan.start_namespace(ns.name());
+
+            for group in self.iter_backup_groups_ok(ns)? {
+                scan.start_group(group.relative_group_path().to_string_lossy().into_owned());
+
+                for info in group.list_backups()? {
+                    worker.check_abort()?;
+                    worker.fail_on_shutdown()?;
+                    if !info.is_finished() {
+                        continue;
+                    }
+                    snapshot_count += 1;
+                    scan.start_snapshot(
+                        info.backup_dir
+                            .relative_path()
+                            .to_string_lossy()
+                            .into_owned(),
+                    );
+
+                    for file in info.files.iter() {
+                        match archive_type(file)? {
+                            ArchiveType::FixedIndex | ArchiveType::DynamicIndex => (),
+                            ArchiveType::Blob => continue,
+                        }
+                        let mut path = info.backup_dir.full_path();
+                        path.push(file);
+                        // snapshots might vanish in the meantime, e.g. by a concurrent prune
+                        let index = match self.open_index(&path) {
+                            Ok(index) => index,
+                            Err(err) => {
+                                task_warn!(worker, "skipping index {path:?} - {err}");
+                                continue;
+                            }
+                        };
+                        for pos in 0..index.index_count() {
+                            let chunk_info = index.chunk_info(pos).unwrap();
+                            let size = if scan.contains(&chunk_info.digest) {
+                                0 // already known, size is not used
+                            } else {
+                                self.chunk_disk_size(&chunk_info.digest, chunk_info.size())
+                            };
+                            scan.add_chunk(chunk_info.digest, size);
+                        }
+                    }
+                }
+            }
+        }
+
+        task_log!(
+            worker,
+            "scanned {snapshot_count} snapshots referencing {} chunks",
+            scan.chunk_count()
+        );
+
+        let mut status = scan.finish();
+        status.upid = upid.map(|upid| upid.to_string());
+
+        let serialized = serde_json::to_string(&status)?;
+        let mut path = self.base_path();
+        path.push(SPACE_USAGE_FILE_NAME);
+
+        let backup_user = pbs_config::backup_user()?;
+        let mode = nix::sys::stat::Mode::from_bits_truncate(0o0644);
+        // owner(rw) = backup, group(r)= backup
+        let options = CreateOptions::new()
+            .perm(mode)
+            .owner(backup_user.uid)
+            .group(backup_user.gid);
+
+        replace_file(path, serialized.as_bytes(), options, false)?;
+
+        Ok(status)
+    }
+
+    /// Returns the result of the last space usage scan, if any.
+    pub fn space_usage_status(&self) -> Result<Option<SpaceUsageStatus>, Error> {
+        let mut path = self.base_path();
+        path.push(SPACE_USAGE_FILE_NAME);
+
+        match file_read_optional_string(path)? {
+            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
     pub fn list_images(&self) -> Result<Vec<PathBuf>, Error> {
         let base = self.base_path();
 
@@ -1154,6 +1354,32 @@ impl DataStore {
         self.inner.chunk_store.insert_chunk(chunk, digest)
     }
 
+    /// Insert a chunk of the local datastore `source` by linking its chunk file.
+    ///
+    /// Returns `false` if the chunk could not be linked and needs to be copied instead, see
+    /// [`ChunkStore::insert_chunk_link`].
+    pub fn insert_chunk_from(&self, source: &DataStore, digest: &[u8; 32]) -> Result<bool, Error> {
+        // chunk files of a datastore with S3 backend may only be cache markers
+        if source.s3_client().is_some() {
+            return Ok(false);
+        }
+        let (source_path, _digest_str) = source.chunk_path(digest);
+        self.inner
+            .chunk_store
+            .insert_chunk_link(&source_path, digest)
+    }
+
+    /// Size of a chunk file on disk.
+    ///
+    /// Falls back to `size`, the uncompressed size from an index, if the chunk file is missing
+    /// or empty, e.g. for chunks stored on an S3 backend.
+    pub fn chunk_disk_size(&self, digest: &[u8; 32], size: u64) -> u64 {
+        match self.stat_chunk(digest) {
+            Ok(stat) if stat.len() > 0 => stat.len(),
+            _ => size,
+        }
+    }
+
     pub fn stat_chunk(&self, digest: &[u8; 32]) -> Result<std::fs::Metadata, Error> {
         let (chunk_path, _digest_str) = self.inner.chunk_store.chunk_path(digest);
         std::fs::metadata(chunk_path).map_err(Error::from)
@@ -1163,8 +1389,17 @@ impl DataStore {
         let (chunk_path, digest_str) = self.inner.chunk_store.chunk_path(digest);
 
         proxmox_lang::try_block!({
-            let mut file = std::fs::File::open(&chunk_path)?;
-            DataBlob::load_from_reader(&mut file)
+            let cached = match self.inner.chunk_store.s3_client() {
+                // empty files are markers for chunks not cached locally
+                Some(_) => matches!(std::fs::metadata(&chunk_path), Ok(m) if m.len() > 0),
+                None => true,
+            };
+            if cached {
+                let mut file = std::fs::File::open(&chunk_path)?;
+                DataBlob::load_from_reader(&mut file)
+            } else {
+                self.inner.chunk_store.fetch_chunk(digest)
+            }
         })
         .map_err(|err| {
             format_err!(
@@ -1176,6 +1411,73 @@ impl DataStore {
         })
     }
 
+    /// The S3 client of the backing bucket, `None` for datastores on the filesystem backend.
+    pub fn s3_client(&self) -> Option<&Arc<S3Client>> {
+        self.inner.chunk_store.s3_client()
+    }
+
+    fn backend_object_key(&self, path: &Path) -> Result<String, Error> {
+        let relative_path = path.strip_prefix(self.base_path()).unwrap_or(path);
+        relative_path
+            .to_str()
+            .map(|key| key.to_string())
+            .ok_or_else(|| format_err!("non-utf8 paths not supported"))
+    }
+
+    /// Upload a single file of the datastore to the S3 backend, if any.
+    ///
+    /// `path` is either absolute or relative to the datastore base path.
+    pub fn backend_upload_file(&self, path: &Path) -> Result<(), Error> {
+        let s3_client = match self.s3_client() {
+            Some(s3_client) => s3_client,
+            None => return Ok(()),
+        };
+
+        let key = self.backend_object_key(path)?;
+        let data = std::fs::read(self.base_path().join(&key))
+            .map_err(|err| format_err!("unable to read {key:?} for upload - {err}"))?;
+        proxmox_async::runtime::block_on(s3_client.put_object(&key, data))
+    }
+
+    /// Upload all files of a snapshot, and the owner of its group, to the S3 backend, if any.
+    pub fn backend_upload_snapshot(&self, backup_dir: &BackupDir) -> Result<(), Error> {
+        if self.s3_client().is_none() {
+            return Ok(());
+        }
+
+        for item in proxmox_sys::fs::read_subdir(libc::AT_FDCWD, &backup_dir.full_path())? {
+            let item = item?;
+            if item.file_type() != Some(nix::dir::Type::File) {
+                continue;
+            }
+            let file_name = match item.file_name().to_str() {
+                Ok(name) if !name.starts_with('.') => name,
+                _ => continue,
+            };
+            self.backend_upload_file(&backup_dir.relative_path().join(file_name))?;
+        }
+
+        self.backend_upload_file(&self.owner_path(backup_dir.backup_ns(), backup_dir.group()))
+    }
+
+    /// Remove all objects below a snapshot or group directory from the S3 backend, if any.
+    ///
+    /// `path` is either absolute or relative to the datastore base path.
+    pub fn backend_remove_dir(&self, path: &Path) -> Result<(), Error> {
+        let s3_client = match self.s3_client() {
+            Some(s3_client) => s3_client,
+            None => return Ok(()),
+        };
+
+        let prefix = format!("{}/", self.backend_object_key(path)?);
+        proxmox_async::runtime::block_on(s3_client.delete_objects_with_prefix(&prefix))
+    }
+
+    /// Populate the local cache of an S3 backed datastore from its bucket.
+    pub fn refresh_backend_cache(&self, worker: &dyn WorkerTaskContext) -> Result<(), Error> {
+        self.inner.chunk_store.refresh_cache(worker)
+    }
+
     /// Updates the protection status of the specified snapshot.
     pub fn update_protection(&self, backup_dir: &BackupDir, protection: bool) -> Result<(), Error> {
         let full_path = backup_dir.full_path();
@@ -1357,7 +1659,7 @@ impl DataStore {
 
         let (operations, _lock) = task_tracking::get_active_operations_locked(name)?;
 
-        if operations.read != 0 || operations.write != 0 {
+        if operations.read != 0 || operations.write != 0 || operations.delete != 0 {
             bail!("datastore is currently in use");
         }
 
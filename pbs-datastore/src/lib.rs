@@ -174,6 +174,7 @@ pub mod checksum_reader;
 pub mod checksum_writer;
 pub mod chunk_stat;
 pub mod chunk_store;
+pub mod chunk_usage;
 pub mod chunker;
 pub mod crypt_reader;
 pub mod crypt_writer;
@@ -186,8 +187,11 @@ pub mod manifest;
 pub mod paperkey;
 pub mod prune;
 pub mod read_chunk;
+pub mod s3_client;
+pub mod space_usage;
 pub mod store_progress;
 pub mod task_tracking;
+pub mod verify_state;
 
 pub mod dynamic_index;
 pub mod fixed_index;
@@ -206,7 +210,7 @@ pub use manifest::BackupManifest;
 pub use store_progress::StoreProgress;
 
 mod datastore;
-pub use datastore::{check_backup_owner, DataStore};
+pub use datastore::{check_backup_owner, is_datastore_mounted, DataStore};
 
 mod hierarchy;
 pub use hierarchy::{
@@ -0,0 +1,219 @@
+//! Deduplication aware space accounting per snapshot, group and namespace
+//!
+//! A chunk is *exclusive* to a snapshot (group, namespace) if no other snapshot (group,
+//! namespace) references it, so pruning the snapshot (group, namespace) would free the space
+//! used by those chunks. All other chunks referenced by it are *shared*.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Name of the file in the datastore base directory holding the result of the last scan.
+pub const SPACE_USAGE_FILE_NAME: &str = ".space-usage";
+
+/// Exclusive and shared bytes of a snapshot, group or namespace.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpaceUsage {
+    /// Bytes only referenced by this entry.
+    pub exclusive: u64,
+    /// Bytes also referenced by other entries of the same level.
+    pub shared: u64,
+}
+
+/// Result of a space usage scan, as stored in [`SPACE_USAGE_FILE_NAME`].
+///
+/// Snapshots and groups are keyed by their path relative to the datastore base directory,
+/// namespaces by their name.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SpaceUsageStatus {
+    /// UPID of the task which created this status.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upid: Option<String>,
+    /// Time the scan finished.
+    pub scan_time: i64,
+    pub namespaces: HashMap<String, SpaceUsage>,
+    pub groups: HashMap<String, SpaceUsage>,
+    pub snapshots: HashMap<String, SpaceUsage>,
+}
+
+const MULTIPLE_SNAPSHOTS: u8 = 1;
+const MULTIPLE_GROUPS: u8 = 2;
+const MULTIPLE_NAMESPACES: u8 = 4;
+
+// kept small on purpose, there is one entry per chunk in the datastore
+struct ChunkRefs {
+    size: u64,
+    last_snapshot: u32,
+    last_group: u32,
+    last_ns: u32,
+    flags: u8,
+}
+
+/// Collects chunk references of all snapshots of a datastore.
+///
+/// Snapshots must be added grouped by namespace and group, i.e. after calling
+/// [`start_group`](Self::start_group) all snapshots of that group have to follow before the next
+/// group is started, and the same holds for namespaces.
+#[derive(Default)]
+pub struct SpaceUsageScan {
+    chunks: HashMap<[u8; 32], ChunkRefs>,
+    namespaces: Vec<(String, u64)>,
+    groups: Vec<(String, u64)>,
+    snapshots: Vec<(String, u64)>,
+}
+
+impl SpaceUsageScan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start accounting chunks for a new namespace.
+    pub fn start_namespace(&mut self, name: String) {
+        self.namespaces.push((name, 0));
+    }
+
+    /// Start accounting chunks for a new group of the current namespace.
+    pub fn start_group(&mut self, path: String) {
+        self.groups.push((path, 0));
+    }
+
+    /// Start accounting chunks for a new snapshot of the current group.
+    pub fn start_snapshot(&mut self, path: String) {
+        self.snapshots.push((path, 0));
+    }
+
+    /// Returns true if the chunk was already referenced by any snapshot.
+    pub fn contains(&self, digest: &[u8; 32]) -> bool {
+        self.chunks.contains_key(digest)
+    }
+
+    /// Number of distinct chunks seen so far.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Add a chunk reference of the current snapshot.
+    ///
+    /// `size` is only used for chunks which were not referenced before, so callers can use
+    /// [`contains`](Self::contains) to avoid looking up the size of known chunks.
+    pub fn add_chunk(&mut self, digest: [u8; 32], size: u64) {
+        let (snapshot, group, ns) = match (
+            self.snapshots.len().checked_sub(1),
+            self.groups.len().checked_sub(1),
+            self.namespaces.len().checked_sub(1),
+        ) {
+            (Some(snapshot), Some(group), Some(ns)) => (snapshot as u32, group as u32, ns as u32),
+            _ => panic!("space usage scan: chunk added without snapshot"),
+        };
+
+        let refs = self.chunks.entry(digest).or_insert_with(|| ChunkRefs {
+            size,
+            last_snapshot: u32::MAX,
+            last_group: u32::MAX,
+            last_ns: u32::MAX,
+            flags: 0,
+        });
+
+        if refs.last_snapshot != snapshot {
+            if refs.last_snapshot != u32::MAX {
+                refs.flags |= MULTIPLE_SNAPSHOTS;
+            }
+            refs.last_snapshot = snapshot;
+            self.snapshots[snapshot as usize].1 += refs.size;
+        }
+        if refs.last_group != group {
+            if refs.last_group != u32::MAX {
+                refs.flags |= MULTIPLE_GROUPS;
+            }
+            refs.last_group = group;
+            self.groups[group as usize].1 += refs.size;
+        }
+        if refs.last_ns != ns {
+            if refs.last_ns != u32::MAX {
+                refs.flags |= MULTIPLE_NAMESPACES;
+            }
+            refs.last_ns = ns;
+            self.namespaces[ns as usize].1 += refs.size;
+        }
+    }
+
+    /// Split the accounted bytes into exclusive and shared bytes.
+    pub fn finish(self) -> SpaceUsageStatus {
+        let mut ns_exclusive = vec![0u64; self.namespaces.len()];
+        let mut group_exclusive = vec![0u64; self.groups.len()];
+        let mut snapshot_exclusive = vec![0u64; self.snapshots.len()];
+
+        for refs in self.chunks.into_values() {
+            if refs.flags & MULTIPLE_SNAPSHOTS == 0 {
+                snapshot_exclusive[refs.last_snapshot as usize] += refs.size;
+            }
+            if refs.flags & MULTIPLE_GROUPS == 0 {
+                group_exclusive[refs.last_group as usize] += refs.size;
+            }
+            if refs.flags & MULTIPLE_NAMESPACES == 0 {
+                ns_exclusive[refs.last_ns as usize] += refs.size;
+            }
+        }
+
+        fn split(list: Vec<(String, u64)>, exclusive: Vec<u64>) -> HashMap<String, SpaceUsage> {
+            list.into_iter()
+                .zip(exclusive)
+                .map(|((key, total), exclusive)| {
+                    let shared = total - exclusive;
+                    (key, SpaceUsage { exclusive, shared })
+                })
+                .collect()
+        }
+
+        SpaceUsageStatus {
+            upid: None,
+            scan_time: proxmox_time::epoch_i64(),
+            namespaces: split(self.namespaces, ns_exclusive),
+            groups: split(self.groups, group_exclusive),
+            snapshots: split(self.snapshots, snapshot_exclusive),
+        }
+    }
+}
+
+#[test]
+fn test_space_usage_scan() {
+    let mut scan = SpaceUsageScan::new();
+
+    scan.start_namespace(String::new());
+    scan.start_group("vm/100".to_string());
+    scan.start_snapshot("vm/100/a".to_string());
+    scan.add_chunk([1u8; 32], 10);
+    scan.add_chunk([2u8; 32], 20);
+    scan.add_chunk([2u8; 32], 20);
+    scan.start_snapshot("vm/100/b".to_string());
+    scan.add_chunk([2u8; 32], 20);
+    scan.add_chunk([3u8; 32], 30);
+    scan.start_group("vm/101".to_string());
+    scan.start_snapshot("vm/101/a".to_string());
+    scan.add_chunk([3u8; 32], 30);
+    scan.add_chunk([4u8; 32], 40);
+    scan.start_namespace("foo".to_string());
+    scan.start_group("ns/foo/vm/100".to_string());
+    scan.start_snapshot("ns/foo/vm/100/a".to_string());
+    scan.add_chunk([4u8; 32], 40);
+    scan.add_chunk([5u8; 32], 50);
+
+    assert_eq!(scan.chunk_count(), 5);
+
+    let status = scan.finish();
+
+    let usage = |exclusive, shared| SpaceUsage { exclusive, shared };
+
+    assert_eq!(status.snapshots["vm/100/a"], usage(10, 20));
+    assert_eq!(status.snapshots["vm/100/b"], usage(0, 50));
+    assert_eq!(status.snapshots["vm/101/a"], usage(0, 70));
+    assert_eq!(status.snapshots["ns/foo/vm/100/a"], usage(50, 40));
+
+    assert_eq!(status.groups["vm/100"], usage(30, 30));
+    assert_eq!(status.groups["vm/101"], usage(0, 70));
+    assert_eq!(status.groups["ns/foo/vm/100"], usage(50, 40));
+
+    assert_eq!(status.namespaces[""], usage(60, 40));
+    assert_eq!(status.namespaces["foo"], usage(50, 40));
+}
@@ -13,6 +13,9 @@ use serde::{Deserialize, Serialize};
 pub struct ActiveOperationStats {
     pub read: i64,
     pub write: i64,
+    // not tracked by older daemon instances
+    #[serde(default)]
+    pub delete: i64,
 }
 
 impl Sum<Self> for ActiveOperationStats {
@@ -23,6 +26,7 @@ impl Sum<Self> for ActiveOperationStats {
         iter.fold(Self::default(), |a, b| Self {
             read: a.read + b.read,
             write: a.write + b.write,
+            delete: a.delete + b.delete,
         })
     }
 }
@@ -112,6 +116,7 @@ pub fn update_active_operations(name: &str, operation: Operation, count: i64) ->
                             match operation {
                                 Operation::Read => task.active_operations.read += count,
                                 Operation::Write => task.active_operations.write += count,
+                                Operation::Delete => task.active_operations.delete += count,
                                 Operation::Lookup => (), // no IO must happen there
                             };
                         }
@@ -128,10 +133,10 @@ pub fn update_active_operations(name: &str, operation: Operation, count: i64) ->
         updated_tasks.push(TaskOperations {
             pid,
             starttime,
-            active_operations: match operation {
-                Operation::Read => ActiveOperationStats { read: 1, write: 0 },
-                Operation::Write => ActiveOperationStats { read: 0, write: 1 },
-                Operation::Lookup => ActiveOperationStats { read: 0, write: 0 },
+            active_operations: ActiveOperationStats {
+                read: (operation == Operation::Read) as i64,
+                write: (operation == Operation::Write) as i64,
+                delete: (operation == Operation::Delete) as i64,
             },
         })
     }
@@ -0,0 +1,197 @@
+//! Last verification times of chunks, used for rolling verification
+//!
+//! Chunks are tracked in buckets by the first two bytes of their digest, which is also the
+//! prefix directory they are stored in. Each bucket records when all chunks in it were last
+//! verified, so a rolling verification can check a share of the buckets on every run and still
+//! cover the whole chunk store within a configured period.
+//!
+//! The state is kept per verification job, as jobs may be limited to a namespace and only verify
+//! the chunks referenced from there.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Error};
+
+use proxmox_sys::fs::{file_get_optional_contents, replace_file, CreateOptions};
+
+use crate::file_formats::CHUNK_VERIFY_STATE_1_0;
+
+/// Prefix of the files in the datastore base directory holding the chunk verify state of a job.
+pub const CHUNK_VERIFY_STATE_FILE_PREFIX: &str = ".chunk-verify-state.";
+
+/// Path of the file holding the chunk verify state of job `job_id` on the datastore at `base`.
+pub fn chunk_verify_state_path(base: &Path, job_id: &str) -> PathBuf {
+    base.join(format!("{CHUNK_VERIFY_STATE_FILE_PREFIX}{job_id}"))
+}
+
+/// Number of buckets, one per chunk prefix directory.
+pub const CHUNK_VERIFY_BUCKETS: usize = 1 << 16;
+
+/// Returns the bucket a chunk belongs to.
+pub fn chunk_verify_bucket(digest: &[u8; 32]) -> usize {
+    u16::from_be_bytes([digest[0], digest[1]]) as usize
+}
+
+/// Chunk buckets selected for a verification run.
+pub struct ChunkVerifySelection {
+    selected: Vec<bool>,
+    count: usize,
+}
+
+impl ChunkVerifySelection {
+    /// Returns true if the chunk with `digest` should be verified.
+    pub fn contains(&self, digest: &[u8; 32]) -> bool {
+        self.selected[chunk_verify_bucket(digest)]
+    }
+
+    /// Number of selected buckets.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// Last verification time (epoch) of every chunk bucket, and time of the last rolling run.
+pub struct ChunkVerifyState {
+    last_run: i64,
+    last_verified: Vec<i64>,
+}
+
+impl Default for ChunkVerifyState {
+    fn default() -> Self {
+        Self {
+            last_run: 0,
+            last_verified: vec![0; CHUNK_VERIFY_BUCKETS],
+        }
+    }
+}
+
+impl ChunkVerifyState {
+    /// Load the state of job `job_id` stored in the datastore base directory `base`.
+    ///
+    /// Returns an empty state (nothing verified yet) if there is none.
+    pub fn load(base: &Path, job_id: &str) -> Result<Self, Error> {
+        let data = match file_get_optional_contents(chunk_verify_state_path(base, job_id))? {
+            Some(data) => data,
+            None => return Ok(Self::default()),
+        };
+
+        if data.len() != 16 + CHUNK_VERIFY_BUCKETS * 8 || data[0..8] != CHUNK_VERIFY_STATE_1_0 {
+            bail!("unable to parse chunk verify state - wrong magic or size");
+        }
+
+        let mut values = data[8..]
+            .chunks_exact(8)
+            .map(|raw| i64::from_le_bytes(raw.try_into().unwrap()));
+
+        let last_run = values.next().unwrap();
+        Ok(Self {
+            last_run,
+            last_verified: values.collect(),
+        })
+    }
+
+    /// Atomically store the state of job `job_id` in the datastore base directory `base`.
+    pub fn save(&self, base: &Path, job_id: &str) -> Result<(), Error> {
+        let mut data = Vec::with_capacity(16 + CHUNK_VERIFY_BUCKETS * 8);
+        data.extend_from_slice(&CHUNK_VERIFY_STATE_1_0);
+        data.extend_from_slice(&self.last_run.to_le_bytes());
+        for time in self.last_verified.iter() {
+            data.extend_from_slice(&time.to_le_bytes());
+        }
+
+        let backup_user = pbs_config::backup_user()?;
+        let mode = nix::sys::stat::Mode::from_bits_truncate(0o0644);
+        // owner(rw) = backup, group(r)= backup
+        let options = CreateOptions::new()
+            .perm(mode)
+            .owner(backup_user.uid)
+            .group(backup_user.gid);
+
+        replace_file(chunk_verify_state_path(base, job_id), &data, options, false)
+    }
+
+    /// Select the buckets to verify in a run at `now`, so that all buckets get verified within
+    /// `period` seconds.
+    ///
+    /// The share of buckets matches the time passed since the last run (assuming daily runs
+    /// if there was none). Buckets whose verification is older than `period` are always
+    /// included, followed by never verified and then least recently verified ones.
+    pub fn select(&self, now: i64, period: i64) -> ChunkVerifySelection {
+        let period = period.max(1);
+        let elapsed = if self.last_run > 0 {
+            (now - self.last_run).clamp(1, period)
+        } else {
+            period.min(86400)
+        };
+
+        let buckets = CHUNK_VERIFY_BUCKETS as i64;
+        let quota = ((buckets * elapsed + period - 1) / period) as usize;
+
+        // overdue buckets first, then never verified ones, then the least recently verified
+        let overdue = |time: i64| time > 0 && now - time >= period;
+        let mut order: Vec<usize> = (0..CHUNK_VERIFY_BUCKETS).collect();
+        order.sort_by_key(|bucket| {
+            let time = self.last_verified[*bucket];
+            (!overdue(time), time > 0, time)
+        });
+
+        let overdue_count = self.last_verified.iter().filter(|t| overdue(**t)).count();
+        let count = quota.max(overdue_count).min(CHUNK_VERIFY_BUCKETS);
+
+        let mut selected = vec![false; CHUNK_VERIFY_BUCKETS];
+        for bucket in order.into_iter().take(count) {
+            selected[bucket] = true;
+        }
+
+        ChunkVerifySelection { selected, count }
+    }
+
+    /// Record that all buckets in `selection` were verified in the run at `now`.
+    pub fn mark_verified(&mut self, selection: &ChunkVerifySelection, now: i64) {
+        for (bucket, selected) in selection.selected.iter().enumerate() {
+            if *selected {
+                self.last_verified[bucket] = now;
+            }
+        }
+        self.last_run = now;
+    }
+
+    /// Share of buckets verified within the last `period` seconds before `now`, in percent.
+    pub fn coverage(&self, now: i64, period: i64) -> f64 {
+        let covered = self
+            .last_verified
+            .iter()
+            .filter(|time| **time > 0 && now - **time < period)
+            .count();
+        (covered as f64 * 100.0) / (CHUNK_VERIFY_BUCKETS as f64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rolling_selection_covers_period() {
+        const DAY: i64 = 86400;
+        let period = 7 * DAY;
+        let mut state = ChunkVerifyState::default();
+
+        let mut now = 1_700_000_000;
+        for _ in 0..7 {
+            let selection = state.select(now, period);
+            assert_eq!(
+                selection.count(),
+                (CHUNK_VERIFY_BUCKETS + 6) / 7,
+                "daily runs verify a seventh of the buckets"
+            );
+            state.mark_verified(&selection, now);
+            now += DAY;
+        }
+        assert_eq!(state.coverage(now - DAY, period), 100.0);
+
+        // after a missed week, everything is overdue
+        now += 7 * DAY;
+        assert_eq!(state.select(now, period).count(), CHUNK_VERIFY_BUCKETS);
+    }
+}
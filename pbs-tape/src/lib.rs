@@ -34,6 +34,8 @@ pub use emulate_tape_writer::EmulateTapeWriter;
 
 pub mod sg_tape;
 
+pub mod ltfs;
+
 pub mod sg_pt_changer;
 
 /// We use 256KB blocksize (always)
@@ -0,0 +1,820 @@
+//! LTFS (Linear Tape File System) volume writer
+//!
+//! Writes plain files onto a two-partition media, using the on-tape
+//! layout defined by the LTFS Format Specification 2.4:
+//!
+//! - both partitions start with a label construct (`VOL1` label,
+//!   filemark, LTFS label, filemark)
+//! - file data is written to the data partition (`b`), followed by a
+//!   filemark, the index and another filemark
+//! - a copy of the final index is written to the index partition (`a`)
+//!
+//! We only write complete volumes (format, write all files, write
+//! index). The resulting media can be mounted by any LTFS
+//! implementation.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt::Write as _;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path};
+
+use anyhow::{bail, format_err, Error};
+
+use proxmox_uuid::Uuid;
+
+use crate::{BlockWrite, PROXMOX_TAPE_BLOCK_SIZE};
+
+/// Version of the LTFS format specification we implement
+pub const LTFS_FORMAT_VERSION: &str = "2.4.0";
+
+/// We use the same block size as for our own tape format
+pub const LTFS_BLOCK_SIZE: usize = PROXMOX_TAPE_BLOCK_SIZE;
+
+/// Partition used for the index (`a`)
+pub const LTFS_INDEX_PARTITION: u8 = 0;
+/// Partition used for file data (`b`)
+pub const LTFS_DATA_PARTITION: u8 = 1;
+
+// The label construct uses the first 4 logical objects of each partition
+// (VOL1 label, filemark, LTFS label, filemark).
+const LTFS_LABEL_CONSTRUCT_OBJECTS: u64 = 4;
+
+/// Media access for LTFS volumes
+///
+/// Logical object numbers (blocks and filemarks) are counted from the
+/// beginning of each partition.
+pub trait LtfsMedia: BlockWrite {
+    /// Erase the media and create the index and data partitions
+    fn format_partitions(&mut self) -> Result<(), Error>;
+
+    /// Position the media at the given logical object of a partition
+    fn locate(&mut self, partition: u8, logical_object: u64) -> Result<(), Error>;
+
+    /// Flush all data to the media
+    fn sync(&mut self) -> Result<(), Error>;
+}
+
+impl<T: LtfsMedia + ?Sized> LtfsMedia for Box<T> {
+    fn format_partitions(&mut self) -> Result<(), Error> {
+        (**self).format_partitions()
+    }
+
+    fn locate(&mut self, partition: u8, logical_object: u64) -> Result<(), Error> {
+        (**self).locate(partition, logical_object)
+    }
+
+    fn sync(&mut self) -> Result<(), Error> {
+        (**self).sync()
+    }
+}
+
+/// Time stamp with nanosecond resolution
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LtfsTime {
+    pub secs: i64,
+    pub nanos: u32,
+}
+
+impl LtfsTime {
+    pub fn new(secs: i64, nanos: u32) -> Self {
+        Self { secs, nanos }
+    }
+
+    /// Current time (second resolution)
+    pub fn now() -> Self {
+        Self::new(proxmox_time::epoch_i64(), 0)
+    }
+
+    // Format as required by the specification: 'YYYY-MM-DDThh:mm:ss.nnnnnnnnnZ'
+    fn format(&self) -> Result<String, Error> {
+        let time = proxmox_time::strftime_utc("%Y-%m-%dT%H:%M:%S", self.secs)?;
+        Ok(format!("{time}.{:09}Z", self.nanos))
+    }
+}
+
+// Extents always start at the beginning of a block, and we write
+// each file as a single extent.
+struct LtfsExtent {
+    start_block: u64,
+    byte_count: u64,
+}
+
+enum LtfsNodeKind {
+    /// Entries by raw file name (names are not necessarily valid UTF-8)
+    Directory(BTreeMap<Vec<u8>, LtfsNode>),
+    File {
+        length: u64,
+        extent: Option<LtfsExtent>,
+    },
+    Symlink(String),
+}
+
+struct LtfsNode {
+    uid: u64,
+    mtime: LtfsTime,
+    kind: LtfsNodeKind,
+}
+
+/// Writes a complete LTFS volume
+///
+/// Files are added with [`LtfsWriter::create_file`], and the volume is
+/// completed by [`LtfsWriter::finish`], which writes the index. Please
+/// note that the volume is not usable without the index.
+pub struct LtfsWriter<M: LtfsMedia> {
+    media: M,
+    volume_name: String,
+    creator: String,
+    volume_uuid: Uuid,
+    format_time: LtfsTime,
+    // next logical object number on the data partition
+    position: u64,
+    highest_uid: u64,
+    root: LtfsNode,
+}
+
+impl<M: LtfsMedia> LtfsWriter<M> {
+    /// Format the media and write the label constructs
+    ///
+    /// The first 6 characters of `volume_name` are used as volume
+    /// identifier inside the `VOL1` label, so they should match the
+    /// media barcode.
+    pub fn format(mut media: M, volume_name: &str, creator: &str) -> Result<Self, Error> {
+        let vol1 = vol1_label(volume_name)?;
+
+        let volume_uuid = Uuid::generate();
+        let format_time = LtfsTime::now();
+
+        media.format_partitions()?;
+
+        let mut writer = Self {
+            media,
+            volume_name: volume_name.to_string(),
+            creator: creator.to_string(),
+            volume_uuid,
+            format_time,
+            position: 0,
+            highest_uid: 1,
+            root: LtfsNode {
+                uid: 1,
+                mtime: format_time,
+                kind: LtfsNodeKind::Directory(BTreeMap::new()),
+            },
+        };
+
+        for partition in [LTFS_INDEX_PARTITION, LTFS_DATA_PARTITION] {
+            writer.media.locate(partition, 0)?;
+            writer.write_block(&vol1)?;
+            writer.media.write_filemark()?;
+            let label = writer.label_xml(partition)?;
+            writer.write_xml(&label)?;
+            writer.media.write_filemark()?;
+        }
+
+        writer.position = LTFS_LABEL_CONSTRUCT_OBJECTS;
+
+        Ok(writer)
+    }
+
+    /// Returns the volume UUID
+    pub fn volume_uuid(&self) -> &Uuid {
+        &self.volume_uuid
+    }
+
+    fn write_block(&mut self, data: &[u8]) -> Result<(), Error> {
+        let leom = self.media.write_block(data)?;
+        if leom {
+            bail!("reached end of media - LTFS volume does not fit onto a single media");
+        }
+        Ok(())
+    }
+
+    fn write_xml(&mut self, xml: &str) -> Result<(), Error> {
+        for data in xml.as_bytes().chunks(LTFS_BLOCK_SIZE) {
+            self.write_block(data)?;
+        }
+        Ok(())
+    }
+
+    fn insert_node(
+        &mut self,
+        path: &Path,
+        mtime: LtfsTime,
+        kind: LtfsNodeKind,
+    ) -> Result<(), Error> {
+        let (dir, name) = lookup_parent(&mut self.root, &mut self.highest_uid, path, mtime)?;
+
+        if let Some(node) = dir.get_mut(&name) {
+            match (&node.kind, &kind) {
+                (LtfsNodeKind::Directory(_), LtfsNodeKind::Directory(_)) => {
+                    node.mtime = mtime;
+                    return Ok(());
+                }
+                _ => bail!("unable to create {path:?} - file already exists"),
+            }
+        }
+
+        self.highest_uid += 1;
+        let uid = self.highest_uid;
+        dir.insert(name, LtfsNode { uid, mtime, kind });
+
+        Ok(())
+    }
+
+    /// Create a directory (missing parent directories are created
+    /// automatically)
+    pub fn create_dir(&mut self, path: &Path, mtime: LtfsTime) -> Result<(), Error> {
+        self.insert_node(path, mtime, LtfsNodeKind::Directory(BTreeMap::new()))
+    }
+
+    /// Create a symbolic link
+    pub fn create_symlink(
+        &mut self,
+        path: &Path,
+        target: &OsStr,
+        mtime: LtfsTime,
+    ) -> Result<(), Error> {
+        let target = String::from_utf8_lossy(target.as_bytes()).into_owned();
+        self.insert_node(path, mtime, LtfsNodeKind::Symlink(target))
+    }
+
+    /// Create a file
+    ///
+    /// Data is written using the returned [`LtfsFileWriter`]. The file
+    /// is only added to the index after [`LtfsFileWriter::finish`].
+    pub fn create_file(
+        &mut self,
+        path: &Path,
+        mtime: LtfsTime,
+    ) -> Result<LtfsFileWriter<'_, M>, Error> {
+        // check path early
+        let (dir, name) = lookup_parent(&mut self.root, &mut self.highest_uid, path, mtime)?;
+        if dir.contains_key(&name) {
+            bail!("unable to create {path:?} - file already exists");
+        }
+
+        Ok(LtfsFileWriter {
+            path: path.to_owned(),
+            mtime,
+            start_block: self.position,
+            length: 0,
+            buffer: Vec::with_capacity(LTFS_BLOCK_SIZE),
+            writer: self,
+        })
+    }
+
+    /// Write the index to both partitions and return the media
+    pub fn finish(mut self) -> Result<M, Error> {
+        let update_time = LtfsTime::now();
+
+        self.media.write_filemark()?;
+        self.position += 1;
+
+        let data_index_block = self.position;
+        let index = self.index_xml(update_time, LTFS_DATA_PARTITION, data_index_block, None)?;
+        self.write_xml(&index)?;
+        self.media.write_filemark()?;
+
+        self.media
+            .locate(LTFS_INDEX_PARTITION, LTFS_LABEL_CONSTRUCT_OBJECTS)?;
+        let index = self.index_xml(
+            update_time,
+            LTFS_INDEX_PARTITION,
+            LTFS_LABEL_CONSTRUCT_OBJECTS,
+            Some(data_index_block),
+        )?;
+        self.write_xml(&index)?;
+        self.media.write_filemark()?;
+
+        self.media.sync()?;
+
+        Ok(self.media)
+    }
+
+    fn label_xml(&self, partition: u8) -> Result<String, Error> {
+        let mut xml = String::new();
+        writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(xml, r#"<ltfslabel version="{LTFS_FORMAT_VERSION}">"#)?;
+        writeln!(xml, "  <creator>{}</creator>", xml_escape(&self.creator))?;
+        writeln!(
+            xml,
+            "  <formattime>{}</formattime>",
+            self.format_time.format()?
+        )?;
+        writeln!(xml, "  <volumeuuid>{}</volumeuuid>", self.volume_uuid)?;
+        writeln!(xml, "  <location>")?;
+        writeln!(
+            xml,
+            "    <partition>{}</partition>",
+            partition_id(partition)
+        )?;
+        writeln!(xml, "  </location>")?;
+        writeln!(xml, "  <partitions>")?;
+        writeln!(
+            xml,
+            "    <index>{}</index>",
+            partition_id(LTFS_INDEX_PARTITION)
+        )?;
+        writeln!(
+            xml,
+            "    <data>{}</data>",
+            partition_id(LTFS_DATA_PARTITION)
+        )?;
+        writeln!(xml, "  </partitions>")?;
+        writeln!(xml, "  <blocksize>{LTFS_BLOCK_SIZE}</blocksize>")?;
+        writeln!(xml, "  <compression>true</compression>")?;
+        writeln!(xml, "</ltfslabel>")?;
+        Ok(xml)
+    }
+
+    fn index_xml(
+        &self,
+        update_time: LtfsTime,
+        partition: u8,
+        start_block: u64,
+        previous_data_index_block: Option<u64>,
+    ) -> Result<String, Error> {
+        let mut xml = String::new();
+        writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(xml, r#"<ltfsindex version="{LTFS_FORMAT_VERSION}">"#)?;
+        writeln!(xml, "  <creator>{}</creator>", xml_escape(&self.creator))?;
+        writeln!(xml, "  <volumeuuid>{}</volumeuuid>", self.volume_uuid)?;
+        writeln!(xml, "  <generationnumber>1</generationnumber>")?;
+        writeln!(xml, "  <updatetime>{}</updatetime>", update_time.format()?)?;
+        writeln!(xml, "  <location>")?;
+        writeln!(
+            xml,
+            "    <partition>{}</partition>",
+            partition_id(partition)
+        )?;
+        writeln!(xml, "    <startblock>{start_block}</startblock>")?;
+        writeln!(xml, "  </location>")?;
+        if let Some(block) = previous_data_index_block {
+            writeln!(xml, "  <previousgenerationlocation>")?;
+            writeln!(
+                xml,
+                "    <partition>{}</partition>",
+                partition_id(LTFS_DATA_PARTITION)
+            )?;
+            writeln!(xml, "    <startblock>{block}</startblock>")?;
+            writeln!(xml, "  </previousgenerationlocation>")?;
+        }
+        writeln!(xml, "  <allowpolicyupdate>true</allowpolicyupdate>")?;
+        writeln!(
+            xml,
+            "  <highestfileuid>{}</highestfileuid>",
+            self.highest_uid
+        )?;
+        self.node_xml(&mut xml, self.volume_name.as_bytes(), &self.root, 1)?;
+        writeln!(xml, "</ltfsindex>")?;
+        Ok(xml)
+    }
+
+    fn node_xml(
+        &self,
+        xml: &mut String,
+        name: &[u8],
+        node: &LtfsNode,
+        level: usize,
+    ) -> Result<(), Error> {
+        let indent = "  ".repeat(level);
+        let mtime = node.mtime.format()?;
+
+        let tag = match node.kind {
+            LtfsNodeKind::Directory(_) => "directory",
+            _ => "file",
+        };
+
+        writeln!(xml, "{indent}<{tag}>")?;
+        writeln!(xml, "{indent}  {}", name_xml(name))?;
+        match node.kind {
+            LtfsNodeKind::File { length, .. } => {
+                writeln!(xml, "{indent}  <length>{length}</length>")?;
+            }
+            LtfsNodeKind::Symlink(_) => {
+                writeln!(xml, "{indent}  <length>0</length>")?;
+            }
+            LtfsNodeKind::Directory(_) => {}
+        }
+        writeln!(xml, "{indent}  <readonly>false</readonly>")?;
+        writeln!(xml, "{indent}  <creationtime>{mtime}</creationtime>")?;
+        writeln!(xml, "{indent}  <changetime>{mtime}</changetime>")?;
+        writeln!(xml, "{indent}  <modifytime>{mtime}</modifytime>")?;
+        writeln!(xml, "{indent}  <accesstime>{mtime}</accesstime>")?;
+        writeln!(
+            xml,
+            "{indent}  <backuptime>{}</backuptime>",
+            self.format_time.format()?
+        )?;
+        writeln!(xml, "{indent}  <fileuid>{}</fileuid>", node.uid)?;
+
+        match node.kind {
+            LtfsNodeKind::Directory(ref entries) => {
+                writeln!(xml, "{indent}  <contents>")?;
+                for (name, node) in entries {
+                    self.node_xml(xml, name, node, level + 2)?;
+                }
+                writeln!(xml, "{indent}  </contents>")?;
+            }
+            LtfsNodeKind::File {
+                extent: Some(ref extent),
+                ..
+            } => {
+                writeln!(xml, "{indent}  <extentinfo>")?;
+                writeln!(xml, "{indent}    <extent>")?;
+                writeln!(xml, "{indent}      <fileoffset>0</fileoffset>")?;
+                writeln!(
+                    xml,
+                    "{indent}      <partition>{}</partition>",
+                    partition_id(LTFS_DATA_PARTITION)
+                )?;
+                writeln!(
+                    xml,
+                    "{indent}      <startblock>{}</startblock>",
+                    extent.start_block
+                )?;
+                writeln!(xml, "{indent}      <byteoffset>0</byteoffset>")?;
+                writeln!(
+                    xml,
+                    "{indent}      <bytecount>{}</bytecount>",
+                    extent.byte_count
+                )?;
+                writeln!(xml, "{indent}    </extent>")?;
+                writeln!(xml, "{indent}  </extentinfo>")?;
+            }
+            LtfsNodeKind::File { extent: None, .. } => {}
+            LtfsNodeKind::Symlink(ref target) => {
+                writeln!(xml, "{indent}  <symlink>{}</symlink>", xml_escape(target))?;
+            }
+        }
+        writeln!(xml, "{indent}</{tag}>")?;
+
+        Ok(())
+    }
+}
+
+/// Writes file data to the data partition
+///
+/// Each file is stored as a single extent. It is required to call
+/// [`LtfsFileWriter::finish`] to add the file to the index.
+pub struct LtfsFileWriter<'a, M: LtfsMedia> {
+    writer: &'a mut LtfsWriter<M>,
+    path: std::path::PathBuf,
+    mtime: LtfsTime,
+    start_block: u64,
+    length: u64,
+    buffer: Vec<u8>,
+}
+
+impl<M: LtfsMedia> LtfsFileWriter<'_, M> {
+    fn flush_block(&mut self) -> Result<(), Error> {
+        if !self.buffer.is_empty() {
+            self.writer.write_block(&self.buffer)?;
+            self.writer.position += 1;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Write remaining data and add the file to the index
+    pub fn finish(mut self) -> Result<u64, Error> {
+        self.flush_block()?;
+
+        let extent = if self.length > 0 {
+            Some(LtfsExtent {
+                start_block: self.start_block,
+                byte_count: self.length,
+            })
+        } else {
+            None
+        };
+
+        let kind = LtfsNodeKind::File {
+            length: self.length,
+            extent,
+        };
+        self.writer.insert_node(&self.path, self.mtime, kind)?;
+
+        Ok(self.length)
+    }
+}
+
+impl<M: LtfsMedia> std::io::Write for LtfsFileWriter<'_, M> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, std::io::Error> {
+        let count = std::cmp::min(data.len(), LTFS_BLOCK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&data[..count]);
+        self.length += count as u64;
+
+        if self.buffer.len() == LTFS_BLOCK_SIZE {
+            self.flush_block()
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
+        }
+
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<(), std::io::Error> {
+        Ok(()) // we can only write full blocks
+    }
+}
+
+// Lookup the parent directory for a path (creating missing directories),
+// and return it together with the file name.
+fn lookup_parent<'a>(
+    root: &'a mut LtfsNode,
+    highest_uid: &mut u64,
+    path: &Path,
+    mtime: LtfsTime,
+) -> Result<(&'a mut BTreeMap<Vec<u8>, LtfsNode>, Vec<u8>), Error> {
+    let mut names = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::RootDir | Component::CurDir => continue,
+            Component::Normal(name) => names.push(ltfs_name(name)?),
+            _ => bail!("invalid path {path:?} - contains '..'"),
+        }
+    }
+
+    let name = names
+        .pop()
+        .ok_or_else(|| format_err!("invalid path {path:?} - no file name"))?;
+
+    let mut dir = match root.kind {
+        LtfsNodeKind::Directory(ref mut entries) => entries,
+        _ => bail!("root node is not a directory - internal error"),
+    };
+
+    for dir_name in names {
+        let node = dir.entry(dir_name.clone()).or_insert_with(|| {
+            *highest_uid += 1;
+            LtfsNode {
+                uid: *highest_uid,
+                mtime,
+                kind: LtfsNodeKind::Directory(BTreeMap::new()),
+            }
+        });
+        dir = match node.kind {
+            LtfsNodeKind::Directory(ref mut entries) => entries,
+            _ => bail!(
+                "unable to create {path:?} - '{}' is not a directory",
+                String::from_utf8_lossy(&dir_name)
+            ),
+        };
+    }
+
+    Ok((dir, name))
+}
+
+fn partition_id(partition: u8) -> char {
+    (b'a' + partition) as char
+}
+
+/// Create the 80 byte ANSI `VOL1` label
+///
+/// The volume identifier uses the first 6 characters of
+/// `volume_name` (converted to upper case).
+pub fn vol1_label(volume_name: &str) -> Result<[u8; 80], Error> {
+    // characters allowed by ANSI X3.27 ('a-characters')
+    const SPECIAL: &[u8] = b" !\"%&'()*+,-./:;<=>?_";
+
+    let volume_id: Vec<u8> = volume_name
+        .bytes()
+        .take(6)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    if volume_id.is_empty() {
+        bail!("unable to create VOL1 label - empty volume name");
+    }
+    if let Some(c) = volume_id
+        .iter()
+        .find(|c| !c.is_ascii_alphanumeric() && !SPECIAL.contains(c))
+    {
+        bail!(
+            "unable to create VOL1 label - invalid character '{}' in volume name",
+            *c as char
+        );
+    }
+
+    let mut label = [b' '; 80];
+    label[0..4].copy_from_slice(b"VOL1");
+    label[4..(4 + volume_id.len())].copy_from_slice(&volume_id);
+    label[10] = b'L'; // accessibility (required by LTFS)
+    label[24..28].copy_from_slice(b"LTFS"); // implementation identifier
+    label[79] = b'4'; // label standard version
+
+    Ok(label)
+}
+
+fn ltfs_name(name: &OsStr) -> Result<Vec<u8>, Error> {
+    let name = name.as_bytes();
+    if name.is_empty() || name.len() > 255 {
+        bail!(
+            "invalid file name '{}' - length must be between 1 and 255",
+            String::from_utf8_lossy(name)
+        );
+    }
+    Ok(name.to_vec())
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Names with characters not allowed in XML, or which are not valid UTF-8, use percent encoding
+fn name_xml(name: &[u8]) -> String {
+    if let Ok(name) = std::str::from_utf8(name) {
+        if !name.chars().any(|c| c.is_control()) {
+            return format!("<name>{}</name>", xml_escape(name));
+        }
+    }
+
+    let mut encoded = String::with_capacity(name.len());
+    let mut rest = name;
+    while !rest.is_empty() {
+        let (valid, invalid_len) = match std::str::from_utf8(rest) {
+            Ok(valid) => (valid, 0),
+            Err(err) => {
+                let valid_len = err.valid_up_to();
+                let valid = std::str::from_utf8(&rest[..valid_len]).unwrap();
+                (valid, err.error_len().unwrap_or(rest.len() - valid_len))
+            }
+        };
+        for c in valid.chars() {
+            if c.is_control() || c == '%' {
+                let mut buf = [0u8; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    let _ = write!(encoded, "%{b:02X}");
+                }
+            } else {
+                encoded.push(c);
+            }
+        }
+        let invalid = &rest[valid.len()..valid.len() + invalid_len];
+        for b in invalid {
+            let _ = write!(encoded, "%{b:02X}");
+        }
+        rest = &rest[valid.len() + invalid_len..];
+    }
+
+    format!(
+        r#"<name percentencoded="true">{}</name>"#,
+        xml_escape(&encoded)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsStr;
+    use std::io::Write;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    use anyhow::Error;
+
+    use super::*;
+
+    // Records written to a partition (`None` is a filemark)
+    #[derive(Default)]
+    struct MemoryMedia {
+        partitions: [Vec<Option<Vec<u8>>>; 2],
+        partition: usize,
+        pos: usize,
+    }
+
+    impl MemoryMedia {
+        fn push(&mut self, record: Option<Vec<u8>>) {
+            let partition = &mut self.partitions[self.partition];
+            partition.truncate(self.pos);
+            partition.push(record);
+            self.pos += 1;
+        }
+    }
+
+    impl BlockWrite for MemoryMedia {
+        fn write_block(&mut self, buffer: &[u8]) -> Result<bool, std::io::Error> {
+            assert!(buffer.len() <= LTFS_BLOCK_SIZE);
+            self.push(Some(buffer.to_vec()));
+            Ok(false)
+        }
+
+        fn write_filemark(&mut self) -> Result<(), std::io::Error> {
+            self.push(None);
+            Ok(())
+        }
+    }
+
+    impl LtfsMedia for MemoryMedia {
+        fn format_partitions(&mut self) -> Result<(), Error> {
+            *self = Self::default();
+            Ok(())
+        }
+
+        fn locate(&mut self, partition: u8, logical_object: u64) -> Result<(), Error> {
+            self.partition = partition as usize;
+            self.pos = logical_object as usize;
+            assert!(self.pos <= self.partitions[self.partition].len());
+            Ok(())
+        }
+
+        fn sync(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn record_text(record: &Option<Vec<u8>>) -> String {
+        String::from_utf8(record.clone().expect("expected data block")).unwrap()
+    }
+
+    #[test]
+    fn test_vol1_label() -> Result<(), Error> {
+        let label = vol1_label("tape01L8")?;
+        assert_eq!(&label[..11], b"VOL1TAPE01L");
+        assert_eq!(&label[24..37], b"LTFS         ");
+        assert_eq!(label[79], b'4');
+
+        assert!(vol1_label("").is_err());
+        assert!(vol1_label("t#pe01").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ltfs_volume_layout() -> Result<(), Error> {
+        let mtime = LtfsTime::new(0, 0);
+        let mut writer = LtfsWriter::format(MemoryMedia::default(), "tape01", "test")?;
+
+        let data = vec![0xaa; LTFS_BLOCK_SIZE + 10];
+        let mut file = writer.create_file(Path::new("/store/vm/100/disk.img"), mtime)?;
+        file.write_all(&data)?;
+        assert_eq!(file.finish()?, data.len() as u64);
+
+        let file = writer.create_file(Path::new("/store/empty"), mtime)?;
+        file.finish()?;
+
+        writer.create_symlink(Path::new("/store/link"), OsStr::new("empty"), mtime)?;
+        writer.create_dir(Path::new("/store/a&b"), mtime)?;
+        writer.create_dir(
+            Path::new(OsStr::from_bytes(b"/store/\xff%\n\xc3\xa4")),
+            mtime,
+        )?;
+
+        assert!(writer
+            .create_file(Path::new("/store/empty"), mtime)
+            .is_err());
+        assert!(writer
+            .create_dir(Path::new("/store/empty/x"), mtime)
+            .is_err());
+        assert!(writer.create_dir(Path::new("/store/../x"), mtime).is_err());
+
+        let media = writer.finish()?;
+
+        for partition in &media.partitions {
+            assert_eq!(partition[0].as_deref(), Some(&vol1_label("tape01")?[..]));
+            assert!(partition[1].is_none());
+            assert!(record_text(&partition[2]).contains("<ltfslabel version=\"2.4.0\">"));
+            assert!(partition[3].is_none());
+        }
+
+        // label construct, 2 data blocks, filemark, index, filemark
+        let data_partition = &media.partitions[LTFS_DATA_PARTITION as usize];
+        assert_eq!(data_partition.len(), 9);
+        assert_eq!(data_partition[4].as_ref().unwrap().len(), LTFS_BLOCK_SIZE);
+        assert_eq!(data_partition[5].as_ref().unwrap().len(), 10);
+        assert!(data_partition[6].is_none());
+        assert!(data_partition[8].is_none());
+
+        let index = record_text(&data_partition[7]);
+        assert!(index.contains("<partition>b</partition>\n    <startblock>7</startblock>"));
+        assert!(index.contains("<startblock>4</startblock>"));
+        assert!(index.contains(&format!("<bytecount>{}</bytecount>", data.len())));
+        assert!(index.contains("<name>disk.img</name>"));
+        assert!(index.contains("<symlink>empty</symlink>"));
+        assert!(index.contains("<name>a&amp;b</name>"));
+        assert!(index.contains(r#"<name percentencoded="true">%FF%25%0Aä</name>"#));
+
+        // label construct, index, filemark
+        let index_partition = &media.partitions[LTFS_INDEX_PARTITION as usize];
+        assert_eq!(index_partition.len(), 6);
+        assert!(index_partition[5].is_none());
+
+        let index = record_text(&index_partition[4]);
+        assert!(index.contains("<partition>a</partition>\n    <startblock>4</startblock>"));
+        assert!(index.contains(
+            "<previousgenerationlocation>\n    <partition>b</partition>\n    <startblock>7</startblock>"
+        ));
+
+        Ok(())
+    }
+}
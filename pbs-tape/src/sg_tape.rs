@@ -29,6 +29,7 @@ use proxmox_sys::error::SysResult;
 use pbs_api_types::{Lp17VolumeStatistics, LtoDriveAndMediaStatus, MamAttribute};
 
 use crate::{
+    ltfs::LtfsMedia,
     sgutils2::{
         alloc_page_aligned_buffer, scsi_cmd_mode_select10, scsi_cmd_mode_select6, scsi_inquiry,
         scsi_mode_sense, scsi_request_sense, InquiryInfo, ModeBlockDescriptor, ModeParameterHeader,
@@ -89,6 +90,21 @@ impl MediumConfigurationModePage {
     }
 }
 
+#[repr(C, packed)]
+#[derive(Endian)]
+struct MediumPartitionModePage {
+    page_code: u8, // 0x11
+    page_length: u8,
+    max_additional_partitions: u8,
+    additional_partitions_defined: u8,
+    flags: u8,
+    medium_format_recognition: u8,
+    partition_units: u8,
+    reserved: u8,
+    // we only use 2 partitions
+    partition_size: [u8; 4],
+}
+
 #[derive(Debug)]
 pub struct LtoTapeStatus {
     pub block_length: u32,
@@ -242,6 +258,50 @@ impl SgTape {
         }
     }
 
+    /// Format media, two partitions (index and data partition, as
+    /// used by LTFS)
+    ///
+    /// The first partition uses the minimal size supported by the
+    /// drive, the second partition uses the remaining capacity.
+    pub fn format_media_partitioned(&mut self) -> Result<(), Error> {
+        let (_head, block_descriptor, page) = self.read_medium_configuration_page()?;
+        if page.is_worm() {
+            bail!("format failed - unable to partition WORM media.");
+        }
+        // FORMAT requires LTO5 or newer
+        if block_descriptor.density_code < 0x58 {
+            bail!("format failed - partitioning requires LTO5 or newer");
+        }
+
+        let (mut head, block_descriptor, mut page) = self.read_medium_partition_page()?;
+        if page.max_additional_partitions < 1 {
+            bail!("format failed - drive does not support partitions");
+        }
+
+        head.reset_mode_data_len(); // mode_data_len need to be zero
+
+        page.page_code &= 0b0011_1111; // clear PS bit
+        page.page_length = (std::mem::size_of::<MediumPartitionModePage>() - 2) as u8;
+        page.additional_partitions_defined = 1;
+        page.flags = 0b0011_1000; // IDP=1, PSUM=11b (use partition_units)
+        page.partition_units = 9; // 10^9 bytes
+        page.partition_size = [0, 1, 0xff, 0xff]; // minimal size, rest of media
+
+        self.mode_select(head, block_descriptor, page, "format (mode select)")?;
+
+        self.rewind()?;
+
+        let mut sg_raw = SgRaw::new(&mut self.file, 16)?;
+        sg_raw.set_timeout(Self::SCSI_TAPE_DEFAULT_TIMEOUT);
+        let mut cmd = Vec::new();
+        cmd.extend([0x04, 0, 0x01, 0, 0, 0]); // FORMAT, partition medium
+        sg_raw
+            .do_command(&cmd)
+            .map_err(|err| format_err!("format (partition medium) failed - {err}"))?;
+
+        Ok(())
+    }
+
     /// Lock/Unlock drive door
     pub fn set_medium_removal(&mut self, allow: bool) -> Result<(), ScsiError> {
         let mut sg_raw = SgRaw::new(&mut self.file, 16)?;
@@ -352,6 +412,28 @@ impl SgTape {
         Ok(())
     }
 
+    /// Locate logical object (block or filemark) inside a partition
+    #[allow(clippy::unusual_byte_groupings)]
+    pub fn locate_partition_block(
+        &mut self,
+        partition: u8,
+        logical_object: u64,
+    ) -> Result<(), Error> {
+        let mut sg_raw = SgRaw::new(&mut self.file, 16)?;
+        sg_raw.set_timeout(Self::SCSI_TAPE_DEFAULT_TIMEOUT);
+
+        let mut cmd = Vec::new();
+        cmd.extend([0x92, 0b000_00_010, 0, partition]); // LOCATE(16) logical object, CP=1
+        cmd.extend(logical_object.to_be_bytes());
+        cmd.extend([0, 0, 0, 0]);
+
+        sg_raw.do_command(&cmd).map_err(|err| {
+            format_err!("locate block {logical_object} in partition {partition} failed - {err}")
+        })?;
+
+        Ok(())
+    }
+
     pub fn position(&mut self) -> Result<ReadPositionLongPage, Error> {
         let expected_size = std::mem::size_of::<ReadPositionLongPage>();
 
@@ -738,9 +820,6 @@ impl SgTape {
 
         let (mut head, mut block_descriptor, mut page) = self.read_compression_page()?;
 
-        let mut sg_raw = SgRaw::new(&mut self.file, 0)?;
-        sg_raw.set_timeout(Self::SCSI_TAPE_DEFAULT_TIMEOUT);
-
         head.reset_mode_data_len(); // mode_data_len need to be zero
 
         if let Some(compression) = compression {
@@ -755,6 +834,19 @@ impl SgTape {
             head.set_buffer_mode(buffer_mode);
         }
 
+        self.mode_select(head, block_descriptor, page, "set drive options")
+    }
+
+    fn mode_select<P: Endian>(
+        &mut self,
+        head: ModeParameterHeader,
+        block_descriptor: ModeBlockDescriptor,
+        page: P,
+        what: &str,
+    ) -> Result<(), Error> {
+        let mut sg_raw = SgRaw::new(&mut self.file, 0)?;
+        sg_raw.set_timeout(Self::SCSI_TAPE_DEFAULT_TIMEOUT);
+
         match head {
             ModeParameterHeader::Long(head) => {
                 let mut data = Vec::new();
@@ -773,9 +865,7 @@ impl SgTape {
 
                 sg_raw
                     .do_out_command(&cmd, &buffer[..data.len()])
-                    .map_err(|err| {
-                        format_err!("set drive options (mode select(10)) failed - {err}")
-                    })?;
+                    .map_err(|err| format_err!("{what} (mode select(10)) failed - {err}"))?;
             }
             ModeParameterHeader::Short(head) => {
                 let mut data = Vec::new();
@@ -786,7 +876,7 @@ impl SgTape {
                 }
 
                 if data.len() > u8::MAX as usize {
-                    bail!("set drive options (mode select(6)) failed - parameters too long")
+                    bail!("{what} (mode select(6)) failed - parameters too long")
                 }
                 let cmd = scsi_cmd_mode_select6(data.len() as u8);
 
@@ -796,9 +886,7 @@ impl SgTape {
 
                 sg_raw
                     .do_out_command(&cmd, &buffer[..data.len()])
-                    .map_err(|err| {
-                        format_err!("set drive options (mode select(6)) failed - {err}")
-                    })?;
+                    .map_err(|err| format_err!("{what} (mode select(6)) failed - {err}"))?;
             }
         }
 
@@ -836,6 +924,34 @@ impl SgTape {
         .map_err(|err| format_err!("read_medium_configuration failed - {err}"))
     }
 
+    fn read_medium_partition_page(
+        &mut self,
+    ) -> Result<
+        (
+            ModeParameterHeader,
+            ModeBlockDescriptor,
+            MediumPartitionModePage,
+        ),
+        Error,
+    > {
+        let (head, block_descriptor, page): (_, _, MediumPartitionModePage) =
+            scsi_mode_sense(&mut self.file, false, 0x11, 0)?;
+
+        proxmox_lang::try_block!({
+            if (page.page_code & 0b0011_1111) != 0x11 {
+                bail!("wrong page code {}", page.page_code);
+            }
+
+            let block_descriptor = match block_descriptor {
+                Some(block_descriptor) => block_descriptor,
+                None => bail!("missing block descriptor"),
+            };
+
+            Ok((head, block_descriptor, page))
+        })
+        .map_err(|err| format_err!("read_medium_partition_page failed - {err}"))
+    }
+
     fn read_compression_page(
         &mut self,
     ) -> Result<
@@ -1024,3 +1140,19 @@ impl<'a> BlockWrite for SgTapeWriter<'a> {
         self.sg_tape.write_filemarks(1, true)
     }
 }
+
+impl<'a> LtfsMedia for SgTapeWriter<'a> {
+    fn format_partitions(&mut self) -> Result<(), Error> {
+        self.sg_tape.format_media_partitioned()
+    }
+
+    fn locate(&mut self, partition: u8, logical_object: u64) -> Result<(), Error> {
+        self.sg_tape
+            .locate_partition_block(partition, logical_object)
+    }
+
+    fn sync(&mut self) -> Result<(), Error> {
+        self.sg_tape.sync()?;
+        Ok(())
+    }
+}
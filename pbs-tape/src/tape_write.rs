@@ -64,3 +64,13 @@ pub trait BlockWrite {
     /// Write a filemark
     fn write_filemark(&mut self) -> Result<(), std::io::Error>;
 }
+
+impl<T: BlockWrite + ?Sized> BlockWrite for Box<T> {
+    fn write_block(&mut self, buffer: &[u8]) -> Result<bool, std::io::Error> {
+        (**self).write_block(buffer)
+    }
+
+    fn write_filemark(&mut self) -> Result<(), std::io::Error> {
+        (**self).write_filemark()
+    }
+}
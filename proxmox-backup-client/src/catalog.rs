@@ -9,6 +9,7 @@ use proxmox_router::cli::*;
 use proxmox_schema::api;
 
 use pbs_api_types::BackupNamespace;
+use pbs_client::pxar::payload::check_no_split_archive;
 use pbs_client::tools::key_source::get_encryption_key_password;
 use pbs_client::{BackupReader, RemoteChunkReader};
 use pbs_tools::crypt_config::CryptConfig;
@@ -180,6 +181,8 @@ async fn catalog_shell(param: Value) -> Result<(), Error> {
         }
     };
 
+    check_no_split_archive(archive_name, "catalog shell")?;
+
     let server_archive_name = if archive_name.ends_with(".pxar") {
         format!("{}.didx", archive_name)
     } else {
@@ -6,7 +6,7 @@ use std::sync::{Arc, Mutex};
 use std::task::Context;
 
 use anyhow::{bail, format_err, Error};
-use futures::stream::{StreamExt, TryStreamExt};
+use futures::stream::{StreamExt, TryStream, TryStreamExt};
 use serde::Deserialize;
 use serde_json::{json, Value};
 use tokio::sync::mpsc;
@@ -15,6 +15,7 @@ use xdg::BaseDirectories;
 
 use pathpatterns::{MatchEntry, MatchType, PatternFlag};
 use proxmox_async::blocking::TokioWriterAdapter;
+use proxmox_compression::zstd::ZstdEncoder;
 use proxmox_human_byte::HumanByte;
 use proxmox_io::StdChannelWriter;
 use proxmox_router::{cli::*, ApiMethod, RpcEnvironment};
@@ -23,6 +24,7 @@ use proxmox_sys::fs::{file_get_json, image_size, replace_file, CreateOptions};
 use proxmox_time::{epoch_i64, strftime_local};
 use pxar::accessor::{MaybeReady, ReadAt, ReadAtOperation};
 
+use pbs_api_types::file_restore::FileRestoreFormat;
 use pbs_api_types::{
     Authid, BackupDir, BackupGroup, BackupNamespace, BackupPart, BackupType, CryptMode,
     Fingerprint, GroupListItem, PruneJobOptions, PruneListItem, RateLimitConfig, SnapshotListItem,
@@ -30,6 +32,10 @@ use pbs_api_types::{
     BACKUP_TYPE_SCHEMA, TRAFFIC_CONTROL_BURST_SCHEMA, TRAFFIC_CONTROL_RATE_SCHEMA,
 };
 use pbs_client::catalog_shell::Shell;
+use pbs_client::pxar::payload::{
+    payload_archive_name, split_archive_names, PayloadReader, PreviousArchive, PreviousReader,
+    PxarChangeDetectionMode,
+};
 use pbs_client::pxar::ErrorHandler as PxarErrorHandler;
 use pbs_client::tools::{
     complete_archive_name, complete_auth_id, complete_backup_group, complete_backup_snapshot,
@@ -45,8 +51,8 @@ use pbs_client::tools::{
 use pbs_client::{
     delete_ticket_info, parse_backup_specification, view_task_result, BackupReader,
     BackupRepository, BackupSpecificationType, BackupStats, BackupWriter, ChunkStream,
-    FixedChunkStream, HttpClient, PxarBackupStream, RemoteChunkReader, UploadOptions,
-    BACKUP_SOURCE_SCHEMA,
+    FixedChunkStream, HttpClient, InjectionChunkStream, PxarBackupStream, RemoteChunkReader,
+    UploadOptions, BACKUP_SOURCE_SCHEMA,
 };
 use pbs_datastore::catalog::{BackupCatalogWriter, CatalogReader, CatalogWriter};
 use pbs_datastore::chunk_store::verify_chunk_size;
@@ -183,6 +189,39 @@ pub async fn dir_or_last_from_group(
     }
 }
 
+/// Upload `data` as dynamically chunked archive.
+async fn upload_dynamic_stream<S>(
+    client: &BackupWriter,
+    archive_name: &str,
+    data: S,
+    chunk_size: Option<usize>,
+    upload_options: UploadOptions,
+) -> Result<BackupStats, Error>
+where
+    S: TryStream + Send + Unpin + 'static,
+    S::Ok: AsRef<[u8]>,
+    S::Error: Into<Error>,
+{
+    let mut chunk_stream = ChunkStream::new(data, chunk_size);
+
+    let (tx, rx) = mpsc::channel(10); // allow to buffer 10 chunks
+
+    let stream = ReceiverStream::new(rx).map_err(Error::from);
+
+    // spawn chunker inside a separate task so that it can run parallel
+    tokio::spawn(async move {
+        while let Some(v) = chunk_stream.next().await {
+            let _ = tx.send(v).await;
+        }
+    });
+
+    let stats = client
+        .upload_stream(archive_name, stream, upload_options)
+        .await?;
+
+    Ok(stats)
+}
+
 async fn backup_directory<P: AsRef<Path>>(
     client: &BackupWriter,
     dir_path: P,
@@ -193,28 +232,144 @@ async fn backup_directory<P: AsRef<Path>>(
     upload_options: UploadOptions,
 ) -> Result<BackupStats, Error> {
     let pxar_stream = PxarBackupStream::open(dir_path.as_ref(), catalog, pxar_create_options)?;
+
+    upload_dynamic_stream(
+        client,
+        archive_name,
+        pxar_stream,
+        chunk_size,
+        upload_options,
+    )
+    .await
+}
+
+async fn backup_tar_from_stdin(
+    client: &BackupWriter,
+    archive_name: &str,
+    chunk_size: Option<usize>,
+    catalog: Arc<Mutex<CatalogWriter<TokioWriterAdapter<StdChannelWriter<Error>>>>>,
+    upload_options: UploadOptions,
+) -> Result<BackupStats, Error> {
+    let pxar_stream = PxarBackupStream::from_tar(std::io::stdin(), catalog)?;
+
+    upload_dynamic_stream(
+        client,
+        archive_name,
+        pxar_stream,
+        chunk_size,
+        upload_options,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn backup_directory_split<P: AsRef<Path>>(
+    client: &BackupWriter,
+    dir_path: P,
+    metadata_archive_name: &str,
+    payload_archive_name: &str,
+    chunk_size: Option<usize>,
+    catalog: Arc<Mutex<CatalogWriter<TokioWriterAdapter<StdChannelWriter<Error>>>>>,
+    pxar_create_options: pbs_client::pxar::PxarCreateOptions,
+    previous: Option<PreviousArchive>,
+    upload_options: UploadOptions,
+) -> Result<(BackupStats, BackupStats), Error> {
+    let (pxar_stream, payload_stream) =
+        PxarBackupStream::open_split(dir_path.as_ref(), catalog, pxar_create_options, previous)?;
     let mut chunk_stream = ChunkStream::new(pxar_stream, chunk_size);
+    let mut payload_chunk_stream = InjectionChunkStream::new(payload_stream, chunk_size);
 
     let (tx, rx) = mpsc::channel(10); // allow to buffer 10 chunks
+    let (payload_tx, payload_rx) = mpsc::channel(10);
 
     let stream = ReceiverStream::new(rx).map_err(Error::from);
+    let payload_stream = ReceiverStream::new(payload_rx).map_err(Error::from);
 
-    // spawn chunker inside a separate task so that it can run parallel
+    // spawn chunkers inside separate tasks so that they can run parallel
     tokio::spawn(async move {
         while let Some(v) = chunk_stream.next().await {
             let _ = tx.send(v).await;
         }
     });
+    tokio::spawn(async move {
+        while let Some(v) = payload_chunk_stream.next().await {
+            let _ = payload_tx.send(v).await;
+        }
+    });
+
+    let stats = futures::try_join!(
+        client.upload_stream(metadata_archive_name, stream, upload_options.clone()),
+        client.upload_reused_chunk_stream(payload_archive_name, payload_stream, upload_options),
+    )?;
 
-    if upload_options.fixed_size.is_some() {
-        bail!("cannot backup directory with fixed chunk size!");
+    Ok(stats)
+}
+
+/// Open the split archive of the previous snapshot to detect unchanged files.
+///
+/// Returns `None` if the previous snapshot has no usable split archive.
+#[allow(clippy::too_many_arguments)]
+async fn open_previous_split_archive(
+    repo: &BackupRepository,
+    ns: &BackupNamespace,
+    snapshot: &BackupDir,
+    manifest: &BackupManifest,
+    crypt_config: Option<Arc<CryptConfig>>,
+    crypt_mode: CryptMode,
+    metadata_archive_name: &str,
+    payload_archive_name: &str,
+) -> Result<Option<PreviousArchive>, Error> {
+    for archive_name in [metadata_archive_name, payload_archive_name] {
+        match manifest.lookup_file_info(archive_name) {
+            Ok(file_info) if file_info.crypt_mode == crypt_mode => (),
+            Ok(_) => {
+                log::info!("Previous archive '{archive_name}' uses a different crypt mode.");
+                return Ok(None);
+            }
+            Err(_) => {
+                log::info!(
+                    "Previous manifest does not contain an archive called '{archive_name}'."
+                );
+                return Ok(None);
+            }
+        }
     }
 
-    let stats = client
-        .upload_stream(archive_name, stream, upload_options)
+    let client = connect(repo)?;
+    let client = BackupReader::start(
+        client,
+        crypt_config.clone(),
+        repo.store(),
+        ns,
+        snapshot,
+        true,
+    )
+    .await?;
+
+    let index = client
+        .download_dynamic_index(manifest, metadata_archive_name)
         .await?;
+    let most_used = index.find_most_used_chunks(8);
+    let file_info = manifest.lookup_file_info(metadata_archive_name)?;
+    let chunk_reader = RemoteChunkReader::new(
+        client.clone(),
+        crypt_config,
+        file_info.chunk_crypt_mode(),
+        most_used,
+    );
+    let reader = BufferedDynamicReader::new(index, chunk_reader);
+    let archive_size = reader.archive_size();
+    let reader: PreviousReader = Arc::new(BufferedDynamicReadAt::new(reader));
+    let accessor = pxar::accessor::aio::Accessor::new(reader, archive_size).await?;
 
-    Ok(stats)
+    let payload_index = client
+        .download_dynamic_index(manifest, payload_archive_name)
+        .await?;
+
+    Ok(Some(PreviousArchive {
+        accessor,
+        payload_index,
+    }))
 }
 
 async fn backup_image<P: AsRef<Path>>(
@@ -244,6 +399,25 @@ async fn backup_image<P: AsRef<Path>>(
     Ok(stats)
 }
 
+async fn backup_stream(
+    client: &BackupWriter,
+    source: &str,
+    archive_name: &str,
+    chunk_size: Option<usize>,
+    upload_options: UploadOptions,
+) -> Result<BackupStats, Error> {
+    let reader: Box<dyn tokio::io::AsyncRead + Send + Unpin> = if source == "-" {
+        Box::new(tokio::io::stdin())
+    } else {
+        Box::new(tokio::fs::File::open(source).await?)
+    };
+
+    let stream = tokio_util::codec::FramedRead::new(reader, tokio_util::codec::BytesCodec::new())
+        .map_err(Error::from);
+
+    upload_dynamic_stream(client, archive_name, stream, chunk_size, upload_options).await
+}
+
 pub fn optional_ns_param(param: &Value) -> Result<BackupNamespace, Error> {
     Ok(match param.get("ns") {
         Some(Value::String(ns)) => ns.parse()?,
@@ -567,7 +741,8 @@ fn spawn_catalog_upload(
        properties: {
            backupspec: {
                type: Array,
-               description: "List of backup source specifications ([<label.ext>:<path>] ...)",
+               description: "List of backup source specifications ([<label.ext>:<path>] ...). \
+                   Use '-' as path of a .pxar archive to read a tar archive from stdin.",
                items: {
                    schema: BACKUP_SOURCE_SCHEMA,
                }
@@ -665,15 +840,21 @@ fn spawn_catalog_upload(
                optional: true,
                default: false,
            },
+           "change-detection-mode": {
+               type: PxarChangeDetectionMode,
+               optional: true,
+           },
        }
    }
 )]
 /// Create (host) backup.
+#[allow(clippy::too_many_arguments)]
 async fn create_backup(
     param: Value,
     all_file_systems: bool,
     skip_lost_and_found: bool,
     dry_run: bool,
+    change_detection_mode: Option<PxarChangeDetectionMode>,
     _info: &ApiMethod,
     _rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<Value, Error> {
@@ -753,6 +934,7 @@ async fn create_backup(
 
     let mut upload_list = vec![];
     let mut target_set = HashSet::new();
+    let mut read_stdin = false;
 
     for backupspec in backupspec_list {
         let spec = parse_backup_specification(backupspec.as_str().unwrap())?;
@@ -764,6 +946,26 @@ async fn create_backup(
         }
         target_set.insert(target.to_string());
 
+        if filename == "-" {
+            // .pxar archives are converted from a tar archive, streams are stored as they are
+            let backup_type = match spec.spec_type {
+                BackupSpecificationType::PXAR => BackupSpecificationType::PXAR,
+                BackupSpecificationType::STREAM => BackupSpecificationType::STREAM,
+                _ => bail!("only .pxar and .stream archives can be read from stdin"),
+            };
+            if read_stdin {
+                bail!("only one archive can be read from stdin");
+            }
+            read_stdin = true;
+            upload_list.push((
+                backup_type,
+                filename.to_owned(),
+                format!("{}.didx", target),
+                0,
+            ));
+            continue;
+        }
+
         use std::os::unix::fs::FileTypeExt;
 
         let metadata = std::fs::metadata(filename)
@@ -822,6 +1024,17 @@ async fn create_backup(
                     metadata.len(),
                 ));
             }
+            BackupSpecificationType::STREAM => {
+                if !(file_type.is_file() || file_type.is_fifo()) {
+                    bail!("got unexpected file type (expected regular file or fifo)");
+                }
+                upload_list.push((
+                    BackupSpecificationType::STREAM,
+                    filename.to_owned(),
+                    format!("{}.didx", target),
+                    0,
+                ));
+            }
         }
     }
 
@@ -889,12 +1102,19 @@ async fn create_backup(
     )
     .await?;
 
+    let mut previous_snapshot = None;
+
     let download_previous_manifest = match client.previous_backup_time().await {
         Ok(Some(backup_time)) => {
             log::info!(
                 "Downloading previous manifest ({})",
                 strftime_local("%c", backup_time)?
             );
+            previous_snapshot = Some(BackupDir::from((
+                backup_type,
+                backup_id.to_owned(),
+                backup_time,
+            )));
             true
         }
         Ok(None) => {
@@ -927,6 +1147,8 @@ async fn create_backup(
         None
     };
 
+    let change_detection_mode = change_detection_mode.unwrap_or_default();
+
     let mut manifest = BackupManifest::new(snapshot);
 
     let mut catalog = None;
@@ -938,12 +1160,22 @@ async fn create_backup(
     };
 
     for (backup_type, filename, target, size) in upload_list {
+        // only images are chunked with a fixed size, all other archives use the dynamic chunker
+        let fixed_size = match backup_type {
+            BackupSpecificationType::IMAGE => Some(size),
+            _ => None,
+        };
+
         match (backup_type, dry_run) {
             // dry-run
             (BackupSpecificationType::CONFIG, true) => log_file("config file", &filename, &target),
             (BackupSpecificationType::LOGFILE, true) => log_file("log file", &filename, &target),
+            (BackupSpecificationType::PXAR, true) if filename == "-" => {
+                log_file("tar archive", &filename, &target)
+            }
             (BackupSpecificationType::PXAR, true) => log_file("directory", &filename, &target),
             (BackupSpecificationType::IMAGE, true) => log_file("image", &filename, &target),
+            (BackupSpecificationType::STREAM, true) => log_file("stream", &filename, &target),
             // no dry-run
             (BackupSpecificationType::CONFIG, false) => {
                 let upload_options = UploadOptions {
@@ -982,11 +1214,33 @@ async fn create_backup(
                 }
                 let catalog = catalog.as_ref().unwrap();
 
-                log_file("directory", &filename, &target);
+                let from_stdin = filename == "-";
+
+                let split_targets = match change_detection_mode {
+                    PxarChangeDetectionMode::Legacy => None,
+                    PxarChangeDetectionMode::Metadata if from_stdin => {
+                        bail!("change detection mode 'metadata' is not supported for tar input");
+                    }
+                    PxarChangeDetectionMode::Metadata => Some(
+                        split_archive_names(&target)
+                            .ok_or_else(|| format_err!("invalid pxar archive name '{target}'"))?,
+                    ),
+                };
+
+                let catalog_target = match split_targets {
+                    Some((ref metadata_target, _)) => metadata_target,
+                    None => &target,
+                };
+
+                if from_stdin {
+                    log_file("tar archive", &filename, catalog_target);
+                } else {
+                    log_file("directory", &filename, catalog_target);
+                }
                 catalog
                     .lock()
                     .unwrap()
-                    .start_directory(std::ffi::CString::new(target.as_str())?.as_c_str())?;
+                    .start_directory(std::ffi::CString::new(catalog_target.as_str())?.as_c_str())?;
 
                 let pxar_options = pbs_client::pxar::PxarCreateOptions {
                     device_set: devices.clone(),
@@ -997,22 +1251,86 @@ async fn create_backup(
 
                 let upload_options = UploadOptions {
                     previous_manifest: previous_manifest.clone(),
+                    fixed_size,
                     compress: true,
                     encrypt: crypto.mode == CryptMode::Encrypt,
-                    ..UploadOptions::default()
                 };
 
-                let stats = backup_directory(
-                    &client,
-                    &filename,
-                    &target,
-                    chunk_size_opt,
-                    catalog.clone(),
-                    pxar_options,
-                    upload_options,
-                )
-                .await?;
-                manifest.add_file(target, stats.size, stats.csum, crypto.mode)?;
+                if let Some((metadata_target, payload_target)) = split_targets {
+                    let previous = match (&previous_manifest, &previous_snapshot) {
+                        (Some(previous_manifest), Some(previous_snapshot)) => {
+                            match open_previous_split_archive(
+                                &repo,
+                                &backup_ns,
+                                previous_snapshot,
+                                previous_manifest,
+                                crypt_config.clone(),
+                                crypto.mode,
+                                &metadata_target,
+                                &payload_target,
+                            )
+                            .await
+                            {
+                                Ok(previous) => previous,
+                                Err(err) => {
+                                    log::warn!("Couldn't open previous split archive - {err}");
+                                    None
+                                }
+                            }
+                        }
+                        _ => None,
+                    };
+                    if previous.is_none() {
+                        log::info!("Reading all files of '{filename}'");
+                    }
+
+                    let (metadata_stats, payload_stats) = backup_directory_split(
+                        &client,
+                        &filename,
+                        &metadata_target,
+                        &payload_target,
+                        chunk_size_opt,
+                        catalog.clone(),
+                        pxar_options,
+                        previous,
+                        upload_options,
+                    )
+                    .await?;
+                    manifest.add_file(
+                        metadata_target,
+                        metadata_stats.size,
+                        metadata_stats.csum,
+                        crypto.mode,
+                    )?;
+                    manifest.add_file(
+                        payload_target,
+                        payload_stats.size,
+                        payload_stats.csum,
+                        crypto.mode,
+                    )?;
+                } else if from_stdin {
+                    let stats = backup_tar_from_stdin(
+                        &client,
+                        &target,
+                        chunk_size_opt,
+                        catalog.clone(),
+                        upload_options,
+                    )
+                    .await?;
+                    manifest.add_file(target, stats.size, stats.csum, crypto.mode)?;
+                } else {
+                    let stats = backup_directory(
+                        &client,
+                        &filename,
+                        &target,
+                        chunk_size_opt,
+                        catalog.clone(),
+                        pxar_options,
+                        upload_options,
+                    )
+                    .await?;
+                    manifest.add_file(target, stats.size, stats.csum, crypto.mode)?;
+                }
                 catalog.lock().unwrap().end_directory()?;
             }
             (BackupSpecificationType::IMAGE, false) => {
@@ -1020,7 +1338,7 @@ async fn create_backup(
 
                 let upload_options = UploadOptions {
                     previous_manifest: previous_manifest.clone(),
-                    fixed_size: Some(size),
+                    fixed_size,
                     compress: true,
                     encrypt: crypto.mode == CryptMode::Encrypt,
                 };
@@ -1030,6 +1348,21 @@ async fn create_backup(
                         .await?;
                 manifest.add_file(target, stats.size, stats.csum, crypto.mode)?;
             }
+            (BackupSpecificationType::STREAM, false) => {
+                log_file("stream", &filename, &target);
+
+                let upload_options = UploadOptions {
+                    previous_manifest: previous_manifest.clone(),
+                    fixed_size,
+                    compress: true,
+                    encrypt: crypto.mode == CryptMode::Encrypt,
+                };
+
+                let stats =
+                    backup_stream(&client, &filename, &target, chunk_size_opt, upload_options)
+                        .await?;
+                manifest.add_file(target, stats.size, stats.csum, crypto.mode)?;
+            }
         }
     }
 
@@ -1139,13 +1472,62 @@ async fn dump_image<W: Write>(
     Ok(())
 }
 
+/// Convert a pxar archive to a tar or zip archive, written to `target` or stdout.
+async fn convert_pxar_archive(
+    accessor: pxar::accessor::aio::Accessor<Arc<dyn ReadAt + Send + Sync>>,
+    format: FileRestoreFormat,
+    zstd: bool,
+    target: Option<&str>,
+) -> Result<(), Error> {
+    use tokio::io::AsyncWriteExt;
+
+    let (writer, reader) = tokio::io::duplex(1024 * 1024);
+
+    let task = match format {
+        FileRestoreFormat::Tar => tokio::spawn(pbs_client::pxar::create_tar(writer, accessor, "/")),
+        FileRestoreFormat::Zip => tokio::spawn(pbs_client::pxar::create_zip(writer, accessor, "/")),
+        FileRestoreFormat::Plain | FileRestoreFormat::Pxar => {
+            bail!("only tar and zip are supported as archive format");
+        }
+    };
+
+    let mut output: Box<dyn tokio::io::AsyncWrite + Send + Unpin> = match target {
+        Some(target) => Box::new(
+            tokio::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(target)
+                .await
+                .map_err(|err| format_err!("unable to create target file {target:?} - {err}"))?,
+        ),
+        None => Box::new(tokio::io::stdout()),
+    };
+
+    if zstd {
+        let mut stream = ZstdEncoder::new(tokio_util::io::ReaderStream::new(reader))?;
+        while let Some(data) = stream.next().await {
+            output.write_all(&data?).await?;
+        }
+    } else {
+        let mut reader = reader;
+        tokio::io::copy(&mut reader, &mut output).await?;
+    }
+    output.flush().await?;
+
+    task.await??;
+
+    Ok(())
+}
+
 fn parse_archive_type(name: &str) -> (String, ArchiveType) {
     if name.ends_with(".didx") || name.ends_with(".fidx") || name.ends_with(".blob") {
         (name.into(), archive_type(name).unwrap())
-    } else if name.ends_with(".pxar") {
+    } else if name.ends_with(".pxar") || name.ends_with(".mpxar") || name.ends_with(".ppxar") {
         (format!("{}.didx", name), ArchiveType::DynamicIndex)
     } else if name.ends_with(".img") {
         (format!("{}.fidx", name), ArchiveType::FixedIndex)
+    } else if name.ends_with(".stream") {
+        (format!("{}.didx", name), ArchiveType::DynamicIndex)
     } else {
         (format!("{}.blob", name), ArchiveType::Blob)
     }
@@ -1176,6 +1558,11 @@ fn parse_archive_type(name: &str) -> (String, ArchiveType) {
 
 We do not extract '.pxar' archives when writing to standard output.
 
+If 'format' is set, the '.pxar' archive is converted to a tar or zip archive written to this
+path or to standard output.
+
+The contents of '.stream' archives are written to this file or to standard output.
+
 "###
             },
             rate: {
@@ -1254,7 +1641,17 @@ We do not extract '.pxar' archives when writing to standard output.
                 description: "ignore errors that occur during device node extraction",
                 optional: true,
                 default: false,
-            }
+            },
+            format: {
+                type: FileRestoreFormat,
+                optional: true,
+            },
+            zstd: {
+                type: Boolean,
+                description: "Compress the archive created with 'format' using zstd.",
+                optional: true,
+                default: false,
+            },
         }
     }
 )]
@@ -1271,6 +1668,8 @@ async fn restore(
     overwrite_symlinks: bool,
     overwrite_hardlinks: bool,
     ignore_extract_device_errors: bool,
+    format: Option<FileRestoreFormat>,
+    zstd: bool,
 ) -> Result<Value, Error> {
     let repo = extract_repository_from_value(&param)?;
 
@@ -1354,6 +1753,17 @@ async fn restore(
         return Ok(Value::Null);
     }
 
+    // fall back to the metadata archive if the directory was backed up as split archive
+    let archive_name = match split_archive_names(&archive_name) {
+        Some((metadata_archive_name, _))
+            if manifest.lookup_file_info(&archive_name).is_err()
+                && manifest.lookup_file_info(&metadata_archive_name).is_ok() =>
+        {
+            metadata_archive_name
+        }
+        _ => archive_name,
+    };
+
     let file_info = manifest.lookup_file_info(&archive_name)?;
 
     if archive_type == ArchiveType::Blob {
@@ -1375,11 +1785,91 @@ async fn restore(
             std::io::copy(&mut reader, &mut writer)
                 .map_err(|err| format_err!("unable to pipe data - {}", err))?;
         }
+    } else if archive_type == ArchiveType::DynamicIndex && archive_name.ends_with(".stream.didx") {
+        if format.is_some() {
+            bail!("unable to convert stream archive '{archive_name}'");
+        }
+
+        let index = client
+            .download_dynamic_index(&manifest, &archive_name)
+            .await?;
+
+        let most_used = index.find_most_used_chunks(8);
+        let chunk_reader = RemoteChunkReader::new(
+            client.clone(),
+            crypt_config,
+            file_info.chunk_crypt_mode(),
+            most_used,
+        );
+        let mut reader = BufferedDynamicReader::new(index, chunk_reader);
+
+        let mut writer = if let Some(target) = target {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .create_new(true)
+                .open(target)
+                .map_err(|err| format_err!("unable to create target file {:?} - {}", target, err))?
+        } else {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .open("/dev/stdout")
+                .map_err(|err| format_err!("unable to open /dev/stdout - {}", err))?
+        };
+
+        std::io::copy(&mut reader, &mut writer)
+            .map_err(|err| format_err!("unable to restore stream - {}", err))?;
     } else if archive_type == ArchiveType::DynamicIndex {
         let index = client
             .download_dynamic_index(&manifest, &archive_name)
             .await?;
 
+        if let Some(format) = format {
+            if payload_archive_name(&archive_name).is_some() {
+                bail!("unable to convert split archive '{archive_name}'");
+            }
+
+            let most_used = index.find_most_used_chunks(8);
+            let chunk_reader = RemoteChunkReader::new(
+                client.clone(),
+                crypt_config,
+                file_info.chunk_crypt_mode(),
+                most_used,
+            );
+            let reader = BufferedDynamicReader::new(index, chunk_reader);
+            let archive_size = reader.archive_size();
+            let reader: Arc<dyn ReadAt + Send + Sync> =
+                Arc::new(BufferedDynamicReadAt::new(reader));
+            let accessor = pxar::accessor::aio::Accessor::new(reader, archive_size).await?;
+
+            convert_pxar_archive(accessor, format, zstd, target).await?;
+
+            return Ok(Value::Null);
+        }
+
+        let payload = match payload_archive_name(&archive_name) {
+            Some(payload_archive_name) => {
+                if target.is_none() {
+                    bail!("unable to pipe split archive '{archive_name}' to stdout");
+                }
+                let payload_index = client
+                    .download_dynamic_index(&manifest, &payload_archive_name)
+                    .await?;
+                let most_used = payload_index.find_most_used_chunks(8);
+                let payload_info = manifest.lookup_file_info(&payload_archive_name)?;
+                let chunk_reader = RemoteChunkReader::new(
+                    client.clone(),
+                    crypt_config.clone(),
+                    payload_info.chunk_crypt_mode(),
+                    most_used,
+                );
+                let reader: Box<dyn PayloadReader + Send> =
+                    Box::new(BufferedDynamicReader::new(payload_index, chunk_reader));
+                Some(reader)
+            }
+            None => None,
+        };
+
         let most_used = index.find_most_used_chunks(8);
 
         let chunk_reader = RemoteChunkReader::new(
@@ -1426,6 +1916,7 @@ async fn restore(
             allow_existing_dirs,
             overwrite_flags,
             on_error,
+            payload,
         };
 
         let mut feature_flags = pbs_client::pxar::Flags::DEFAULT;
@@ -18,6 +18,7 @@ use proxmox_schema::*;
 use proxmox_sortable_macro::sortable;
 
 use pbs_api_types::BackupNamespace;
+use pbs_client::pxar::payload::check_no_split_archive;
 use pbs_client::tools::key_source::get_encryption_key_password;
 use pbs_client::{BackupReader, RemoteChunkReader};
 use pbs_datastore::cached_chunk_reader::CachedChunkReader;
@@ -219,6 +220,8 @@ async fn mount_do(param: Value, pipe: Option<OwnedFd>) -> Result<Value, Error> {
         }
     };
 
+    check_no_split_archive(archive_name, "mounting")?;
+
     let server_archive_name = if archive_name.ends_with(".pxar") {
         if target.is_none() {
             bail!("use the 'mount' command to mount pxar archives");
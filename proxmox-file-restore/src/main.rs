@@ -21,6 +21,7 @@ use pxar::accessor::aio::Accessor;
 use pxar::decoder::aio::Decoder;
 
 use pbs_api_types::{file_restore::FileRestoreFormat, BackupDir, BackupNamespace, CryptMode};
+use pbs_client::pxar::payload::check_no_split_archive;
 use pbs_client::pxar::{create_tar, create_zip, extract_sub_dir, extract_sub_dir_seq};
 use pbs_client::tools::{
     complete_group_or_snapshot, complete_repository, connect, extract_repository_from_value,
@@ -75,6 +76,8 @@ fn parse_path(path: String, base64: bool) -> Result<ExtractPath, Error> {
         (file, path)
     };
 
+    check_no_split_archive(&file, "file-restore")?;
+
     if file.ends_with(".pxar.didx") {
         Ok(ExtractPath::Pxar(file, path))
     } else if file.ends_with(".img.fidx") {
@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 use std::ffi::OsStr;
 use std::fs::OpenOptions;
+use std::io::Write;
 use std::os::unix::fs::OpenOptionsExt;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -15,6 +16,9 @@ use pathpatterns::{MatchEntry, MatchType, PatternFlag};
 use pbs_client::pxar::{
     format_single_line_entry, Flags, OverwriteFlags, PxarExtractOptions, ENCODER_MAX_ENTRIES,
 };
+use pxar::accessor::aio::Accessor;
+use pxar::accessor::sync::FileReader;
+use pxar::accessor::ReadAt;
 
 use proxmox_router::cli::*;
 use proxmox_schema::api;
@@ -214,6 +218,7 @@ fn extract_archive(
         overwrite_flags,
         extract_match_default,
         on_error,
+        payload: None,
     };
 
     if archive == "-" {
@@ -383,6 +388,7 @@ async fn create_archive(
         },
         None,
         options,
+        None,
     )
     .await?;
 
@@ -447,6 +453,91 @@ fn dump_archive(archive: String) -> Result<(), Error> {
     Ok(())
 }
 
+#[api(
+    input: {
+        properties: {
+            archive: {
+                description: "Archive name.",
+            },
+            source: {
+                description: "Tar archive to convert, '-' or none to read from stdin.",
+                optional: true,
+            },
+        },
+    },
+)]
+/// Create a new .pxar archive from a tar archive.
+///
+/// Extended attributes, file capabilities and ACLs are taken from the pax headers of the tar
+/// archive.
+fn import_tar_archive(archive: String, source: Option<String>) -> Result<(), Error> {
+    let file = OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .mode(0o640)
+        .open(archive)?;
+    let mut writer = std::io::BufWriter::with_capacity(1024 * 1024, file);
+
+    match source.as_deref() {
+        None | Some("-") => {
+            pbs_client::pxar::import_tar(std::io::stdin().lock(), &mut writer, None)?
+        }
+        Some(source) => {
+            let reader = std::io::BufReader::new(std::fs::File::open(source)?);
+            pbs_client::pxar::import_tar(reader, &mut writer, None)?
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            archive: {
+                description: "Archive name.",
+            },
+            target: {
+                description: "Target tar archive, '-' or none to write to stdout.",
+                optional: true,
+            },
+        },
+    },
+)]
+/// Convert a .pxar archive into a tar archive.
+///
+/// Extended attributes, file capabilities and ACLs are stored as pax headers.
+async fn export_tar_archive(archive: String, target: Option<String>) -> Result<(), Error> {
+    use tokio::io::AsyncWriteExt;
+
+    let file = std::fs::File::open(archive)?;
+    let file_size = file.metadata()?.len();
+    let reader: Arc<dyn ReadAt + Send + Sync + 'static> = Arc::new(FileReader::new(file));
+    let accessor = Accessor::new(reader, file_size).await?;
+
+    let mut output: Box<dyn tokio::io::AsyncWrite + Unpin + Send> = match target.as_deref() {
+        None | Some("-") => Box::new(tokio::io::stdout()),
+        Some(target) => {
+            let file = OpenOptions::new()
+                .create_new(true)
+                .write(true)
+                .mode(0o640)
+                .open(target)?;
+            Box::new(tokio::fs::File::from_std(file))
+        }
+    };
+
+    let (writer, mut reader) = tokio::io::duplex(1024 * 1024);
+    let task = tokio::spawn(pbs_client::pxar::create_tar(writer, accessor, "/"));
+
+    tokio::io::copy(&mut reader, &mut output).await?;
+    output.flush().await?;
+
+    task.await?
+}
+
 fn main() {
     init_cli_logger("PXAR_LOG", "info");
 
@@ -478,6 +569,20 @@ fn main() {
             CliCommand::new(&API_METHOD_DUMP_ARCHIVE)
                 .arg_param(&["archive"])
                 .completion_cb("archive", complete_file_name),
+        )
+        .insert(
+            "import-tar",
+            CliCommand::new(&API_METHOD_IMPORT_TAR_ARCHIVE)
+                .arg_param(&["archive", "source"])
+                .completion_cb("archive", complete_file_name)
+                .completion_cb("source", complete_file_name),
+        )
+        .insert(
+            "export-tar",
+            CliCommand::new(&API_METHOD_EXPORT_TAR_ARCHIVE)
+                .arg_param(&["archive", "target"])
+                .completion_cb("archive", complete_file_name)
+                .completion_cb("target", complete_file_name),
         );
 
     let rpcenv = CliEnvironment::new();
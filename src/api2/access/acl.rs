@@ -272,6 +272,12 @@ pub fn update_acl(
     Ok(())
 }
 
+crate::audited_api_method!(
+    AUDITED_UPDATE_ACL = API_METHOD_UPDATE_ACL,
+    "PUT",
+    "/access/acl"
+);
+
 pub const ROUTER: Router = Router::new()
     .get(&API_METHOD_READ_ACL)
-    .put(&API_METHOD_UPDATE_ACL);
+    .put(&AUDITED_UPDATE_ACL);
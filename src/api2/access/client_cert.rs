@@ -0,0 +1,318 @@
+//! TLS client certificate login and certificate management
+use anyhow::{format_err, Error};
+use hex::FromHex;
+use serde_json::{json, Value};
+
+use proxmox_auth_api::api::ApiTicket;
+use proxmox_auth_api::ticket::Ticket;
+use proxmox_router::{http_bail, http_err, ApiMethod, Permission, Router, RpcEnvironment};
+use proxmox_schema::{api, param_bail};
+
+use pbs_api_types::{
+    ClientCertificate, Userid, CLIENT_CERT_ID_SCHEMA, PRIV_PERMISSIONS_MODIFY, PRIV_SYS_AUDIT,
+    PROXMOX_CONFIG_DIGEST_SCHEMA, SINGLE_LINE_COMMENT_SCHEMA,
+};
+use pbs_tools::cert::CertInfo;
+
+use crate::auth::private_auth_keyring;
+use crate::auth_helpers::*;
+
+#[api(
+    input: {
+        properties: {
+            username: {
+                type: Userid,
+            },
+        },
+    },
+    returns: {
+        properties: {
+            challenge: {
+                type: String,
+                description: "One-time challenge to sign for the login.",
+            },
+        },
+    },
+    protected: true,
+    access: {
+        permission: &Permission::World,
+    },
+)]
+/// Create a one-time challenge for a TLS client certificate login
+///
+/// The challenge is only valid for a short time, and has to be signed together with the
+/// fingerprint of this server's certificate, see `cert-ticket`.
+pub fn create_cert_challenge(username: Userid) -> Result<Value, Error> {
+    let challenge = crate::server::auth::create_client_cert_challenge(&username)?;
+    Ok(json!({ "challenge": challenge }))
+}
+
+#[api(
+    input: {
+        properties: {
+            username: {
+                type: Userid,
+            },
+            certificate: {
+                description: "PEM encoded TLS client certificate.",
+                type: String,
+            },
+            challenge: {
+                description: "Challenge issued by 'cert-challenge', part of the signed data.",
+                type: String,
+            },
+            signature: {
+                description: "Hex encoded signature of the login data, made with the certificate's private key.",
+                type: String,
+            },
+        },
+    },
+    returns: {
+        properties: {
+            username: {
+                type: String,
+                description: "User name.",
+            },
+            ticket: {
+                type: String,
+                description: "Auth ticket.",
+            },
+            CSRFPreventionToken: {
+                type: String,
+                description: "Cross Site Request Forgery Prevention Token.",
+            },
+        },
+    },
+    protected: true,
+    access: {
+        permission: &Permission::World,
+    },
+)]
+/// Verify a TLS client certificate login and create a ticket
+///
+/// Returns: An authentication ticket with additional infos.
+pub fn create_cert_ticket(
+    username: Userid,
+    certificate: String,
+    challenge: String,
+    signature: String,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    use proxmox_rest_server::RestEnvironment;
+
+    let env: &RestEnvironment = rpcenv
+        .as_any()
+        .downcast_ref::<RestEnvironment>()
+        .ok_or_else(|| format_err!("detected wrong RpcEnvironment type"))?;
+
+    let result = proxmox_lang::try_block!({
+        crate::server::auth::verify_client_cert_login(
+            &username,
+            &certificate,
+            &challenge,
+            &signature,
+        )?;
+
+        let api_ticket = ApiTicket::Full(username.clone());
+        let ticket = Ticket::new("PBS", &api_ticket)?.sign(private_auth_keyring(), None)?;
+        let token = assemble_csrf_prevention_token(csrf_secret(), &username);
+
+        env.log_auth(username.as_str());
+
+        Ok(json!({
+            "username": username,
+            "ticket": ticket,
+            "CSRFPreventionToken": token,
+        }))
+    });
+
+    if let Err(ref err) = result {
+        let msg = err.to_string();
+        env.log_failed_auth(Some(username.to_string()), &msg);
+        return Err(http_err!(UNAUTHORIZED, "{}", msg));
+    }
+
+    result
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "The list of registered client certificates (with config digest).",
+        type: Array,
+        items: { type: ClientCertificate },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "users"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List registered TLS client certificates
+pub fn list_client_certs(
+    _param: Value,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<ClientCertificate>, Error> {
+    let (config, digest) = pbs_config::client_cert::config()?;
+
+    let list: Vec<ClientCertificate> = config.convert_to_typed_array("client-cert")?;
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: CLIENT_CERT_ID_SCHEMA,
+            },
+            userid: {
+                type: Userid,
+            },
+            certificate: {
+                description: "PEM encoded TLS client certificate.",
+                type: String,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "users"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Register a TLS client certificate which allows the user to log in without password.
+pub fn create_client_cert(
+    id: String,
+    userid: Userid,
+    certificate: String,
+    comment: Option<String>,
+) -> Result<(), Error> {
+    let cert = CertInfo::from_pem(certificate.as_bytes())
+        .map_err(|err| format_err!("unable to parse certificate - {err}"))?;
+
+    if cert.is_expired_after_epoch(proxmox_time::epoch_i64())? {
+        param_bail!("certificate", "certificate is expired");
+    }
+
+    let _lock = pbs_config::client_cert::lock_config()?;
+
+    let (mut config, _digest) = pbs_config::client_cert::config()?;
+
+    if config.sections.get(&id).is_some() {
+        param_bail!("id", "client certificate '{}' already exists.", id);
+    }
+
+    let fingerprint = cert.fingerprint()?;
+    let list: Vec<ClientCertificate> = config.convert_to_typed_array("client-cert")?;
+    if let Some(other) = list.iter().find(|c| c.fingerprint == fingerprint) {
+        param_bail!(
+            "certificate",
+            "certificate is already registered as '{}'.",
+            other.id
+        );
+    }
+
+    let data = ClientCertificate {
+        id: id.clone(),
+        userid,
+        fingerprint,
+        comment,
+    };
+    config.set_data(&id, "client-cert", &data)?;
+
+    pbs_config::client_cert::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            id: {
+                schema: CLIENT_CERT_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: ClientCertificate },
+    access: {
+        permission: &Permission::Privilege(&["access", "users"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read a registered TLS client certificate.
+pub fn read_client_cert(
+    id: String,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<ClientCertificate, Error> {
+    let (config, digest) = pbs_config::client_cert::config()?;
+    let data: ClientCertificate = config.lookup("client-cert", &id)?;
+    rpcenv["digest"] = hex::encode(digest).into();
+    Ok(data)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: CLIENT_CERT_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "users"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Remove a registered TLS client certificate.
+pub fn delete_client_cert(id: String, digest: Option<String>) -> Result<(), Error> {
+    let _lock = pbs_config::client_cert::lock_config()?;
+
+    let (mut config, expected_digest) = pbs_config::client_cert::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.sections.get(&id) {
+        Some(_) => {
+            config.sections.remove(&id);
+        }
+        None => http_bail!(NOT_FOUND, "client certificate '{}' does not exist.", id),
+    }
+
+    pbs_config::client_cert::save_config(&config)?;
+
+    Ok(())
+}
+
+crate::audited_api_method!(
+    AUDITED_CREATE_CLIENT_CERT = API_METHOD_CREATE_CLIENT_CERT,
+    "POST",
+    "/access/client-certs"
+);
+crate::audited_api_method!(
+    AUDITED_DELETE_CLIENT_CERT = API_METHOD_DELETE_CLIENT_CERT,
+    "DELETE",
+    "/access/client-certs/{id}"
+);
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_CLIENT_CERT)
+    .delete(&AUDITED_DELETE_CLIENT_CERT);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_CLIENT_CERTS)
+    .post(&AUDITED_CREATE_CLIENT_CERT)
+    .match_all("id", &ITEM_ROUTER);
@@ -18,6 +18,7 @@ use pbs_config::acl::AclTreeNode;
 use pbs_config::CachedUserInfo;
 
 pub mod acl;
+pub mod client_cert;
 pub mod domain;
 pub mod openid;
 pub mod role;
@@ -204,10 +205,25 @@ pub fn list_permissions(
     Ok(map)
 }
 
+crate::audited_api_method!(
+    AUDITED_CHANGE_PASSWORD = API_METHOD_CHANGE_PASSWORD,
+    "PUT",
+    "/access/password"
+);
+
 #[sortable]
 const SUBDIRS: SubdirMap = &sorted!([
     ("acl", &acl::ROUTER),
-    ("password", &Router::new().put(&API_METHOD_CHANGE_PASSWORD)),
+    (
+        "cert-challenge",
+        &Router::new().post(&client_cert::API_METHOD_CREATE_CERT_CHALLENGE)
+    ),
+    (
+        "cert-ticket",
+        &Router::new().post(&client_cert::API_METHOD_CREATE_CERT_TICKET)
+    ),
+    ("client-certs", &client_cert::ROUTER),
+    ("password", &Router::new().put(&AUDITED_CHANGE_PASSWORD)),
     (
         "permissions",
         &Router::new().get(&API_METHOD_LIST_PERMISSIONS)
@@ -298,16 +298,32 @@ async fn update_tfa_entry(
     Ok(())
 }
 
+crate::audited_api_method!(
+    AUDITED_ADD_TFA_ENTRY = API_METHOD_ADD_TFA_ENTRY,
+    "POST",
+    "/access/tfa/{userid}"
+);
+crate::audited_api_method!(
+    AUDITED_UPDATE_TFA_ENTRY = API_METHOD_UPDATE_TFA_ENTRY,
+    "PUT",
+    "/access/tfa/{userid}/{id}"
+);
+crate::audited_api_method!(
+    AUDITED_DELETE_TFA = API_METHOD_DELETE_TFA,
+    "DELETE",
+    "/access/tfa/{userid}/{id}"
+);
+
 pub const ROUTER: Router = Router::new()
     .get(&API_METHOD_LIST_TFA)
     .match_all("userid", &USER_ROUTER);
 
 const USER_ROUTER: Router = Router::new()
     .get(&API_METHOD_LIST_USER_TFA)
-    .post(&API_METHOD_ADD_TFA_ENTRY)
+    .post(&AUDITED_ADD_TFA_ENTRY)
     .match_all("id", &ITEM_ROUTER);
 
 const ITEM_ROUTER: Router = Router::new()
     .get(&API_METHOD_GET_TFA_ENTRY)
-    .put(&API_METHOD_UPDATE_TFA_ENTRY)
-    .delete(&API_METHOD_DELETE_TFA);
+    .put(&AUDITED_UPDATE_TFA_ENTRY)
+    .delete(&AUDITED_DELETE_TFA);
@@ -776,11 +776,42 @@ pub fn unlock_tfa(userid: Userid) -> Result<bool, Error> {
     }
 }
 
+crate::audited_api_method!(
+    AUDITED_UPDATE_TOKEN = API_METHOD_UPDATE_TOKEN,
+    "PUT",
+    "/access/users/{userid}/token/{token-name}"
+);
+crate::audited_api_method!(
+    AUDITED_GENERATE_TOKEN = API_METHOD_GENERATE_TOKEN,
+    "POST",
+    "/access/users/{userid}/token/{token-name}"
+);
+crate::audited_api_method!(
+    AUDITED_DELETE_TOKEN = API_METHOD_DELETE_TOKEN,
+    "DELETE",
+    "/access/users/{userid}/token/{token-name}"
+);
+crate::audited_api_method!(
+    AUDITED_UPDATE_USER = API_METHOD_UPDATE_USER,
+    "PUT",
+    "/access/users/{userid}"
+);
+crate::audited_api_method!(
+    AUDITED_DELETE_USER = API_METHOD_DELETE_USER,
+    "DELETE",
+    "/access/users/{userid}"
+);
+crate::audited_api_method!(
+    AUDITED_CREATE_USER = API_METHOD_CREATE_USER,
+    "POST",
+    "/access/users"
+);
+
 const TOKEN_ITEM_ROUTER: Router = Router::new()
     .get(&API_METHOD_READ_TOKEN)
-    .put(&API_METHOD_UPDATE_TOKEN)
-    .post(&API_METHOD_GENERATE_TOKEN)
-    .delete(&API_METHOD_DELETE_TOKEN);
+    .put(&AUDITED_UPDATE_TOKEN)
+    .post(&AUDITED_GENERATE_TOKEN)
+    .delete(&AUDITED_DELETE_TOKEN);
 
 const TOKEN_ROUTER: Router = Router::new()
     .get(&API_METHOD_LIST_TOKENS)
@@ -792,11 +823,11 @@ const USER_SUBDIRS: SubdirMap = &[("token", &TOKEN_ROUTER), ("unlock-tfa", &UNLO
 
 const USER_ROUTER: Router = Router::new()
     .get(&API_METHOD_READ_USER)
-    .put(&API_METHOD_UPDATE_USER)
-    .delete(&API_METHOD_DELETE_USER)
+    .put(&AUDITED_UPDATE_USER)
+    .delete(&AUDITED_DELETE_USER)
     .subdirs(USER_SUBDIRS);
 
 pub const ROUTER: Router = Router::new()
     .get(&API_METHOD_LIST_USERS)
-    .post(&API_METHOD_CREATE_USER)
+    .post(&AUDITED_CREATE_USER)
     .match_all("userid", &USER_ROUTER);
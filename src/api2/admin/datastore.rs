@@ -17,6 +17,7 @@ use tokio_stream::wrappers::ReceiverStream;
 use proxmox_async::blocking::WrappedReaderStream;
 use proxmox_async::{io::AsyncChannelWriter, stream::AsyncReaderStream};
 use proxmox_compression::zstd::ZstdEncoder;
+use proxmox_human_byte::HumanByte;
 use proxmox_router::{
     http_err, list_subdirs_api_method, ApiHandler, ApiMethod, ApiResponseFuture, Permission,
     Router, RpcEnvironment, RpcEnvironmentType, SubdirMap,
@@ -26,21 +27,23 @@ use proxmox_sortable_macro::sortable;
 use proxmox_sys::fs::{
     file_read_firstline, file_read_optional_string, replace_file, CreateOptions,
 };
-use proxmox_sys::{task_log, task_warn};
+use proxmox_sys::{task_log, task_warn, WorkerTaskContext};
 
 use pxar::accessor::aio::Accessor;
 use pxar::EntryKind;
 
 use pbs_api_types::{
     print_ns_and_snapshot, print_store_and_ns, Authid, BackupContent, BackupNamespace, BackupType,
-    Counts, CryptMode, DataStoreListItem, DataStoreStatus, GarbageCollectionStatus, GroupListItem,
-    KeepOptions, Operation, PruneJobOptions, RRDMode, RRDTimeFrame, SnapshotListItem,
-    SnapshotVerifyState, BACKUP_ARCHIVE_NAME_SCHEMA, BACKUP_ID_SCHEMA, BACKUP_NAMESPACE_SCHEMA,
-    BACKUP_TIME_SCHEMA, BACKUP_TYPE_SCHEMA, DATASTORE_SCHEMA, IGNORE_VERIFIED_BACKUPS_SCHEMA,
-    MAX_NAMESPACE_DEPTH, NS_MAX_DEPTH_SCHEMA, PRIV_DATASTORE_AUDIT, PRIV_DATASTORE_BACKUP,
-    PRIV_DATASTORE_MODIFY, PRIV_DATASTORE_PRUNE, PRIV_DATASTORE_READ, PRIV_DATASTORE_VERIFY,
-    UPID_SCHEMA, VERIFICATION_OUTDATED_AFTER_SCHEMA,
+    Counts, CryptMode, DataStoreConfig, DataStoreListItem, DataStoreStatus,
+    GarbageCollectionStatus, GroupListItem, KeepOptions, NamespaceUsage, Operation,
+    PruneJobOptions, RRDMode, RRDTimeFrame, SnapshotListItem, SnapshotVerifyState,
+    BACKUP_ARCHIVE_NAME_SCHEMA, BACKUP_ID_SCHEMA, BACKUP_NAMESPACE_SCHEMA, BACKUP_TIME_SCHEMA,
+    BACKUP_TYPE_SCHEMA, DATASTORE_SCHEMA, IGNORE_VERIFIED_BACKUPS_SCHEMA, MAX_NAMESPACE_DEPTH,
+    NS_MAX_DEPTH_SCHEMA, PRIV_DATASTORE_AUDIT, PRIV_DATASTORE_BACKUP, PRIV_DATASTORE_MODIFY,
+    PRIV_DATASTORE_PRUNE, PRIV_DATASTORE_READ, PRIV_DATASTORE_VERIFY, UPID_SCHEMA,
+    VERIFICATION_OUTDATED_AFTER_SCHEMA,
 };
+use pbs_client::pxar::payload::check_no_split_archive;
 use pbs_client::pxar::{create_tar, create_zip};
 use pbs_config::CachedUserInfo;
 use pbs_datastore::backup_info::BackupInfo;
@@ -53,9 +56,10 @@ use pbs_datastore::fixed_index::FixedIndexReader;
 use pbs_datastore::index::IndexFile;
 use pbs_datastore::manifest::{BackupManifest, CLIENT_LOG_BLOB_NAME, MANIFEST_BLOB_NAME};
 use pbs_datastore::prune::compute_prune_info;
+use pbs_datastore::space_usage::SpaceUsageStatus;
 use pbs_datastore::{
-    check_backup_owner, task_tracking, BackupDir, BackupGroup, DataStore, LocalChunkReader,
-    StoreProgress, CATALOG_NAME,
+    check_backup_owner, is_datastore_mounted, task_tracking, BackupDir, BackupGroup, DataStore,
+    LocalChunkReader, StoreProgress, CATALOG_NAME,
 };
 use pbs_tools::json::required_string_param;
 use proxmox_rest_server::{formatter, WorkerTask};
@@ -63,8 +67,8 @@ use proxmox_rest_server::{formatter, WorkerTask};
 use crate::api2::backup::optional_ns_param;
 use crate::api2::node::rrd::create_value_from_rrd;
 use crate::backup::{
-    check_ns_privs_full, verify_all_backups, verify_backup_dir, verify_backup_group, verify_filter,
-    ListAccessibleBackupGroups, NS_PRIVS_OK,
+    check_ns_privs_full, verify_all_backups, verify_backup_dir, verify_backup_group,
+    verify_chunk_store, verify_filter, ListAccessibleBackupGroups, NS_PRIVS_OK,
 };
 
 use crate::server::jobstate::Job;
@@ -81,6 +85,16 @@ fn get_group_note_path(
     note_path
 }
 
+fn load_space_usage_status(store: &DataStore) -> Option<SpaceUsageStatus> {
+    match store.space_usage_status() {
+        Ok(status) => status,
+        Err(err) => {
+            eprintln!("error reading space usage status: '{}'", err);
+            None
+        }
+    }
+}
+
 // helper to unify common sequence of checks:
 // 1. check privs on NS (full or limited access)
 // 2. load datastore
@@ -194,6 +208,8 @@ pub fn list_groups(
 
     let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
 
+    let space_usage = load_space_usage_status(&datastore);
+
     datastore
         .iter_backup_groups(ns.clone())? // FIXME: Namespaces and recursion parameters!
         .try_fold(Vec::new(), |mut group_info, group| {
@@ -239,6 +255,11 @@ pub fn list_groups(
             let note_path = get_group_note_path(&datastore, &ns, group.as_ref());
             let comment = file_read_firstline(note_path).ok();
 
+            let usage = space_usage.as_ref().and_then(|status| {
+                let path = group.relative_group_path();
+                status.groups.get(path.to_str()?).copied()
+            });
+
             group_info.push(GroupListItem {
                 backup: group.into(),
                 last_backup: last_backup.backup_dir.backup_time(),
@@ -246,6 +267,8 @@ pub fn list_groups(
                 backup_count,
                 files: last_backup.files,
                 comment,
+                exclusive_size: usage.map(|usage| usage.exclusive),
+                shared_size: usage.map(|usage| usage.shared),
             });
 
             Ok(group_info)
@@ -290,7 +313,7 @@ pub async fn delete_group(
             &auth_id,
             PRIV_DATASTORE_MODIFY,
             PRIV_DATASTORE_PRUNE,
-            Some(Operation::Write),
+            Some(Operation::Delete),
             &group,
         )?;
 
@@ -397,7 +420,7 @@ pub async fn delete_snapshot(
             &auth_id,
             PRIV_DATASTORE_MODIFY,
             PRIV_DATASTORE_PRUNE,
-            Some(Operation::Write),
+            Some(Operation::Delete),
             &backup_dir.group,
         )?;
 
@@ -497,12 +520,20 @@ unsafe fn list_snapshots_blocking(
         (None, None) => datastore.list_backup_groups(ns.clone())?,
     };
 
+    let space_usage = load_space_usage_status(&datastore);
+
     let info_to_snapshot_list_item = |group: &BackupGroup, owner, info: BackupInfo| {
         let backup = pbs_api_types::BackupDir {
             group: group.into(),
             time: info.backup_dir.backup_time(),
         };
         let protected = info.backup_dir.is_protected();
+        let usage = space_usage.as_ref().and_then(|status| {
+            let path = info.backup_dir.relative_path();
+            status.snapshots.get(path.to_str()?).copied()
+        });
+        let exclusive_size = usage.map(|usage| usage.exclusive);
+        let shared_size = usage.map(|usage| usage.shared);
 
         match get_all_snapshot_files(&info) {
             Ok((manifest, files)) => {
@@ -541,6 +572,8 @@ unsafe fn list_snapshots_blocking(
                     size,
                     owner,
                     protected,
+                    exclusive_size,
+                    shared_size,
                 }
             }
             Err(err) => {
@@ -564,6 +597,8 @@ unsafe fn list_snapshots_blocking(
                     size: None,
                     owner,
                     protected,
+                    exclusive_size,
+                    shared_size,
                 }
             }
         }
@@ -920,6 +955,56 @@ pub fn verify(
     Ok(json!(upid_str))
 }
 
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_VERIFY, false),
+    },
+)]
+/// Verify all chunks of the chunk store once, independent of snapshots.
+///
+/// Snapshots referencing corrupt chunks are marked as failed.
+pub fn verify_chunks(
+    store: String,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let upid_str = WorkerTask::new_thread(
+        "verify_chunks",
+        Some(store),
+        auth_id.to_string(),
+        to_stdout,
+        move |worker| {
+            let verify_worker = crate::backup::VerifyWorker::new(worker.clone(), datastore);
+            let failed_dirs = verify_chunk_store(&verify_worker, worker.upid())?;
+            if !failed_dirs.is_empty() {
+                task_log!(worker, "The following snapshots reference corrupt chunks:");
+                for dir in failed_dirs {
+                    task_log!(worker, "\t{}", dir);
+                }
+                bail!("verification failed - please check the log for details");
+            }
+            Ok(())
+        },
+    )?;
+
+    Ok(json!(upid_str))
+}
+
 #[api(
     input: {
         properties: {
@@ -971,7 +1056,7 @@ pub fn prune(
         &auth_id,
         PRIV_DATASTORE_MODIFY,
         PRIV_DATASTORE_PRUNE,
-        Some(Operation::Write),
+        Some(Operation::Delete),
         &group,
     )?;
 
@@ -1110,7 +1195,7 @@ pub fn prune_datastore(
         true,
     )?;
 
-    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Delete))?;
     let ns = prune_options.ns.clone().unwrap_or_default();
     let worker_id = format!("{}:{}", store, ns);
 
@@ -1150,7 +1235,7 @@ pub fn start_garbage_collection(
     _info: &ApiMethod,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<Value, Error> {
-    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Delete))?;
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
 
     let job = Job::new("garbage_collection", &store)
@@ -1171,6 +1256,58 @@ pub fn start_garbage_collection(
     Ok(json!(upid_str))
 }
 
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_MODIFY, false),
+    },
+)]
+/// Start a scan computing the exclusive and shared space usage of all snapshots and groups.
+pub fn start_space_usage_scan(
+    store: String,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let upid_str = WorkerTask::new_thread(
+        "space-usage",
+        Some(store),
+        auth_id.to_string(),
+        to_stdout,
+        move |worker| {
+            let upid = worker.upid().clone();
+            let status = datastore.scan_space_usage(&*worker, Some(&upid))?;
+            let mut namespaces: Vec<_> = status.namespaces.iter().collect();
+            namespaces.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            for (ns, usage) in namespaces {
+                task_log!(
+                    worker,
+                    "namespace '{}': exclusive {}, shared {}",
+                    ns,
+                    HumanByte::from(usage.exclusive),
+                    HumanByte::from(usage.shared),
+                );
+            }
+            Ok(())
+        },
+    )?;
+
+    Ok(json!(upid_str))
+}
+
 #[api(
     input: {
         properties: {
@@ -1199,6 +1336,75 @@ pub fn garbage_collection_status(
     Ok(status)
 }
 
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                type: BackupNamespace,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
+        },
+    },
+    returns: {
+        description: "Space usage of the namespaces, each including its sub-namespaces.",
+        type: Array,
+        items: { type: NamespaceUsage },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Only namespaces with DATASTORE_AUDIT on /datastore/{store}[/{namespace}] \
+            are listed.",
+    },
+)]
+/// Get the logical and deduplicated space usage of namespaces.
+pub async fn namespace_usage(
+    store: String,
+    ns: Option<BackupNamespace>,
+    max_depth: Option<usize>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<NamespaceUsage>, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+
+    tokio::task::spawn_blocking(move || {
+        let user_info = CachedUserInfo::new()?;
+        let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
+        let quotas = pbs_config::quota::datastore_quotas(&store)?;
+
+        let mut list = Vec::new();
+
+        for ns in datastore.recursive_iter_backup_ns_ok(ns.unwrap_or_default(), max_depth)? {
+            let privs = user_info.lookup_privs(&auth_id, &ns.acl_path(&store));
+            if privs & PRIV_DATASTORE_AUDIT == 0 {
+                continue;
+            }
+
+            let usage = datastore.chunk_usage(ns.clone(), None)?;
+            let quota = quotas
+                .iter()
+                .find(|quota| quota.ns.as_ref() == Some(&ns))
+                .map(|quota| quota.limit);
+
+            list.push(NamespaceUsage {
+                ns,
+                snapshot_count: usage.snapshot_count,
+                logical_size: usage.logical_size,
+                unique_size: usage.unique_size,
+                quota,
+            });
+        }
+
+        Ok(list)
+    })
+    .await?
+}
+
 #[api(
     returns: {
         description: "List the accessible datastores.",
@@ -1642,7 +1848,7 @@ pub const API_METHOD_PXAR_FILE_DOWNLOAD: ApiMethod = ApiMethod::new(
             ("backup-id", false,  &BACKUP_ID_SCHEMA),
             ("backup-time", false, &BACKUP_TIME_SCHEMA),
             ("filepath", false, &StringSchema::new("Base64 encoded path").schema()),
-            ("tar", true, &BooleanSchema::new("Download as .tar.zst").schema()),
+            ("tar", true, &BooleanSchema::new("Download as .tar.zst (including xattrs and ACLs)").schema()),
         ]),
     )
 ).access(
@@ -1690,6 +1896,7 @@ pub fn pxar_file_download(
         let mut split = components.splitn(2, |c| *c == b'/');
         let pxar_name = std::str::from_utf8(split.next().unwrap())?;
         let file_path = split.next().unwrap_or(b"/");
+        check_no_split_archive(pxar_name, "file download")?;
         let (manifest, files) = read_backup_index(&backup_dir)?;
         for file in files {
             if file.filename == pxar_name && file.crypt_mode == Some(CryptMode::Encrypt) {
@@ -1721,6 +1928,20 @@ pub fn pxar_file_download(
             .ok_or_else(|| format_err!("error opening '{:?}'", path))?;
 
         let body = match file.kind() {
+            EntryKind::File { .. } | EntryKind::Hardlink(_) | EntryKind::Directory if tar => {
+                let (sender, receiver) = tokio::sync::mpsc::channel::<Result<_, Error>>(100);
+                let channelwriter = AsyncChannelWriter::new(sender, 1024 * 1024);
+                proxmox_rest_server::spawn_internal_task(create_tar(
+                    channelwriter,
+                    decoder,
+                    path.clone(),
+                ));
+                let zstdstream = ZstdEncoder::new(ReceiverStream::new(receiver))?;
+                Body::wrap_stream(zstdstream.map_err(move |err| {
+                    log::error!("error during streaming of tar.zst '{:?}' - {}", path, err);
+                    err
+                }))
+            }
             EntryKind::File { .. } => Body::wrap_stream(
                 AsyncReaderStream::new(file.contents().await?).map_err(move |err| {
                     eprintln!("error during streaming of file '{:?}' - {}", filepath, err);
@@ -1737,28 +1958,15 @@ pub fn pxar_file_download(
             EntryKind::Directory => {
                 let (sender, receiver) = tokio::sync::mpsc::channel::<Result<_, Error>>(100);
                 let channelwriter = AsyncChannelWriter::new(sender, 1024 * 1024);
-                if tar {
-                    proxmox_rest_server::spawn_internal_task(create_tar(
-                        channelwriter,
-                        decoder,
-                        path.clone(),
-                    ));
-                    let zstdstream = ZstdEncoder::new(ReceiverStream::new(receiver))?;
-                    Body::wrap_stream(zstdstream.map_err(move |err| {
-                        log::error!("error during streaming of tar.zst '{:?}' - {}", path, err);
-                        err
-                    }))
-                } else {
-                    proxmox_rest_server::spawn_internal_task(create_zip(
-                        channelwriter,
-                        decoder,
-                        path.clone(),
-                    ));
-                    Body::wrap_stream(ReceiverStream::new(receiver).map_err(move |err| {
-                        log::error!("error during streaming of zip '{:?}' - {}", path, err);
-                        err
-                    }))
-                }
+                proxmox_rest_server::spawn_internal_task(create_zip(
+                    channelwriter,
+                    decoder,
+                    path.clone(),
+                ));
+                Body::wrap_stream(ReceiverStream::new(receiver).map_err(move |err| {
+                    log::error!("error during streaming of zip '{:?}' - {}", path, err);
+                    err
+                }))
             }
             other => bail!("cannot download file of type {:?}", other),
         };
@@ -1839,6 +2047,7 @@ pub fn get_active_operations(store: String, _param: Value) -> Result<Value, Erro
     Ok(json!({
         "read": active_operations.read,
         "write": active_operations.write,
+        "delete": active_operations.delete,
     }))
 }
 
@@ -2234,11 +2443,248 @@ pub async fn set_backup_owner(
 
         backup_group.set_owner(&new_owner, true)?;
 
+        // the group's chunks now count for the quotas of the new owner
+        crate::backup::invalidate_quota_usage(&store);
+
         Ok(())
     })
     .await?
 }
 
+/// Mount the backing device of a removable datastore on the datastore path.
+///
+/// This is a synchronous operation and should be run in a worker-thread.
+pub(crate) fn do_mount_device(datastore: &DataStoreConfig) -> Result<(), Error> {
+    let uuid = match datastore.backing_device.as_deref() {
+        Some(uuid) => uuid,
+        None => bail!("datastore '{}' is not removable", datastore.name),
+    };
+
+    if is_datastore_mounted(datastore) {
+        log::info!("datastore '{}' is already mounted", datastore.name);
+        return Ok(());
+    }
+
+    let device = format!("/dev/disk/by-uuid/{uuid}");
+    if !std::path::Path::new(&device).exists() {
+        bail!(
+            "backing device '{uuid}' of datastore '{}' not found",
+            datastore.name
+        );
+    }
+
+    proxmox_sys::fs::create_path(&datastore.path, None, None)?;
+
+    log::info!("mounting '{device}' on '{}'", datastore.path);
+    let mut command = std::process::Command::new("mount");
+    command.arg(&device);
+    command.arg(&datastore.path);
+    proxmox_sys::command::run_command(command, None)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+        }
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_MODIFY, false),
+    },
+)]
+/// Mount the backing device of a removable datastore.
+pub fn mount(store: String, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+    let (section_config, _digest) = pbs_config::datastore::config()?;
+    let datastore: DataStoreConfig = section_config.lookup("datastore", &store)?;
+
+    if !datastore.is_removable() {
+        bail!("datastore '{store}' is not removable");
+    }
+
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let upid = WorkerTask::new_thread(
+        "mount-device",
+        Some(store),
+        auth_id.to_string(),
+        to_stdout,
+        move |_worker| do_mount_device(&datastore),
+    )?;
+
+    Ok(json!(upid))
+}
+
+/// Wait until all active operations on a datastore are finished and unmount its backing device.
+fn do_unmount_device(
+    datastore: &DataStoreConfig,
+    worker: &dyn WorkerTaskContext,
+) -> Result<(), Error> {
+    let mut last_status = String::new();
+    loop {
+        let active_operations = task_tracking::get_active_operations(&datastore.name)?;
+        if active_operations.read == 0
+            && active_operations.write == 0
+            && active_operations.delete == 0
+        {
+            break;
+        }
+
+        let status = format!(
+            "waiting for {} read, {} write and {} delete operations to finish",
+            active_operations.read, active_operations.write, active_operations.delete,
+        );
+        if status != last_status {
+            task_log!(worker, "{status}");
+            last_status = status;
+        }
+
+        worker.check_abort()?;
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+
+    task_log!(worker, "unmounting '{}'", datastore.path);
+    // lookups are not tracked as operations, one might have a file open just now
+    let mut tries = 3;
+    loop {
+        let mut command = std::process::Command::new("umount");
+        command.arg(&datastore.path);
+        match proxmox_sys::command::run_command(command, None) {
+            Ok(_) => return Ok(()),
+            Err(err) if tries > 1 => {
+                task_warn!(worker, "unmounting failed, retrying - {err}");
+                tries -= 1;
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+        }
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_MODIFY, false),
+    },
+)]
+/// Unmount the backing device of a removable datastore.
+///
+/// The datastore is put into the 'unmounting' maintenance mode until all active operations are
+/// finished and the device is unmounted, afterwards the previous maintenance mode is restored.
+pub async fn unmount(store: String, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+    let lock = pbs_config::datastore::lock_config()?;
+    let (mut section_config, _digest) = pbs_config::datastore::config()?;
+    let mut datastore: DataStoreConfig = section_config.lookup("datastore", &store)?;
+
+    if !datastore.is_removable() {
+        bail!("datastore '{store}' is not removable");
+    }
+    if !is_datastore_mounted(&datastore) {
+        bail!("datastore '{store}' is not mounted");
+    }
+    if let Some(mode) = datastore.get_maintenance_mode() {
+        // lookups are only refused while the datastore is being deleted
+        if mode.is_unmounting() || mode.check(Some(Operation::Lookup)).is_err() {
+            bail!("datastore '{store}' is already being unmounted or deleted");
+        }
+    }
+
+    let previous_mode = datastore.maintenance_mode.take();
+    datastore.maintenance_mode = Some("type=unmounting".to_string());
+    section_config.set_data(&store, "datastore", &datastore)?;
+    pbs_config::datastore::save_config(&section_config)?;
+    drop(lock);
+
+    // make sure no daemon keeps files on the device open through its datastore cache
+    DataStore::remove_unused_datastores()?;
+    if let Err(err) = crate::server::notify_datastore_removed().await {
+        log::warn!("failed to notify proxy about unmounting datastore '{store}': {err}");
+    }
+
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let upid = WorkerTask::new_thread(
+        "unmount-device",
+        Some(store.clone()),
+        auth_id.to_string(),
+        to_stdout,
+        move |worker| {
+            let result = do_unmount_device(&datastore, &worker);
+
+            let _lock = pbs_config::datastore::lock_config()?;
+            let (mut section_config, _digest) = pbs_config::datastore::config()?;
+            let mut datastore: DataStoreConfig = section_config.lookup("datastore", &store)?;
+            datastore.maintenance_mode = previous_mode;
+            section_config.set_data(&store, "datastore", &datastore)?;
+            pbs_config::datastore::save_config(&section_config)?;
+
+            result
+        },
+    )?;
+
+    Ok(json!(upid))
+}
+
+crate::audited_api_method!(
+    AUDITED_SET_BACKUP_OWNER = API_METHOD_SET_BACKUP_OWNER,
+    "POST",
+    "/admin/datastore/{store}/change-owner"
+);
+crate::audited_api_method!(
+    AUDITED_SET_GROUP_NOTES = API_METHOD_SET_GROUP_NOTES,
+    "PUT",
+    "/admin/datastore/{store}/group-notes"
+);
+crate::audited_api_method!(
+    AUDITED_DELETE_GROUP = API_METHOD_DELETE_GROUP,
+    "DELETE",
+    "/admin/datastore/{store}/groups"
+);
+crate::audited_api_method!(
+    AUDITED_SET_NOTES = API_METHOD_SET_NOTES,
+    "PUT",
+    "/admin/datastore/{store}/notes"
+);
+crate::audited_api_method!(
+    AUDITED_SET_PROTECTION = API_METHOD_SET_PROTECTION,
+    "PUT",
+    "/admin/datastore/{store}/protected"
+);
+crate::audited_api_method!(
+    AUDITED_PRUNE = API_METHOD_PRUNE,
+    "POST",
+    "/admin/datastore/{store}/prune"
+);
+crate::audited_api_method!(
+    AUDITED_PRUNE_DATASTORE = API_METHOD_PRUNE_DATASTORE,
+    "POST",
+    "/admin/datastore/{store}/prune-datastore"
+);
+crate::audited_api_method!(
+    AUDITED_DELETE_SNAPSHOT = API_METHOD_DELETE_SNAPSHOT,
+    "DELETE",
+    "/admin/datastore/{store}/snapshots"
+);
+
 #[sortable]
 const DATASTORE_INFO_SUBDIRS: SubdirMap = &[
     (
@@ -2248,7 +2694,7 @@ const DATASTORE_INFO_SUBDIRS: SubdirMap = &[
     ("catalog", &Router::new().get(&API_METHOD_CATALOG)),
     (
         "change-owner",
-        &Router::new().post(&API_METHOD_SET_BACKUP_OWNER),
+        &Router::new().post(&AUDITED_SET_BACKUP_OWNER),
     ),
     (
         "download",
@@ -2269,35 +2715,40 @@ const DATASTORE_INFO_SUBDIRS: SubdirMap = &[
         "group-notes",
         &Router::new()
             .get(&API_METHOD_GET_GROUP_NOTES)
-            .put(&API_METHOD_SET_GROUP_NOTES),
+            .put(&AUDITED_SET_GROUP_NOTES),
     ),
     (
         "groups",
         &Router::new()
             .get(&API_METHOD_LIST_GROUPS)
-            .delete(&API_METHOD_DELETE_GROUP),
+            .delete(&AUDITED_DELETE_GROUP),
     ),
+    ("mount", &Router::new().post(&API_METHOD_MOUNT)),
     (
         "namespace",
         // FIXME: move into datastore:: sub-module?!
         &crate::api2::admin::namespace::ROUTER,
     ),
+    (
+        "namespace-usage",
+        &Router::new().get(&API_METHOD_NAMESPACE_USAGE),
+    ),
     (
         "notes",
         &Router::new()
             .get(&API_METHOD_GET_NOTES)
-            .put(&API_METHOD_SET_NOTES),
+            .put(&AUDITED_SET_NOTES),
     ),
     (
         "protected",
         &Router::new()
             .get(&API_METHOD_GET_PROTECTION)
-            .put(&API_METHOD_SET_PROTECTION),
+            .put(&AUDITED_SET_PROTECTION),
     ),
-    ("prune", &Router::new().post(&API_METHOD_PRUNE)),
+    ("prune", &Router::new().post(&AUDITED_PRUNE)),
     (
         "prune-datastore",
-        &Router::new().post(&API_METHOD_PRUNE_DATASTORE),
+        &Router::new().post(&AUDITED_PRUNE_DATASTORE),
     ),
     (
         "pxar-file-download",
@@ -2308,14 +2759,23 @@ const DATASTORE_INFO_SUBDIRS: SubdirMap = &[
         "snapshots",
         &Router::new()
             .get(&API_METHOD_LIST_SNAPSHOTS)
-            .delete(&API_METHOD_DELETE_SNAPSHOT),
+            .delete(&AUDITED_DELETE_SNAPSHOT),
+    ),
+    (
+        "space-usage",
+        &Router::new().post(&API_METHOD_START_SPACE_USAGE_SCAN),
     ),
     ("status", &Router::new().get(&API_METHOD_STATUS)),
+    ("unmount", &Router::new().post(&API_METHOD_UNMOUNT)),
     (
         "upload-backup-log",
         &Router::new().upload(&API_METHOD_UPLOAD_BACKUP_LOG),
     ),
     ("verify", &Router::new().post(&API_METHOD_VERIFY)),
+    (
+        "verify-chunks",
+        &Router::new().post(&API_METHOD_VERIFY_CHUNKS),
+    ),
 ];
 
 const DATASTORE_INFO_ROUTER: Router = Router::new()
@@ -156,7 +156,7 @@ pub fn delete_namespace(
 
     check_ns_modification_privs(&store, &ns, &auth_id)?;
 
-    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Delete))?;
 
     if !datastore.remove_namespace_recursive(&ns, delete_groups)? {
         if delete_groups {
@@ -169,7 +169,13 @@ pub fn delete_namespace(
     Ok(Value::Null)
 }
 
+crate::audited_api_method!(
+    AUDITED_DELETE_NAMESPACE = API_METHOD_DELETE_NAMESPACE,
+    "DELETE",
+    "/admin/datastore/{store}/namespace"
+);
+
 pub const ROUTER: Router = Router::new()
     .get(&API_METHOD_LIST_NAMESPACES)
     .post(&API_METHOD_CREATE_NAMESPACE)
-    .delete(&API_METHOD_DELETE_NAMESPACE);
+    .delete(&AUDITED_DELETE_NAMESPACE);
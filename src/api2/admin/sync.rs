@@ -37,7 +37,7 @@ use crate::{
         items: { type: SyncJobStatus },
     },
     access: {
-        description: "Limited to sync jobs where user has Datastore.Audit on target datastore, and Remote.Audit on source remote.",
+        description: "Limited to sync jobs where user has Datastore.Audit on target datastore, and Remote.Audit on source remote (Datastore.Audit on a local source datastore).",
         permission: &Permission::Anybody,
     },
 )]
@@ -92,7 +92,7 @@ pub fn list_sync_jobs(
         }
     },
     access: {
-        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote. Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
+        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote (Datastore.Read or Datastore.Backup on a local source datastore). Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
         permission: &Permission::Anybody,
     },
 )]
@@ -16,7 +16,7 @@ use pbs_datastore::fixed_index::FixedIndexWriter;
 use pbs_datastore::{DataBlob, DataStore};
 use proxmox_rest_server::{formatter::*, WorkerTask};
 
-use crate::backup::verify_backup_dir_with_lock;
+use crate::backup::{verify_backup_dir_with_lock, BackupQuota};
 
 use hyper::{Body, Response};
 
@@ -83,6 +83,7 @@ struct SharedBackupState {
     known_chunks: KnownChunksMap,
     backup_size: u64, // sums up size of all files
     backup_stat: UploadStatistic,
+    quotas: Vec<BackupQuota>,
 }
 
 impl SharedBackupState {
@@ -99,6 +100,14 @@ impl SharedBackupState {
         self.uid_counter += 1;
         self.uid_counter
     }
+
+    // Account a newly uploaded chunk with its encoded size in all quotas applying to the backup
+    fn account_quotas(&self, digest: [u8; 32], size: u32) -> Result<(), Error> {
+        for quota in self.quotas.iter() {
+            quota.add_chunk(digest, size as u64)?;
+        }
+        Ok(())
+    }
 }
 
 /// `RpcEnvironmet` implementation for backup service
@@ -133,6 +142,7 @@ impl BackupEnvironment {
             known_chunks: HashMap::new(),
             backup_size: 0,
             backup_stat: UploadStatistic::new(),
+            quotas: Vec::new(),
         };
 
         Self {
@@ -149,6 +159,12 @@ impl BackupEnvironment {
         }
     }
 
+    /// Set the quotas which get enforced for newly uploaded chunks.
+    pub fn set_quotas(&self, quotas: Vec<BackupQuota>) {
+        let mut state = self.state.lock().unwrap();
+        state.quotas = quotas;
+    }
+
     /// Register a Chunk with associated length.
     ///
     /// We do not fully trust clients, so a client may only use registered
@@ -179,6 +195,8 @@ impl BackupEnvironment {
 
         state.ensure_unfinished()?;
 
+        state.account_quotas(digest, compressed_size)?;
+
         let mut data = match state.fixed_writers.get_mut(&wid) {
             Some(data) => data,
             None => bail!("fixed writer '{}' not registered", wid),
@@ -233,6 +251,8 @@ impl BackupEnvironment {
 
         state.ensure_unfinished()?;
 
+        state.account_quotas(digest, compressed_size)?;
+
         let mut data = match state.dynamic_writers.get_mut(&wid) {
             Some(data) => data,
             None => bail!("dynamic writer '{}' not registered", wid),
@@ -625,6 +645,10 @@ impl BackupEnvironment {
 
         self.datastore.try_ensure_sync_level()?;
 
+        self.datastore
+            .backend_upload_snapshot(&self.backup_dir)
+            .map_err(|err| format_err!("unable to upload snapshot to backend - {err}"))?;
+
         // marks the backup as successful
         state.finished = true;
 
@@ -173,6 +173,16 @@ fn upgrade_to_backup_protocol(
             }
         };
 
+        let quotas = {
+            let datastore = datastore.clone();
+            let ns = backup_group.backup_ns().clone();
+            let owner = owner.clone();
+            tokio::task::spawn_blocking(move || {
+                crate::backup::load_backup_quotas(&datastore, &ns, &owner)
+            })
+            .await??
+        };
+
         let backup_dir = backup_group.backup_dir(backup_dir_arg.time)?;
 
         let _last_guard = if let Some(last) = &last_backup {
@@ -213,6 +223,7 @@ fn upgrade_to_backup_protocol(
 
                 env.debug = debug;
                 env.last_backup = last_backup;
+                env.set_quotas(quotas);
 
                 let origin = match rpcenv.get_client_ip().map(|addr| addr.ip()) {
                     Some(ip) => format!(" from {ip}"),
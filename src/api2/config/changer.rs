@@ -13,6 +13,8 @@ use pbs_api_types::{
 use pbs_config::CachedUserInfo;
 use pbs_tape::linux_list_drives::{check_drive_path, linux_tape_changer_list};
 
+use crate::api2::tape::changer::check_reconcile_drive;
+
 #[api(
     protected: true,
     input: {
@@ -54,6 +56,10 @@ pub fn create_changer(config: ScsiTapeChanger) -> Result<(), Error> {
         }
     }
 
+    if let Some(ref drive) = config.reconcile_drive {
+        check_reconcile_drive(&section_config, &config.name, drive)?;
+    }
+
     section_config.set_data(&config.name, "changer", &config)?;
 
     pbs_config::drive::save_config(&section_config)?;
@@ -138,6 +144,10 @@ pub fn list_changers(
 pub enum DeletableProperty {
     /// Delete export-slots.
     ExportSlots,
+    /// Delete reconcile-schedule.
+    ReconcileSchedule,
+    /// Delete reconcile-drive.
+    ReconcileDrive,
 }
 
 #[api(
@@ -194,6 +204,12 @@ pub fn update_changer(
                 DeletableProperty::ExportSlots => {
                     data.export_slots = None;
                 }
+                DeletableProperty::ReconcileSchedule => {
+                    data.reconcile_schedule = None;
+                }
+                DeletableProperty::ReconcileDrive => {
+                    data.reconcile_drive = None;
+                }
             }
         }
     }
@@ -222,6 +238,15 @@ pub fn update_changer(
         }
     }
 
+    if update.reconcile_schedule.is_some() {
+        data.reconcile_schedule = update.reconcile_schedule;
+    }
+
+    if let Some(drive) = update.reconcile_drive {
+        check_reconcile_drive(&config, &name, &drive)?;
+        data.reconcile_drive = Some(drive);
+    }
+
     config.set_data(&name, "changer", &data)?;
 
     pbs_config::drive::save_config(&config)?;
@@ -282,6 +307,8 @@ pub fn delete_changer(name: String, _param: Value) -> Result<(), Error> {
 
     pbs_config::drive::save_config(&config)?;
 
+    let _ = crate::server::jobstate::remove_state_file("tape-reconcile", &name);
+
     Ok(())
 }
 
@@ -17,6 +17,7 @@ use pbs_api_types::{
 };
 use pbs_config::BackupLockGuard;
 use pbs_datastore::chunk_store::ChunkStore;
+use pbs_datastore::s3_client::datastore_s3_client;
 
 use crate::api2::admin::{
     prune::list_prune_jobs, sync::list_sync_jobs, verify::list_verification_jobs,
@@ -73,19 +74,33 @@ pub(crate) fn do_create_datastore(
 ) -> Result<(), Error> {
     let path: PathBuf = datastore.path.clone().into();
 
+    if datastore.is_removable() {
+        crate::api2::admin::datastore::do_mount_device(&datastore)?;
+    }
+
     let tuning: DatastoreTuning = serde_json::from_value(
         DatastoreTuning::API_SCHEMA
             .parse_property_string(datastore.tuning.as_deref().unwrap_or(""))?,
     )?;
+    // also checks that the configured s3 endpoint exists
+    let s3_client = datastore_s3_client(&datastore)?;
+
     let backup_user = pbs_config::backup_user()?;
-    let _store = ChunkStore::create(
+    let store = ChunkStore::create(
         &datastore.name,
         path,
         backup_user.uid,
         backup_user.gid,
         worker,
         tuning.sync_level.unwrap_or_default(),
-    )?;
+    )?
+    .with_s3_client(s3_client)?
+    .with_cache_size(datastore.backend_config()?.cache_size.map(|s| s.as_u64()));
+
+    // reuse the contents of a bucket which already holds a datastore
+    if let (Some(_), Some(worker)) = (store.s3_client(), worker) {
+        store.refresh_cache(worker)?;
+    }
 
     config.set_data(&datastore.name, "datastore", &datastore)?;
 
@@ -245,6 +260,12 @@ pub fn update_datastore(
 
     let mut data: DataStoreConfig = config.lookup("datastore", &name)?;
 
+    let is_unmounting = |data: &DataStoreConfig| {
+        data.get_maintenance_mode()
+            .map_or(false, |m| m.is_unmounting())
+    };
+    let was_unmounting = is_unmounting(&data);
+
     if let Some(delete) = delete {
         for delete_prop in delete {
             match delete_prop {
@@ -362,6 +383,13 @@ pub fn update_datastore(
         data.maintenance_mode = update.maintenance_mode;
     }
 
+    if is_unmounting(&data) != was_unmounting {
+        param_bail!(
+            "maintenance-mode",
+            "the 'unmounting' maintenance mode is managed by the unmount API"
+        );
+    }
+
     config.set_data(&name, "datastore", &data)?;
 
     pbs_config::datastore::save_config(&config)?;
@@ -480,12 +508,28 @@ pub async fn delete_datastore(
     Ok(upid)
 }
 
+crate::audited_api_method!(
+    AUDITED_UPDATE_DATASTORE = API_METHOD_UPDATE_DATASTORE,
+    "PUT",
+    "/config/datastore/{name}"
+);
+crate::audited_api_method!(
+    AUDITED_DELETE_DATASTORE = API_METHOD_DELETE_DATASTORE,
+    "DELETE",
+    "/config/datastore/{name}"
+);
+crate::audited_api_method!(
+    AUDITED_CREATE_DATASTORE = API_METHOD_CREATE_DATASTORE,
+    "POST",
+    "/config/datastore"
+);
+
 const ITEM_ROUTER: Router = Router::new()
     .get(&API_METHOD_READ_DATASTORE)
-    .put(&API_METHOD_UPDATE_DATASTORE)
-    .delete(&API_METHOD_DELETE_DATASTORE);
+    .put(&AUDITED_UPDATE_DATASTORE)
+    .delete(&AUDITED_DELETE_DATASTORE);
 
 pub const ROUTER: Router = Router::new()
     .get(&API_METHOD_LIST_DATASTORES)
-    .post(&API_METHOD_CREATE_DATASTORE)
+    .post(&AUDITED_CREATE_DATASTORE)
     .match_all("name", &ITEM_ROUTER);
@@ -4,11 +4,13 @@ use proxmox_sortable_macro::sortable;
 
 pub mod influxdbhttp;
 pub mod influxdbudp;
+pub mod otlphttp;
 
 #[sortable]
 const SUBDIRS: SubdirMap = &sorted!([
     ("influxdb-http", &influxdbhttp::ROUTER),
     ("influxdb-udp", &influxdbudp::ROUTER),
+    ("otlp-http", &otlphttp::ROUTER),
 ]);
 
 pub const ROUTER: Router = Router::new()
@@ -0,0 +1,294 @@
+use anyhow::{bail, format_err, Error};
+use hex::FromHex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use proxmox_router::{Permission, Router, RpcEnvironment};
+use proxmox_schema::api;
+
+use pbs_api_types::{
+    OtlpHttp, OtlpHttpUpdater, METRIC_SERVER_ID_SCHEMA, PRIV_SYS_AUDIT, PRIV_SYS_MODIFY,
+    PROXMOX_CONFIG_DIGEST_SCHEMA,
+};
+
+use pbs_config::metrics;
+
+use crate::server::otlp::test_otlp_http;
+
+async fn test_server(config: &OtlpHttp) -> Result<(), Error> {
+    if config.enable {
+        test_otlp_http(config)
+            .await
+            .map_err(|err| format_err!("could not connect to {}: {}", config.url, err))
+    } else {
+        Ok(())
+    }
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured OpenTelemetry collectors.",
+        type: Array,
+        items: { type: OtlpHttp },
+    },
+    access: {
+        permission: &Permission::Privilege(&[], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List configured OpenTelemetry collectors.
+pub fn list_otlp_http_servers(
+    _param: Value,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<OtlpHttp>, Error> {
+    let (config, digest) = metrics::config()?;
+
+    let mut list: Vec<OtlpHttp> = config.convert_to_typed_array("otlp-http")?;
+
+    // don't return headers via api, they may contain credentials
+    for item in list.iter_mut() {
+        item.header = None;
+    }
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            config: {
+                type: OtlpHttp,
+                flatten: true,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&[], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new OpenTelemetry collector configuration
+pub async fn create_otlp_http_server(config: OtlpHttp) -> Result<(), Error> {
+    let _lock = metrics::lock_config()?;
+
+    let (mut metrics, _digest) = metrics::config()?;
+
+    if metrics.sections.get(&config.name).is_some() {
+        bail!("metric server '{}' already exists.", config.name);
+    }
+
+    test_server(&config).await?;
+
+    metrics.set_data(&config.name, "otlp-http", &config)?;
+
+    metrics::save_config(&metrics)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&[], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove an OpenTelemetry collector configuration
+pub fn delete_otlp_http_server(
+    name: String,
+    digest: Option<String>,
+    _rpcenv: &mut dyn RpcEnvironment,
+) -> Result<(), Error> {
+    let _lock = metrics::lock_config()?;
+
+    let (mut metrics, expected_digest) = metrics::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    if metrics.sections.remove(&name).is_none() {
+        bail!("name '{}' does not exist.", name);
+    }
+
+    metrics::save_config(&metrics)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+        },
+    },
+    returns:  { type: OtlpHttp },
+    access: {
+        permission: &Permission::Privilege(&[], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read the OpenTelemetry collector configuration
+pub fn read_otlp_http_server(
+    name: String,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<OtlpHttp, Error> {
+    let (metrics, digest) = metrics::config()?;
+
+    let mut config: OtlpHttp = metrics.lookup("otlp-http", &name)?;
+
+    config.header = None;
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(config)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the enable property.
+    Enable,
+    /// Delete the header property.
+    Header,
+    /// Delete the resource_attribute property.
+    ResourceAttribute,
+    /// Delete the verify_tls property.
+    VerifyTls,
+    /// Delete the comment property.
+    Comment,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+            update: {
+                type: OtlpHttpUpdater,
+                flatten: true,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&[], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update an OpenTelemetry collector configuration
+pub async fn update_otlp_http_server(
+    name: String,
+    update: OtlpHttpUpdater,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+    _rpcenv: &mut dyn RpcEnvironment,
+) -> Result<(), Error> {
+    let _lock = metrics::lock_config()?;
+
+    let (mut metrics, expected_digest) = metrics::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut config: OtlpHttp = metrics.lookup("otlp-http", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::Enable => {
+                    config.enable = true;
+                }
+                DeletableProperty::Header => {
+                    config.header = None;
+                }
+                DeletableProperty::ResourceAttribute => {
+                    config.resource_attribute = None;
+                }
+                DeletableProperty::VerifyTls => {
+                    config.verify_tls = None;
+                }
+                DeletableProperty::Comment => {
+                    config.comment = None;
+                }
+            }
+        }
+    }
+
+    if let Some(comment) = update.comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            config.comment = None;
+        } else {
+            config.comment = Some(comment);
+        }
+    }
+
+    if let Some(url) = update.url {
+        config.url = url;
+    }
+
+    if let Some(enable) = update.enable {
+        config.enable = enable;
+    }
+
+    if update.header.is_some() {
+        config.header = update.header;
+    }
+    if update.resource_attribute.is_some() {
+        config.resource_attribute = update.resource_attribute;
+    }
+    if update.verify_tls.is_some() {
+        config.verify_tls = update.verify_tls;
+    }
+
+    test_server(&config).await?;
+
+    metrics.set_data(&name, "otlp-http", &config)?;
+
+    metrics::save_config(&metrics)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_OTLP_HTTP_SERVER)
+    .put(&API_METHOD_UPDATE_OTLP_HTTP_SERVER)
+    .delete(&API_METHOD_DELETE_OTLP_HTTP_SERVER);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_OTLP_HTTP_SERVERS)
+    .post(&API_METHOD_CREATE_OTLP_HTTP_SERVER)
+    .match_all("name", &ITEM_ROUTER);
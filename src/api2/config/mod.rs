@@ -11,8 +11,11 @@ pub mod datastore;
 pub mod drive;
 pub mod media_pool;
 pub mod metrics;
+pub mod notifications;
 pub mod prune;
+pub mod quota;
 pub mod remote;
+pub mod s3;
 pub mod sync;
 pub mod tape_backup_job;
 pub mod tape_encryption_keys;
@@ -28,8 +31,11 @@ const SUBDIRS: SubdirMap = &sorted!([
     ("drive", &drive::ROUTER),
     ("media-pool", &media_pool::ROUTER),
     ("metrics", &metrics::ROUTER),
+    ("notifications", &notifications::ROUTER),
     ("prune", &prune::ROUTER),
+    ("quota", &quota::ROUTER),
     ("remote", &remote::ROUTER),
+    ("s3", &s3::ROUTER),
     ("sync", &sync::ROUTER),
     ("tape-backup-job", &tape_backup_job::ROUTER),
     ("tape-encryption-keys", &tape_encryption_keys::ROUTER),
@@ -0,0 +1,307 @@
+use anyhow::{bail, Error};
+use hex::FromHex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use proxmox_router::{Permission, Router, RpcEnvironment};
+use proxmox_schema::{api, param_bail};
+
+use pbs_api_types::{
+    MatcherConfig, MatcherConfigUpdater, NOTIFICATION_ID_SCHEMA, PRIV_SYS_AUDIT, PRIV_SYS_MODIFY,
+    PROXMOX_CONFIG_DIGEST_SCHEMA,
+};
+
+use pbs_config::notifications;
+
+/// Check that the rules of a matcher are valid and its targets exist.
+fn check_matcher(
+    section_config: &proxmox_section_config::SectionConfigData,
+    matcher: &MatcherConfig,
+) -> Result<(), Error> {
+    for rule in matcher.match_field.iter().flatten() {
+        if let Some(regex) = rule.strip_prefix("regex:") {
+            let (_field, regex) = regex.split_once('=').unwrap(); // checked by the schema
+            if let Err(err) = regex::Regex::new(regex) {
+                param_bail!("match-field", "invalid regex '{}' - {}", regex, err);
+            }
+        }
+    }
+    for target in matcher.target.iter().flatten() {
+        match section_config.sections.get(target) {
+            Some((section_type, _))
+                if notifications::NOTIFICATION_TARGET_TYPES.contains(&section_type.as_str()) => {}
+            _ => param_bail!("target", "notification target '{}' does not exist.", target),
+        }
+    }
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured notification matchers.",
+        type: Array,
+        items: { type: MatcherConfig },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List configured notification matchers.
+pub fn list_matchers(
+    _param: Value,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<MatcherConfig>, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let list: Vec<MatcherConfig> = config.convert_to_typed_array("matcher")?;
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            config: {
+                type: MatcherConfig,
+                flatten: true,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new notification matcher.
+pub fn create_matcher(config: MatcherConfig) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, _digest) = notifications::config()?;
+
+    if section_config.sections.get(&config.name).is_some() {
+        param_bail!(
+            "name",
+            "notification target or matcher '{}' already exists.",
+            config.name
+        );
+    }
+
+    check_matcher(&section_config, &config)?;
+
+    section_config.set_data(&config.name, "matcher", &config)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: MatcherConfig },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read a notification matcher.
+pub fn read_matcher(name: String, rpcenv: &mut dyn RpcEnvironment) -> Result<MatcherConfig, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let config: MatcherConfig = config.lookup("matcher", &name)?;
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(config)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the match-field property.
+    MatchField,
+    /// Delete the match-severity property.
+    MatchSeverity,
+    /// Delete the mode property.
+    Mode,
+    /// Delete the invert-match property.
+    InvertMatch,
+    /// Delete the target property.
+    Target,
+    /// Delete the comment property.
+    Comment,
+    /// Delete the disable property.
+    Disable,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+            update: {
+                type: MatcherConfigUpdater,
+                flatten: true,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update a notification matcher.
+pub fn update_matcher(
+    name: String,
+    update: MatcherConfigUpdater,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, expected_digest) = notifications::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut config: MatcherConfig = section_config.lookup("matcher", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::MatchField => {
+                    config.match_field = None;
+                }
+                DeletableProperty::MatchSeverity => {
+                    config.match_severity = None;
+                }
+                DeletableProperty::Mode => {
+                    config.mode = None;
+                }
+                DeletableProperty::InvertMatch => {
+                    config.invert_match = None;
+                }
+                DeletableProperty::Target => {
+                    config.target = None;
+                }
+                DeletableProperty::Comment => {
+                    config.comment = None;
+                }
+                DeletableProperty::Disable => {
+                    config.disable = None;
+                }
+            }
+        }
+    }
+
+    if let Some(comment) = update.comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            config.comment = None;
+        } else {
+            config.comment = Some(comment);
+        }
+    }
+    if update.match_field.is_some() {
+        config.match_field = update.match_field;
+    }
+    if update.match_severity.is_some() {
+        config.match_severity = update.match_severity;
+    }
+    if update.mode.is_some() {
+        config.mode = update.mode;
+    }
+    if update.invert_match.is_some() {
+        config.invert_match = update.invert_match;
+    }
+    if update.target.is_some() {
+        config.target = update.target;
+    }
+    if update.disable.is_some() {
+        config.disable = update.disable;
+    }
+
+    check_matcher(&section_config, &config)?;
+
+    section_config.set_data(&name, "matcher", &config)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove a notification matcher.
+pub fn delete_matcher(name: String, digest: Option<String>) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, expected_digest) = notifications::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match section_config.sections.get(&name) {
+        Some((section_type, _)) if section_type == "matcher" => {
+            section_config.sections.remove(&name);
+        }
+        _ => bail!("notification matcher '{}' does not exist.", name),
+    }
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_MATCHER)
+    .put(&API_METHOD_UPDATE_MATCHER)
+    .delete(&API_METHOD_DELETE_MATCHER);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_MATCHERS)
+    .post(&API_METHOD_CREATE_MATCHER)
+    .match_all("name", &ITEM_ROUTER);
@@ -0,0 +1,139 @@
+use anyhow::{bail, Error};
+use serde_json::Value;
+
+use proxmox_router::list_subdirs_api_method;
+use proxmox_router::{Permission, Router, RpcEnvironment, SubdirMap};
+use proxmox_schema::api;
+use proxmox_section_config::SectionConfigData;
+use proxmox_sortable_macro::sortable;
+
+use pbs_api_types::{
+    MatcherConfig, NotificationSeverity, NotificationTargetInfo, NotificationTargetType,
+    NOTIFICATION_ID_SCHEMA, PRIV_SYS_AUDIT, PRIV_SYS_MODIFY,
+};
+
+use pbs_config::notifications;
+
+use crate::server::notifications::{send_to_target, Notification};
+
+pub mod gotify;
+pub mod matchers;
+pub mod sendmail;
+pub mod smtp;
+pub mod webhook;
+
+/// Remove a deleted target from the target lists of all matchers.
+fn remove_target_references(config: &mut SectionConfigData, name: &str) -> Result<(), Error> {
+    let matchers: Vec<MatcherConfig> = config.convert_to_typed_array("matcher")?;
+    for mut matcher in matchers {
+        if let Some(ref mut targets) = matcher.target {
+            if targets.iter().any(|target| target == name) {
+                targets.retain(|target| target != name);
+                config.set_data(&matcher.name.clone(), "matcher", &matcher)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of all notification targets.",
+        type: Array,
+        items: { type: NotificationTargetInfo },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List all notification targets.
+pub fn list_targets(
+    _param: Value,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<NotificationTargetInfo>, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let mut list = Vec::new();
+
+    for (name, (section_type, data)) in config.sections.iter() {
+        let ty = match section_type.as_str() {
+            "sendmail" => NotificationTargetType::Sendmail,
+            "smtp" => NotificationTargetType::Smtp,
+            "gotify" => NotificationTargetType::Gotify,
+            "webhook" => NotificationTargetType::Webhook,
+            _ => continue,
+        };
+        list.push(NotificationTargetInfo {
+            name: name.to_string(),
+            ty,
+            disable: data["disable"].as_bool(),
+            comment: data["comment"].as_str().map(String::from),
+        });
+    }
+
+    list.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Send a test notification to a target.
+pub fn test_target(name: String) -> Result<(), Error> {
+    let (config, _digest) = notifications::config()?;
+
+    if !config.sections.contains_key(&name) {
+        bail!("notification target '{}' does not exist.", name);
+    }
+
+    let notification = Notification::new(
+        "test",
+        NotificationSeverity::Info,
+        format!("Test notification from {}", proxmox_sys::nodename()),
+        format!("This is a test of the notification target '{name}'."),
+    );
+
+    send_to_target(&config, &name, &notification)
+}
+
+const TARGET_ITEM_ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(TARGET_ITEM_SUBDIRS))
+    .subdirs(TARGET_ITEM_SUBDIRS);
+
+#[sortable]
+const TARGET_ITEM_SUBDIRS: SubdirMap =
+    &sorted!([("test", &Router::new().post(&API_METHOD_TEST_TARGET)),]);
+
+const TARGETS_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_TARGETS)
+    .match_all("name", &TARGET_ITEM_ROUTER);
+
+#[sortable]
+const SUBDIRS: SubdirMap = &sorted!([
+    ("gotify", &gotify::ROUTER),
+    ("matchers", &matchers::ROUTER),
+    ("sendmail", &sendmail::ROUTER),
+    ("smtp", &smtp::ROUTER),
+    ("targets", &TARGETS_ROUTER),
+    ("webhook", &webhook::ROUTER),
+]);
+
+pub const ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(SUBDIRS))
+    .subdirs(SUBDIRS);
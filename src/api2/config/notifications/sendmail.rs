@@ -0,0 +1,277 @@
+use anyhow::{bail, Error};
+use hex::FromHex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use proxmox_router::{Permission, Router, RpcEnvironment};
+use proxmox_schema::{api, param_bail};
+
+use pbs_api_types::{
+    SendmailConfig, SendmailConfigUpdater, NOTIFICATION_ID_SCHEMA, PRIV_SYS_AUDIT, PRIV_SYS_MODIFY,
+    PROXMOX_CONFIG_DIGEST_SCHEMA,
+};
+
+use pbs_config::notifications;
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured sendmail targets.",
+        type: Array,
+        items: { type: SendmailConfig },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List configured sendmail targets.
+pub fn list_sendmail_targets(
+    _param: Value,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<SendmailConfig>, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let list: Vec<SendmailConfig> = config.convert_to_typed_array("sendmail")?;
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            config: {
+                type: SendmailConfig,
+                flatten: true,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new sendmail target.
+pub fn create_sendmail_target(config: SendmailConfig) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, _digest) = notifications::config()?;
+
+    if section_config.sections.get(&config.name).is_some() {
+        param_bail!(
+            "name",
+            "notification target or matcher '{}' already exists.",
+            config.name
+        );
+    }
+
+    section_config.set_data(&config.name, "sendmail", &config)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: SendmailConfig },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read a sendmail target.
+pub fn read_sendmail_target(
+    name: String,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<SendmailConfig, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let config: SendmailConfig = config.lookup("sendmail", &name)?;
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(config)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the mailto property.
+    Mailto,
+    /// Delete the mailto-user property.
+    MailtoUser,
+    /// Delete the from-address property.
+    FromAddress,
+    /// Delete the author property.
+    Author,
+    /// Delete the comment property.
+    Comment,
+    /// Delete the disable property.
+    Disable,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+            update: {
+                type: SendmailConfigUpdater,
+                flatten: true,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update a sendmail target.
+pub fn update_sendmail_target(
+    name: String,
+    update: SendmailConfigUpdater,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, expected_digest) = notifications::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut config: SendmailConfig = section_config.lookup("sendmail", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::Mailto => {
+                    config.mailto = None;
+                }
+                DeletableProperty::MailtoUser => {
+                    config.mailto_user = None;
+                }
+                DeletableProperty::FromAddress => {
+                    config.from_address = None;
+                }
+                DeletableProperty::Author => {
+                    config.author = None;
+                }
+                DeletableProperty::Comment => {
+                    config.comment = None;
+                }
+                DeletableProperty::Disable => {
+                    config.disable = None;
+                }
+            }
+        }
+    }
+
+    if let Some(comment) = update.comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            config.comment = None;
+        } else {
+            config.comment = Some(comment);
+        }
+    }
+    if update.mailto.is_some() {
+        config.mailto = update.mailto;
+    }
+    if update.mailto_user.is_some() {
+        config.mailto_user = update.mailto_user;
+    }
+    if update.from_address.is_some() {
+        config.from_address = update.from_address;
+    }
+    if update.author.is_some() {
+        config.author = update.author;
+    }
+    if update.disable.is_some() {
+        config.disable = update.disable;
+    }
+
+    section_config.set_data(&name, "sendmail", &config)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove a sendmail target.
+pub fn delete_sendmail_target(name: String, digest: Option<String>) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, expected_digest) = notifications::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match section_config.sections.get(&name) {
+        Some((section_type, _)) if section_type == "sendmail" => {
+            section_config.sections.remove(&name);
+        }
+        _ => bail!("sendmail target '{}' does not exist.", name),
+    }
+
+    super::remove_target_references(&mut section_config, &name)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_SENDMAIL_TARGET)
+    .put(&API_METHOD_UPDATE_SENDMAIL_TARGET)
+    .delete(&API_METHOD_DELETE_SENDMAIL_TARGET);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_SENDMAIL_TARGETS)
+    .post(&API_METHOD_CREATE_SENDMAIL_TARGET)
+    .match_all("name", &ITEM_ROUTER);
@@ -0,0 +1,314 @@
+use anyhow::{bail, Error};
+use hex::FromHex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use proxmox_router::{Permission, Router, RpcEnvironment};
+use proxmox_schema::{api, param_bail};
+
+use pbs_api_types::{
+    SmtpConfig, SmtpConfigUpdater, NOTIFICATION_ID_SCHEMA, PRIV_SYS_AUDIT, PRIV_SYS_MODIFY,
+    PROXMOX_CONFIG_DIGEST_SCHEMA,
+};
+
+use pbs_config::notifications;
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured SMTP targets.",
+        type: Array,
+        items: { type: SmtpConfig },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List configured SMTP targets.
+pub fn list_smtp_targets(
+    _param: Value,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<SmtpConfig>, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let mut list: Vec<SmtpConfig> = config.convert_to_typed_array("smtp")?;
+
+    // don't return secrets via api
+    for item in list.iter_mut() {
+        item.password = None;
+    }
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            config: {
+                type: SmtpConfig,
+                flatten: true,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new SMTP target.
+pub fn create_smtp_target(config: SmtpConfig) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, _digest) = notifications::config()?;
+
+    if section_config.sections.get(&config.name).is_some() {
+        param_bail!(
+            "name",
+            "notification target or matcher '{}' already exists.",
+            config.name
+        );
+    }
+
+    section_config.set_data(&config.name, "smtp", &config)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: SmtpConfig },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read an SMTP target.
+pub fn read_smtp_target(
+    name: String,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<SmtpConfig, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let mut config: SmtpConfig = config.lookup("smtp", &name)?;
+
+    config.password = None;
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(config)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the port property.
+    Port,
+    /// Delete the mode property.
+    Mode,
+    /// Delete the username property.
+    Username,
+    /// Delete the password property.
+    Password,
+    /// Delete the mailto property.
+    Mailto,
+    /// Delete the mailto-user property.
+    MailtoUser,
+    /// Delete the author property.
+    Author,
+    /// Delete the comment property.
+    Comment,
+    /// Delete the disable property.
+    Disable,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+            update: {
+                type: SmtpConfigUpdater,
+                flatten: true,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update an SMTP target.
+pub fn update_smtp_target(
+    name: String,
+    update: SmtpConfigUpdater,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, expected_digest) = notifications::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut config: SmtpConfig = section_config.lookup("smtp", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::Port => {
+                    config.port = None;
+                }
+                DeletableProperty::Mode => {
+                    config.mode = None;
+                }
+                DeletableProperty::Username => {
+                    config.username = None;
+                }
+                DeletableProperty::Password => {
+                    config.password = None;
+                }
+                DeletableProperty::Mailto => {
+                    config.mailto = None;
+                }
+                DeletableProperty::MailtoUser => {
+                    config.mailto_user = None;
+                }
+                DeletableProperty::Author => {
+                    config.author = None;
+                }
+                DeletableProperty::Comment => {
+                    config.comment = None;
+                }
+                DeletableProperty::Disable => {
+                    config.disable = None;
+                }
+            }
+        }
+    }
+
+    if let Some(comment) = update.comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            config.comment = None;
+        } else {
+            config.comment = Some(comment);
+        }
+    }
+    if let Some(server) = update.server {
+        config.server = server;
+    }
+    if update.port.is_some() {
+        config.port = update.port;
+    }
+    if update.mode.is_some() {
+        config.mode = update.mode;
+    }
+    if update.username.is_some() {
+        config.username = update.username;
+    }
+    if update.password.is_some() {
+        config.password = update.password;
+    }
+    if update.mailto.is_some() {
+        config.mailto = update.mailto;
+    }
+    if update.mailto_user.is_some() {
+        config.mailto_user = update.mailto_user;
+    }
+    if let Some(from_address) = update.from_address {
+        config.from_address = from_address;
+    }
+    if update.author.is_some() {
+        config.author = update.author;
+    }
+    if update.disable.is_some() {
+        config.disable = update.disable;
+    }
+
+    section_config.set_data(&name, "smtp", &config)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove an SMTP target.
+pub fn delete_smtp_target(name: String, digest: Option<String>) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, expected_digest) = notifications::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match section_config.sections.get(&name) {
+        Some((section_type, _)) if section_type == "smtp" => {
+            section_config.sections.remove(&name);
+        }
+        _ => bail!("SMTP target '{}' does not exist.", name),
+    }
+
+    super::remove_target_references(&mut section_config, &name)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_SMTP_TARGET)
+    .put(&API_METHOD_UPDATE_SMTP_TARGET)
+    .delete(&API_METHOD_DELETE_SMTP_TARGET);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_SMTP_TARGETS)
+    .post(&API_METHOD_CREATE_SMTP_TARGET)
+    .match_all("name", &ITEM_ROUTER);
@@ -0,0 +1,304 @@
+use anyhow::{bail, Error};
+use hex::FromHex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use proxmox_router::{Permission, Router, RpcEnvironment};
+use proxmox_schema::{api, param_bail};
+
+use pbs_api_types::{
+    WebhookConfig, WebhookConfigUpdater, NOTIFICATION_ID_SCHEMA, PRIV_SYS_AUDIT, PRIV_SYS_MODIFY,
+    PROXMOX_CONFIG_DIGEST_SCHEMA,
+};
+
+use pbs_config::notifications;
+
+fn check_body(config: &WebhookConfig) -> Result<(), Error> {
+    if let Some(ref body) = config.body {
+        let body = match base64::decode(body) {
+            Ok(body) => body,
+            Err(err) => param_bail!("body", "body template is not base64 encoded - {}", err),
+        };
+        if String::from_utf8(body).is_err() {
+            param_bail!("body", "body template is not valid UTF-8");
+        }
+    }
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured webhook targets.",
+        type: Array,
+        items: { type: WebhookConfig },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List configured webhook targets.
+pub fn list_webhook_targets(
+    _param: Value,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<WebhookConfig>, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let mut list: Vec<WebhookConfig> = config.convert_to_typed_array("webhook")?;
+
+    // don't return secrets via api
+    for item in list.iter_mut() {
+        item.secret = None;
+    }
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            config: {
+                type: WebhookConfig,
+                flatten: true,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new webhook target.
+pub fn create_webhook_target(config: WebhookConfig) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, _digest) = notifications::config()?;
+
+    if section_config.sections.get(&config.name).is_some() {
+        param_bail!(
+            "name",
+            "notification target or matcher '{}' already exists.",
+            config.name
+        );
+    }
+
+    check_body(&config)?;
+
+    section_config.set_data(&config.name, "webhook", &config)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: WebhookConfig },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read a webhook target.
+pub fn read_webhook_target(
+    name: String,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<WebhookConfig, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let mut config: WebhookConfig = config.lookup("webhook", &name)?;
+
+    config.secret = None;
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(config)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the method property.
+    Method,
+    /// Delete the header property.
+    Header,
+    /// Delete the body property.
+    Body,
+    /// Delete the secret property.
+    Secret,
+    /// Delete the comment property.
+    Comment,
+    /// Delete the disable property.
+    Disable,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+            update: {
+                type: WebhookConfigUpdater,
+                flatten: true,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update a webhook target.
+pub fn update_webhook_target(
+    name: String,
+    update: WebhookConfigUpdater,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, expected_digest) = notifications::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut config: WebhookConfig = section_config.lookup("webhook", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::Method => {
+                    config.method = None;
+                }
+                DeletableProperty::Header => {
+                    config.header = None;
+                }
+                DeletableProperty::Body => {
+                    config.body = None;
+                }
+                DeletableProperty::Secret => {
+                    config.secret = None;
+                }
+                DeletableProperty::Comment => {
+                    config.comment = None;
+                }
+                DeletableProperty::Disable => {
+                    config.disable = None;
+                }
+            }
+        }
+    }
+
+    if let Some(comment) = update.comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            config.comment = None;
+        } else {
+            config.comment = Some(comment);
+        }
+    }
+    if update.method.is_some() {
+        config.method = update.method;
+    }
+    if let Some(url) = update.url {
+        config.url = url;
+    }
+    if update.header.is_some() {
+        config.header = update.header;
+    }
+    if update.body.is_some() {
+        config.body = update.body;
+    }
+    if update.secret.is_some() {
+        config.secret = update.secret;
+    }
+    if update.disable.is_some() {
+        config.disable = update.disable;
+    }
+
+    check_body(&config)?;
+
+    section_config.set_data(&name, "webhook", &config)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove a webhook target.
+pub fn delete_webhook_target(name: String, digest: Option<String>) -> Result<(), Error> {
+    let _lock = notifications::lock_config()?;
+
+    let (mut section_config, expected_digest) = notifications::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match section_config.sections.get(&name) {
+        Some((section_type, _)) if section_type == "webhook" => {
+            section_config.sections.remove(&name);
+        }
+        _ => bail!("webhook target '{}' does not exist.", name),
+    }
+
+    super::remove_target_references(&mut section_config, &name)?;
+
+    notifications::save_config(&section_config)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_WEBHOOK_TARGET)
+    .put(&API_METHOD_UPDATE_WEBHOOK_TARGET)
+    .delete(&API_METHOD_DELETE_WEBHOOK_TARGET);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_WEBHOOK_TARGETS)
+    .post(&API_METHOD_CREATE_WEBHOOK_TARGET)
+    .match_all("name", &ITEM_ROUTER);
@@ -0,0 +1,280 @@
+use ::serde::{Deserialize, Serialize};
+use anyhow::Error;
+use hex::FromHex;
+use serde_json::Value;
+
+use proxmox_router::{http_bail, Permission, Router, RpcEnvironment};
+use proxmox_schema::{api, param_bail};
+
+use pbs_api_types::{
+    Authid, QuotaConfig, QuotaConfigUpdater, PRIV_DATASTORE_AUDIT, PRIV_DATASTORE_MODIFY,
+    PROXMOX_CONFIG_DIGEST_SCHEMA, QUOTA_ID_SCHEMA,
+};
+use pbs_config::quota;
+
+use pbs_config::CachedUserInfo;
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List configured quotas.",
+        type: Array,
+        items: { type: QuotaConfig },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit on the quota's datastore or namespace.",
+    },
+)]
+/// List all quotas
+pub fn list_quotas(
+    _param: Value,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<QuotaConfig>, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let (config, digest) = quota::config()?;
+
+    let list = config.convert_to_typed_array("quota")?;
+
+    let list = list
+        .into_iter()
+        .filter(|quota: &QuotaConfig| {
+            let privs = user_info.lookup_privs(&auth_id, &quota.acl_path());
+
+            privs & PRIV_DATASTORE_AUDIT != 0
+        })
+        .collect();
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            config: {
+                type: QuotaConfig,
+                flatten: true,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on the quota's datastore or namespace.",
+    },
+)]
+/// Create a new quota.
+pub fn create_quota(config: QuotaConfig, rpcenv: &mut dyn RpcEnvironment) -> Result<(), Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    user_info.check_privs(&auth_id, &config.acl_path(), PRIV_DATASTORE_MODIFY, false)?;
+
+    if let Err(err) = config.check() {
+        param_bail!("ns", "{err}");
+    }
+
+    let _lock = quota::lock_config()?;
+
+    let (mut section_config, _digest) = quota::config()?;
+
+    if section_config.sections.get(&config.id).is_some() {
+        param_bail!("id", "quota '{}' already exists.", config.id);
+    }
+
+    section_config.set_data(&config.id, "quota", &config)?;
+
+    quota::save_config(&section_config)?;
+
+    Ok(())
+}
+
+#[api(
+   input: {
+        properties: {
+            id: {
+                schema: QUOTA_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: QuotaConfig },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit on the quota's datastore or namespace.",
+    },
+)]
+/// Read a quota configuration.
+pub fn read_quota(id: String, rpcenv: &mut dyn RpcEnvironment) -> Result<QuotaConfig, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let (config, digest) = quota::config()?;
+
+    let quota: QuotaConfig = config.lookup("quota", &id)?;
+
+    user_info.check_privs(&auth_id, &quota.acl_path(), PRIV_DATASTORE_AUDIT, false)?;
+
+    rpcenv["digest"] = hex::encode(digest).into();
+
+    Ok(quota)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the comment.
+    Comment,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: QUOTA_ID_SCHEMA,
+            },
+            update: {
+                type: QuotaConfigUpdater,
+                flatten: true,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on the quota's datastore or namespace.",
+    },
+)]
+/// Update quota configuration.
+pub fn update_quota(
+    id: String,
+    update: QuotaConfigUpdater,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<(), Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let _lock = quota::lock_config()?;
+
+    let (mut config, expected_digest) = quota::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: QuotaConfig = config.lookup("quota", &id)?;
+
+    user_info.check_privs(&auth_id, &data.acl_path(), PRIV_DATASTORE_MODIFY, false)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::Comment => {
+                    data.comment = None;
+                }
+            }
+        }
+    }
+
+    if let Some(comment) = update.comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+
+    if let Some(limit) = update.limit {
+        data.limit = limit;
+    }
+
+    config.set_data(&id, "quota", &data)?;
+
+    quota::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: QUOTA_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on the quota's datastore or namespace.",
+    },
+)]
+/// Remove a quota configuration
+pub fn delete_quota(
+    id: String,
+    digest: Option<String>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<(), Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let _lock = quota::lock_config()?;
+
+    let (mut config, expected_digest) = quota::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.lookup::<QuotaConfig>("quota", &id) {
+        Ok(quota) => {
+            user_info.check_privs(&auth_id, &quota.acl_path(), PRIV_DATASTORE_MODIFY, false)?;
+            config.sections.remove(&id);
+        }
+        Err(_) => {
+            http_bail!(NOT_FOUND, "quota '{}' does not exist.", id)
+        }
+    };
+
+    quota::save_config(&config)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_QUOTA)
+    .put(&API_METHOD_UPDATE_QUOTA)
+    .delete(&API_METHOD_DELETE_QUOTA);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_QUOTAS)
+    .post(&API_METHOD_CREATE_QUOTA)
+    .match_all("id", &ITEM_ROUTER);
@@ -1,6 +1,7 @@
 use ::serde::{Deserialize, Serialize};
 use anyhow::{bail, format_err, Error};
 use hex::FromHex;
+use openssl::pkey::PKey;
 use pbs_api_types::BackupNamespace;
 use pbs_api_types::NamespaceListItem;
 use proxmox_router::list_subdirs_api_method;
@@ -14,10 +15,12 @@ use proxmox_schema::{api, param_bail};
 use pbs_api_types::{
     Authid, DataStoreListItem, GroupListItem, RateLimitConfig, Remote, RemoteConfig,
     RemoteConfigUpdater, RemoteWithoutPassword, SyncJobConfig, DATASTORE_SCHEMA, PRIV_REMOTE_AUDIT,
-    PRIV_REMOTE_MODIFY, PROXMOX_CONFIG_DIGEST_SCHEMA, REMOTE_ID_SCHEMA, REMOTE_PASSWORD_SCHEMA,
+    PRIV_REMOTE_MODIFY, PROXMOX_CONFIG_DIGEST_SCHEMA, REMOTE_CLIENT_CERT_SCHEMA,
+    REMOTE_CLIENT_KEY_SCHEMA, REMOTE_ID_SCHEMA, REMOTE_PASSWORD_SCHEMA,
 };
 use pbs_client::{HttpClient, HttpClientOptions};
 use pbs_config::sync;
+use pbs_tools::cert::CertInfo;
 
 use pbs_config::CachedUserInfo;
 use serde_json::json;
@@ -62,6 +65,58 @@ pub fn list_remotes(
     Ok(list)
 }
 
+/// Check that the client certificate and private key belong together, and return the
+/// certificate's fingerprint.
+fn check_client_cert(cert_pem: &str, key_pem: &str) -> Result<String, Error> {
+    let cert = match CertInfo::from_pem(cert_pem.as_bytes()) {
+        Ok(cert) => cert,
+        Err(err) => param_bail!("client-cert", "unable to parse certificate - {}", err),
+    };
+    let key = match PKey::private_key_from_pem(key_pem.as_bytes()) {
+        Ok(key) => key,
+        Err(err) => param_bail!("client-key", "unable to parse private key - {}", err),
+    };
+    if !cert.public_key()?.public_eq(&key) {
+        param_bail!(
+            "client-key",
+            "private key does not match client certificate"
+        );
+    }
+    if cert.is_expired_after_epoch(proxmox_time::epoch_i64())? {
+        param_bail!("client-cert", "client certificate is expired");
+    }
+    cert.fingerprint()
+}
+
+/// Check that the remote has exactly one way to authenticate.
+///
+/// API tokens always need their secret, as they cannot log in with a client certificate.
+fn check_remote_credentials(remote: &Remote) -> Result<(), Error> {
+    let has_cert = remote.config.client_cert_fingerprint.is_some();
+    if remote.config.auth_id.is_token() {
+        if has_cert {
+            param_bail!(
+                "client-cert",
+                "client certificate login is not possible for API token '{}'",
+                remote.config.auth_id
+            );
+        }
+        if remote.password.is_empty() {
+            param_bail!(
+                "password",
+                "API token secret required for API token '{}'",
+                remote.config.auth_id
+            );
+        }
+    } else if has_cert == !remote.password.is_empty() {
+        param_bail!(
+            "password",
+            "either a password or a client certificate is required, but not both"
+        );
+    }
+    Ok(())
+}
+
 #[api(
     protected: true,
     input: {
@@ -75,8 +130,17 @@ pub fn list_remotes(
             },
             password: {
                 // We expect the plain password here (not base64 encoded)
+                optional: true,
                 schema: REMOTE_PASSWORD_SCHEMA,
             },
+            "client-cert": {
+                optional: true,
+                schema: REMOTE_CLIENT_CERT_SCHEMA,
+            },
+            "client-key": {
+                optional: true,
+                schema: REMOTE_CLIENT_KEY_SCHEMA,
+            },
         },
     },
     access: {
@@ -84,7 +148,13 @@ pub fn list_remotes(
     },
 )]
 /// Create new remote.
-pub fn create_remote(name: String, config: RemoteConfig, password: String) -> Result<(), Error> {
+pub fn create_remote(
+    name: String,
+    mut config: RemoteConfig,
+    password: Option<String>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
+) -> Result<(), Error> {
     let _lock = pbs_config::remote::lock_config()?;
 
     let (mut section_config, _digest) = pbs_config::remote::config()?;
@@ -93,12 +163,32 @@ pub fn create_remote(name: String, config: RemoteConfig, password: String) -> Re
         param_bail!("name", "remote '{}' already exists.", name);
     }
 
+    let client_cert = match (client_cert, client_key) {
+        (Some(cert), Some(key)) => Some((cert, key)),
+        (None, None) => None,
+        _ => param_bail!(
+            "client-key",
+            "client certificate and key must be given together"
+        ),
+    };
+
+    config.client_cert_fingerprint = match client_cert {
+        Some((ref cert, ref key)) => Some(check_client_cert(cert, key)?),
+        None => None,
+    };
+
     let remote = Remote {
         name: name.clone(),
         config,
-        password,
+        password: password.unwrap_or_default(),
     };
 
+    check_remote_credentials(&remote)?;
+
+    if let Some((cert, key)) = client_cert {
+        pbs_config::remote::store_client_cert(&name, cert.as_bytes(), key.as_bytes())?;
+    }
+
     section_config.set_data(&name, "remote", &remote)?;
 
     pbs_config::remote::save_config(&section_config)?;
@@ -142,6 +232,8 @@ pub enum DeletableProperty {
     Fingerprint,
     /// Delete the port property.
     Port,
+    /// Delete the client certificate (a password has to be set instead).
+    ClientCert,
 }
 
 #[api(
@@ -160,6 +252,14 @@ pub enum DeletableProperty {
                 optional: true,
                 schema: REMOTE_PASSWORD_SCHEMA,
             },
+            "client-cert": {
+                optional: true,
+                schema: REMOTE_CLIENT_CERT_SCHEMA,
+            },
+            "client-key": {
+                optional: true,
+                schema: REMOTE_CLIENT_KEY_SCHEMA,
+            },
             delete: {
                 description: "List of properties to delete.",
                 type: Array,
@@ -183,6 +283,8 @@ pub fn update_remote(
     name: String,
     update: RemoteConfigUpdater,
     password: Option<String>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
     delete: Option<Vec<DeletableProperty>>,
     digest: Option<String>,
 ) -> Result<(), Error> {
@@ -209,6 +311,9 @@ pub fn update_remote(
                 DeletableProperty::Port => {
                     data.config.port = None;
                 }
+                DeletableProperty::ClientCert => {
+                    data.config.client_cert_fingerprint = None;
+                }
             }
         }
     }
@@ -230,14 +335,47 @@ pub fn update_remote(
     if let Some(auth_id) = update.auth_id {
         data.config.auth_id = auth_id;
     }
+    let client_cert = match (client_cert, client_key) {
+        (Some(cert), Some(key)) => Some((cert, key)),
+        (None, None) => None,
+        _ => param_bail!(
+            "client-key",
+            "client certificate and key must be given together"
+        ),
+    };
+
+    // switching between password and certificate login drops the other credentials
+    if let Some((ref cert, ref key)) = client_cert {
+        if password.is_some() {
+            param_bail!(
+                "password",
+                "cannot set both a password and a client certificate"
+            );
+        }
+        data.config.client_cert_fingerprint = Some(check_client_cert(cert, key)?);
+        data.password = String::new();
+    }
     if let Some(password) = password {
         data.password = password;
+        data.config.client_cert_fingerprint = None;
     }
 
     if update.fingerprint.is_some() {
         data.config.fingerprint = update.fingerprint;
     }
 
+    check_remote_credentials(&data)?;
+
+    match client_cert {
+        Some((cert, key)) => {
+            pbs_config::remote::store_client_cert(&name, cert.as_bytes(), key.as_bytes())?
+        }
+        None if data.config.client_cert_fingerprint.is_none() => {
+            pbs_config::remote::remove_client_cert(&name)?
+        }
+        None => {}
+    }
+
     config.set_data(&name, "remote", &data)?;
 
     pbs_config::remote::save_config(&config)?;
@@ -268,7 +406,7 @@ pub fn delete_remote(name: String, digest: Option<String>) -> Result<(), Error>
 
     let job_list: Vec<SyncJobConfig> = sync_jobs.convert_to_typed_array("sync")?;
     for job in job_list {
-        if job.remote == name {
+        if job.remote.as_deref() == Some(&name) {
             param_bail!(
                 "name",
                 "remote '{}' is used by sync job '{}' (datastore '{}')",
@@ -296,6 +434,7 @@ pub fn delete_remote(name: String, digest: Option<String>) -> Result<(), Error>
     }
 
     pbs_config::remote::save_config(&config)?;
+    pbs_config::remote::remove_client_cert(&name)?;
 
     Ok(())
 }
@@ -305,10 +444,19 @@ pub async fn remote_client(
     remote: &Remote,
     limit: Option<RateLimitConfig>,
 ) -> Result<HttpClient, Error> {
-    let mut options = HttpClientOptions::new_non_interactive(
-        remote.password.clone(),
-        remote.config.fingerprint.clone(),
-    );
+    let mut options = if remote.config.client_cert_fingerprint.is_some() {
+        HttpClientOptions::default()
+            .fingerprint(remote.config.fingerprint.clone())
+            .client_cert(
+                pbs_config::remote::client_cert_path(&remote.name),
+                pbs_config::remote::client_key_path(&remote.name),
+            )
+    } else {
+        HttpClientOptions::new_non_interactive(
+            remote.password.clone(),
+            remote.config.fingerprint.clone(),
+        )
+    };
 
     if let Some(limit) = limit {
         options = options.rate_limit(limit);
@@ -331,6 +479,18 @@ pub async fn remote_client(
             )
         })?;
 
+    if remote.config.auth_id.is_token() {
+        // login() does not talk to the remote for API tokens, so verify the secret explicitly
+        client.get("api2/json/version", None).await.map_err(|err| {
+            format_err!(
+                "remote connection to '{}' failed - API token '{}' rejected - {}",
+                remote.config.host,
+                remote.config.auth_id,
+                err
+            )
+        })?;
+    }
+
     Ok(client)
 }
 
@@ -515,13 +675,29 @@ const SCAN_ROUTER: Router = Router::new()
     .get(&API_METHOD_SCAN_REMOTE_DATASTORES)
     .match_all("store", &DATASTORE_SCAN_ROUTER);
 
+crate::audited_api_method!(
+    AUDITED_CREATE_REMOTE = API_METHOD_CREATE_REMOTE,
+    "POST",
+    "/config/remote"
+);
+crate::audited_api_method!(
+    AUDITED_UPDATE_REMOTE = API_METHOD_UPDATE_REMOTE,
+    "PUT",
+    "/config/remote/{name}"
+);
+crate::audited_api_method!(
+    AUDITED_DELETE_REMOTE = API_METHOD_DELETE_REMOTE,
+    "DELETE",
+    "/config/remote/{name}"
+);
+
 const ITEM_ROUTER: Router = Router::new()
     .get(&API_METHOD_READ_REMOTE)
-    .put(&API_METHOD_UPDATE_REMOTE)
-    .delete(&API_METHOD_DELETE_REMOTE)
+    .put(&AUDITED_UPDATE_REMOTE)
+    .delete(&AUDITED_DELETE_REMOTE)
     .subdirs(&[("scan", &SCAN_ROUTER)]);
 
 pub const ROUTER: Router = Router::new()
     .get(&API_METHOD_LIST_REMOTES)
-    .post(&API_METHOD_CREATE_REMOTE)
+    .post(&AUDITED_CREATE_REMOTE)
     .match_all("name", &ITEM_ROUTER);
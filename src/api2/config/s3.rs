@@ -0,0 +1,315 @@
+use ::serde::{Deserialize, Serialize};
+use anyhow::Error;
+use hex::FromHex;
+use serde_json::Value;
+
+use proxmox_router::{http_bail, ApiMethod, Permission, Router, RpcEnvironment};
+use proxmox_schema::{api, param_bail};
+
+use pbs_api_types::{
+    DataStoreConfig, S3Endpoint, S3EndpointConfig, S3EndpointConfigUpdater,
+    S3EndpointWithoutSecret, PRIV_SYS_AUDIT, PRIV_SYS_MODIFY, PROXMOX_CONFIG_DIGEST_SCHEMA,
+    S3_ENDPOINT_ID_SCHEMA, S3_SECRET_KEY_SCHEMA,
+};
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "The list of configured S3 endpoints (with config digest).",
+        type: Array,
+        items: { type: S3EndpointWithoutSecret },
+    },
+    access: {
+        permission: &Permission::Privilege(&[], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List all S3 endpoints
+pub fn list_s3_endpoints(
+    _param: Value,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<S3EndpointWithoutSecret>, Error> {
+    let (config, digest) = pbs_config::s3::config()?;
+
+    // Note: This removes the secret key (we do not want to return it).
+    let list: Vec<S3EndpointWithoutSecret> = config.convert_to_typed_array("s3-endpoint")?;
+
+    rpcenv["digest"] = hex::encode(digest).into();
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: S3_ENDPOINT_ID_SCHEMA,
+            },
+            config: {
+                type: S3EndpointConfig,
+                flatten: true,
+            },
+            "secret-key": {
+                // We expect the plain secret key here (not base64 encoded)
+                schema: S3_SECRET_KEY_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&[], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create new S3 endpoint.
+pub fn create_s3_endpoint(
+    id: String,
+    config: S3EndpointConfig,
+    secret_key: String,
+) -> Result<(), Error> {
+    let _lock = pbs_config::s3::lock_config()?;
+
+    let (mut section_config, _digest) = pbs_config::s3::config()?;
+
+    if section_config.sections.get(&id).is_some() {
+        param_bail!("id", "s3 endpoint '{}' already exists.", id);
+    }
+
+    let endpoint = S3Endpoint {
+        id: id.clone(),
+        config,
+        secret_key,
+    };
+
+    section_config.set_data(&id, "s3-endpoint", &endpoint)?;
+
+    pbs_config::s3::save_config(&section_config)?;
+
+    Ok(())
+}
+
+#[api(
+   input: {
+        properties: {
+            id: {
+                schema: S3_ENDPOINT_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: S3EndpointWithoutSecret },
+    access: {
+        permission: &Permission::Privilege(&[], PRIV_SYS_AUDIT, false),
+    }
+)]
+/// Read S3 endpoint configuration data.
+pub fn read_s3_endpoint(
+    id: String,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<S3EndpointWithoutSecret, Error> {
+    let (config, digest) = pbs_config::s3::config()?;
+    let data: S3EndpointWithoutSecret = config.lookup("s3-endpoint", &id)?;
+    rpcenv["digest"] = hex::encode(digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the comment property.
+    Comment,
+    /// Delete the port property.
+    Port,
+    /// Delete the region property.
+    Region,
+    /// Delete the path-style property.
+    PathStyle,
+    /// Delete the insecure-http property.
+    InsecureHttp,
+    /// Delete the fingerprint property.
+    Fingerprint,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: S3_ENDPOINT_ID_SCHEMA,
+            },
+            update: {
+                type: S3EndpointConfigUpdater,
+                flatten: true,
+            },
+            "secret-key": {
+                // We expect the plain secret key here (not base64 encoded)
+                optional: true,
+                schema: S3_SECRET_KEY_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&[], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update S3 endpoint configuration.
+pub fn update_s3_endpoint(
+    id: String,
+    update: S3EndpointConfigUpdater,
+    secret_key: Option<String>,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+    let _lock = pbs_config::s3::lock_config()?;
+
+    let (mut config, expected_digest) = pbs_config::s3::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: S3Endpoint = config.lookup("s3-endpoint", &id)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::Comment => {
+                    data.config.comment = None;
+                }
+                DeletableProperty::Port => {
+                    data.config.port = None;
+                }
+                DeletableProperty::Region => {
+                    data.config.region = None;
+                }
+                DeletableProperty::PathStyle => {
+                    data.config.path_style = None;
+                }
+                DeletableProperty::InsecureHttp => {
+                    data.config.insecure_http = None;
+                }
+                DeletableProperty::Fingerprint => {
+                    data.config.fingerprint = None;
+                }
+            }
+        }
+    }
+
+    if let Some(comment) = update.comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.config.comment = None;
+        } else {
+            data.config.comment = Some(comment);
+        }
+    }
+    if let Some(host) = update.host {
+        data.config.host = host;
+    }
+    if update.port.is_some() {
+        data.config.port = update.port;
+    }
+    if update.region.is_some() {
+        data.config.region = update.region;
+    }
+    if let Some(access_key) = update.access_key {
+        data.config.access_key = access_key;
+    }
+    if let Some(secret_key) = secret_key {
+        data.secret_key = secret_key;
+    }
+    if update.path_style.is_some() {
+        data.config.path_style = update.path_style;
+    }
+    if update.insecure_http.is_some() {
+        data.config.insecure_http = update.insecure_http;
+    }
+    if update.fingerprint.is_some() {
+        data.config.fingerprint = update.fingerprint;
+    }
+
+    config.set_data(&id, "s3-endpoint", &data)?;
+
+    pbs_config::s3::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: S3_ENDPOINT_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&[], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove an S3 endpoint from the configuration file.
+pub fn delete_s3_endpoint(id: String, digest: Option<String>) -> Result<(), Error> {
+    let (datastores, _) = pbs_config::datastore::config()?;
+
+    let datastore_list: Vec<DataStoreConfig> = datastores.convert_to_typed_array("datastore")?;
+    for datastore in datastore_list {
+        if datastore.backend_config()?.endpoint.as_deref() == Some(id.as_str()) {
+            param_bail!(
+                "id",
+                "s3 endpoint '{}' is used by datastore '{}'",
+                id,
+                datastore.name
+            );
+        }
+    }
+
+    let _lock = pbs_config::s3::lock_config()?;
+
+    let (mut config, expected_digest) = pbs_config::s3::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = <[u8; 32]>::from_hex(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.sections.get(&id) {
+        Some(_) => {
+            config.sections.remove(&id);
+        }
+        None => http_bail!(NOT_FOUND, "s3 endpoint '{}' does not exist.", id),
+    }
+
+    pbs_config::s3::save_config(&config)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_S3_ENDPOINT)
+    .put(&API_METHOD_UPDATE_S3_ENDPOINT)
+    .delete(&API_METHOD_DELETE_S3_ENDPOINT);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_S3_ENDPOINTS)
+    .post(&API_METHOD_CREATE_S3_ENDPOINT)
+    .match_all("id", &ITEM_ROUTER);
@@ -7,9 +7,10 @@ use proxmox_router::{http_bail, Permission, Router, RpcEnvironment};
 use proxmox_schema::{api, param_bail};
 
 use pbs_api_types::{
-    Authid, SyncJobConfig, SyncJobConfigUpdater, JOB_ID_SCHEMA, PRIV_DATASTORE_AUDIT,
-    PRIV_DATASTORE_BACKUP, PRIV_DATASTORE_MODIFY, PRIV_DATASTORE_PRUNE, PRIV_REMOTE_AUDIT,
-    PRIV_REMOTE_READ, PROXMOX_CONFIG_DIGEST_SCHEMA,
+    Authid, SyncDirection, SyncJobConfig, SyncJobConfigUpdater, JOB_ID_SCHEMA,
+    PRIV_DATASTORE_AUDIT, PRIV_DATASTORE_BACKUP, PRIV_DATASTORE_MODIFY, PRIV_DATASTORE_PRUNE,
+    PRIV_DATASTORE_READ, PRIV_REMOTE_AUDIT, PRIV_REMOTE_DATASTORE_BACKUP,
+    PRIV_REMOTE_DATASTORE_PRUNE, PRIV_REMOTE_READ, PRIV_SYS_MODIFY, PROXMOX_CONFIG_DIGEST_SCHEMA,
 };
 use pbs_config::sync;
 
@@ -25,19 +26,32 @@ pub fn check_sync_job_read_access(
         return false;
     }
 
-    let remote_privs = user_info.lookup_privs(auth_id, &["remote", &job.remote]);
-    remote_privs & PRIV_REMOTE_AUDIT != 0
+    match job.remote {
+        Some(ref remote) => {
+            let remote_privs = user_info.lookup_privs(auth_id, &["remote", remote]);
+            remote_privs & PRIV_REMOTE_AUDIT != 0
+        }
+        None => {
+            let source_privs = user_info.lookup_privs(auth_id, &job.local_source_acl_path());
+            source_privs & PRIV_DATASTORE_AUDIT != 0
+        }
+    }
 }
 
-/// checks whether user can run the corresponding pull job
+/// checks whether user can run the corresponding sync job
 ///
-/// namespace creation/deletion ACL and backup group ownership checks happen in the pull code directly.
-/// remote side checks/filters remote datastore/namespace/group access.
+/// namespace creation/deletion ACL and backup group ownership checks happen in the pull and push
+/// code directly. remote side checks/filters remote datastore/namespace/group access, for local
+/// pull jobs the source groups are filtered by the pull code as well.
 pub fn check_sync_job_modify_access(
     user_info: &CachedUserInfo,
     auth_id: &Authid,
     job: &SyncJobConfig,
 ) -> bool {
+    if job.sync_direction() == SyncDirection::Push {
+        return check_push_job_modify_access(user_info, auth_id, job);
+    }
+
     let ns_anchor_privs = user_info.lookup_privs(auth_id, &job.acl_path());
     if ns_anchor_privs & PRIV_DATASTORE_BACKUP == 0 {
         return false;
@@ -63,8 +77,80 @@ pub fn check_sync_job_modify_access(
         return false;
     }
 
-    let remote_privs = user_info.lookup_privs(auth_id, &["remote", &job.remote, &job.remote_store]);
-    remote_privs & PRIV_REMOTE_READ != 0
+    if let Some(ref key) = job.encryption_key {
+        let key_privs = user_info.lookup_privs(auth_id, &["system", "sync-encryption-keys", key]);
+        if key_privs & PRIV_SYS_MODIFY == 0 {
+            return false;
+        }
+    }
+
+    match job.remote {
+        Some(ref remote) => {
+            let remote_privs =
+                user_info.lookup_privs(auth_id, &["remote", remote, &job.remote_store]);
+            remote_privs & PRIV_REMOTE_READ != 0
+        }
+        None => {
+            // without Datastore.Read, only groups owned by the job owner are pulled
+            let source_privs = user_info.lookup_privs(auth_id, &job.local_source_acl_path());
+            source_privs & (PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP) != 0
+        }
+    }
+}
+
+/// checks whether user can run the corresponding push job
+///
+/// the local side only gets read, the remote side gets written to (and pruned with
+/// remove-vanished). Without Datastore.Read, only groups owned by the job owner are pushed.
+fn check_push_job_modify_access(
+    user_info: &CachedUserInfo,
+    auth_id: &Authid,
+    job: &SyncJobConfig,
+) -> bool {
+    let ns_anchor_privs = user_info.lookup_privs(auth_id, &job.acl_path());
+    if ns_anchor_privs & (PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP) == 0 {
+        return false;
+    }
+
+    let correct_owner = match job.owner {
+        Some(ref owner) => {
+            owner == auth_id
+                || (owner.is_token() && !auth_id.is_token() && owner.user() == auth_id.user())
+        }
+        // default sync owner
+        None => auth_id == Authid::root_auth_id(),
+    };
+
+    // pushing as someone else would expose groups they own
+    if !correct_owner && ns_anchor_privs & PRIV_DATASTORE_MODIFY == 0 {
+        return false;
+    }
+
+    let remote = match job.remote {
+        Some(ref remote) => remote,
+        None => return false, // push jobs need a remote
+    };
+
+    let remote_privs = user_info.lookup_privs(auth_id, &["remote", remote, &job.remote_store]);
+    if let Some(true) = job.remove_vanished {
+        if remote_privs & PRIV_REMOTE_DATASTORE_PRUNE == 0 {
+            return false;
+        }
+    }
+    remote_privs & PRIV_REMOTE_DATASTORE_BACKUP != 0
+}
+
+/// Server-side encryption is only supported for pull jobs, with an existing key.
+fn check_encryption_key(job: &SyncJobConfig) -> Result<(), Error> {
+    if let Some(ref key) = job.encryption_key {
+        if job.sync_direction() == SyncDirection::Push {
+            param_bail!("encryption-key", "push sync jobs cannot encrypt snapshots");
+        }
+        if let Err(err) = crate::server::pull::load_sync_encryption_key(key) {
+            param_bail!("encryption-key", "{}", err);
+        }
+    }
+    Ok(())
 }
 
 #[api(
@@ -77,7 +163,7 @@ pub fn check_sync_job_modify_access(
         items: { type: SyncJobConfig },
     },
     access: {
-        description: "Limited to sync job entries where user has Datastore.Audit on target datastore, and Remote.Audit on source remote.",
+        description: "Limited to sync job entries where user has Datastore.Audit on target datastore, and Remote.Audit on source remote (or Datastore.Audit on the source datastore for local sync jobs).",
         permission: &Permission::Anybody,
     },
 )]
@@ -113,7 +199,7 @@ pub fn list_sync_jobs(
         },
     },
     access: {
-        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote. Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
+        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote (or Datastore.Read or Datastore.Backup on the source datastore for local sync jobs). Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
         permission: &Permission::Anybody,
     },
 )]
@@ -127,6 +213,11 @@ pub fn create_sync_job(
 
     let _lock = sync::lock_config()?;
 
+    if config.remote.is_none() && config.sync_direction() == SyncDirection::Push {
+        param_bail!("remote", "push sync jobs require a remote");
+    }
+    check_encryption_key(&config)?;
+
     if !check_sync_job_modify_access(&user_info, &auth_id, &config) {
         bail!("permission check failed");
     }
@@ -165,7 +256,7 @@ pub fn create_sync_job(
     },
     returns: { type: SyncJobConfig },
     access: {
-        description: "Limited to sync job entries where user has Datastore.Audit on target datastore, and Remote.Audit on source remote.",
+        description: "Limited to sync job entries where user has Datastore.Audit on target datastore, and Remote.Audit on source remote (or Datastore.Audit on the source datastore for local sync jobs).",
         permission: &Permission::Anybody,
     },
 )]
@@ -217,6 +308,12 @@ pub enum DeletableProperty {
     MaxDepth,
     /// Delete the transfer_last property,
     TransferLast,
+    /// Delete the sync_direction property,
+    SyncDirection,
+    /// Delete the remote property, to sync from a local datastore.
+    Remote,
+    /// Delete the encryption_key property,
+    EncryptionKey,
 }
 
 #[api(
@@ -246,7 +343,7 @@ pub enum DeletableProperty {
     },
     access: {
         permission: &Permission::Anybody,
-        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote. Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
+        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote (or Datastore.Read or Datastore.Backup on the source datastore for local sync jobs). Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
     },
 )]
 /// Update sync job config.
@@ -314,6 +411,15 @@ pub fn update_sync_job(
                 DeletableProperty::TransferLast => {
                     data.transfer_last = None;
                 }
+                DeletableProperty::SyncDirection => {
+                    data.sync_direction = None;
+                }
+                DeletableProperty::Remote => {
+                    data.remote = None;
+                }
+                DeletableProperty::EncryptionKey => {
+                    data.encryption_key = None;
+                }
             }
         }
     }
@@ -334,7 +440,7 @@ pub fn update_sync_job(
         data.ns = Some(ns);
     }
     if let Some(remote) = update.remote {
-        data.remote = remote;
+        data.remote = Some(remote);
     }
     if let Some(remote_store) = update.remote_store {
         data.remote_store = remote_store;
@@ -351,6 +457,12 @@ pub fn update_sync_job(
     if let Some(transfer_last) = update.transfer_last {
         data.transfer_last = Some(transfer_last);
     }
+    if let Some(sync_direction) = update.sync_direction {
+        data.sync_direction = Some(sync_direction);
+    }
+    if let Some(encryption_key) = update.encryption_key {
+        data.encryption_key = Some(encryption_key);
+    }
 
     if update.limit.rate_in.is_some() {
         data.limit.rate_in = update.limit.rate_in;
@@ -388,6 +500,11 @@ pub fn update_sync_job(
         }
     }
 
+    if data.remote.is_none() && data.sync_direction() == SyncDirection::Push {
+        param_bail!("remote", "push sync jobs require a remote");
+    }
+    check_encryption_key(&data)?;
+
     if !check_sync_job_modify_access(&user_info, &auth_id, &data) {
         bail!("permission check failed");
     }
@@ -418,7 +535,7 @@ pub fn update_sync_job(
     },
     access: {
         permission: &Permission::Anybody,
-        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote. Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
+        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote (or Datastore.Read or Datastore.Backup on the source datastore for local sync jobs). Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
     },
 )]
 /// Remove a sync job configuration
@@ -458,14 +575,30 @@ pub fn delete_sync_job(
     Ok(())
 }
 
+crate::audited_api_method!(
+    AUDITED_CREATE_SYNC_JOB = API_METHOD_CREATE_SYNC_JOB,
+    "POST",
+    "/config/sync"
+);
+crate::audited_api_method!(
+    AUDITED_UPDATE_SYNC_JOB = API_METHOD_UPDATE_SYNC_JOB,
+    "PUT",
+    "/config/sync/{id}"
+);
+crate::audited_api_method!(
+    AUDITED_DELETE_SYNC_JOB = API_METHOD_DELETE_SYNC_JOB,
+    "DELETE",
+    "/config/sync/{id}"
+);
+
 const ITEM_ROUTER: Router = Router::new()
     .get(&API_METHOD_READ_SYNC_JOB)
-    .put(&API_METHOD_UPDATE_SYNC_JOB)
-    .delete(&API_METHOD_DELETE_SYNC_JOB);
+    .put(&AUDITED_UPDATE_SYNC_JOB)
+    .delete(&AUDITED_DELETE_SYNC_JOB);
 
 pub const ROUTER: Router = Router::new()
     .get(&API_METHOD_LIST_SYNC_JOBS)
-    .post(&API_METHOD_CREATE_SYNC_JOB)
+    .post(&AUDITED_CREATE_SYNC_JOB)
     .match_all("id", &ITEM_ROUTER);
 
 #[test]
@@ -489,6 +622,8 @@ acl:1:/datastore/localstore2:write@pbs:DatastorePowerUser
 acl:1:/datastore/localstore3:write@pbs:DatastoreAdmin
 acl:1:/remote/remote1:read@pbs,write@pbs:RemoteAudit
 acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
+acl:1:/remote/remote2/remotestore1:write@pbs:RemoteSyncPushOperator
+acl:1:/datastore/localstore4:write@pbs:DatastoreReader
 "###,
     )
     .expect("test acl.cfg is not parsable");
@@ -503,7 +638,7 @@ acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
 
     let mut job = SyncJobConfig {
         id: "regular".to_string(),
-        remote: "remote0".to_string(),
+        remote: Some("remote0".to_string()),
         remote_store: "remotestore1".to_string(),
         remote_ns: None,
         store: "localstore0".to_string(),
@@ -516,6 +651,8 @@ acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
         schedule: None,
         limit: pbs_api_types::RateLimitConfig::default(), // no limit
         transfer_last: None,
+        sync_direction: None,
+        encryption_key: None,
     };
 
     // should work without ACLs
@@ -538,11 +675,11 @@ acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
     assert!(!check_sync_job_read_access(&user_info, &read_auth_id, &job));
 
     // reading without proper read permissions on local end must fail
-    job.remote = "remote1".to_string();
+    job.remote = Some("remote1".to_string());
     assert!(!check_sync_job_read_access(&user_info, &read_auth_id, &job));
 
     // reading without proper read permissions on remote end must fail
-    job.remote = "remote0".to_string();
+    job.remote = Some("remote0".to_string());
     job.store = "localstore1".to_string();
     assert!(!check_sync_job_read_access(&user_info, &read_auth_id, &job));
 
@@ -555,10 +692,10 @@ acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
     ));
 
     // writing without proper write permissions on local end must fail
-    job.remote = "remote1".to_string();
+    job.remote = Some("remote1".to_string());
 
     // writing without proper write permissions on remote end must fail
-    job.remote = "remote0".to_string();
+    job.remote = Some("remote0".to_string());
     job.store = "localstore1".to_string();
     assert!(!check_sync_job_modify_access(
         &user_info,
@@ -567,7 +704,7 @@ acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
     ));
 
     // reset remote to one where users have access
-    job.remote = "remote1".to_string();
+    job.remote = Some("remote1".to_string());
 
     // user with read permission can only read, but not modify/run
     assert!(check_sync_job_read_access(&user_info, &read_auth_id, &job));
@@ -598,6 +735,16 @@ acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
         &job
     ));
 
+    // encrypting with a server-side key requires privileges on the key
+    job.encryption_key = Some("offsite".to_string());
+    assert!(!check_sync_job_modify_access(
+        &user_info,
+        &write_auth_id,
+        &job
+    ));
+    assert!(check_sync_job_modify_access(&user_info, root_auth_id, &job));
+    job.encryption_key = None;
+
     // but can't modify/run with deletion
     job.remove_vanished = Some(true);
     assert!(!check_sync_job_modify_access(
@@ -645,5 +792,77 @@ acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
         &job
     ));
 
+    // pushing requires Remote.DatastoreBackup on the remote end
+    job.sync_direction = Some(SyncDirection::Push);
+    job.store = "localstore1".to_string();
+    job.owner = Some(write_auth_id.clone());
+    job.remove_vanished = None;
+    assert!(!check_sync_job_modify_access(
+        &user_info,
+        &write_auth_id,
+        &job
+    ));
+    job.remote = Some("remote2".to_string());
+    assert!(check_sync_job_modify_access(
+        &user_info,
+        &write_auth_id,
+        &job
+    ));
+
+    // and Remote.DatastorePrune for removing vanished snapshots
+    job.remove_vanished = Some(true);
+    assert!(check_sync_job_modify_access(
+        &user_info,
+        &write_auth_id,
+        &job
+    ));
+    job.remote = Some("remote1".to_string());
+    assert!(!check_sync_job_modify_access(
+        &user_info,
+        &write_auth_id,
+        &job
+    ));
+
+    // read-only users can't push either
+    let read_auth_id: Authid = "read@pbs".parse()?;
+    job.remote = Some("remote2".to_string());
+    job.owner = Some(read_auth_id.clone());
+    assert!(!check_sync_job_modify_access(
+        &user_info,
+        &read_auth_id,
+        &job
+    ));
+
+    // local push jobs are not possible
+    job.remote = None;
+    job.owner = Some(write_auth_id.clone());
+    job.remove_vanished = None;
+    assert!(!check_sync_job_modify_access(
+        &user_info,
+        &write_auth_id,
+        &job
+    ));
+
+    // local pull jobs need access to the source datastore instead of a remote
+    job.sync_direction = None;
+    job.remote_store = "localstore0".to_string();
+    assert!(!check_sync_job_read_access(
+        &user_info,
+        &write_auth_id,
+        &job
+    ));
+    assert!(!check_sync_job_modify_access(
+        &user_info,
+        &write_auth_id,
+        &job
+    ));
+    job.remote_store = "localstore4".to_string();
+    assert!(check_sync_job_read_access(&user_info, &write_auth_id, &job));
+    assert!(check_sync_job_modify_access(
+        &user_info,
+        &write_auth_id,
+        &job
+    ));
+
     Ok(())
 }
@@ -138,6 +138,12 @@ pub enum DeletableProperty {
     MaxDepth,
     /// Delete the 'ns' property
     Ns,
+    /// Delete the 'allocation' property
+    Allocation,
+    /// Delete the 'retention' property
+    Retention,
+    /// Delete the 'incremental' property
+    Incremental,
 }
 
 #[api(
@@ -217,6 +223,15 @@ pub fn update_tape_backup_job(
                 DeletableProperty::Ns => {
                     data.setup.ns = None;
                 }
+                DeletableProperty::Allocation => {
+                    data.setup.allocation = None;
+                }
+                DeletableProperty::Retention => {
+                    data.setup.retention = None;
+                }
+                DeletableProperty::Incremental => {
+                    data.setup.incremental = None;
+                }
             }
         }
     }
@@ -252,6 +267,15 @@ pub fn update_tape_backup_job(
     if update.setup.max_depth.is_some() {
         data.setup.max_depth = update.setup.max_depth;
     }
+    if update.setup.allocation.is_some() {
+        data.setup.allocation = update.setup.allocation;
+    }
+    if update.setup.retention.is_some() {
+        data.setup.retention = update.setup.retention;
+    }
+    if update.setup.incremental.is_some() {
+        data.setup.incremental = update.setup.incremental;
+    }
 
     let schedule_changed = data.schedule != update.schedule;
     if update.schedule.is_some() {
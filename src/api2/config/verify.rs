@@ -8,7 +8,8 @@ use proxmox_schema::{api, param_bail};
 
 use pbs_api_types::{
     Authid, VerificationJobConfig, VerificationJobConfigUpdater, JOB_ID_SCHEMA,
-    PRIV_DATASTORE_AUDIT, PRIV_DATASTORE_VERIFY, PROXMOX_CONFIG_DIGEST_SCHEMA,
+    PRIV_DATASTORE_AUDIT, PRIV_DATASTORE_VERIFY, PRIV_REMOTE_READ, PRIV_TAPE_READ,
+    PROXMOX_CONFIG_DIGEST_SCHEMA,
 };
 use pbs_config::verify;
 
@@ -56,6 +57,58 @@ pub fn list_verification_jobs(
     Ok(list)
 }
 
+/// Check the chunk repair settings of a job, and the privileges to read from them.
+fn check_repair_sources(
+    user_info: &CachedUserInfo,
+    auth_id: &Authid,
+    job: &VerificationJobConfig,
+) -> Result<(), Error> {
+    match (&job.repair_remote, &job.repair_remote_store) {
+        (Some(remote), Some(remote_store)) => {
+            let (remote_config, _digest) = pbs_config::remote::config()?;
+            if remote_config.sections.get(remote).is_none() {
+                param_bail!("repair-remote", "remote '{}' does not exist.", remote);
+            }
+            let acl_path = match job.repair_remote_ns {
+                Some(ref ns) if !ns.is_root() => {
+                    let mut path = vec!["remote", remote, remote_store];
+                    path.extend(ns.components());
+                    path
+                }
+                _ => vec!["remote", remote, remote_store],
+            };
+            user_info.check_privs(auth_id, &acl_path, PRIV_REMOTE_READ, false)?;
+        }
+        (None, None) => {
+            if job.repair_remote_ns.is_some() {
+                param_bail!(
+                    "repair-remote-ns",
+                    "repair remote namespace set without remote"
+                );
+            }
+        }
+        (Some(_), None) => {
+            param_bail!("repair-remote-store", "repair remote needs a datastore");
+        }
+        (None, Some(_)) => {
+            param_bail!(
+                "repair-remote",
+                "repair remote datastore set without remote"
+            );
+        }
+    }
+
+    if let Some(ref drive) = job.repair_drive {
+        let (drive_config, _digest) = pbs_config::drive::config()?;
+        if drive_config.sections.get(drive).is_none() {
+            param_bail!("repair-drive", "drive '{}' does not exist.", drive);
+        }
+        user_info.check_privs(auth_id, &["tape", "drive", drive], PRIV_TAPE_READ, false)?;
+    }
+
+    Ok(())
+}
+
 #[api(
     protected: true,
     input: {
@@ -81,6 +134,8 @@ pub fn create_verification_job(
 
     user_info.check_privs(&auth_id, &config.acl_path(), PRIV_DATASTORE_VERIFY, false)?;
 
+    check_repair_sources(&user_info, &auth_id, &config)?;
+
     let _lock = verify::lock_config()?;
 
     let (mut section_config, _digest) = verify::config()?;
@@ -149,6 +204,16 @@ pub enum DeletableProperty {
     Ns,
     /// Delete max-depth property, defaulting to full recursion again
     MaxDepth,
+    /// Delete the sample period property (verify all chunks on every run).
+    SamplePeriod,
+    /// Delete the repair remote property.
+    RepairRemote,
+    /// Delete the repair remote datastore property.
+    RepairRemoteStore,
+    /// Delete the repair remote namespace property.
+    RepairRemoteNs,
+    /// Delete the repair drive property.
+    RepairDrive,
 }
 
 #[api(
@@ -204,6 +269,7 @@ pub fn update_verification_job(
     }
 
     let mut data: VerificationJobConfig = config.lookup("verification", &id)?;
+    let old_scope = (data.store.clone(), data.ns.clone(), data.max_depth);
 
     // check existing store and NS
     user_info.check_privs(&auth_id, &data.acl_path(), PRIV_DATASTORE_VERIFY, true)?;
@@ -229,6 +295,21 @@ pub fn update_verification_job(
                 DeletableProperty::MaxDepth => {
                     data.max_depth = None;
                 }
+                DeletableProperty::SamplePeriod => {
+                    data.sample_period = None;
+                }
+                DeletableProperty::RepairRemote => {
+                    data.repair_remote = None;
+                }
+                DeletableProperty::RepairRemoteStore => {
+                    data.repair_remote_store = None;
+                }
+                DeletableProperty::RepairRemoteNs => {
+                    data.repair_remote_ns = None;
+                }
+                DeletableProperty::RepairDrive => {
+                    data.repair_drive = None;
+                }
             }
         }
     }
@@ -267,13 +348,35 @@ pub fn update_verification_job(
         }
     }
 
+    if update.sample_period.is_some() {
+        data.sample_period = update.sample_period;
+    }
+    if update.repair_remote.is_some() {
+        data.repair_remote = update.repair_remote;
+    }
+    if update.repair_remote_store.is_some() {
+        data.repair_remote_store = update.repair_remote_store;
+    }
+    if let Some(ns) = update.repair_remote_ns {
+        data.repair_remote_ns = if ns.is_root() { None } else { Some(ns) };
+    }
+    if update.repair_drive.is_some() {
+        data.repair_drive = update.repair_drive;
+    }
+
     // check new store and NS
     user_info.check_privs(&auth_id, &data.acl_path(), PRIV_DATASTORE_VERIFY, true)?;
+    check_repair_sources(&user_info, &auth_id, &data)?;
 
     config.set_data(&id, "verification", &data)?;
 
     verify::save_config(&config)?;
 
+    // the verified chunk buckets refer to the chunks reachable from the old namespace
+    if old_scope != (data.store.clone(), data.ns.clone(), data.max_depth) {
+        crate::server::remove_chunk_verify_state(&old_scope.0, &id)?;
+    }
+
     if schedule_changed {
         crate::server::jobstate::update_job_last_run_time("verificationjob", &id)?;
     }
@@ -330,6 +433,7 @@ pub fn delete_verification_job(
     verify::save_config(&config)?;
 
     crate::server::jobstate::remove_state_file("verificationjob", &id)?;
+    crate::server::remove_chunk_verify_state(&job.store, &id)?;
 
     Ok(())
 }
@@ -0,0 +1,120 @@
+use anyhow::Error;
+use serde_json::Value;
+
+use proxmox_router::{Permission, Router, RpcEnvironment};
+use proxmox_schema::api;
+
+use pbs_api_types::{AuditLogEntry, AuditResult, Authid, NODE_SCHEMA, PRIV_SYS_AUDIT};
+
+use crate::server::audit::read_audit_log;
+
+#[api(
+    input: {
+        properties: {
+            node: {
+                schema: NODE_SCHEMA,
+            },
+            since: {
+                type: Integer,
+                description: "Only list entries since this UNIX epoch.",
+                optional: true,
+            },
+            until: {
+                type: Integer,
+                description: "Only list entries until this UNIX epoch.",
+                optional: true,
+            },
+            "auth-id": {
+                type: Authid,
+                optional: true,
+            },
+            method: {
+                type: String,
+                description: "Only list calls with this HTTP method.",
+                optional: true,
+                max_length: 16,
+            },
+            path: {
+                type: String,
+                description: "Only list calls with an API path starting with this prefix.",
+                optional: true,
+                max_length: 1024,
+            },
+            result: {
+                type: AuditResult,
+                optional: true,
+            },
+            start: {
+                type: u64,
+                description: "List entries beginning from this offset.",
+                default: 0,
+                optional: true,
+            },
+            limit: {
+                type: u64,
+                description: "Only list this amount of entries. (0 means no limit)",
+                default: 50,
+                optional: true,
+            },
+        },
+    },
+    returns: {
+        description: "A list of audit log entries, newest first.",
+        type: Array,
+        items: { type: AuditLogEntry },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "log"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read the audit log of privileged API calls.
+#[allow(clippy::too_many_arguments)]
+pub fn read_log(
+    since: Option<i64>,
+    until: Option<i64>,
+    auth_id: Option<Authid>,
+    method: Option<String>,
+    path: Option<String>,
+    result: Option<AuditResult>,
+    start: u64,
+    limit: u64,
+    _param: Value,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<AuditLogEntry>, Error> {
+    let limit = if limit == 0 {
+        usize::MAX
+    } else {
+        limit as usize
+    };
+
+    let filter = |entry: &AuditLogEntry| {
+        if matches!(until, Some(until) if entry.time > until) {
+            return false;
+        }
+        if auth_id.is_some() && entry.auth_id != auth_id {
+            return false;
+        }
+        if let Some(ref method) = method {
+            if !entry.method.eq_ignore_ascii_case(method) {
+                return false;
+            }
+        }
+        if let Some(ref path) = path {
+            if !entry.path.starts_with(path.as_str()) {
+                return false;
+            }
+        }
+        if matches!(result, Some(result) if entry.result != result) {
+            return false;
+        }
+        true
+    };
+
+    let (total, list) = read_audit_log(since, filter, start as usize, limit)?;
+
+    rpcenv["total"] = Value::from(total);
+
+    Ok(list)
+}
+
+pub const ROUTER: Router = Router::new().get(&API_METHOD_READ_LOG);
@@ -67,6 +67,10 @@ pub enum DeletableProperty {
     Description,
     /// Delete the task-log-max-days property
     TaskLogMaxDays,
+    /// Delete the audit-log-syslog property
+    AuditLogSyslog,
+    /// Delete the audit-log-metrics property
+    AuditLogMetrics,
 }
 
 #[api(
@@ -155,6 +159,12 @@ pub fn update_node_config(
                 DeletableProperty::TaskLogMaxDays => {
                     config.task_log_max_days = None;
                 }
+                DeletableProperty::AuditLogSyslog => {
+                    config.audit_log_syslog = None;
+                }
+                DeletableProperty::AuditLogMetrics => {
+                    config.audit_log_metrics = None;
+                }
             }
         }
     }
@@ -198,6 +208,12 @@ pub fn update_node_config(
     if update.task_log_max_days.is_some() {
         config.task_log_max_days = update.task_log_max_days;
     }
+    if update.audit_log_syslog.is_some() {
+        config.audit_log_syslog = update.audit_log_syslog;
+    }
+    if update.audit_log_metrics.is_some() {
+        config.audit_log_metrics = update.audit_log_metrics;
+    }
 
     crate::config::node::save_config(&config)?;
 
@@ -30,6 +30,7 @@ use crate::auth::{private_auth_keyring, public_auth_keyring};
 use crate::tools;
 
 pub mod apt;
+pub mod audit_log;
 pub mod certificates;
 pub mod config;
 pub mod disks;
@@ -323,6 +324,7 @@ fn list_nodes() -> Result<Value, Error> {
 
 pub const SUBDIRS: SubdirMap = &[
     ("apt", &apt::ROUTER),
+    ("audit-log", &audit_log::ROUTER),
     ("certificates", &certificates::ROUTER),
     ("config", &config::ROUTER),
     ("disks", &disks::ROUTER),
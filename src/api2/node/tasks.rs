@@ -78,12 +78,17 @@ fn check_job_privs(auth_id: &Authid, user_info: &CachedUserInfo, upid: &UPID) ->
                 if let (Some(remote), Some(remote_store), Some(local_store)) =
                     (remote, remote_store, local_store)
                 {
+                    let remote = match remote.as_str() {
+                        "-" => None, // local sync job
+                        remote => Some(remote),
+                    };
                     return check_pull_privs(
                         auth_id,
                         local_store.as_str(),
                         local_ns,
-                        remote.as_str(),
+                        remote,
                         remote_store.as_str(),
+                        None,
                         false,
                     );
                 }
@@ -7,23 +7,29 @@ use proxmox_schema::api;
 use proxmox_sys::task_log;
 
 use pbs_api_types::{
-    Authid, BackupNamespace, GroupFilter, RateLimitConfig, SyncJobConfig, DATASTORE_SCHEMA,
-    GROUP_FILTER_LIST_SCHEMA, NS_MAX_DEPTH_REDUCED_SCHEMA, PRIV_DATASTORE_BACKUP,
-    PRIV_DATASTORE_PRUNE, PRIV_REMOTE_READ, REMOTE_ID_SCHEMA, REMOVE_VANISHED_BACKUPS_SCHEMA,
-    TRANSFER_LAST_SCHEMA,
+    Authid, BackupNamespace, GroupFilter, RateLimitConfig, SyncDirection, SyncJobConfig,
+    DATASTORE_SCHEMA, GROUP_FILTER_LIST_SCHEMA, NS_MAX_DEPTH_REDUCED_SCHEMA, PRIV_DATASTORE_BACKUP,
+    PRIV_DATASTORE_PRUNE, PRIV_DATASTORE_READ, PRIV_REMOTE_READ, PRIV_SYS_MODIFY, REMOTE_ID_SCHEMA,
+    REMOVE_VANISHED_BACKUPS_SCHEMA, SYNC_ENCRYPTION_KEY_SCHEMA, TRANSFER_LAST_SCHEMA,
 };
 use pbs_config::CachedUserInfo;
 use proxmox_rest_server::WorkerTask;
 
 use crate::server::jobstate::Job;
 use crate::server::pull::{pull_store, PullParameters};
+use crate::server::push::{push_store, PushParameters};
 
+/// Check if the provided user is allowed to pull from the source and write to the local target.
+///
+/// Without `remote`, `remote_store` and `remote_ns` refer to a local datastore, from which
+/// groups are pulled depending on the user's privileges there.
 pub fn check_pull_privs(
     auth_id: &Authid,
     store: &str,
     ns: Option<&str>,
-    remote: &str,
+    remote: Option<&str>,
     remote_store: &str,
+    remote_ns: Option<&str>,
     delete: bool,
 ) -> Result<(), Error> {
     let user_info = CachedUserInfo::new()?;
@@ -39,12 +45,26 @@ pub fn check_pull_privs(
         PRIV_DATASTORE_BACKUP,
         false,
     )?;
-    user_info.check_privs(
-        auth_id,
-        &["remote", remote, remote_store],
-        PRIV_REMOTE_READ,
-        false,
-    )?;
+    match remote {
+        Some(remote) => user_info.check_privs(
+            auth_id,
+            &["remote", remote, remote_store],
+            PRIV_REMOTE_READ,
+            false,
+        )?,
+        None => {
+            let source_acl_path = match remote_ns {
+                Some(ns) => vec!["datastore", remote_store, ns],
+                None => vec!["datastore", remote_store],
+            };
+            user_info.check_privs(
+                auth_id,
+                &source_acl_path,
+                PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP,
+                true,
+            )?;
+        }
+    }
 
     if delete {
         user_info.check_privs(
@@ -58,6 +78,21 @@ pub fn check_pull_privs(
     Ok(())
 }
 
+/// Check if the provided user is allowed to encrypt pulled snapshots with the server-side
+/// `encryption_key`.
+pub fn check_sync_encryption_key_privs(
+    user_info: &CachedUserInfo,
+    auth_id: &Authid,
+    encryption_key: &str,
+) -> Result<(), Error> {
+    user_info.check_privs(
+        auth_id,
+        &["system", "sync-encryption-keys", encryption_key],
+        PRIV_SYS_MODIFY,
+        true,
+    )
+}
+
 impl TryFrom<&SyncJobConfig> for PullParameters {
     type Error = Error;
 
@@ -65,7 +100,7 @@ impl TryFrom<&SyncJobConfig> for PullParameters {
         PullParameters::new(
             &sync_job.store,
             sync_job.ns.clone().unwrap_or_default(),
-            &sync_job.remote,
+            sync_job.remote.as_deref(),
             &sync_job.remote_store,
             sync_job.remote_ns.clone().unwrap_or_default(),
             sync_job
@@ -78,6 +113,7 @@ impl TryFrom<&SyncJobConfig> for PullParameters {
             sync_job.group_filter.clone(),
             sync_job.limit.clone(),
             sync_job.transfer_last,
+            sync_job.encryption_key.as_deref(),
         )
     }
 }
@@ -91,7 +127,7 @@ pub fn do_sync_job(
 ) -> Result<String, Error> {
     let job_id = format!(
         "{}:{}:{}:{}:{}",
-        sync_job.remote,
+        sync_job.remote.as_deref().unwrap_or("-"),
         sync_job.remote_store,
         sync_job.store,
         sync_job.ns.clone().unwrap_or_default(),
@@ -113,22 +149,49 @@ pub fn do_sync_job(
             let sync_job2 = sync_job.clone();
 
             let worker_future = async move {
-                let pull_params = PullParameters::try_from(&sync_job)?;
-                let client = pull_params.client().await?;
-
                 task_log!(worker, "Starting datastore sync job '{}'", job_id);
                 if let Some(event_str) = schedule {
                     task_log!(worker, "task triggered by schedule '{}'", event_str);
                 }
-                task_log!(
-                    worker,
-                    "sync datastore '{}' from '{}/{}'",
-                    sync_job.store,
-                    sync_job.remote,
-                    sync_job.remote_store,
-                );
 
-                pull_store(&worker, &client, pull_params).await?;
+                match sync_job.sync_direction() {
+                    SyncDirection::Pull => {
+                        let pull_params = PullParameters::try_from(&sync_job)?;
+                        let client = pull_params.client().await?;
+
+                        match sync_job.remote {
+                            Some(ref remote) => task_log!(
+                                worker,
+                                "sync datastore '{}' from '{}/{}'",
+                                sync_job.store,
+                                remote,
+                                sync_job.remote_store,
+                            ),
+                            None => task_log!(
+                                worker,
+                                "sync datastore '{}' from local datastore '{}'",
+                                sync_job.store,
+                                sync_job.remote_store,
+                            ),
+                        }
+
+                        pull_store(&worker, &client, pull_params).await?;
+                    }
+                    SyncDirection::Push => {
+                        let push_params = PushParameters::try_from(&sync_job)?;
+                        let client = push_params.client().await?;
+
+                        task_log!(
+                            worker,
+                            "sync datastore '{}' to '{}/{}'",
+                            sync_job.store,
+                            sync_job.remote.as_deref().unwrap_or_default(),
+                            sync_job.remote_store,
+                        );
+
+                        push_store(&worker, &client, push_params).await?;
+                    }
+                }
 
                 task_log!(worker, "sync job '{}' end", &job_id);
 
@@ -153,12 +216,10 @@ pub fn do_sync_job(
                 }
             }
 
-            if let Some(email) = email {
-                if let Err(err) =
-                    crate::server::send_sync_status(&email, notify, &sync_job2, &result)
-                {
-                    eprintln!("send sync notification failed: {}", err);
-                }
+            if let Err(err) =
+                crate::server::send_sync_status(email.as_deref(), notify, &sync_job2, &result)
+            {
+                eprintln!("send sync notification failed: {}", err);
             }
 
             result
@@ -180,6 +241,7 @@ pub fn do_sync_job(
             },
             remote: {
                 schema: REMOTE_ID_SCHEMA,
+                optional: true,
             },
             "remote-store": {
                 schema: DATASTORE_SCHEMA,
@@ -208,23 +270,30 @@ pub fn do_sync_job(
                 schema: TRANSFER_LAST_SCHEMA,
                 optional: true,
             },
+            "encryption-key": {
+                schema: SYNC_ENCRYPTION_KEY_SCHEMA,
+                optional: true,
+            },
         },
     },
     access: {
         // Note: used parameters are no uri parameters, so we need to test inside function body
         description: r###"The user needs Datastore.Backup privilege on '/datastore/{store}',
 and needs to own the backup group. Remote.Read is required on '/remote/{remote}/{remote-store}'.
+Without remote, Datastore.Read or Datastore.Backup is required on '/datastore/{remote-store}'
+instead, with the latter only groups owned by the user are pulled.
 The delete flag additionally requires the Datastore.Prune privilege on '/datastore/{store}'.
+Using an encryption key requires Sys.Modify on '/system/sync-encryption-keys/{encryption-key}'.
 "###,
         permission: &Permission::Anybody,
     },
 )]
-/// Sync store from other repository
+/// Sync store from other repository, or from a local datastore if no remote is given
 #[allow(clippy::too_many_arguments)]
 async fn pull(
     store: String,
     ns: Option<BackupNamespace>,
-    remote: String,
+    remote: Option<String>,
     remote_store: String,
     remote_ns: Option<BackupNamespace>,
     remove_vanished: Option<bool>,
@@ -232,6 +301,7 @@ async fn pull(
     group_filter: Option<Vec<GroupFilter>>,
     limit: RateLimitConfig,
     transfer_last: Option<usize>,
+    encryption_key: Option<String>,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<String, Error> {
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
@@ -244,27 +314,39 @@ async fn pull(
         Some(ns.to_string())
     };
 
+    let remote_ns = remote_ns.unwrap_or_default();
+    let remote_ns_str = if remote_ns.is_root() {
+        None
+    } else {
+        Some(remote_ns.to_string())
+    };
+
     check_pull_privs(
         &auth_id,
         &store,
         ns_str.as_deref(),
-        &remote,
+        remote.as_deref(),
         &remote_store,
+        remote_ns_str.as_deref(),
         delete,
     )?;
+    if let Some(ref encryption_key) = encryption_key {
+        check_sync_encryption_key_privs(&CachedUserInfo::new()?, &auth_id, encryption_key)?;
+    }
 
     let pull_params = PullParameters::new(
         &store,
         ns,
-        &remote,
+        remote.as_deref(),
         &remote_store,
-        remote_ns.unwrap_or_default(),
+        remote_ns,
         auth_id.clone(),
         remove_vanished,
         max_depth,
         group_filter,
         limit,
         transfer_last,
+        encryption_key.as_deref(),
     )?;
     let client = pull_params.client().await?;
 
@@ -276,13 +358,21 @@ async fn pull(
         auth_id.to_string(),
         true,
         move |worker| async move {
-            task_log!(
-                worker,
-                "pull datastore '{}' from '{}/{}'",
-                store,
-                remote,
-                remote_store,
-            );
+            match remote {
+                Some(remote) => task_log!(
+                    worker,
+                    "pull datastore '{}' from '{}/{}'",
+                    store,
+                    remote,
+                    remote_store,
+                ),
+                None => task_log!(
+                    worker,
+                    "pull datastore '{}' from local datastore '{}'",
+                    store,
+                    remote_store,
+                ),
+            }
 
             let pull_future = pull_store(&worker, &client, pull_params);
             (select! {
@@ -0,0 +1,512 @@
+//! Metrics in the Prometheus/OpenMetrics text exposition format
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use anyhow::Error;
+use futures::FutureExt;
+use hyper::http::request::Parts;
+use hyper::{header, Body, Response, StatusCode};
+use serde_json::Value;
+
+use proxmox_router::{ApiHandler, ApiMethod, ApiResponseFuture, Permission, RpcEnvironment};
+use proxmox_schema::ObjectSchema;
+
+use pbs_api_types::{
+    Authid, Operation, PruneJobConfig, RRDMode, RRDTimeFrame, SyncJobConfig, TapeBackupJobConfig,
+    VerificationJobConfig, PRIV_DATASTORE_AUDIT, PRIV_SYS_AUDIT, PRIV_TAPE_AUDIT,
+};
+use pbs_config::CachedUserInfo;
+use pbs_datastore::DataStore;
+use proxmox_rest_server::{TaskListInfoIterator, TaskState};
+
+use crate::rrd_cache::extract_rrd_data;
+use crate::server::jobstate::JobState;
+use crate::traffic_control_cache::TRAFFIC_CONTROL_CACHE;
+
+/// Tasks which finished within this many seconds are included in the task counts.
+const TASK_COUNT_TIMEFRAME: i64 = 24 * 3600;
+
+/// A metric family with all of its samples.
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    ty: &'static str,
+    samples: Vec<(Vec<(&'static str, String)>, f64)>,
+}
+
+impl Metric {
+    fn gauge(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            ty: "gauge",
+            samples: Vec::new(),
+        }
+    }
+
+    fn counter(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            ty: "counter",
+            samples: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, labels: &[(&'static str, &str)], value: f64) {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect();
+        self.samples.push((labels, value));
+    }
+
+    /// Append the metric in the text exposition format, metrics without samples are skipped.
+    fn encode(&self, output: &mut String) {
+        if self.samples.is_empty() {
+            return;
+        }
+
+        let _ = writeln!(output, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(output, "# TYPE {} {}", self.name, self.ty);
+
+        for (labels, value) in &self.samples {
+            output.push_str(self.name);
+            if !labels.is_empty() {
+                let labels: Vec<String> = labels
+                    .iter()
+                    .map(|(name, value)| format!("{name}=\"{}\"", escape_label_value(value)))
+                    .collect();
+                let _ = write!(output, "{{{}}}", labels.join(","));
+            }
+            let _ = writeln!(output, " {}", format_value(*value));
+        }
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        let sign = if value > 0.0 { "+" } else { "-" };
+        format!("{sign}Inf")
+    } else {
+        value.to_string()
+    }
+}
+
+/// The most recent value of an RRD metric.
+fn rrd_last_value(basedir: &str, name: &str) -> Option<f64> {
+    match extract_rrd_data(basedir, name, RRDTimeFrame::Hour, RRDMode::Average) {
+        Ok(Some(entry)) => entry.data.iter().rev().find_map(|value| *value),
+        Ok(None) => None,
+        Err(err) => {
+            log::warn!("could not read RRD data {basedir}/{name} - {err}");
+            None
+        }
+    }
+}
+
+fn host_metrics() -> Vec<Metric> {
+    let list = [
+        ("pbs_host_cpu_usage_ratio", "CPU usage of the host.", "cpu"),
+        ("pbs_host_iowait_ratio", "IO wait of the host.", "iowait"),
+        ("pbs_host_load1", "Load average (1 minute).", "loadavg"),
+        ("pbs_host_memory_total_bytes", "Total memory.", "memtotal"),
+        ("pbs_host_memory_used_bytes", "Used memory.", "memused"),
+        (
+            "pbs_host_swap_total_bytes",
+            "Total swap space.",
+            "swaptotal",
+        ),
+        ("pbs_host_swap_used_bytes", "Used swap space.", "swapused"),
+        (
+            "pbs_host_network_receive_bytes_per_second",
+            "Incoming network traffic.",
+            "netin",
+        ),
+        (
+            "pbs_host_network_transmit_bytes_per_second",
+            "Outgoing network traffic.",
+            "netout",
+        ),
+        (
+            "pbs_host_disk_total_bytes",
+            "Size of the root file system.",
+            "total",
+        ),
+        (
+            "pbs_host_disk_used_bytes",
+            "Used space on the root file system.",
+            "used",
+        ),
+    ];
+
+    list.into_iter()
+        .map(|(name, help, rrd_name)| {
+            let mut metric = Metric::gauge(name, help);
+            if let Some(value) = rrd_last_value("host", rrd_name) {
+                metric.add(&[], value);
+            }
+            metric
+        })
+        .collect()
+}
+
+async fn datastore_metrics(stores: &[String]) -> Vec<Metric> {
+    let mut total = Metric::gauge("pbs_datastore_total_bytes", "Size of the datastore.");
+    let mut used = Metric::gauge("pbs_datastore_used_bytes", "Used space of the datastore.");
+    let mut avail = Metric::gauge(
+        "pbs_datastore_available_bytes",
+        "Available space of the datastore.",
+    );
+    let mut gc_index_files = Metric::gauge(
+        "pbs_datastore_gc_index_files",
+        "Index files found by the last garbage collection.",
+    );
+    let mut gc_disk_bytes = Metric::gauge(
+        "pbs_datastore_gc_disk_bytes",
+        "Bytes used by chunks after the last garbage collection.",
+    );
+    let mut gc_disk_chunks = Metric::gauge(
+        "pbs_datastore_gc_disk_chunks",
+        "Chunks on disk after the last garbage collection.",
+    );
+    let mut gc_removed_bytes = Metric::gauge(
+        "pbs_datastore_gc_removed_bytes",
+        "Bytes removed by the last garbage collection.",
+    );
+    let mut gc_pending_bytes = Metric::gauge(
+        "pbs_datastore_gc_pending_bytes",
+        "Bytes pending removal after the last garbage collection.",
+    );
+    let mut gc_bad_chunks = Metric::gauge(
+        "pbs_datastore_gc_bad_chunks",
+        "Bad chunks still present after the last garbage collection.",
+    );
+
+    for store in stores {
+        let datastore = match DataStore::lookup_datastore(store, Some(Operation::Read)) {
+            Ok(datastore) => datastore,
+            Err(_) => continue,
+        };
+        let labels = [("datastore", store.as_str())];
+
+        match crate::tools::fs::fs_info(datastore.base_path()).await {
+            Ok(status) => {
+                total.add(&labels, status.total as f64);
+                used.add(&labels, status.used as f64);
+                avail.add(&labels, status.available as f64);
+            }
+            Err(err) => log::warn!("could not get usage of datastore '{store}' - {err}"),
+        }
+
+        let gc_status = datastore.last_gc_status();
+        if gc_status.upid.is_some() {
+            gc_index_files.add(&labels, gc_status.index_file_count as f64);
+            gc_disk_bytes.add(&labels, gc_status.disk_bytes as f64);
+            gc_disk_chunks.add(&labels, gc_status.disk_chunks as f64);
+            gc_removed_bytes.add(&labels, gc_status.removed_bytes as f64);
+            gc_pending_bytes.add(&labels, gc_status.pending_bytes as f64);
+            gc_bad_chunks.add(&labels, gc_status.still_bad as f64);
+        }
+    }
+
+    vec![
+        total,
+        used,
+        avail,
+        gc_index_files,
+        gc_disk_bytes,
+        gc_disk_chunks,
+        gc_removed_bytes,
+        gc_pending_bytes,
+        gc_bad_chunks,
+    ]
+}
+
+/// Returns (job type, job id, datastore) of all jobs the user may see.
+fn visible_jobs(
+    auth_id: &Authid,
+    user_info: &CachedUserInfo,
+    stores: &[String],
+) -> Result<Vec<(&'static str, String, String)>, Error> {
+    let mut jobs = Vec::new();
+
+    for store in stores {
+        jobs.push(("garbage_collection", store.clone(), store.clone()));
+    }
+
+    let (config, _digest) = pbs_config::sync::config()?;
+    let list: Vec<SyncJobConfig> = config.convert_to_typed_array("sync")?;
+    jobs.extend(list.into_iter().map(|job| ("syncjob", job.id, job.store)));
+
+    let (config, _digest) = pbs_config::verify::config()?;
+    let list: Vec<VerificationJobConfig> = config.convert_to_typed_array("verification")?;
+    jobs.extend(
+        list.into_iter()
+            .map(|job| ("verificationjob", job.id, job.store)),
+    );
+
+    let (config, _digest) = pbs_config::prune::config()?;
+    let list: Vec<PruneJobConfig> = config.convert_to_typed_array("prune")?;
+    jobs.extend(list.into_iter().map(|job| ("prunejob", job.id, job.store)));
+
+    jobs.retain(|(_, _, store)| stores.contains(store));
+
+    let (config, _digest) = pbs_config::tape_job::config()?;
+    let list: Vec<TapeBackupJobConfig> = config.convert_to_typed_array("backup")?;
+    for job in list {
+        let privs = user_info.lookup_privs(auth_id, &["tape", "job", &job.id]);
+        if (privs & PRIV_TAPE_AUDIT) != 0 {
+            jobs.push(("tape-backup-job", job.id, job.setup.store));
+        }
+    }
+
+    Ok(jobs)
+}
+
+fn task_state_name(state: &TaskState) -> &'static str {
+    match state {
+        TaskState::OK { .. } => "ok",
+        TaskState::Warning { .. } => "warning",
+        TaskState::Error { .. } => "error",
+        TaskState::Unknown { .. } => "unknown",
+    }
+}
+
+fn job_metrics(jobs: &[(&'static str, String, String)]) -> Vec<Metric> {
+    let mut running = Metric::gauge("pbs_job_running", "Whether the job is currently running.");
+    let mut last_run = Metric::gauge(
+        "pbs_job_last_run_timestamp_seconds",
+        "End time of the last run of the job.",
+    );
+    let mut last_success = Metric::gauge(
+        "pbs_job_last_run_success",
+        "Whether the last run of the job finished without errors or warnings.",
+    );
+    let mut last_status = Metric::gauge(
+        "pbs_job_last_run_status",
+        "Status of the last run of the job, given by the 'status' label.",
+    );
+
+    for (jobtype, id, store) in jobs {
+        let labels = [
+            ("type", *jobtype),
+            ("id", id.as_str()),
+            ("datastore", store.as_str()),
+        ];
+
+        match JobState::load(jobtype, id) {
+            Ok(JobState::Created { .. }) => running.add(&labels, 0.0),
+            Ok(JobState::Started { .. }) => running.add(&labels, 1.0),
+            Ok(JobState::Finished { state, .. }) => {
+                running.add(&labels, 0.0);
+                last_run.add(&labels, state.endtime() as f64);
+                let success = matches!(state, TaskState::OK { .. });
+                last_success.add(&labels, if success { 1.0 } else { 0.0 });
+                let status = task_state_name(&state);
+                last_status.add(
+                    &[
+                        ("type", *jobtype),
+                        ("id", id.as_str()),
+                        ("datastore", store.as_str()),
+                        ("status", status),
+                    ],
+                    1.0,
+                );
+            }
+            Err(err) => log::warn!("could not load state of {jobtype} '{id}' - {err}"),
+        }
+    }
+
+    vec![running, last_run, last_success, last_status]
+}
+
+fn task_metrics() -> Result<Vec<Metric>, Error> {
+    let mut running: BTreeMap<String, u64> = BTreeMap::new();
+    let mut finished: BTreeMap<(String, &'static str), u64> = BTreeMap::new();
+
+    let since = proxmox_time::epoch_i64() - TASK_COUNT_TIMEFRAME;
+
+    for info in TaskListInfoIterator::new(false)? {
+        let info = match info {
+            Ok(info) => info,
+            Err(_) => break,
+        };
+        let worker_type = info.upid.worker_type.clone();
+        match info.state {
+            None => *running.entry(worker_type).or_default() += 1,
+            Some(ref state) => {
+                if state.endtime() < since {
+                    // tasks are sorted by end time, so all remaining tasks are older
+                    break;
+                }
+                let key = (worker_type, task_state_name(state));
+                *finished.entry(key).or_default() += 1;
+            }
+        }
+    }
+
+    let mut running_metric = Metric::gauge("pbs_tasks_running", "Number of running tasks.");
+    for (worker_type, count) in &running {
+        running_metric.add(&[("type", worker_type.as_str())], *count as f64);
+    }
+
+    let mut finished_metric = Metric::gauge(
+        "pbs_tasks_finished_24h",
+        "Number of tasks which finished within the last 24 hours.",
+    );
+    for ((worker_type, status), count) in &finished {
+        finished_metric.add(
+            &[("type", worker_type.as_str()), ("status", *status)],
+            *count as f64,
+        );
+    }
+
+    Ok(vec![running_metric, finished_metric])
+}
+
+fn traffic_control_metrics() -> Vec<Metric> {
+    let mut rate_in = Metric::gauge(
+        "pbs_traffic_control_rate_in_bytes_per_second",
+        "Current incoming data rate of the traffic control rule.",
+    );
+    let mut rate_out = Metric::gauge(
+        "pbs_traffic_control_rate_out_bytes_per_second",
+        "Current outgoing data rate of the traffic control rule.",
+    );
+    let mut traffic_in = Metric::counter(
+        "pbs_traffic_control_in_bytes_total",
+        "Incoming traffic of the traffic control rule.",
+    );
+    let mut traffic_out = Metric::counter(
+        "pbs_traffic_control_out_bytes_total",
+        "Outgoing traffic of the traffic control rule.",
+    );
+
+    let cache = TRAFFIC_CONTROL_CACHE.lock().unwrap();
+    let mut rules: Vec<_> = cache.current_rate_map().iter().collect();
+    rules.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    for (rule, stat) in rules {
+        let labels = [("rule", rule.as_str())];
+        rate_in.add(&labels, stat.rate_in as f64);
+        rate_out.add(&labels, stat.rate_out as f64);
+        traffic_in.add(&labels, stat.traffic_in as f64);
+        traffic_out.add(&labels, stat.traffic_out as f64);
+    }
+
+    vec![rate_in, rate_out, traffic_in, traffic_out]
+}
+
+async fn collect_metrics(auth_id: &Authid) -> Result<String, Error> {
+    let user_info = CachedUserInfo::new()?;
+
+    let (config, _digest) = pbs_config::datastore::config()?;
+    let stores: Vec<String> = config
+        .sections
+        .keys()
+        .filter(|store| {
+            let privs = user_info.lookup_privs(auth_id, &["datastore", store]);
+            (privs & PRIV_DATASTORE_AUDIT) != 0
+        })
+        .cloned()
+        .collect();
+
+    let system_audit =
+        |path: &[&str]| (user_info.lookup_privs(auth_id, path) & PRIV_SYS_AUDIT) != 0;
+
+    let mut metrics = Vec::new();
+
+    if system_audit(&["system", "status"]) {
+        metrics.extend(host_metrics());
+        metrics.extend(traffic_control_metrics());
+    }
+
+    metrics.extend(datastore_metrics(&stores).await);
+    metrics.extend(job_metrics(&visible_jobs(auth_id, &user_info, &stores)?));
+
+    if system_audit(&["system", "tasks"]) {
+        metrics.extend(task_metrics()?);
+    }
+
+    let mut output = String::new();
+    for metric in &metrics {
+        metric.encode(&mut output);
+    }
+
+    Ok(output)
+}
+
+pub const API_METHOD_GET_METRICS: ApiMethod = ApiMethod::new(
+    &ApiHandler::AsyncHttp(&get_metrics),
+    &ObjectSchema::new("Get metrics in the Prometheus text exposition format.", &[]),
+)
+.access(
+    Some(
+        "Host and traffic control metrics require Sys.Audit on /system/status, task counts \
+        Sys.Audit on /system/tasks. Datastore and job metrics are included for datastores with \
+        Datastore.Audit, tape backup jobs with Tape.Audit on /tape/job/{id}.",
+    ),
+    &Permission::Anybody,
+);
+
+fn get_metrics(
+    _parts: Parts,
+    _req_body: Body,
+    _param: Value,
+    _info: &ApiMethod,
+    rpcenv: Box<dyn RpcEnvironment>,
+) -> ApiResponseFuture {
+    async move {
+        let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+
+        let output = collect_metrics(&auth_id).await?;
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(
+                header::CONTENT_TYPE,
+                "text/plain; version=0.0.4; charset=utf-8",
+            )
+            .body(output.into())
+            .unwrap())
+    }
+    .boxed()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_metric_encoding() {
+        let mut metric = Metric::gauge("pbs_test_bytes", "Test metric.");
+        metric.add(&[("datastore", "store1")], 1024.0);
+        metric.add(&[("datastore", "a\"b\\c\nd"), ("id", "x")], 0.5);
+
+        let mut output = String::new();
+        metric.encode(&mut output);
+        Metric::counter("pbs_empty_total", "No samples.").encode(&mut output);
+
+        assert_eq!(
+            output,
+            "# HELP pbs_test_bytes Test metric.\n\
+            # TYPE pbs_test_bytes gauge\n\
+            pbs_test_bytes{datastore=\"store1\"} 1024\n\
+            pbs_test_bytes{datastore=\"a\\\"b\\\\c\\nd\",id=\"x\"} 0.5\n"
+        );
+
+        assert_eq!(format_value(f64::INFINITY), "+Inf");
+        assert_eq!(format_value(f64::NAN), "NaN");
+    }
+}
@@ -20,6 +20,8 @@ use crate::tools::statistics::linear_regression;
 
 use crate::backup::can_access_any_namespace;
 
+mod metrics;
+
 #[api(
     returns: {
         description: "Lists the Status of the Datastores.",
@@ -137,10 +139,16 @@ pub async fn datastore_status(
     Ok(list)
 }
 
-const SUBDIRS: SubdirMap = &[(
-    "datastore-usage",
-    &Router::new().get(&API_METHOD_DATASTORE_STATUS),
-)];
+const SUBDIRS: SubdirMap = &[
+    (
+        "datastore-usage",
+        &Router::new().get(&API_METHOD_DATASTORE_STATUS),
+    ),
+    (
+        "metrics",
+        &Router::new().get(&metrics::API_METHOD_GET_METRICS),
+    ),
+];
 
 pub const ROUTER: Router = Router::new()
     .get(&list_subdirs_api_method!(SUBDIRS))
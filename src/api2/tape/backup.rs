@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 
 use anyhow::{bail, format_err, Error};
@@ -27,7 +28,7 @@ use crate::{
     tape::{
         changer::update_changer_online_status,
         drive::{lock_tape_device, media_changer, set_tape_device_state, TapeLockError},
-        Inventory, MediaPool, PoolWriter, TAPE_STATUS_DIR,
+        media_catalog_snapshot_list, Inventory, MediaPool, PoolWriter, TAPE_STATUS_DIR,
     },
 };
 
@@ -214,16 +215,14 @@ pub fn do_tape_backup_job(
 
             let status = worker.create_state(&job_result);
 
-            if let Some(email) = email {
-                if let Err(err) = crate::server::send_tape_backup_status(
-                    &email,
-                    Some(job.jobname()),
-                    &setup,
-                    &job_result,
-                    summary,
-                ) {
-                    eprintln!("send tape backup notification failed: {}", err);
-                }
+            if let Err(err) = crate::server::send_tape_backup_status(
+                email.as_deref(),
+                Some(job.jobname()),
+                &setup,
+                &job_result,
+                summary,
+            ) {
+                eprintln!("send tape backup notification failed: {}", err);
             }
 
             if let Err(err) = job.finish(status) {
@@ -354,16 +353,14 @@ pub fn backup(
                 force_media_set,
             );
 
-            if let Some(email) = email {
-                if let Err(err) = crate::server::send_tape_backup_status(
-                    &email,
-                    None,
-                    &setup,
-                    &job_result,
-                    summary,
-                ) {
-                    eprintln!("send tape backup notification failed: {}", err);
-                }
+            if let Err(err) = crate::server::send_tape_backup_status(
+                email.as_deref(),
+                None,
+                &setup,
+                &job_result,
+                summary,
+            ) {
+                eprintln!("send tape backup notification failed: {}", err);
             }
 
             // ignore errors
@@ -398,7 +395,26 @@ fn backup_worker(
     let root_namespace = setup.ns.clone().unwrap_or_default();
     let ns_magic = !root_namespace.is_root() || setup.max_depth != Some(0);
 
-    let pool = MediaPool::with_config(TAPE_STATUS_DIR, pool_config, changer_name, false)?;
+    let mut pool = MediaPool::with_config(TAPE_STATUS_DIR, pool_config, changer_name, false)?;
+
+    if let Some(ref allocation) = setup.allocation {
+        task_log!(worker, "media set allocation policy: {allocation}");
+        pool.override_media_set_policy(allocation.parse()?);
+    }
+    if let Some(ref retention) = setup.retention {
+        task_log!(worker, "media retention policy: {retention}");
+        pool.override_retention(retention)?;
+    }
+
+    let pool_snapshots = if setup.incremental.unwrap_or(false) {
+        task_log!(
+            worker,
+            "incremental: true (skip snapshots already on media of the pool)"
+        );
+        Some(pool_snapshot_list(worker, &pool))
+    } else {
+        None
+    };
 
     let mut pool_writer =
         PoolWriter::new(pool, &setup.drive, worker, email, force_media_set, ns_magic)?;
@@ -486,7 +502,8 @@ fn backup_worker(
                     datastore_name,
                     info.backup_dir.backup_ns(),
                     info.backup_dir.as_ref(),
-                ) {
+                ) || on_pool_media(&pool_snapshots, datastore_name, &rel_path)
+                {
                     task_log!(worker, "skip snapshot {}", rel_path);
                     continue;
                 }
@@ -512,7 +529,8 @@ fn backup_worker(
                     datastore_name,
                     info.backup_dir.backup_ns(),
                     info.backup_dir.as_ref(),
-                ) {
+                ) || on_pool_media(&pool_snapshots, datastore_name, &rel_path)
+                {
                     task_log!(worker, "skip snapshot {}", rel_path);
                     continue;
                 }
@@ -575,8 +593,42 @@ fn backup_worker(
     Ok(())
 }
 
+// List the snapshots stored on media of the pool which are not expired
+fn pool_snapshot_list(worker: &WorkerTask, pool: &MediaPool) -> HashSet<(String, String)> {
+    let current_time = proxmox_time::epoch_i64();
+
+    let mut list = HashSet::new();
+
+    for media in pool.list_media() {
+        if media.media_set_label().is_none() || pool.media_is_expired(&media, current_time) {
+            continue;
+        }
+        match media_catalog_snapshot_list(TAPE_STATUS_DIR, media.id()) {
+            Ok(snapshots) => list.extend(snapshots),
+            Err(err) => task_warn!(
+                worker,
+                "could not read catalog of media '{}' - {err}",
+                media.label_text()
+            ),
+        }
+    }
+
+    list
+}
+
+fn on_pool_media(
+    pool_snapshots: &Option<HashSet<(String, String)>>,
+    store: &str,
+    snapshot: &str,
+) -> bool {
+    match pool_snapshots {
+        Some(list) => list.contains(&(store.to_string(), snapshot.to_string())),
+        None => false,
+    }
+}
+
 // Try to update the the media online status
-fn update_media_online_status(drive: &str) -> Result<Option<String>, Error> {
+pub(crate) fn update_media_online_status(drive: &str) -> Result<Option<String>, Error> {
     let (config, _digest) = pbs_config::drive::config()?;
 
     if let Ok(Some((mut changer, changer_name))) = media_changer(&config, drive) {
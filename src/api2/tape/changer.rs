@@ -1,25 +1,40 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use anyhow::Error;
+use anyhow::{bail, format_err, Error};
 use serde_json::Value;
 
-use proxmox_router::{list_subdirs_api_method, Permission, Router, RpcEnvironment, SubdirMap};
+use proxmox_lang::try_block;
+use proxmox_router::{
+    list_subdirs_api_method, Permission, Router, RpcEnvironment, RpcEnvironmentType, SubdirMap,
+};
 use proxmox_schema::api;
+use proxmox_section_config::SectionConfigData;
+use proxmox_sys::{task_log, task_warn};
+use proxmox_uuid::Uuid;
 
 use pbs_api_types::{
-    Authid, ChangerListEntry, LtoTapeDrive, MtxEntryKind, MtxStatusEntry, ScsiTapeChanger,
-    CHANGER_NAME_SCHEMA, PRIV_TAPE_AUDIT, PRIV_TAPE_READ,
+    Authid, ChangerListEntry, LtoTapeDrive, MediaLocation, MtxEntryKind, MtxStatusEntry,
+    ScsiTapeChanger, CHANGER_NAME_SCHEMA, DRIVE_NAME_SCHEMA, PRIV_TAPE_AUDIT, PRIV_TAPE_READ,
+    UPID_SCHEMA,
 };
 use pbs_config::CachedUserInfo;
 use pbs_tape::{
     linux_list_drives::{linux_tape_changer_list, lookup_device_identification},
     ElementStatus,
 };
+use proxmox_rest_server::WorkerTask;
 
-use crate::tape::{
-    changer::{mtx_status_to_online_set, OnlineStatusMap, ScsiMediaChange},
-    drive::get_tape_device_state,
-    Inventory, TAPE_STATUS_DIR,
+use crate::{
+    server::jobstate::Job,
+    tape::{
+        changer::{mtx_status_to_online_set, MediaChange, OnlineStatusMap, ScsiMediaChange},
+        drive::{
+            get_tape_device_state, lock_tape_device, media_changer, open_drive,
+            set_tape_device_state, TapeLockError,
+        },
+        lock_media_pool, lock_media_set, lock_unassigned_media_pool, Inventory, MediaCatalog,
+        MediaId, TAPE_STATUS_DIR,
+    },
 };
 
 #[api(
@@ -199,7 +214,418 @@ pub fn list_changers(
     Ok(list)
 }
 
+/// Check that `drive` is connected to the changer `changer_name`
+pub fn check_reconcile_drive(
+    config: &SectionConfigData,
+    changer_name: &str,
+    drive: &str,
+) -> Result<(), Error> {
+    match media_changer(config, drive)? {
+        Some((_, name)) if name == changer_name => Ok(()),
+        _ => bail!(
+            "drive '{}' is not connected to changer '{}'",
+            drive,
+            changer_name
+        ),
+    }
+}
+
+#[derive(Default)]
+struct ReconcileSummary {
+    online: usize,
+    missing: usize,
+    foreign: usize,
+    blank: usize,
+    duplicate: usize,
+    mismatch: usize,
+}
+
+// Store a media label read from tape, like 'update_inventory' does
+fn inventorize_media(inventory: &mut Inventory, media_id: MediaId) -> Result<(), Error> {
+    let _pool_lock = if let Some(pool) = media_id.pool() {
+        lock_media_pool(TAPE_STATUS_DIR, &pool)?
+    } else {
+        lock_unassigned_media_pool(TAPE_STATUS_DIR)?
+    };
+
+    if let Some(ref set) = media_id.media_set_label {
+        let _lock = lock_media_set(TAPE_STATUS_DIR, &set.uuid, None)?;
+        MediaCatalog::destroy_unrelated_catalog(TAPE_STATUS_DIR, &media_id)?;
+        inventory.store(media_id, false)?;
+    } else {
+        MediaCatalog::destroy(TAPE_STATUS_DIR, &media_id.label.uuid)?;
+        inventory.store(media_id, false)?;
+    }
+
+    Ok(())
+}
+
+fn reconcile_worker(
+    worker: &WorkerTask,
+    config: &SectionConfigData,
+    changer_name: &str,
+    drive: Option<&str>,
+    verify: bool,
+) -> Result<(), Error> {
+    let mut changer_config: ScsiTapeChanger = config.lookup("changer", changer_name)?;
+    let status = changer_config.status(false)?;
+
+    let mut inventory = Inventory::load(TAPE_STATUS_DIR)?;
+    let mut summary = ReconcileSummary::default();
+
+    // barcodes of all media inside the library (excluding import/export slots)
+    let mut label_text_list = Vec::new();
+    let mut seen = HashSet::new();
+
+    let drive_elements = status.drives.iter().map(|d| (&d.status, false));
+    let slot_elements = status.slots.iter().map(|s| (&s.status, s.import_export));
+
+    for (element_status, import_export) in drive_elements.chain(slot_elements) {
+        match element_status {
+            ElementStatus::Empty => continue,
+            ElementStatus::Full => {
+                task_warn!(worker, "found media without barcode");
+                summary.foreign += 1;
+            }
+            ElementStatus::VolumeTag(label_text) => {
+                if import_export {
+                    task_log!(worker, "media '{}' is in an import/export slot", label_text);
+                    continue;
+                }
+                if label_text.starts_with("CLN") {
+                    continue;
+                }
+                if !seen.insert(label_text.clone()) {
+                    task_warn!(worker, "duplicate barcode '{}' inside changer", label_text);
+                    summary.duplicate += 1;
+                    continue;
+                }
+                label_text_list.push(label_text.clone());
+            }
+        }
+    }
+
+    // media sharing the same label text in the inventory
+    let mut label_map: HashMap<String, Vec<Uuid>> = HashMap::new();
+    for uuid in inventory.media_list() {
+        if let Some(media_id) = inventory.lookup_media(uuid) {
+            label_map
+                .entry(media_id.label.label_text.clone())
+                .or_default()
+                .push(uuid.clone());
+        }
+    }
+    for (label_text, uuid_list) in label_map.iter() {
+        if uuid_list.len() > 1 {
+            let uuid_list: Vec<String> = uuid_list.iter().map(|u| u.to_string()).collect();
+            task_warn!(
+                worker,
+                "duplicate label '{}' in inventory ({})",
+                label_text,
+                uuid_list.join(", ")
+            );
+            summary.duplicate += 1;
+        }
+    }
+
+    // compare recorded locations with the changer content
+    for uuid in inventory.media_list() {
+        let label_text = match inventory.lookup_media(uuid) {
+            Some(media_id) => &media_id.label.label_text,
+            None => continue,
+        };
+        let inside = seen.contains(label_text);
+        match inventory.status_and_location(uuid).1 {
+            MediaLocation::Online(ref name) if name == changer_name && !inside => {
+                task_warn!(worker, "media '{}' is missing from changer", label_text);
+                summary.missing += 1;
+            }
+            MediaLocation::Vault(ref vault) if inside => {
+                task_log!(
+                    worker,
+                    "media '{}' found inside changer (was in vault '{}')",
+                    label_text,
+                    vault
+                );
+            }
+            _ => {}
+        }
+    }
+
+    let read_list: Vec<&String> = label_text_list
+        .iter()
+        .filter(|label_text| verify || !label_map.contains_key(label_text.as_str()))
+        .collect();
+
+    // label text to uuid, as read from tape
+    let mut read_uuids: HashMap<String, Uuid> = HashMap::new();
+
+    match drive {
+        None => {
+            for label_text in read_list {
+                if !label_map.contains_key(label_text.as_str()) {
+                    task_warn!(
+                        worker,
+                        "unknown media '{}' (no drive to read the label)",
+                        label_text
+                    );
+                    summary.foreign += 1;
+                }
+            }
+        }
+        Some(drive) => {
+            let (mut changer, _) = media_changer(config, drive)?
+                .ok_or_else(|| format_err!("drive '{}' has no associated changer", drive))?;
+
+            for label_text in read_list {
+                worker.check_abort()?;
+
+                if let Err(err) = changer.load_media(label_text) {
+                    task_warn!(worker, "unable to load media '{}' - {}", label_text, err);
+                    continue;
+                }
+
+                let mut handle = open_drive(config, drive)?;
+                match handle.read_label() {
+                    Err(err) => {
+                        task_warn!(
+                            worker,
+                            "unable to read label from media '{}' - {}",
+                            label_text,
+                            err
+                        );
+                        summary.foreign += 1;
+                    }
+                    Ok((None, _)) => {
+                        task_log!(worker, "media '{}' is empty", label_text);
+                        summary.blank += 1;
+                    }
+                    Ok((Some(media_id), _key_config)) => {
+                        if *label_text != media_id.label.label_text {
+                            task_warn!(
+                                worker,
+                                "label text mismatch ({} != {})",
+                                label_text,
+                                media_id.label.label_text
+                            );
+                            summary.mismatch += 1;
+                        } else {
+                            let uuid = media_id.label.uuid.clone();
+                            match inventory.find_media_by_label_text(label_text) {
+                                Some(known) if known.label.uuid == uuid => {
+                                    task_log!(worker, "media '{}' verified", label_text);
+                                }
+                                Some(known) => {
+                                    task_warn!(
+                                        worker,
+                                        "media '{}' has uuid '{}', but inventory lists '{}'",
+                                        label_text,
+                                        uuid,
+                                        known.label.uuid
+                                    );
+                                    summary.mismatch += 1;
+                                }
+                                None => {
+                                    task_log!(
+                                        worker,
+                                        "inventorize media '{}' with uuid '{}'",
+                                        label_text,
+                                        uuid
+                                    );
+                                }
+                            }
+                            if inventory.lookup_media(&uuid).is_none() {
+                                inventorize_media(&mut inventory, media_id)?;
+                            }
+                            read_uuids.insert(label_text.clone(), uuid);
+                        }
+                    }
+                }
+                changer.unload_media(None)?;
+            }
+        }
+    }
+
+    // update online status, preferring the uuids read from tape
+    let mut online_set = HashSet::new();
+    for label_text in label_text_list.iter() {
+        if let Some(uuid) = read_uuids.get(label_text) {
+            online_set.insert(uuid.clone());
+        } else if let Some(media_id) = inventory.find_media_by_label_text(label_text) {
+            online_set.insert(media_id.label.uuid.clone());
+        }
+    }
+    summary.online = online_set.len();
+
+    let mut map = OnlineStatusMap::new(config)?;
+    map.update_online_status(changer_name, online_set)?;
+    inventory.update_online_status(&map)?;
+
+    task_log!(
+        worker,
+        "reconcile summary: {} online, {} missing, {} foreign, {} blank, {} duplicate, {} mismatch",
+        summary.online,
+        summary.missing,
+        summary.foreign,
+        summary.blank,
+        summary.duplicate,
+        summary.mismatch,
+    );
+
+    Ok(())
+}
+
+pub fn do_tape_reconcile_job(
+    mut job: Job,
+    changer: String,
+    drive: Option<String>,
+    verify: bool,
+    auth_id: &Authid,
+    schedule: Option<String>,
+    to_stdout: bool,
+) -> Result<String, Error> {
+    let worker_type = job.jobtype().to_string();
+
+    let (config, _digest) = pbs_config::drive::config()?;
+    let _changer_config: ScsiTapeChanger = config.lookup("changer", &changer)?;
+
+    if let Some(ref drive) = drive {
+        check_reconcile_drive(&config, &changer, drive)?;
+    }
+
+    // manual runs fail early if the drive is busy
+    let drive_lock = match drive {
+        Some(ref drive) if schedule.is_none() => Some(lock_tape_device(&config, drive)?),
+        _ => None,
+    };
+
+    let upid_str = WorkerTask::new_thread(
+        &worker_type,
+        Some(changer.clone()),
+        auth_id.to_string(),
+        to_stdout,
+        move |worker| {
+            job.start(&worker.upid().to_string())?;
+
+            let result = try_block!({
+                task_log!(
+                    worker,
+                    "Starting inventory reconcile for changer '{}'",
+                    changer
+                );
+                if let Some(event_str) = schedule {
+                    task_log!(worker, "task triggered by schedule '{}'", event_str);
+                }
+
+                let drive_lock = match (&drive, drive_lock) {
+                    (_, Some(lock)) => Some(lock),
+                    (None, None) => None,
+                    // scheduled runs do not wait for busy drives
+                    (Some(name), None) => match lock_tape_device(&config, name) {
+                        Ok(lock) => Some(lock),
+                        Err(TapeLockError::TimeOut) => {
+                            task_warn!(
+                                worker,
+                                "drive '{}' is busy - skip reading media labels",
+                                name
+                            );
+                            None
+                        }
+                        Err(TapeLockError::Other(err)) => return Err(err),
+                    },
+                };
+                let drive = if drive_lock.is_some() { drive } else { None };
+
+                match drive {
+                    Some(ref drive) => {
+                        set_tape_device_state(drive, &worker.upid().to_string())?;
+                        let result =
+                            reconcile_worker(&worker, &config, &changer, Some(drive), verify);
+                        if let Err(err) = set_tape_device_state(drive, "") {
+                            eprintln!("could not unset drive state for {}: {}", drive, err);
+                        }
+                        result
+                    }
+                    None => reconcile_worker(&worker, &config, &changer, None, verify),
+                }
+            });
+
+            let status = worker.create_state(&result);
+
+            if let Err(err) = job.finish(status) {
+                eprintln!("could not finish job state for {}: {}", job.jobtype(), err);
+            }
+
+            result
+        },
+    )?;
+
+    Ok(upid_str)
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: CHANGER_NAME_SCHEMA,
+            },
+            drive: {
+                schema: DRIVE_NAME_SCHEMA,
+                optional: true,
+            },
+            verify: {
+                description: "Also load already inventoried media and compare their labels.",
+                type: bool,
+                default: false,
+                optional: true,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        description: "The user needs Tape.Read privilege on /tape/device/{name} \
+                      and on /tape/device/{drive} if a drive is used.",
+        permission: &Permission::Privilege(&["tape", "device", "{name}"], PRIV_TAPE_READ, false),
+    },
+)]
+/// Reconcile the media inventory with the changer content
+///
+/// Compares the barcodes reported by the changer with the media
+/// inventory, and updates the media location. If a drive is given
+/// (or configured as 'reconcile-drive'), unknown media gets loaded to
+/// read its label. Missing, foreign and duplicate-label media are
+/// reported as task warnings.
+pub fn reconcile(
+    name: String,
+    drive: Option<String>,
+    verify: bool,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+
+    let (config, _digest) = pbs_config::drive::config()?;
+    let changer_config: ScsiTapeChanger = config.lookup("changer", &name)?;
+
+    let drive = drive.or(changer_config.reconcile_drive);
+
+    if let Some(ref drive) = drive {
+        let user_info = CachedUserInfo::new()?;
+        user_info.check_privs(&auth_id, &["tape", "device", drive], PRIV_TAPE_READ, false)?;
+    }
+
+    let job = Job::new("tape-reconcile", &name)?;
+
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let upid_str = do_tape_reconcile_job(job, name, drive, verify, &auth_id, None, to_stdout)?;
+
+    Ok(upid_str.into())
+}
+
 const SUBDIRS: SubdirMap = &[
+    ("reconcile", &Router::new().post(&API_METHOD_RECONCILE)),
     ("status", &Router::new().get(&API_METHOD_GET_STATUS)),
     ("transfer", &Router::new().post(&API_METHOD_TRANSFER)),
 ];
@@ -15,16 +15,18 @@ use proxmox_sys::{task_log, task_warn};
 use proxmox_uuid::Uuid;
 
 use pbs_api_types::{
-    Authid, DriveListEntry, LabelUuidMap, Lp17VolumeStatistics, LtoDriveAndMediaStatus,
-    LtoTapeDrive, MamAttribute, MediaIdFlat, CHANGER_NAME_SCHEMA, DRIVE_NAME_SCHEMA,
-    MEDIA_LABEL_SCHEMA, MEDIA_POOL_NAME_SCHEMA, UPID_SCHEMA,
+    parse_ns_and_snapshot, Authid, DriveListEntry, LabelUuidMap, Lp17VolumeStatistics,
+    LtoDriveAndMediaStatus, LtoTapeDrive, MamAttribute, MediaIdFlat, CHANGER_NAME_SCHEMA,
+    DRIVE_NAME_SCHEMA, MEDIA_LABEL_SCHEMA, MEDIA_POOL_NAME_SCHEMA, TAPE_RESTORE_SNAPSHOT_SCHEMA,
+    UPID_SCHEMA,
 };
 
-use pbs_api_types::{PRIV_TAPE_AUDIT, PRIV_TAPE_READ, PRIV_TAPE_WRITE};
+use pbs_api_types::{PRIV_DATASTORE_READ, PRIV_TAPE_AUDIT, PRIV_TAPE_READ, PRIV_TAPE_WRITE};
 
 use pbs_config::CachedUserInfo;
 use pbs_tape::{
     linux_list_drives::{lookup_device_identification, lto_tape_device_list, open_lto_tape_device},
+    ltfs::LtfsWriter,
     sg_tape::tape_alert_flags_critical,
     BlockReadError,
 };
@@ -32,6 +34,7 @@ use proxmox_rest_server::WorkerTask;
 
 use crate::{
     api2::tape::restore::{fast_catalog_restore, restore_media},
+    backup::check_ns_privs,
     tape::{
         changer::update_changer_online_status,
         drive::{
@@ -41,8 +44,9 @@ use crate::{
         },
         encryption_keys::insert_key,
         file_formats::{MediaLabel, MediaSetLabel},
-        lock_media_pool, lock_media_set, lock_unassigned_media_pool, Inventory, MediaCatalog,
-        MediaId, TAPE_STATUS_DIR,
+        lock_media_pool, lock_media_set, lock_unassigned_media_pool,
+        ltfs_export::export_snapshots_ltfs,
+        Inventory, MediaCatalog, MediaId, TAPE_STATUS_DIR,
     },
 };
 
@@ -375,6 +379,126 @@ pub fn format_media(
     Ok(upid_str.into())
 }
 
+#[api(
+    input: {
+        properties: {
+            drive: {
+                schema: DRIVE_NAME_SCHEMA,
+            },
+            "label-text": {
+                schema: MEDIA_LABEL_SCHEMA,
+            },
+            snapshots: {
+                description: "List of snapshots to export.",
+                type: Array,
+                items: {
+                    schema: TAPE_RESTORE_SNAPSHOT_SCHEMA,
+                },
+            },
+            force: {
+                description: "Overwrite media which is in the inventory or has an \
+                              unreadable label.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        // Note: parameters are no uri parameter, so we need to test inside function body
+        description: "The user needs Tape.Write privilege on /tape/drive/{drive} \
+            and Datastore.Read privilege on the namespaces of all snapshots.",
+        permission: &Permission::Anybody,
+    },
+)]
+/// Export snapshots as plain files to a new LTFS volume.
+///
+/// This overwrites the media. Media containing a Proxmox Backup Server
+/// label needs to be formatted first. Media known to the inventory, or
+/// with an unreadable label, is only overwritten with `force`.
+pub fn export_ltfs(
+    drive: String,
+    label_text: String,
+    snapshots: Vec<String>,
+    force: bool,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    user_info.check_privs(&auth_id, &["tape", "drive", &drive], PRIV_TAPE_WRITE, false)?;
+
+    for store_snapshot in snapshots.iter() {
+        let (store, snapshot) = store_snapshot
+            .split_once(':')
+            .ok_or_else(|| format_err!("invalid snapshot '{}'", store_snapshot))?;
+        let (ns, _) = parse_ns_and_snapshot(snapshot)?;
+        check_ns_privs(store, &ns, &auth_id, PRIV_DATASTORE_READ)?;
+    }
+
+    if !force {
+        let inventory = Inventory::load(TAPE_STATUS_DIR)?;
+        if inventory.find_media_by_label_text(&label_text).is_some() {
+            bail!("media '{label_text}' is in the inventory - use 'force' to overwrite it");
+        }
+    }
+
+    let upid_str = run_drive_worker(
+        rpcenv,
+        drive.clone(),
+        "export-ltfs",
+        Some(drive.clone()),
+        move |worker, config| {
+            task_log!(worker, "try to load media '{}'", label_text);
+            if let Some((mut changer, _)) = media_changer(&config, &drive)? {
+                changer.load_media(&label_text)?;
+            }
+
+            let mut handle = open_drive(&config, &drive)?;
+
+            match handle.read_label() {
+                Ok((Some(media_id), _)) => {
+                    bail!(
+                        "media contains Proxmox Backup Server label '{}' - please format first",
+                        media_id.label.label_text
+                    );
+                }
+                Ok((None, _)) => task_log!(worker, "found empty media"),
+                Err(err) => {
+                    if !force {
+                        bail!(
+                            "unable to read media label: {err} - use 'force' to overwrite the media"
+                        );
+                    }
+                    task_log!(worker, "unable to read media label: {}", err);
+                    task_log!(worker, "overwrite anyways");
+                }
+            }
+
+            let creator = format!(
+                "Proxmox Backup Server {} - Linux",
+                pbs_buildcfg::PROXMOX_PKG_VERSION
+            );
+
+            task_log!(worker, "format LTFS volume '{}'", label_text);
+            let mut writer = LtfsWriter::format(handle.ltfs_media()?, &label_text, &creator)?;
+            task_log!(worker, "volume uuid: {}", writer.volume_uuid());
+
+            export_snapshots_ltfs(&*worker, &mut writer, &snapshots)?;
+
+            task_log!(worker, "write LTFS index");
+            writer.finish()?;
+
+            Ok(())
+        },
+    )?;
+
+    Ok(upid_str.into())
+}
+
 #[api(
     input: {
         properties: {
@@ -1436,6 +1560,7 @@ pub const SUBDIRS: SubdirMap = &sorted!([
     ("catalog", &Router::new().post(&API_METHOD_CATALOG_MEDIA)),
     ("clean", &Router::new().put(&API_METHOD_CLEAN_DRIVE)),
     ("eject-media", &Router::new().post(&API_METHOD_EJECT_MEDIA)),
+    ("export-ltfs", &Router::new().post(&API_METHOD_EXPORT_LTFS)),
     (
         "format-media",
         &Router::new().post(&API_METHOD_FORMAT_MEDIA)
@@ -0,0 +1,716 @@
+use std::collections::BTreeSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, format_err, Error};
+use serde_json::Value;
+
+use proxmox_io::ReadExt;
+use proxmox_router::{Permission, Router, RpcEnvironment, RpcEnvironmentType};
+use proxmox_schema::api;
+use proxmox_section_config::SectionConfigData;
+use proxmox_sys::{task_log, task_warn, WorkerTaskContext};
+use proxmox_uuid::Uuid;
+
+use pbs_api_types::{
+    parse_ns_and_snapshot, Authid, MediaPoolConfig, Userid, DRIVE_NAME_SCHEMA,
+    MEDIA_POOL_NAME_SCHEMA, MEDIA_SET_UUID_SCHEMA, PRIV_TAPE_READ, PRIV_TAPE_WRITE, UPID_SCHEMA,
+};
+use pbs_config::CachedUserInfo;
+use pbs_datastore::DataBlob;
+use pbs_tape::{
+    BlockedReader, BlockedWriter, EmulateTapeReader, EmulateTapeWriter, MediaContentHeader,
+    TapeRead, TapeWrite, PROXMOX_BACKUP_CONTENT_HEADER_MAGIC_1_0, PROXMOX_TAPE_BLOCK_SIZE,
+};
+use proxmox_rest_server::WorkerTask;
+
+use crate::{
+    server::lookup_user_email,
+    tape::{
+        drive::{lock_tape_device, request_and_load_media, set_tape_device_state, TapeDriver},
+        file_formats::{
+            ChunkArchiveDecoder, ChunkArchiveHeader, SnapshotArchiveHeader,
+            PROXMOX_BACKUP_CHUNK_ARCHIVE_MAGIC_1_1, PROXMOX_BACKUP_SNAPSHOT_ARCHIVE_MAGIC_1_1,
+            PROXMOX_BACKUP_SNAPSHOT_ARCHIVE_MAGIC_1_2,
+        },
+        lock_media_set, Inventory, MediaCatalog, MediaId, MediaPool, PoolWriter, TAPE_STATUS_DIR,
+    },
+};
+
+use super::backup::update_media_online_status;
+
+pub const ROUTER: Router = Router::new().post(&API_METHOD_DUPLICATE_MEDIA_SET);
+
+#[api(
+    input: {
+        properties: {
+            "media-set": {
+                schema: MEDIA_SET_UUID_SCHEMA,
+            },
+            pool: {
+                schema: MEDIA_POOL_NAME_SCHEMA,
+            },
+            drive: {
+                schema: DRIVE_NAME_SCHEMA,
+            },
+            "source-drive": {
+                schema: DRIVE_NAME_SCHEMA,
+                optional: true,
+            },
+            "spool-dir": {
+                description: "Directory used to stage the content of a source media, \
+                              when there is no second drive.",
+                type: String,
+                optional: true,
+                max_length: 4096,
+            },
+            "notify-user": {
+                type: Userid,
+                optional: true,
+            },
+            "eject-media": {
+                description: "Eject media upon job completion.",
+                type: bool,
+                optional: true,
+            },
+            "export-media-set": {
+                description: "Export media set upon job completion.",
+                type: bool,
+                optional: true,
+            },
+            "allow-unencrypted": {
+                description: "Allow to copy an encrypted media set to a pool without \
+                              encryption key, writing the data unencrypted.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        // Note: parameters are no uri parameter, so we need to test inside function body
+        description: "The user needs Tape.Read privilege on the pool of the media set and on \
+                      /tape/drive/{source-drive}, and Tape.Write privilege on /tape/pool/{pool} \
+                      and /tape/drive/{drive}.",
+        permission: &Permission::Anybody,
+    },
+)]
+/// Duplicate a media set to another media pool
+///
+/// Reads the chunk and snapshot archives of the media set and writes them to a new media set
+/// of the target pool. The content is either copied from a second drive, or staged media by
+/// media in a local spool directory.
+#[allow(clippy::too_many_arguments)]
+pub fn duplicate_media_set(
+    media_set: String,
+    pool: String,
+    drive: String,
+    source_drive: Option<String>,
+    spool_dir: Option<String>,
+    notify_user: Option<Userid>,
+    eject_media: Option<bool>,
+    export_media_set: Option<bool>,
+    allow_unencrypted: bool,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let media_set_uuid: Uuid = media_set.parse()?;
+
+    let _lock = lock_media_set(TAPE_STATUS_DIR, &media_set_uuid, None)?;
+
+    let inventory = Inventory::load(TAPE_STATUS_DIR)?;
+
+    let source_pool = inventory.lookup_media_set_pool(&media_set_uuid)?;
+    if source_pool == pool {
+        bail!("media set {media_set_uuid} already belongs to pool '{pool}'");
+    }
+
+    user_info.check_privs(
+        &auth_id,
+        &["tape", "pool", &source_pool],
+        PRIV_TAPE_READ,
+        false,
+    )?;
+    user_info.check_privs(&auth_id, &["tape", "pool", &pool], PRIV_TAPE_WRITE, false)?;
+    user_info.check_privs(&auth_id, &["tape", "drive", &drive], PRIV_TAPE_WRITE, false)?;
+
+    let spool_dir = match (&source_drive, spool_dir) {
+        (Some(source_drive), _) => {
+            if source_drive == &drive {
+                bail!("source and target drive are the same - use a spool directory instead");
+            }
+            user_info.check_privs(
+                &auth_id,
+                &["tape", "drive", source_drive],
+                PRIV_TAPE_READ,
+                false,
+            )?;
+            None
+        }
+        (None, Some(spool_dir)) => {
+            let spool_dir = PathBuf::from(spool_dir);
+            if !spool_dir.is_absolute() {
+                bail!("spool directory must be an absolute path");
+            }
+            Some(spool_dir)
+        }
+        (None, None) => bail!("either a source drive or a spool directory is required"),
+    };
+
+    let (config, _digest) = pbs_config::media_pool::config()?;
+    let pool_config: MediaPoolConfig = config.lookup("pool", &pool)?;
+
+    let (drive_config, _digest) = pbs_config::drive::config()?;
+
+    // early check/lock before starting worker
+    let drive_lock = lock_tape_device(&drive_config, &drive)?;
+    let source_drive_lock = match source_drive {
+        Some(ref source_drive) => Some(lock_tape_device(&drive_config, source_drive)?),
+        None => None,
+    };
+
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let email = notify_user
+        .as_ref()
+        .and_then(lookup_user_email)
+        .or_else(|| lookup_user_email(&auth_id.clone().into()));
+
+    let upid_str = WorkerTask::new_thread(
+        "tape-duplicate",
+        Some(format!("{media_set}:{pool}")),
+        auth_id.to_string(),
+        to_stdout,
+        move |worker| {
+            let _drive_lock = drive_lock; // keep lock guard
+            let _source_drive_lock = source_drive_lock;
+
+            set_tape_device_state(&drive, &worker.upid().to_string())?;
+            if let Some(ref source_drive) = source_drive {
+                set_tape_device_state(source_drive, &worker.upid().to_string())?;
+            }
+
+            task_log!(worker, "Mediaset '{media_set}'");
+            task_log!(worker, "Source pool: {source_pool}");
+            task_log!(worker, "Target pool: {pool}");
+
+            let source = match source_drive {
+                Some(ref source_drive) => DuplicateSource::Drive(source_drive.clone()),
+                None => DuplicateSource::Spool(spool_dir.unwrap()),
+            };
+
+            let res = duplicate_worker(
+                &worker,
+                inventory,
+                media_set_uuid,
+                &pool_config,
+                &drive,
+                &drive_config,
+                source,
+                email,
+                eject_media.unwrap_or(false),
+                export_media_set.unwrap_or(false),
+                allow_unencrypted,
+            );
+
+            if res.is_ok() {
+                task_log!(worker, "Duplicate mediaset '{media_set}' done");
+            }
+
+            // ignore errors
+            let _ = set_tape_device_state(&drive, "");
+            if let Some(ref source_drive) = source_drive {
+                let _ = set_tape_device_state(source_drive, "");
+            }
+
+            res
+        },
+    )?;
+
+    Ok(upid_str.into())
+}
+
+/// How the archives of the source media set are read
+enum DuplicateSource {
+    /// Read directly from a second drive
+    Drive(String),
+    /// Stage each source media in this directory, using the target drive
+    Spool(PathBuf),
+}
+
+/// Reader for the archives of a single source media
+enum SourceMedia {
+    Drive(Box<dyn TapeDriver>),
+    Spool(PathBuf),
+}
+
+impl SourceMedia {
+    fn open_file(&mut self, file_nr: u64) -> Result<Box<dyn TapeRead + '_>, Error> {
+        match self {
+            SourceMedia::Drive(drive) => {
+                if drive.current_file_number()? != file_nr {
+                    drive.move_to_file(file_nr)?;
+                }
+                Ok(drive.read_next_file()?)
+            }
+            SourceMedia::Spool(path) => {
+                let file = std::fs::File::open(spool_file_path(path, file_nr))?;
+                let reader = BlockedReader::open(EmulateTapeReader::new(file))?;
+                Ok(Box::new(reader))
+            }
+        }
+    }
+}
+
+fn spool_file_path(path: &Path, file_nr: u64) -> PathBuf {
+    let mut path = path.to_owned();
+    path.push(format!("{file_nr:08}"));
+    path
+}
+
+/// The files of a media which contain complete snapshot archives or chunks
+fn media_file_list(catalog: &MediaCatalog) -> BTreeSet<u64> {
+    let mut file_list = BTreeSet::new();
+    for content in catalog.content().values() {
+        file_list.extend(content.snapshot_index.values());
+        file_list.extend(content.chunk_index.values());
+    }
+    file_list
+}
+
+#[allow(clippy::too_many_arguments)]
+fn duplicate_worker(
+    worker: &WorkerTask,
+    inventory: Inventory,
+    media_set_uuid: Uuid,
+    pool_config: &MediaPoolConfig,
+    drive: &str,
+    drive_config: &SectionConfigData,
+    source: DuplicateSource,
+    email: Option<String>,
+    eject_media: bool,
+    export_media_set: bool,
+    allow_unencrypted: bool,
+) -> Result<(), Error> {
+    let members = inventory.compute_media_set_members(&media_set_uuid)?;
+
+    let mut media_list = Vec::new();
+    let mut encrypted = false;
+
+    for (seq_nr, media_uuid) in members.media_list().iter().enumerate() {
+        let media_uuid = match media_uuid {
+            Some(media_uuid) => media_uuid,
+            None => bail!("media set {media_set_uuid} is incomplete (missing member {seq_nr})."),
+        };
+        let media_id = inventory.lookup_media(media_uuid).unwrap();
+        if let Some(ref set) = media_id.media_set_label {
+            encrypted |= set.encryption_key_fingerprint.is_some();
+        }
+        let catalog =
+            MediaCatalog::open(TAPE_STATUS_DIR, media_id, false, false).map_err(|err| {
+                format_err!(
+                    "unable to open catalog of media '{}' - {err} (please catalog the media first)",
+                    media_id.label.label_text,
+                )
+            })?;
+        media_list.push((media_id.clone(), catalog));
+    }
+
+    // keep the namespace format of the source
+    let ns_magic = media_list.iter().any(|(_, catalog)| {
+        catalog.content().values().any(|content| {
+            content
+                .snapshot_index
+                .keys()
+                .any(|snapshot| snapshot.starts_with("ns/"))
+        })
+    });
+
+    task_log!(worker, "update media online status");
+    let changer_name = update_media_online_status(drive)?;
+
+    let pool = MediaPool::with_config(TAPE_STATUS_DIR, pool_config, changer_name, false)?;
+
+    if encrypted && pool.encrypt_fingerprint().is_none() {
+        if !allow_unencrypted {
+            bail!(
+                "source media set is encrypted, but target pool '{}' is not - refusing to \
+                 write the data unencrypted (use 'allow-unencrypted' to override)",
+                pool.name()
+            );
+        }
+        task_warn!(
+            worker,
+            "source media set is encrypted, but target pool '{}' is not - data will be \
+             written unencrypted",
+            pool.name()
+        );
+    }
+
+    let mut pool_writer = PoolWriter::new(pool, drive, worker, email.clone(), true, ns_magic)?;
+    pool_writer.set_copy_of(media_set_uuid.clone());
+
+    let mut need_catalog = false;
+
+    for (media_id, catalog) in media_list.iter() {
+        worker.check_abort()?;
+
+        let file_list = media_file_list(catalog);
+        if file_list.is_empty() {
+            task_log!(
+                worker,
+                "media '{}' has no content",
+                media_id.label.label_text
+            );
+            continue;
+        }
+        need_catalog = true;
+
+        let mut source_media = match source {
+            DuplicateSource::Drive(ref source_drive) => {
+                let drive =
+                    load_source_media(worker, drive_config, source_drive, media_id, &email)?;
+                SourceMedia::Drive(drive)
+            }
+            DuplicateSource::Spool(ref spool_dir) => {
+                // the target drive is also used to read the source media, so sync the
+                // data written so far and commit the catalog before unloading the tape
+                pool_writer.commit()?;
+                pool_writer.eject_media(worker)?;
+                let mut drive = load_source_media(worker, drive_config, drive, media_id, &email)?;
+                let path = spool_media(
+                    worker,
+                    &mut drive,
+                    spool_dir,
+                    &media_id.label.uuid,
+                    &file_list,
+                )?;
+                task_log!(worker, "eject source media");
+                drive.eject_media()?;
+                SourceMedia::Spool(path)
+            }
+        };
+
+        let result = duplicate_media(
+            worker,
+            &mut pool_writer,
+            &mut source_media,
+            catalog,
+            &file_list,
+        );
+
+        if let SourceMedia::Spool(ref path) = source_media {
+            if let Err(err) = std::fs::remove_dir_all(path) {
+                task_warn!(worker, "unable to remove spool directory {path:?} - {err}");
+            }
+        }
+
+        result?;
+    }
+
+    pool_writer.commit()?;
+
+    if need_catalog {
+        task_log!(worker, "append media catalog");
+
+        let uuid = pool_writer.load_writable_media(worker)?;
+        let done = pool_writer.append_catalog_archive(worker)?;
+        if !done {
+            task_log!(
+                worker,
+                "catalog does not fit on tape, writing to next volume"
+            );
+            pool_writer.set_media_status_full(&uuid)?;
+            pool_writer.load_writable_media(worker)?;
+            let done = pool_writer.append_catalog_archive(worker)?;
+            if !done {
+                bail!("write_catalog_archive failed on second media");
+            }
+        }
+    }
+
+    if export_media_set {
+        pool_writer.export_media_set(worker)?;
+    } else if eject_media {
+        pool_writer.eject_media(worker)?;
+    }
+
+    Ok(())
+}
+
+fn load_source_media(
+    worker: &WorkerTask,
+    drive_config: &SectionConfigData,
+    drive_name: &str,
+    media_id: &MediaId,
+    email: &Option<String>,
+) -> Result<Box<dyn TapeDriver>, Error> {
+    let (mut drive, info) =
+        request_and_load_media(worker, drive_config, drive_name, &media_id.label, email)?;
+
+    match info.media_set_label {
+        None => {
+            bail!(
+                "missing media set label on media {} ({})",
+                media_id.label.label_text,
+                media_id.label.uuid
+            );
+        }
+        Some(ref set) => {
+            let encrypt_fingerprint = set
+                .encryption_key_fingerprint
+                .clone()
+                .map(|fp| (fp, set.uuid.clone()));
+
+            drive.set_encryption(encrypt_fingerprint)?;
+        }
+    }
+
+    Ok(drive)
+}
+
+/// Copy the listed files of a source media to the spool directory
+fn spool_media(
+    worker: &WorkerTask,
+    drive: &mut Box<dyn TapeDriver>,
+    spool_dir: &Path,
+    media_uuid: &Uuid,
+    file_list: &BTreeSet<u64>,
+) -> Result<PathBuf, Error> {
+    let mut path = spool_dir.to_owned();
+    path.push(media_uuid.to_string());
+
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path)?;
+
+    let mut buffer = proxmox_io::vec::undefined(PROXMOX_TAPE_BLOCK_SIZE);
+
+    for file_nr in file_list {
+        worker.check_abort()?;
+
+        let current_file_number = drive.current_file_number()?;
+        if current_file_number != *file_nr {
+            drive.move_to_file(*file_nr)?;
+        }
+
+        task_log!(worker, "File {file_nr}: copy to spool directory");
+
+        let mut reader = drive.read_next_file()?;
+
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(spool_file_path(&path, *file_nr))?;
+        let mut writer = BlockedWriter::new(EmulateTapeWriter::new(file, usize::MAX));
+
+        loop {
+            let got = reader.read(&mut buffer[..])?;
+            if got == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..got])?;
+        }
+        writer.finish(reader.is_incomplete()?)?;
+    }
+
+    Ok(path)
+}
+
+fn duplicate_media(
+    worker: &WorkerTask,
+    pool_writer: &mut PoolWriter,
+    source: &mut SourceMedia,
+    catalog: &MediaCatalog,
+    file_list: &BTreeSet<u64>,
+) -> Result<(), Error> {
+    for file_nr in file_list {
+        worker.check_abort()?;
+
+        if !duplicate_archive(
+            worker,
+            pool_writer,
+            source.open_file(*file_nr)?,
+            *file_nr,
+            catalog,
+        )? {
+            // snapshot archive did not fit, retry on next media
+            if !duplicate_archive(
+                worker,
+                pool_writer,
+                source.open_file(*file_nr)?,
+                *file_nr,
+                catalog,
+            )? {
+                bail!("copy snapshot archive failed on second media");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy a single archive to the target media set
+///
+/// Returns `Ok(false)` if a snapshot archive did not fit on the current media. The media is
+/// marked full in that case, and the archive needs to be copied again.
+fn duplicate_archive<'a>(
+    worker: &WorkerTask,
+    pool_writer: &mut PoolWriter,
+    mut reader: Box<dyn 'a + TapeRead>,
+    file_nr: u64,
+    catalog: &MediaCatalog,
+) -> Result<bool, Error> {
+    let header: MediaContentHeader = unsafe { reader.read_le_value()? };
+    if header.magic != PROXMOX_BACKUP_CONTENT_HEADER_MAGIC_1_0 {
+        bail!("missing MediaContentHeader");
+    }
+
+    match header.content_magic {
+        PROXMOX_BACKUP_SNAPSHOT_ARCHIVE_MAGIC_1_1 | PROXMOX_BACKUP_SNAPSHOT_ARCHIVE_MAGIC_1_2 => {
+            let header_data = reader.read_exact_allocated(header.size as usize)?;
+
+            let archive_header: SnapshotArchiveHeader = serde_json::from_slice(&header_data)
+                .map_err(|err| format_err!("unable to parse snapshot archive header - {err}"))?;
+
+            let store = archive_header.store;
+            let snapshot = archive_header.snapshot;
+
+            if catalog.lookup_snapshot(&store, &snapshot) != Some(file_nr) {
+                task_log!(
+                    worker,
+                    "File {file_nr}: skip incomplete snapshot {store}:{snapshot}"
+                );
+                return Ok(true);
+            }
+
+            task_log!(
+                worker,
+                "File {file_nr}: snapshot archive {store}:{snapshot}"
+            );
+
+            let (ns, backup_dir) = parse_ns_and_snapshot(&snapshot)?;
+
+            let uuid = pool_writer.load_writable_media(worker)?;
+
+            worker.check_abort()?;
+
+            let (done, _bytes) = pool_writer.append_snapshot_archive_copy(
+                worker,
+                header.content_magic,
+                &header_data,
+                &store,
+                &ns,
+                &backup_dir,
+                &mut reader,
+            )?;
+
+            if !done {
+                task_log!(
+                    worker,
+                    "snapshot does not fit on tape, writing to next volume"
+                );
+                pool_writer.set_media_status_full(&uuid)?;
+            }
+
+            Ok(done)
+        }
+        PROXMOX_BACKUP_CHUNK_ARCHIVE_MAGIC_1_1 => {
+            let header_data = reader.read_exact_allocated(header.size as usize)?;
+
+            let archive_header: ChunkArchiveHeader = serde_json::from_slice(&header_data)
+                .map_err(|err| format_err!("unable to parse chunk archive header - {err}"))?;
+
+            let store = archive_header.store;
+
+            task_log!(
+                worker,
+                "File {file_nr}: chunk archive for datastore '{store}'"
+            );
+
+            let mut chunk_iter = ChunkArchiveChunks::new(reader).peekable();
+
+            loop {
+                worker.check_abort()?;
+
+                // test is we have remaining chunks
+                match chunk_iter.peek() {
+                    None => break,
+                    Some(Ok(_)) => { /* Ok */ }
+                    Some(Err(err)) => bail!("{}", err),
+                }
+
+                let uuid = pool_writer.load_writable_media(worker)?;
+
+                worker.check_abort()?;
+
+                let (leom, _bytes) =
+                    pool_writer.append_chunk_archive(worker, &mut chunk_iter, &store)?;
+
+                if leom {
+                    pool_writer.set_media_status_full(&uuid)?;
+                }
+            }
+
+            Ok(true)
+        }
+        other => bail!("unexpected file type: {other:?}"),
+    }
+}
+
+/// Iterate over the chunks of a chunk archive read from tape
+///
+/// Stops at the end of incomplete or aborted archives, like a restore does.
+struct ChunkArchiveChunks<'a> {
+    decoder: ChunkArchiveDecoder<Box<dyn 'a + TapeRead>>,
+    done: bool,
+}
+
+impl<'a> ChunkArchiveChunks<'a> {
+    fn new(reader: Box<dyn 'a + TapeRead>) -> Self {
+        Self {
+            decoder: ChunkArchiveDecoder::new(reader),
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for ChunkArchiveChunks<'a> {
+    type Item = Result<([u8; 32], DataBlob), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.decoder.next_chunk() {
+            Ok(Some(chunk)) => Some(Ok(chunk)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+
+                let reader = self.decoder.reader();
+
+                // check if this stream is marked incomplete
+                if let Ok(true) = reader.is_incomplete() {
+                    return None;
+                }
+
+                // check if this is an aborted stream without end marker
+                if let Ok(false) = reader.has_end_marker() {
+                    return None;
+                }
+
+                // else the archive is corrupt
+                Some(Err(err))
+            }
+        }
+    }
+}
@@ -0,0 +1,845 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, format_err, Error};
+use futures::{FutureExt, TryStreamExt};
+use hyper::http::request::Parts;
+use hyper::{header, Body, Response, StatusCode};
+use serde_json::Value;
+
+use proxmox_async::io::AsyncChannelWriter;
+use proxmox_human_byte::HumanByte;
+use proxmox_io::ReadExt;
+use proxmox_router::{
+    http_err, ApiHandler, ApiMethod, ApiResponseFuture, Permission, Router, RpcEnvironment,
+    RpcEnvironmentType,
+};
+use proxmox_schema::{api, ObjectSchema};
+use proxmox_section_config::SectionConfigData;
+use proxmox_sortable_macro::sortable;
+use proxmox_sys::fs::{replace_file, CreateOptions};
+use proxmox_sys::{task_log, WorkerTaskContext};
+use proxmox_uuid::Uuid;
+use pxar::accessor::aio::Accessor;
+
+use pbs_api_types::{
+    Authid, CryptMode, TapeFileRestoreFormat, TapeFileRestoreResult, Userid, DRIVE_NAME_SCHEMA,
+    MEDIA_POOL_NAME_SCHEMA, MEDIA_SET_UUID_SCHEMA, PRIV_TAPE_READ, TAPE_FILE_RESTORE_TARGET_SCHEMA,
+    TAPE_RESTORE_SNAPSHOT_SCHEMA, UPID_SCHEMA,
+};
+use pbs_client::pxar::payload::check_no_split_archive;
+use pbs_client::pxar::{create_tar, create_zip};
+use pbs_config::CachedUserInfo;
+use pbs_datastore::catalog::CatalogReader;
+use pbs_datastore::dynamic_index::{BufferedDynamicReader, DynamicIndexReader, LocalDynamicReadAt};
+use pbs_datastore::index::IndexFile;
+use pbs_datastore::manifest::BackupManifest;
+use pbs_datastore::read_chunk::ReadChunk;
+use pbs_datastore::{DataBlob, CATALOG_NAME};
+use pbs_tape::{MediaContentHeader, PROXMOX_BACKUP_CONTENT_HEADER_MAGIC_1_0};
+use pbs_tools::json::required_string_param;
+use proxmox_rest_server::WorkerTask;
+
+use crate::{
+    server::lookup_user_email,
+    tape::{
+        create_tape_file_restore_dir,
+        drive::{
+            lock_tape_device, open_drive, request_and_load_media, set_tape_device_state, TapeDriver,
+        },
+        file_formats::{
+            ChunkArchiveDecoder, ChunkArchiveHeader, SnapshotArchiveHeader,
+            PROXMOX_BACKUP_CHUNK_ARCHIVE_MAGIC_1_1, PROXMOX_BACKUP_SNAPSHOT_ARCHIVE_MAGIC_1_1,
+            PROXMOX_BACKUP_SNAPSHOT_ARCHIVE_MAGIC_1_2,
+        },
+        lock_media_set, Inventory, MediaId, MediaSetCatalog, TAPE_FILE_RESTORE_DIR,
+        TAPE_STATUS_DIR,
+    },
+};
+
+use super::restore::{get_media_set_catalog, log_required_tapes, try_restore_snapshot_archive};
+
+const RESULT_ROUTER: Router = Router::new()
+    .download(&API_METHOD_DOWNLOAD_RESTORE_RESULT)
+    .delete(&API_METHOD_DELETE_RESTORE_RESULT);
+
+const POOL_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_RESTORE_RESULTS)
+    .match_all("target", &RESULT_ROUTER);
+
+pub const ROUTER: Router = Router::new()
+    .post(&API_METHOD_RESTORE_FILE)
+    .match_all("pool", &POOL_ROUTER);
+
+#[api(
+    input: {
+        properties: {
+            "media-set": {
+                schema: MEDIA_SET_UUID_SCHEMA,
+            },
+            drive: {
+                schema: DRIVE_NAME_SCHEMA,
+            },
+            snapshot: {
+                schema: TAPE_RESTORE_SNAPSHOT_SCHEMA,
+            },
+            filepath: {
+                description: "Path of the file or directory, starting with the archive name \
+                              (e.g. '/root.pxar.didx/etc/hosts').",
+                type: String,
+                max_length: 4096,
+            },
+            format: {
+                type: TapeFileRestoreFormat,
+                optional: true,
+            },
+            target: {
+                schema: TAPE_FILE_RESTORE_TARGET_SCHEMA,
+            },
+            "notify-user": {
+                type: Userid,
+                optional: true,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        // Note: parameters are no uri parameter, so we need to test inside function body
+        description: "The user needs Tape.Read privilege on /tape/pool/{pool} and \
+                      /tape/drive/{drive}.",
+        permission: &Permission::Anybody,
+    },
+)]
+/// Restore a single file or directory from a media set
+///
+/// Only the index files of the snapshot and the chunks needed for the requested path are read
+/// from tape. The result is stored as pxar, zip or tar archive `target` in the tape file restore
+/// directory of the media pool, where it can be downloaded and removed.
+#[allow(clippy::too_many_arguments)]
+pub fn restore_file(
+    media_set: String,
+    drive: String,
+    snapshot: String,
+    filepath: String,
+    format: Option<TapeFileRestoreFormat>,
+    target: String,
+    notify_user: Option<Userid>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    user_info.check_privs(&auth_id, &["tape", "drive", &drive], PRIV_TAPE_READ, false)?;
+
+    let media_set_uuid: Uuid = media_set.parse()?;
+
+    let _lock = lock_media_set(TAPE_STATUS_DIR, &media_set_uuid, None)?;
+
+    let inventory = Inventory::load(TAPE_STATUS_DIR)?;
+
+    let pool = inventory.lookup_media_set_pool(&media_set_uuid)?;
+    user_info.check_privs(&auth_id, &["tape", "pool", &pool], PRIV_TAPE_READ, false)?;
+
+    // only write to a fixed directory, the target name cannot contain a path
+    let target = Path::new(TAPE_FILE_RESTORE_DIR).join(&pool).join(target);
+    if target.exists() {
+        bail!("target {target:?} already exists");
+    }
+
+    let filepath = filepath.trim_matches('/').to_string();
+    let (archive_name, path) = match filepath.split_once('/') {
+        Some((archive_name, path)) => (archive_name.to_string(), format!("/{path}")),
+        None => (filepath.clone(), String::from("/")),
+    };
+    check_no_split_archive(&archive_name, "single file restore")?;
+    if !archive_name.ends_with(".pxar.didx") {
+        bail!("'{archive_name}' is not a pxar archive");
+    }
+
+    let format = format.unwrap_or_default();
+
+    let (drive_config, _digest) = pbs_config::drive::config()?;
+
+    // early check/lock before starting worker
+    let drive_lock = lock_tape_device(&drive_config, &drive)?;
+
+    create_tape_file_restore_dir(&pool)?;
+
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let email = notify_user
+        .as_ref()
+        .and_then(lookup_user_email)
+        .or_else(|| lookup_user_email(&auth_id.clone().into()));
+
+    let upid_str = WorkerTask::new_thread(
+        "tape-file-restore",
+        Some(media_set.clone()),
+        auth_id.to_string(),
+        to_stdout,
+        move |worker| {
+            let _drive_lock = drive_lock; // keep lock guard
+
+            set_tape_device_state(&drive, &worker.upid().to_string())?;
+
+            task_log!(worker, "Mediaset '{media_set}'");
+            task_log!(worker, "Pool: {pool}");
+
+            // target names cannot start with a dot, so this never clashes with a target
+            let mut tmp_path = target.clone();
+            tmp_path.set_file_name(format!(
+                ".{}.tmp",
+                target.file_name().unwrap().to_string_lossy()
+            ));
+
+            let res = match std::fs::create_dir(&tmp_path) {
+                Ok(()) => {
+                    let res = restore_file_worker(
+                        worker.clone(),
+                        inventory,
+                        media_set_uuid,
+                        drive_config,
+                        &drive,
+                        &snapshot,
+                        &archive_name,
+                        &path,
+                        format,
+                        &target,
+                        &tmp_path,
+                        email,
+                    );
+
+                    // only remove the directory if this task created it
+                    if let Err(err) = std::fs::remove_dir_all(&tmp_path) {
+                        task_log!(worker, "error cleaning up {tmp_path:?}: {err}");
+                    }
+
+                    res
+                }
+                Err(err) => Err(format_err!("unable to create {tmp_path:?} - {err}")),
+            };
+
+            if res.is_ok() {
+                task_log!(worker, "Restore of '{filepath}' to {target:?} done");
+            }
+
+            if let Err(err) = set_tape_device_state(&drive, "") {
+                task_log!(worker, "could not unset drive state for {drive}: {err}");
+            }
+
+            res
+        },
+    )?;
+
+    Ok(upid_str.into())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn restore_file_worker(
+    worker: Arc<WorkerTask>,
+    inventory: Inventory,
+    media_set_uuid: Uuid,
+    drive_config: SectionConfigData,
+    drive_name: &str,
+    store_snapshot: &str,
+    archive_name: &str,
+    path: &str,
+    format: TapeFileRestoreFormat,
+    target: &Path,
+    tmp_path: &Path,
+    email: Option<String>,
+) -> Result<(), Error> {
+    // we can unwrap here because of the api format
+    let (store, snapshot) = store_snapshot.split_once(':').unwrap();
+
+    let catalog = get_media_set_catalog(&inventory, &media_set_uuid)?;
+
+    let (media_uuid, file_num) = match catalog.lookup_snapshot(store, snapshot) {
+        Some((media_uuid, file_num)) => (media_uuid.clone(), file_num),
+        None => bail!("did not find snapshot '{store_snapshot}' in media set"),
+    };
+
+    let snapshot_path = tmp_path.join("snapshot");
+    let chunk_path = tmp_path.join("chunks");
+    std::fs::create_dir(&snapshot_path)?;
+    std::fs::create_dir(&chunk_path)?;
+
+    let reader = TapeChunkReader::new(
+        worker.clone(),
+        store,
+        catalog,
+        inventory,
+        drive_config,
+        drive_name,
+        email,
+        chunk_path,
+    );
+
+    task_log!(worker, "Phase 1: read snapshot index files");
+    let manifest = reader.restore_snapshot(&media_uuid, file_num, snapshot, &snapshot_path)?;
+
+    let file_info = manifest.lookup_file_info(archive_name)?;
+    if file_info.crypt_mode == CryptMode::Encrypt {
+        bail!("cannot decode '{archive_name}' - is encrypted");
+    }
+
+    let catalog_path = if path == "/" {
+        format!("/{archive_name}")
+    } else {
+        format!("/{archive_name}{path}")
+    };
+
+    task_log!(worker, "Phase 2: look up '{catalog_path}'");
+    if manifest.lookup_file_info(CATALOG_NAME).is_ok() {
+        let index = DynamicIndexReader::open(&snapshot_path.join(CATALOG_NAME))?;
+        let mut catalog_reader =
+            CatalogReader::new(BufferedDynamicReader::new(index, reader.clone()));
+        let entry = catalog_reader.lookup_recursive(catalog_path.as_bytes())?;
+        if format == TapeFileRestoreFormat::Pxar && !entry.is_directory() {
+            bail!("pxar format is only supported for directories");
+        }
+    } else {
+        task_log!(
+            worker,
+            "snapshot has no catalog, searching the archive directly"
+        );
+    }
+
+    let archive_path = snapshot_path.join(archive_name);
+    let open_archive = || -> Result<BufferedDynamicReader<TapeChunkReader>, Error> {
+        let index = DynamicIndexReader::open(&archive_path)?;
+        let (csum, size) = index.compute_csum();
+        manifest.verify_file(archive_name, &csum, size)?;
+        Ok(BufferedDynamicReader::new(index, reader.clone()))
+    };
+
+    let archive = open_archive()?;
+    let archive_size = archive.archive_size();
+    let range = proxmox_async::runtime::block_on(async {
+        let accessor = Accessor::new(LocalDynamicReadAt::new(archive), archive_size).await?;
+        let root = accessor.open_root().await?;
+        let file = root
+            .lookup(path)
+            .await?
+            .ok_or_else(|| format_err!("error opening '{path}' in {archive_name}"))?;
+        let file = match file.kind() {
+            pxar::EntryKind::Hardlink(_) => accessor.follow_hardlink(&file).await?,
+            _ => file,
+        };
+        if format == TapeFileRestoreFormat::Pxar && !file.is_dir() {
+            bail!("pxar format is only supported for directories");
+        }
+        Ok::<_, Error>(file.entry_range_info().entry_range.clone())
+    })?;
+
+    // collect all chunks covering the entry
+    let index = DynamicIndexReader::open(&archive_path)?;
+    let mut digests = HashSet::new();
+    if range.end > range.start {
+        let first = index
+            .chunk_from_offset(range.start)
+            .ok_or_else(|| format_err!("invalid offset {}", range.start))?
+            .0;
+        let last = index
+            .chunk_from_offset(range.end - 1)
+            .ok_or_else(|| format_err!("invalid offset {}", range.end - 1))?
+            .0;
+        for pos in first..=last {
+            if let Some(digest) = index.index_digest(pos) {
+                digests.insert(*digest);
+            }
+        }
+    }
+    task_log!(
+        worker,
+        "Phase 3: read {} chunks ({}) from tape",
+        digests.len(),
+        HumanByte::from(range.end - range.start),
+    );
+    reader.fetch_chunks(digests)?;
+
+    task_log!(worker, "Phase 4: write {format:?} archive to {target:?}");
+    // write into the temporary directory first, so that a download never sees a partial result
+    let output_path = tmp_path.join("output");
+    let output = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&output_path)
+        .map_err(|err| format_err!("unable to create {output_path:?} - {err}"))?;
+
+    let write_output = |mut output: std::fs::File| -> Result<(), Error> {
+        let mut archive = open_archive()?;
+        match format {
+            TapeFileRestoreFormat::Pxar => {
+                archive.seek(SeekFrom::Start(range.start))?;
+                std::io::copy(&mut archive.take(range.end - range.start), &mut output)?;
+            }
+            TapeFileRestoreFormat::Zip | TapeFileRestoreFormat::Tar => {
+                let output = &mut output;
+                proxmox_async::runtime::block_on(async move {
+                    let accessor =
+                        Accessor::new(LocalDynamicReadAt::new(archive), archive_size).await?;
+
+                    let (sender, mut receiver) =
+                        tokio::sync::mpsc::channel::<Result<_, Error>>(100);
+                    let channelwriter = AsyncChannelWriter::new(sender, 1024 * 1024);
+
+                    let create = async move {
+                        if format == TapeFileRestoreFormat::Zip {
+                            create_zip(channelwriter, accessor, path).await
+                        } else {
+                            create_tar(channelwriter, accessor, path).await
+                        }
+                    };
+                    let write = async move {
+                        while let Some(data) = receiver.recv().await {
+                            output.write_all(&data?)?;
+                        }
+                        Ok::<_, Error>(())
+                    };
+
+                    futures::future::try_join(create, write).await
+                })?;
+            }
+        }
+        output.sync_all()?;
+        Ok(())
+    };
+
+    write_output(output)?;
+
+    // unlike rename, this fails if the target was created in the meantime
+    std::fs::hard_link(&output_path, target)
+        .map_err(|err| format_err!("unable to create {target:?} - {err}"))?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            pool: {
+                schema: MEDIA_POOL_NAME_SCHEMA,
+            },
+        },
+    },
+    returns: {
+        description: "The single file restore results of the media pool.",
+        type: Array,
+        items: {
+            type: TapeFileRestoreResult,
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["tape", "pool", "{pool}"], PRIV_TAPE_READ, false),
+    },
+)]
+/// List the single file restore results of a media pool
+pub fn list_restore_results(pool: String) -> Result<Vec<TapeFileRestoreResult>, Error> {
+    let path = Path::new(TAPE_FILE_RESTORE_DIR).join(pool);
+
+    let mut list = Vec::new();
+
+    let dir = match std::fs::read_dir(&path) {
+        Ok(dir) => dir,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(list),
+        Err(err) => bail!("unable to read {path:?} - {err}"),
+    };
+
+    for entry in dir {
+        let entry = entry?;
+        let target = match entry.file_name().into_string() {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        // skip the temporary directories of running restores
+        if target.starts_with('.') {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        list.push(TapeFileRestoreResult {
+            target,
+            size: metadata.len(),
+            mtime: metadata.mtime(),
+        });
+    }
+
+    list.sort_by(|a, b| a.target.cmp(&b.target));
+
+    Ok(list)
+}
+
+#[sortable]
+pub const API_METHOD_DOWNLOAD_RESTORE_RESULT: ApiMethod = ApiMethod::new(
+    &ApiHandler::AsyncHttp(&download_restore_result),
+    &ObjectSchema::new(
+        "Download a single file restore result.",
+        &sorted!([
+            ("pool", false, &MEDIA_POOL_NAME_SCHEMA),
+            ("target", false, &TAPE_FILE_RESTORE_TARGET_SCHEMA),
+        ]),
+    ),
+)
+.access(
+    None,
+    &Permission::Privilege(&["tape", "pool", "{pool}"], PRIV_TAPE_READ, false),
+);
+
+fn download_restore_result(
+    _parts: Parts,
+    _req_body: Body,
+    param: Value,
+    _info: &ApiMethod,
+    _rpcenv: Box<dyn RpcEnvironment>,
+) -> ApiResponseFuture {
+    async move {
+        let pool = required_string_param(&param, "pool")?;
+        let target = required_string_param(&param, "target")?;
+
+        let path = Path::new(TAPE_FILE_RESTORE_DIR).join(pool).join(target);
+
+        let file = tokio::fs::File::open(&path)
+            .await
+            .map_err(|err| http_err!(BAD_REQUEST, "File open failed: {}", err))?;
+
+        let payload =
+            tokio_util::codec::FramedRead::new(file, tokio_util::codec::BytesCodec::new())
+                .map_ok(|bytes| bytes.freeze())
+                .map_err(move |err| {
+                    eprintln!("error during streaming of '{:?}' - {}", &path, err);
+                    err
+                });
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .body(Body::wrap_stream(payload))
+            .unwrap())
+    }
+    .boxed()
+}
+
+#[api(
+    input: {
+        properties: {
+            pool: {
+                schema: MEDIA_POOL_NAME_SCHEMA,
+            },
+            target: {
+                schema: TAPE_FILE_RESTORE_TARGET_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["tape", "pool", "{pool}"], PRIV_TAPE_READ, false),
+    },
+)]
+/// Remove a single file restore result
+pub fn delete_restore_result(pool: String, target: String) -> Result<(), Error> {
+    let path = Path::new(TAPE_FILE_RESTORE_DIR).join(pool).join(target);
+
+    std::fs::remove_file(&path).map_err(|err| format_err!("unable to remove {path:?} - {err}"))
+}
+
+/// Reads chunks of a single datastore from a local cache directory, and fetches missing chunks
+/// from the media set.
+#[derive(Clone)]
+struct TapeChunkReader {
+    inner: Arc<TapeChunkReaderInner>,
+}
+
+struct TapeChunkReaderInner {
+    worker: Arc<WorkerTask>,
+    store: String,
+    catalog: MediaSetCatalog,
+    inventory: Inventory,
+    drive_config: SectionConfigData,
+    drive: String,
+    email: Option<String>,
+    cache_path: PathBuf,
+    // uuid of the loaded media, also serializes drive access
+    current_media: Mutex<Option<Uuid>>,
+}
+
+impl TapeChunkReader {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        worker: Arc<WorkerTask>,
+        store: &str,
+        catalog: MediaSetCatalog,
+        inventory: Inventory,
+        drive_config: SectionConfigData,
+        drive: &str,
+        email: Option<String>,
+        cache_path: PathBuf,
+    ) -> Self {
+        Self {
+            inner: Arc::new(TapeChunkReaderInner {
+                worker,
+                store: store.to_string(),
+                catalog,
+                inventory,
+                drive_config,
+                drive: drive.to_string(),
+                email,
+                cache_path,
+                current_media: Mutex::new(None),
+            }),
+        }
+    }
+
+    fn chunk_path(&self, digest: &[u8; 32]) -> PathBuf {
+        self.inner.cache_path.join(hex::encode(digest))
+    }
+
+    /// Load the media into the drive (if not already loaded) and open it
+    fn open_media(
+        &self,
+        current_media: &mut Option<Uuid>,
+        uuid: &Uuid,
+    ) -> Result<Box<dyn TapeDriver>, Error> {
+        let inner = &self.inner;
+
+        if current_media.as_ref() == Some(uuid) {
+            return open_drive(&inner.drive_config, &inner.drive);
+        }
+
+        let media_id = inner
+            .inventory
+            .lookup_media(uuid)
+            .ok_or_else(|| format_err!("unknown media '{uuid}'"))?;
+
+        let (mut drive, _info) = request_and_load_media(
+            &inner.worker,
+            &inner.drive_config,
+            &inner.drive,
+            &media_id.label,
+            &inner.email,
+        )?;
+        set_media_encryption(&inner.worker, &mut drive, media_id)?;
+
+        *current_media = Some(uuid.clone());
+
+        Ok(drive)
+    }
+
+    /// Restore the index files and manifest of a snapshot archive
+    fn restore_snapshot(
+        &self,
+        uuid: &Uuid,
+        file_num: u64,
+        snapshot: &str,
+        path: &Path,
+    ) -> Result<BackupManifest, Error> {
+        let inner = &self.inner;
+        let mut current_media = inner.current_media.lock().unwrap();
+        let mut drive = self.open_media(&mut current_media, uuid)?;
+
+        if drive.current_file_number()? != file_num {
+            drive.move_to_file(file_num)?;
+        }
+        let mut reader = drive.read_next_file()?;
+
+        let header: MediaContentHeader = unsafe { reader.read_le_value()? };
+        if header.magic != PROXMOX_BACKUP_CONTENT_HEADER_MAGIC_1_0 {
+            bail!("missing MediaContentHeader");
+        }
+
+        match header.content_magic {
+            PROXMOX_BACKUP_SNAPSHOT_ARCHIVE_MAGIC_1_1
+            | PROXMOX_BACKUP_SNAPSHOT_ARCHIVE_MAGIC_1_2 => {
+                let header_data = reader.read_exact_allocated(header.size as usize)?;
+
+                let archive_header: SnapshotArchiveHeader = serde_json::from_slice(&header_data)
+                    .map_err(|err| {
+                        format_err!("unable to parse snapshot archive header - {err}")
+                    })?;
+
+                if archive_header.store != inner.store || archive_header.snapshot != snapshot {
+                    bail!(
+                        "file {file_num} contains snapshot archive {}:{}",
+                        archive_header.store,
+                        archive_header.snapshot,
+                    );
+                }
+
+                task_log!(
+                    inner.worker,
+                    "File {file_num}: snapshot archive {}:{snapshot}",
+                    inner.store,
+                );
+
+                let mut decoder = pxar::decoder::sync::Decoder::from_std(reader)?;
+                try_restore_snapshot_archive(inner.worker.clone(), &mut decoder, path)
+            }
+            other => bail!("unexpected file type: {other:?}"),
+        }
+    }
+
+    /// Fetch the given chunks from tape into the cache directory
+    ///
+    /// Chunks are grouped by media and chunk archive, so that each archive is read at most once.
+    fn fetch_chunks(&self, digests: HashSet<[u8; 32]>) -> Result<(), Error> {
+        let inner = &self.inner;
+
+        // sorted media_uuid => (sorted file_num => (set of digests)))
+        let mut media_file_chunk_map: BTreeMap<Uuid, BTreeMap<u64, HashSet<[u8; 32]>>> =
+            BTreeMap::new();
+
+        for digest in digests {
+            if self.chunk_path(&digest).exists() {
+                continue;
+            }
+            match inner.catalog.lookup_chunk(&inner.store, &digest) {
+                Some((uuid, nr)) => {
+                    media_file_chunk_map
+                        .entry(uuid.clone())
+                        .or_insert_with(BTreeMap::new)
+                        .entry(nr)
+                        .or_insert_with(HashSet::new)
+                        .insert(digest);
+                }
+                None => bail!("chunk {} not found in media set", hex::encode(digest)),
+            }
+        }
+
+        if media_file_chunk_map.is_empty() {
+            return Ok(());
+        }
+
+        let mut current_media = inner.current_media.lock().unwrap();
+
+        if media_file_chunk_map.len() > 1 {
+            log_required_tapes(&inner.worker, &inner.inventory, media_file_chunk_map.keys());
+        }
+
+        for (uuid, file_chunk_map) in media_file_chunk_map.iter_mut() {
+            let mut drive = self.open_media(&mut current_media, uuid)?;
+            for (nr, chunks) in file_chunk_map.iter_mut() {
+                self.read_chunk_archive(&mut drive, *nr, chunks)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_chunk_archive(
+        &self,
+        drive: &mut Box<dyn TapeDriver>,
+        nr: u64,
+        chunks: &mut HashSet<[u8; 32]>,
+    ) -> Result<(), Error> {
+        let inner = &self.inner;
+
+        if drive.current_file_number()? != nr {
+            drive.move_to_file(nr)?;
+        }
+        let mut reader = drive.read_next_file()?;
+
+        let header: MediaContentHeader = unsafe { reader.read_le_value()? };
+        if header.magic != PROXMOX_BACKUP_CONTENT_HEADER_MAGIC_1_0 {
+            bail!("file is missing the MediaContentHeader");
+        }
+        if header.content_magic != PROXMOX_BACKUP_CHUNK_ARCHIVE_MAGIC_1_1 {
+            bail!("unexpected content magic {:?}", header.content_magic);
+        }
+
+        let header_data = reader.read_exact_allocated(header.size as usize)?;
+        let archive_header: ChunkArchiveHeader = serde_json::from_slice(&header_data)
+            .map_err(|err| format_err!("unable to parse chunk archive header - {err}"))?;
+
+        if archive_header.store != inner.store {
+            bail!(
+                "unexpected chunk archive for store '{}'",
+                archive_header.store
+            );
+        }
+
+        task_log!(
+            inner.worker,
+            "File {nr}: read {} chunks from chunk archive",
+            chunks.len(),
+        );
+
+        let mut decoder = ChunkArchiveDecoder::new(reader);
+
+        while let Some((digest, blob)) = decoder.next_chunk()? {
+            inner.worker.check_abort()?;
+
+            if chunks.remove(&digest) {
+                blob.verify_crc()?;
+                replace_file(
+                    self.chunk_path(&digest),
+                    blob.raw_data(),
+                    CreateOptions::new(),
+                    false,
+                )?;
+            }
+            if chunks.is_empty() {
+                break;
+            }
+        }
+
+        if !chunks.is_empty() {
+            bail!("file {nr} is missing {} chunks", chunks.len());
+        }
+
+        Ok(())
+    }
+}
+
+impl ReadChunk for TapeChunkReader {
+    fn read_raw_chunk(&self, digest: &[u8; 32]) -> Result<DataBlob, Error> {
+        let path = self.chunk_path(digest);
+        if !path.exists() {
+            self.fetch_chunks(HashSet::from([*digest]))?;
+        }
+
+        let mut file = std::fs::File::open(&path)
+            .map_err(|err| format_err!("unable to open chunk {path:?} - {err}"))?;
+        let chunk = DataBlob::load_from_reader(&mut file)?;
+        if chunk.crypt_mode()? == CryptMode::Encrypt {
+            bail!("cannot decode encrypted chunk {}", hex::encode(digest));
+        }
+
+        Ok(chunk)
+    }
+
+    fn read_chunk(&self, digest: &[u8; 32]) -> Result<Vec<u8>, Error> {
+        let chunk = ReadChunk::read_raw_chunk(self, digest)?;
+
+        chunk.decode(None, Some(digest))
+    }
+}
+
+fn set_media_encryption(
+    worker: &WorkerTask,
+    drive: &mut Box<dyn TapeDriver>,
+    media_id: &MediaId,
+) -> Result<(), Error> {
+    match media_id.media_set_label {
+        None => bail!(
+            "missing media set label on media {} ({})",
+            media_id.label.label_text,
+            media_id.label.uuid
+        ),
+        Some(ref set) => {
+            let encrypt_fingerprint = set.encryption_key_fingerprint.clone().map(|fp| {
+                task_log!(worker, "Encryption key fingerprint: {}", fp);
+                (fp, set.uuid.clone())
+            });
+
+            drive.set_encryption(encrypt_fingerprint)?;
+        }
+    }
+
+    Ok(())
+}
@@ -40,6 +40,8 @@ pub async fn list_media_sets(
 
     let (config, _digest) = pbs_config::media_pool::config()?;
 
+    let inventory = Inventory::load(TAPE_STATUS_DIR)?;
+
     let mut media_sets: HashSet<Uuid> = HashSet::new();
     let mut list = Vec::new();
 
@@ -71,12 +73,15 @@ pub async fn list_media_sets(
                     .generate_media_set_name(&media_set_uuid, config.template.clone())
                     .unwrap_or_else(|_| media_set_uuid.to_string());
 
+                let copy_of = inventory.media_set_copy_of(&media_set_uuid).cloned();
+
                 media_sets.insert(media_set_uuid.clone());
                 list.push(MediaSetListEntry {
                     media_set_name,
                     media_set_uuid,
                     media_set_ctime,
                     pool: pool_name.to_string(),
+                    copy_of,
                 });
             }
         }
@@ -12,6 +12,8 @@ use pbs_tape::linux_list_drives::{linux_tape_changer_list, lto_tape_device_list}
 pub mod backup;
 pub mod changer;
 pub mod drive;
+pub mod duplicate;
+pub mod file_restore;
 pub mod media;
 pub mod restore;
 
@@ -57,8 +59,10 @@ const SUBDIRS: SubdirMap = &[
     ("backup", &backup::ROUTER),
     ("changer", &changer::ROUTER),
     ("drive", &drive::ROUTER),
+    ("duplicate", &duplicate::ROUTER),
     ("media", &media::ROUTER),
     ("restore", &restore::ROUTER),
+    ("restore-file", &file_restore::ROUTER),
     (
         "scan-changers",
         &Router::new().get(&API_METHOD_SCAN_CHANGERS),
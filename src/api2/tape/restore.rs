@@ -606,7 +606,7 @@ fn check_snapshot_restorable(
     Ok(can_restore_some)
 }
 
-fn log_required_tapes<'a>(
+pub(crate) fn log_required_tapes<'a>(
     worker: &WorkerTask,
     inventory: &Inventory,
     list: impl Iterator<Item = &'a Uuid>,
@@ -959,7 +959,7 @@ fn restore_list_worker(
     res
 }
 
-fn get_media_set_catalog(
+pub(crate) fn get_media_set_catalog(
     inventory: &Inventory,
     media_set_uuid: &Uuid,
 ) -> Result<MediaSetCatalog, Error> {
@@ -1180,6 +1180,94 @@ fn restore_file_chunk_map(
     Ok(())
 }
 
+/// Restore the given chunks of `datastore` from any tape whose catalog contains them.
+///
+/// This is used to repair corrupt or missing chunks. Returns the set of restored chunks.
+pub(crate) fn restore_chunks_from_tape(
+    worker: Arc<WorkerTask>,
+    datastore: Arc<DataStore>,
+    drive: &str,
+    digests: &HashSet<[u8; 32]>,
+    email: &Option<String>,
+) -> Result<HashSet<[u8; 32]>, Error> {
+    let store = datastore.name();
+
+    let inventory = Inventory::load(TAPE_STATUS_DIR)?;
+
+    let mut media_file_chunk_map: BTreeMap<Uuid, BTreeMap<u64, HashSet<[u8; 32]>>> =
+        BTreeMap::new();
+    let mut located = HashSet::new();
+
+    for media_id in inventory.list_used_media() {
+        if located.len() == digests.len() {
+            break;
+        }
+        let catalog = match MediaCatalog::open(TAPE_STATUS_DIR, &media_id, false, false) {
+            Ok(catalog) => catalog,
+            Err(err) => {
+                task_warn!(
+                    worker,
+                    "could not open catalog of media '{}' - {err}",
+                    media_id.label.label_text,
+                );
+                continue;
+            }
+        };
+        for digest in digests.iter() {
+            if located.contains(digest) {
+                continue;
+            }
+            if let Some(nr) = catalog.lookup_chunk(store, digest) {
+                media_file_chunk_map
+                    .entry(media_id.label.uuid.clone())
+                    .or_insert_with(BTreeMap::new)
+                    .entry(nr)
+                    .or_insert_with(HashSet::new)
+                    .insert(*digest);
+                located.insert(*digest);
+            }
+        }
+    }
+
+    if media_file_chunk_map.is_empty() {
+        task_log!(worker, "no media contains the requested chunks");
+        return Ok(HashSet::new());
+    }
+    log_required_tapes(&worker, &inventory, media_file_chunk_map.keys());
+
+    let (drive_config, _digest) = pbs_config::drive::config()?;
+    let _drive_lock = lock_tape_device(&drive_config, drive)?;
+    set_tape_device_state(drive, &worker.upid().to_string())?;
+
+    let store_map = DataStoreMap::try_from(store.to_string())?;
+
+    let result = proxmox_lang::try_block!({
+        for (media_uuid, file_chunk_map) in media_file_chunk_map.iter_mut() {
+            let media_id = inventory.lookup_media(media_uuid).unwrap();
+            let (mut drive, _info) =
+                request_and_load_media(&worker, &drive_config, drive, &media_id.label, email)?;
+            restore_file_chunk_map(worker.clone(), &mut drive, &store_map, file_chunk_map)?;
+        }
+        Ok(())
+    });
+
+    if let Err(err) = set_tape_device_state(drive, "") {
+        task_log!(worker, "could not unset drive state for {drive}: {err}");
+    }
+    result?;
+
+    // restore_file_chunk_map removes every chunk it found on tape
+    for file_chunk_map in media_file_chunk_map.values() {
+        for chunks in file_chunk_map.values() {
+            for digest in chunks.iter() {
+                located.remove(digest);
+            }
+        }
+    }
+
+    Ok(located)
+}
+
 fn restore_partial_chunk_archive<'a>(
     worker: Arc<WorkerTask>,
     reader: Box<dyn 'a + TapeRead>,
@@ -1715,7 +1803,7 @@ fn restore_snapshot_archive<'a>(
     }
 }
 
-fn try_restore_snapshot_archive<R: pxar::decoder::SeqRead>(
+pub(crate) fn try_restore_snapshot_archive<R: pxar::decoder::SeqRead>(
     worker: Arc<WorkerTask>,
     decoder: &mut pxar::decoder::sync::Decoder<R>,
     snapshot_path: &Path,
@@ -8,3 +8,6 @@ pub use verify::*;
 
 mod hierarchy;
 pub use hierarchy::*;
+
+mod quota;
+pub use quota::*;
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Error};
+use lazy_static::lazy_static;
+
+use proxmox_human_byte::HumanByte;
+
+use pbs_api_types::{Authid, BackupNamespace, QuotaConfig};
+use pbs_datastore::chunk_usage::ChunkUsage;
+use pbs_datastore::manifest::{archive_type, ArchiveType};
+use pbs_datastore::{BackupDir, DataStore};
+
+/// Cache key of a quota's usage: datastore, quota ID, namespace and owner.
+type QuotaUsageKey = (String, String, Option<BackupNamespace>, Option<Authid>);
+
+lazy_static! {
+    // Usage of all quotas loaded since the last garbage collection of their datastore. Chunks
+    // are only removed by garbage collection, so the cached usage only grows until then.
+    static ref QUOTA_USAGE_CACHE: Mutex<HashMap<QuotaUsageKey, Arc<Mutex<Option<ChunkUsage>>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Drop the cached quota usage of datastore `store`, so that it gets recomputed on next use.
+///
+/// Called after garbage collection, and whenever backups move between quotas, e.g. when the
+/// owner of a backup group changes.
+pub fn invalidate_quota_usage(store: &str) {
+    QUOTA_USAGE_CACHE
+        .lock()
+        .unwrap()
+        .retain(|(cached_store, ..), _| cached_store != store);
+}
+
+/// Usage tracking of a single quota during a backup.
+///
+/// The usage is shared by all backups and syncs the quota applies to.
+pub struct BackupQuota {
+    config: QuotaConfig,
+    usage: Arc<Mutex<Option<ChunkUsage>>>,
+}
+
+impl BackupQuota {
+    /// Account a chunk referenced by the new backup, fails if the quota gets exceeded.
+    ///
+    /// `size` is the on-disk size of the chunk. Chunks which are already referenced by another
+    /// backup covered by the quota are free.
+    pub fn add_chunk(&self, digest: [u8; 32], size: u64) -> Result<(), Error> {
+        let mut guard = self.usage.lock().unwrap();
+        let usage = guard.get_or_insert_with(ChunkUsage::new);
+
+        if usage.contains(&digest) {
+            return Ok(());
+        }
+
+        let limit = self.config.limit.as_u64();
+        if usage.unique_size + size > limit {
+            bail!(
+                "quota '{}' exceeded ({} of {} used)",
+                self.config.id,
+                HumanByte::from(usage.unique_size),
+                self.config.limit,
+            );
+        }
+
+        usage.add_chunk(digest, size);
+
+        Ok(())
+    }
+}
+
+/// Returns true if `quota` applies to a backup group in namespace `ns` owned by `owner`.
+pub fn quota_applies(quota: &QuotaConfig, ns: &BackupNamespace, owner: &Authid) -> bool {
+    if let Some(quota_ns) = &quota.ns {
+        if quota_ns.contains(ns).is_none() {
+            return false;
+        }
+    }
+    if let Some(quota_owner) = &quota.owner {
+        if pbs_datastore::check_backup_owner(owner, quota_owner).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Load all quotas applying to a new backup in namespace `ns` owned by `owner`, together with
+/// their current usage.
+///
+/// The usage of a quota is computed by scanning all index files covered by it the first time it
+/// is needed after a (re)start or garbage collection, so this is a potentially slow, blocking
+/// operation. Afterwards, backups and syncs add their new chunks to the cached usage.
+pub fn load_backup_quotas(
+    datastore: &Arc<DataStore>,
+    ns: &BackupNamespace,
+    owner: &Authid,
+) -> Result<Vec<BackupQuota>, Error> {
+    let mut quotas = Vec::new();
+
+    for config in pbs_config::quota::datastore_quotas(datastore.name())? {
+        if !quota_applies(&config, ns, owner) {
+            continue;
+        }
+
+        let key = (
+            datastore.name().to_string(),
+            config.id.clone(),
+            config.ns.clone(),
+            config.owner.clone(),
+        );
+        let usage = QUOTA_USAGE_CACHE
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .clone();
+
+        let unique_size = {
+            // only the entry of this quota is locked while scanning
+            let mut guard = usage.lock().unwrap();
+            if guard.is_none() {
+                *guard =
+                    Some(datastore.chunk_usage(
+                        config.ns.clone().unwrap_or_default(),
+                        config.owner.as_ref(),
+                    )?);
+            }
+            guard.as_ref().unwrap().unique_size
+        };
+
+        if unique_size >= config.limit.as_u64() {
+            bail!(
+                "quota '{}' exceeded ({} of {} used)",
+                config.id,
+                HumanByte::from(unique_size),
+                config.limit,
+            );
+        }
+
+        quotas.push(BackupQuota { config, usage });
+    }
+
+    Ok(quotas)
+}
+
+/// Account all chunks referenced by the indices of `snapshot` in `quotas`.
+///
+/// Used for snapshots which were not uploaded chunk by chunk, e.g. by a sync job.
+pub fn account_snapshot_quotas(quotas: &[BackupQuota], snapshot: &BackupDir) -> Result<(), Error> {
+    if quotas.is_empty() {
+        return Ok(());
+    }
+
+    let datastore = snapshot.datastore();
+    let (manifest, _) = snapshot.load_manifest()?;
+
+    for item in manifest.files() {
+        match archive_type(&item.filename)? {
+            ArchiveType::FixedIndex | ArchiveType::DynamicIndex => (),
+            ArchiveType::Blob => continue,
+        }
+        let mut path = snapshot.full_path();
+        path.push(&item.filename);
+        let index = datastore.open_index(&path)?;
+        for pos in 0..index.index_count() {
+            let info = index.chunk_info(pos).unwrap();
+            let size = datastore.chunk_disk_size(&info.digest, info.size());
+            for quota in quotas {
+                quota.add_chunk(info.digest, size)?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -10,11 +10,13 @@ use proxmox_sys::{task_log, WorkerTaskContext};
 
 use pbs_api_types::{
     print_ns_and_snapshot, print_store_and_ns, Authid, BackupNamespace, BackupType, CryptMode,
-    SnapshotVerifyState, VerifyState, PRIV_DATASTORE_BACKUP, PRIV_DATASTORE_VERIFY, UPID,
+    SnapshotVerifyState, VerifyCoverage, VerifyState, PRIV_DATASTORE_BACKUP, PRIV_DATASTORE_VERIFY,
+    UPID,
 };
 use pbs_datastore::backup_info::{BackupDir, BackupGroup, BackupInfo};
 use pbs_datastore::index::IndexFile;
 use pbs_datastore::manifest::{archive_type, ArchiveType, BackupManifest, FileInfo};
+use pbs_datastore::verify_state::ChunkVerifySelection;
 use pbs_datastore::{DataBlob, DataStore, StoreProgress};
 use proxmox_sys::fs::lock_dir_noblock_shared;
 
@@ -22,6 +24,23 @@ use crate::tools::parallel_handler::ParallelHandler;
 
 use crate::backup::hierarchy::ListAccessibleBackupGroups;
 
+/// A source for healthy copies of chunks found corrupt or missing during verification.
+pub trait ChunkRepair: Send + Sync {
+    /// Name of the source, used in the task log.
+    fn name(&self) -> String;
+
+    /// Try to restore the given chunks of `archive` in `backup_dir` into the datastore.
+    ///
+    /// Returns the set of digests which were successfully repaired.
+    fn repair_chunks(
+        &self,
+        worker: &dyn WorkerTaskContext,
+        backup_dir: &BackupDir,
+        archive: &str,
+        digests: &HashSet<[u8; 32]>,
+    ) -> Result<HashSet<[u8; 32]>, Error>;
+}
+
 /// A VerifyWorker encapsulates a task worker, datastore and information about which chunks have
 /// already been verified or detected as corrupt.
 pub struct VerifyWorker {
@@ -29,6 +48,10 @@ pub struct VerifyWorker {
     datastore: Arc<DataStore>,
     verified_chunks: Arc<Mutex<HashSet<[u8; 32]>>>,
     corrupt_chunks: Arc<Mutex<HashSet<[u8; 32]>>>,
+    // chunks to check and period in days for rolling verification
+    sample: Option<(Arc<ChunkVerifySelection>, u64)>,
+    // sources to fetch corrupt chunks from, tried in order
+    repair: Vec<Box<dyn ChunkRepair>>,
 }
 
 impl VerifyWorker {
@@ -41,8 +64,80 @@ impl VerifyWorker {
             verified_chunks: Arc::new(Mutex::new(HashSet::with_capacity(16 * 1024))),
             // start with 64 chunks since we assume there are few corrupt ones
             corrupt_chunks: Arc::new(Mutex::new(HashSet::with_capacity(64))),
+            sample: None,
+            repair: Vec::new(),
         }
     }
+
+    /// Only verify the chunks in `selection`, as part of a rolling verification which covers
+    /// all chunks within `period` days.
+    pub fn with_chunk_selection(
+        mut self,
+        selection: Arc<ChunkVerifySelection>,
+        period: u64,
+    ) -> Self {
+        self.sample = Some((selection, period));
+        self
+    }
+
+    /// Add a source to repair corrupt chunks from. Sources are tried in the order they were added.
+    pub fn with_chunk_repair(mut self, repair: Box<dyn ChunkRepair>) -> Self {
+        self.repair.push(repair);
+        self
+    }
+
+    /// Try to repair `digests` from the configured sources, returns the repaired chunks.
+    fn repair_chunks(
+        &self,
+        backup_dir: &BackupDir,
+        archive: &str,
+        digests: &HashSet<[u8; 32]>,
+    ) -> HashSet<[u8; 32]> {
+        let mut repaired = HashSet::new();
+
+        for source in self.repair.iter() {
+            let missing: HashSet<[u8; 32]> = digests.difference(&repaired).copied().collect();
+            if missing.is_empty() {
+                break;
+            }
+            task_log!(
+                self.worker,
+                "trying to repair {} chunks from {}",
+                missing.len(),
+                source.name()
+            );
+            match source.repair_chunks(&*self.worker, backup_dir, archive, &missing) {
+                Ok(list) => {
+                    task_log!(
+                        self.worker,
+                        "repaired {} chunks from {}",
+                        list.len(),
+                        source.name()
+                    );
+                    repaired.extend(list);
+                }
+                Err(err) => {
+                    task_log!(
+                        self.worker,
+                        "repair from {} failed - {}",
+                        source.name(),
+                        err
+                    );
+                }
+            }
+        }
+
+        if !repaired.is_empty() {
+            let mut corrupt_chunks = self.corrupt_chunks.lock().unwrap();
+            let mut verified_chunks = self.verified_chunks.lock().unwrap();
+            for digest in repaired.iter() {
+                corrupt_chunks.remove(digest);
+                verified_chunks.insert(*digest);
+            }
+        }
+
+        repaired
+    }
 }
 
 fn verify_blob(backup_dir: &BackupDir, info: &FileInfo) -> Result<(), Error> {
@@ -105,12 +200,30 @@ fn rename_corrupted_chunk(
     };
 }
 
+/// Returns the number of chunks checked and the number of chunks in the index.
+///
+/// If `selection` is set, only the chunks contained in it are checked.
 fn verify_index_chunks(
     verify_worker: &VerifyWorker,
+    backup_dir: &BackupDir,
+    archive: &str,
     index: Box<dyn IndexFile + Send>,
     crypt_mode: CryptMode,
-) -> Result<(), Error> {
+    selection: Option<&ChunkVerifySelection>,
+) -> Result<(u64, u64), Error> {
     let errors = Arc::new(AtomicUsize::new(0));
+    // errors which cannot be fixed by repairing chunks
+    let mismatch_errors = Arc::new(AtomicUsize::new(0));
+    // corrupt or missing chunks referenced by this index, candidates for repair
+    let damaged = Arc::new(Mutex::new(HashSet::new()));
+
+    let total_chunks = index.index_count() as u64;
+    let checked_chunks = match selection {
+        Some(selection) => (0..index.index_count())
+            .filter(|pos| selection.contains(index.index_digest(*pos).unwrap()))
+            .count() as u64,
+        None => total_chunks,
+    };
 
     let start_time = Instant::now();
 
@@ -122,6 +235,8 @@ fn verify_index_chunks(
     let corrupt_chunks2 = Arc::clone(&verify_worker.corrupt_chunks);
     let verified_chunks2 = Arc::clone(&verify_worker.verified_chunks);
     let errors2 = Arc::clone(&errors);
+    let damaged2 = Arc::clone(&damaged);
+    let mismatch_errors2 = Arc::clone(&mismatch_errors);
 
     let decoder_pool = ParallelHandler::new(
         "verify chunk decoder",
@@ -130,8 +245,11 @@ fn verify_index_chunks(
             let chunk_crypt_mode = match chunk.crypt_mode() {
                 Err(err) => {
                     corrupt_chunks2.lock().unwrap().insert(digest);
+                    damaged2.lock().unwrap().insert(digest);
                     task_log!(worker2, "can't verify chunk, unknown CryptMode - {}", err);
                     errors2.fetch_add(1, Ordering::SeqCst);
+                    // a repair must not find the corrupt chunk still in place
+                    rename_corrupted_chunk(datastore2.clone(), &digest, &worker2);
                     return Ok(());
                 }
                 Ok(mode) => mode,
@@ -145,10 +263,12 @@ fn verify_index_chunks(
                     crypt_mode
                 );
                 errors2.fetch_add(1, Ordering::SeqCst);
+                mismatch_errors2.fetch_add(1, Ordering::SeqCst);
             }
 
             if let Err(err) = chunk.verify_unencrypted(size as usize, &digest) {
                 corrupt_chunks2.lock().unwrap().insert(digest);
+                damaged2.lock().unwrap().insert(digest);
                 task_log!(worker2, "{}", err);
                 errors2.fetch_add(1, Ordering::SeqCst);
                 rename_corrupted_chunk(datastore2.clone(), &digest, &worker2);
@@ -161,7 +281,9 @@ fn verify_index_chunks(
     );
 
     let skip_chunk = |digest: &[u8; 32]| -> bool {
-        if verify_worker
+        if selection.map(|s| !s.contains(digest)).unwrap_or(false) {
+            true // not part of this rolling verification run
+        } else if verify_worker
             .verified_chunks
             .lock()
             .unwrap()
@@ -180,6 +302,7 @@ fn verify_index_chunks(
                 "chunk {} was marked as corrupt",
                 digest_str
             );
+            damaged.lock().unwrap().insert(*digest);
             errors.fetch_add(1, Ordering::SeqCst);
             true
         } else {
@@ -218,6 +341,7 @@ fn verify_index_chunks(
                     .lock()
                     .unwrap()
                     .insert(info.digest);
+                damaged.lock().unwrap().insert(info.digest);
                 task_log!(
                     verify_worker.worker,
                     "can't verify chunk, load failed - {}",
@@ -249,7 +373,7 @@ fn verify_index_chunks(
     let read_speed = read_bytes_mib / elapsed;
     let decode_speed = decoded_bytes_mib / elapsed;
 
-    let error_count = errors.load(Ordering::SeqCst);
+    let mut error_count = errors.load(Ordering::SeqCst);
 
     task_log!(
         verify_worker.worker,
@@ -262,18 +386,32 @@ fn verify_index_chunks(
         error_count,
     );
 
-    if errors.load(Ordering::SeqCst) > 0 {
+    let damaged = std::mem::take(&mut *damaged.lock().unwrap());
+    if !damaged.is_empty() && !verify_worker.repair.is_empty() {
+        let repaired = verify_worker.repair_chunks(backup_dir, archive, &damaged);
+        for digest in repaired.iter() {
+            task_log!(
+                verify_worker.worker,
+                "repaired chunk {}",
+                hex::encode(digest)
+            );
+        }
+        error_count = mismatch_errors.load(Ordering::SeqCst) + damaged.len() - repaired.len();
+    }
+
+    if error_count > 0 {
         bail!("chunks could not be verified");
     }
 
-    Ok(())
+    Ok((checked_chunks, total_chunks))
 }
 
 fn verify_fixed_index(
     verify_worker: &VerifyWorker,
     backup_dir: &BackupDir,
     info: &FileInfo,
-) -> Result<(), Error> {
+    selection: Option<&ChunkVerifySelection>,
+) -> Result<(u64, u64), Error> {
     let mut path = backup_dir.relative_path();
     path.push(&info.filename);
 
@@ -288,14 +426,22 @@ fn verify_fixed_index(
         bail!("wrong index checksum");
     }
 
-    verify_index_chunks(verify_worker, Box::new(index), info.chunk_crypt_mode())
+    verify_index_chunks(
+        verify_worker,
+        backup_dir,
+        &info.filename,
+        Box::new(index),
+        info.chunk_crypt_mode(),
+        selection,
+    )
 }
 
 fn verify_dynamic_index(
     verify_worker: &VerifyWorker,
     backup_dir: &BackupDir,
     info: &FileInfo,
-) -> Result<(), Error> {
+    selection: Option<&ChunkVerifySelection>,
+) -> Result<(u64, u64), Error> {
     let mut path = backup_dir.relative_path();
     path.push(&info.filename);
 
@@ -310,7 +456,14 @@ fn verify_dynamic_index(
         bail!("wrong index checksum");
     }
 
-    verify_index_chunks(verify_worker, Box::new(index), info.chunk_crypt_mode())
+    verify_index_chunks(
+        verify_worker,
+        backup_dir,
+        &info.filename,
+        Box::new(index),
+        info.chunk_crypt_mode(),
+        selection,
+    )
 }
 
 /// Verify a single backup snapshot
@@ -401,22 +554,51 @@ pub fn verify_backup_dir_with_lock(
         backup_dir.dir()
     );
 
+    // a rolling verification might not cover the chunks which failed before, so all chunks of a
+    // snapshot have to be checked to clear its failed state
+    let previously_failed = matches!(
+        serde_json::from_value::<SnapshotVerifyState>(manifest.unprotected["verify_state"].clone()),
+        Ok(last_verify) if last_verify.state == VerifyState::Failed
+    );
+    let selection = match verify_worker.sample.as_ref() {
+        Some(_) if previously_failed => {
+            task_log!(
+                verify_worker.worker,
+                "  previous verification failed, checking all chunks"
+            );
+            None
+        }
+        Some((selection, _)) => Some(selection.as_ref()),
+        None => None,
+    };
+
     let mut error_count = 0;
+    let mut checked_chunks = 0;
+    let mut total_chunks = 0;
 
     let mut verify_result = VerifyState::Ok;
     for info in manifest.files() {
         let result = proxmox_lang::try_block!({
             task_log!(verify_worker.worker, "  check {}", info.filename);
             match archive_type(&info.filename)? {
-                ArchiveType::FixedIndex => verify_fixed_index(verify_worker, backup_dir, info),
-                ArchiveType::DynamicIndex => verify_dynamic_index(verify_worker, backup_dir, info),
-                ArchiveType::Blob => verify_blob(backup_dir, info),
+                ArchiveType::FixedIndex => {
+                    verify_fixed_index(verify_worker, backup_dir, info, selection)
+                }
+                ArchiveType::DynamicIndex => {
+                    verify_dynamic_index(verify_worker, backup_dir, info, selection)
+                }
+                ArchiveType::Blob => verify_blob(backup_dir, info).map(|()| (0, 0)),
             }
         });
 
         verify_worker.worker.check_abort()?;
         verify_worker.worker.fail_on_shutdown()?;
 
+        if let Ok((checked, total)) = result {
+            checked_chunks += checked;
+            total_chunks += total;
+        }
+
         if let Err(err) = result {
             task_log!(
                 verify_worker.worker,
@@ -431,9 +613,19 @@ pub fn verify_backup_dir_with_lock(
         }
     }
 
+    let coverage = match (selection, verify_worker.sample.as_ref()) {
+        (Some(_), Some((_, period))) => Some(VerifyCoverage {
+            verified_chunks: checked_chunks,
+            total_chunks,
+            period: *period,
+        }),
+        _ => None,
+    };
+
     let verify_state = SnapshotVerifyState {
         state: verify_result,
         upid,
+        coverage,
     };
     let verify_state = serde_json::to_value(verify_state)?;
     backup_dir
@@ -588,6 +780,255 @@ pub fn verify_all_backups(
     Ok(errors)
 }
 
+/// Verify all chunks in the chunk store of a datastore, independent of the snapshots
+///
+/// Every chunk is read once, in inode order per chunk directory, and its CRC and (if not
+/// encrypted) digest are checked. Corrupt chunks are renamed, and all snapshots referencing them
+/// are looked up and marked as failed. Chunks missing from the chunk store are not detected.
+///
+/// Returns
+/// - Ok(failed_dirs) where failed_dirs reference corrupt chunks
+/// - Err(_) if task was aborted
+pub fn verify_chunk_store(verify_worker: &VerifyWorker, upid: &UPID) -> Result<Vec<String>, Error> {
+    let worker = Arc::clone(&verify_worker.worker);
+    let datastore = &verify_worker.datastore;
+
+    task_log!(
+        worker,
+        "verify chunk store of datastore {}",
+        datastore.name()
+    );
+
+    let start_time = Instant::now();
+
+    let worker2 = Arc::clone(&verify_worker.worker);
+    let datastore2 = Arc::clone(&verify_worker.datastore);
+    let corrupt_chunks2 = Arc::clone(&verify_worker.corrupt_chunks);
+
+    let decoder_pool = ParallelHandler::new(
+        "verify chunk decoder",
+        4,
+        move |(chunk, digest): (DataBlob, [u8; 32])| {
+            // the CRC was checked on load, the digest can only be checked without encryption
+            if chunk.is_encrypted() {
+                return Ok(());
+            }
+            if let Err(err) = chunk.decode(None, Some(&digest)) {
+                corrupt_chunks2.lock().unwrap().insert(digest);
+                task_log!(worker2, "chunk {} - {}", hex::encode(digest), err);
+                rename_corrupted_chunk(datastore2.clone(), &digest, &worker2);
+            }
+            Ok(())
+        },
+    );
+
+    let mut chunk_count = 0;
+    let mut read_bytes = 0;
+    let mut last_percentage = 0;
+
+    // returns the number of chunks and bytes read
+    let verify_batch = |batch: &mut Vec<(u64, [u8; 32])>| -> Result<(usize, u64), Error> {
+        let mut chunk_count = 0;
+        let mut read_bytes = 0;
+        // sorting by inode improves data locality, which makes it lots faster on spinners
+        batch.sort_unstable();
+        for (_, digest) in batch.drain(..) {
+            worker.check_abort()?;
+            worker.fail_on_shutdown()?;
+
+            match datastore.load_chunk(&digest) {
+                Ok(chunk) => {
+                    chunk_count += 1;
+                    read_bytes += chunk.raw_size();
+                    decoder_pool.send((chunk, digest))?;
+                }
+                Err(_) if !datastore.chunk_path(&digest).0.exists() => {
+                    // removed by garbage collection in the meantime
+                }
+                Err(err) => {
+                    verify_worker.corrupt_chunks.lock().unwrap().insert(digest);
+                    task_log!(
+                        worker,
+                        "chunk {} - load failed - {}",
+                        hex::encode(digest),
+                        err
+                    );
+                    rename_corrupted_chunk(datastore.clone(), &digest, &worker);
+                }
+            }
+        }
+        Ok((chunk_count, read_bytes))
+    };
+
+    let mut batch = Vec::new();
+    let mut batch_prefix = None;
+    for (entry, percentage, bad) in datastore.get_chunk_iterator()? {
+        let entry = entry?;
+        if bad {
+            continue;
+        }
+
+        let mut digest = [0u8; 32];
+        hex::decode_to_slice(&entry.file_name().to_bytes()[..64], &mut digest)?;
+
+        // chunks are read per chunk directory, which share the first two digest bytes
+        if batch_prefix != Some([digest[0], digest[1]]) {
+            let (count, bytes) = verify_batch(&mut batch)?;
+            chunk_count += count;
+            read_bytes += bytes;
+            batch_prefix = Some([digest[0], digest[1]]);
+        }
+        batch.push((entry.ino(), digest));
+
+        if percentage != last_percentage {
+            task_log!(worker, "processed {}% ({} chunks)", percentage, chunk_count);
+            last_percentage = percentage;
+        }
+    }
+    let (count, bytes) = verify_batch(&mut batch)?;
+    chunk_count += count;
+    read_bytes += bytes;
+
+    decoder_pool.complete()?;
+
+    let elapsed = start_time.elapsed().as_secs_f64();
+    let read_bytes_mib = (read_bytes as f64) / (1024.0 * 1024.0);
+
+    let corrupt_chunks = verify_worker.corrupt_chunks.lock().unwrap().clone();
+
+    task_log!(
+        worker,
+        "verified {} chunks ({:.2} MiB) in {:.2} seconds, speed {:.2} MiB/s ({} corrupt)",
+        chunk_count,
+        read_bytes_mib,
+        elapsed,
+        read_bytes_mib / elapsed,
+        corrupt_chunks.len(),
+    );
+
+    if corrupt_chunks.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    task_log!(worker, "looking up snapshots referencing corrupt chunks");
+    mark_snapshots_referencing_chunks(verify_worker, &corrupt_chunks, upid)
+}
+
+/// Find all snapshots with an index referencing one of `digests`, and mark them as failed.
+fn mark_snapshots_referencing_chunks(
+    verify_worker: &VerifyWorker,
+    digests: &HashSet<[u8; 32]>,
+    upid: &UPID,
+) -> Result<Vec<String>, Error> {
+    let worker = &verify_worker.worker;
+    let datastore = &verify_worker.datastore;
+
+    let mut failed_dirs = Vec::new();
+
+    for ns in datastore.recursive_iter_backup_ns_ok(BackupNamespace::root(), None)? {
+        for group in datastore.iter_backup_groups_ok(ns)? {
+            let list = match group.list_backups() {
+                Ok(list) => list,
+                Err(err) => {
+                    task_log!(
+                        worker,
+                        "unable to list backups of {} - {}",
+                        group.group(),
+                        err
+                    );
+                    continue;
+                }
+            };
+
+            for info in list {
+                worker.check_abort()?;
+                worker.fail_on_shutdown()?;
+
+                let backup_dir = info.backup_dir;
+                let affected = match index_files_referencing(datastore, &backup_dir, digests) {
+                    Ok(affected) => affected,
+                    Err(err) => {
+                        task_log!(
+                            worker,
+                            "unable to check {} - {}",
+                            print_ns_and_snapshot(backup_dir.backup_ns(), backup_dir.as_ref()),
+                            err,
+                        );
+                        continue;
+                    }
+                };
+                if affected.is_empty() {
+                    continue;
+                }
+
+                let snapshot = print_ns_and_snapshot(backup_dir.backup_ns(), backup_dir.as_ref());
+                task_log!(
+                    worker,
+                    "{} references corrupt chunks in {}",
+                    snapshot,
+                    affected.join(", "),
+                );
+
+                if let Err(err) = mark_snapshot_failed(&backup_dir, upid) {
+                    task_log!(worker, "unable to mark {} as failed - {}", snapshot, err);
+                }
+                failed_dirs.push(snapshot);
+            }
+        }
+    }
+
+    Ok(failed_dirs)
+}
+
+/// Returns the names of the index files of a snapshot referencing one of `digests`.
+fn index_files_referencing(
+    datastore: &DataStore,
+    backup_dir: &BackupDir,
+    digests: &HashSet<[u8; 32]>,
+) -> Result<Vec<String>, Error> {
+    let (manifest, _) = backup_dir.load_manifest()?;
+
+    let mut affected = Vec::new();
+    for info in manifest.files() {
+        let mut path = backup_dir.relative_path();
+        path.push(&info.filename);
+
+        let index: Box<dyn IndexFile> = match archive_type(&info.filename)? {
+            ArchiveType::FixedIndex => Box::new(datastore.open_fixed_reader(&path)?),
+            ArchiveType::DynamicIndex => Box::new(datastore.open_dynamic_reader(&path)?),
+            ArchiveType::Blob => continue,
+        };
+
+        let referenced =
+            (0..index.index_count()).any(|pos| digests.contains(index.index_digest(pos).unwrap()));
+        if referenced {
+            affected.push(info.filename.clone());
+        }
+    }
+
+    Ok(affected)
+}
+
+fn mark_snapshot_failed(backup_dir: &BackupDir, upid: &UPID) -> Result<(), Error> {
+    let _snap_lock = lock_dir_noblock_shared(
+        &backup_dir.full_path(),
+        "snapshot",
+        "locked by another operation",
+    )?;
+
+    let verify_state = SnapshotVerifyState {
+        state: VerifyState::Failed,
+        upid: upid.clone(),
+        coverage: None,
+    };
+    let verify_state = serde_json::to_value(verify_state)?;
+    backup_dir
+        .update_manifest(|manifest| {
+            manifest.unprotected["verify_state"] = verify_state;
+        })
+        .map_err(|err| format_err!("unable to update manifest blob - {}", err))
+}
+
 /// Filter out any snapshot from being (re-)verified where this fn returns false.
 pub fn verify_filter(
     ignore_verified_snapshots: bool,
@@ -601,6 +1042,7 @@ pub fn verify_filter(
     let raw_verify_state = manifest.unprotected["verify_state"].clone();
     match serde_json::from_value::<SnapshotVerifyState>(raw_verify_state) {
         Err(_) => true, // no last verification, always include
+        Ok(last_verify) if last_verify.coverage.is_some() => true, // only partially verified
         Ok(last_verify) => {
             match outdated_after {
                 None => false, // never re-verify if ignored and no max age
@@ -20,7 +20,7 @@ use proxmox_sys::linux::procfs::{Loadavg, ProcFsMemInfo, ProcFsNetDev, ProcFsSta
 use proxmox_sys::logrotate::LogRotate;
 use proxmox_sys::{task_log, task_warn};
 
-use pbs_datastore::DataStore;
+use pbs_datastore::{is_datastore_mounted, DataStore};
 
 use proxmox_rest_server::{
     cleanup_old_tasks, cookie_from_header, rotate_task_log_archive, ApiConfig, RestEnvironment,
@@ -43,8 +43,8 @@ use pbs_buildcfg::configdir;
 use proxmox_time::CalendarEvent;
 
 use pbs_api_types::{
-    Authid, DataStoreConfig, Operation, PruneJobConfig, SyncJobConfig, TapeBackupJobConfig,
-    VerificationJobConfig,
+    Authid, DataStoreConfig, Operation, PruneJobConfig, ScsiTapeChanger, SyncJobConfig,
+    TapeBackupJobConfig, VerificationJobConfig,
 };
 
 use proxmox_rest_server::daemon;
@@ -58,6 +58,7 @@ use proxmox_backup::tools::{
 
 use proxmox_backup::api2::pull::do_sync_job;
 use proxmox_backup::api2::tape::backup::do_tape_backup_job;
+use proxmox_backup::api2::tape::changer::do_tape_reconcile_job;
 use proxmox_backup::server::do_prune_job;
 use proxmox_backup::server::do_verification_job;
 
@@ -428,11 +429,23 @@ async fn schedule_tasks() -> Result<(), Error> {
     schedule_datastore_sync_jobs().await;
     schedule_datastore_verify_jobs().await;
     schedule_tape_backup_jobs().await;
+    schedule_tape_reconcile_jobs().await;
     schedule_task_log_rotate().await;
 
     Ok(())
 }
 
+/// Removable datastores which are currently unplugged are skipped by scheduled jobs.
+fn datastore_unmounted(store: &str) -> bool {
+    let store_config: DataStoreConfig = match pbs_config::datastore::config()
+        .and_then(|(config, _digest)| config.lookup("datastore", store))
+    {
+        Ok(store_config) => store_config,
+        Err(_) => return false, // let the job report the error
+    };
+    !is_datastore_mounted(&store_config)
+}
+
 async fn schedule_datastore_garbage_collection() {
     let config = match pbs_config::datastore::config() {
         Err(err) => {
@@ -451,6 +464,11 @@ async fn schedule_datastore_garbage_collection() {
             }
         };
 
+        // removable datastores which are currently unplugged are simply skipped
+        if !is_datastore_mounted(&store_config) {
+            continue;
+        }
+
         let event_str = match store_config.gc_schedule {
             Some(event_str) => event_str,
             None => continue,
@@ -509,7 +527,7 @@ async fn schedule_datastore_garbage_collection() {
             Err(_) => continue, // could not get lock
         };
 
-        let datastore = match DataStore::lookup_datastore(&store, Some(Operation::Write)) {
+        let datastore = match DataStore::lookup_datastore(&store, Some(Operation::Delete)) {
             Ok(datastore) => datastore,
             Err(err) => {
                 log::warn!("skipping scheduled GC on {store}, could look it up - {err}");
@@ -556,6 +574,10 @@ async fn schedule_datastore_prune_jobs() {
             continue; // no 'keep' values set, keep all
         }
 
+        if datastore_unmounted(&job_config.store) {
+            continue;
+        }
+
         let worker_type = "prunejob";
         let auth_id = Authid::root_auth_id().clone();
         if check_schedule(worker_type, &job_config.schedule, &job_id) {
@@ -599,6 +621,13 @@ async fn schedule_datastore_sync_jobs() {
             None => continue,
         };
 
+        // local sync jobs use a datastore as source
+        if datastore_unmounted(&job_config.store)
+            || (job_config.remote.is_none() && datastore_unmounted(&job_config.remote_store))
+        {
+            continue;
+        }
+
         let worker_type = "syncjob";
         if check_schedule(worker_type, &event_str, &job_id) {
             let job = match Job::new(worker_type, &job_id) {
@@ -635,6 +664,10 @@ async fn schedule_datastore_verify_jobs() {
             None => continue,
         };
 
+        if datastore_unmounted(&job_config.store) {
+            continue;
+        }
+
         let worker_type = "verificationjob";
         let auth_id = Authid::root_auth_id().clone();
         if check_schedule(worker_type, &event_str, &job_id) {
@@ -671,6 +704,10 @@ async fn schedule_tape_backup_jobs() {
             None => continue,
         };
 
+        if datastore_unmounted(&job_config.setup.store) {
+            continue;
+        }
+
         let worker_type = "tape-backup-job";
         let auth_id = Authid::root_auth_id().clone();
         if check_schedule(worker_type, &event_str, &job_id) {
@@ -687,6 +724,52 @@ async fn schedule_tape_backup_jobs() {
     }
 }
 
+async fn schedule_tape_reconcile_jobs() {
+    let config = match pbs_config::drive::config() {
+        Err(err) => {
+            eprintln!("unable to read drive config - {err}");
+            return;
+        }
+        Ok((config, _digest)) => config,
+    };
+    let changer_list: Vec<ScsiTapeChanger> = match config.convert_to_typed_array("changer") {
+        Ok(list) => list,
+        Err(err) => {
+            eprintln!("tape changer config from_value failed - {err}");
+            return;
+        }
+    };
+    for changer in changer_list {
+        let event_str = match changer.reconcile_schedule {
+            Some(ref event_str) => event_str.clone(),
+            None => continue,
+        };
+
+        let worker_type = "tape-reconcile";
+        let auth_id = Authid::root_auth_id().clone();
+        if check_schedule(worker_type, &event_str, &changer.name) {
+            let job = match Job::new(worker_type, &changer.name) {
+                Ok(job) => job,
+                Err(_) => continue, // could not get lock
+            };
+            if let Err(err) = do_tape_reconcile_job(
+                job,
+                changer.name.clone(),
+                changer.reconcile_drive,
+                false,
+                &auth_id,
+                Some(event_str),
+                false,
+            ) {
+                eprintln!(
+                    "unable to start tape reconcile job for changer {} - {err}",
+                    changer.name
+                );
+            }
+        };
+    }
+}
+
 async fn schedule_task_log_rotate() {
     let worker_type = "logrotate";
     let job_id = "access-log_and_task-archive";
@@ -766,7 +849,7 @@ async fn schedule_task_log_rotate() {
                     pbs_buildcfg::API_AUTH_LOG_FN,
                     true,
                     Some(max_files),
-                    Some(options),
+                    Some(options.clone()),
                 )?;
 
                 if logrotate.rotate(max_size)? {
@@ -777,6 +860,20 @@ async fn schedule_task_log_rotate() {
                     task_log!(worker, "API authentication log was not rotated");
                 }
 
+                // the audit log is opened for every entry, so no re-open is necessary
+                let mut logrotate = LogRotate::new(
+                    pbs_buildcfg::API_AUDIT_LOG_FN,
+                    true,
+                    Some(max_files),
+                    Some(options),
+                )?;
+
+                if logrotate.rotate(max_size)? {
+                    task_log!(worker, "API audit log was rotated");
+                } else {
+                    task_log!(worker, "API audit log was not rotated");
+                }
+
                 if has_rotated {
                     task_log!(worker, "cleaning up old task logs");
                     if let Err(err) = cleanup_old_tasks(&worker, true) {
@@ -886,13 +983,6 @@ async fn run_stat_generator() {
 async fn send_data_to_metric_servers(
     stats: Arc<(HostStats, DiskStat, Vec<DiskStat>)>,
 ) -> Result<(), Error> {
-    let (config, _digest) = pbs_config::metrics::config()?;
-    let channel_list = get_metric_server_connections(config)?;
-
-    if channel_list.is_empty() {
-        return Ok(());
-    }
-
     let ctime = proxmox_time::epoch_i64();
     let nodename = proxmox_sys::nodename();
 
@@ -949,66 +1039,7 @@ async fn send_data_to_metric_servers(
         ));
     }
 
-    // we must have a concrete functions, because the inferred lifetime from a
-    // closure is not general enough for the tokio::spawn call we are in here...
-    fn map_fn(item: &(proxmox_metrics::Metrics, String)) -> &proxmox_metrics::Metrics {
-        &item.0
-    }
-
-    let results =
-        proxmox_metrics::send_data_to_channels(&values, channel_list.iter().map(map_fn)).await;
-    for (res, name) in results
-        .into_iter()
-        .zip(channel_list.iter().map(|(_, name)| name))
-    {
-        if let Err(err) = res {
-            log::error!("error sending into channel of {name}: {err}");
-        }
-    }
-
-    futures::future::join_all(channel_list.into_iter().map(|(channel, name)| async move {
-        if let Err(err) = channel.join().await {
-            log::error!("error sending to metric server {name}: {err}");
-        }
-    }))
-    .await;
-
-    Ok(())
-}
-
-/// Get the metric server connections from a config
-pub fn get_metric_server_connections(
-    metric_config: proxmox_section_config::SectionConfigData,
-) -> Result<Vec<(proxmox_metrics::Metrics, String)>, Error> {
-    let mut res = Vec::new();
-
-    for config in
-        metric_config.convert_to_typed_array::<pbs_api_types::InfluxDbUdp>("influxdb-udp")?
-    {
-        if !config.enable {
-            continue;
-        }
-        let future = proxmox_metrics::influxdb_udp(&config.host, config.mtu);
-        res.push((future, config.name));
-    }
-
-    for config in
-        metric_config.convert_to_typed_array::<pbs_api_types::InfluxDbHttp>("influxdb-http")?
-    {
-        if !config.enable {
-            continue;
-        }
-        let future = proxmox_metrics::influxdb_http(
-            &config.url,
-            config.organization.as_deref().unwrap_or("proxmox"),
-            config.bucket.as_deref().unwrap_or("proxmox"),
-            config.token.as_deref(),
-            config.verify_tls.unwrap_or(true),
-            config.max_body_size.unwrap_or(25_000_000),
-        )?;
-        res.push((future, config.name));
-    }
-    Ok(res)
+    server::metric_server::send_data_to_metric_servers(&values).await
 }
 
 struct HostStats {
@@ -1105,6 +1136,7 @@ fn collect_disk_stats_sync() -> (DiskStat, Vec<DiskStat>) {
                 if config
                     .get_maintenance_mode()
                     .map_or(false, |mode| mode.check(Some(Operation::Read)).is_err())
+                    || !is_datastore_mounted(&config)
                 {
                     continue;
                 }
@@ -19,9 +19,10 @@ use pbs_config::drive::complete_drive_name;
 use pbs_config::media_pool::complete_pool_name;
 
 use pbs_api_types::{
-    Authid, BackupNamespace, GroupListItem, Userid, DATASTORE_MAP_LIST_SCHEMA, DATASTORE_SCHEMA,
-    DRIVE_NAME_SCHEMA, GROUP_FILTER_LIST_SCHEMA, MEDIA_LABEL_SCHEMA, MEDIA_POOL_NAME_SCHEMA,
-    NS_MAX_DEPTH_SCHEMA, TAPE_RESTORE_NAMESPACE_SCHEMA, TAPE_RESTORE_SNAPSHOT_SCHEMA,
+    Authid, BackupNamespace, GroupListItem, TapeFileRestoreFormat, Userid,
+    DATASTORE_MAP_LIST_SCHEMA, DATASTORE_SCHEMA, DRIVE_NAME_SCHEMA, GROUP_FILTER_LIST_SCHEMA,
+    MEDIA_LABEL_SCHEMA, MEDIA_POOL_NAME_SCHEMA, NS_MAX_DEPTH_SCHEMA,
+    TAPE_FILE_RESTORE_TARGET_SCHEMA, TAPE_RESTORE_NAMESPACE_SCHEMA, TAPE_RESTORE_SNAPSHOT_SCHEMA,
 };
 use pbs_tape::{BlockReadError, MediaContentHeader, PROXMOX_BACKUP_CONTENT_HEADER_MAGIC_1_0};
 
@@ -142,6 +143,55 @@ async fn format_media(mut param: Value) -> Result<(), Error> {
     Ok(())
 }
 
+#[api(
+    input: {
+        properties: {
+            drive: {
+                schema: DRIVE_NAME_SCHEMA,
+                optional: true,
+            },
+            "label-text": {
+                schema: MEDIA_LABEL_SCHEMA,
+            },
+            snapshots: {
+                description: "List of snapshots to export.",
+                type: Array,
+                items: {
+                    schema: TAPE_RESTORE_SNAPSHOT_SCHEMA,
+                },
+            },
+            force: {
+                description: "Overwrite media which is in the inventory or has an \
+                              unreadable label.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+       },
+    },
+)]
+/// Export snapshots as plain files to a new LTFS volume
+async fn export_ltfs(mut param: Value) -> Result<(), Error> {
+    let output_format = extract_output_format(&mut param);
+
+    let (config, _digest) = pbs_config::drive::config()?;
+
+    let drive = extract_drive_name(&mut param, &config)?;
+
+    let client = connect_to_localhost()?;
+
+    let path = format!("api2/json/tape/drive/{}/export-ltfs", drive);
+    let result = client.post(&path, Some(param)).await?;
+
+    view_task_result(&client, result, &output_format).await?;
+
+    Ok(())
+}
+
 #[api(
     input: {
         properties: {
@@ -949,6 +999,166 @@ async fn restore(mut param: Value) -> Result<(), Error> {
     Ok(())
 }
 
+#[api(
+   input: {
+        properties: {
+            "media-set": {
+                description: "Media set UUID.",
+                type: String,
+            },
+            snapshot: {
+                schema: TAPE_RESTORE_SNAPSHOT_SCHEMA,
+            },
+            filepath: {
+                description: "Path of the file or directory, starting with the archive name \
+                              (e.g. '/root.pxar.didx/etc/hosts').",
+                type: String,
+            },
+            target: {
+                schema: TAPE_FILE_RESTORE_TARGET_SCHEMA,
+            },
+            format: {
+                type: TapeFileRestoreFormat,
+                optional: true,
+            },
+            drive: {
+                schema: DRIVE_NAME_SCHEMA,
+                optional: true,
+            },
+            "notify-user": {
+                type: Userid,
+                optional: true,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        },
+    },
+)]
+/// Restore a single file or directory from a media-set
+async fn restore_file(mut param: Value) -> Result<(), Error> {
+    let output_format = extract_output_format(&mut param);
+
+    let (config, _digest) = pbs_config::drive::config()?;
+
+    param["drive"] = extract_drive_name(&mut param, &config)?.into();
+
+    let client = connect_to_localhost()?;
+
+    let result = client
+        .post("api2/json/tape/restore-file", Some(param))
+        .await?;
+
+    view_task_result(&client, result, &output_format).await?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            pool: {
+                schema: MEDIA_POOL_NAME_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        },
+    },
+)]
+/// List the single file restore results of a media pool
+async fn list_restore_results(pool: String, param: Value) -> Result<(), Error> {
+    let output_format = get_output_format(&param);
+
+    let client = connect_to_localhost()?;
+
+    let path = format!("api2/json/tape/restore-file/{}", pool);
+    let mut result = client.get(&path, None).await?;
+    let mut data = result["data"].take();
+
+    let info = &api2::tape::file_restore::API_METHOD_LIST_RESTORE_RESULTS;
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("target"))
+        .column(ColumnConfig::new("size").renderer(render_bytes_human_readable))
+        .column(ColumnConfig::new("mtime").renderer(render_epoch));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(())
+}
+
+#[api(
+   input: {
+        properties: {
+            "media-set": {
+                description: "Media set UUID.",
+                type: String,
+            },
+            pool: {
+                schema: MEDIA_POOL_NAME_SCHEMA,
+            },
+            drive: {
+                schema: DRIVE_NAME_SCHEMA,
+                optional: true,
+            },
+            "source-drive": {
+                schema: DRIVE_NAME_SCHEMA,
+                optional: true,
+            },
+            "spool-dir": {
+                description: "Directory used to stage the content of a source media, \
+                              when there is no second drive.",
+                type: String,
+                optional: true,
+            },
+            "notify-user": {
+                type: Userid,
+                optional: true,
+            },
+            "eject-media": {
+                description: "Eject media upon job completion.",
+                type: bool,
+                optional: true,
+            },
+            "export-media-set": {
+                description: "Export media set upon job completion.",
+                type: bool,
+                optional: true,
+            },
+            "allow-unencrypted": {
+                description: "Allow to copy an encrypted media set to a pool without \
+                              encryption key, writing the data unencrypted.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        },
+    },
+)]
+/// Duplicate a media set to another media pool
+async fn duplicate(mut param: Value) -> Result<(), Error> {
+    let output_format = extract_output_format(&mut param);
+
+    let (config, _digest) = pbs_config::drive::config()?;
+
+    param["drive"] = extract_drive_name(&mut param, &config)?.into();
+
+    let client = connect_to_localhost()?;
+
+    let result = client.post("api2/json/tape/duplicate", Some(param)).await?;
+
+    view_task_result(&client, result, &output_format).await?;
+
+    Ok(())
+}
+
 #[api(
     input: {
         properties: {
@@ -1017,6 +1227,35 @@ fn main() {
                 .completion_cb("media-set", complete_media_set_uuid)
                 .completion_cb("snapshots", complete_media_set_snapshots),
         )
+        .insert(
+            "restore-file",
+            CliCommand::new(&API_METHOD_RESTORE_FILE)
+                .arg_param(&["media-set", "snapshot", "filepath", "target"])
+                .completion_cb("media-set", complete_media_set_uuid)
+                .completion_cb("snapshot", complete_media_set_snapshots)
+                .completion_cb("drive", complete_drive_name),
+        )
+        .insert(
+            "restore-file-list",
+            CliCommand::new(&API_METHOD_LIST_RESTORE_RESULTS)
+                .arg_param(&["pool"])
+                .completion_cb("pool", complete_pool_name),
+        )
+        .insert(
+            "restore-file-remove",
+            CliCommand::new(&api2::tape::file_restore::API_METHOD_DELETE_RESTORE_RESULT)
+                .arg_param(&["pool", "target"])
+                .completion_cb("pool", complete_pool_name),
+        )
+        .insert(
+            "duplicate",
+            CliCommand::new(&API_METHOD_DUPLICATE)
+                .arg_param(&["media-set", "pool"])
+                .completion_cb("media-set", complete_media_set_uuid)
+                .completion_cb("pool", complete_pool_name)
+                .completion_cb("drive", complete_drive_name)
+                .completion_cb("source-drive", complete_drive_name),
+        )
         .insert(
             "barcode-label",
             CliCommand::new(&API_METHOD_BARCODE_LABEL_MEDIA)
@@ -1043,6 +1282,12 @@ fn main() {
             "format",
             CliCommand::new(&API_METHOD_FORMAT_MEDIA).completion_cb("drive", complete_drive_name),
         )
+        .insert(
+            "export-ltfs",
+            CliCommand::new(&API_METHOD_EXPORT_LTFS)
+                .arg_param(&["label-text", "snapshots"])
+                .completion_cb("drive", complete_drive_name),
+        )
         .insert(
             "eject",
             CliCommand::new(&API_METHOD_EJECT_MEDIA).completion_cb("drive", complete_drive_name),
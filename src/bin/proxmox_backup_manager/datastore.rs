@@ -4,7 +4,9 @@ use serde_json::Value;
 use proxmox_router::{cli::*, ApiHandler, RpcEnvironment};
 use proxmox_schema::api;
 
-use pbs_api_types::{DataStoreConfig, DATASTORE_SCHEMA, PROXMOX_CONFIG_DIGEST_SCHEMA};
+use pbs_api_types::{
+    DataStoreConfig, BACKING_DEVICE_SCHEMA, DATASTORE_SCHEMA, PROXMOX_CONFIG_DIGEST_SCHEMA,
+};
 use pbs_client::view_task_result;
 
 use proxmox_backup::api2;
@@ -139,6 +141,128 @@ async fn delete_datastore(mut param: Value, rpcenv: &mut dyn RpcEnvironment) ->
     Ok(())
 }
 
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        },
+    },
+)]
+/// Mount the backing device of a removable datastore.
+async fn mount_datastore(mut param: Value) -> Result<(), Error> {
+    let output_format = extract_output_format(&mut param);
+    let store = pbs_tools::json::required_string_param(&param, "store")?;
+
+    let client = connect_to_localhost()?;
+
+    let path = format!("api2/json/admin/datastore/{store}/mount");
+    let result = client.post(&path, None).await?;
+
+    view_task_result(&client, result, &output_format).await?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        },
+    },
+)]
+/// Unmount the backing device of a removable datastore, waits for active operations to finish.
+async fn unmount_datastore(mut param: Value) -> Result<(), Error> {
+    let output_format = extract_output_format(&mut param);
+    let store = pbs_tools::json::required_string_param(&param, "store")?;
+
+    let client = connect_to_localhost()?;
+
+    let path = format!("api2/json/admin/datastore/{store}/unmount");
+    let result = client.post(&path, None).await?;
+
+    view_task_result(&client, result, &output_format).await?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        },
+    },
+)]
+/// Compute the exclusive and shared space usage of all snapshots and groups of a datastore.
+async fn scan_space_usage(mut param: Value) -> Result<(), Error> {
+    let output_format = extract_output_format(&mut param);
+    let store = pbs_tools::json::required_string_param(&param, "store")?;
+
+    let client = connect_to_localhost()?;
+
+    let path = format!("api2/json/admin/datastore/{store}/space-usage");
+    let result = client.post(&path, None).await?;
+
+    view_task_result(&client, result, &output_format).await?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            uuid: {
+                schema: BACKING_DEVICE_SCHEMA,
+            },
+        },
+    },
+)]
+/// Mount the removable datastore backed by the device with the given filesystem UUID.
+///
+/// This is called by udev whenever a block device with a filesystem appears, devices which do not
+/// back any datastore are silently ignored.
+async fn uuid_mount(uuid: String) -> Result<(), Error> {
+    let (config, _digest) = pbs_config::datastore::config()?;
+    let list: Vec<DataStoreConfig> = config.convert_to_typed_array("datastore")?;
+
+    let store = match list
+        .into_iter()
+        .find(|store| store.backing_device.as_deref() == Some(uuid.as_str()))
+    {
+        Some(store) => store,
+        None => return Ok(()),
+    };
+
+    let client = connect_to_localhost()?;
+
+    let path = format!("api2/json/admin/datastore/{}/mount", store.name);
+    let result = client.post(&path, None).await?;
+
+    view_task_result(&client, result, "text").await?;
+
+    Ok(())
+}
+
 pub fn datastore_commands() -> CommandLineInterface {
     let cmd_def = CliCommandMap::new()
         .insert("list", CliCommand::new(&API_METHOD_LIST_DATASTORES))
@@ -171,6 +295,28 @@ pub fn datastore_commands() -> CommandLineInterface {
             CliCommand::new(&API_METHOD_DELETE_DATASTORE)
                 .arg_param(&["name"])
                 .completion_cb("name", pbs_config::datastore::complete_datastore_name),
+        )
+        .insert(
+            "mount",
+            CliCommand::new(&API_METHOD_MOUNT_DATASTORE)
+                .arg_param(&["store"])
+                .completion_cb("store", pbs_config::datastore::complete_datastore_name),
+        )
+        .insert(
+            "space-usage",
+            CliCommand::new(&API_METHOD_SCAN_SPACE_USAGE)
+                .arg_param(&["store"])
+                .completion_cb("store", pbs_config::datastore::complete_datastore_name),
+        )
+        .insert(
+            "unmount",
+            CliCommand::new(&API_METHOD_UNMOUNT_DATASTORE)
+                .arg_param(&["store"])
+                .completion_cb("store", pbs_config::datastore::complete_datastore_name),
+        )
+        .insert(
+            "uuid-mount",
+            CliCommand::new(&API_METHOD_UUID_MOUNT).arg_param(&["uuid"]),
         );
 
     cmd_def.into()
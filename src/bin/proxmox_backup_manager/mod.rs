@@ -12,10 +12,16 @@ mod ldap;
 pub use ldap::*;
 mod network;
 pub use network::*;
+mod notifications;
+pub use notifications::*;
 mod prune;
 pub use prune::*;
+mod quota;
+pub use quota::*;
 mod remote;
 pub use remote::*;
+mod s3;
+pub use s3::*;
 mod sync;
 pub use sync::*;
 mod verify;
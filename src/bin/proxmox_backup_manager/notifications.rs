@@ -0,0 +1,221 @@
+use anyhow::Error;
+use serde_json::Value;
+
+use proxmox_router::{cli::*, ApiHandler, ApiMethod, RpcEnvironment};
+use proxmox_schema::api;
+
+use pbs_api_types::NOTIFICATION_ID_SCHEMA;
+use pbs_config::notifications::{complete_matcher_name, complete_target_name};
+
+use proxmox_backup::api2;
+use proxmox_backup::api2::config::notifications::{gotify, matchers, sendmail, smtp, webhook};
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List all notification targets.
+fn list_targets(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::notifications::API_METHOD_LIST_TARGETS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("name"))
+        .column(ColumnConfig::new("type"))
+        .column(ColumnConfig::new("disable"))
+        .column(ColumnConfig::new("comment"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List notification matchers.
+fn list_matchers(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+    let output_format = get_output_format(&param);
+
+    let info = &matchers::API_METHOD_LIST_MATCHERS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("name"))
+        .column(ColumnConfig::new("match-severity"))
+        .column(ColumnConfig::new("match-field"))
+        .column(ColumnConfig::new("mode"))
+        .column(ColumnConfig::new("target"))
+        .column(ColumnConfig::new("disable"))
+        .column(ColumnConfig::new("comment"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_ID_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show notification matcher configuration.
+fn show_matcher(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+    let output_format = get_output_format(&param);
+
+    let info = &matchers::API_METHOD_READ_MATCHER;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+/// Subcommands to show and manage the targets of one type.
+fn target_type_commands(
+    list: &'static ApiMethod,
+    read: &'static ApiMethod,
+    create: &'static ApiMethod,
+    update: &'static ApiMethod,
+    delete: &'static ApiMethod,
+) -> CliCommandMap {
+    CliCommandMap::new()
+        .insert("list", CliCommand::new(list))
+        .insert(
+            "show",
+            CliCommand::new(read)
+                .arg_param(&["name"])
+                .completion_cb("name", complete_target_name),
+        )
+        .insert("create", CliCommand::new(create).arg_param(&["name"]))
+        .insert(
+            "update",
+            CliCommand::new(update)
+                .arg_param(&["name"])
+                .completion_cb("name", complete_target_name),
+        )
+        .insert(
+            "remove",
+            CliCommand::new(delete)
+                .arg_param(&["name"])
+                .completion_cb("name", complete_target_name),
+        )
+}
+
+pub fn notification_commands() -> CommandLineInterface {
+    let target_cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_TARGETS))
+        .insert(
+            "test",
+            CliCommand::new(&api2::config::notifications::API_METHOD_TEST_TARGET)
+                .arg_param(&["name"])
+                .completion_cb("name", complete_target_name),
+        );
+
+    let matcher_cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_MATCHERS))
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_MATCHER)
+                .arg_param(&["name"])
+                .completion_cb("name", complete_matcher_name),
+        )
+        .insert(
+            "create",
+            CliCommand::new(&matchers::API_METHOD_CREATE_MATCHER)
+                .arg_param(&["name"])
+                .completion_cb("target", complete_target_name),
+        )
+        .insert(
+            "update",
+            CliCommand::new(&matchers::API_METHOD_UPDATE_MATCHER)
+                .arg_param(&["name"])
+                .completion_cb("name", complete_matcher_name)
+                .completion_cb("target", complete_target_name),
+        )
+        .insert(
+            "remove",
+            CliCommand::new(&matchers::API_METHOD_DELETE_MATCHER)
+                .arg_param(&["name"])
+                .completion_cb("name", complete_matcher_name),
+        );
+
+    let cmd_def = CliCommandMap::new()
+        .insert("target", target_cmd_def)
+        .insert("matcher", matcher_cmd_def)
+        .insert(
+            "sendmail",
+            target_type_commands(
+                &sendmail::API_METHOD_LIST_SENDMAIL_TARGETS,
+                &sendmail::API_METHOD_READ_SENDMAIL_TARGET,
+                &sendmail::API_METHOD_CREATE_SENDMAIL_TARGET,
+                &sendmail::API_METHOD_UPDATE_SENDMAIL_TARGET,
+                &sendmail::API_METHOD_DELETE_SENDMAIL_TARGET,
+            ),
+        )
+        .insert(
+            "smtp",
+            target_type_commands(
+                &smtp::API_METHOD_LIST_SMTP_TARGETS,
+                &smtp::API_METHOD_READ_SMTP_TARGET,
+                &smtp::API_METHOD_CREATE_SMTP_TARGET,
+                &smtp::API_METHOD_UPDATE_SMTP_TARGET,
+                &smtp::API_METHOD_DELETE_SMTP_TARGET,
+            ),
+        )
+        .insert(
+            "gotify",
+            target_type_commands(
+                &gotify::API_METHOD_LIST_GOTIFY_TARGETS,
+                &gotify::API_METHOD_READ_GOTIFY_TARGET,
+                &gotify::API_METHOD_CREATE_GOTIFY_TARGET,
+                &gotify::API_METHOD_UPDATE_GOTIFY_TARGET,
+                &gotify::API_METHOD_DELETE_GOTIFY_TARGET,
+            ),
+        )
+        .insert(
+            "webhook",
+            target_type_commands(
+                &webhook::API_METHOD_LIST_WEBHOOK_TARGETS,
+                &webhook::API_METHOD_READ_WEBHOOK_TARGET,
+                &webhook::API_METHOD_CREATE_WEBHOOK_TARGET,
+                &webhook::API_METHOD_UPDATE_WEBHOOK_TARGET,
+                &webhook::API_METHOD_DELETE_WEBHOOK_TARGET,
+            ),
+        );
+
+    cmd_def.into()
+}
@@ -0,0 +1,158 @@
+use anyhow::Error;
+use serde_json::Value;
+
+use proxmox_router::{cli::*, ApiHandler, RpcEnvironment};
+use proxmox_schema::api;
+
+use pbs_api_types::{BackupNamespace, DATASTORE_SCHEMA, NS_MAX_DEPTH_SCHEMA, QUOTA_ID_SCHEMA};
+use pbs_tools::format::render_bytes_human_readable;
+
+use proxmox_backup::api2;
+use proxmox_backup::client_helpers::connect_to_localhost;
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List configured quotas.
+fn list_quotas(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::quota::API_METHOD_LIST_QUOTAS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("id"))
+        .column(ColumnConfig::new("store"))
+        .column(ColumnConfig::new("ns"))
+        .column(ColumnConfig::new("owner"))
+        .column(ColumnConfig::new("limit"))
+        .column(ColumnConfig::new("comment"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            id: {
+                schema: QUOTA_ID_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show quota configuration
+fn show_quota(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::quota::API_METHOD_READ_QUOTA;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                type: BackupNamespace,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show the logical and deduplicated space usage of namespaces.
+async fn show_namespace_usage(mut param: Value) -> Result<Value, Error> {
+    let output_format = extract_output_format(&mut param);
+    let store = param["store"].as_str().unwrap().to_owned();
+    param.as_object_mut().unwrap().remove("store");
+
+    let client = connect_to_localhost()?;
+
+    let path = format!("api2/json/admin/datastore/{store}/namespace-usage");
+    let mut result = client.get(&path, Some(param)).await?;
+
+    let mut data = result["data"].take();
+
+    let info = &api2::admin::datastore::API_METHOD_NAMESPACE_USAGE;
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("ns"))
+        .column(ColumnConfig::new("snapshot-count"))
+        .column(ColumnConfig::new("logical-size").renderer(render_bytes_human_readable))
+        .column(ColumnConfig::new("unique-size").renderer(render_bytes_human_readable))
+        .column(ColumnConfig::new("quota"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+pub fn quota_commands() -> CommandLineInterface {
+    let cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_QUOTAS))
+        .insert(
+            "usage",
+            CliCommand::new(&API_METHOD_SHOW_NAMESPACE_USAGE)
+                .arg_param(&["store"])
+                .completion_cb("store", pbs_config::datastore::complete_datastore_name),
+        )
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_QUOTA)
+                .arg_param(&["id"])
+                .completion_cb("id", pbs_config::quota::complete_quota_id),
+        )
+        .insert(
+            "create",
+            CliCommand::new(&api2::config::quota::API_METHOD_CREATE_QUOTA)
+                .arg_param(&["id"])
+                .completion_cb("store", pbs_config::datastore::complete_datastore_name),
+        )
+        .insert(
+            "update",
+            CliCommand::new(&api2::config::quota::API_METHOD_UPDATE_QUOTA)
+                .arg_param(&["id"])
+                .completion_cb("id", pbs_config::quota::complete_quota_id),
+        )
+        .insert(
+            "remove",
+            CliCommand::new(&api2::config::quota::API_METHOD_DELETE_QUOTA)
+                .arg_param(&["id"])
+                .completion_cb("id", pbs_config::quota::complete_quota_id),
+        );
+
+    cmd_def.into()
+}
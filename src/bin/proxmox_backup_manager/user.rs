@@ -231,6 +231,7 @@ pub fn user_commands() -> CommandLineInterface {
                 .completion_cb("token-name", pbs_config::user::complete_token_name),
         )
         .insert("tfa", tfa_commands())
+        .insert("client-cert", client_cert_commands())
         .insert(
             "permissions",
             CliCommand::new(&API_METHOD_LIST_PERMISSIONS)
@@ -265,3 +266,24 @@ fn tfa_commands() -> CommandLineInterface {
         )
         .into()
 }
+
+fn client_cert_commands() -> CommandLineInterface {
+    CliCommandMap::new()
+        .insert(
+            "list",
+            CliCommand::new(&api2::access::client_cert::API_METHOD_LIST_CLIENT_CERTS),
+        )
+        .insert(
+            "add",
+            CliCommand::new(&api2::access::client_cert::API_METHOD_CREATE_CLIENT_CERT)
+                .arg_param(&["id"])
+                .completion_cb("userid", pbs_config::user::complete_userid),
+        )
+        .insert(
+            "remove",
+            CliCommand::new(&api2::access::client_cert::API_METHOD_DELETE_CLIENT_CERT)
+                .arg_param(&["id"])
+                .completion_cb("id", pbs_config::client_cert::complete_client_cert_id),
+        )
+        .into()
+}
@@ -7,11 +7,12 @@ use proxmox_section_config::SectionConfigData;
 
 use pbs_config::drive::{complete_changer_name, complete_drive_name};
 
-use pbs_api_types::CHANGER_NAME_SCHEMA;
+use pbs_api_types::{CHANGER_NAME_SCHEMA, DRIVE_NAME_SCHEMA};
+use pbs_client::view_task_result;
 
 use pbs_tape::linux_list_drives::complete_changer_path;
 
-use proxmox_backup::{api2, tape::drive::media_changer};
+use proxmox_backup::{api2, client_helpers::connect_to_localhost, tape::drive::media_changer};
 
 pub fn lookup_changer_name(param: &Value, config: &SectionConfigData) -> Result<String, Error> {
     if let Some(name) = param["name"].as_str() {
@@ -70,6 +71,13 @@ pub fn changer_commands() -> CommandLineInterface {
             CliCommand::new(&API_METHOD_TRANSFER)
                 .arg_param(&["name"])
                 .completion_cb("name", complete_changer_name),
+        )
+        .insert(
+            "reconcile",
+            CliCommand::new(&API_METHOD_RECONCILE)
+                .arg_param(&["name"])
+                .completion_cb("name", complete_changer_name)
+                .completion_cb("drive", complete_drive_name),
         );
 
     cmd_def.into()
@@ -161,7 +169,9 @@ fn get_config(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<(), Error
     let options = default_table_format_options()
         .column(ColumnConfig::new("name"))
         .column(ColumnConfig::new("path"))
-        .column(ColumnConfig::new("export-slots"));
+        .column(ColumnConfig::new("export-slots"))
+        .column(ColumnConfig::new("reconcile-schedule"))
+        .column(ColumnConfig::new("reconcile-drive"));
 
     format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
 
@@ -260,3 +270,46 @@ pub async fn transfer(mut param: Value, rpcenv: &mut dyn RpcEnvironment) -> Resu
 
     Ok(())
 }
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: CHANGER_NAME_SCHEMA,
+                optional: true,
+            },
+            drive: {
+                schema: DRIVE_NAME_SCHEMA,
+                optional: true,
+            },
+            verify: {
+                description: "Also load already inventoried media and compare their labels.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        },
+    },
+)]
+/// Reconcile the media inventory with the changer content
+async fn reconcile(mut param: Value) -> Result<(), Error> {
+    let output_format = extract_output_format(&mut param);
+
+    let (config, _digest) = pbs_config::drive::config()?;
+
+    let name = lookup_changer_name(&param, &config)?;
+    param.as_object_mut().unwrap().remove("name");
+
+    let client = connect_to_localhost()?;
+
+    let path = format!("api2/json/tape/changer/{}/reconcile", name);
+    let result = client.post(&path, Some(param)).await?;
+
+    view_task_result(&client, result, &output_format).await?;
+
+    Ok(())
+}
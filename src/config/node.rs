@@ -225,6 +225,14 @@ pub struct NodeConfig {
     /// Maximum days to keep Task logs
     #[serde(skip_serializing_if = "Option::is_none")]
     pub task_log_max_days: Option<usize>,
+
+    /// Forward API audit log entries to the syslog
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audit_log_syslog: Option<bool>,
+
+    /// Send API audit log entries to the configured metric servers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audit_log_metrics: Option<bool>,
 }
 
 impl NodeConfig {
@@ -1,7 +1,18 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{bail, format_err, Error};
+use lazy_static::lazy_static;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{Id, PKey};
+use openssl::sign::{Signer, Verifier};
+
 use proxmox_rest_server::AuthError;
 use proxmox_router::UserInformation;
 
+use pbs_api_types::{client_cert_login_data, Userid};
 use pbs_config::CachedUserInfo;
+use pbs_tools::cert::CertInfo;
 
 pub async fn check_pbs_auth(
     headers: &http::HeaderMap,
@@ -11,3 +22,123 @@ pub async fn check_pbs_auth(
     proxmox_auth_api::api::http_check_auth(headers, method)
         .map(move |name| (name, Box::new(user_info) as _))
 }
+
+/// Time (in seconds) a client certificate login challenge stays valid.
+const CLIENT_CERT_CHALLENGE_LIFETIME: i64 = 60;
+
+lazy_static! {
+    // challenges used for a successful login, with their expiry time
+    static ref CLIENT_CERT_USED_CHALLENGES: Mutex<HashMap<String, i64>> =
+        Mutex::new(HashMap::new());
+}
+
+// Authenticates the random part, issue time and user of a challenge with the CSRF secret.
+fn client_cert_challenge_mac(userid: &Userid, nonce: &str, issued: i64) -> Result<String, Error> {
+    let key = PKey::hmac(crate::auth_helpers::csrf_secret())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(format!("client-cert:{userid}:{nonce}:{issued:08X}").as_bytes())?;
+    Ok(hex::encode(signer.sign_to_vec()?))
+}
+
+/// Issue a one-time challenge for a TLS client certificate based login of `userid`.
+///
+/// Challenges are not stored, they carry their issue time and are authenticated with the CSRF
+/// secret, so issuing them needs no server-side resources.
+pub fn create_client_cert_challenge(userid: &Userid) -> Result<String, Error> {
+    let mut nonce = [0u8; 16];
+    openssl::rand::rand_bytes(&mut nonce)?;
+    let nonce = hex::encode(nonce);
+
+    let issued = proxmox_time::epoch_i64();
+    let mac = client_cert_challenge_mac(userid, &nonce, issued)?;
+
+    Ok(format!("{nonce}.{issued:08X}.{mac}"))
+}
+
+// Check that `challenge` was issued for `userid` and is not expired yet, returns its expiry time.
+fn check_client_cert_challenge(userid: &Userid, challenge: &str, now: i64) -> Result<i64, Error> {
+    let mut parts = challenge.split('.');
+    let (nonce, issued, mac) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(nonce), Some(issued), Some(mac), None) => (nonce, issued, mac),
+        _ => bail!("invalid login challenge"),
+    };
+    let issued =
+        i64::from_str_radix(issued, 16).map_err(|_| format_err!("invalid login challenge"))?;
+
+    let expected = client_cert_challenge_mac(userid, nonce, issued)?;
+    if !openssl::memcmp::eq(expected.as_bytes(), mac.as_bytes()) {
+        bail!("invalid login challenge");
+    }
+
+    let expire = issued + CLIENT_CERT_CHALLENGE_LIFETIME;
+    if issued > now + 5 || expire <= now {
+        bail!("expired login challenge");
+    }
+
+    Ok(expire)
+}
+
+/// Verify a TLS client certificate based login of `userid`.
+///
+/// The client proves possession of the certificate's private key by signing a challenge issued
+/// by [`create_client_cert_challenge`] together with the fingerprint of this server's
+/// certificate. Each challenge can only be used for one login. The certificate has to be
+/// registered for the user and valid at the time of login, and the user has to be active.
+pub fn verify_client_cert_login(
+    userid: &Userid,
+    certificate: &str,
+    challenge: &str,
+    signature: &str,
+) -> Result<(), Error> {
+    let now = proxmox_time::epoch_i64();
+
+    let expire = check_client_cert_challenge(userid, challenge, now)?;
+    if CLIENT_CERT_USED_CHALLENGES
+        .lock()
+        .unwrap()
+        .contains_key(challenge)
+    {
+        bail!("login challenge was already used");
+    }
+
+    let cert = CertInfo::from_pem(certificate.as_bytes())
+        .map_err(|err| format_err!("unable to parse client certificate - {err}"))?;
+
+    if cert.not_before_unix()? > now || cert.is_expired_after_epoch(now)? {
+        bail!("client certificate is not valid at this time");
+    }
+
+    let fingerprint = cert.fingerprint()?;
+    let registered = pbs_config::client_cert::lookup(userid, &fingerprint)?;
+    if registered.is_none() {
+        bail!("client certificate {fingerprint} is not registered for user '{userid}'");
+    }
+
+    let server_fingerprint = crate::cert_info()?.fingerprint()?;
+    let login_data = client_cert_login_data(userid, challenge, &server_fingerprint);
+
+    let signature = hex::decode(signature).map_err(|_| format_err!("invalid signature"))?;
+    let public_key = cert.public_key()?;
+    // EdDSA signs the message itself, without a separate digest
+    let mut verifier = match public_key.id() {
+        Id::ED25519 | Id::ED448 => Verifier::new_without_digest(&public_key)?,
+        _ => Verifier::new(MessageDigest::sha256(), &public_key)?,
+    };
+    if !verifier.verify_oneshot(&signature, login_data.as_bytes())? {
+        bail!("invalid signature");
+    }
+
+    let user_info = CachedUserInfo::new()?;
+    if !user_info.is_active_user_id(userid) {
+        bail!("user account '{userid}' disabled or expired.");
+    }
+
+    // only valid signatures get here, so this is bounded by the rate of successful logins
+    let mut used = CLIENT_CERT_USED_CHALLENGES.lock().unwrap();
+    used.retain(|_, used_expire| *used_expire > now);
+    if used.insert(challenge.to_string(), expire).is_some() {
+        bail!("login challenge was already used");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! Sources to repair corrupt chunks from during verification
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::{format_err, Error};
+
+use proxmox_rest_server::WorkerTask;
+use proxmox_sys::{task_log, task_warn, WorkerTaskContext};
+
+use pbs_api_types::{BackupNamespace, Remote, VerificationJobConfig};
+use pbs_client::BackupReader;
+use pbs_datastore::backup_info::BackupDir;
+use pbs_datastore::{DataBlob, DataStore};
+
+use crate::backup::{ChunkRepair, VerifyWorker};
+
+/// Fetches chunks from the same snapshot on a remote, using the reader protocol.
+pub struct RemoteChunkRepair {
+    datastore: Arc<DataStore>,
+    remote: Remote,
+    remote_store: String,
+    local_ns: BackupNamespace,
+    remote_ns: BackupNamespace,
+}
+
+impl RemoteChunkRepair {
+    async fn fetch_chunks(
+        &self,
+        worker: &dyn WorkerTaskContext,
+        backup_dir: &BackupDir,
+        archive: &str,
+        digests: &HashSet<[u8; 32]>,
+    ) -> Result<HashSet<[u8; 32]>, Error> {
+        let ns = backup_dir
+            .backup_ns()
+            .map_prefix(&self.local_ns, &self.remote_ns)?;
+
+        let client = crate::api2::config::remote::remote_client(&self.remote, None).await?;
+        let reader = BackupReader::start(
+            client,
+            None,
+            &self.remote_store,
+            &ns,
+            backup_dir.as_ref(),
+            false,
+        )
+        .await?;
+
+        // the reader only hands out chunks of indexes which were downloaded before
+        reader.download(archive, std::io::sink()).await?;
+
+        let mut repaired = HashSet::new();
+        for digest in digests.iter() {
+            worker.check_abort()?;
+
+            let result = async {
+                let mut data = Vec::new();
+                reader.download_chunk(digest, &mut data).await?;
+                let chunk = DataBlob::load_from_reader(&mut &data[..])?;
+                if !chunk.is_encrypted() {
+                    chunk.decode(None, Some(digest))?; // verify digest
+                }
+                self.datastore.insert_chunk(&chunk, digest)?;
+                Ok::<_, Error>(())
+            }
+            .await;
+
+            match result {
+                Ok(()) => {
+                    repaired.insert(*digest);
+                }
+                Err(err) => task_warn!(
+                    worker,
+                    "could not fetch chunk {} - {}",
+                    hex::encode(digest),
+                    err
+                ),
+            }
+        }
+
+        Ok(repaired)
+    }
+}
+
+impl ChunkRepair for RemoteChunkRepair {
+    fn name(&self) -> String {
+        format!(
+            "remote '{}' (store '{}')",
+            self.remote.name, self.remote_store
+        )
+    }
+
+    fn repair_chunks(
+        &self,
+        worker: &dyn WorkerTaskContext,
+        backup_dir: &BackupDir,
+        archive: &str,
+        digests: &HashSet<[u8; 32]>,
+    ) -> Result<HashSet<[u8; 32]>, Error> {
+        proxmox_async::runtime::block_on(self.fetch_chunks(worker, backup_dir, archive, digests))
+    }
+}
+
+/// Restores chunks from any tape whose media catalog contains them.
+pub struct TapeChunkRepair {
+    worker: Arc<WorkerTask>,
+    datastore: Arc<DataStore>,
+    drive: String,
+    email: Option<String>,
+}
+
+impl ChunkRepair for TapeChunkRepair {
+    fn name(&self) -> String {
+        format!("tape drive '{}'", self.drive)
+    }
+
+    fn repair_chunks(
+        &self,
+        _worker: &dyn WorkerTaskContext,
+        _backup_dir: &BackupDir,
+        _archive: &str,
+        digests: &HashSet<[u8; 32]>,
+    ) -> Result<HashSet<[u8; 32]>, Error> {
+        crate::api2::tape::restore::restore_chunks_from_tape(
+            Arc::clone(&self.worker),
+            Arc::clone(&self.datastore),
+            &self.drive,
+            digests,
+            &self.email,
+        )
+    }
+}
+
+fn lookup_remote(name: &str) -> Result<Remote, Error> {
+    let (remote_config, _digest) = pbs_config::remote::config()?;
+    remote_config
+        .lookup("remote", name)
+        .map_err(|err| format_err!("repair remote '{}' - {}", name, err))
+}
+
+/// Add the chunk repair sources configured in a verification job to `verify_worker`.
+///
+/// Sources which cannot be set up are logged and skipped.
+pub(crate) fn add_chunk_repair_sources(
+    mut verify_worker: VerifyWorker,
+    worker: &Arc<WorkerTask>,
+    datastore: &Arc<DataStore>,
+    job: &VerificationJobConfig,
+    email: &Option<String>,
+) -> VerifyWorker {
+    if let (Some(remote), Some(remote_store)) = (&job.repair_remote, &job.repair_remote_store) {
+        match lookup_remote(remote) {
+            Ok(remote) => {
+                task_log!(
+                    worker,
+                    "repairing corrupt chunks from remote '{}', datastore '{}'",
+                    remote.name,
+                    remote_store
+                );
+                verify_worker = verify_worker.with_chunk_repair(Box::new(RemoteChunkRepair {
+                    datastore: Arc::clone(datastore),
+                    remote,
+                    remote_store: remote_store.clone(),
+                    local_ns: job.ns.clone().unwrap_or_default(),
+                    remote_ns: job.repair_remote_ns.clone().unwrap_or_default(),
+                }));
+            }
+            Err(err) => task_warn!(worker, "{}", err),
+        }
+    }
+
+    if let Some(ref drive) = job.repair_drive {
+        task_log!(
+            worker,
+            "repairing corrupt chunks from tape using drive '{}'",
+            drive
+        );
+        verify_worker = verify_worker.with_chunk_repair(Box::new(TapeChunkRepair {
+            worker: Arc::clone(worker),
+            datastore: Arc::clone(datastore),
+            drive: drive.clone(),
+            email: email.clone(),
+        }));
+    }
+
+    verify_worker
+}
@@ -11,10 +11,12 @@ use proxmox_schema::ApiType;
 use proxmox_sys::email::sendmail;
 
 use pbs_api_types::{
-    APTUpdateInfo, DataStoreConfig, DatastoreNotify, GarbageCollectionStatus, Notify,
-    SyncJobConfig, TapeBackupJobSetup, User, Userid, VerificationJobConfig,
+    APTUpdateInfo, DataStoreConfig, DatastoreNotify, GarbageCollectionStatus, NotificationSeverity,
+    Notify, SyncJobConfig, TapeBackupJobSetup, User, Userid, VerificationJobConfig,
 };
 
+use crate::server::notifications::{send_notification, Notification};
+
 const GC_OK_TEMPLATE: &str = r###"
 
 Datastore:            {{datastore}}
@@ -92,8 +94,12 @@ const SYNC_OK_TEMPLATE: &str = r###"
 
 Job ID:       {{job.id}}
 Datastore:    {{job.store}}
+{{#if job.remote ~}}
 Remote:       {{job.remote}}
 Remote Store: {{job.remote-store}}
+{{else ~}}
+Source Store: {{job.remote-store}}
+{{/if}}
 
 Synchronization successful.
 
@@ -108,8 +114,12 @@ const SYNC_ERR_TEMPLATE: &str = r###"
 
 Job ID:       {{job.id}}
 Datastore:    {{job.store}}
+{{#if job.remote ~}}
 Remote:       {{job.remote}}
 Remote Store: {{job.remote-store}}
+{{else ~}}
+Source Store: {{job.remote-store}}
+{{/if}}
 
 Synchronization failed: {{error}}
 
@@ -312,21 +322,44 @@ fn send_job_status_mail(email: &str, subject: &str, text: &str) -> Result<(), Er
     Ok(())
 }
 
+/// Send the job status mail to `email`, if set, and pass the notification on to the
+/// notification system.
+fn send_job_status(email: Option<&str>, notification: Notification) -> Result<(), Error> {
+    let mail_result = match email {
+        Some(email) => send_job_status_mail(email, &notification.title, &notification.body),
+        None => Ok(()),
+    };
+
+    let notification_result = send_notification(&notification);
+
+    mail_result.and(notification_result)
+}
+
+fn job_severity(success: bool) -> NotificationSeverity {
+    if success {
+        NotificationSeverity::Info
+    } else {
+        NotificationSeverity::Error
+    }
+}
+
+/// Check the legacy per datastore notify setting, `default` is used if it is not set.
+fn notify_mail(notify: Option<Notify>, default: Notify, success: bool) -> bool {
+    match notify.unwrap_or(default) {
+        Notify::Never => false,
+        Notify::Error => !success,
+        Notify::Always => true,
+    }
+}
+
 pub fn send_gc_status(
-    email: &str,
+    email: Option<&str>,
     notify: DatastoreNotify,
     datastore: &str,
     status: &GarbageCollectionStatus,
     result: &Result<(), Error>,
 ) -> Result<(), Error> {
-    match notify.gc {
-        None => { /* send notifications by default */ }
-        Some(notify) => {
-            if notify == Notify::Never || (result.is_ok() && notify == Notify::Error) {
-                return Ok(());
-            }
-        }
-    }
+    let email = email.filter(|_| notify_mail(notify.gc, Notify::Always, result.is_ok()));
 
     let (fqdn, port) = get_server_url();
     let mut data = json!({
@@ -359,13 +392,14 @@ pub fn send_gc_status(
         Err(_) => format!("Garbage Collect Datastore '{datastore}' failed"),
     };
 
-    send_job_status_mail(email, &subject, &text)?;
+    let notification = Notification::new("gc", job_severity(result.is_ok()), subject, text)
+        .with_field("datastore", datastore);
 
-    Ok(())
+    send_job_status(email, notification)
 }
 
 pub fn send_verify_status(
-    email: &str,
+    email: Option<&str>,
     notify: DatastoreNotify,
     job: VerificationJobConfig,
     result: &Result<Vec<String>, Error>,
@@ -394,23 +428,18 @@ pub fn send_verify_status(
         }
     };
 
-    match notify.verify {
-        None => { /* send notifications by default */ }
-        Some(notify) => {
-            if notify == Notify::Never || (result_is_ok && notify == Notify::Error) {
-                return Ok(());
-            }
-        }
-    }
+    let email = email.filter(|_| notify_mail(notify.verify, Notify::Always, result_is_ok));
 
     let subject = match result {
         Ok(errors) if errors.is_empty() => format!("Verify Datastore '{}' successful", job.store),
         _ => format!("Verify Datastore '{}' failed", job.store),
     };
 
-    send_job_status_mail(email, &subject, &text)?;
+    let notification = Notification::new("verify", job_severity(result_is_ok), subject, text)
+        .with_field("datastore", &job.store)
+        .with_field("job-id", &job.id);
 
-    Ok(())
+    send_job_status(email, notification)
 }
 
 pub fn send_prune_status(
@@ -418,15 +447,8 @@ pub fn send_prune_status(
     jobname: &str,
     result: &Result<(), Error>,
 ) -> Result<(), Error> {
-    let (email, notify) = match lookup_datastore_notify_settings(store) {
-        (Some(email), notify) => (email, notify),
-        (None, _) => return Ok(()),
-    };
-
-    let notify_prune = notify.prune.unwrap_or(Notify::Error);
-    if notify_prune == Notify::Never || (result.is_ok() && notify_prune == Notify::Error) {
-        return Ok(());
-    }
+    let (email, notify) = lookup_datastore_notify_settings(store);
+    let email = email.filter(|_| notify_mail(notify.prune, Notify::Error, result.is_ok()));
 
     let (fqdn, port) = get_server_url();
     let mut data = json!({
@@ -449,25 +471,20 @@ pub fn send_prune_status(
         Err(_) => format!("Pruning datastore '{store}' failed"),
     };
 
-    send_job_status_mail(&email, &subject, &text)?;
+    let notification = Notification::new("prune", job_severity(result.is_ok()), subject, text)
+        .with_field("datastore", store)
+        .with_field("job-id", jobname);
 
-    Ok(())
+    send_job_status(email.as_deref(), notification)
 }
 
 pub fn send_sync_status(
-    email: &str,
+    email: Option<&str>,
     notify: DatastoreNotify,
     job: &SyncJobConfig,
     result: &Result<(), Error>,
 ) -> Result<(), Error> {
-    match notify.sync {
-        None => { /* send notifications by default */ }
-        Some(notify) => {
-            if notify == Notify::Never || (result.is_ok() && notify == Notify::Error) {
-                return Ok(());
-            }
-        }
-    }
+    let email = email.filter(|_| notify_mail(notify.sync, Notify::Always, result.is_ok()));
 
     let (fqdn, port) = get_server_url();
     let mut data = json!({
@@ -484,24 +501,25 @@ pub fn send_sync_status(
         }
     };
 
+    let source = match job.remote {
+        Some(ref remote) => format!("remote '{}' datastore '{}'", remote, job.remote_store),
+        None => format!("local datastore '{}'", job.remote_store),
+    };
+
     let subject = match result {
-        Ok(()) => format!(
-            "Sync remote '{}' datastore '{}' successful",
-            job.remote, job.remote_store,
-        ),
-        Err(_) => format!(
-            "Sync remote '{}' datastore '{}' failed",
-            job.remote, job.remote_store,
-        ),
+        Ok(()) => format!("Sync {source} successful"),
+        Err(_) => format!("Sync {source} failed"),
     };
 
-    send_job_status_mail(email, &subject, &text)?;
+    let notification = Notification::new("sync", job_severity(result.is_ok()), subject, text)
+        .with_field("datastore", &job.store)
+        .with_field("job-id", &job.id);
 
-    Ok(())
+    send_job_status(email, notification)
 }
 
 pub fn send_tape_backup_status(
-    email: &str,
+    email: Option<&str>,
     id: Option<&str>,
     job: &TapeBackupJobSetup,
     result: &Result<(), Error>,
@@ -534,9 +552,15 @@ pub fn send_tape_backup_status(
         (Err(_), None) => format!("Tape Backup datastore '{}' failed", job.store,),
     };
 
-    send_job_status_mail(email, &subject, &text)?;
+    let mut notification =
+        Notification::new("tape-backup", job_severity(result.is_ok()), subject, text)
+            .with_field("datastore", &job.store)
+            .with_field("media-pool", &job.pool);
+    if let Some(id) = id {
+        notification = notification.with_field("job-id", id);
+    }
 
-    Ok(())
+    send_job_status(email, notification)
 }
 
 /// Send email to a person to request a manual media change
@@ -544,7 +568,7 @@ pub fn send_load_media_email(
     changer: bool,
     device: &str,
     label_text: &str,
-    to: &str,
+    to: Option<&str>,
     reason: Option<String>,
 ) -> Result<(), Error> {
     use std::fmt::Write as _;
@@ -571,7 +595,10 @@ pub fn send_load_media_email(
     }
     let _ = writeln!(text, "Media: {label_text}");
 
-    send_job_status_mail(to, &subject, &text)
+    let notification = Notification::new("tape-load", NotificationSeverity::Notice, subject, text)
+        .with_field(device_type, device);
+
+    send_job_status(to, notification)
 }
 
 fn get_server_url() -> (String, usize) {
@@ -594,24 +621,26 @@ fn get_server_url() -> (String, usize) {
 
 pub fn send_updates_available(updates: &[&APTUpdateInfo]) -> Result<(), Error> {
     // update mails always go to the root@pam configured email..
-    if let Some(email) = lookup_user_email(Userid::root_userid()) {
-        let nodename = proxmox_sys::nodename();
-        let subject = format!("New software packages available ({nodename})");
-
-        let (fqdn, port) = get_server_url();
-
-        let text = HANDLEBARS.render(
-            "package_update_template",
-            &json!({
-                "fqdn": fqdn,
-                "port": port,
-                "updates": updates,
-            }),
-        )?;
-
-        send_job_status_mail(&email, &subject, &text)?;
-    }
-    Ok(())
+    let email = lookup_user_email(Userid::root_userid());
+
+    let nodename = proxmox_sys::nodename();
+    let subject = format!("New software packages available ({nodename})");
+
+    let (fqdn, port) = get_server_url();
+
+    let text = HANDLEBARS.render(
+        "package_update_template",
+        &json!({
+            "fqdn": fqdn,
+            "port": port,
+            "updates": updates,
+        }),
+    )?;
+
+    let notification =
+        Notification::new("package-updates", NotificationSeverity::Info, subject, text);
+
+    send_job_status(email.as_deref(), notification)
 }
 
 /// send email on certificate renewal failure.
@@ -621,24 +650,24 @@ pub fn send_certificate_renewal_mail(result: &Result<(), Error>) -> Result<(), E
         _ => return Ok(()),
     };
 
-    if let Some(email) = lookup_user_email(Userid::root_userid()) {
-        let (fqdn, port) = get_server_url();
+    let email = lookup_user_email(Userid::root_userid());
+
+    let (fqdn, port) = get_server_url();
 
-        let text = HANDLEBARS.render(
-            "certificate_renewal_err_template",
-            &json!({
-                "fqdn": fqdn,
-                "port": port,
-                "error": error,
-            }),
-        )?;
+    let text = HANDLEBARS.render(
+        "certificate_renewal_err_template",
+        &json!({
+            "fqdn": fqdn,
+            "port": port,
+            "error": error,
+        }),
+    )?;
 
-        let subject = "Could not renew certificate";
+    let subject = "Could not renew certificate".to_string();
 
-        send_job_status_mail(&email, subject, &text)?;
-    }
+    let notification = Notification::new("acme", NotificationSeverity::Error, subject, text);
 
-    Ok(())
+    send_job_status(email.as_deref(), notification)
 }
 
 /// Lookup users email address
@@ -37,17 +37,19 @@ pub fn do_garbage_collection_job(
 
             let result = datastore.garbage_collection(&*worker, worker.upid());
 
+            // removed chunks no longer count for any quota
+            crate::backup::invalidate_quota_usage(&store);
+
             let status = worker.create_state(&result);
 
             if let Err(err) = job.finish(status) {
                 eprintln!("could not finish job state for {}: {err}", job.jobtype());
             }
 
-            if let Some(email) = email {
-                let gc_status = datastore.last_gc_status();
-                if let Err(err) = send_gc_status(&email, notify, &store, &gc_status, &result) {
-                    eprintln!("send gc notification failed: {err}");
-                }
+            let gc_status = datastore.last_gc_status();
+            if let Err(err) = send_gc_status(email.as_deref(), notify, &store, &gc_status, &result)
+            {
+                eprintln!("send gc notification failed: {err}");
             }
 
             result
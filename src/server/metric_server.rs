@@ -0,0 +1,100 @@
+//! Sending data to the configured metric servers
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Error;
+
+use proxmox_metrics::{Metrics, MetricsData};
+use proxmox_section_config::SectionConfigData;
+
+use pbs_api_types::{InfluxDbHttp, InfluxDbUdp, OtlpHttp};
+
+use super::otlp::send_otlp_http;
+
+/// Time limit for sending to an OpenTelemetry collector, so a slow or unreachable collector
+/// does not hold up the statistics loop, which runs every 10 seconds.
+const OTLP_SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Get the metric server connections from a config
+pub fn get_metric_server_connections(
+    metric_config: &SectionConfigData,
+) -> Result<Vec<(Metrics, String)>, Error> {
+    let mut res = Vec::new();
+
+    for config in metric_config.convert_to_typed_array::<InfluxDbUdp>("influxdb-udp")? {
+        if !config.enable {
+            continue;
+        }
+        let future = proxmox_metrics::influxdb_udp(&config.host, config.mtu);
+        res.push((future, config.name));
+    }
+
+    for config in metric_config.convert_to_typed_array::<InfluxDbHttp>("influxdb-http")? {
+        if !config.enable {
+            continue;
+        }
+        let future = proxmox_metrics::influxdb_http(
+            &config.url,
+            config.organization.as_deref().unwrap_or("proxmox"),
+            config.bucket.as_deref().unwrap_or("proxmox"),
+            config.token.as_deref(),
+            config.verify_tls.unwrap_or(true),
+            config.max_body_size.unwrap_or(25_000_000),
+        )?;
+        res.push((future, config.name));
+    }
+    Ok(res)
+}
+
+/// Send data to all enabled InfluxDB servers and OpenTelemetry collectors.
+///
+/// Errors of single servers are only logged.
+pub async fn send_data_to_metric_servers(values: &[Arc<MetricsData>]) -> Result<(), Error> {
+    let (config, _digest) = pbs_config::metrics::config()?;
+    let otlp_servers: Vec<OtlpHttp> = config
+        .convert_to_typed_array::<OtlpHttp>("otlp-http")?
+        .into_iter()
+        .filter(|server| server.enable)
+        .collect();
+    let channel_list = get_metric_server_connections(&config)?;
+
+    if channel_list.is_empty() && otlp_servers.is_empty() {
+        return Ok(());
+    }
+
+    // we must have a concrete functions, because the inferred lifetime from a
+    // closure is not general enough for the tokio::spawn call we are in here...
+    fn map_fn(item: &(Metrics, String)) -> &Metrics {
+        &item.0
+    }
+
+    let results =
+        proxmox_metrics::send_data_to_channels(values, channel_list.iter().map(map_fn)).await;
+    for (res, name) in results
+        .into_iter()
+        .zip(channel_list.iter().map(|(_, name)| name))
+    {
+        if let Err(err) = res {
+            log::error!("error sending into channel of {name}: {err}");
+        }
+    }
+
+    futures::future::join_all(channel_list.into_iter().map(|(channel, name)| async move {
+        if let Err(err) = channel.join().await {
+            log::error!("error sending to metric server {name}: {err}");
+        }
+    }))
+    .await;
+
+    futures::future::join_all(otlp_servers.iter().map(|server| async move {
+        match tokio::time::timeout(OTLP_SEND_TIMEOUT, send_otlp_http(server, values)).await {
+            Ok(Ok(())) => (),
+            Ok(Err(err)) => log::error!("error sending to metric server {}: {err}", server.name),
+            Err(_) => log::error!("error sending to metric server {}: timed out", server.name),
+        }
+    }))
+    .await;
+
+    Ok(())
+}
@@ -28,12 +28,23 @@ pub use realm_sync_job::*;
 mod email_notifications;
 pub use email_notifications::*;
 
+pub mod notifications;
+
+pub mod otlp;
+
+pub mod metric_server;
+
+pub mod audit;
+
 mod report;
 pub use report::*;
 
 pub mod auth;
 
+pub(crate) mod chunk_repair;
+
 pub(crate) mod pull;
+pub(crate) mod push;
 
 pub(crate) async fn reload_proxy_certificate() -> Result<(), Error> {
     let proxy_pid = proxmox_rest_server::read_pid(pbs_buildcfg::PROXMOX_BACKUP_PROXY_PID_FN)?;
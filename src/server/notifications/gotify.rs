@@ -0,0 +1,43 @@
+use anyhow::{format_err, Error};
+use hyper::{Body, Request};
+use serde_json::json;
+
+use pbs_api_types::{GotifyConfig, NotificationSeverity};
+
+use super::{http_request, Notification};
+
+fn priority(severity: NotificationSeverity) -> u32 {
+    match severity {
+        NotificationSeverity::Info => 1,
+        NotificationSeverity::Notice => 3,
+        NotificationSeverity::Warning => 5,
+        NotificationSeverity::Error => 9,
+    }
+}
+
+/// Send a notification as message to a Gotify server.
+pub(super) async fn send(target: &GotifyConfig, notification: &Notification) -> Result<(), Error> {
+    let token = target
+        .token
+        .as_deref()
+        .ok_or_else(|| format_err!("no application token configured"))?;
+
+    let body = json!({
+        "title": notification.title,
+        "message": notification.body,
+        "priority": priority(notification.severity),
+        "extras": {
+            "client::display": {
+                "contentType": "text/plain",
+            },
+        },
+    });
+
+    let url = format!("{}/message", target.server.trim_end_matches('/'));
+    let request = Request::post(url)
+        .header("Content-Type", "application/json")
+        .header("X-Gotify-Key", token)
+        .body(Body::from(body.to_string()))?;
+
+    http_request(request).await
+}
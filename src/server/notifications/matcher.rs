@@ -0,0 +1,150 @@
+use std::collections::BTreeSet;
+
+use anyhow::{bail, format_err, Error};
+use regex::Regex;
+
+use proxmox_section_config::SectionConfigData;
+
+use pbs_api_types::{MatchModeOperator, MatcherConfig};
+
+use super::Notification;
+
+/// Returns the names of all targets of enabled matchers which match `notification`.
+///
+/// Matchers with invalid rules are logged and skipped.
+pub fn matching_targets(config: &SectionConfigData, notification: &Notification) -> Vec<String> {
+    let matchers: Vec<MatcherConfig> = match config.convert_to_typed_array("matcher") {
+        Ok(list) => list,
+        Err(err) => {
+            log::error!("could not parse notification matchers - {err}");
+            return Vec::new();
+        }
+    };
+
+    let mut targets = BTreeSet::new();
+
+    for matcher in matchers {
+        if matcher.disable.unwrap_or(false) {
+            continue;
+        }
+        match matcher_matches(&matcher, notification) {
+            Ok(true) => {
+                if let Some(list) = matcher.target {
+                    targets.extend(list);
+                }
+            }
+            Ok(false) => {}
+            Err(err) => log::error!("notification matcher '{}' failed - {err}", matcher.name),
+        }
+    }
+
+    targets.into_iter().collect()
+}
+
+/// Check a matcher against a notification. A matcher without rules matches everything.
+fn matcher_matches(matcher: &MatcherConfig, notification: &Notification) -> Result<bool, Error> {
+    let mut results = Vec::new();
+
+    if let Some(ref severities) = matcher.match_severity {
+        results.push(severities.contains(&notification.severity));
+    }
+
+    if let Some(ref rules) = matcher.match_field {
+        for rule in rules {
+            results.push(field_matches(rule, notification)?);
+        }
+    }
+
+    let matched = if results.is_empty() {
+        true
+    } else {
+        match matcher.mode.unwrap_or_default() {
+            MatchModeOperator::All => results.iter().all(|r| *r),
+            MatchModeOperator::Any => results.iter().any(|r| *r),
+        }
+    };
+
+    Ok(matched != matcher.invert_match.unwrap_or(false))
+}
+
+/// Check a single 'exact:<field>=<value>[,<value>...]' or 'regex:<field>=<regex>' rule.
+fn field_matches(rule: &str, notification: &Notification) -> Result<bool, Error> {
+    let (kind, rest) = rule
+        .split_once(':')
+        .ok_or_else(|| format_err!("invalid match-field rule '{rule}'"))?;
+    let (field, pattern) = rest
+        .split_once('=')
+        .ok_or_else(|| format_err!("invalid match-field rule '{rule}'"))?;
+
+    let value = match notification.fields.get(field) {
+        Some(value) => value,
+        None => return Ok(false),
+    };
+
+    match kind {
+        "exact" => Ok(pattern.split(',').any(|expected| expected == value)),
+        "regex" => {
+            let regex = Regex::new(pattern)
+                .map_err(|err| format_err!("invalid regex in rule '{rule}' - {err}"))?;
+            Ok(regex.is_match(value))
+        }
+        _ => bail!("unknown match-field kind '{kind}'"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pbs_api_types::NotificationSeverity;
+
+    fn notification(severity: NotificationSeverity) -> Notification {
+        Notification::new("gc", severity, "title".into(), "body".into())
+            .with_field("datastore", "store1")
+    }
+
+    fn matcher() -> MatcherConfig {
+        MatcherConfig {
+            name: "test".into(),
+            match_field: None,
+            match_severity: None,
+            mode: None,
+            invert_match: None,
+            target: Some(vec!["mail".into()]),
+            comment: None,
+            disable: None,
+        }
+    }
+
+    #[test]
+    fn test_matcher_rules() -> Result<(), Error> {
+        let info = notification(NotificationSeverity::Info);
+        let error = notification(NotificationSeverity::Error);
+
+        let mut m = matcher();
+        assert!(matcher_matches(&m, &info)?);
+
+        m.match_severity = Some(vec![NotificationSeverity::Error]);
+        assert!(!matcher_matches(&m, &info)?);
+        assert!(matcher_matches(&m, &error)?);
+
+        m.match_field = Some(vec!["exact:type=verify,gc".into()]);
+        assert!(matcher_matches(&m, &error)?);
+
+        m.match_field = Some(vec!["regex:datastore=^store2$".into()]);
+        assert!(!matcher_matches(&m, &error)?);
+
+        m.mode = Some(MatchModeOperator::Any);
+        assert!(matcher_matches(&m, &error)?);
+        assert!(!matcher_matches(&m, &info)?);
+
+        m.invert_match = Some(true);
+        assert!(matcher_matches(&m, &info)?);
+
+        m.match_field = Some(vec!["exact:job-id=daily".into()]);
+        m.match_severity = None;
+        m.invert_match = None;
+        assert!(!matcher_matches(&m, &info)?);
+
+        Ok(())
+    }
+}
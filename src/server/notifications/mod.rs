@@ -0,0 +1,192 @@
+//! Notification system
+//!
+//! Notifications are routed to the configured targets (sendmail, SMTP, Gotify and webhooks) by
+//! matchers, which check the severity and the fields of a notification.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{bail, Error};
+use hyper::{Body, Request};
+use serde_json::{json, Value};
+
+use proxmox_section_config::SectionConfigData;
+
+use pbs_api_types::{
+    GotifyConfig, NotificationSeverity, SendmailConfig, SmtpConfig, Userid, WebhookConfig,
+};
+
+mod gotify;
+mod matcher;
+mod sendmail;
+mod smtp;
+mod webhook;
+
+pub use matcher::matching_targets;
+
+/// A notification, sent to all targets of matching matchers.
+pub struct Notification {
+    /// Severity of the event
+    pub severity: NotificationSeverity,
+    /// Short summary, used as mail subject or message title
+    pub title: String,
+    /// Message text
+    pub body: String,
+    /// Metadata used by matchers, e.g. 'type', 'datastore' or 'job-id'
+    pub fields: BTreeMap<String, String>,
+    /// Time of the event
+    pub timestamp: i64,
+}
+
+impl Notification {
+    /// Create a new notification of type `ty` (e.g. 'gc', 'verify' or 'sync').
+    pub fn new(ty: &str, severity: NotificationSeverity, title: String, body: String) -> Self {
+        let mut fields = BTreeMap::new();
+        fields.insert("type".to_string(), ty.to_string());
+        fields.insert("hostname".to_string(), proxmox_sys::nodename().to_string());
+
+        Self {
+            severity,
+            title,
+            body,
+            fields,
+            timestamp: proxmox_time::epoch_i64(),
+        }
+    }
+
+    /// Add a metadata field.
+    pub fn with_field(mut self, name: &str, value: &str) -> Self {
+        self.fields.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Template data available to targets which render their own messages.
+    fn template_data(&self) -> Value {
+        json!({
+            "title": self.title,
+            "message": self.body,
+            "severity": self.severity,
+            "timestamp": self.timestamp,
+            "fields": self.fields,
+        })
+    }
+}
+
+/// Send a notification to the targets of all matching matchers.
+///
+/// Does nothing if no matchers are configured.
+pub fn send_notification(notification: &Notification) -> Result<(), Error> {
+    let (config, _digest) = pbs_config::notifications::config()?;
+
+    let mut failed = Vec::new();
+    for target in matching_targets(&config, notification) {
+        if let Err(err) = send_to_target(&config, &target, notification) {
+            log::error!("could not notify target '{target}' - {err}");
+            failed.push(target);
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!("could not notify targets: {}", failed.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Send a notification to a single target, even if no matcher routes to it.
+pub fn send_to_target(
+    config: &SectionConfigData,
+    name: &str,
+    notification: &Notification,
+) -> Result<(), Error> {
+    let section_type = match config.sections.get(name) {
+        Some((section_type, _)) => section_type.as_str(),
+        None => bail!("notification target '{name}' does not exist"),
+    };
+
+    match section_type {
+        "sendmail" => {
+            let target: SendmailConfig = config.lookup("sendmail", name)?;
+            check_enabled(name, target.disable)?;
+            sendmail::send(&target, notification)
+        }
+        "smtp" => {
+            let target: SmtpConfig = config.lookup("smtp", name)?;
+            check_enabled(name, target.disable)?;
+            proxmox_async::runtime::block_on(smtp::send(&target, notification))
+        }
+        "gotify" => {
+            let target: GotifyConfig = config.lookup("gotify", name)?;
+            check_enabled(name, target.disable)?;
+            proxmox_async::runtime::block_on(gotify::send(&target, notification))
+        }
+        "webhook" => {
+            let target: WebhookConfig = config.lookup("webhook", name)?;
+            check_enabled(name, target.disable)?;
+            proxmox_async::runtime::block_on(webhook::send(&target, notification))
+        }
+        other => bail!("'{name}' is not a notification target (type '{other}')"),
+    }
+}
+
+fn check_enabled(name: &str, disable: Option<bool>) -> Result<(), Error> {
+    if disable.unwrap_or(false) {
+        bail!("notification target '{name}' is disabled");
+    }
+    Ok(())
+}
+
+/// Collect the recipients of a mail target, looking up the addresses of users.
+fn mail_recipients(mailto: &Option<Vec<String>>, mailto_user: &Option<Vec<Userid>>) -> Vec<String> {
+    let mut recipients = BTreeSet::new();
+
+    if let Some(mailto) = mailto {
+        recipients.extend(mailto.iter().cloned());
+    }
+    if let Some(users) = mailto_user {
+        for userid in users {
+            match crate::server::lookup_user_email(userid) {
+                Some(email) => {
+                    recipients.insert(email);
+                }
+                None => log::warn!("user '{userid}' has no email address configured"),
+            }
+        }
+    }
+
+    recipients.into_iter().collect()
+}
+
+/// HTML alternative of a mail body.
+///
+/// NOTE: some (web)mailers have big problems displaying text mails, so include html as well
+fn html_body(text: &str) -> String {
+    let escaped_text = handlebars::html_escape(text);
+    format!("<html><body><pre>\n{escaped_text}\n<pre>")
+}
+
+/// The author used for mails if the target does not configure one.
+fn default_author() -> String {
+    format!("Proxmox Backup Server - {}", proxmox_sys::nodename())
+}
+
+/// Send an HTTP request for Gotify and webhook targets, using the node's HTTP proxy.
+async fn http_request(request: Request<Body>) -> Result<(), Error> {
+    let proxy_config = match crate::config::node::config() {
+        Ok((node_config, _digest)) => node_config.http_proxy(),
+        Err(_) => None,
+    };
+    let client = crate::tools::pbs_simple_http(proxy_config);
+
+    let response = client.request(request).await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        bail!(
+            "request failed with status {} - {}",
+            status,
+            String::from_utf8_lossy(&body).trim()
+        );
+    }
+
+    Ok(())
+}
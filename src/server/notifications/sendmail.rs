@@ -0,0 +1,31 @@
+use anyhow::{bail, Error};
+
+use proxmox_sys::email::sendmail;
+
+use pbs_api_types::SendmailConfig;
+
+use super::{default_author, html_body, mail_recipients, Notification};
+
+/// Send a notification with the local sendmail binary.
+pub(super) fn send(target: &SendmailConfig, notification: &Notification) -> Result<(), Error> {
+    let recipients = mail_recipients(&target.mailto, &target.mailto_user);
+    if recipients.is_empty() {
+        bail!("no recipients configured");
+    }
+    let recipients: Vec<&str> = recipients.iter().map(|r| r.as_str()).collect();
+
+    let from = match target.from_address {
+        Some(ref from) => Some(from.clone()),
+        None => crate::config::node::config()?.0.email_from,
+    };
+    let author = target.author.clone().unwrap_or_else(default_author);
+
+    sendmail(
+        &recipients,
+        &notification.title,
+        Some(&notification.body),
+        Some(&html_body(&notification.body)),
+        from.as_deref(),
+        Some(&author),
+    )
+}
@@ -0,0 +1,302 @@
+//! Minimal SMTP client for notification mails
+
+use anyhow::{bail, format_err, Error};
+use openssl::ssl::{SslConnector, SslMethod};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_openssl::SslStream;
+
+use pbs_api_types::{SmtpConfig, SmtpMode};
+
+use super::{default_author, html_body, mail_recipients, Notification};
+
+const SMTP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+struct SmtpConnection<S> {
+    stream: BufReader<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SmtpConnection<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    /// Read a (possibly multi-line) reply, returns the code and the text of all lines.
+    async fn read_reply(&mut self) -> Result<(u16, String), Error> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                bail!("connection closed by server");
+            }
+            let line = line.trim_end();
+            if line.len() < 3 {
+                bail!("invalid reply '{line}'");
+            }
+            let code: u16 = line[..3]
+                .parse()
+                .map_err(|_| format_err!("invalid reply '{line}'"))?;
+            text.push_str(line.get(4..).unwrap_or(""));
+            text.push('\n');
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, text));
+            }
+        }
+    }
+
+    async fn expect(&mut self, expected: u16) -> Result<String, Error> {
+        let (code, text) = self.read_reply().await?;
+        if code != expected {
+            bail!("unexpected reply {code} - {}", text.trim());
+        }
+        Ok(text)
+    }
+
+    async fn command(&mut self, command: &str, expected: u16) -> Result<String, Error> {
+        self.labeled_command(command, command, expected).await
+    }
+
+    /// Send `command`, using `label` instead of the command text in errors.
+    ///
+    /// Used for commands containing credentials.
+    async fn labeled_command(
+        &mut self,
+        command: &str,
+        label: &str,
+        expected: u16,
+    ) -> Result<String, Error> {
+        self.stream.write_all(command.as_bytes()).await?;
+        self.stream.write_all(b"\r\n").await?;
+        self.stream.flush().await?;
+        self.expect(expected)
+            .await
+            .map_err(|err| format_err!("{label} failed - {err}"))
+    }
+
+    /// Authenticate and transmit the mail, after the server accepted EHLO.
+    async fn transmit(
+        &mut self,
+        ehlo: &str,
+        target: &SmtpConfig,
+        recipients: &[String],
+        message: &str,
+    ) -> Result<(), Error> {
+        if let Some(ref username) = target.username {
+            let password = target.password.as_deref().unwrap_or("");
+            self.authenticate(ehlo, username, password).await?;
+        }
+
+        self.command(&format!("MAIL FROM:<{}>", target.from_address), 250)
+            .await?;
+        for recipient in recipients {
+            self.stream
+                .write_all(format!("RCPT TO:<{recipient}>\r\n").as_bytes())
+                .await?;
+            self.stream.flush().await?;
+            match self.read_reply().await? {
+                (250 | 251, _) => {}
+                (code, text) => bail!("recipient {recipient} rejected - {code} {}", text.trim()),
+            }
+        }
+
+        self.command("DATA", 354).await?;
+        self.stream.write_all(message.as_bytes()).await?;
+        self.command(".", 250).await?;
+
+        // the mail was accepted, so ignore errors on disconnect
+        let _ = self.command("QUIT", 221).await;
+
+        Ok(())
+    }
+
+    async fn authenticate(
+        &mut self,
+        ehlo: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<(), Error> {
+        let mechanisms: Vec<&str> = ehlo
+            .lines()
+            .find_map(|line| line.strip_prefix("AUTH "))
+            .map(|list| list.split_whitespace().collect())
+            .unwrap_or_default();
+
+        if mechanisms.contains(&"PLAIN") {
+            let credentials = base64::encode(format!("\0{username}\0{password}"));
+            self.labeled_command(&format!("AUTH PLAIN {credentials}"), "AUTH PLAIN", 235)
+                .await?;
+        } else if mechanisms.contains(&"LOGIN") {
+            self.command("AUTH LOGIN", 334).await?;
+            self.labeled_command(&base64::encode(username), "AUTH LOGIN (username)", 334)
+                .await?;
+            self.labeled_command(&base64::encode(password), "AUTH LOGIN (password)", 235)
+                .await?;
+        } else {
+            bail!("server does not support AUTH PLAIN or LOGIN");
+        }
+
+        Ok(())
+    }
+}
+
+async fn tls_connect(server: &str, stream: TcpStream) -> Result<SslStream<TcpStream>, Error> {
+    let ssl = SslConnector::builder(SslMethod::tls_client())?
+        .build()
+        .configure()?
+        .into_ssl(server)?;
+
+    let mut stream = SslStream::new(ssl, stream)?;
+    std::pin::Pin::new(&mut stream)
+        .connect()
+        .await
+        .map_err(|err| format_err!("TLS handshake failed - {err}"))?;
+
+    Ok(stream)
+}
+
+/// Encode a header value if it contains non-ASCII characters (RFC 2047).
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", base64::encode(value))
+    }
+}
+
+/// Base64 encode a mail part, wrapped to 76 characters per line.
+fn encode_part(text: &str) -> String {
+    let encoded = base64::encode(text);
+    let mut result = String::with_capacity(encoded.len() + encoded.len() / 38);
+    for chunk in encoded.as_bytes().chunks(76) {
+        // base64 output is always ASCII
+        result.push_str(std::str::from_utf8(chunk).unwrap());
+        result.push_str("\r\n");
+    }
+    result
+}
+
+/// Format the mail, with a plain text and an HTML alternative.
+fn format_message(
+    target: &SmtpConfig,
+    recipients: &[String],
+    notification: &Notification,
+) -> Result<String, Error> {
+    let author = target.author.clone().unwrap_or_else(default_author);
+    let date = proxmox_time::strftime_local("%a, %d %b %Y %T %z", notification.timestamp)?;
+    let boundary = format!("----_=_NextPart_001_{}", proxmox_uuid::Uuid::generate());
+    let domain = target
+        .from_address
+        .rsplit_once('@')
+        .map(|(_, domain)| domain)
+        .unwrap_or("localhost");
+
+    let mut message = String::new();
+    message.push_str(&format!(
+        "From: {} <{}>\r\n",
+        encode_header(&author),
+        target.from_address
+    ));
+    message.push_str(&format!("To: {}\r\n", recipients.join(", ")));
+    message.push_str(&format!(
+        "Subject: {}\r\n",
+        encode_header(&notification.title)
+    ));
+    message.push_str(&format!("Date: {date}\r\n"));
+    message.push_str(&format!(
+        "Message-ID: <{}@{domain}>\r\n",
+        proxmox_uuid::Uuid::generate()
+    ));
+    message.push_str("Auto-Submitted: auto-generated\r\n");
+    message.push_str("MIME-Version: 1.0\r\n");
+    message.push_str(&format!(
+        "Content-Type: multipart/alternative; boundary=\"{boundary}\"\r\n\r\n"
+    ));
+
+    for (content_type, text) in [
+        ("text/plain", notification.body.clone()),
+        ("text/html", html_body(&notification.body)),
+    ] {
+        message.push_str(&format!("--{boundary}\r\n"));
+        message.push_str(&format!("Content-Type: {content_type}; charset=utf-8\r\n"));
+        message.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
+        message.push_str(&encode_part(&text));
+    }
+    message.push_str(&format!("--{boundary}--\r\n"));
+
+    // the parts are base64 encoded, so no line starts with a dot and needs to be escaped
+    Ok(message)
+}
+
+async fn send_mail(target: &SmtpConfig, recipients: &[String], message: &str) -> Result<(), Error> {
+    let mode = target.mode.unwrap_or_default();
+    let port = target.port.unwrap_or(match mode {
+        SmtpMode::Tls => 465,
+        SmtpMode::Starttls => 587,
+        SmtpMode::Insecure => 25,
+    });
+    let ehlo = format!("EHLO {}", proxmox_sys::nodename());
+
+    let stream = TcpStream::connect((target.server.as_str(), port))
+        .await
+        .map_err(|err| format_err!("could not connect to {}:{port} - {err}", target.server))?;
+
+    match mode {
+        SmtpMode::Tls => {
+            let stream = tls_connect(&target.server, stream).await?;
+            let mut conn = SmtpConnection::new(stream);
+            conn.expect(220).await?;
+            let reply = conn.command(&ehlo, 250).await?;
+            conn.transmit(&reply, target, recipients, message).await
+        }
+        SmtpMode::Starttls => {
+            let mut conn = SmtpConnection::new(stream);
+            conn.expect(220).await?;
+            conn.command(&ehlo, 250).await?;
+            conn.command("STARTTLS", 220).await?;
+
+            let stream = tls_connect(&target.server, conn.stream.into_inner()).await?;
+            let mut conn = SmtpConnection::new(stream);
+            let reply = conn.command(&ehlo, 250).await?;
+            conn.transmit(&reply, target, recipients, message).await
+        }
+        SmtpMode::Insecure => {
+            let mut conn = SmtpConnection::new(stream);
+            conn.expect(220).await?;
+            let reply = conn.command(&ehlo, 250).await?;
+            conn.transmit(&reply, target, recipients, message).await
+        }
+    }
+}
+
+/// Send a notification as mail via an SMTP server.
+pub(super) async fn send(target: &SmtpConfig, notification: &Notification) -> Result<(), Error> {
+    let recipients = mail_recipients(&target.mailto, &target.mailto_user);
+    if recipients.is_empty() {
+        bail!("no recipients configured");
+    }
+
+    let message = format_message(target, &recipients, notification)?;
+
+    tokio::time::timeout(SMTP_TIMEOUT, send_mail(target, &recipients, &message))
+        .await
+        .map_err(|_| format_err!("timeout while sending mail to {}", target.server))?
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_part() {
+        let text = "x".repeat(100);
+        let encoded = encode_part(&text);
+        assert!(encoded.lines().all(|line| line.len() <= 76));
+        let joined: String = encoded.lines().collect();
+        assert_eq!(base64::decode(joined).unwrap(), text.as_bytes());
+
+        assert_eq!(encode_header("GC ok"), "GC ok");
+        assert_eq!(encode_header("Grüße"), "=?utf-8?B?R3LDvMOfZQ==?=");
+    }
+}
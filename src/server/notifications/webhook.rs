@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use anyhow::{format_err, Error};
+use handlebars::{Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderError};
+use hyper::{Body, Method, Request};
+
+use pbs_api_types::{WebhookConfig, WebhookMethod};
+
+use super::{http_request, Notification};
+
+/// Render a template of a webhook target.
+///
+/// Besides the notification data, templates can use the target's secrets and the 'json' helper,
+/// which outputs a value as JSON (e.g. `{{ json message }}` for a quoted and escaped string).
+fn render(hb: &Handlebars, template: &str, data: &serde_json::Value) -> Result<String, Error> {
+    hb.render_template(template, data)
+        .map_err(|err| format_err!("could not render template '{template}' - {err}"))
+}
+
+fn handlebars_json_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _rc: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let param = h
+        .param(0)
+        .ok_or_else(|| RenderError::new("json: param not found"))?;
+
+    out.write(&param.value().to_string())?;
+
+    Ok(())
+}
+
+/// Send a notification as HTTP request to a webhook.
+pub(super) async fn send(target: &WebhookConfig, notification: &Notification) -> Result<(), Error> {
+    let mut hb = Handlebars::new();
+    hb.register_escape_fn(handlebars::no_escape);
+    hb.register_helper("json", Box::new(handlebars_json_helper));
+
+    let mut secrets = BTreeMap::new();
+    for secret in target.secret.iter().flatten() {
+        if let Some((name, value)) = secret.split_once('=') {
+            secrets.insert(name, value);
+        }
+    }
+
+    let mut data = notification.template_data();
+    data["secrets"] = serde_json::to_value(secrets)?;
+
+    let method = match target.method.unwrap_or_default() {
+        WebhookMethod::Post => Method::POST,
+        WebhookMethod::Put => Method::PUT,
+        WebhookMethod::Get => Method::GET,
+    };
+
+    let url = render(&hb, &target.url, &data)?;
+
+    let mut request = Request::builder().method(method).uri(url);
+
+    for header in target.header.iter().flatten() {
+        let (name, value) = header
+            .split_once(": ")
+            .ok_or_else(|| format_err!("invalid header '{header}'"))?;
+        request = request.header(name, render(&hb, value, &data)?);
+    }
+
+    let body = match target.body {
+        Some(ref body) => {
+            let template = String::from_utf8(base64::decode(body)?)
+                .map_err(|err| format_err!("body template is not valid UTF-8 - {err}"))?;
+            Body::from(render(&hb, &template, &data)?)
+        }
+        None => Body::empty(),
+    };
+
+    http_request(request.body(body)?).await
+}
@@ -0,0 +1,227 @@
+//! Send metrics to OpenTelemetry collectors via OTLP/HTTP with JSON encoding
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::{bail, format_err, Error};
+use hyper::{header, Body, Method, Request};
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use serde_json::{json, Value};
+
+use proxmox_http::client::Client;
+use proxmox_http::HttpOptions;
+use proxmox_metrics::MetricsData;
+
+use pbs_api_types::OtlpHttp;
+
+use crate::tools::{DEFAULT_USER_AGENT_STRING, PROXMOX_BACKUP_TCP_KEEPALIVE_TIME};
+
+const OTLP_METRICS_PATH: &str = "/v1/metrics";
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// The resource attributes, the configured ones override the defaults.
+fn resource_attributes(config: &OtlpHttp) -> Vec<Value> {
+    let mut attributes = BTreeMap::new();
+    attributes.insert("service.name", "proxmox-backup-server");
+    attributes.insert("host.name", proxmox_sys::nodename());
+
+    for attribute in config.resource_attribute.iter().flatten() {
+        if let Some((key, value)) = attribute.split_once('=') {
+            attributes.insert(key, value);
+        }
+    }
+
+    attributes
+        .into_iter()
+        .map(|(key, value)| string_attribute(key, value))
+        .collect()
+}
+
+/// Convert the data sent to InfluxDB servers into an OTLP `ExportMetricsServiceRequest`.
+///
+/// Each numeric value becomes a gauge named `pbs.<measurement>.<value name>`, the tags become
+/// attributes of the data point.
+fn export_request(data: &[Arc<MetricsData>], resource_attributes: Vec<Value>) -> Value {
+    let mut metrics: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+
+    for item in data {
+        let values = match item.values.as_object() {
+            Some(values) => values,
+            None => continue,
+        };
+
+        let mut tags: Vec<(&String, &String)> = item.tags.iter().collect();
+        tags.sort_unstable();
+        let attributes: Vec<Value> = tags
+            .into_iter()
+            .map(|(key, value)| string_attribute(key, value))
+            .collect();
+        let time = (item.ctime as u64 * 1_000_000_000).to_string();
+
+        for (name, value) in values {
+            let mut data_point = json!({
+                "attributes": attributes,
+                "timeUnixNano": time,
+            });
+            if let Some(value) = value.as_i64() {
+                data_point["asInt"] = value.to_string().into();
+            } else if let Some(value) = value.as_f64() {
+                data_point["asDouble"] = value.into();
+            } else {
+                // only numeric values can be sent, e.g. skip the device name of NICs
+                continue;
+            }
+
+            metrics
+                .entry(format!("pbs.{}.{name}", item.measurement))
+                .or_default()
+                .push(data_point);
+        }
+    }
+
+    let metrics: Vec<Value> = metrics
+        .into_iter()
+        .map(|(name, data_points)| {
+            json!({
+                "name": name,
+                "gauge": { "dataPoints": data_points },
+            })
+        })
+        .collect();
+
+    json!({
+        "resourceMetrics": [{
+            "resource": { "attributes": resource_attributes },
+            "scopeMetrics": [{
+                "scope": {
+                    "name": "proxmox-backup",
+                    "version": pbs_buildcfg::PROXMOX_PKG_VERSION,
+                },
+                "metrics": metrics,
+            }],
+        }],
+    })
+}
+
+/// Use the default OTLP path if the configured URL has none.
+fn metrics_url(url: &str) -> Result<hyper::Uri, Error> {
+    let uri: hyper::Uri = url
+        .parse()
+        .map_err(|err| format_err!("invalid url '{url}' - {err}"))?;
+
+    if uri.path().is_empty() || uri.path() == "/" {
+        let mut parts = uri.into_parts();
+        parts.path_and_query = Some(OTLP_METRICS_PATH.parse()?);
+        Ok(hyper::Uri::from_parts(parts)?)
+    } else {
+        Ok(uri)
+    }
+}
+
+fn http_client(config: &OtlpHttp) -> Result<Client, Error> {
+    let proxy_config = match crate::config::node::config() {
+        Ok((node_config, _digest)) => node_config.http_proxy(),
+        Err(_) => None,
+    };
+    let options = HttpOptions {
+        proxy_config,
+        user_agent: Some(DEFAULT_USER_AGENT_STRING.to_string()),
+        tcp_keepalive: Some(PROXMOX_BACKUP_TCP_KEEPALIVE_TIME),
+    };
+
+    let mut ssl_connector = SslConnector::builder(SslMethod::tls_client())?;
+    if !config.verify_tls.unwrap_or(true) {
+        ssl_connector.set_verify(SslVerifyMode::NONE);
+    }
+
+    Ok(Client::with_ssl_connector(ssl_connector.build(), options))
+}
+
+async fn post(config: &OtlpHttp, payload: Value) -> Result<(), Error> {
+    let client = http_client(config)?;
+
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri(metrics_url(&config.url)?)
+        .header(header::CONTENT_TYPE, "application/json");
+
+    for header in config.header.iter().flatten() {
+        let (name, value) = header
+            .split_once(": ")
+            .ok_or_else(|| format_err!("invalid header '{header}'"))?;
+        request = request.header(name, value);
+    }
+
+    let request = request.body(Body::from(payload.to_string()))?;
+
+    let response = client.request(request).await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        bail!(
+            "request failed with status {} - {}",
+            status,
+            String::from_utf8_lossy(&body).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// Send the metric data to an OpenTelemetry collector.
+pub async fn send_otlp_http(config: &OtlpHttp, data: &[Arc<MetricsData>]) -> Result<(), Error> {
+    let payload = export_request(data, resource_attributes(config));
+    post(config, payload).await
+}
+
+/// Test the connection to an OpenTelemetry collector by sending an empty request.
+pub async fn test_otlp_http(config: &OtlpHttp) -> Result<(), Error> {
+    post(config, json!({ "resourceMetrics": [] })).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_export_request() -> Result<(), Error> {
+        let data = vec![Arc::new(
+            MetricsData::new(
+                "blockstat",
+                1700000000,
+                json!({ "total": 1024, "io_ticks": 0.5, "device": "sda" }),
+            )?
+            .tag("object", "host")
+            .tag("datastore", "store1"),
+        )];
+
+        let request = export_request(&data, vec![string_attribute("host.name", "pbs")]);
+        let resource = &request["resourceMetrics"][0];
+        assert_eq!(resource["resource"]["attributes"][0]["key"], "host.name");
+
+        let metrics = resource["scopeMetrics"][0]["metrics"].as_array().unwrap();
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0]["name"], "pbs.blockstat.io_ticks");
+        assert_eq!(metrics[1]["name"], "pbs.blockstat.total");
+
+        let point = &metrics[1]["gauge"]["dataPoints"][0];
+        assert_eq!(point["asInt"], "1024");
+        assert_eq!(point["timeUnixNano"], "1700000000000000000");
+        assert_eq!(point["attributes"][0]["key"], "datastore");
+        assert_eq!(point["attributes"][1]["value"]["stringValue"], "host");
+
+        assert_eq!(
+            metrics_url("http://collector:4318")?.to_string(),
+            "http://collector:4318/v1/metrics"
+        );
+        assert_eq!(
+            metrics_url("https://collector/otlp/v1/metrics")?.to_string(),
+            "https://collector/otlp/v1/metrics"
+        );
+
+        Ok(())
+    }
+}
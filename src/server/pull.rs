@@ -1,7 +1,8 @@
-//! Sync datastore from remote server
+//! Sync datastore from remote server or another local datastore
 
 use std::collections::{HashMap, HashSet};
 use std::io::{Seek, SeekFrom};
+use std::os::unix::fs::OpenOptionsExt;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
@@ -12,98 +13,179 @@ use pbs_config::CachedUserInfo;
 use serde_json::json;
 
 use proxmox_router::HttpError;
-use proxmox_sys::task_log;
+use proxmox_sys::fs::{replace_file, CreateOptions};
+use proxmox_sys::{task_log, task_warn};
 
 use pbs_api_types::{
-    print_store_and_ns, Authid, BackupNamespace, GroupFilter, GroupListItem, NamespaceListItem,
-    Operation, RateLimitConfig, Remote, SnapshotListItem, MAX_NAMESPACE_DEPTH,
-    PRIV_DATASTORE_AUDIT, PRIV_DATASTORE_BACKUP,
+    print_store_and_ns, Authid, BackupNamespace, CryptMode, GroupFilter, GroupListItem,
+    NamespaceListItem, Operation, RateLimitConfig, Remote, SnapshotListItem, MAX_NAMESPACE_DEPTH,
+    PRIV_DATASTORE_AUDIT, PRIV_DATASTORE_BACKUP, PRIV_DATASTORE_READ,
 };
 
 use pbs_client::{
     BackupReader, BackupRepository, HttpClient, HttpClientOptions, RemoteChunkReader,
 };
-use pbs_datastore::data_blob::DataBlob;
+use pbs_datastore::data_blob::{DataBlob, DataChunkBuilder};
 use pbs_datastore::dynamic_index::DynamicIndexReader;
 use pbs_datastore::fixed_index::FixedIndexReader;
 use pbs_datastore::index::IndexFile;
 use pbs_datastore::manifest::{
     archive_type, ArchiveType, BackupManifest, FileInfo, CLIENT_LOG_BLOB_NAME, MANIFEST_BLOB_NAME,
 };
-use pbs_datastore::{check_backup_owner, DataStore, StoreProgress};
+use pbs_datastore::read_chunk::ReadChunk;
+use pbs_datastore::{
+    check_backup_owner, DataStore, LocalChunkReader, SnapshotReader, StoreProgress,
+};
+use pbs_tools::crypt_config::CryptConfig;
 use pbs_tools::sha::sha256;
 use proxmox_rest_server::WorkerTask;
 
-use crate::backup::{check_ns_modification_privs, check_ns_privs};
+use crate::backup::{
+    account_snapshot_quotas, check_ns_modification_privs, check_ns_privs, load_backup_quotas,
+    BackupQuota,
+};
 use crate::tools::parallel_handler::ParallelHandler;
 
+/// Directory holding the server-side keys used to encrypt pulled snapshots.
+pub(crate) const SYNC_ENCRYPTION_KEY_DIR: &str = pbs_buildcfg::configdir!("/sync-encryption-keys");
+
+/// Loads the server-side key `name` used to encrypt pulled snapshots.
+///
+/// Keys are stored as `<name>.json` key files without password in [SYNC_ENCRYPTION_KEY_DIR],
+/// for example created with `proxmox-backup-client key create --kdf none`.
+pub(crate) fn load_sync_encryption_key(name: &str) -> Result<Arc<CryptConfig>, Error> {
+    let path = std::path::Path::new(SYNC_ENCRYPTION_KEY_DIR).join(format!("{name}.json"));
+    let (key, _created, _fingerprint) = pbs_key_config::load_and_decrypt_key(&path, &|| {
+        bail!("password protected keys are not supported for sync encryption")
+    })?;
+    Ok(Arc::new(CryptConfig::new(key)?))
+}
+
+/// Source of a pull operation.
+pub(crate) enum PullSource {
+    /// Remote that is pulled from, with the full specification of the remote datastore
+    Remote {
+        remote: Remote,
+        repo: BackupRepository,
+    },
+    /// Local datastore that is pulled from
+    Local(Arc<DataStore>),
+}
+
+impl PullSource {
+    /// Name of the datastore that is pulled from.
+    fn store(&self) -> &str {
+        match self {
+            PullSource::Remote { repo, .. } => repo.store(),
+            PullSource::Local(store) => store.name(),
+        }
+    }
+}
+
+/// Client for accessing the source of a pull operation, see [PullParameters::client].
+pub(crate) enum PullClient {
+    Remote {
+        client: HttpClient,
+        repo: BackupRepository,
+    },
+    Local(Arc<DataStore>),
+}
+
 /// Parameters for a pull operation.
 pub(crate) struct PullParameters {
-    /// Remote that is pulled from
-    remote: Remote,
-    /// Full specification of remote datastore
-    source: BackupRepository,
+    /// Remote or local datastore that is pulled from
+    source: PullSource,
     /// Local store that is pulled into
     store: Arc<DataStore>,
-    /// Remote namespace
-    remote_ns: BackupNamespace,
+    /// Source namespace
+    source_ns: BackupNamespace,
     /// Local namespace (anchor)
     ns: BackupNamespace,
     /// Owner of synced groups (needs to match local owner of pre-existing groups)
     owner: Authid,
-    /// Whether to remove groups which exist locally, but not on the remote end
+    /// Whether to remove groups which exist locally, but not on the source end
     remove_vanished: bool,
     /// How many levels of sub-namespaces to pull (0 == no recursion, None == maximum recursion)
     max_depth: Option<usize>,
     /// Filters for reducing the pull scope
     group_filter: Option<Vec<GroupFilter>>,
-    /// Rate limits for all transfers from `remote`
+    /// Rate limits for all transfers from a `remote` source
     limit: RateLimitConfig,
     /// How many snapshots should be transferred at most (taking the newest N snapshots)
     transfer_last: Option<usize>,
+    /// Server-side key for encrypting plaintext snapshots while pulling them
+    crypt_config: Option<Arc<CryptConfig>>,
 }
 
 impl PullParameters {
     /// Creates a new instance of `PullParameters`.
     ///
     /// `remote` will be dereferenced via [pbs_api_types::RemoteConfig], and combined into a
-    /// [BackupRepository] with `remote_store`.
+    /// [BackupRepository] with `remote_store`. Without `remote`, `remote_store` is the name of a
+    /// local datastore to pull from. `encryption_key` names a key loaded with
+    /// [load_sync_encryption_key].
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         store: &str,
         ns: BackupNamespace,
-        remote: &str,
+        remote: Option<&str>,
         remote_store: &str,
-        remote_ns: BackupNamespace,
+        source_ns: BackupNamespace,
         owner: Authid,
         remove_vanished: Option<bool>,
         max_depth: Option<usize>,
         group_filter: Option<Vec<GroupFilter>>,
         limit: RateLimitConfig,
         transfer_last: Option<usize>,
+        encryption_key: Option<&str>,
     ) -> Result<Self, Error> {
         let store = DataStore::lookup_datastore(store, Some(Operation::Write))?;
 
         if let Some(max_depth) = max_depth {
             ns.check_max_depth(max_depth)?;
-            remote_ns.check_max_depth(max_depth)?;
+            source_ns.check_max_depth(max_depth)?;
         }
 
-        let (remote_config, _digest) = pbs_config::remote::config()?;
-        let remote: Remote = remote_config.lookup("remote", remote)?;
+        let source = match remote {
+            Some(remote) => {
+                let (remote_config, _digest) = pbs_config::remote::config()?;
+                let remote: Remote = remote_config.lookup("remote", remote)?;
+
+                let repo = BackupRepository::new(
+                    Some(remote.config.auth_id.clone()),
+                    Some(remote.config.host.clone()),
+                    remote.config.port,
+                    remote_store.to_string(),
+                );
+
+                PullSource::Remote { remote, repo }
+            }
+            None => {
+                let source = DataStore::lookup_datastore(remote_store, Some(Operation::Read))?;
+
+                if source.name() == store.name()
+                    && (source_ns.contains(&ns).is_some() || ns.contains(&source_ns).is_some())
+                {
+                    bail!(
+                        "cannot sync {} into {} - namespaces overlap",
+                        print_store_and_ns(source.name(), &source_ns),
+                        print_store_and_ns(store.name(), &ns),
+                    );
+                }
+
+                PullSource::Local(source)
+            }
+        };
 
         let remove_vanished = remove_vanished.unwrap_or(false);
 
-        let source = BackupRepository::new(
-            Some(remote.config.auth_id.clone()),
-            Some(remote.config.host.clone()),
-            remote.config.port,
-            remote_store.to_string(),
-        );
+        let crypt_config = match encryption_key {
+            Some(name) => Some(load_sync_encryption_key(name)?),
+            None => None,
+        };
 
         Ok(Self {
-            remote,
-            remote_ns,
+            source_ns,
             ns,
             source,
             store,
@@ -113,18 +195,80 @@ impl PullParameters {
             group_filter,
             limit,
             transfer_last,
+            crypt_config,
         })
     }
 
-    /// Creates a new [HttpClient] for accessing the [Remote] that is pulled from.
-    pub async fn client(&self) -> Result<HttpClient, Error> {
-        crate::api2::config::remote::remote_client(&self.remote, Some(self.limit.clone())).await
+    /// Creates a new [PullClient] for accessing the source that is pulled from.
+    ///
+    /// For a [Remote] this is a new [HttpClient], for a local datastore no connection is needed.
+    pub async fn client(&self) -> Result<PullClient, Error> {
+        match &self.source {
+            PullSource::Remote { remote, repo } => {
+                let client =
+                    crate::api2::config::remote::remote_client(remote, Some(self.limit.clone()))
+                        .await?;
+                Ok(PullClient::Remote {
+                    client,
+                    repo: repo.clone(),
+                })
+            }
+            PullSource::Local(store) => Ok(PullClient::Local(store.clone())),
+        }
+    }
+}
+
+/// Reader for the contents of a single source snapshot.
+enum SnapshotSource {
+    Remote(Arc<BackupReader>),
+    Local(Arc<DataStore>, SnapshotReader),
+}
+
+impl SnapshotSource {
+    /// Copies the file `filename` of the source snapshot into `output`.
+    async fn download<W: std::io::Write + Send>(
+        &self,
+        filename: &str,
+        mut output: W,
+    ) -> Result<(), Error> {
+        match self {
+            SnapshotSource::Remote(reader) => reader.download(filename, output).await,
+            SnapshotSource::Local(_, reader) => {
+                let mut file = reader.open_file(filename)?;
+                std::io::copy(&mut file, &mut output)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn chunk_source(&self, crypt_mode: CryptMode) -> ChunkSource {
+        match self {
+            SnapshotSource::Remote(reader) => ChunkSource::Remote(RemoteChunkReader::new(
+                reader.clone(),
+                None,
+                crypt_mode,
+                HashMap::new(),
+            )),
+            SnapshotSource::Local(store, _) => ChunkSource::Local(
+                store.clone(),
+                LocalChunkReader::new(store.clone(), None, crypt_mode),
+            ),
+        }
     }
 }
 
+/// Reader for the chunks referenced by a source index.
+#[derive(Clone)]
+enum ChunkSource {
+    Remote(RemoteChunkReader),
+    /// Chunks of a local datastore are hardlinked or reflinked into the target if possible,
+    /// and only read and written otherwise.
+    Local(Arc<DataStore>, LocalChunkReader),
+}
+
 async fn pull_index_chunks<I: IndexFile>(
     worker: &WorkerTask,
-    chunk_reader: RemoteChunkReader,
+    chunk_source: ChunkSource,
     target: Arc<DataStore>,
     index: I,
     downloaded_chunks: Arc<Mutex<HashSet<[u8; 32]>>>,
@@ -163,12 +307,14 @@ async fn pull_index_chunks<I: IndexFile>(
     let verify_and_write_channel = verify_pool.channel();
 
     let bytes = Arc::new(AtomicUsize::new(0));
+    let linked_chunks = Arc::new(AtomicUsize::new(0));
 
     stream
         .map(|info| {
             let target = Arc::clone(&target);
-            let chunk_reader = chunk_reader.clone();
+            let chunk_source = chunk_source.clone();
             let bytes = Arc::clone(&bytes);
+            let linked_chunks = Arc::clone(&linked_chunks);
             let verify_and_write_channel = verify_and_write_channel.clone();
 
             Ok::<_, Error>(async move {
@@ -180,7 +326,23 @@ async fn pull_index_chunks<I: IndexFile>(
                     return Ok::<_, Error>(());
                 }
                 //task_log!(worker, "sync {} chunk {}", pos, hex::encode(digest));
-                let chunk = chunk_reader.read_raw_chunk(&info.digest).await?;
+                let chunk = match chunk_source {
+                    ChunkSource::Remote(ref chunk_reader) => {
+                        chunk_reader.read_raw_chunk(&info.digest).await?
+                    }
+                    ChunkSource::Local(ref source, ref chunk_reader) => {
+                        let linked = proxmox_async::runtime::block_in_place(|| {
+                            target.insert_chunk_from(source, &info.digest)
+                        })?;
+                        if linked {
+                            linked_chunks.fetch_add(1, Ordering::SeqCst);
+                            return Ok(());
+                        }
+                        proxmox_async::runtime::block_in_place(|| {
+                            ReadChunk::read_raw_chunk(chunk_reader, &info.digest)
+                        })?
+                    }
+                };
                 let raw_size = chunk.raw_size() as usize;
 
                 // decode, verify and write in a separate threads to maximize throughput
@@ -212,11 +374,315 @@ async fn pull_index_chunks<I: IndexFile>(
         (bytes as f64) / (1024.0 * 1024.0 * elapsed)
     );
 
+    let linked_chunks = linked_chunks.load(Ordering::SeqCst);
+    if linked_chunks > 0 {
+        task_log!(
+            worker,
+            "linked {} chunks from source datastore",
+            linked_chunks
+        );
+    }
+
+    Ok(())
+}
+
+/// Encrypts plaintext snapshots with a server-side key while pulling them.
+#[derive(Clone)]
+struct SyncEncryption {
+    crypt_config: Arc<CryptConfig>,
+    /// Digests of already encrypted chunks, by the digest of their plaintext source chunk
+    digest_map: Arc<Mutex<HashMap<[u8; 32], [u8; 32]>>>,
+}
+
+/// Key in the unprotected part of an encrypted manifest holding the checksum of the source files.
+const SYNC_SOURCE_CSUM_KEY: &str = "sync-source-csum";
+
+/// Reads the plaintext chunks referenced by `index` and stores them encrypted in `target`.
+///
+/// Returns the digests of the encrypted chunks in index order.
+async fn encrypt_index_chunks<I: IndexFile>(
+    worker: &WorkerTask,
+    chunk_source: ChunkSource,
+    target: Arc<DataStore>,
+    index: &I,
+    encryption: &SyncEncryption,
+) -> Result<Vec<[u8; 32]>, Error> {
+    use futures::stream::{self, StreamExt, TryStreamExt};
+
+    let start_time = SystemTime::now();
+
+    let bytes = Arc::new(AtomicUsize::new(0));
+
+    let digests: Vec<[u8; 32]> =
+        stream::iter((0..index.index_count()).map(|pos| index.chunk_info(pos).unwrap()))
+            .map(|info| {
+                let target = Arc::clone(&target);
+                let chunk_source = chunk_source.clone();
+                let bytes = Arc::clone(&bytes);
+                let encryption = encryption.clone();
+
+                async move {
+                    if let Some(digest) = encryption.digest_map.lock().unwrap().get(&info.digest) {
+                        return Ok::<_, Error>(*digest);
+                    }
+
+                    let chunk = match chunk_source {
+                        ChunkSource::Remote(ref chunk_reader) => {
+                            chunk_reader.read_raw_chunk(&info.digest).await?
+                        }
+                        ChunkSource::Local(_, ref chunk_reader) => {
+                            proxmox_async::runtime::block_in_place(|| {
+                                ReadChunk::read_raw_chunk(chunk_reader, &info.digest)
+                            })?
+                        }
+                    };
+                    bytes.fetch_add(chunk.raw_size() as usize, Ordering::SeqCst);
+
+                    let digest = proxmox_async::runtime::block_in_place(|| {
+                        let data = chunk.decode(None, Some(&info.digest))?;
+                        let mut builder = DataChunkBuilder::new(&data)
+                            .compress(true)
+                            .crypt_config(&encryption.crypt_config);
+                        let digest = *builder.digest();
+                        if !target.cond_touch_chunk(&digest, false)? {
+                            let (chunk, _) = builder.build()?;
+                            target.insert_chunk(&chunk, &digest)?;
+                        }
+                        Ok::<_, Error>(digest)
+                    })?;
+
+                    encryption
+                        .digest_map
+                        .lock()
+                        .unwrap()
+                        .insert(info.digest, digest);
+
+                    Ok(digest)
+                }
+            })
+            .buffered(20)
+            .try_collect()
+            .await?;
+
+    let elapsed = start_time.elapsed()?.as_secs_f64();
+
+    let bytes = bytes.load(Ordering::SeqCst);
+
+    task_log!(
+        worker,
+        "downloaded and encrypted {} bytes ({:.2} MiB/s)",
+        bytes,
+        (bytes as f64) / (1024.0 * 1024.0 * elapsed)
+    );
+
+    Ok(digests)
+}
+
+/// Pulls an index referenced by a manifest, rewriting it to reference encrypted chunks.
+///
+/// Returns checksum and size of the new index.
+async fn pull_index_encrypted(
+    worker: &WorkerTask,
+    reader: &SnapshotSource,
+    snapshot: &pbs_datastore::BackupDir,
+    archive_info: &FileInfo,
+    encryption: &SyncEncryption,
+) -> Result<([u8; 32], u64), Error> {
+    let archive_name = &archive_info.filename;
+    let mut path = snapshot.full_path();
+    path.push(archive_name);
+
+    let mut tmp_path = path.clone();
+    tmp_path.set_extension("tmp");
+
+    let mut relative_path = snapshot.relative_path();
+    relative_path.push(archive_name);
+
+    task_log!(worker, "sync and encrypt archive {}", archive_name);
+
+    let mut tmpfile = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .read(true)
+        .open(&tmp_path)?;
+
+    reader.download(archive_name, &mut tmpfile).await?;
+
+    let target = snapshot.datastore().clone();
+    let chunk_source = reader.chunk_source(archive_info.chunk_crypt_mode());
+
+    let result = match archive_type(archive_name)? {
+        ArchiveType::DynamicIndex => {
+            let index = DynamicIndexReader::new(tmpfile).map_err(|err| {
+                format_err!("unable to read dynamic index {:?} - {}", tmp_path, err)
+            })?;
+            let (csum, size) = index.compute_csum();
+            verify_archive(archive_info, &csum, size)?;
+
+            let digests =
+                encrypt_index_chunks(worker, chunk_source, target.clone(), &index, encryption)
+                    .await?;
+
+            let mut writer = target.create_dynamic_writer(&relative_path)?;
+            for (pos, digest) in digests.iter().enumerate() {
+                let info = index.chunk_info(pos).unwrap();
+                writer.add_chunk(info.range.end, digest)?;
+            }
+            writer.close()?;
+
+            DynamicIndexReader::open(&path)?.compute_csum()
+        }
+        ArchiveType::FixedIndex => {
+            let index = FixedIndexReader::new(tmpfile).map_err(|err| {
+                format_err!("unable to read fixed index '{:?}' - {}", tmp_path, err)
+            })?;
+            let (csum, size) = index.compute_csum();
+            verify_archive(archive_info, &csum, size)?;
+
+            let digests =
+                encrypt_index_chunks(worker, chunk_source, target.clone(), &index, encryption)
+                    .await?;
+
+            let mut writer = target.create_fixed_writer(
+                &relative_path,
+                index.size as usize,
+                index.chunk_size,
+            )?;
+            for (pos, digest) in digests.iter().enumerate() {
+                writer.add_digest(pos, digest)?;
+            }
+            writer.close()?;
+
+            FixedIndexReader::open(&path)?.compute_csum()
+        }
+        ArchiveType::Blob => bail!("archive '{}' is not an index", archive_name),
+    };
+
+    let _ = std::fs::remove_file(&tmp_path);
+
+    Ok(result)
+}
+
+/// Pulls the blob `filename` and stores it encrypted.
+///
+/// The source blob is verified against `archive_info` if given. Returns checksum and size of the
+/// new blob.
+async fn pull_blob_encrypted(
+    reader: &SnapshotSource,
+    snapshot: &pbs_datastore::BackupDir,
+    filename: &str,
+    archive_info: Option<&FileInfo>,
+    crypt_config: &CryptConfig,
+) -> Result<([u8; 32], u64), Error> {
+    let mut raw_data = Vec::new();
+    reader.download(filename, &mut raw_data).await?;
+
+    if let Some(archive_info) = archive_info {
+        let csum = openssl::sha::sha256(&raw_data);
+        verify_archive(archive_info, &csum, raw_data.len() as u64)?;
+    }
+
+    let data = DataBlob::from_raw(raw_data)?.decode(None, None)?;
+    let blob = DataBlob::encode(&data, Some(crypt_config), true)?;
+
+    let mut path = snapshot.full_path();
+    path.push(filename);
+    replace_file(&path, blob.raw_data(), CreateOptions::new(), false)?;
+
+    Ok((openssl::sha::sha256(blob.raw_data()), blob.raw_size()))
+}
+
+/// Pulls a plaintext snapshot, encrypting it with the server-side key of `encryption`.
+///
+/// Encrypting changes the digests of all chunks, so indexes are rewritten and a new manifest,
+/// signed with the key, is created. The checksum of the source file list is kept in the
+/// unprotected part of the manifest to detect unchanged snapshots on re-sync.
+async fn pull_snapshot_encrypted(
+    worker: &WorkerTask,
+    reader: &SnapshotSource,
+    snapshot: &pbs_datastore::BackupDir,
+    source_manifest: BackupManifest,
+    encryption: &SyncEncryption,
+) -> Result<(), Error> {
+    let mut manifest_name = snapshot.full_path();
+    manifest_name.push(MANIFEST_BLOB_NAME);
+
+    let mut client_log_name = snapshot.full_path();
+    client_log_name.push(CLIENT_LOG_BLOB_NAME);
+
+    let crypt_config = &encryption.crypt_config;
+    let source_csum = hex::encode(openssl::sha::sha256(&serde_json::to_vec(
+        source_manifest.files(),
+    )?));
+
+    if manifest_name.exists() {
+        let (manifest, _) = snapshot.load_manifest()?;
+        if manifest.unprotected[SYNC_SOURCE_CSUM_KEY].as_str() == Some(source_csum.as_str()) {
+            if !client_log_name.exists() {
+                // Note: be silent if there is no log
+                let _ =
+                    pull_blob_encrypted(reader, snapshot, CLIENT_LOG_BLOB_NAME, None, crypt_config)
+                        .await;
+            }
+            task_log!(worker, "no data changes");
+            return Ok(()); // nothing changed
+        }
+    }
+
+    let mut manifest = BackupManifest::new(snapshot.dir().clone());
+    manifest.unprotected = source_manifest.unprotected.clone();
+    if let Some(unprotected) = manifest.unprotected.as_object_mut() {
+        // verification results refer to the source chunks
+        unprotected.remove("verify_state");
+    }
+    manifest.unprotected[SYNC_SOURCE_CSUM_KEY] = source_csum.into();
+
+    for item in source_manifest.files() {
+        let (csum, size) = match archive_type(&item.filename)? {
+            ArchiveType::DynamicIndex | ArchiveType::FixedIndex => {
+                pull_index_encrypted(worker, reader, snapshot, item, encryption).await?
+            }
+            ArchiveType::Blob => {
+                task_log!(worker, "sync and encrypt archive {}", item.filename);
+                pull_blob_encrypted(reader, snapshot, &item.filename, Some(item), crypt_config)
+                    .await?
+            }
+        };
+        manifest.add_file(item.filename.clone(), size, csum, CryptMode::Encrypt)?;
+    }
+
+    if !client_log_name.exists() {
+        let res =
+            pull_blob_encrypted(reader, snapshot, CLIENT_LOG_BLOB_NAME, None, crypt_config).await;
+        // Note: be silent if there is no log - only log successful download
+        if res.is_ok() {
+            task_log!(worker, "got backup log file {:?}", CLIENT_LOG_BLOB_NAME);
+        }
+    }
+
+    let manifest_data = manifest.to_string(Some(crypt_config))?;
+    let manifest_blob = DataBlob::encode(manifest_data.as_bytes(), None, true)?;
+    replace_file(
+        &manifest_name,
+        manifest_blob.raw_data(),
+        CreateOptions::new(),
+        false,
+    )?;
+
+    snapshot
+        .cleanup_unreferenced_files(&manifest)
+        .map_err(|err| format_err!("failed to cleanup unreferenced files - {err}"))?;
+
+    snapshot
+        .datastore()
+        .backend_upload_snapshot(snapshot)
+        .map_err(|err| format_err!("failed to upload snapshot to backend - {err}"))?;
+
     Ok(())
 }
 
 async fn download_manifest(
-    reader: &BackupReader,
+    reader: &SnapshotSource,
     filename: &std::path::Path,
 ) -> Result<std::fs::File, Error> {
     let mut tmp_manifest_file = std::fs::OpenOptions::new()
@@ -262,8 +728,7 @@ fn verify_archive(info: &FileInfo, csum: &[u8; 32], size: u64) -> Result<(), Err
 /// - Rename tmp file into real path
 async fn pull_single_archive(
     worker: &WorkerTask,
-    reader: &BackupReader,
-    chunk_reader: &mut RemoteChunkReader,
+    reader: &SnapshotSource,
     snapshot: &pbs_datastore::BackupDir,
     archive_info: &FileInfo,
     downloaded_chunks: Arc<Mutex<HashSet<[u8; 32]>>>,
@@ -295,7 +760,7 @@ async fn pull_single_archive(
 
             pull_index_chunks(
                 worker,
-                chunk_reader.clone(),
+                reader.chunk_source(archive_info.chunk_crypt_mode()),
                 snapshot.datastore().clone(),
                 index,
                 downloaded_chunks,
@@ -311,7 +776,7 @@ async fn pull_single_archive(
 
             pull_index_chunks(
                 worker,
-                chunk_reader.clone(),
+                reader.chunk_source(archive_info.chunk_crypt_mode()),
                 snapshot.datastore().clone(),
                 index,
                 downloaded_chunks,
@@ -334,20 +799,20 @@ async fn pull_single_archive(
 // not mentioned in the manifest.
 async fn try_client_log_download(
     worker: &WorkerTask,
-    reader: Arc<BackupReader>,
+    reader: &SnapshotSource,
     path: &std::path::Path,
 ) -> Result<(), Error> {
     let mut tmp_path = path.to_owned();
     tmp_path.set_extension("tmp");
 
-    let tmpfile = std::fs::OpenOptions::new()
+    let mut tmpfile = std::fs::OpenOptions::new()
         .write(true)
         .create(true)
         .read(true)
         .open(&tmp_path)?;
 
     // Note: be silent if there is no log - only log successful download
-    if let Ok(()) = reader.download(CLIENT_LOG_BLOB_NAME, tmpfile).await {
+    if let Ok(()) = reader.download(CLIENT_LOG_BLOB_NAME, &mut tmpfile).await {
         if let Err(err) = std::fs::rename(&tmp_path, path) {
             bail!("Atomic rename file {:?} failed - {}", path, err);
         }
@@ -364,13 +829,16 @@ async fn try_client_log_download(
 /// -- if it matches, only download log and treat snapshot as already synced
 /// - Iterate over referenced files
 /// -- if file already exists, verify contents
-/// -- if not, pull it from the remote
+/// -- if not, pull it from the source
 /// - Download log if not already existing
+///
+/// With `encryption`, plaintext snapshots are encrypted instead, see [pull_snapshot_encrypted].
 async fn pull_snapshot(
     worker: &WorkerTask,
-    reader: Arc<BackupReader>,
+    reader: &SnapshotSource,
     snapshot: &pbs_datastore::BackupDir,
     downloaded_chunks: Arc<Mutex<HashSet<[u8; 32]>>>,
+    encryption: Option<&SyncEncryption>,
 ) -> Result<(), Error> {
     let mut manifest_name = snapshot.full_path();
     manifest_name.push(MANIFEST_BLOB_NAME);
@@ -381,7 +849,7 @@ async fn pull_snapshot(
     let mut tmp_manifest_name = manifest_name.clone();
     tmp_manifest_name.set_extension("tmp");
 
-    let download_res = download_manifest(&reader, &tmp_manifest_name).await;
+    let download_res = download_manifest(reader, &tmp_manifest_name).await;
     let mut tmp_manifest_file = match download_res {
         Ok(manifest_file) => manifest_file,
         Err(err) => {
@@ -407,6 +875,24 @@ async fn pull_snapshot(
     };
     let tmp_manifest_blob = DataBlob::load_from_reader(&mut tmp_manifest_file)?;
 
+    if let Some(encryption) = encryption {
+        let data = tmp_manifest_blob.decode(None, None)?;
+        let source_manifest = BackupManifest::from_data(&data, None)?;
+        if source_manifest
+            .files()
+            .iter()
+            .all(|item| item.crypt_mode != CryptMode::Encrypt)
+        {
+            let _ = std::fs::remove_file(&tmp_manifest_name);
+            return pull_snapshot_encrypted(worker, reader, snapshot, source_manifest, encryption)
+                .await;
+        }
+        task_log!(
+            worker,
+            "snapshot already contains encrypted archives - syncing it unchanged"
+        );
+    }
+
     if manifest_name.exists() {
         let manifest_blob = proxmox_lang::try_block!({
             let mut manifest_file = std::fs::File::open(&manifest_name).map_err(|err| {
@@ -471,22 +957,7 @@ async fn pull_snapshot(
             }
         }
 
-        let mut chunk_reader = RemoteChunkReader::new(
-            reader.clone(),
-            None,
-            item.chunk_crypt_mode(),
-            HashMap::new(),
-        );
-
-        pull_single_archive(
-            worker,
-            &reader,
-            &mut chunk_reader,
-            snapshot,
-            item,
-            downloaded_chunks.clone(),
-        )
-        .await?;
+        pull_single_archive(worker, reader, snapshot, item, downloaded_chunks.clone()).await?;
     }
 
     if let Err(err) = std::fs::rename(&tmp_manifest_name, &manifest_name) {
@@ -501,18 +972,28 @@ async fn pull_snapshot(
         .cleanup_unreferenced_files(&manifest)
         .map_err(|err| format_err!("failed to cleanup unreferenced files - {err}"))?;
 
+    snapshot
+        .datastore()
+        .backend_upload_snapshot(snapshot)
+        .map_err(|err| format_err!("failed to upload snapshot to backend - {err}"))?;
+
     Ok(())
 }
 
 /// Pulls a `snapshot`, removing newly created ones on error, but keeping existing ones in any case.
 ///
+/// The chunks of the pulled snapshot are accounted in `quotas`, a new snapshot exceeding one of
+/// them is removed again.
+///
 /// The `reader` is configured to read from the remote / source namespace, while the `snapshot` is
 /// pointing to the local datastore and target namespace.
 async fn pull_snapshot_from(
     worker: &WorkerTask,
-    reader: Arc<BackupReader>,
+    reader: &SnapshotSource,
     snapshot: &pbs_datastore::BackupDir,
     downloaded_chunks: Arc<Mutex<HashSet<[u8; 32]>>>,
+    encryption: Option<&SyncEncryption>,
+    quotas: &[BackupQuota],
 ) -> Result<(), Error> {
     let (_path, is_new, _snap_lock) = snapshot
         .datastore()
@@ -521,7 +1002,14 @@ async fn pull_snapshot_from(
     if is_new {
         task_log!(worker, "sync snapshot {}", snapshot.dir());
 
-        if let Err(err) = pull_snapshot(worker, reader, snapshot, downloaded_chunks).await {
+        let result =
+            match pull_snapshot(worker, reader, snapshot, downloaded_chunks, encryption).await {
+                Ok(()) => proxmox_async::runtime::block_in_place(|| {
+                    account_snapshot_quotas(quotas, snapshot)
+                }),
+                Err(err) => Err(err),
+            };
+        if let Err(err) = result {
             if let Err(cleanup_err) = snapshot.datastore().remove_backup_dir(
                 snapshot.backup_ns(),
                 snapshot.as_ref(),
@@ -534,14 +1022,15 @@ async fn pull_snapshot_from(
         task_log!(worker, "sync snapshot {} done", snapshot.dir());
     } else {
         task_log!(worker, "re-sync snapshot {}", snapshot.dir());
-        pull_snapshot(worker, reader, snapshot, downloaded_chunks).await?;
+        pull_snapshot(worker, reader, snapshot, downloaded_chunks, encryption).await?;
+        proxmox_async::runtime::block_in_place(|| account_snapshot_quotas(quotas, snapshot))?;
     }
 
     Ok(())
 }
 
 #[derive(PartialEq, Eq)]
-enum SkipReason {
+pub(crate) enum SkipReason {
     AlreadySynced,
     TransferLast,
 }
@@ -552,22 +1041,22 @@ impl std::fmt::Display for SkipReason {
             f,
             "{}",
             match self {
-                SkipReason::AlreadySynced => "older than the newest local snapshot",
+                SkipReason::AlreadySynced => "older than the newest synced snapshot",
                 SkipReason::TransferLast => "due to transfer-last",
             }
         )
     }
 }
 
-struct SkipInfo {
+pub(crate) struct SkipInfo {
     oldest: i64,
     newest: i64,
-    count: u64,
+    pub(crate) count: u64,
     skip_reason: SkipReason,
 }
 
 impl SkipInfo {
-    fn new(skip_reason: SkipReason) -> Self {
+    pub(crate) fn new(skip_reason: SkipReason) -> Self {
         SkipInfo {
             oldest: i64::MAX,
             newest: i64::MIN,
@@ -576,13 +1065,13 @@ impl SkipInfo {
         }
     }
 
-    fn reset(&mut self) {
+    pub(crate) fn reset(&mut self) {
         self.count = 0;
         self.oldest = i64::MAX;
         self.newest = i64::MIN;
     }
 
-    fn update(&mut self, backup_time: i64) {
+    pub(crate) fn update(&mut self, backup_time: i64) {
         self.count += 1;
 
         if backup_time < self.oldest {
@@ -619,16 +1108,126 @@ impl std::fmt::Display for SkipInfo {
     }
 }
 
+/// Opens the source `snapshot` for reading, returns `None` if it vanished in the meantime.
+async fn open_snapshot_source(
+    client: &PullClient,
+    params: &PullParameters,
+    source_ns: &BackupNamespace,
+    snapshot: &pbs_api_types::BackupDir,
+) -> Result<Option<SnapshotSource>, Error> {
+    match client {
+        PullClient::Remote { client, repo } => {
+            // get updated auth_info (new tickets)
+            let auth_info = client.login().await?;
+
+            let options = HttpClientOptions::new_non_interactive(
+                auth_info.ticket.clone(),
+                client.fingerprint(),
+            )
+            .rate_limit(params.limit.clone());
+
+            let new_client = HttpClient::new(repo.host(), repo.port(), repo.auth_id(), options)?;
+
+            let reader =
+                BackupReader::start(new_client, None, repo.store(), source_ns, snapshot, true)
+                    .await?;
+
+            Ok(Some(SnapshotSource::Remote(reader)))
+        }
+        PullClient::Local(source) => {
+            let source_snapshot = source.backup_dir(source_ns.clone(), snapshot.clone())?;
+            if !source_snapshot.full_path().exists() {
+                return Ok(None);
+            }
+            let reader = SnapshotReader::new(source.clone(), source_ns.clone(), snapshot.clone())?;
+            Ok(Some(SnapshotSource::Local(source.clone(), reader)))
+        }
+    }
+}
+
+/// Seeds the digest map of `encryption` from the previously pulled and encrypted `snapshot`.
+///
+/// The chunks of the source indexes are paired by position with those of the local, re-encrypted
+/// indexes, so chunks still referenced by newer snapshots are not downloaded and encrypted again.
+async fn seed_digest_map(
+    reader: &SnapshotSource,
+    snapshot: &pbs_datastore::BackupDir,
+    encryption: &SyncEncryption,
+) -> Result<(), Error> {
+    let (manifest, _) = snapshot.load_manifest()?;
+    let source_csum = match manifest.unprotected[SYNC_SOURCE_CSUM_KEY].as_str() {
+        Some(csum) => csum.to_string(),
+        None => return Ok(()), // not encrypted while pulling
+    };
+
+    let mut raw_data = Vec::new();
+    reader.download(MANIFEST_BLOB_NAME, &mut raw_data).await?;
+    let data = DataBlob::from_raw(raw_data)?.decode(None, None)?;
+    let source_manifest = BackupManifest::from_data(&data, None)?;
+    let csum = hex::encode(openssl::sha::sha256(&serde_json::to_vec(
+        source_manifest.files(),
+    )?));
+    if csum != source_csum {
+        return Ok(()); // the source snapshot changed since
+    }
+
+    let mut digest_map = HashMap::new();
+    for item in source_manifest.files() {
+        let index_type = archive_type(&item.filename)?;
+        if index_type == ArchiveType::Blob {
+            continue;
+        }
+
+        let mut tmpfile = std::fs::OpenOptions::new()
+            .write(true)
+            .read(true)
+            .custom_flags(libc::O_TMPFILE)
+            .open("/tmp")?;
+        reader.download(&item.filename, &mut tmpfile).await?;
+
+        let mut path = snapshot.full_path();
+        path.push(&item.filename);
+
+        let (source, local): (Box<dyn IndexFile>, Box<dyn IndexFile>) = match index_type {
+            ArchiveType::DynamicIndex => (
+                Box::new(DynamicIndexReader::new(tmpfile)?),
+                Box::new(DynamicIndexReader::open(&path)?),
+            ),
+            _ => (
+                Box::new(FixedIndexReader::new(tmpfile)?),
+                Box::new(FixedIndexReader::open(&path)?),
+            ),
+        };
+
+        let (csum, size) = source.compute_csum();
+        verify_archive(item, &csum, size)?;
+        if source.index_count() != local.index_count() {
+            continue;
+        }
+        for pos in 0..source.index_count() {
+            // unwrap: pos < index_count
+            digest_map.insert(
+                *source.index_digest(pos).unwrap(),
+                *local.index_digest(pos).unwrap(),
+            );
+        }
+    }
+
+    encryption.digest_map.lock().unwrap().extend(digest_map);
+
+    Ok(())
+}
+
 /// Pulls a group according to `params`.
 ///
 /// Pulling a group consists of the following steps:
-/// - Query the list of snapshots available for this group in the source namespace on the remote
+/// - Query the list of snapshots available for this group in the source namespace
 /// - Sort by snapshot time
 /// - Get last snapshot timestamp on local datastore
 /// - Iterate over list of snapshots
-/// -- Recreate client/BackupReader
+/// -- Recreate client/BackupReader, or lock the local source snapshot
 /// -- pull snapshot, unless it's not finished yet or older than last local snapshot
-/// - (remove_vanished) list all local snapshots, remove those that don't exist on remote
+/// - (remove_vanished) list all local snapshots, remove those that don't exist on the source
 ///
 /// Backwards-compat: if `source_ns` is [None], only the group type and ID will be sent to the
 /// remote when querying snapshots. This allows us to interact with old remotes that don't have
@@ -636,50 +1235,101 @@ impl std::fmt::Display for SkipInfo {
 ///
 /// Permission checks:
 /// - remote snapshot access is checked by remote (twice: query and opening the backup reader)
+/// - local source group access is already checked by pull_ns
 /// - local group owner is already checked by pull_store
 async fn pull_group(
     worker: &WorkerTask,
-    client: &HttpClient,
+    client: &PullClient,
     params: &PullParameters,
     group: &pbs_api_types::BackupGroup,
-    remote_ns: BackupNamespace,
+    source_ns: BackupNamespace,
     progress: &mut StoreProgress,
 ) -> Result<(), Error> {
     task_log!(worker, "sync group {}", group);
 
-    let path = format!(
-        "api2/json/admin/datastore/{}/snapshots",
-        params.source.store()
-    );
+    let target_ns = source_ns.map_prefix(&params.source_ns, &params.ns)?;
 
-    let mut args = json!({
-        "backup-type": group.ty,
-        "backup-id": group.id,
-    });
+    // list of source snapshots and whether they are finished
+    let mut list: Vec<(pbs_api_types::BackupDir, bool)> = match client {
+        PullClient::Remote { client, repo } => {
+            let path = format!("api2/json/admin/datastore/{}/snapshots", repo.store());
 
-    if !remote_ns.is_root() {
-        args["ns"] = serde_json::to_value(&remote_ns)?;
-    }
+            let mut args = json!({
+                "backup-type": group.ty,
+                "backup-id": group.id,
+            });
 
-    let target_ns = remote_ns.map_prefix(&params.remote_ns, &params.ns)?;
+            if !source_ns.is_root() {
+                args["ns"] = serde_json::to_value(&source_ns)?;
+            }
 
-    let mut result = client.get(&path, Some(args)).await?;
-    let mut list: Vec<SnapshotListItem> = serde_json::from_value(result["data"].take())?;
+            let mut result = client.get(&path, Some(args)).await?;
+            let list: Vec<SnapshotListItem> = serde_json::from_value(result["data"].take())?;
 
-    list.sort_unstable_by(|a, b| a.backup.time.cmp(&b.backup.time));
+            client.login().await?; // make sure auth is complete
 
-    client.login().await?; // make sure auth is complete
+            list.into_iter()
+                .map(|item| (item.backup, item.size.is_some()))
+                .collect()
+        }
+        PullClient::Local(source) => source
+            .backup_group(source_ns.clone(), group.clone())
+            .list_backups()?
+            .into_iter()
+            .map(|info| (info.backup_dir.dir().clone(), info.is_finished()))
+            .collect(),
+    };
 
-    let fingerprint = client.fingerprint();
+    list.sort_unstable_by(|a, b| a.0.time.cmp(&b.0.time));
 
     let last_sync = params.store.last_successful_backup(&target_ns, group)?;
     let last_sync_time = last_sync.unwrap_or(i64::MIN);
 
-    let mut remote_snapshots = std::collections::HashSet::new();
+    let mut source_snapshots = std::collections::HashSet::new();
+
+    // fails early if a quota applying to the target group is already exhausted
+    let quotas = proxmox_async::runtime::block_in_place(|| {
+        load_backup_quotas(&params.store, &target_ns, &params.owner)
+    })?;
 
     // start with 65536 chunks (up to 256 GiB)
     let downloaded_chunks = Arc::new(Mutex::new(HashSet::with_capacity(1024 * 64)));
 
+    let encryption = params
+        .crypt_config
+        .as_ref()
+        .map(|crypt_config| SyncEncryption {
+            crypt_config: crypt_config.clone(),
+            digest_map: Arc::new(Mutex::new(HashMap::new())),
+        });
+
+    // chunks still referenced by new snapshots are encrypted already
+    if let (Some(encryption), Some(last_sync)) = (&encryption, last_sync) {
+        let last_snapshot = list
+            .iter()
+            .find(|(snapshot, finished)| *finished && snapshot.time == last_sync);
+        if let Some((snapshot, _)) = last_snapshot {
+            let result = async {
+                if let Some(reader) =
+                    open_snapshot_source(client, params, &source_ns, snapshot).await?
+                {
+                    let local = params
+                        .store
+                        .backup_dir(target_ns.clone(), snapshot.clone())?;
+                    seed_digest_map(&reader, &local, encryption).await?;
+                }
+                Ok::<_, Error>(())
+            }
+            .await;
+            if let Err(err) = result {
+                task_warn!(
+                    worker,
+                    "unable to reuse encrypted chunks of {snapshot} - {err}"
+                );
+            }
+        }
+    }
+
     progress.group_snapshots = list.len() as u64;
 
     let mut already_synced_skip_info = SkipInfo::new(SkipReason::AlreadySynced);
@@ -692,11 +1342,9 @@ async fn pull_group(
         .map(|count| total_amount.saturating_sub(count))
         .unwrap_or_default();
 
-    for (pos, item) in list.into_iter().enumerate() {
-        let snapshot = item.backup;
-
+    for (pos, (snapshot, finished)) in list.into_iter().enumerate() {
         // in-progress backups can't be synced
-        if item.size.is_none() {
+        if !finished {
             task_log!(
                 worker,
                 "skipping snapshot {} - in-progress backup",
@@ -705,7 +1353,7 @@ async fn pull_group(
             continue;
         }
 
-        remote_snapshots.insert(snapshot.time);
+        source_snapshots.insert(snapshot.time);
 
         if last_sync_time > snapshot.time {
             already_synced_skip_info.update(snapshot.time);
@@ -723,33 +1371,29 @@ async fn pull_group(
             transfer_last_skip_info.reset();
         }
 
-        // get updated auth_info (new tickets)
-        let auth_info = client.login().await?;
-
-        let options =
-            HttpClientOptions::new_non_interactive(auth_info.ticket.clone(), fingerprint.clone())
-                .rate_limit(params.limit.clone());
-
-        let new_client = HttpClient::new(
-            params.source.host(),
-            params.source.port(),
-            params.source.auth_id(),
-            options,
-        )?;
-
-        let reader = BackupReader::start(
-            new_client,
-            None,
-            params.source.store(),
-            &remote_ns,
-            &snapshot,
-            true,
-        )
-        .await?;
+        let reader = match open_snapshot_source(client, params, &source_ns, &snapshot).await? {
+            Some(reader) => reader,
+            None => {
+                task_log!(
+                    worker,
+                    "skipping snapshot {} - vanished since start of sync",
+                    snapshot,
+                );
+                continue;
+            }
+        };
 
         let snapshot = params.store.backup_dir(target_ns.clone(), snapshot)?;
 
-        let result = pull_snapshot_from(worker, reader, &snapshot, downloaded_chunks.clone()).await;
+        let result = pull_snapshot_from(
+            worker,
+            &reader,
+            &snapshot,
+            downloaded_chunks.clone(),
+            encryption.as_ref(),
+            &quotas,
+        )
+        .await;
 
         progress.done_snapshots = pos as u64 + 1;
         task_log!(worker, "percentage done: {}", progress);
@@ -762,7 +1406,7 @@ async fn pull_group(
         let local_list = group.list_backups()?;
         for info in local_list {
             let snapshot = info.backup_dir;
-            if remote_snapshots.contains(&snapshot.backup_time()) {
+            if source_snapshots.contains(&snapshot.backup_time()) {
                 continue;
             }
             if snapshot.is_protected() {
@@ -783,23 +1427,46 @@ async fn pull_group(
     Ok(())
 }
 
+// lists the namespaces of a local source datastore the owner is allowed to access
+fn list_local_namespaces(
+    source: &Arc<DataStore>,
+    params: &PullParameters,
+) -> Result<Vec<BackupNamespace>, Error> {
+    let user_info = CachedUserInfo::new()?;
+
+    let mut list: Vec<BackupNamespace> = source
+        .recursive_iter_backup_ns_ok(params.source_ns.clone(), params.max_depth)?
+        .filter(|ns| {
+            let privs = user_info.lookup_privs(&params.owner, &ns.acl_path(source.name()));
+            privs & (PRIV_DATASTORE_BACKUP | PRIV_DATASTORE_READ) != 0
+        })
+        .collect();
+
+    // parents first
+    list.sort_unstable_by_key(|ns| ns.name_len());
+
+    Ok(list)
+}
+
 // will modify params if switching to backwards mode for lack of NS support on remote end
 async fn query_namespaces(
     worker: &WorkerTask,
-    client: &HttpClient,
+    client: &PullClient,
     params: &mut PullParameters,
 ) -> Result<Vec<BackupNamespace>, Error> {
-    let path = format!(
-        "api2/json/admin/datastore/{}/namespace",
-        params.source.store()
-    );
+    let (client, repo) = match client {
+        PullClient::Remote { client, repo } => (client, repo),
+        PullClient::Local(source) => return list_local_namespaces(source, params),
+    };
+
+    let path = format!("api2/json/admin/datastore/{}/namespace", repo.store());
     let mut data = json!({});
     if let Some(max_depth) = params.max_depth {
         data["max-depth"] = json!(max_depth);
     }
 
-    if !params.remote_ns.is_root() {
-        data["parent"] = json!(params.remote_ns);
+    if !params.source_ns.is_root() {
+        data["parent"] = json!(params.source_ns);
     }
 
     let mut result = match client.get(&path, Some(data)).await {
@@ -807,7 +1474,7 @@ async fn query_namespaces(
         Err(err) => match err.downcast_ref::<HttpError>() {
             Some(HttpError { code, message }) => match *code {
                 StatusCode::NOT_FOUND => {
-                    if params.remote_ns.is_root() && params.max_depth.is_none() {
+                    if params.source_ns.is_root() && params.max_depth.is_none() {
                         task_log!(worker, "Could not query remote for namespaces (404) -> temporarily switching to backwards-compat mode");
                         task_log!(worker, "Either make backwards-compat mode explicit (max-depth == 0) or upgrade remote system.");
                         params.max_depth = Some(0);
@@ -815,7 +1482,7 @@ async fn query_namespaces(
                         bail!("Remote namespace set/recursive sync requested, but remote does not support namespaces.")
                     }
 
-                    return Ok(vec![params.remote_ns.clone()]);
+                    return Ok(vec![params.source_ns.clone()]);
                 }
                 _ => {
                     bail!("Querying namespaces failed - HTTP error {code} - {message}");
@@ -885,7 +1552,7 @@ fn check_and_remove_vanished_ns(
     // clamp like remote does so that we don't list more than we can ever have synced.
     let max_depth = params
         .max_depth
-        .unwrap_or_else(|| MAX_NAMESPACE_DEPTH - params.remote_ns.depth());
+        .unwrap_or_else(|| MAX_NAMESPACE_DEPTH - params.source_ns.depth());
 
     let mut local_ns_list: Vec<BackupNamespace> = params
         .store
@@ -932,23 +1599,24 @@ fn check_and_remove_vanished_ns(
 /// Pulls a store according to `params`.
 ///
 /// Pulling a store consists of the following steps:
-/// - Query list of namespaces on the remote
+/// - Query list of namespaces on the remote or local source
 /// - Iterate list
 /// -- create sub-NS if needed (and allowed)
 /// -- attempt to pull each NS in turn
-/// - (remove_vanished && max_depth > 0) remove sub-NS which are not or no longer available on the remote
+/// - (remove_vanished && max_depth > 0) remove sub-NS which are not or no longer available on the source
 ///
 /// Backwards compat: if the remote namespace is `/` and recursion is disabled, no namespace is
 /// passed to the remote at all to allow pulling from remotes which have no notion of namespaces.
 ///
 /// Permission checks:
-/// - access to local datastore, namespace anchor and remote entry need to be checked at call site
-/// - remote namespaces are filtered by remote
+/// - access to local datastore, namespace anchor and remote entry (or local source datastore) need
+///   to be checked at call site
+/// - remote namespaces are filtered by remote, local source namespaces by the owner's privileges
 /// - creation and removal of sub-NS checked here
 /// - access to sub-NS checked here
 pub(crate) async fn pull_store(
     worker: &WorkerTask,
-    client: &HttpClient,
+    client: &PullClient,
     mut params: PullParameters,
 ) -> Result<(), Error> {
     // explicit create shared lock to prevent GC on newly created chunks
@@ -956,8 +1624,8 @@ pub(crate) async fn pull_store(
     let mut errors = false;
 
     let old_max_depth = params.max_depth;
-    let namespaces = if params.remote_ns.is_root() && params.max_depth == Some(0) {
-        vec![params.remote_ns.clone()] // backwards compat - don't query remote namespaces!
+    let namespaces = if params.source_ns.is_root() && params.max_depth == Some(0) {
+        vec![params.source_ns.clone()] // backwards compat - don't query remote namespaces!
     } else {
         query_namespaces(worker, client, &mut params).await?
     };
@@ -969,7 +1637,7 @@ pub(crate) async fn pull_store(
     for namespace in namespaces {
         let source_store_ns_str = print_store_and_ns(params.source.store(), &namespace);
 
-        let target_ns = namespace.map_prefix(&params.remote_ns, &params.ns)?;
+        let target_ns = namespace.map_prefix(&params.source_ns, &params.ns)?;
         let target_store_ns_str = print_store_and_ns(params.store.name(), &target_ns);
 
         task_log!(worker, "----");
@@ -1040,7 +1708,7 @@ pub(crate) async fn pull_store(
 /// Pulls a namespace according to `params`.
 ///
 /// Pulling a namespace consists of the following steps:
-/// - Query list of groups on the remote (in `source_ns`)
+/// - Query list of groups on the remote or local source (in `source_ns`)
 /// - Filter list according to configured group filters
 /// - Iterate list and attempt to pull each group in turn
 /// - (remove_vanished) remove groups with matching owner and matching the configured group filters which are
@@ -1048,36 +1716,60 @@ pub(crate) async fn pull_store(
 ///
 /// Permission checks:
 /// - remote namespaces are filtered by remote
+/// - local source groups are filtered by owner unless Datastore.Read is granted
 /// - owner check for vanished groups done here
 pub(crate) async fn pull_ns(
     worker: &WorkerTask,
-    client: &HttpClient,
+    client: &PullClient,
     params: &PullParameters,
     source_ns: BackupNamespace,
     target_ns: BackupNamespace,
 ) -> Result<(StoreProgress, bool), Error> {
-    let path = format!("api2/json/admin/datastore/{}/groups", params.source.store());
-
-    let args = if !source_ns.is_root() {
-        Some(json!({
-            "ns": source_ns,
-        }))
-    } else {
-        None
-    };
+    let mut list: Vec<pbs_api_types::BackupGroup> = match client {
+        PullClient::Remote { client, repo } => {
+            let path = format!("api2/json/admin/datastore/{}/groups", repo.store());
+
+            let args = if !source_ns.is_root() {
+                Some(json!({
+                    "ns": source_ns,
+                }))
+            } else {
+                None
+            };
 
-    let mut result = client
-        .get(&path, args)
-        .await
-        .map_err(|err| format_err!("Failed to retrieve backup groups from remote - {}", err))?;
+            let mut result = client.get(&path, args).await.map_err(|err| {
+                format_err!("Failed to retrieve backup groups from remote - {}", err)
+            })?;
 
-    let mut list: Vec<GroupListItem> = serde_json::from_value(result["data"].take())?;
+            let list: Vec<GroupListItem> = serde_json::from_value(result["data"].take())?;
+            list.into_iter().map(|item| item.backup).collect()
+        }
+        PullClient::Local(source) => {
+            let user_info = CachedUserInfo::new()?;
+            let privs = user_info.lookup_privs(&params.owner, &source_ns.acl_path(source.name()));
+            let owner_check_required = privs & PRIV_DATASTORE_READ == 0;
+
+            source
+                .iter_backup_groups_ok(source_ns.clone())?
+                .filter(|group| {
+                    if !owner_check_required {
+                        return true;
+                    }
+                    match group.get_owner() {
+                        Ok(owner) => check_backup_owner(&owner, &params.owner).is_ok(),
+                        Err(_) => false,
+                    }
+                })
+                .map(|group| group.group().clone())
+                .collect()
+        }
+    };
 
     let total_count = list.len();
     list.sort_unstable_by(|a, b| {
-        let type_order = a.backup.ty.cmp(&b.backup.ty);
+        let type_order = a.ty.cmp(&b.ty);
         if type_order == std::cmp::Ordering::Equal {
-            a.backup.id.cmp(&b.backup.id)
+            a.id.cmp(&b.id)
         } else {
             type_order
         }
@@ -1087,9 +1779,6 @@ pub(crate) async fn pull_ns(
         filters.iter().any(|filter| group.matches(filter))
     };
 
-    // Get groups with target NS set
-    let list: Vec<pbs_api_types::BackupGroup> = list.into_iter().map(|item| item.backup).collect();
-
     let list = if let Some(ref group_filter) = &params.group_filter {
         let unfiltered_count = list.len();
         let list: Vec<pbs_api_types::BackupGroup> = list
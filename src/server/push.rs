@@ -0,0 +1,843 @@
+//! Sync datastore by pushing contents to remote server
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, format_err, Error};
+use futures::stream::{self, StreamExt};
+use serde_json::json;
+
+use proxmox_sys::task_log;
+
+use pbs_api_types::{
+    print_store_and_ns, Authid, BackupNamespace, GroupFilter, GroupListItem, NamespaceListItem,
+    Operation, RateLimitConfig, Remote, SnapshotListItem, MAX_NAMESPACE_DEPTH,
+    PRIV_DATASTORE_BACKUP, PRIV_DATASTORE_READ,
+};
+use pbs_client::{
+    BackupRepository, BackupWriter, HttpClient, HttpClientOptions, MergedChunkInfo, UploadOptions,
+};
+use pbs_config::CachedUserInfo;
+use pbs_datastore::data_blob::ChunkInfo;
+use pbs_datastore::dynamic_index::DynamicIndexReader;
+use pbs_datastore::fixed_index::FixedIndexReader;
+use pbs_datastore::index::IndexFile;
+use pbs_datastore::manifest::{
+    archive_type, ArchiveType, BackupManifest, CLIENT_LOG_BLOB_NAME, MANIFEST_BLOB_NAME,
+};
+use pbs_datastore::{check_backup_owner, DataStore, SnapshotReader, StoreProgress};
+use proxmox_rest_server::WorkerTask;
+
+use super::pull::{SkipInfo, SkipReason};
+
+/// Parameters for a push operation.
+pub(crate) struct PushParameters {
+    /// Remote that is pushed to
+    remote: Remote,
+    /// Full specification of remote datastore
+    target: BackupRepository,
+    /// Local store that is pushed from
+    store: Arc<DataStore>,
+    /// Local namespace (anchor)
+    ns: BackupNamespace,
+    /// Remote namespace (anchor)
+    remote_ns: BackupNamespace,
+    /// Local user whose privileges limit which groups are pushed
+    local_user: Authid,
+    /// Whether to remove groups which exist on the remote, but not locally
+    remove_vanished: bool,
+    /// How many levels of sub-namespaces to push (0 == no recursion, None == maximum recursion)
+    max_depth: Option<usize>,
+    /// Filters for reducing the push scope
+    group_filter: Option<Vec<GroupFilter>>,
+    /// Rate limits for all transfers to `remote`
+    limit: RateLimitConfig,
+    /// How many snapshots should be transferred at most (taking the newest N snapshots)
+    transfer_last: Option<usize>,
+}
+
+impl PushParameters {
+    /// Creates a new instance of `PushParameters`.
+    ///
+    /// `remote` will be dereferenced via [pbs_api_types::RemoteConfig], and combined into a
+    /// [BackupRepository] with `remote_store`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        store: &str,
+        ns: BackupNamespace,
+        remote: &str,
+        remote_store: &str,
+        remote_ns: BackupNamespace,
+        local_user: Authid,
+        remove_vanished: Option<bool>,
+        max_depth: Option<usize>,
+        group_filter: Option<Vec<GroupFilter>>,
+        limit: RateLimitConfig,
+        transfer_last: Option<usize>,
+    ) -> Result<Self, Error> {
+        let store = DataStore::lookup_datastore(store, Some(Operation::Read))?;
+
+        if let Some(max_depth) = max_depth {
+            ns.check_max_depth(max_depth)?;
+            remote_ns.check_max_depth(max_depth)?;
+        }
+
+        let (remote_config, _digest) = pbs_config::remote::config()?;
+        let remote: Remote = remote_config.lookup("remote", remote)?;
+
+        let remove_vanished = remove_vanished.unwrap_or(false);
+
+        let target = BackupRepository::new(
+            Some(remote.config.auth_id.clone()),
+            Some(remote.config.host.clone()),
+            remote.config.port,
+            remote_store.to_string(),
+        );
+
+        Ok(Self {
+            remote,
+            target,
+            store,
+            ns,
+            remote_ns,
+            local_user,
+            remove_vanished,
+            max_depth,
+            group_filter,
+            limit,
+            transfer_last,
+        })
+    }
+
+    /// Creates a new [HttpClient] for accessing the [Remote] that is pushed to.
+    pub async fn client(&self) -> Result<HttpClient, Error> {
+        crate::api2::config::remote::remote_client(&self.remote, Some(self.limit.clone())).await
+    }
+
+    fn apply_filters(&self, group: &pbs_api_types::BackupGroup) -> bool {
+        match &self.group_filter {
+            Some(filters) => filters.iter().any(|filter| group.matches(filter)),
+            None => true,
+        }
+    }
+}
+
+/// Queries the namespaces below the remote namespace anchor.
+async fn query_remote_namespaces(
+    client: &HttpClient,
+    params: &PushParameters,
+) -> Result<Vec<BackupNamespace>, Error> {
+    let path = format!(
+        "api2/json/admin/datastore/{}/namespace",
+        params.target.store()
+    );
+    let mut data = json!({});
+    if !params.remote_ns.is_root() {
+        data["parent"] = json!(params.remote_ns);
+    }
+
+    let mut result = client
+        .get(&path, Some(data))
+        .await
+        .map_err(|err| format_err!("Querying remote namespaces failed - {err}"))?;
+    let list: Vec<NamespaceListItem> = serde_json::from_value(result["data"].take())?;
+
+    Ok(list.into_iter().map(|item| item.ns).collect())
+}
+
+/// Creates `target_ns` on the remote, including all missing parents.
+async fn check_and_create_remote_ns(
+    client: &HttpClient,
+    params: &PushParameters,
+    existing: &mut HashSet<BackupNamespace>,
+    target_ns: &BackupNamespace,
+) -> Result<bool, Error> {
+    if target_ns.is_root() || existing.contains(target_ns) {
+        return Ok(false);
+    }
+
+    let parent = target_ns.parent();
+    if !parent.is_root() && !existing.contains(&parent) {
+        bail!("parent namespace {parent} does not exist on remote");
+    }
+
+    let name = match target_ns.components().last() {
+        Some(name) => name.to_owned(),
+        None => bail!("Failed to determine last component of namespace."),
+    };
+
+    let path = format!(
+        "api2/json/admin/datastore/{}/namespace",
+        params.target.store()
+    );
+    let mut args = json!({ "name": name });
+    if !parent.is_root() {
+        args["parent"] = json!(parent);
+    }
+
+    client
+        .post(&path, Some(args))
+        .await
+        .map_err(|err| format_err!("creating remote namespace {target_ns} failed - {err}"))?;
+
+    existing.insert(target_ns.clone());
+
+    Ok(true)
+}
+
+async fn check_and_remove_vanished_remote_ns(
+    worker: &WorkerTask,
+    client: &HttpClient,
+    params: &PushParameters,
+    remote_ns_list: Vec<BackupNamespace>,
+    synced_ns: &HashSet<BackupNamespace>,
+) -> bool {
+    let mut errors = false;
+
+    // clamp like the local side does so that we don't remove more than we can ever have pushed
+    let max_depth = params
+        .max_depth
+        .unwrap_or_else(|| MAX_NAMESPACE_DEPTH - params.remote_ns.depth());
+
+    let mut vanished: Vec<BackupNamespace> = remote_ns_list
+        .into_iter()
+        .filter(|ns| {
+            !ns.is_root()
+                && ns != &params.remote_ns
+                && !synced_ns.contains(ns)
+                && ns.depth() - params.remote_ns.depth() <= max_depth
+        })
+        .collect();
+
+    // children first!
+    vanished.sort_unstable_by_key(|ns| std::cmp::Reverse(ns.name_len()));
+
+    let path = format!(
+        "api2/json/admin/datastore/{}/namespace",
+        params.target.store()
+    );
+
+    for ns in vanished {
+        let args = json!({ "ns": ns, "delete-groups": true });
+        match client.delete(&path, Some(args)).await {
+            Ok(_) => task_log!(worker, "Removed remote namespace {}", ns),
+            Err(err) => {
+                task_log!(worker, "Failed to remove remote namespace {} - {}", ns, err);
+                errors = true;
+            }
+        }
+    }
+
+    errors
+}
+
+/// Pushes a store according to `params`.
+///
+/// Pushing a store consists of the following steps:
+/// - Query list of namespaces on the remote
+/// - Iterate the list of local namespaces below the local anchor
+/// -- create sub-NS on the remote if needed
+/// -- attempt to push each NS in turn
+/// - (remove_vanished) remove sub-NS on the remote which are not or no longer available locally
+///
+/// Permission checks:
+/// - access to local datastore, namespace anchor and remote entry need to be checked at call site
+/// - access to local sub-NS and groups checked here
+/// - remote side checks creation of NS, groups and snapshots
+pub(crate) async fn push_store(
+    worker: &WorkerTask,
+    client: &HttpClient,
+    params: PushParameters,
+) -> Result<(), Error> {
+    let mut errors = false;
+
+    let user_info = CachedUserInfo::new()?;
+
+    let remote_ns_list = if params.remote_ns.is_root() && params.max_depth == Some(0) {
+        Vec::new() // no need to query the remote, the root namespace always exists
+    } else {
+        query_remote_namespaces(client, &params).await?
+    };
+    let mut existing_remote_ns: HashSet<BackupNamespace> = remote_ns_list.iter().cloned().collect();
+
+    if !params.remote_ns.is_root() && !existing_remote_ns.contains(&params.remote_ns) {
+        bail!(
+            "remote namespace anchor {} does not exist on remote",
+            params.remote_ns
+        );
+    }
+
+    // clamp so that we don't push more levels than the remote side can hold
+    let max_depth = params
+        .max_depth
+        .unwrap_or_else(|| MAX_NAMESPACE_DEPTH - params.ns.depth().max(params.remote_ns.depth()));
+    let mut namespaces: Vec<BackupNamespace> = params
+        .store
+        .recursive_iter_backup_ns_ok(params.ns.clone(), Some(max_depth))?
+        .filter(|ns| {
+            let privs =
+                user_info.lookup_privs(&params.local_user, &ns.acl_path(params.store.name()));
+            privs & (PRIV_DATASTORE_BACKUP | PRIV_DATASTORE_READ) != 0
+        })
+        .collect();
+
+    // parents first
+    namespaces.sort_unstable_by_key(|ns| ns.name_len());
+
+    let (mut groups, mut snapshots) = (0, 0);
+    let mut synced_ns = HashSet::with_capacity(namespaces.len());
+
+    for namespace in namespaces {
+        let source_store_ns_str = print_store_and_ns(params.store.name(), &namespace);
+
+        let target_ns = namespace.map_prefix(&params.ns, &params.remote_ns)?;
+        let target_store_ns_str = print_store_and_ns(params.target.store(), &target_ns);
+
+        task_log!(worker, "----");
+        task_log!(
+            worker,
+            "Pushing {} into {}",
+            source_store_ns_str,
+            target_store_ns_str
+        );
+
+        synced_ns.insert(target_ns.clone());
+
+        match check_and_create_remote_ns(client, &params, &mut existing_remote_ns, &target_ns).await
+        {
+            Ok(true) => task_log!(worker, "Created remote namespace {}", target_ns),
+            Ok(false) => {}
+            Err(err) => {
+                task_log!(
+                    worker,
+                    "Cannot push {} into {} - {}",
+                    source_store_ns_str,
+                    target_store_ns_str,
+                    err,
+                );
+                errors = true;
+                continue;
+            }
+        }
+
+        match push_ns(worker, client, &params, &namespace, &target_ns).await {
+            Ok((ns_progress, ns_errors)) => {
+                errors |= ns_errors;
+
+                if params.max_depth != Some(0) {
+                    groups += ns_progress.done_groups;
+                    snapshots += ns_progress.done_snapshots;
+                    task_log!(
+                        worker,
+                        "Finished pushing namespace {}, current progress: {} groups, {} snapshots",
+                        namespace,
+                        groups,
+                        snapshots,
+                    );
+                }
+            }
+            Err(err) => {
+                errors = true;
+                task_log!(
+                    worker,
+                    "Encountered errors while pushing namespace {} - {}",
+                    namespace,
+                    err,
+                );
+            }
+        }
+    }
+
+    if params.remove_vanished {
+        errors |= check_and_remove_vanished_remote_ns(
+            worker,
+            client,
+            &params,
+            remote_ns_list,
+            &synced_ns,
+        )
+        .await;
+    }
+
+    if errors {
+        bail!("sync failed with some errors.");
+    }
+
+    Ok(())
+}
+
+/// Pushes a namespace according to `params`.
+///
+/// Pushing a namespace consists of the following steps:
+/// - List the local groups in `source_ns` the local user has access to
+/// - Filter list according to configured group filters
+/// - Iterate list and attempt to push each group in turn
+/// - (remove_vanished) remove groups on the remote which are owned by the remote's auth-id,
+///   match the configured group filters and are not or no longer available locally
+pub(crate) async fn push_ns(
+    worker: &WorkerTask,
+    client: &HttpClient,
+    params: &PushParameters,
+    source_ns: &BackupNamespace,
+    target_ns: &BackupNamespace,
+) -> Result<(StoreProgress, bool), Error> {
+    let user_info = CachedUserInfo::new()?;
+    let privs =
+        user_info.lookup_privs(&params.local_user, &source_ns.acl_path(params.store.name()));
+    let owner_check_required = privs & PRIV_DATASTORE_READ == 0;
+
+    let mut list: Vec<pbs_datastore::BackupGroup> = params
+        .store
+        .iter_backup_groups_ok(source_ns.clone())?
+        .filter(|group| {
+            if !owner_check_required {
+                return true;
+            }
+            match group.get_owner() {
+                Ok(owner) => check_backup_owner(&owner, &params.local_user).is_ok(),
+                Err(_) => false,
+            }
+        })
+        .collect();
+
+    list.sort_unstable_by(|a, b| a.group().cmp(b.group()));
+
+    let unfiltered_count = list.len();
+    let list: Vec<pbs_datastore::BackupGroup> = list
+        .into_iter()
+        .filter(|group| params.apply_filters(group.group()))
+        .collect();
+    task_log!(
+        worker,
+        "found {} groups to push (out of {} total)",
+        list.len(),
+        unfiltered_count
+    );
+
+    let groups_path = format!("api2/json/admin/datastore/{}/groups", params.target.store());
+    let args = if !target_ns.is_root() {
+        Some(json!({ "ns": target_ns }))
+    } else {
+        None
+    };
+    let mut result = client
+        .get(&groups_path, args)
+        .await
+        .map_err(|err| format_err!("Failed to retrieve backup groups from remote - {}", err))?;
+    let remote_groups: Vec<GroupListItem> = serde_json::from_value(result["data"].take())?;
+    let existing_remote_groups: HashSet<pbs_api_types::BackupGroup> = remote_groups
+        .iter()
+        .map(|item| item.backup.clone())
+        .collect();
+
+    let mut errors = false;
+    let mut local_groups = HashSet::with_capacity(list.len());
+    let mut progress = StoreProgress::new(list.len() as u64);
+
+    for (done, group) in list.into_iter().enumerate() {
+        progress.done_groups = done as u64;
+        progress.done_snapshots = 0;
+        progress.group_snapshots = 0;
+
+        local_groups.insert(group.group().clone());
+
+        let remote_exists = existing_remote_groups.contains(group.group());
+        if let Err(err) = push_group(
+            worker,
+            client,
+            params,
+            &group,
+            target_ns,
+            remote_exists,
+            &mut progress,
+        )
+        .await
+        {
+            task_log!(worker, "push group {} failed - {}", group.group(), err);
+            errors = true; // do not stop here, instead continue
+        }
+    }
+
+    if params.remove_vanished {
+        for remote_group in remote_groups {
+            if local_groups.contains(&remote_group.backup) {
+                continue;
+            }
+            // only remove groups created by this remote's auth-id
+            if remote_group.owner.as_ref() != Some(&params.remote.config.auth_id) {
+                continue;
+            }
+            if !params.apply_filters(&remote_group.backup) {
+                continue;
+            }
+
+            task_log!(
+                worker,
+                "delete vanished remote group '{}'",
+                remote_group.backup
+            );
+            let mut args = json!({
+                "backup-type": remote_group.backup.ty,
+                "backup-id": remote_group.backup.id,
+            });
+            if !target_ns.is_root() {
+                args["ns"] = json!(target_ns);
+            }
+            if let Err(err) = client.delete(&groups_path, Some(args)).await {
+                task_log!(worker, "error during cleanup: {}", err);
+                errors = true;
+            }
+        }
+    }
+
+    Ok((progress, errors))
+}
+
+/// Pushes a group according to `params`.
+///
+/// Pushing a group consists of the following steps:
+/// - Query the list of snapshots available for this group in the target namespace on the remote
+/// - Sort the local snapshots by time
+/// - Iterate over list of local snapshots
+/// -- push snapshot, unless it's not finished yet or older than last remote snapshot
+/// - (remove_vanished) remove snapshots on the remote which do not exist locally (anymore)
+pub(crate) async fn push_group(
+    worker: &WorkerTask,
+    client: &HttpClient,
+    params: &PushParameters,
+    group: &pbs_datastore::BackupGroup,
+    target_ns: &BackupNamespace,
+    remote_exists: bool,
+    progress: &mut StoreProgress,
+) -> Result<(), Error> {
+    task_log!(worker, "push group {}", group.group());
+
+    let path = format!(
+        "api2/json/admin/datastore/{}/snapshots",
+        params.target.store()
+    );
+
+    let remote_snapshots: Vec<SnapshotListItem> = if remote_exists {
+        let mut args = json!({
+            "backup-type": group.backup_type(),
+            "backup-id": group.backup_id(),
+        });
+        if !target_ns.is_root() {
+            args["ns"] = json!(target_ns);
+        }
+        let mut result = client.get(&path, Some(args)).await?;
+        serde_json::from_value(result["data"].take())?
+    } else {
+        Vec::new()
+    };
+
+    let last_remote_time = remote_snapshots
+        .iter()
+        .filter(|item| item.size.is_some())
+        .map(|item| item.backup.time)
+        .max()
+        .unwrap_or(i64::MIN);
+
+    let mut list = group.list_backups()?;
+    pbs_datastore::BackupInfo::sort_list(&mut list, true);
+    let list: Vec<_> = list.into_iter().filter(|info| info.is_finished()).collect();
+
+    let local_snapshots: HashSet<i64> = list
+        .iter()
+        .map(|info| info.backup_dir.backup_time())
+        .collect();
+
+    progress.group_snapshots = list.len() as u64;
+
+    let mut already_synced_skip_info = SkipInfo::new(SkipReason::AlreadySynced);
+    let mut transfer_last_skip_info = SkipInfo::new(SkipReason::TransferLast);
+
+    let cutoff = params
+        .transfer_last
+        .map(|count| list.len().saturating_sub(count))
+        .unwrap_or_default();
+
+    let fingerprint = client.fingerprint();
+
+    for (pos, info) in list.into_iter().enumerate() {
+        let snapshot = info.backup_dir;
+        let backup_time = snapshot.backup_time();
+
+        if last_remote_time > backup_time {
+            already_synced_skip_info.update(backup_time);
+            continue;
+        } else if already_synced_skip_info.count > 0 {
+            task_log!(worker, "{}", already_synced_skip_info);
+            already_synced_skip_info.reset();
+        }
+
+        if pos < cutoff && last_remote_time != backup_time {
+            transfer_last_skip_info.update(backup_time);
+            continue;
+        } else if transfer_last_skip_info.count > 0 {
+            task_log!(worker, "{}", transfer_last_skip_info);
+            transfer_last_skip_info.reset();
+        }
+
+        if last_remote_time == backup_time {
+            // the newest remote snapshot is immutable once finished, nothing to do
+            continue;
+        }
+
+        // get updated auth_info (new tickets)
+        let auth_info = client.login().await?;
+
+        let options =
+            HttpClientOptions::new_non_interactive(auth_info.ticket.clone(), fingerprint.clone())
+                .rate_limit(params.limit.clone());
+
+        let new_client = HttpClient::new(
+            params.target.host(),
+            params.target.port(),
+            params.target.auth_id(),
+            options,
+        )?;
+
+        let result = push_snapshot(worker, new_client, client, params, &snapshot, target_ns).await;
+
+        progress.done_snapshots = pos as u64 + 1;
+        task_log!(worker, "percentage done: {}", progress);
+
+        result?; // stop on error
+    }
+
+    if params.remove_vanished {
+        for item in remote_snapshots {
+            if local_snapshots.contains(&item.backup.time) {
+                continue;
+            }
+            if item.protected {
+                task_log!(
+                    worker,
+                    "don't delete vanished remote snapshot {} (protected)",
+                    item.backup
+                );
+                continue;
+            }
+            task_log!(worker, "delete vanished remote snapshot {}", item.backup);
+            let mut args = json!({
+                "backup-type": item.backup.group.ty,
+                "backup-id": item.backup.group.id,
+                "backup-time": item.backup.time,
+            });
+            if !target_ns.is_root() {
+                args["ns"] = json!(target_ns);
+            }
+            client.delete(&path, Some(args)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Pushes a single snapshot to the remote.
+///
+/// Pushing a snapshot consists of the following steps:
+/// - Lock the local snapshot and open a [BackupWriter] for it on the remote
+/// - Download the previous manifest and indexes from the remote to learn the known chunks
+/// - Upload each referenced file, sending only chunks not known to the remote
+/// - Upload the local manifest and finish the backup
+/// - Upload the client log, if present
+///
+/// As the snapshot is written by the remote's backup API, the remote enforces its quotas for the
+/// target namespace and the remote user.
+async fn push_snapshot(
+    worker: &WorkerTask,
+    writer_client: HttpClient,
+    client: &HttpClient,
+    params: &PushParameters,
+    snapshot: &pbs_datastore::BackupDir,
+    target_ns: &BackupNamespace,
+) -> Result<(), Error> {
+    task_log!(worker, "push snapshot {}", snapshot.dir());
+
+    let reader = SnapshotReader::new(
+        params.store.clone(),
+        snapshot.backup_ns().clone(),
+        snapshot.dir().clone(),
+    )?;
+    let (manifest, _) = snapshot.load_manifest()?;
+
+    let writer = BackupWriter::start(
+        writer_client,
+        None,
+        params.target.store(),
+        target_ns,
+        snapshot.dir(),
+        false,
+        false,
+    )
+    .await?;
+
+    let result = push_snapshot_files(worker, &writer, &reader, &manifest).await;
+    if let Err(err) = result {
+        writer.cancel();
+        return Err(err);
+    }
+
+    writer.finish().await?;
+
+    if reader
+        .file_list()
+        .iter()
+        .any(|name| name == CLIENT_LOG_BLOB_NAME)
+    {
+        let mut raw_data = Vec::new();
+        std::io::Read::read_to_end(&mut reader.open_file(CLIENT_LOG_BLOB_NAME)?, &mut raw_data)?;
+
+        let path = format!(
+            "api2/json/admin/datastore/{}/upload-backup-log",
+            params.target.store()
+        );
+        let mut args = serde_json::to_value(snapshot.dir())?;
+        if !target_ns.is_root() {
+            args["ns"] = json!(target_ns);
+        }
+        client
+            .upload(
+                "application/octet-stream",
+                hyper::Body::from(raw_data),
+                &path,
+                Some(args),
+            )
+            .await?;
+    }
+
+    task_log!(worker, "push snapshot {} done", snapshot.dir());
+
+    Ok(())
+}
+
+async fn push_snapshot_files(
+    worker: &WorkerTask,
+    writer: &Arc<BackupWriter>,
+    reader: &SnapshotReader,
+    manifest: &BackupManifest,
+) -> Result<(), Error> {
+    // an error just means there is no previous snapshot on the remote
+    let previous_manifest = writer.download_previous_manifest().await.ok();
+
+    // chunks of the previous remote snapshot's indexes, registered with the writer on download
+    let known_chunks = Arc::new(Mutex::new(HashSet::new()));
+
+    for item in manifest.files() {
+        let archive_name = &item.filename;
+        task_log!(worker, "push archive {}", archive_name);
+
+        match archive_type(archive_name)? {
+            ArchiveType::Blob => {
+                let file = reader.open_file(archive_name)?;
+                let stats = writer.upload_blob(file, archive_name).await?;
+                manifest.verify_file(archive_name, &stats.csum, stats.size)?;
+            }
+            ArchiveType::DynamicIndex => {
+                if let Some(previous) = &previous_manifest {
+                    if previous.lookup_file_info(archive_name).is_ok() {
+                        let _ = writer
+                            .download_previous_dynamic_index(
+                                archive_name,
+                                previous,
+                                known_chunks.clone(),
+                            )
+                            .await;
+                    }
+                }
+                let index = DynamicIndexReader::new(reader.open_file(archive_name)?)?;
+                let (csum, size) = index.compute_csum();
+                manifest.verify_file(archive_name, &csum, size)?;
+
+                let stream = index_chunk_stream(
+                    reader.snapshot().datastore().clone(),
+                    Box::new(index),
+                    known_chunks.clone(),
+                );
+                writer
+                    .upload_index_chunk_info(
+                        archive_name,
+                        stream,
+                        csum,
+                        size,
+                        UploadOptions::default(),
+                    )
+                    .await?;
+            }
+            ArchiveType::FixedIndex => {
+                if let Some(previous) = &previous_manifest {
+                    if previous.lookup_file_info(archive_name).is_ok() {
+                        let _ = writer
+                            .download_previous_fixed_index(
+                                archive_name,
+                                previous,
+                                known_chunks.clone(),
+                            )
+                            .await;
+                    }
+                }
+                let index = FixedIndexReader::new(reader.open_file(archive_name)?)?;
+                let (csum, size) = index.compute_csum();
+                manifest.verify_file(archive_name, &csum, size)?;
+
+                let options = UploadOptions {
+                    fixed_size: Some(index.index_bytes()),
+                    ..UploadOptions::default()
+                };
+                let stream = index_chunk_stream(
+                    reader.snapshot().datastore().clone(),
+                    Box::new(index),
+                    known_chunks.clone(),
+                );
+                writer
+                    .upload_index_chunk_info(archive_name, stream, csum, size, options)
+                    .await?;
+            }
+        }
+    }
+
+    // upload the manifest as is, it references the unchanged files and keeps signatures intact
+    let manifest_file = reader.open_file(MANIFEST_BLOB_NAME)?;
+    writer
+        .upload_blob(manifest_file, MANIFEST_BLOB_NAME)
+        .await?;
+
+    Ok(())
+}
+
+/// Stream the chunks of `index`, loading those unknown to the remote from `store`.
+///
+/// The remote only accepts references to chunks registered with the current backup writer, so
+/// chunks are either known from the previous snapshot's indexes or uploaded once.
+fn index_chunk_stream(
+    store: Arc<DataStore>,
+    index: Box<dyn IndexFile + Send>,
+    known_chunks: Arc<Mutex<HashSet<[u8; 32]>>>,
+) -> impl futures::Stream<Item = Result<MergedChunkInfo, Error>> {
+    stream::iter(0..index.index_count()).then(move |pos| {
+        let store = store.clone();
+        let chunk_info = index.chunk_info(pos);
+        let known_chunks = known_chunks.clone();
+        async move {
+            let info = chunk_info.ok_or_else(|| format_err!("index entry {pos} out of range"))?;
+            let offset = info.range.start;
+
+            if known_chunks.lock().unwrap().contains(&info.digest) {
+                return Ok(MergedChunkInfo::Known(vec![(offset, info.digest)]));
+            }
+
+            let chunk = proxmox_async::runtime::block_in_place(|| store.load_chunk(&info.digest))?;
+
+            known_chunks.lock().unwrap().insert(info.digest);
+
+            Ok(MergedChunkInfo::New(ChunkInfo {
+                chunk,
+                digest: info.digest,
+                chunk_len: info.size(),
+                offset,
+            }))
+        }
+    })
+}
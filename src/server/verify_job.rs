@@ -1,6 +1,15 @@
-use anyhow::{format_err, Error};
+use std::path::Path;
+use std::sync::Arc;
 
-use pbs_api_types::{Authid, Operation, VerificationJobConfig};
+use anyhow::{bail, format_err, Error};
+
+use pbs_api_types::{
+    Authid, DataStoreConfig, Operation, SnapshotVerifyState, VerificationJobConfig, VerifyState,
+};
+use pbs_datastore::manifest::BackupManifest;
+use pbs_datastore::verify_state::{
+    chunk_verify_state_path, ChunkVerifyState, CHUNK_VERIFY_BUCKETS,
+};
 use pbs_datastore::DataStore;
 use proxmox_rest_server::WorkerTask;
 use proxmox_sys::task_log;
@@ -10,6 +19,35 @@ use crate::{
     server::jobstate::Job,
 };
 
+fn last_verify_failed(manifest: &BackupManifest) -> bool {
+    let raw_verify_state = manifest.unprotected["verify_state"].clone();
+    matches!(
+        serde_json::from_value::<SnapshotVerifyState>(raw_verify_state),
+        Ok(SnapshotVerifyState {
+            state: VerifyState::Failed,
+            ..
+        })
+    )
+}
+
+/// Removes the chunk verify state of verification job `job_id` on datastore `store`.
+///
+/// Used when the job is removed, or when it covers different chunks after an update.
+pub fn remove_chunk_verify_state(store: &str, job_id: &str) -> Result<(), Error> {
+    let (config, _digest) = pbs_config::datastore::config()?;
+    let store_config: DataStoreConfig = match config.lookup("datastore", store) {
+        Ok(store_config) => store_config,
+        Err(_) => return Ok(()), // the datastore is gone
+    };
+
+    let path = chunk_verify_state_path(Path::new(&store_config.path), job_id);
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => bail!("could not remove chunk verify state {path:?} - {err}"),
+    }
+}
+
 /// Runs a verification job.
 pub fn do_verification_job(
     mut job: Job,
@@ -46,17 +84,75 @@ pub fn do_verification_job(
                 None => Default::default(),
             };
 
-            let verify_worker = crate::backup::VerifyWorker::new(worker.clone(), datastore);
+            let mut verify_worker =
+                crate::backup::VerifyWorker::new(worker.clone(), datastore.clone());
+
+            let repair =
+                verification_job.repair_remote.is_some() || verification_job.repair_drive.is_some();
+            if repair {
+                verify_worker = crate::server::chunk_repair::add_chunk_repair_sources(
+                    verify_worker,
+                    &worker,
+                    &datastore,
+                    &verification_job,
+                    &email,
+                );
+            }
+
+            let now = proxmox_time::epoch_i64();
+            let mut rolling = None;
+            if let Some(period) = verification_job.sample_period {
+                let state = ChunkVerifyState::load(&datastore.base_path(), job.jobname())
+                    .unwrap_or_else(|err| {
+                        task_log!(
+                            worker,
+                            "could not load chunk verify state, starting over - {err}"
+                        );
+                        ChunkVerifyState::default()
+                    });
+                let selection = Arc::new(state.select(now, period as i64 * 86400));
+                task_log!(
+                    worker,
+                    "rolling verification over {period} days - checking {} of {} chunk buckets",
+                    selection.count(),
+                    CHUNK_VERIFY_BUCKETS,
+                );
+                verify_worker = verify_worker.with_chunk_selection(Arc::clone(&selection), period);
+                rolling = Some((state, selection, period));
+            }
+
+            // rolling verification needs to visit all snapshots to reach the selected chunks
+            // with repair sources, snapshots which failed before get another chance
+            let full_filter = move |manifest: &BackupManifest| {
+                (repair && last_verify_failed(manifest))
+                    || verify_filter(ignore_verified_snapshots, outdated_after, manifest)
+            };
+            let filter: Option<&dyn Fn(&BackupManifest) -> bool> = match rolling {
+                Some(_) => None,
+                None => Some(&full_filter),
+            };
+
             let result = verify_all_backups(
                 &verify_worker,
                 worker.upid(),
                 ns,
                 verification_job.max_depth,
                 None,
-                Some(&move |manifest| {
-                    verify_filter(ignore_verified_snapshots, outdated_after, manifest)
-                }),
+                filter,
             );
+
+            if let (Ok(_), Some((mut state, selection, period))) = (&result, rolling) {
+                // corrupt chunks were found and renamed, so the buckets still count as verified
+                state.mark_verified(&selection, now);
+                match state.save(&datastore.base_path(), job.jobname()) {
+                    Ok(()) => task_log!(
+                        worker,
+                        "chunk buckets verified within the last {period} days: {:.2}%",
+                        state.coverage(now, period as i64 * 86400),
+                    ),
+                    Err(err) => task_log!(worker, "could not save chunk verify state - {err}"),
+                }
+            }
             let job_result = match result {
                 Ok(ref failed_dirs) if failed_dirs.is_empty() => Ok(()),
                 Ok(ref failed_dirs) => {
@@ -78,12 +174,13 @@ pub fn do_verification_job(
                 eprintln!("could not finish job state for {}: {}", job.jobtype(), err);
             }
 
-            if let Some(email) = email {
-                if let Err(err) =
-                    crate::server::send_verify_status(&email, notify, verification_job, &result)
-                {
-                    eprintln!("send verify notification failed: {}", err);
-                }
+            if let Err(err) = crate::server::send_verify_status(
+                email.as_deref(),
+                notify,
+                verification_job,
+                &result,
+            ) {
+                eprintln!("send verify notification failed: {}", err);
             }
 
             job_result
@@ -24,7 +24,8 @@ use pbs_api_types::{
 use pbs_key_config::KeyConfig;
 use pbs_tape::{
     linux_list_drives::open_lto_tape_device,
-    sg_tape::{SgTape, TapeAlertFlags},
+    ltfs::LtfsMedia,
+    sg_tape::{SgTape, SgTapeWriter, TapeAlertFlags},
     BlockReadError, MediaContentHeader, TapeRead, TapeWrite,
 };
 use proxmox_sys::command::run_command;
@@ -284,6 +285,10 @@ impl TapeDriver for LtoTapeHandle {
         self.sg_tape.tape_alert_flags()
     }
 
+    fn ltfs_media<'a>(&'a mut self) -> Result<Box<dyn LtfsMedia + 'a>, Error> {
+        Ok(Box::new(SgTapeWriter::new(&mut self.sg_tape)))
+    }
+
     /// Set or clear encryption key
     ///
     /// Note: Only 'root' can read secret encryption keys, so we need
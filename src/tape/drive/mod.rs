@@ -19,13 +19,16 @@ use proxmox_sys::fs::{
 
 use proxmox_io::ReadExt;
 use proxmox_section_config::SectionConfigData;
-use proxmox_sys::{task_log, WorkerTaskContext};
+use proxmox_sys::{task_log, task_warn, WorkerTaskContext};
 use proxmox_uuid::Uuid;
 
 use pbs_api_types::{Fingerprint, LtoTapeDrive, VirtualTapeDrive};
 use pbs_key_config::KeyConfig;
 
-use pbs_tape::{sg_tape::TapeAlertFlags, BlockReadError, MediaContentHeader, TapeRead, TapeWrite};
+use pbs_tape::{
+    ltfs::LtfsMedia, sg_tape::TapeAlertFlags, BlockReadError, MediaContentHeader, TapeRead,
+    TapeWrite,
+};
 
 use crate::{
     server::send_load_media_email,
@@ -212,6 +215,14 @@ pub trait TapeDriver {
         }
         Ok(())
     }
+
+    /// Access the media for writing an LTFS volume
+    ///
+    /// This is used to export data in a format readable by other
+    /// software (see [`pbs_tape::ltfs`]).
+    fn ltfs_media<'a>(&'a mut self) -> Result<Box<dyn LtfsMedia + 'a>, Error> {
+        bail!("drive does not support LTFS");
+    }
 }
 
 /// A boxed implementor of [`MediaChange`].
@@ -399,14 +410,18 @@ pub fn request_and_load_media(
                                     device_type,
                                     device
                                 );
-                                if let Some(to) = notify_email {
-                                    send_load_media_email(
-                                        changer.is_some(),
-                                        device,
-                                        &label_text,
-                                        to,
-                                        Some(new.to_string()),
-                                    )?;
+                                // a failing notification must not abort the tape job
+                                if let Err(err) = send_load_media_email(
+                                    changer.is_some(),
+                                    device,
+                                    &label_text,
+                                    notify_email.as_deref(),
+                                    Some(new.to_string()),
+                                ) {
+                                    task_warn!(
+                                        worker,
+                                        "unable to send load media notification - {err}"
+                                    );
                                 }
                                 *old = new;
                             }
@@ -1,7 +1,7 @@
 // Note: This is only for test an debug
 
 use std::fs::File;
-use std::io;
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
 use anyhow::{bail, format_err, Error};
 use serde::{Deserialize, Serialize};
@@ -10,8 +10,10 @@ use proxmox_sys::fs::{replace_file, CreateOptions};
 
 use pbs_key_config::KeyConfig;
 use pbs_tape::{
-    BlockReadError, BlockedReader, BlockedWriter, DriveStatus, ElementStatus, EmulateTapeReader,
-    EmulateTapeWriter, MediaContentHeader, MtxStatus, StorageElementStatus, TapeRead, TapeWrite,
+    ltfs::{LtfsMedia, LTFS_DATA_PARTITION, LTFS_INDEX_PARTITION},
+    BlockReadError, BlockWrite, BlockedReader, BlockedWriter, DriveStatus, ElementStatus,
+    EmulateTapeReader, EmulateTapeWriter, MediaContentHeader, MtxStatus, StorageElementStatus,
+    TapeRead, TapeWrite,
 };
 
 use crate::tape::{
@@ -88,6 +90,18 @@ impl VirtualTapeHandle {
         path
     }
 
+    fn ltfs_partition_path(&self, tape_name: &str, partition: u8) -> std::path::PathBuf {
+        let mut path = self.path.clone();
+        path.push(format!("ltfs-{}-{}.tap", tape_name, partition));
+        path
+    }
+
+    fn remove_ltfs_partitions(&self, tape_name: &str) {
+        for partition in [LTFS_INDEX_PARTITION, LTFS_DATA_PARTITION] {
+            let _ = std::fs::remove_file(self.ltfs_partition_path(tape_name, partition));
+        }
+    }
+
     fn load_tape_index(&self, tape_name: &str) -> Result<TapeIndex, Error> {
         let path = self.tape_index_path(tape_name);
         let raw = proxmox_sys::fs::file_get_contents(path)?;
@@ -401,6 +415,7 @@ impl TapeDriver for VirtualTapeHandle {
                 ref mut pos,
             }) => {
                 *pos = self.truncate_tape(name, 0)?;
+                self.remove_ltfs_partitions(name);
                 self.store_status(&status)?;
                 Ok(())
             }
@@ -461,6 +476,136 @@ impl TapeDriver for VirtualTapeHandle {
         let status = VirtualDriveStatus { current_tape: None };
         self.store_status(&status)
     }
+
+    fn ltfs_media<'a>(&'a mut self) -> Result<Box<dyn LtfsMedia + 'a>, Error> {
+        let status = self.load_status()?;
+        match status.current_tape {
+            Some(VirtualTapeStatus { name, .. }) => Ok(Box::new(VirtualLtfsMedia {
+                handle: self,
+                tape_name: name,
+                file: None,
+                truncate: false,
+            })),
+            None => bail!("drive is empty (no tape loaded)."),
+        }
+    }
+}
+
+/// LTFS media emulation
+///
+/// Each partition is stored as SIMH tape image (`ltfs-<label>-<partition>.tap`),
+/// so that it can be inspected with other tools. Records are stored as
+/// 32bit little endian length, data (padded to even size) and length
+/// again. Filemarks are stored as zero length.
+struct VirtualLtfsMedia<'a> {
+    handle: &'a mut VirtualTapeHandle,
+    tape_name: String,
+    // partition image, positioned at the current logical object
+    file: Option<File>,
+    // truncate the partition on first write after locate
+    truncate: bool,
+}
+
+impl VirtualLtfsMedia<'_> {
+    // Returns true if we reached the max. size of the virtual tape
+    fn write_record(&mut self, data: &[u8]) -> Result<bool, io::Error> {
+        let file = match self.file {
+            Some(ref mut file) => file,
+            None => proxmox_lang::io_bail!("media is not positioned - locate first"),
+        };
+
+        if self.truncate {
+            let pos = file.stream_position()?;
+            file.set_len(pos)?;
+            self.truncate = false;
+        }
+
+        let len = (data.len() as u32).to_le_bytes();
+        file.write_all(&len)?;
+        if !data.is_empty() {
+            file.write_all(data)?;
+            if data.len() % 2 != 0 {
+                file.write_all(&[0])?;
+            }
+            file.write_all(&len)?;
+        }
+
+        let mut used_space = 0;
+        for partition in [LTFS_INDEX_PARTITION, LTFS_DATA_PARTITION] {
+            let path = self.handle.ltfs_partition_path(&self.tape_name, partition);
+            used_space += path.metadata()?.len() as usize;
+        }
+
+        Ok(used_space > self.handle.max_size)
+    }
+}
+
+impl BlockWrite for VirtualLtfsMedia<'_> {
+    fn write_block(&mut self, buffer: &[u8]) -> Result<bool, io::Error> {
+        if buffer.is_empty() {
+            proxmox_lang::io_bail!("VirtualLtfsMedia: got write with empty block");
+        }
+        self.write_record(buffer)
+    }
+
+    fn write_filemark(&mut self) -> Result<(), io::Error> {
+        self.write_record(&[])?;
+        Ok(())
+    }
+}
+
+impl LtfsMedia for VirtualLtfsMedia<'_> {
+    fn format_partitions(&mut self) -> Result<(), Error> {
+        self.file = None;
+        self.handle.format_media(true)?; // also removes old partitions
+
+        for partition in [LTFS_INDEX_PARTITION, LTFS_DATA_PARTITION] {
+            let path = self.handle.ltfs_partition_path(&self.tape_name, partition);
+            File::create(&path)
+                .map_err(|err| format_err!("unable to create {:?} - {}", path, err))?;
+        }
+
+        Ok(())
+    }
+
+    fn locate(&mut self, partition: u8, logical_object: u64) -> Result<(), Error> {
+        if partition > LTFS_DATA_PARTITION {
+            bail!("locate failed - invalid partition {}", partition);
+        }
+
+        let path = self.handle.ltfs_partition_path(&self.tape_name, partition);
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|err| format_err!("unable to open {:?} - {}", path, err))?;
+
+        for _ in 0..logical_object {
+            let mut len = [0u8; 4];
+            file.read_exact(&mut len)
+                .map_err(|_| format_err!("locate failed - move beyond end of data"))?;
+            let len = u32::from_le_bytes(len) as i64;
+            if len > 0 {
+                file.seek(SeekFrom::Current(len + (len % 2) + 4))?;
+            }
+        }
+
+        if file.stream_position()? > file.metadata()?.len() {
+            bail!("locate failed - move beyond end of data");
+        }
+
+        self.file = Some(file);
+        self.truncate = true;
+
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<(), Error> {
+        if let Some(ref file) = self.file {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
 }
 
 impl MediaChange for VirtualTapeHandle {
@@ -623,3 +768,77 @@ impl MediaChange for VirtualTapeDrive {
         handle.clean_drive()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use pbs_tape::ltfs::{LtfsTime, LtfsWriter};
+
+    use super::*;
+
+    // Parse a SIMH tape image (`None` is a filemark)
+    fn read_tap_records(path: &Path) -> Result<Vec<Option<Vec<u8>>>, Error> {
+        let raw = std::fs::read(path)?;
+        let mut records = Vec::new();
+        let mut pos = 0;
+        while pos < raw.len() {
+            let len = u32::from_le_bytes(raw[pos..pos + 4].try_into()?) as usize;
+            pos += 4;
+            if len == 0 {
+                records.push(None);
+                continue;
+            }
+            records.push(Some(raw[pos..pos + len].to_vec()));
+            pos += len + (len % 2);
+            assert_eq!(&raw[pos..pos + 4], &(len as u32).to_le_bytes());
+            pos += 4;
+        }
+        Ok(records)
+    }
+
+    #[test]
+    fn test_virtual_ltfs_export() -> Result<(), Error> {
+        let testdir =
+            std::env::temp_dir().join(format!("ltfs-virtual-tape-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&testdir);
+        std::fs::create_dir_all(&testdir)?;
+
+        let config = VirtualTapeDrive {
+            name: "ltfs-test".to_string(),
+            path: testdir.to_string_lossy().to_string(),
+            max_size: None,
+        };
+
+        let mut handle = open_virtual_tape_drive(&config)?;
+        handle.load_media("tape01")?;
+
+        let mtime = LtfsTime::new(0, 0);
+        let mut writer = LtfsWriter::format(handle.ltfs_media()?, "tape01", "test")?;
+        writer.create_dir(Path::new("store"), mtime)?;
+        let mut file_writer = writer.create_file(Path::new("store/data.img"), mtime)?;
+        file_writer.write_all(&[1u8; 1001])?;
+        assert_eq!(file_writer.finish()?, 1001);
+        writer.finish()?;
+
+        let data = read_tap_records(&handle.ltfs_partition_path("tape01", LTFS_DATA_PARTITION))?;
+        assert_eq!(data.len(), 8);
+        assert_eq!(&data[0].as_ref().unwrap()[..4], b"VOL1");
+        assert_eq!(data[4].as_deref(), Some(&[1u8; 1001][..]));
+        assert!(data[5].is_none() && data[7].is_none());
+
+        let index = read_tap_records(&handle.ltfs_partition_path("tape01", LTFS_INDEX_PARTITION))?;
+        assert_eq!(index.len(), 6);
+        assert!(String::from_utf8_lossy(index[4].as_ref().unwrap()).contains("data.img"));
+
+        // formatting as PBS media removes the LTFS volume
+        handle.format_media(true)?;
+        assert!(!handle
+            .ltfs_partition_path("tape01", LTFS_DATA_PARTITION)
+            .exists());
+
+        std::fs::remove_dir_all(&testdir)?;
+
+        Ok(())
+    }
+}
@@ -108,6 +108,64 @@ pub fn tape_write_snapshot_archive<'a>(
     }
 }
 
+/// Copy a snapshot archive read from another tape
+///
+/// Writes a new header with the same content magic and header data,
+/// followed by the unmodified `pxar` archive from `reader`.
+///
+/// Returns `Ok(Some(content_uuid))` on success, and `Ok(None)` if
+/// `LEOM` was detected before all data was written. Like with
+/// [tape_write_snapshot_archive], the stream is marked incomplete in
+/// that case and needs to be copied again to the next media.
+pub fn tape_copy_snapshot_archive<'a>(
+    writer: &mut (dyn TapeWrite + 'a),
+    content_magic: [u8; 8],
+    header_data: &[u8],
+    reader: &mut dyn Read,
+) -> Result<Option<Uuid>, std::io::Error> {
+    let header = MediaContentHeader::new(content_magic, header_data.len() as u32);
+    let content_uuid = header.uuid.into();
+
+    let mut file_copy_buffer = proxmox_io::vec::undefined(PROXMOX_TAPE_BLOCK_SIZE);
+
+    let result: Result<(), std::io::Error> = proxmox_lang::try_block!({
+        let leom = writer.write_header(&header, header_data)?;
+        if leom {
+            return Err(std::io::Error::from_raw_os_error(
+                nix::errno::Errno::ENOSPC as i32,
+            ));
+        }
+
+        loop {
+            let got = reader.read(&mut file_copy_buffer[..])?;
+            if got == 0 {
+                break;
+            }
+            if writer.write_all(&file_copy_buffer[..got])? {
+                return Err(std::io::Error::from_raw_os_error(
+                    nix::errno::Errno::ENOSPC as i32,
+                ));
+            }
+        }
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => {
+            writer.finish(false)?;
+            Ok(Some(content_uuid))
+        }
+        Err(err) => {
+            if err.is_errno(nix::errno::Errno::ENOSPC) && writer.logical_end_of_media() {
+                writer.finish(true)?; // mark as incomplete
+                Ok(None)
+            } else {
+                Err(err)
+            }
+        }
+    }
+}
+
 // Helper to create pxar archives on tape
 //
 // We generate and error at LEOM,
@@ -80,6 +80,22 @@ struct MediaStateEntry {
     location: Option<MediaLocation>,
     #[serde(skip_serializing_if = "Option::is_none")]
     status: Option<MediaStatus>,
+    /// Media set this media was duplicated from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    copy_of: Option<Uuid>,
+    /// Retention policy overriding the one of the pool (set by tape backup jobs). The
+    /// longest policy of all members applies to the whole media set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retention: Option<String>,
+}
+
+// Time in seconds a retention policy protects the data, used to compare policies
+fn retention_protect_time(policy: &RetentionPolicy) -> f64 {
+    match policy {
+        RetentionPolicy::OverwriteAlways => 0.0,
+        RetentionPolicy::ProtectFor(time_span) => f64::from(time_span.clone()),
+        RetentionPolicy::KeepForever => f64::INFINITY,
+    }
 }
 
 /// Media Inventory
@@ -199,6 +215,11 @@ impl Inventory {
                     }
                 }
             }
+            // the copy relation and retention belong to the media set
+            let same_media_set = match (&previous.id.media_set_label, &media_id.media_set_label) {
+                (Some(a), Some(b)) => a.uuid == b.uuid,
+                _ => false,
+            };
             let entry = MediaStateEntry {
                 id: media_id,
                 location: previous.location,
@@ -207,6 +228,16 @@ impl Inventory {
                 } else {
                     previous.status
                 },
+                copy_of: if same_media_set {
+                    previous.copy_of
+                } else {
+                    None
+                },
+                retention: if same_media_set {
+                    previous.retention
+                } else {
+                    None
+                },
             };
             self.map.insert(uuid, entry);
         } else {
@@ -214,6 +245,8 @@ impl Inventory {
                 id: media_id,
                 location: None,
                 status: None,
+                copy_of: None,
+                retention: None,
             };
             self.map.insert(uuid, entry);
         }
@@ -488,15 +521,26 @@ impl Inventory {
         media_set_policy: &MediaSetPolicy,
         retention_policy: &RetentionPolicy,
     ) -> i64 {
-        if let RetentionPolicy::KeepForever = retention_policy {
-            return i64::MAX;
-        }
-
         let set = match media.media_set_label {
             None => return i64::MAX,
             Some(ref set) => set,
         };
 
+        // a longer retention policy recorded for the media set overrides the one of the pool
+        let media_set_retention_policy = self.media_set_retention(&set.uuid);
+        let retention_policy = match media_set_retention_policy {
+            Some(ref policy)
+                if retention_protect_time(policy) > retention_protect_time(retention_policy) =>
+            {
+                policy
+            }
+            _ => retention_policy,
+        };
+
+        if let RetentionPolicy::KeepForever = retention_policy {
+            return i64::MAX;
+        }
+
         let set_start_time = match self.media_set_start_time(&set.uuid) {
             None => {
                 // missing information, use ctime from this
@@ -696,6 +740,63 @@ impl Inventory {
         }
     }
 
+    /// Lock database, reload database, record the media set the media was duplicated from,
+    /// store database
+    pub fn set_media_copy_of(&mut self, uuid: &Uuid, media_set_uuid: &Uuid) -> Result<(), Error> {
+        let _lock = self.lock()?;
+        self.map = self.load_media_db()?;
+        if let Some(entry) = self.map.get_mut(uuid) {
+            entry.copy_of = Some(media_set_uuid.clone());
+            self.update_helpers();
+            self.replace_file()?;
+            Ok(())
+        } else {
+            bail!("no such media '{}'", uuid);
+        }
+    }
+
+    /// Lock database, reload database, set the retention policy overriding the one of the
+    /// pool, store database
+    pub fn set_media_retention(
+        &mut self,
+        uuid: &Uuid,
+        retention: Option<String>,
+    ) -> Result<(), Error> {
+        let _lock = self.lock()?;
+        self.map = self.load_media_db()?;
+        if let Some(entry) = self.map.get_mut(uuid) {
+            entry.retention = retention;
+            self.update_helpers();
+            self.replace_file()?;
+            Ok(())
+        } else {
+            bail!("no such media '{}'", uuid);
+        }
+    }
+
+    /// Returns the longest retention policy recorded for any member of the media set
+    pub fn media_set_retention(&self, media_set_uuid: &Uuid) -> Option<RetentionPolicy> {
+        self.map
+            .values()
+            .filter(|entry| match entry.id.media_set_label {
+                Some(ref set) => &set.uuid == media_set_uuid,
+                None => false,
+            })
+            .filter_map(|entry| entry.retention.as_ref())
+            .filter_map(|retention| retention.parse::<RetentionPolicy>().ok())
+            .max_by(|a, b| retention_protect_time(a).total_cmp(&retention_protect_time(b)))
+    }
+
+    /// Returns the media set a media set was duplicated from
+    pub fn media_set_copy_of(&self, media_set_uuid: &Uuid) -> Option<&Uuid> {
+        self.map
+            .values()
+            .find_map(|entry| match entry.id.media_set_label {
+                Some(ref set) if &set.uuid == media_set_uuid => entry.copy_of.as_ref(),
+                _ => None,
+            })
+    }
+
     /// Lock database, reload database, set location to vault, store database
     pub fn set_media_location_vault(&mut self, uuid: &Uuid, vault: &str) -> Result<(), Error> {
         self.set_media_location(uuid, Some(MediaLocation::Vault(vault.to_string())))
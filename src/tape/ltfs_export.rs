@@ -0,0 +1,208 @@
+//! Export datastore snapshots as plain files to LTFS volumes
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{bail, format_err, Error};
+
+use proxmox_human_byte::HumanByte;
+use proxmox_sys::{task_log, task_warn, WorkerTaskContext};
+
+use pbs_api_types::{parse_ns_and_snapshot, print_ns_and_snapshot, CryptMode, Operation};
+use pbs_client::pxar::payload::{open_payload, payload_archive_name, PayloadReader, PayloadRef};
+use pbs_datastore::dynamic_index::{BufferedDynamicReader, DynamicIndexReader};
+use pbs_datastore::fixed_index::FixedIndexReader;
+use pbs_datastore::index::IndexFile;
+use pbs_datastore::manifest::{archive_type, ArchiveType, BackupManifest, MANIFEST_BLOB_NAME};
+use pbs_datastore::read_chunk::ReadChunk;
+use pbs_datastore::{DataBlob, DataStore, LocalChunkReader, SnapshotReader};
+use pbs_tape::ltfs::{LtfsMedia, LtfsTime, LtfsWriter};
+
+/// Export snapshots to an LTFS volume
+///
+/// Snapshots are given as `store:[ns/]type/id/time`, and are written
+/// to `/<store>/<snapshot path>/`:
+///
+/// - blobs are stored decoded (e.g. `index.json`)
+/// - fixed index archives are stored as image (e.g. `drive-scsi0.img`)
+/// - pxar archives are extracted into a directory (e.g. `root/`), split archives are
+///   extracted together with their payload archive
+/// - other dynamic index archives are stored as decoded stream
+///
+/// Encrypted snapshots cannot be exported.
+pub fn export_snapshots_ltfs<M: LtfsMedia>(
+    worker: &dyn WorkerTaskContext,
+    writer: &mut LtfsWriter<M>,
+    snapshots: &[String],
+) -> Result<(), Error> {
+    for store_snapshot in snapshots {
+        worker.check_abort()?;
+
+        let (store, snapshot) = store_snapshot
+            .split_once(':')
+            .ok_or_else(|| format_err!("invalid snapshot '{store_snapshot}'"))?;
+        let (ns, dir) = parse_ns_and_snapshot(snapshot)?;
+
+        let datastore = DataStore::lookup_datastore(store, Some(Operation::Read))?;
+        let snapshot_reader = SnapshotReader::new(datastore.clone(), ns.clone(), dir.clone())?;
+        let (manifest, _) = snapshot_reader.snapshot().load_manifest()?;
+
+        let snapshot_path = Path::new(store).join(snapshot_reader.snapshot().relative_path());
+        let mtime = LtfsTime::new(dir.time, 0);
+
+        task_log!(
+            worker,
+            "export snapshot {store}:{}",
+            print_ns_and_snapshot(&ns, &dir)
+        );
+
+        writer.create_dir(&snapshot_path, mtime)?;
+
+        let chunk_reader = LocalChunkReader::new(datastore, None, CryptMode::None);
+
+        for filename in snapshot_reader.file_list() {
+            worker.check_abort()?;
+
+            if filename != MANIFEST_BLOB_NAME {
+                if let Ok(info) = manifest.lookup_file_info(filename) {
+                    if info.crypt_mode == CryptMode::Encrypt {
+                        bail!("unable to export '{filename}' - snapshot is encrypted");
+                    }
+                }
+            }
+
+            if filename.ends_with(".ppxar.didx") {
+                continue; // extracted together with the metadata archive
+            }
+
+            let file = snapshot_reader.open_file(filename)?;
+
+            let size = match archive_type(filename)? {
+                ArchiveType::Blob => {
+                    let mut file = file;
+                    let blob = DataBlob::load_from_reader(&mut file)?;
+                    let data = blob.decode(None, None)?;
+                    let path = snapshot_path.join(strip_suffix(filename, ".blob"));
+                    let mut file_writer = writer.create_file(&path, mtime)?;
+                    file_writer.write_all(&data)?;
+                    file_writer.finish()?
+                }
+                ArchiveType::FixedIndex => {
+                    let index = FixedIndexReader::new(file)?;
+                    check_index_csum(&manifest, filename, &index)?;
+                    let path = snapshot_path.join(strip_suffix(filename, ".fidx"));
+                    let mut file_writer = writer.create_file(&path, mtime)?;
+                    for pos in 0..index.index_count() {
+                        worker.check_abort()?;
+                        let digest = index.index_digest(pos).unwrap();
+                        let data = chunk_reader.read_chunk(digest)?;
+                        file_writer.write_all(&data)?;
+                    }
+                    file_writer.finish()?
+                }
+                ArchiveType::DynamicIndex => {
+                    let index = DynamicIndexReader::new(file)?;
+                    check_index_csum(&manifest, filename, &index)?;
+                    let archive = BufferedDynamicReader::new(index, chunk_reader.clone());
+                    if let Some(name) = filename.strip_suffix(".pxar.didx") {
+                        let path = snapshot_path.join(name);
+                        export_pxar_archive(worker, writer, archive, None, &path)?
+                    } else if let Some(payload_name) = payload_archive_name(filename) {
+                        let payload_index =
+                            DynamicIndexReader::new(snapshot_reader.open_file(&payload_name)?)?;
+                        check_index_csum(&manifest, &payload_name, &payload_index)?;
+                        let mut payload =
+                            BufferedDynamicReader::new(payload_index, chunk_reader.clone());
+                        let path = snapshot_path.join(strip_suffix(filename, ".mpxar.didx"));
+                        export_pxar_archive(worker, writer, archive, Some(&mut payload), &path)?
+                    } else {
+                        let path = snapshot_path.join(strip_suffix(filename, ".didx"));
+                        let mut archive = archive;
+                        let mut file_writer = writer.create_file(&path, mtime)?;
+                        std::io::copy(&mut archive, &mut file_writer)?;
+                        file_writer.finish()?
+                    }
+                }
+            };
+
+            task_log!(worker, "exported {filename} ({})", HumanByte::from(size));
+        }
+    }
+
+    Ok(())
+}
+
+fn strip_suffix<'a>(filename: &'a str, suffix: &str) -> &'a str {
+    filename.strip_suffix(suffix).unwrap_or(filename)
+}
+
+fn check_index_csum(
+    manifest: &BackupManifest,
+    filename: &str,
+    index: &dyn IndexFile,
+) -> Result<(), Error> {
+    let (csum, size) = index.compute_csum();
+    manifest.verify_file(filename, &csum, size)
+}
+
+// Extract all regular files, directories and symlinks. For split archives,
+// `payload` reads the file contents from the payload archive. Returns the
+// number of bytes written.
+fn export_pxar_archive<M: LtfsMedia>(
+    worker: &dyn WorkerTaskContext,
+    writer: &mut LtfsWriter<M>,
+    archive: BufferedDynamicReader<LocalChunkReader>,
+    mut payload: Option<&mut dyn PayloadReader>,
+    base: &Path,
+) -> Result<u64, Error> {
+    let mut decoder = pxar::decoder::sync::Decoder::from_std(archive)?;
+
+    let mut bytes = 0;
+    let mut skipped = 0;
+
+    while let Some(entry) = decoder.next() {
+        let entry = entry?;
+        worker.check_abort()?;
+
+        let path = base.join(entry.path().strip_prefix("/").unwrap_or(entry.path()));
+        let stat = &entry.metadata().stat;
+        let mtime = LtfsTime::new(stat.mtime.secs, stat.mtime.nanos);
+
+        match entry.kind() {
+            pxar::EntryKind::Directory => writer.create_dir(&path, mtime)?,
+            pxar::EntryKind::File { size, .. } => {
+                let mut contents = decoder
+                    .contents()
+                    .ok_or_else(|| format_err!("missing contents for {path:?}"))?;
+                let mut file_writer = writer.create_file(&path, mtime)?;
+                match payload.as_mut() {
+                    Some(payload) => {
+                        let payload_ref = PayloadRef::read_from(&mut contents, *size)
+                            .map_err(|err| format_err!("{path:?}: {err}"))?;
+                        let mut payload = open_payload(&mut **payload, &payload_ref)?;
+                        std::io::copy(&mut payload, &mut file_writer)?;
+                    }
+                    None => {
+                        std::io::copy(&mut contents, &mut file_writer)?;
+                    }
+                }
+                bytes += file_writer.finish()?;
+            }
+            pxar::EntryKind::Symlink(link) => {
+                writer.create_symlink(&path, link.as_os_str(), mtime)?
+            }
+            pxar::EntryKind::GoodbyeTable => {}
+            // LTFS cannot represent hardlinks and special files
+            _ => skipped += 1,
+        }
+    }
+
+    if skipped > 0 {
+        task_warn!(
+            worker,
+            "skipped {skipped} hardlinks or special files in {base:?}"
+        );
+    }
+
+    Ok(bytes)
+}
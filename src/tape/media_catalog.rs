@@ -51,6 +51,9 @@ pub struct MediaCatalog {
 
     content: HashMap<String, DatastoreContent>,
 
+    // media set this media set is a copy of
+    copy_of: Option<Uuid>,
+
     pending: Vec<u8>,
 }
 
@@ -215,6 +218,7 @@ impl MediaCatalog {
                 current_archive: None,
                 last_entry: None,
                 content: HashMap::new(),
+                copy_of: None,
                 pending: Vec::new(),
             };
 
@@ -294,6 +298,7 @@ impl MediaCatalog {
                 current_archive: None,
                 last_entry: None,
                 content: HashMap::new(),
+                copy_of: None,
                 pending: Vec::new(),
             };
 
@@ -357,6 +362,11 @@ impl MediaCatalog {
         &self.content
     }
 
+    /// Returns the uuid of the media set this media set is a copy of
+    pub fn copy_of(&self) -> Option<&Uuid> {
+        self.copy_of.as_ref()
+    }
+
     /// Commit pending changes
     ///
     /// This is necessary to store changes persistently.
@@ -519,6 +529,42 @@ impl MediaCatalog {
         Ok(())
     }
 
+    fn check_register_copy_origin(&self) -> Result<(), Error> {
+        if self.copy_of.is_some() {
+            bail!("register copy origin failed: already registered");
+        }
+
+        match self.last_entry {
+            Some((_, 1)) => Ok(()),
+            _ => bail!("register copy origin failed: expected directly after media set label"),
+        }
+    }
+
+    /// Register the media set this media set is a copy of
+    ///
+    /// Only valid directly after the media set label (file 1).
+    pub fn register_copy_origin(&mut self, media_set_uuid: &Uuid) -> Result<(), Error> {
+        self.check_register_copy_origin()?;
+
+        let entry = CopyOriginEntry {
+            media_set_uuid: *media_set_uuid.as_bytes(),
+        };
+
+        if self.log_to_stdout {
+            println!("O|{}", media_set_uuid);
+        }
+
+        self.pending.push(b'O');
+
+        unsafe {
+            self.pending.write_le_value(entry)?;
+        }
+
+        self.copy_of = Some(media_set_uuid.clone());
+
+        Ok(())
+    }
+
     /// Register a chunk archive
     pub fn register_chunk_archive(
         &mut self,
@@ -899,6 +945,13 @@ impl MediaCatalog {
 
                     self.last_entry = Some((uuid, file_number));
                 }
+                b'O' => {
+                    let entry: CopyOriginEntry = unsafe { file.read_le_value()? };
+
+                    self.check_register_copy_origin()?;
+
+                    self.copy_of = Some(Uuid::from(entry.media_set_uuid));
+                }
                 b'L' => {
                     let entry: LabelEntry = unsafe { file.read_le_value()? };
                     let file_number = entry.file_number;
@@ -1030,6 +1083,12 @@ struct LabelEntry {
     seq_nr: u64, // only used for media set labels
 }
 
+#[derive(Endian)]
+#[repr(C)]
+struct CopyOriginEntry {
+    media_set_uuid: [u8; 16],
+}
+
 #[derive(Endian)]
 #[repr(C)]
 struct ChunkArchiveStart {
@@ -33,6 +33,10 @@ pub struct MediaPool {
     media_set_policy: MediaSetPolicy,
     retention: RetentionPolicy,
 
+    // Job specific policies, overriding the pool configuration
+    media_set_policy_override: Option<MediaSetPolicy>,
+    retention_override: Option<String>,
+
     changer_name: Option<String>,
     force_media_availability: bool,
 
@@ -89,6 +93,8 @@ impl MediaPool {
             state_path: state_path.as_ref().to_owned(),
             media_set_policy,
             retention,
+            media_set_policy_override: None,
+            retention_override: None,
             changer_name,
             inventory,
             current_media_set,
@@ -106,6 +112,22 @@ impl MediaPool {
         self.force_media_availability = true;
     }
 
+    /// Use another media set allocation policy than the pool configuration
+    pub fn override_media_set_policy(&mut self, media_set_policy: MediaSetPolicy) {
+        self.media_set_policy_override = Some(media_set_policy);
+    }
+
+    /// Use another retention policy than the pool configuration
+    ///
+    /// The policy gets recorded in the inventory for all media added
+    /// to the current media set, so it also applies when the pool is
+    /// used without the override.
+    pub fn override_retention(&mut self, retention: &str) -> Result<(), Error> {
+        let _: RetentionPolicy = retention.parse()?;
+        self.retention_override = Some(retention.to_string());
+        Ok(())
+    }
+
     /// Returns the the current media set
     pub fn current_media_set(&self) -> &MediaSet {
         &self.current_media_set
@@ -270,7 +292,11 @@ impl MediaPool {
         };
 
         if create_new_set.is_none() {
-            match &self.media_set_policy {
+            let media_set_policy = self
+                .media_set_policy_override
+                .as_ref()
+                .unwrap_or(&self.media_set_policy);
+            match media_set_policy {
                 MediaSetPolicy::AlwaysCreate => {
                     create_new_set = Some(String::from("policy is AlwaysCreate"));
                 }
@@ -388,6 +414,11 @@ impl MediaPool {
         let clear_media_status = true; // remove Full status
         self.inventory.store(media_id, clear_media_status)?; // store persistently
 
+        if self.retention_override.is_some() {
+            self.inventory
+                .set_media_retention(&uuid, self.retention_override.clone())?;
+        }
+
         self.current_media_set.add_media(uuid);
 
         Ok(())
@@ -1,5 +1,7 @@
 //! Magnetic tape backup
 
+use std::path::Path;
+
 use anyhow::{format_err, Error};
 
 use proxmox_sys::fs::{create_path, CreateOptions};
@@ -20,6 +22,7 @@ pub use inventory::*;
 pub mod changer;
 pub mod drive;
 pub mod encryption_keys;
+pub mod ltfs_export;
 
 mod media_pool;
 pub use media_pool::*;
@@ -45,6 +48,10 @@ pub const DRIVE_STATE_DIR: &str = concat!(PROXMOX_BACKUP_RUN_DIR_M!(), "/drive-s
 /// Directory path where we store cached changer state
 pub const CHANGER_STATE_DIR: &str = concat!(PROXMOX_BACKUP_RUN_DIR_M!(), "/changer-state");
 
+/// Directory path where single file restores from tape are written to
+pub const TAPE_FILE_RESTORE_DIR: &str =
+    concat!(PROXMOX_BACKUP_STATE_DIR_M!(), "/tape-file-restore");
+
 /// We limit chunk archive size, so that we can faster restore a
 /// specific chunk (The catalog only store file numbers, so we
 /// need to read the whole archive to restore a single chunk)
@@ -72,6 +79,33 @@ pub fn create_tape_status_dir() -> Result<(), Error> {
     Ok(())
 }
 
+/// Create the tape file restore dir of a media pool with correct permission
+pub fn create_tape_file_restore_dir(pool: &str) -> Result<(), Error> {
+    let backup_user = pbs_config::backup_user()?;
+    let mode = nix::sys::stat::Mode::from_bits_truncate(0o0750);
+    let options = CreateOptions::new()
+        .perm(mode)
+        .owner(backup_user.uid)
+        .group(backup_user.gid);
+
+    let parent_opts = CreateOptions::new()
+        .owner(backup_user.uid)
+        .group(backup_user.gid);
+
+    create_path(
+        TAPE_FILE_RESTORE_DIR,
+        Some(parent_opts),
+        Some(options.clone()),
+    )
+    .map_err(|err: Error| format_err!("unable to create tape file restore dir - {}", err))?;
+
+    let path = Path::new(TAPE_FILE_RESTORE_DIR).join(pool);
+    create_path(&path, None, Some(options))
+        .map_err(|err: Error| format_err!("unable to create {:?} - {}", path, err))?;
+
+    Ok(())
+}
+
 /// Create drive lock dir with correct permission
 pub fn create_drive_lock_dir() -> Result<(), Error> {
     let backup_user = pbs_config::backup_user()?;
@@ -15,7 +15,8 @@ use anyhow::{bail, Error};
 use proxmox_sys::{task_log, task_warn};
 use proxmox_uuid::Uuid;
 
-use pbs_datastore::{DataStore, SnapshotReader};
+use pbs_api_types::{BackupDir, BackupNamespace};
+use pbs_datastore::{DataBlob, DataStore, SnapshotReader};
 use pbs_tape::{sg_tape::tape_alert_flags_critical, TapeWrite};
 use proxmox_rest_server::WorkerTask;
 
@@ -23,9 +24,11 @@ use crate::tape::{
     drive::{media_changer, request_and_load_media, TapeDriver},
     encryption_keys::load_key_configs,
     file_formats::{
-        tape_write_catalog, tape_write_snapshot_archive, ChunkArchiveWriter, MediaSetLabel,
+        tape_copy_snapshot_archive, tape_write_catalog, tape_write_snapshot_archive,
+        ChunkArchiveWriter, MediaSetLabel,
     },
-    MediaCatalog, MediaId, MediaPool, COMMIT_BLOCK_SIZE, MAX_CHUNK_ARCHIVE_SIZE, TAPE_STATUS_DIR,
+    Inventory, MediaCatalog, MediaId, MediaPool, COMMIT_BLOCK_SIZE, MAX_CHUNK_ARCHIVE_SIZE,
+    TAPE_STATUS_DIR,
 };
 
 use super::file_formats::{
@@ -51,6 +54,8 @@ pub struct PoolWriter {
     notify_email: Option<String>,
     ns_magic: bool,
     used_tapes: HashSet<Uuid>,
+    // media set this pool writer creates a copy of
+    copy_of: Option<Uuid>,
 }
 
 impl PoolWriter {
@@ -89,6 +94,7 @@ impl PoolWriter {
             notify_email,
             ns_magic,
             used_tapes: HashSet::new(),
+            copy_of: None,
         })
     }
 
@@ -96,6 +102,14 @@ impl PoolWriter {
         &mut self.pool
     }
 
+    /// Mark the written media set as copy of another media set
+    ///
+    /// The relation is recorded in the media catalog and inventory of
+    /// each newly written media.
+    pub fn set_copy_of(&mut self, media_set_uuid: Uuid) {
+        self.copy_of = Some(media_set_uuid);
+    }
+
     /// Set media status to FULL (persistent - stores pool status)
     pub fn set_media_status_full(&mut self, uuid: &Uuid) -> Result<(), Error> {
         self.pool.set_media_status_full(uuid)?;
@@ -261,13 +275,21 @@ impl PoolWriter {
             }
         }
 
-        let (catalog, is_new_media) = update_media_set_label(
+        let (mut catalog, is_new_media) = update_media_set_label(
             worker,
             drive.as_mut(),
             old_media_id.media_set_label,
             media.id(),
         )?;
 
+        if is_new_media {
+            if let Some(ref copy_of) = self.copy_of {
+                catalog.register_copy_origin(copy_of)?;
+                catalog.commit()?;
+                Inventory::load(TAPE_STATUS_DIR)?.set_media_copy_of(&media_uuid, copy_of)?;
+            }
+        }
+
         self.catalog_set.lock().unwrap().append_catalog(catalog)?;
 
         let media_set = media.media_set_label().unwrap();
@@ -483,16 +505,74 @@ impl PoolWriter {
         Ok((done, bytes_written))
     }
 
+    /// Move to EOM (if not already there), then copies a snapshot
+    /// archive read from another tape. On success, this return
+    /// 'Ok(true)' and the media catalog gets updated.
+
+    /// Like with `append_snapshot_archive`, this may fail when there
+    /// is not enough space on the media (return value 'Ok(false, _)').
+    /// The caller should mark the media as full and copy the archive
+    /// again to another media.
+    #[allow(clippy::too_many_arguments)]
+    pub fn append_snapshot_archive_copy(
+        &mut self,
+        worker: &WorkerTask,
+        content_magic: [u8; 8],
+        header_data: &[u8],
+        store: &str,
+        ns: &BackupNamespace,
+        snapshot: &BackupDir,
+        reader: &mut dyn std::io::Read,
+    ) -> Result<(bool, usize), Error> {
+        let status = match self.status {
+            Some(ref mut status) => status,
+            None => bail!("PoolWriter - no media loaded"),
+        };
+
+        let current_file_number = Self::prepare_tape_write(status, worker)?;
+
+        let (done, bytes_written) = {
+            let mut writer: Box<dyn TapeWrite> = status.drive.write_file()?;
+
+            match tape_copy_snapshot_archive(writer.as_mut(), content_magic, header_data, reader)? {
+                Some(content_uuid) => {
+                    self.catalog_set.lock().unwrap().register_snapshot(
+                        content_uuid,
+                        current_file_number,
+                        store,
+                        ns,
+                        snapshot,
+                    )?;
+                    (true, writer.bytes_written())
+                }
+                None => (false, writer.bytes_written()),
+            }
+        };
+
+        status.bytes_written += bytes_written;
+
+        let request_sync = status.bytes_written >= COMMIT_BLOCK_SIZE;
+
+        if !done || request_sync {
+            self.commit()?;
+        }
+
+        Ok((done, bytes_written))
+    }
+
     /// Move to EOM (if not already there), then creates a new chunk
     /// archive and writes chunks from 'chunk_iter'. This stops when
     /// it detect LEOM or when we reach max archive size
     /// (4GB). Written chunks are registered in the media catalog.
-    pub fn append_chunk_archive(
+    pub fn append_chunk_archive<I>(
         &mut self,
         worker: &WorkerTask,
-        chunk_iter: &mut std::iter::Peekable<NewChunksIterator>,
+        chunk_iter: &mut std::iter::Peekable<I>,
         store: &str,
-    ) -> Result<(bool, usize), Error> {
+    ) -> Result<(bool, usize), Error>
+    where
+        I: Iterator<Item = Result<([u8; 32], DataBlob), Error>>,
+    {
         let status = match self.status {
             Some(ref mut status) => status,
             None => bail!("PoolWriter - no media loaded"),
@@ -554,13 +634,16 @@ impl PoolWriter {
 
 /// write up to <max_size> of chunks
 #[allow(clippy::type_complexity)]
-fn write_chunk_archive<'a>(
+fn write_chunk_archive<'a, I>(
     _worker: &WorkerTask,
     writer: Box<dyn 'a + TapeWrite>,
-    chunk_iter: &mut std::iter::Peekable<NewChunksIterator>,
+    chunk_iter: &mut std::iter::Peekable<I>,
     store: &str,
     max_size: usize,
-) -> Result<(Vec<[u8; 32]>, Uuid, bool, usize), Error> {
+) -> Result<(Vec<[u8; 32]>, Uuid, bool, usize), Error>
+where
+    I: Iterator<Item = Result<([u8; 32], DataBlob), Error>>,
+{
     let (mut writer, content_uuid) = ChunkArchiveWriter::new(writer, store, true)?;
 
     // we want to get the chunk list in correct order
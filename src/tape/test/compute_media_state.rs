@@ -132,3 +132,97 @@ fn test_media_expire_time() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_media_expire_time_retention_override() -> Result<(), Error> {
+    let testdir = create_testdir("test_media_expire_time_retention_override")?;
+
+    let ctime = 0;
+
+    let mut inventory = Inventory::load(&testdir)?;
+
+    // tape0: single tape media set, written with 'keep' retention
+    let sl0 = MediaSetLabel::with_data("p1", Uuid::generate(), 0, ctime, None);
+    let tape0_uuid = inventory.generate_used_tape("tape0", sl0, 0);
+    inventory.set_media_retention(&tape0_uuid, Some(String::from("keep")))?;
+
+    // tape1: single tape media set
+    let sl1 = MediaSetLabel::with_data("p1", Uuid::generate(), 0, ctime + 60, None);
+    let tape1_uuid = inventory.generate_used_tape("tape1", sl1, 0);
+
+    // tape2: single tape media set
+    let sl2 = MediaSetLabel::with_data("p1", Uuid::generate(), 0, ctime + 120, None);
+    let _tape2_uuid = inventory.generate_used_tape("tape2", sl2, 0);
+
+    let event = "*:0/2".parse()?;
+    let span = "120 seconds".parse()?;
+
+    let pool = MediaPool::new(
+        "p1",
+        &testdir,
+        MediaSetPolicy::CreateAt(event),
+        RetentionPolicy::ProtectFor(span),
+        None,
+        None,
+        false,
+    )?;
+
+    assert!(!pool.media_is_expired(&pool.lookup_media(&tape0_uuid)?, 180));
+    assert!(!pool.media_is_expired(&pool.lookup_media(&tape0_uuid)?, i64::MAX - 1));
+
+    assert!(pool.media_is_expired(&pool.lookup_media(&tape1_uuid)?, 240));
+
+    Ok(())
+}
+
+#[test]
+fn test_media_expire_time_media_set_retention() -> Result<(), Error> {
+    let testdir = create_testdir("test_media_expire_time_media_set_retention")?;
+
+    let ctime = 0;
+
+    let mut inventory = Inventory::load(&testdir)?;
+
+    // tape0, tape1: media set where only the second media was written with 'keep' retention
+    let sl0 = MediaSetLabel::with_data("p1", Uuid::generate(), 0, ctime, None);
+    let sl1 = MediaSetLabel::with_data("p1", sl0.uuid.clone(), 1, ctime + 10, None);
+    let tape0_uuid = inventory.generate_used_tape("tape0", sl0, 0);
+    let tape1_uuid = inventory.generate_used_tape("tape1", sl1, 0);
+    inventory.set_media_retention(&tape1_uuid, Some(String::from("keep")))?;
+
+    // tape2, tape3: media set with a shorter recorded retention than the pool
+    let sl2 = MediaSetLabel::with_data("p1", Uuid::generate(), 0, ctime + 60, None);
+    let sl3 = MediaSetLabel::with_data("p1", sl2.uuid.clone(), 1, ctime + 70, None);
+    let tape2_uuid = inventory.generate_used_tape("tape2", sl2, 0);
+    let tape3_uuid = inventory.generate_used_tape("tape3", sl3, 0);
+    inventory.set_media_retention(&tape3_uuid, Some(String::from("overwrite")))?;
+
+    // tape4: single tape media set
+    let sl4 = MediaSetLabel::with_data("p1", Uuid::generate(), 0, ctime + 120, None);
+    let _tape4_uuid = inventory.generate_used_tape("tape4", sl4, 0);
+
+    let event = "*:0/2".parse()?;
+    let span = "120 seconds".parse()?;
+
+    let pool = MediaPool::new(
+        "p1",
+        &testdir,
+        MediaSetPolicy::CreateAt(event),
+        RetentionPolicy::ProtectFor(span),
+        None,
+        None,
+        false,
+    )?;
+
+    // the longest retention of any member applies to the whole media set
+    assert!(!pool.media_is_expired(&pool.lookup_media(&tape0_uuid)?, i64::MAX - 1));
+    assert!(!pool.media_is_expired(&pool.lookup_media(&tape1_uuid)?, i64::MAX - 1));
+
+    // the pool retention is longer than the recorded one
+    assert!(!pool.media_is_expired(&pool.lookup_media(&tape2_uuid)?, 180));
+    assert!(!pool.media_is_expired(&pool.lookup_media(&tape3_uuid)?, 180));
+    assert!(pool.media_is_expired(&pool.lookup_media(&tape2_uuid)?, 240));
+    assert!(pool.media_is_expired(&pool.lookup_media(&tape3_uuid)?, 240));
+
+    Ok(())
+}
@@ -227,3 +227,26 @@ fn test_latest_media_set() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_media_set_copy_of() -> Result<(), Error> {
+    let testdir = create_testdir("test_media_set_copy_of")?;
+    let mut inventory = Inventory::load(&testdir)?;
+
+    let sl1 = MediaSetLabel::with_data("p1", Uuid::generate(), 0, 10, None);
+    let sl2 = MediaSetLabel::with_data("p2", Uuid::generate(), 0, 20, None);
+
+    let _tape1_uuid = inventory.generate_used_tape("tape1", sl1.clone(), 0);
+    let tape2_uuid = inventory.generate_used_tape("tape2", sl2.clone(), 0);
+
+    assert!(inventory
+        .set_media_copy_of(&Uuid::generate(), &sl1.uuid)
+        .is_err());
+    inventory.set_media_copy_of(&tape2_uuid, &sl1.uuid)?;
+
+    let inventory = Inventory::load(&testdir)?;
+    assert_eq!(inventory.media_set_copy_of(&sl2.uuid), Some(&sl1.uuid));
+    assert_eq!(inventory.media_set_copy_of(&sl1.uuid), None);
+
+    Ok(())
+}
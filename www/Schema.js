@@ -11,5 +11,9 @@ Ext.define('PBS.Schema', {
 	    type: 'InfluxDB (UDP)',
 	    xtype: 'InfluxDbUdp',
 	},
+	'otlp-http': {
+	    type: 'OpenTelemetry (OTLP/HTTP)',
+	    xtype: 'OtlpHttp',
+	},
     },
 });
@@ -403,6 +403,7 @@ Ext.define('PBS.Utils', {
 	    dircreate: [gettext('Directory Storage'), gettext('Create')],
 	    dirremove: [gettext('Directory'), gettext('Remove')],
 	    'eject-media': [gettext('Drive'), gettext('Eject Media')],
+	    'export-ltfs': [gettext('Drive'), gettext('LTFS Export')],
 	    "format-media": [gettext('Drive'), gettext('Format media')],
 	    "forget-group": [gettext('Group'), gettext('Remove Group')],
 	    garbage_collection: ['Datastore', gettext('Garbage Collect')],
@@ -415,16 +416,20 @@ Ext.define('PBS.Utils', {
 	    prunejob: (type, id) => PBS.Utils.render_prune_job_worker_id(id, gettext('Prune Job')),
 	    reader: (type, id) => PBS.Utils.render_datastore_worker_id(id, gettext('Read Objects')),
 	    'rewind-media': [gettext('Drive'), gettext('Rewind Media')],
+	    'space-usage': ['Datastore', gettext('Space Usage Scan')],
 	    sync: ['Datastore', gettext('Remote Sync')],
 	    syncjob: [gettext('Sync Job'), gettext('Remote Sync')],
 	    'tape-backup': (type, id) => PBS.Utils.render_tape_backup_id(id, gettext('Tape Backup')),
 	    'tape-backup-job': (type, id) => PBS.Utils.render_tape_backup_id(id, gettext('Tape Backup Job')),
+	    'tape-file-restore': [gettext('Media Set'), gettext('Tape File Restore')],
+	    'tape-reconcile': [gettext('Changer'), gettext('Inventory Reconcile')],
 	    'tape-restore': ['Datastore', gettext('Tape Restore')],
 	    'unload-media': [gettext('Drive'), gettext('Unload Media')],
 	    verificationjob: [gettext('Verify Job'), gettext('Scheduled Verification')],
 	    verify: ['Datastore', gettext('Verification')],
 	    verify_group: ['Group', gettext('Verification')],
 	    verify_snapshot: ['Snapshot', gettext('Verification')],
+	    verify_chunks: ['Datastore', gettext('Chunk Verification')],
 	    zfscreate: [gettext('ZFS Storage'), gettext('Create')],
 	});
 
@@ -682,7 +687,12 @@ Ext.define('PBS.Utils', {
 	let extra = '';
 
 	if (activeTasks !== undefined) {
-	    const conflictingTasks = activeTasks.write + (type === 'offline' ? activeTasks.read : 0);
+	    let conflictingTasks = activeTasks.write;
+	    if (type === 'offline' || type === 'unmounting') {
+		conflictingTasks += activeTasks.read + (activeTasks.delete ?? 0);
+	    } else if (type === 'read-only') {
+		conflictingTasks += activeTasks.delete ?? 0;
+	    }
 
 	    if (conflictingTasks > 0) {
 		extra += '| <i class="fa fa-spinner fa-pulse fa-fw"></i> ';
@@ -702,6 +712,10 @@ Ext.define('PBS.Utils', {
 		break;
 	    case 'offline': modeText = gettext("Offline");
 		break;
+	    case 'delete-only': modeText = gettext("Delete-only");
+		break;
+	    case 'unmounting': modeText = gettext("Unmounting");
+		break;
 	}
 	return `${modeText} ${extra}`;
     },
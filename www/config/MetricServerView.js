@@ -98,6 +98,12 @@ Ext.define('PBS.config.MetricServerView', {
 		    iconCls: 'fa fa-fw fa-bar-chart',
 		    handler: 'addServer',
 		},
+		{
+		    text: 'OpenTelemetry (OTLP/HTTP)',
+		    type: 'otlp-http',
+		    iconCls: 'fa fa-fw fa-bar-chart',
+		    handler: 'addServer',
+		},
 	    ],
 	},
 	{
@@ -14,6 +14,7 @@ Ext.define('pbs-data-store-snapshots', {
 	'verification',
 	'fingerprint',
 	{ name: 'size', type: 'int', allowNull: true },
+	{ name: 'exclusive-size', type: 'int', allowNull: true },
 	{ name: 'sortWeight', type: 'int', allowNull: true },
 	{ name: 'ty', type: 'string', allowNull: true },
 	{
@@ -164,12 +165,13 @@ Ext.define('PBS.DataStoreContent', {
 		let { result: { data: groups } } = await Proxmox.Async.api2({ url });
 		let map = {};
 		for (const group of groups) {
-		    map[`${group["backup-type"]}/${group["backup-id"]}`] = group.comment;
+		    map[`${group["backup-type"]}/${group["backup-id"]}`] = group;
 		}
 		view.getRootNode().cascade(node => {
 		    if (node.data.ty === 'group') {
-			let group = `${node.data.backup_type}/${node.data.backup_id}`;
-			node.set('comment', map[group], { dirty: false });
+			let group = map[`${node.data.backup_type}/${node.data.backup_id}`] ?? {};
+			node.set('comment', group.comment, { dirty: false });
+			node.set('exclusive-size', group['exclusive-size'], { dirty: false });
 		    }
 		});
 	    } catch (err) {
@@ -1048,6 +1050,19 @@ Ext.define('PBS.DataStoreContent', {
 		return Proxmox.Utils.format_size(v);
 	    },
 	},
+	{
+	    header: gettext("Exclusive Size"),
+	    tooltip: gettext("Space which would be freed by removing the snapshot or group, as of the last space usage scan"),
+	    sortable: true,
+	    hidden: true,
+	    dataIndex: 'exclusive-size',
+	    renderer: (v, meta, { data }) => {
+		if (v === undefined || v === null || (data.ty !== 'dir' && data.ty !== 'group')) {
+		    return '';
+		}
+		return Proxmox.Utils.format_size(v);
+	    },
+	},
 	{
 	    xtype: 'numbercolumn',
 	    format: '0',
@@ -5,6 +5,7 @@ Ext.define('PBS.form.maintenanceType', {
     comboItems: [
 	['__default__', gettext('None')],
 	['read-only', gettext('Read only')],
+	['delete-only', gettext('Delete only')],
 	['offline', gettext('Offline')],
     ],
 });
@@ -0,0 +1,137 @@
+Ext.define('PBS.window.OtlpHttpEdit', {
+    extend: 'Proxmox.window.Edit',
+    mixins: ['Proxmox.Mixin.CBind'],
+
+    subject: 'OpenTelemetry (OTLP/HTTP)',
+
+    cbindData: function() {
+	let me = this;
+	me.isCreate = !me.serverid;
+	me.serverid = me.serverid || "";
+	me.url = `/api2/extjs/config/metrics/otlp-http/${me.serverid}`;
+	me.headerEmptyText = me.isCreate ? 'Authorization: Bearer <token>' : gettext('unchanged');
+	me.method = me.isCreate ? 'POST' : 'PUT';
+	if (!me.isCreate) {
+	    me.subject = `${me.subject}: ${me.serverid}`;
+	}
+	return {};
+    },
+
+    items: [
+	{
+	    xtype: 'inputpanel',
+
+	    cbind: {
+		isCreate: '{isCreate}',
+	    },
+
+	    onGetValues: function(values) {
+		let me = this;
+
+		let splitLines = (text) => (text ?? '').split('\n')
+		    .map(line => line.trim())
+		    .filter(line => line !== '');
+
+		let headers = splitLines(values.header);
+		if (headers.length > 0) {
+		    values.header = headers;
+		} else {
+		    // headers are not returned by the API, so an empty field keeps them
+		    delete values.header;
+		}
+
+		let attributes = splitLines(values['resource-attribute']);
+		values['resource-attribute'] = attributes.length > 0 ? attributes : '';
+		PBS.Utils.delete_if_default(values, 'resource-attribute', '', me.isCreate);
+
+		return values;
+	    },
+
+	    column1: [
+		{
+		    xtype: 'pmxDisplayEditField',
+		    name: 'name',
+		    fieldLabel: gettext('Name'),
+		    allowBlank: false,
+		    cbind: {
+			editable: '{isCreate}',
+			value: '{serverid}',
+		    },
+		},
+		{
+		    xtype: 'proxmoxtextfield',
+		    name: 'url',
+		    fieldLabel: gettext('URL'),
+		    emptyText: 'https://collector.example.com:4318/v1/metrics',
+		    allowBlank: false,
+		},
+	    ],
+
+	    column2: [
+		{
+		    xtype: 'checkbox',
+		    name: 'enable',
+		    fieldLabel: gettext('Enabled'),
+		    inputValue: 1,
+		    uncheckedValue: 0,
+		    checked: true,
+		},
+		{
+		    xtype: 'proxmoxcheckbox',
+		    name: 'verify-tls',
+		    fieldLabel: gettext('Verify Certificate'),
+		    checked: true,
+		    defaultValue: 1,
+		    cbind: {
+			deleteDefaultValue: '{!isCreate}',
+		    },
+		},
+	    ],
+
+	    columnB: [
+		{
+		    xtype: 'textarea',
+		    name: 'header',
+		    fieldLabel: gettext('Headers'),
+		    cbind: {
+			emptyText: '{headerEmptyText}',
+		    },
+		},
+		{
+		    xtype: 'textarea',
+		    name: 'resource-attribute',
+		    fieldLabel: gettext('Resource Attributes'),
+		    emptyText: 'deployment.environment=production',
+		},
+		{
+		    xtype: 'proxmoxtextfield',
+		    name: 'comment',
+		    fieldLabel: gettext('Comment'),
+		    cbind: {
+			deleteEmpty: '{!isCreate}',
+		    },
+		},
+	    ],
+	},
+    ],
+
+    initComponent: function() {
+	let me = this;
+
+	// load manually to show the resource attributes one per line
+	let autoLoad = me.autoLoad;
+	me.autoLoad = false;
+
+	me.callParent();
+
+	if (autoLoad) {
+	    me.load({
+		success: function(response, options) {
+		    let values = response.result.data;
+		    values['resource-attribute'] = (values['resource-attribute'] ?? []).join('\n');
+		    me.setValues(values);
+		},
+	    });
+	}
+    },
+});